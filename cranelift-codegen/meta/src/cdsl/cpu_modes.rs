@@ -38,9 +38,16 @@ impl CpuMode {
         self.default_legalize = Some(group.id);
     }
     pub fn legalize_type(&mut self, lane_type: impl Into<LaneType>, group: &TransformGroup) {
+        self.legalize_value_type(lane_type.into(), group);
+    }
+    pub fn legalize_value_type(
+        &mut self,
+        value_type: impl Into<ValueType>,
+        group: &TransformGroup,
+    ) {
         assert!(self
             .typed_legalize
-            .insert(lane_type.into().into(), group.id)
+            .insert(value_type.into(), group.id)
             .is_none());
     }
 
@@ -139,6 +139,8 @@ pub struct InstructionContent {
     pub other_side_effects: bool,
     /// Does this instruction write to CPU flags?
     pub writes_cpu_flags: bool,
+    /// Should this opcode be considered to clobber all live registers, during regalloc?
+    pub clobbers_all_regs: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -247,6 +249,7 @@ pub struct InstructionBuilder {
     can_store: bool,
     can_trap: bool,
     other_side_effects: bool,
+    clobbers_all_regs: bool,
 }
 
 impl InstructionBuilder {
@@ -268,6 +271,7 @@ impl InstructionBuilder {
             can_store: false,
             can_trap: false,
             other_side_effects: false,
+            clobbers_all_regs: false,
         }
     }
 
@@ -327,6 +331,10 @@ impl InstructionBuilder {
         self.other_side_effects = val;
         self
     }
+    pub fn clobbers_all_regs(mut self, val: bool) -> Self {
+        self.clobbers_all_regs = val;
+        self
+    }
 
     fn build(self, format_registry: &FormatRegistry, opcode_number: OpcodeNumber) -> Instruction {
         let operands_in = self.operands_in.unwrap_or_else(Vec::new);
@@ -385,6 +393,7 @@ impl InstructionBuilder {
                 can_trap: self.can_trap,
                 other_side_effects: self.other_side_effects,
                 writes_cpu_flags,
+                clobbers_all_regs: self.clobbers_all_regs,
             }),
         }
     }
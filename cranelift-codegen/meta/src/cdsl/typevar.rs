@@ -268,19 +268,18 @@ impl TypeVar {
 
     /// Get a Rust expression that computes the type of this type variable.
     pub fn to_rust_code(&self) -> String {
+        // Derived types of known types are known too, which also avoids calling derived functions
+        // returning an `Option`, like `half_width`.
+        if let Some(singleton) = self.singleton_type() {
+            return singleton.rust_name();
+        }
         match &self.base {
             Some(base) => format!(
                 "{}.{}()",
                 base.type_var.to_rust_code(),
                 base.derived_func.name()
             ),
-            None => {
-                if let Some(singleton) = self.singleton_type() {
-                    singleton.rust_name()
-                } else {
-                    self.name.clone()
-                }
-            }
+            None => self.name.clone(),
         }
     }
 }
@@ -295,15 +295,39 @@ fn emit_dst_inst(def: &Def, def_pool: &DefPool, var_pool: &VarPool, fmt: &mut Fo
     if is_value_split(def) {
         // Split instructions are not emitted with the builder, but by calling special functions in
         // the `legalizer::split` module. These functions will eliminate concat-split patterns.
+        // They can't reuse the source results, which become aliases of the split values instead.
+        let split_vars = def
+            .defined_vars
+            .iter()
+            .map(|&var_index| {
+                let var = var_pool.get(var_index);
+                if var.is_output() {
+                    format!("{}_split", var.name)
+                } else {
+                    var.name.to_string()
+                }
+            })
+            .collect::<Vec<_>>();
         fmt.line("let curpos = pos.position();");
         fmt.line("let srcloc = pos.srcloc();");
         fmtln!(
             fmt,
-            "let {} = split::{}(pos.func, cfg, curpos, srcloc, {});",
-            defined_vars,
+            "let ({}) = split::{}(pos.func, cfg, curpos, srcloc, {});",
+            split_vars.join(", "),
             def.apply.inst.snake_name(),
             def.apply.args[0].to_rust_code(var_pool)
         );
+        for (&var_index, split_var) in def.defined_vars.iter().zip(&split_vars) {
+            let var = var_pool.get(var_index);
+            if var.is_output() {
+                fmtln!(
+                    fmt,
+                    "pos.func.dfg.change_to_alias({}, {});",
+                    var.name,
+                    split_var
+                );
+            }
+        }
         return;
     }
 
@@ -0,0 +1,609 @@
+use crate::cdsl::encodings::{Encoding, EncodingBuilder};
+use crate::cdsl::instructions::{
+    InstSpec, InstructionGroup, InstructionPredicate, InstructionPredicateRegistry,
+};
+use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
+use crate::cdsl::types::LaneType;
+
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I8};
+use crate::shared::Definitions as SharedDefinitions;
+
+use super::recipes::RecipeGroup;
+
+fn enc(inst: impl Into<InstSpec>, recipe: EncodingRecipeNumber, bits: u16) -> EncodingBuilder {
+    EncodingBuilder::new(inst.into(), recipe, bits)
+}
+
+pub struct PerCpuModeEncodings<'defs> {
+    pub inst_pred_reg: InstructionPredicateRegistry,
+    pub enc32: Vec<Encoding>,
+    recipes: &'defs Recipes,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn new(recipes: &'defs Recipes) -> Self {
+        Self {
+            inst_pred_reg: InstructionPredicateRegistry::new(),
+            enc32: Vec::new(),
+            recipes,
+        }
+    }
+    fn add32(&mut self, encoding: EncodingBuilder) {
+        self.enc32
+            .push(encoding.build(self.recipes, &mut self.inst_pred_reg));
+    }
+}
+
+// All A32 instructions are 32 bits wide, but the encbits only have 16 bits. The functions below
+// take a full instruction template with all the operand fields cleared, and pack the bits that
+// aren't provided by the recipe into the encbits. They are unpacked by the `put_*` functions in
+// `binemit.rs`. The templates are written with the `al` condition.
+
+/// Encbits for most instructions, whose opcode is in bits 27:20 and 7:4, and whose operand
+/// fields are in bits 19:12 and 3:0: `w[27:20] | w[7:4] << 8 | w[11:8] << 12`.
+fn a32_bits(w: u32) -> u16 {
+    assert_eq!(
+        w & 0xf00f_f00f,
+        0xe000_0000,
+        "{:#010x} can't use the a32_bits layout",
+        w
+    );
+    ((w >> 20) & 0xff | ((w >> 4) & 0xf) << 8 | ((w >> 8) & 0xf) << 12) as u16
+}
+
+/// Encbits for the VFP data-processing instructions with one operand, whose opcode extension is
+/// in bits 19:16: `w[19:16] | w[7:4] << 8 | w[11:8] << 12`.
+fn vfp_rr_bits(w: u32) -> u16 {
+    assert_eq!(
+        w & 0xfff0_f02f,
+        0xeeb0_0000,
+        "{:#010x} can't use the vfp_rr_bits layout",
+        w
+    );
+    ((w >> 16) & 0xf | ((w >> 4) & 0xf) << 8 | ((w >> 8) & 0xf) << 12) as u16
+}
+
+/// Encbits for the branch instructions: `w[31:20]`.
+fn branch_bits(w: u32) -> u16 {
+    assert_eq!(
+        w & 0x00ff_ffff,
+        0,
+        "{:#010x} can't use the branch_bits layout",
+        w
+    );
+    (w >> 20) as u16
+}
+
+pub(crate) fn define<'defs>(
+    shared_defs: &'defs SharedDefinitions,
+    arm32: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    // Instructions shorthands.
+    let shared = &shared_defs.instructions;
+
+    let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let band = shared.by_name("band");
+    let band_imm = shared.by_name("band_imm");
+    let band_not = shared.by_name("band_not");
+    let bconst = shared.by_name("bconst");
+    let bint = shared.by_name("bint");
+    let bitcast = shared.by_name("bitcast");
+    let bnot = shared.by_name("bnot");
+    let bor = shared.by_name("bor");
+    let bor_imm = shared.by_name("bor_imm");
+    let brff = shared.by_name("brff");
+    let brif = shared.by_name("brif");
+    let brnz = shared.by_name("brnz");
+    let brz = shared.by_name("brz");
+    let bxor = shared.by_name("bxor");
+    let bxor_imm = shared.by_name("bxor_imm");
+    let call = shared.by_name("call");
+    let call_indirect = shared.by_name("call_indirect");
+    let clz = shared.by_name("clz");
+    let copy = shared.by_name("copy");
+    let copy_nop = shared.by_name("copy_nop");
+    let copy_special = shared.by_name("copy_special");
+    let copy_to_ssa = shared.by_name("copy_to_ssa");
+    let ctz = shared.by_name("ctz");
+    let debugtrap = shared.by_name("debugtrap");
+    let f32const = shared.by_name("f32const");
+    let f64const = shared.by_name("f64const");
+    let fabs = shared.by_name("fabs");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fcvt_from_uint = shared.by_name("fcvt_from_uint");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
+    let ffcmp = shared.by_name("ffcmp");
+    let fill = shared.by_name("fill");
+    let fill_nop = shared.by_name("fill_nop");
+    let fmul = shared.by_name("fmul");
+    let fneg = shared.by_name("fneg");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
+    let func_addr = shared.by_name("func_addr");
+    let iadd = shared.by_name("iadd");
+    let iadd_ifcin = shared.by_name("iadd_ifcin");
+    let iadd_ifcout = shared.by_name("iadd_ifcout");
+    let iadd_imm = shared.by_name("iadd_imm");
+    let icmp = shared.by_name("icmp");
+    let icmp_imm = shared.by_name("icmp_imm");
+    let iconst = shared.by_name("iconst");
+    let ifcmp = shared.by_name("ifcmp");
+    let ifcmp_imm = shared.by_name("ifcmp_imm");
+    let imul = shared.by_name("imul");
+    let indirect_jump_table_br = shared.by_name("indirect_jump_table_br");
+    let ireduce = shared.by_name("ireduce");
+    let irsub_imm = shared.by_name("irsub_imm");
+    let ishl = shared.by_name("ishl");
+    let ishl_imm = shared.by_name("ishl_imm");
+    let istore16 = shared.by_name("istore16");
+    let istore8 = shared.by_name("istore8");
+    let isub = shared.by_name("isub");
+    let isub_ifbin = shared.by_name("isub_ifbin");
+    let isub_ifbout = shared.by_name("isub_ifbout");
+    let jump = shared.by_name("jump");
+    let jump_table_base = shared.by_name("jump_table_base");
+    let jump_table_entry = shared.by_name("jump_table_entry");
+    let load = shared.by_name("load");
+    let regfill = shared.by_name("regfill");
+    let regmove = shared.by_name("regmove");
+    let regspill = shared.by_name("regspill");
+    let resumable_trap = shared.by_name("resumable_trap");
+    let return_ = shared.by_name("return");
+    let rotr = shared.by_name("rotr");
+    let rotr_imm = shared.by_name("rotr_imm");
+    let selectif = shared.by_name("selectif");
+    let sextend = shared.by_name("sextend");
+    let sload16 = shared.by_name("sload16");
+    let sload8 = shared.by_name("sload8");
+    let smulhi = shared.by_name("smulhi");
+    let spill = shared.by_name("spill");
+    let sqrt = shared.by_name("sqrt");
+    let sshr = shared.by_name("sshr");
+    let sshr_imm = shared.by_name("sshr_imm");
+    let stack_addr = shared.by_name("stack_addr");
+    let store = shared.by_name("store");
+    let symbol_value = shared.by_name("symbol_value");
+    let trap = shared.by_name("trap");
+    let trapff = shared.by_name("trapff");
+    let trapif = shared.by_name("trapif");
+    let trueff = shared.by_name("trueff");
+    let trueif = shared.by_name("trueif");
+    let uextend = shared.by_name("uextend");
+    let uload16 = shared.by_name("uload16");
+    let uload8 = shared.by_name("uload8");
+    let umulhi = shared.by_name("umulhi");
+    let ushr = shared.by_name("ushr");
+    let ushr_imm = shared.by_name("ushr_imm");
+
+    // arm32 specific instructions.
+    let arm32_fcvt_to_sint_sat = arm32.by_name("arm32_fcvt_to_sint_sat");
+    let arm32_fcvt_to_uint_sat = arm32.by_name("arm32_fcvt_to_uint_sat");
+    let arm32_pop = arm32.by_name("arm32_pop");
+    let arm32_push = arm32.by_name("arm32_push");
+    let arm32_sdiv = arm32.by_name("arm32_sdiv");
+    let arm32_udiv = arm32.by_name("arm32_udiv");
+
+    // Recipes shorthands, prefixed with r_.
+    let r_addi = recipes.by_name("addi");
+    let r_adjustsp = recipes.by_name("adjustsp");
+    let r_adjustsp_imm8 = recipes.by_name("adjustsp_imm8");
+    let r_b = recipes.by_name("B");
+    let r_bcond = recipes.by_name("Bcond");
+    let r_bconst = recipes.by_name("bconst");
+    let r_bfcond = recipes.by_name("Bfcond");
+    let r_bkpt = recipes.by_name("bkpt");
+    let r_bl = recipes.by_name("BL");
+    let r_blx = recipes.by_name("BLX");
+    let r_brz = recipes.by_name("brz");
+    let r_bx = recipes.by_name("BX");
+    let r_copysp = recipes.by_name("copysp");
+    let r_copytossa = recipes.by_name("copytossa");
+    let r_ctz = recipes.by_name("ctz");
+    let r_d_to_s = recipes.by_name("DtoS");
+    let r_dconst = recipes.by_name("dconst");
+    let r_div = recipes.by_name("div");
+    let r_dpop = recipes.by_name("dpop");
+    let r_dpush = recipes.by_name("dpush");
+    let r_fill = recipes.by_name("fill");
+    let r_fillnull = recipes.by_name("fillnull");
+    let r_fnaddr = recipes.by_name("fnaddr");
+    let r_g_to_s = recipes.by_name("GtoS");
+    let r_gvaddr = recipes.by_name("gvaddr");
+    let r_icmp = recipes.by_name("icmp");
+    let r_icmp_imm = recipes.by_name("icmp_imm");
+    let r_iconst = recipes.by_name("iconst");
+    let r_ifcmp = recipes.by_name("ifcmp");
+    let r_ifcmp_imm = recipes.by_name("ifcmp_imm");
+    let r_jt_base = recipes.by_name("jt_base");
+    let r_jt_entry = recipes.by_name("jt_entry");
+    let r_ld = recipes.by_name("ld");
+    let r_ldh = recipes.by_name("ldh");
+    let r_mul = recipes.by_name("mul");
+    let r_mulhi = recipes.by_name("mulhi");
+    let r_null = recipes.by_name("null");
+    let r_pop = recipes.by_name("pop");
+    let r_push = recipes.by_name("push");
+    let r_r = recipes.by_name("R");
+    let r_r_ifcin = recipes.by_name("R_ifcin");
+    let r_r_ifcout = recipes.by_name("R_ifcout");
+    let r_regfill = recipes.by_name("regfill");
+    let r_regspill = recipes.by_name("regspill");
+    let r_ret = recipes.by_name("ret");
+    let r_rext = recipes.by_name("Rext");
+    let r_ri8 = recipes.by_name("Ri8");
+    let r_rm = recipes.by_name("Rm");
+    let r_rmov = recipes.by_name("Rmov");
+    let r_s_to_d = recipes.by_name("StoD");
+    let r_s_to_g = recipes.by_name("StoG");
+    let r_sconst = recipes.by_name("sconst");
+    let r_selectif = recipes.by_name("selectif");
+    let r_shift_imm = recipes.by_name("shift_imm");
+    let r_shift_r = recipes.by_name("shift_r");
+    let r_spaddr = recipes.by_name("spaddr");
+    let r_spill = recipes.by_name("spill");
+    let r_st = recipes.by_name("st");
+    let r_stacknull = recipes.by_name("stacknull");
+    let r_sth = recipes.by_name("sth");
+    let r_trapff = recipes.by_name("trapff");
+    let r_trapif = recipes.by_name("trapif");
+    let r_trueff = recipes.by_name("trueff");
+    let r_trueif = recipes.by_name("trueif");
+    let r_udf = recipes.by_name("udf");
+
+    // Predicates shorthands.
+    let formats = &shared_defs.format_registry;
+    let f_call = formats.get(formats.by_name("Call"));
+
+    // Definitions.
+    let mut e = PerCpuModeEncodings::new(&recipes.recipes);
+
+    // Integer arithmetic and logical instructions with registers.
+    for &(inst, w) in &[
+        (iadd, 0xe080_0000),
+        (isub, 0xe040_0000),
+        (band, 0xe000_0000),
+        (bor, 0xe180_0000),
+        (bxor, 0xe020_0000),
+        (band_not, 0xe1c0_0000),
+    ] {
+        e.add32(enc(inst.bind(I32), r_r, a32_bits(w)));
+    }
+
+    // Booleans are 0 or 1, so the logical instructions work on them.
+    for &(inst, w) in &[(band, 0xe000_0000), (bor, 0xe180_0000), (bxor, 0xe020_0000)] {
+        e.add32(enc(inst.bind(B1), r_r, a32_bits(w)));
+    }
+
+    // Additions and subtractions producing or consuming a carry or borrow in the flags. The
+    // borrow of the subtractions is the inverted carry flag, and `sbc` subtracts the inverted
+    // carry flag too.
+    e.add32(enc(
+        iadd_ifcout.bind(I32),
+        r_r_ifcout,
+        a32_bits(0xe090_0000),
+    ));
+    e.add32(enc(
+        isub_ifbout.bind(I32),
+        r_r_ifcout,
+        a32_bits(0xe050_0000),
+    ));
+    e.add32(enc(iadd_ifcin.bind(I32), r_r_ifcin, a32_bits(0xe0a0_0000)));
+    e.add32(enc(isub_ifbin.bind(I32), r_r_ifcin, a32_bits(0xe0c0_0000)));
+
+    // Multiplications and divisions.
+    e.add32(enc(imul.bind(I32), r_mul, a32_bits(0xe000_0090)));
+    e.add32(enc(umulhi.bind(I32), r_mulhi, a32_bits(0xe080_0090)));
+    e.add32(enc(smulhi.bind(I32), r_mulhi, a32_bits(0xe0c0_0090)));
+    e.add32(enc(arm32_sdiv, r_div, a32_bits(0xe710_0010)));
+    e.add32(enc(arm32_udiv, r_div, a32_bits(0xe730_0010)));
+
+    // Instructions with an 8-bit immediate. Larger immediates are legalized to an `iconst`.
+    e.add32(enc(iadd_imm.bind(I32), r_addi, a32_bits(0xe280_0000)));
+    for &(inst, w) in &[
+        (band_imm, 0xe200_0000),
+        (bor_imm, 0xe380_0000),
+        (bxor_imm, 0xe220_0000),
+        (irsub_imm, 0xe260_0000),
+    ] {
+        e.add32(enc(inst.bind(I32), r_ri8, a32_bits(w)));
+    }
+
+    // Shifts by a register or an immediate are `mov` instructions with a shifted operand.
+    for &(inst, inst_imm, w) in &[
+        (ishl, ishl_imm, 0xe1a0_0000),
+        (ushr, ushr_imm, 0xe1a0_0020),
+        (sshr, sshr_imm, 0xe1a0_0040),
+        (rotr, rotr_imm, 0xe1a0_0060),
+    ] {
+        e.add32(enc(inst.bind(I32).bind(I32), r_shift_r, a32_bits(w | 0x10)));
+        e.add32(enc(inst_imm.bind(I32), r_shift_imm, a32_bits(w)));
+    }
+
+    // Bit counting. `ctz` reverses the bits with `rbit` first.
+    e.add32(enc(clz.bind(I32), r_rext, a32_bits(0xe160_0f10)));
+    e.add32(enc(ctz.bind(I32), r_ctz, a32_bits(0xe160_0f10)));
+
+    e.add32(enc(bnot.bind(I32), r_rm, a32_bits(0xe1e0_0000)));
+
+    // Extensions.
+    for &(inst, from, w) in &[
+        (uextend, I8, 0xe6e0_0070),
+        (sextend, I8, 0xe6a0_0070),
+        (uextend, I16, 0xe6f0_0070),
+        (sextend, I16, 0xe6b0_0070),
+    ] {
+        e.add32(enc(inst.bind(I32).bind(from), r_rext, a32_bits(w)));
+    }
+
+    // The narrow types live in 32-bit registers, so reductions are free.
+    e.add32(enc(ireduce.bind(I16).bind(I32), r_null, 0));
+    e.add32(enc(ireduce.bind(I8).bind(I32), r_null, 0));
+    e.add32(enc(bint.bind(I32).bind(B1), r_null, 0));
+
+    // Constants.
+    e.add32(enc(iconst.bind(I32), r_iconst, 0));
+    e.add32(enc(bconst.bind(B1), r_bconst, a32_bits(0xe3a0_0000)));
+    e.add32(enc(f32const, r_sconst, a32_bits(0xed90_0a00)));
+    e.add32(enc(f64const, r_dconst, a32_bits(0xed90_0b00)));
+
+    // Integer comparisons, with the `cmp` instruction.
+    e.add32(enc(icmp.bind(I32), r_icmp, a32_bits(0xe150_0000)));
+    e.add32(enc(icmp_imm.bind(I32), r_icmp_imm, a32_bits(0xe350_0000)));
+    e.add32(enc(ifcmp.bind(I32), r_ifcmp, a32_bits(0xe150_0000)));
+    e.add32(enc(ifcmp_imm.bind(I32), r_ifcmp_imm, a32_bits(0xe350_0000)));
+
+    // Using the flags.
+    e.add32(enc(trueif, r_trueif, 0));
+    e.add32(enc(trueff, r_trueff, 0));
+    e.add32(enc(selectif.bind(I32), r_selectif, a32_bits(0xe1a0_0000)));
+
+    // Floating point instructions. The recipes are prefixed with `s` for the single precision
+    // forms and `d` for the double precision forms, and the `sz` bit 8 selects double
+    // precision in the templates.
+    for &(ty, prefix, sz) in &[(F32, "s", 0), (F64, "d", 0x100)] {
+        let r = |name: &str| recipes.by_name(&format!("{}{}", prefix, name));
+
+        for &(inst, w) in &[
+            (fadd, 0xee30_0a00),
+            (fsub, 0xee30_0a40),
+            (fmul, 0xee20_0a00),
+            (fdiv, 0xee80_0a00),
+        ] {
+            e.add32(enc(inst.bind(ty), r("R"), a32_bits(w | sz)));
+        }
+
+        for &(inst, w) in &[
+            (fabs, 0xeeb0_0ac0),
+            (fneg, 0xeeb1_0a40),
+            (sqrt, 0xeeb1_0ac0),
+        ] {
+            e.add32(enc(inst.bind(ty), r("RR"), vfp_rr_bits(w | sz)));
+        }
+
+        // The comparisons copy the floating point flags to the CPU flags with `vmrs`.
+        e.add32(enc(fcmp.bind(ty), r("fcmp"), vfp_rr_bits(0xeeb4_0a40 | sz)));
+        e.add32(enc(
+            ffcmp.bind(ty),
+            r("ffcmp"),
+            vfp_rr_bits(0xeeb4_0a40 | sz),
+        ));
+
+        // Conversions between integers and floats, through an S register. The `vcvt`
+        // instructions to integers round towards zero and saturate, converting NaN to 0.
+        e.add32(enc(
+            fcvt_from_sint.bind(ty).bind(I32),
+            r("itof"),
+            vfp_rr_bits(0xeeb8_0ac0 | sz),
+        ));
+        e.add32(enc(
+            fcvt_from_uint.bind(ty).bind(I32),
+            r("itof"),
+            vfp_rr_bits(0xeeb8_0a40 | sz),
+        ));
+        e.add32(enc(
+            arm32_fcvt_to_sint_sat.bind(ty),
+            r("ftoi"),
+            vfp_rr_bits(0xeebd_0ac0 | sz),
+        ));
+        e.add32(enc(
+            arm32_fcvt_to_uint_sat.bind(ty),
+            r("ftoi"),
+            vfp_rr_bits(0xeebc_0ac0 | sz),
+        ));
+
+        // Loads and stores, with a word-aligned offset.
+        e.add32(enc(
+            load.bind(ty).bind_any(),
+            r("ld"),
+            a32_bits(0xed90_0a00 | sz),
+        ));
+        e.add32(enc(
+            store.bind(ty).bind_any(),
+            r("st"),
+            a32_bits(0xed80_0a00 | sz),
+        ));
+
+        // Spill and fill.
+        e.add32(enc(spill.bind(ty), r("spill"), a32_bits(0xed80_0a00 | sz)));
+        e.add32(enc(
+            regspill.bind(ty),
+            r("regspill"),
+            a32_bits(0xed80_0a00 | sz),
+        ));
+        e.add32(enc(fill.bind(ty), r("fill"), a32_bits(0xed90_0a00 | sz)));
+        e.add32(enc(
+            regfill.bind(ty),
+            r("regfill"),
+            a32_bits(0xed90_0a00 | sz),
+        ));
+        e.add32(enc(fill_nop.bind(ty), r("fillnull"), 0));
+
+        // Register copies.
+        e.add32(enc(copy.bind(ty), r("RR"), vfp_rr_bits(0xeeb0_0a40 | sz)));
+        e.add32(enc(
+            regmove.bind(ty),
+            r("Rmov"),
+            vfp_rr_bits(0xeeb0_0a40 | sz),
+        ));
+        e.add32(enc(
+            copy_to_ssa.bind(ty),
+            r("copytossa"),
+            vfp_rr_bits(0xeeb0_0a40 | sz),
+        ));
+        e.add32(enc(copy_nop.bind(ty), r_stacknull, 0));
+    }
+
+    e.add32(enc(
+        fpromote.bind(F64).bind(F32),
+        r_s_to_d,
+        vfp_rr_bits(0xeeb7_0ac0),
+    ));
+    e.add32(enc(
+        fdemote.bind(F32).bind(F64),
+        r_d_to_s,
+        vfp_rr_bits(0xeeb7_0bc0),
+    ));
+
+    e.add32(enc(
+        bitcast.bind(F32).bind(I32),
+        r_g_to_s,
+        a32_bits(0xee00_0a10),
+    ));
+    e.add32(enc(
+        bitcast.bind(I32).bind(F32),
+        r_s_to_g,
+        a32_bits(0xee10_0a10),
+    ));
+
+    // Loads and stores. Words and bytes take a 12-bit offset, the other sizes an 8-bit offset.
+    // Larger offsets are legalized to an address computation.
+    e.add32(enc(load.bind(I32).bind_any(), r_ld, a32_bits(0xe590_0000)));
+    e.add32(enc(
+        uload8.bind(I32).bind_any(),
+        r_ld,
+        a32_bits(0xe5d0_0000),
+    ));
+    e.add32(enc(
+        sload8.bind(I32).bind_any(),
+        r_ldh,
+        a32_bits(0xe1d0_00d0),
+    ));
+    e.add32(enc(
+        uload16.bind(I32).bind_any(),
+        r_ldh,
+        a32_bits(0xe1d0_00b0),
+    ));
+    e.add32(enc(
+        sload16.bind(I32).bind_any(),
+        r_ldh,
+        a32_bits(0xe1d0_00f0),
+    ));
+    e.add32(enc(store.bind(I32).bind_any(), r_st, a32_bits(0xe580_0000)));
+    e.add32(enc(
+        istore8.bind(I32).bind_any(),
+        r_st,
+        a32_bits(0xe5c0_0000),
+    ));
+    e.add32(enc(
+        istore16.bind(I32).bind_any(),
+        r_sth,
+        a32_bits(0xe1c0_00b0),
+    ));
+
+    // Spill and fill, and register copies. The narrow types are handled as 32-bit values.
+    for &ty in &[LaneType::from(I8), I16.into(), I32.into(), B1.into()] {
+        e.add32(enc(spill.bind(ty), r_spill, a32_bits(0xe580_0000)));
+        e.add32(enc(regspill.bind(ty), r_regspill, a32_bits(0xe580_0000)));
+        e.add32(enc(fill.bind(ty), r_fill, a32_bits(0xe590_0000)));
+        e.add32(enc(regfill.bind(ty), r_regfill, a32_bits(0xe590_0000)));
+        e.add32(enc(fill_nop.bind(ty), r_fillnull, 0));
+        e.add32(enc(copy_nop.bind(ty), r_stacknull, 0));
+
+        e.add32(enc(copy.bind(ty), r_rm, a32_bits(0xe1a0_0000)));
+        e.add32(enc(regmove.bind(ty), r_rmov, a32_bits(0xe1a0_0000)));
+        e.add32(enc(
+            copy_to_ssa.bind(ty),
+            r_copytossa,
+            a32_bits(0xe1a0_0000),
+        ));
+    }
+
+    // Stack pointer manipulations, used in the prologue and epilogues.
+    e.add32(enc(stack_addr.bind(I32), r_spaddr, 0));
+    e.add32(enc(copy_special, r_copysp, a32_bits(0xe1a0_0000)));
+    e.add32(enc(
+        adjust_sp_down_imm,
+        r_adjustsp_imm8,
+        a32_bits(0xe240_0000),
+    ));
+    e.add32(enc(adjust_sp_down_imm, r_adjustsp, a32_bits(0xe040_0000)));
+    e.add32(enc(
+        adjust_sp_up_imm,
+        r_adjustsp_imm8,
+        a32_bits(0xe280_0000),
+    ));
+    e.add32(enc(adjust_sp_up_imm, r_adjustsp, a32_bits(0xe080_0000)));
+
+    // Pushes are `str rt, [sp, #-4]!` and `vpush {dd}`, pops are `ldr rt, [sp], #4` and
+    // `vpop {dd}`.
+    e.add32(enc(arm32_push.bind(I32), r_push, a32_bits(0xe520_0000)));
+    e.add32(enc(arm32_push.bind(F64), r_dpush, a32_bits(0xed20_0b00)));
+    e.add32(enc(arm32_pop.bind(I32), r_pop, a32_bits(0xe490_0000)));
+    e.add32(enc(arm32_pop.bind(F64), r_dpop, a32_bits(0xecb0_0b00)));
+
+    // Control flow. The conditional branches get their condition from the recipe.
+    e.add32(enc(jump, r_b, branch_bits(0xea00_0000)));
+    e.add32(enc(brif, r_bcond, branch_bits(0xea00_0000)));
+    e.add32(enc(brff, r_bfcond, branch_bits(0xea00_0000)));
+
+    for &(inst, w) in &[(brz, 0x0a00_0000), (brnz, 0x1a00_0000)] {
+        e.add32(enc(inst.bind(I32), r_brz, branch_bits(w)));
+        e.add32(enc(inst.bind(B1), r_brz, branch_bits(w)));
+    }
+
+    // Jump tables.
+    e.add32(enc(
+        jump_table_base.bind(I32),
+        r_jt_base,
+        a32_bits(0xe080_0000),
+    ));
+    e.add32(enc(
+        jump_table_entry.bind(I32),
+        r_jt_entry,
+        a32_bits(0xe790_0100),
+    ));
+    e.add32(enc(
+        indirect_jump_table_br.bind(I32),
+        r_bx,
+        a32_bits(0xe120_0f10),
+    ));
+
+    // Calls. The `bl` instruction has a range of +/- 32 MB, so it's only used for colocated
+    // functions. Other calls are legalized to a `func_addr` and an indirect call.
+    let is_colocated_func = InstructionPredicate::new_is_colocated_func(f_call, "func_ref");
+    e.add32(enc(call, r_bl, branch_bits(0xeb00_0000)).inst_predicate(is_colocated_func));
+    e.add32(enc(call_indirect.bind(I32), r_blx, a32_bits(0xe120_0f30)));
+
+    // Returns branch to %r14, which is provided by a special-purpose `link` return value that
+    // is added by legalize_signature().
+    e.add32(enc(return_, r_ret, a32_bits(0xe120_0f10)));
+
+    e.add32(enc(func_addr.bind(I32), r_fnaddr, 0));
+    e.add32(enc(symbol_value.bind(I32), r_gvaddr, 0));
+
+    // Traps. The conditional traps branch over an `udf` with the inverted condition.
+    e.add32(enc(trap, r_udf, a32_bits(0xe7f0_00f0)));
+    e.add32(enc(resumable_trap, r_udf, a32_bits(0xe7f0_00f0)));
+    e.add32(enc(debugtrap, r_bkpt, a32_bits(0xe120_0070)));
+    e.add32(enc(trapif, r_trapif, branch_bits(0xea00_0000)));
+    e.add32(enc(trapff, r_trapff, branch_bits(0xea00_0000)));
+
+    e
+}
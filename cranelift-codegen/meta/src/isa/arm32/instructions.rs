@@ -0,0 +1,139 @@
+#![allow(non_snake_case)]
+
+use crate::cdsl::formats::FormatRegistry;
+use crate::cdsl::instructions::{
+    AllInstructions, InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
+};
+use crate::cdsl::operands::{create_operand as operand, create_operand_doc as operand_doc};
+use crate::cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+pub(crate) fn define(
+    mut all_instructions: &mut AllInstructions,
+    format_registry: &FormatRegistry,
+) -> InstructionGroup {
+    let mut ig = InstructionGroupBuilder::new(
+        "arm32",
+        "arm32 specific instruction set",
+        &mut all_instructions,
+        format_registry,
+    );
+
+    let i32_ = &TypeVar::new(
+        "i32",
+        "A 32-bit integer",
+        TypeSetBuilder::new().ints(32..32).build(),
+    );
+    let n = &operand_doc("n", i32_, "Numerator");
+    let d = &operand_doc("d", i32_, "Denominator");
+    let q = &operand_doc("q", i32_, "Quotient");
+
+    ig.push(
+        Inst::new(
+            "arm32_udiv",
+            r#"
+        Unsigned division without traps.
+
+        Interpret the bits in `n` and `d` as unsigned numbers and divide them.
+        Unlike `udiv`, this never traps: division by zero produces zero.
+        "#,
+        )
+        .operands_in(vec![n, d])
+        .operands_out(vec![q]),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm32_sdiv",
+            r#"
+        Signed division without traps.
+
+        Interpret the bits in `n` and `d` as signed numbers and divide them,
+        rounding towards zero. Unlike `sdiv`, this never traps: division by
+        zero produces zero, and `INT_MIN / -1` wraps to `INT_MIN`.
+        "#,
+        )
+        .operands_in(vec![n, d])
+        .operands_out(vec![q]),
+    );
+
+    let Float = &TypeVar::new(
+        "Float",
+        "A scalar floating point number",
+        TypeSetBuilder::new().floats(32..64).build(),
+    );
+    let f32_ = &TypeVar::new(
+        "f32",
+        "A 32-bit floating point register value",
+        TypeSetBuilder::new().floats(32..32).build(),
+    );
+    let x = &operand("x", Float);
+    let a = &operand_doc("a", f32_, "The integer bits of the result");
+
+    ig.push(
+        Inst::new(
+            "arm32_fcvt_to_sint_sat",
+            r#"
+        Convert floating point to signed 32-bit integer with saturation.
+
+        The VFP conversion instructions only write to floating point
+        registers, so the integer result is produced as the bits of an `f32`
+        value. It can be moved to an integer register with a `bitcast`.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm32_fcvt_to_uint_sat",
+            r#"
+        Convert floating point to unsigned 32-bit integer with saturation.
+
+        Like `arm32_fcvt_to_sint_sat`, the integer result is produced as the
+        bits of an `f32` value.
+        "#,
+        )
+        .operands_in(vec![x])
+        .operands_out(vec![a]),
+    );
+
+    let Any32 = &TypeVar::new(
+        "Any32",
+        "A 32-bit integer or 64-bit floating point register value",
+        TypeSetBuilder::new().ints(32..32).floats(64..64).build(),
+    );
+    let x = &operand("x", Any32);
+
+    ig.push(
+        Inst::new(
+            "arm32_push",
+            r#"
+    Pushes a value onto the stack.
+
+    Decrements the stack pointer by the size of `x` and stores `x` at the
+    new top of the stack. This is implemented for i32 and f64 values.
+    "#,
+        )
+        .operands_in(vec![x])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm32_pop",
+            r#"
+    Pops a value from the stack.
+
+    Loads `x` from the top of the stack and then increments the stack
+    pointer by the size of `x`. This is implemented for i32 and f64 values.
+    "#,
+        )
+        .operands_out(vec![x])
+        .other_side_effects(true)
+        .can_load(true),
+    );
+
+    ig.build()
+}
@@ -0,0 +1,311 @@
+use crate::cdsl::ast::{var, ExprBuilder, Literal};
+use crate::cdsl::instructions::InstructionGroup;
+use crate::cdsl::xform::TransformGroupBuilder;
+use crate::shared::types::Int::{I32, I64};
+use crate::shared::Definitions as SharedDefinitions;
+
+pub(crate) fn define(shared: &mut SharedDefinitions, arm32_instructions: &InstructionGroup) {
+    let mut expand = TransformGroupBuilder::new(
+        "arm32_expand",
+        r#"
+    Legalize instructions by expansion.
+
+    Use arm32-specific instructions if needed."#,
+    )
+    .isa("arm32")
+    .chain_with(shared.transform_groups.by_name("expand_flags").id);
+
+    let mut narrow = TransformGroupBuilder::new(
+        "arm32_narrow",
+        r#"
+    Legalize instructions by narrowing.
+
+    Split the 64-bit integer operations that the generic narrowing
+    doesn't handle into 32-bit operations."#,
+    )
+    .isa("arm32")
+    .chain_with(shared.transform_groups.by_name("narrow_flags").id);
+
+    // List of instructions.
+    let insts = &shared.instructions;
+    let band = insts.by_name("band");
+    let bitcast = insts.by_name("bitcast");
+    let bor = insts.by_name("bor");
+    let brnz = insts.by_name("brnz");
+    let brz = insts.by_name("brz");
+    let fcmp = insts.by_name("fcmp");
+    let fcvt_to_sint = insts.by_name("fcvt_to_sint");
+    let fcvt_to_sint_sat = insts.by_name("fcvt_to_sint_sat");
+    let fcvt_to_uint = insts.by_name("fcvt_to_uint");
+    let fcvt_to_uint_sat = insts.by_name("fcvt_to_uint_sat");
+    let iadd = insts.by_name("iadd");
+    let iconcat = insts.by_name("iconcat");
+    let icmp = insts.by_name("icmp");
+    let iconst = insts.by_name("iconst");
+    let imul = insts.by_name("imul");
+    let ireduce = insts.by_name("ireduce");
+    let irsub_imm = insts.by_name("irsub_imm");
+    let isplit = insts.by_name("isplit");
+    let isub = insts.by_name("isub");
+    let istore8 = insts.by_name("istore8");
+    let istore16 = insts.by_name("istore16");
+    let load = insts.by_name("load");
+    let popcnt = insts.by_name("popcnt");
+    let rotl = insts.by_name("rotl");
+    let rotr = insts.by_name("rotr");
+    let sdiv = insts.by_name("sdiv");
+    let sextend = insts.by_name("sextend");
+    let sload8 = insts.by_name("sload8");
+    let sload16 = insts.by_name("sload16");
+    let srem = insts.by_name("srem");
+    let sshr_imm = insts.by_name("sshr_imm");
+    let store = insts.by_name("store");
+    let udiv = insts.by_name("udiv");
+    let uextend = insts.by_name("uextend");
+    let uload8 = insts.by_name("uload8");
+    let uload16 = insts.by_name("uload16");
+    let umulhi = insts.by_name("umulhi");
+    let urem = insts.by_name("urem");
+    let ushr_imm = insts.by_name("ushr_imm");
+
+    // arm32 specific instructions.
+    let arm32_fcvt_to_sint_sat = arm32_instructions.by_name("arm32_fcvt_to_sint_sat");
+    let arm32_fcvt_to_uint_sat = arm32_instructions.by_name("arm32_fcvt_to_uint_sat");
+
+    // The arm32 division instructions don't trap, so the legalization inserts the checks.
+    expand.custom_legalize(sdiv, "expand_sdivrem");
+    expand.custom_legalize(srem, "expand_sdivrem");
+    expand.custom_legalize(udiv, "expand_udivrem");
+    expand.custom_legalize(urem, "expand_udivrem");
+
+    // Reductions from 64 bits take the low half. They are controlled by their 32-bit result
+    // type, so they are in the expansion group.
+    expand.custom_legalize(ireduce, "expand_ireduce");
+
+    // Conversions from float to int can trap.
+    expand.custom_legalize(fcvt_to_sint, "expand_fcvt_to_int");
+    expand.custom_legalize(fcvt_to_uint, "expand_fcvt_to_int");
+
+    // Loads and stores with an offset that doesn't fit in the addressing mode.
+    for &inst in &[
+        load, uload8, sload8, uload16, sload16, store, istore8, istore16,
+    ] {
+        expand.custom_legalize(inst, "expand_mem_offset");
+    }
+
+    let imm = &shared.imm;
+
+    let a = var("a");
+    let a1 = var("a1");
+    let a2 = var("a2");
+    let x = var("x");
+    let y = var("y");
+
+    // The VFP conversions to integers produce their result in a floating point register.
+    expand.legalize(
+        def!(a = fcvt_to_sint_sat.I32(x)),
+        vec![
+            def!(a1 = arm32_fcvt_to_sint_sat(x)),
+            def!(a = bitcast.I32(a1)),
+        ],
+    );
+    expand.legalize(
+        def!(a = fcvt_to_uint_sat.I32(x)),
+        vec![
+            def!(a1 = arm32_fcvt_to_uint_sat(x)),
+            def!(a = bitcast.I32(a1)),
+        ],
+    );
+
+    // There is no rotate-left instruction, rotate right by the negated amount instead.
+    let imm64_0 = Literal::constant(&imm.imm64, 0);
+    expand.legalize(
+        def!(a = rotl(x, y)),
+        vec![def!(a1 = irsub_imm(y, imm64_0)), def!(a = rotr(x, a1))],
+    );
+
+    // Floating point condition codes.
+    //
+    // A single `vcmp` followed by a condition check supports all the condition codes except
+    // `one` and `ueq`, which need two tests.
+    let floatcc_one = Literal::enumerator_for(&imm.floatcc, "one");
+    let floatcc_ueq = Literal::enumerator_for(&imm.floatcc, "ueq");
+    let floatcc_ord = Literal::enumerator_for(&imm.floatcc, "ord");
+    let floatcc_uno = Literal::enumerator_for(&imm.floatcc, "uno");
+    let floatcc_eq = Literal::enumerator_for(&imm.floatcc, "eq");
+    let floatcc_ne = Literal::enumerator_for(&imm.floatcc, "ne");
+
+    expand.legalize(
+        def!(a = fcmp(floatcc_one, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_ord, x, y)),
+            def!(a2 = fcmp(floatcc_ne, x, y)),
+            def!(a = band(a1, a2)),
+        ],
+    );
+    expand.legalize(
+        def!(a = fcmp(floatcc_ueq, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_uno, x, y)),
+            def!(a2 = fcmp(floatcc_eq, x, y)),
+            def!(a = bor(a1, a2)),
+        ],
+    );
+
+    // Population count using only general purpose registers.
+    let lv1 = var("lv1");
+    let lv3 = var("lv3");
+    let lv4 = var("lv4");
+    let lv5 = var("lv5");
+    let lv6 = var("lv6");
+    let lv7 = var("lv7");
+    let lv8 = var("lv8");
+    let lv9 = var("lv9");
+    let lv10 = var("lv10");
+    let lv11 = var("lv11");
+    let lv12 = var("lv12");
+    let lv13 = var("lv13");
+    let lv14 = var("lv14");
+    let lv15 = var("lv15");
+    let lv16 = var("lv16");
+    let lc77 = var("lc77");
+    #[allow(non_snake_case)]
+    let lc0F = var("lc0F");
+    let lc01 = var("lc01");
+
+    let imm64_1 = Literal::constant(&imm.imm64, 1);
+    let imm64_4 = Literal::constant(&imm.imm64, 4);
+    expand.legalize(
+        def!(lv16 = popcnt.I32(lv1)),
+        vec![
+            def!(lv3 = ushr_imm(lv1, imm64_1)),
+            def!(lc77 = iconst(Literal::constant(&imm.imm64, 0x77777777))),
+            def!(lv4 = band(lv3, lc77)),
+            def!(lv5 = isub(lv1, lv4)),
+            def!(lv6 = ushr_imm(lv4, imm64_1)),
+            def!(lv7 = band(lv6, lc77)),
+            def!(lv8 = isub(lv5, lv7)),
+            def!(lv9 = ushr_imm(lv7, imm64_1)),
+            def!(lv10 = band(lv9, lc77)),
+            def!(lv11 = isub(lv8, lv10)),
+            def!(lv12 = ushr_imm(lv11, imm64_4)),
+            def!(lv13 = iadd(lv11, lv12)),
+            def!(lc0F = iconst(Literal::constant(&imm.imm64, 0x0F0F0F0F))),
+            def!(lv14 = band(lv13, lc0F)),
+            def!(lc01 = iconst(Literal::constant(&imm.imm64, 0x01010101))),
+            def!(lv15 = imul(lv14, lc01)),
+            def!(lv16 = ushr_imm(lv15, Literal::constant(&imm.imm64, 24))),
+        ],
+    );
+
+    expand.build_and_add_to(&mut shared.transform_groups);
+
+    let xl = var("xl");
+    let xh = var("xh");
+    let yl = var("yl");
+    let yh = var("yh");
+    let al = var("al");
+    let ah = var("ah");
+    let b1 = var("b1");
+    let b2 = var("b2");
+    let b3 = var("b3");
+    let ebb = var("ebb");
+    let vararg = var("vararg");
+
+    // 64-bit multiplication: the low halves are multiplied into a 64-bit product, and the cross
+    // products only contribute to the high half.
+    narrow.legalize(
+        def!(a = imul.I64(x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(al = imul(xl, yl)),
+            def!(b1 = umulhi(xl, yl)),
+            def!(b2 = imul(xl, yh)),
+            def!(b3 = imul(xh, yl)),
+            def!(a1 = iadd(b2, b3)),
+            def!(ah = iadd(b1, a1)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // Testing a 64-bit value for zero tests the bitwise or of its halves.
+    narrow.legalize(
+        def!(brz.I64(x, ebb, vararg)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(a = bor(xl, xh)),
+            def!(brz(a, ebb, vararg)),
+        ],
+    );
+    narrow.legalize(
+        def!(brnz.I64(x, ebb, vararg)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(a = bor(xl, xh)),
+            def!(brnz(a, ebb, vararg)),
+        ],
+    );
+
+    // 64-bit comparisons compare the high halves, and the low halves as unsigned integers when
+    // the high halves are equal.
+    let intcc = |name| Literal::enumerator_for(&imm.intcc, name);
+    narrow.legalize(
+        def!(a = icmp.I64(intcc("eq"), x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(b1 = icmp(intcc("eq"), xl, yl)),
+            def!(b2 = icmp(intcc("eq"), xh, yh)),
+            def!(a = band(b1, b2)),
+        ],
+    );
+    narrow.legalize(
+        def!(a = icmp.I64(intcc("ne"), x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(b1 = icmp(intcc("ne"), xl, yl)),
+            def!(b2 = icmp(intcc("ne"), xh, yh)),
+            def!(a = bor(b1, b2)),
+        ],
+    );
+    for &(cc, hi_cc, lo_cc) in &[
+        ("slt", "slt", "ult"),
+        ("sle", "slt", "ule"),
+        ("sgt", "sgt", "ugt"),
+        ("sge", "sgt", "uge"),
+        ("ult", "ult", "ult"),
+        ("ule", "ult", "ule"),
+        ("ugt", "ugt", "ugt"),
+        ("uge", "ugt", "uge"),
+    ] {
+        narrow.legalize(
+            def!(a = icmp.I64(intcc(cc), x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!((yl, yh) = isplit(y)),
+                def!(b1 = icmp(intcc(hi_cc), xh, yh)),
+                def!(b2 = icmp(intcc("eq"), xh, yh)),
+                def!(b3 = icmp(intcc(lo_cc), xl, yl)),
+                def!(a1 = band(b2, b3)),
+                def!(a = bor(b1, a1)),
+            ],
+        );
+    }
+
+    // Extensions to 64 bits compute the high half.
+    let imm64_31 = Literal::constant(&imm.imm64, 31);
+    let uextend_i64_i32 = uextend.bind(I64).bind(I32);
+    let sextend_i64_i32 = sextend.bind(I64).bind(I32);
+    narrow.legalize(
+        def!(a = uextend_i64_i32(x)),
+        vec![def!(ah = iconst.I32(imm64_0)), def!(a = iconcat(x, ah))],
+    );
+    narrow.legalize(
+        def!(a = sextend_i64_i32(x)),
+        vec![def!(ah = sshr_imm(x, imm64_31)), def!(a = iconcat(x, ah))],
+    );
+
+    narrow.build_and_add_to(&mut shared.transform_groups);
+}
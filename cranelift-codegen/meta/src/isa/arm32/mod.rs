@@ -1,12 +1,18 @@
 use crate::cdsl::cpu_modes::CpuMode;
-use crate::cdsl::instructions::{InstructionGroupBuilder, InstructionPredicateMap};
 use crate::cdsl::isa::TargetIsa;
-use crate::cdsl::recipes::Recipes;
 use crate::cdsl::regs::{IsaRegs, IsaRegsBuilder, RegBankBuilder, RegClassBuilder};
 use crate::cdsl::settings::{SettingGroup, SettingGroupBuilder};
 
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I64, I8};
 use crate::shared::Definitions as SharedDefinitions;
 
+mod encodings;
+mod instructions;
+mod legalize;
+mod recipes;
+
 fn define_settings(_shared: &SettingGroup) -> SettingGroup {
     let setting = SettingGroupBuilder::new("arm32");
     setting.build()
@@ -53,30 +59,43 @@ pub(crate) fn define(shared_defs: &mut SharedDefinitions) -> TargetIsa {
     let settings = define_settings(&shared_defs.settings);
     let regs = define_regs();
 
-    let inst_group = InstructionGroupBuilder::new(
-        "arm32",
-        "arm32 specific instruction set",
+    let inst_group = instructions::define(
         &mut shared_defs.all_instructions,
         &shared_defs.format_registry,
-    )
-    .build();
+    );
+    legalize::define(shared_defs, &inst_group);
 
     // CPU modes for 32-bit ARM and Thumb2.
     let mut a32 = CpuMode::new("A32");
     let mut t32 = CpuMode::new("T32");
 
-    // TODO refine these.
-    let narrow_flags = shared_defs.transform_groups.by_name("narrow_flags");
-    a32.legalize_default(narrow_flags);
-    t32.legalize_default(narrow_flags);
+    let expand_flags = shared_defs.transform_groups.by_name("expand_flags");
+    let widen = shared_defs.transform_groups.by_name("widen");
+    let arm32_expand = shared_defs.transform_groups.by_name("arm32_expand");
+    let arm32_narrow = shared_defs.transform_groups.by_name("arm32_narrow");
 
-    let cpu_modes = vec![a32, t32];
+    for cpu_mode in &mut [&mut a32, &mut t32] {
+        cpu_mode.legalize_monomorphic(expand_flags);
+        cpu_mode.legalize_default(arm32_narrow);
+        cpu_mode.legalize_type(B1, expand_flags);
+        cpu_mode.legalize_type(I8, widen);
+        cpu_mode.legalize_type(I16, widen);
+        cpu_mode.legalize_type(I32, arm32_expand);
+        cpu_mode.legalize_type(I64, arm32_narrow);
+        cpu_mode.legalize_type(F32, arm32_expand);
+        cpu_mode.legalize_type(F64, arm32_expand);
+    }
 
-    // TODO implement arm32 recipes.
-    let recipes = Recipes::new();
+    let recipes = recipes::define(shared_defs, &regs);
 
-    // TODO implement arm32 encodings and predicates.
-    let encodings_predicates = InstructionPredicateMap::new();
+    // TODO implement the T32 encodings. Thumb2 code can't be generated yet.
+    let encodings = encodings::define(shared_defs, &inst_group, &recipes);
+    a32.set_encodings(encodings.enc32);
+    let encodings_predicates = encodings.inst_pred_reg.extract();
+
+    let recipes = recipes.collect();
+
+    let cpu_modes = vec![a32, t32];
 
     TargetIsa::new(
         "arm32",
@@ -0,0 +1,1174 @@
+use std::collections::HashMap;
+
+use crate::cdsl::ast::Literal;
+use crate::cdsl::formats::{FormatRegistry, InstructionFormat};
+use crate::cdsl::instructions::InstructionPredicate;
+use crate::cdsl::recipes::{
+    EncodingRecipeBuilder, EncodingRecipeNumber, OperandConstraint, Recipes, Register, Stack,
+};
+use crate::cdsl::regs::IsaRegs;
+use crate::shared::Definitions as SharedDefinitions;
+
+/// An helper to create recipes and use them when defining the arm32 encodings.
+pub struct RecipeGroup<'formats> {
+    /// Memoized format registry, to pass it to the builders.
+    formats: &'formats FormatRegistry,
+
+    /// The actualy list of recipes explicitly created in this file.
+    pub recipes: Recipes,
+
+    /// Provides fast lookup from a name to an encoding recipe.
+    name_to_recipe: HashMap<String, EncodingRecipeNumber>,
+}
+
+impl<'formats> RecipeGroup<'formats> {
+    fn new(formats: &'formats FormatRegistry) -> Self {
+        Self {
+            formats,
+            recipes: Recipes::new(),
+            name_to_recipe: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, builder: EncodingRecipeBuilder) {
+        assert!(
+            self.name_to_recipe.get(&builder.name).is_none(),
+            format!("arm32 recipe '{}' created twice", builder.name)
+        );
+        let name = builder.name.clone();
+        let number = self.recipes.push(builder.build(self.formats));
+        self.name_to_recipe.insert(name, number);
+    }
+
+    pub fn by_name(&self, name: &str) -> EncodingRecipeNumber {
+        let number = *self
+            .name_to_recipe
+            .get(name)
+            .expect(&format!("unknown arm32 recipe name {}", name));
+        number
+    }
+
+    pub fn collect(self) -> Recipes {
+        self.recipes
+    }
+}
+
+/// Returns a predicate checking that the `cond` field is one of the given condition codes.
+fn supported_floatccs_predicate(
+    supported_cc: &[Literal],
+    format: &InstructionFormat,
+) -> InstructionPredicate {
+    supported_cc
+        .iter()
+        .fold(InstructionPredicate::new(), |pred, literal| {
+            pred.or(InstructionPredicate::new_is_field_equal(
+                format,
+                "cond",
+                literal.to_rust_code(),
+            ))
+        })
+}
+
+pub(crate) fn define<'formats>(
+    shared_defs: &'formats SharedDefinitions,
+    regs: &IsaRegs,
+) -> RecipeGroup<'formats> {
+    let formats = &shared_defs.format_registry;
+
+    // The set of floating point condition codes that can be tested with a single condition after
+    // a `vcmp` instruction. The `one` and `ueq` conditions need two tests.
+    let floatcc = &shared_defs.imm.floatcc;
+    let supported_floatccs: Vec<Literal> = [
+        "ord", "uno", "eq", "ne", "lt", "le", "gt", "ge", "ult", "ule", "ugt", "uge",
+    ]
+    .iter()
+    .map(|name| Literal::enumerator_for(floatcc, name))
+    .collect();
+
+    // Format shorthands.
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_float = formats.by_name("BranchFloat");
+    let f_branch_int = formats.by_name("BranchInt");
+    let f_branch_table_base = formats.by_name("BranchTableBase");
+    let f_branch_table_entry = formats.by_name("BranchTableEntry");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_copy_special = formats.by_name("CopySpecial");
+    let f_copy_to_ssa = formats.by_name("CopyToSsa");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_float_cond = formats.by_name("FloatCond");
+    let f_float_cond_trap = formats.by_name("FloatCondTrap");
+    let f_func_addr = formats.by_name("FuncAddr");
+    let f_indirect_jump = formats.by_name("IndirectJump");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_int_cond = formats.by_name("IntCond");
+    let f_int_cond_trap = formats.by_name("IntCondTrap");
+    let f_int_select = formats.by_name("IntSelect");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
+    let f_reg_fill = formats.by_name("RegFill");
+    let f_reg_move = formats.by_name("RegMove");
+    let f_reg_spill = formats.by_name("RegSpill");
+    let f_stack_load = formats.by_name("StackLoad");
+    let f_store = formats.by_name("Store");
+    let f_ternary = formats.by_name("Ternary");
+    let f_trap = formats.by_name("Trap");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_bool = formats.by_name("UnaryBool");
+    let f_unary_global_value = formats.by_name("UnaryGlobalValue");
+    let f_unary_ieee32 = formats.by_name("UnaryIeee32");
+    let f_unary_ieee64 = formats.by_name("UnaryIeee64");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let gpr = regs.class_by_name("GPR");
+    let s = regs.class_by_name("S");
+    let d = regs.class_by_name("D");
+    let flag = regs.class_by_name("FLAG");
+
+    // Specific register shorthands.
+    let reg_nzcv = Register::new(flag, regs.regunit_by_name(flag, "nzcv"));
+
+    // Stack operand shorthands.
+    let stack_gpr = Stack::new(gpr);
+
+    // Definitions.
+    let mut recipes = RecipeGroup::new(&shared_defs.format_registry);
+
+    // A null unary instruction that takes a GPR register. Can be used for identity copies and
+    // no-op conversions.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", f_unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .emit(""),
+    );
+
+    // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
+    recipes.push(
+        EncodingRecipeBuilder::new("stacknull", f_unary, 0)
+            .operands_in(vec![stack_gpr])
+            .operands_out(vec![stack_gpr])
+            .emit(""),
+    );
+
+    // No-op fills, created by late-stage redundant-fill removal.
+    recipes.push(
+        EncodingRecipeBuilder::new("fillnull", f_unary, 0)
+            .operands_in(vec![stack_gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+
+    // Data-processing instructions with three registers, `Rd = Rn op Rm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("R", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(in_reg0) | rd(out_reg0) | rm(in_reg1), sink);"),
+    );
+
+    // Same as R, but also producing or consuming a carry in the flags.
+    recipes.push(
+        EncodingRecipeBuilder::new("R_ifcout", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_nzcv),
+            ])
+            .emit("put_a32(bits, rn(in_reg0) | rd(out_reg0) | rm(in_reg1), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("R_ifcin", f_ternary, 4)
+            .operands_in(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_nzcv),
+            ])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(in_reg0) | rd(out_reg0) | rm(in_reg1), sink);"),
+    );
+
+    // Multiplications, where `Rd` is in the `Rn` field and the operands in the `Rm` and `Rs`
+    // fields.
+    recipes.push(
+        EncodingRecipeBuilder::new("mul", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(out_reg0) | rs(in_reg1) | rm(in_reg0), sink);"),
+    );
+
+    // High half of a long multiplication. The low half is written to the scratch register %r12.
+    recipes.push(
+        EncodingRecipeBuilder::new("mulhi", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let lo = rd(RU::r12.into());
+                    put_a32(bits, rn(out_reg0) | lo | rs(in_reg1) | rm(in_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Divisions, laid out like the multiplications with the `Ra` field set to all ones.
+    recipes.push(
+        EncodingRecipeBuilder::new("div", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(out_reg0) | 0xf << 12 | rs(in_reg1) | rm(in_reg0), sink);"),
+    );
+
+    // Unary instructions with the source in `Rm`, e.g. `mov` and `mvn`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rm", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rd(out_reg0) | rm(in_reg0), sink);"),
+    );
+
+    // Same as Rm, but for a regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmov", f_reg_move, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rd(dst) | rm(src), sink);"),
+    );
+
+    // Same as Rm, but for a copy-to-SSA.
+    recipes.push(
+        EncodingRecipeBuilder::new("copytossa", f_copy_to_ssa, 4)
+            // No operands_in to mention, because a source register is specified directly.
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rd(out_reg0) | rm(src), sink);"),
+    );
+
+    // Unary instructions with the `Rn` field set to all ones, e.g. `clz`, `rbit` and the
+    // extensions.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rext", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, 0xf << 16 | rd(out_reg0) | rm(in_reg0), sink);"),
+    );
+
+    // Count trailing zeros as `rbit` followed by `clz`. The encoding bits are for the `clz`.
+    recipes.push(
+        EncodingRecipeBuilder::new("ctz", f_unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // rbit rd, rm
+                    sink.put4(0xe6ff_0f30 | rd(out_reg0) | rm(in_reg0));
+                    put_a32(bits, 0xf << 16 | rd(out_reg0) | rm(out_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Shifts by a register. The shift instructions use the bottom byte of the amount, so it is
+    // masked into the scratch register %r12 first to get the modulo semantics of Cranelift.
+    recipes.push(
+        EncodingRecipeBuilder::new("shift_r", f_binary, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let scratch = RU::r12.into();
+                    // and r12, rs, #31
+                    sink.put4(0xe200_001f | rn(in_reg1) | rd(scratch));
+                    put_a32(bits, rd(out_reg0) | rs(scratch) | rm(in_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Shifts by an immediate. A shift by 0 is encoded as a plain `mov`, since some shift types
+    // use that encoding for a shift by 32 or a rotation through the carry.
+    recipes.push(
+        EncodingRecipeBuilder::new("shift_imm", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    let shift = (imm as u32) & 31;
+                    let bits = if shift == 0 { bits & !SHIFT_TYPE_BITS } else { bits };
+                    put_a32(bits, rd(out_reg0) | shift << 7 | rm(in_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Data-processing instructions with an 8-bit unsigned immediate.
+    {
+        let format = formats.get(f_binary_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("Ri8", f_binary_imm, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 8, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_a32(bits, rn(in_reg0) | rd(out_reg0) | imm as u32, sink);
+                    "#,
+                ),
+        );
+
+        // Additions of a small signed immediate, using a subtraction for negative values.
+        recipes.push(
+            EncodingRecipeBuilder::new("addi", f_binary_imm, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "imm", 9, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        let (bits, imm) = if imm < 0 { (bits ^ ADD_SUB_BITS, -imm) } else { (bits, imm) };
+                        let imm = arm_imm(imm as u32).unwrap();
+                        put_a32(bits, rn(in_reg0) | rd(out_reg0) | imm, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Integer constants, materialized by a `mov` or `mvn` of a modified immediate, or by a `movw`
+    // and `movt` pair.
+    recipes.push(
+        EncodingRecipeBuilder::new("iconst", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_iconst")
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    put_iconst(imm as u32, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Boolean constants are 0 or 1 in a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("bconst", f_unary_bool, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rd(out_reg0) | u32::from(imm), sink);"),
+    );
+
+    // Integer comparisons producing a boolean: `cmp` followed by a conditional `mov`.
+    recipes.push(
+        EncodingRecipeBuilder::new("icmp", f_int_compare, 12)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_a32(bits, rn(in_reg0) | rm(in_reg1), sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    {
+        let format = formats.get(f_int_compare_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("icmp_imm", f_int_compare_imm, 12)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 8, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_a32(bits, rn(in_reg0) | imm as u32, sink);
+                        put_cset(icc2cond(cond), out_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Comparisons producing CPU flags.
+    recipes.push(
+        EncodingRecipeBuilder::new("ifcmp", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![reg_nzcv])
+            .emit("put_a32(bits, rn(in_reg0) | rm(in_reg1), sink);"),
+    );
+
+    {
+        let format = formats.get(f_binary_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("ifcmp_imm", f_binary_imm, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![reg_nzcv])
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 8, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_a32(bits, rn(in_reg0) | imm as u32, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Test flags and set a register to 0 or 1.
+    recipes.push(
+        EncodingRecipeBuilder::new("trueif", f_int_cond, 8)
+            .operands_in(vec![reg_nzcv])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_cset(icc2cond(cond), out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("trueff", f_float_cond, 8)
+            .operands_in(vec![reg_nzcv])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_float_cond),
+            ))
+            .emit("put_cset(fcc2cond(cond), out_reg0, sink);"),
+    );
+
+    // Conditional select using CPU flags: the result is tied to the first value, which is
+    // replaced by the second one with a conditional `mov` when the condition doesn't hold.
+    recipes.push(
+        EncodingRecipeBuilder::new("selectif", f_int_select, 4)
+            .operands_in(vec![
+                OperandConstraint::FixedReg(reg_nzcv),
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::RegClass(gpr),
+            ])
+            .operands_out(vec![1])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let cond = icc2cond(cond.inverse());
+                    put_a32_cond(bits, cond, rd(in_reg1) | rm(in_reg2), sink);
+                "#,
+            ),
+    );
+
+    // Loads and stores with a 12-bit offset, for words and bytes.
+    {
+        let format = formats.get(f_load);
+        recipes.push(
+            EncodingRecipeBuilder::new("ld", f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 12, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem12(bits, offset.into(), in_reg0, rd(out_reg0), sink);
+                    "#,
+                ),
+        );
+
+        // Loads of halfwords and signed bytes with an 8-bit offset.
+        recipes.push(
+            EncodingRecipeBuilder::new("ldh", f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 8, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem8(bits, offset.into(), in_reg0, out_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    {
+        let format = formats.get(f_store);
+        recipes.push(
+            EncodingRecipeBuilder::new("st", f_store, 4)
+                .operands_in(vec![gpr, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 12, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem12(bits, offset.into(), in_reg1, rd(in_reg0), sink);
+                    "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new("sth", f_store, 4)
+                .operands_in(vec![gpr, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 8, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem8(bits, offset.into(), in_reg1, in_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Spills and fills, relative to the stack pointer. Offsets that don't fit in the instruction
+    // are computed in the scratch register %r12 first.
+    recipes.push(
+        EncodingRecipeBuilder::new("spill", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![stack_gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_stack_access(bits, out_stk0, rd(in_reg0), sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regspill", f_reg_spill, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    put_stack_access(bits, dst, rd(src), sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fill", f_unary, 4)
+            .operands_in(vec![stack_gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit("put_stack_access(bits, in_stk0, rd(out_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regfill", f_reg_fill, 4)
+            .operands_in(vec![stack_gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    put_stack_access(bits, src, rd(dst), sink);
+                "#,
+            ),
+    );
+
+    // Floating point instructions, defined for the single precision S registers and the double
+    // precision D registers. The emitted code places the register operands with the `vd`, `vn`
+    // and `vm` placeholders, which are replaced by the functions for the register class.
+    for &(prefix, fpr) in &[("s", s), ("d", d)] {
+        let name = |base: &str| format!("{}{}", prefix, base);
+        let code = |emit: &str| {
+            if prefix == "s" {
+                emit.replace("vd(", "sd(")
+                    .replace("vn(", "sn(")
+                    .replace("vm(", "sm(")
+            } else {
+                emit.replace("vd(", "dd(")
+                    .replace("vn(", "dn(")
+                    .replace("vm(", "dm(")
+            }
+        };
+        let stack_fpr = Stack::new(fpr);
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("fillnull"), f_unary, 0)
+                .operands_in(vec![stack_fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(""),
+        );
+
+        // Arithmetic with three registers, `Vd = Vn op Vm`.
+        recipes.push(
+            EncodingRecipeBuilder::new(name("R"), f_binary, 4)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(code(
+                    "put_a32(bits, vd(out_reg0) | vn(in_reg0) | vm(in_reg1), sink);",
+                )),
+        );
+
+        // Unary operations with the `rr` encoding bits, e.g. `vabs`, `vsqrt` and `vmov`.
+        recipes.push(
+            EncodingRecipeBuilder::new(name("RR"), f_unary, 4)
+                .operands_in(vec![fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(code("put_vfp_rr(bits, vd(out_reg0) | vm(in_reg0), sink);")),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("Rmov"), f_reg_move, 4)
+                .operands_in(vec![fpr])
+                .clobbers_flags(false)
+                .emit(code("put_vfp_rr(bits, vd(dst) | vm(src), sink);")),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("copytossa"), f_copy_to_ssa, 4)
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(code("put_vfp_rr(bits, vd(out_reg0) | vm(src), sink);")),
+        );
+
+        // Conversions from a 32-bit integer: the integer is moved into the low S register of
+        // the destination and converted in place.
+        recipes.push(
+            EncodingRecipeBuilder::new(name("itof"), f_unary, 8)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .emit(code(
+                    r#"
+                        // vmov s, rt
+                        sink.put4(0xee00_0a10 | sn(out_reg0) | rd(in_reg0));
+                        put_vfp_rr(bits, vd(out_reg0) | sm(out_reg0), sink);
+                    "#,
+                )),
+        );
+
+        // Conversions to a 32-bit integer, in an S register.
+        recipes.push(
+            EncodingRecipeBuilder::new(name("ftoi"), f_unary, 4)
+                .operands_in(vec![fpr])
+                .operands_out(vec![s])
+                .clobbers_flags(false)
+                .emit(code("put_vfp_rr(bits, sd(out_reg0) | vm(in_reg0), sink);")),
+        );
+
+        // Comparisons: `vcmp` followed by a `vmrs` copying the floating point flags to the CPU
+        // flags.
+        recipes.push(
+            EncodingRecipeBuilder::new(name("fcmp"), f_float_compare, 16)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![gpr])
+                .inst_predicate(supported_floatccs_predicate(
+                    &supported_floatccs,
+                    formats.get(f_float_compare),
+                ))
+                .emit(code(
+                    r#"
+                        put_vfp_rr(bits, vd(in_reg0) | vm(in_reg1), sink);
+                        put_vmrs(sink);
+                        put_cset(fcc2cond(cond), out_reg0, sink);
+                    "#,
+                )),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("ffcmp"), f_binary, 8)
+                .operands_in(vec![fpr, fpr])
+                .operands_out(vec![reg_nzcv])
+                .emit(code(
+                    r#"
+                        put_vfp_rr(bits, vd(in_reg0) | vm(in_reg1), sink);
+                        put_vmrs(sink);
+                    "#,
+                )),
+        );
+
+        // Loads and stores with a word-aligned 8-bit offset.
+        recipes.push(
+            EncodingRecipeBuilder::new(name("ld"), f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    formats.get(f_load),
+                    "offset",
+                    10,
+                    2,
+                ))
+                .emit(code(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_vmem(bits, offset.into(), in_reg0, vd(out_reg0), sink);
+                    "#,
+                )),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("st"), f_store, 4)
+                .operands_in(vec![fpr, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    formats.get(f_store),
+                    "offset",
+                    10,
+                    2,
+                ))
+                .emit(code(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_vmem(bits, offset.into(), in_reg1, vd(in_reg0), sink);
+                    "#,
+                )),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("spill"), f_unary, 4)
+                .operands_in(vec![fpr])
+                .operands_out(vec![stack_fpr])
+                .clobbers_flags(false)
+                .compute_size("size_for_stack_access")
+                .emit(code(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        put_stack_access(bits, out_stk0, vd(in_reg0), sink);
+                    "#,
+                )),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("regspill"), f_reg_spill, 4)
+                .operands_in(vec![fpr])
+                .clobbers_flags(false)
+                .compute_size("size_for_stack_access")
+                .emit(code(
+                    r#"
+                        sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                        let dst = StackRef::sp(dst, &func.stack_slots);
+                        put_stack_access(bits, dst, vd(src), sink);
+                    "#,
+                )),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("fill"), f_unary, 4)
+                .operands_in(vec![stack_fpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .compute_size("size_for_stack_access")
+                .emit(code("put_stack_access(bits, in_stk0, vd(out_reg0), sink);")),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new(name("regfill"), f_reg_fill, 4)
+                .operands_in(vec![stack_fpr])
+                .clobbers_flags(false)
+                .compute_size("size_for_stack_access")
+                .emit(code(
+                    r#"
+                        let src = StackRef::sp(src, &func.stack_slots);
+                        put_stack_access(bits, src, vd(dst), sink);
+                    "#,
+                )),
+        );
+    }
+
+    // Conversions between single and double precision.
+    recipes.push(
+        EncodingRecipeBuilder::new("StoD", f_unary, 4)
+            .operands_in(vec![s])
+            .operands_out(vec![d])
+            .clobbers_flags(false)
+            .emit("put_vfp_rr(bits, dd(out_reg0) | sm(in_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("DtoS", f_unary, 4)
+            .operands_in(vec![d])
+            .operands_out(vec![s])
+            .clobbers_flags(false)
+            .emit("put_vfp_rr(bits, sd(out_reg0) | dm(in_reg0), sink);"),
+    );
+
+    // Moves between a GPR and an S register, without conversion.
+    recipes.push(
+        EncodingRecipeBuilder::new("GtoS", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![s])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, sn(out_reg0) | rd(in_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("StoG", f_unary, 4)
+            .operands_in(vec![s])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, sn(in_reg0) | rd(out_reg0), sink);"),
+    );
+
+    // Floating point constants are loaded from a literal that is branched over:
+    //
+    //   vldr vd, [pc]
+    //   b    over
+    //   .word value
+    recipes.push(
+        EncodingRecipeBuilder::new("sconst", f_unary_ieee32, 12)
+            .operands_out(vec![s])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_vmem(bits, 0, RU::r15.into(), sd(out_reg0), sink);
+                    put_b(BRANCH_BITS, i64::from(sink.offset() + 8), sink);
+                    sink.put4(imm.bits());
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("dconst", f_unary_ieee64, 16)
+            .operands_out(vec![d])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_vmem(bits, 0, RU::r15.into(), dd(out_reg0), sink);
+                    put_b(BRANCH_BITS, i64::from(sink.offset() + 12), sink);
+                    sink.put8(imm.bits());
+                "#,
+            ),
+    );
+
+    // Address of a stack slot, added to the stack pointer as a modified immediate, or
+    // materialized with a `movw` and `movt` pair first.
+    recipes.push(
+        EncodingRecipeBuilder::new("spaddr", f_stack_load, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_spaddr")
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let imm: i32 = offset.into();
+                    let offset = sp.offset.checked_add(imm).unwrap();
+                    put_sp_offset(offset as u32, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Stack pointer manipulations, used in the prologue and epilogues.
+    recipes.push(
+        EncodingRecipeBuilder::new("copysp", f_copy_special, 4)
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rd(dst) | rm(src), sink);"),
+    );
+
+    {
+        let format = formats.get(f_unary_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("adjustsp_imm8", f_unary_imm, 4)
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 8, 0,
+                ))
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        let sp = RU::r13.into();
+                        put_a32(bits, rn(sp) | rd(sp) | imm as u32, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Larger adjustments are materialized in the scratch register %r12. The encoding bits are
+    // for the register form of the instruction.
+    recipes.push(
+        EncodingRecipeBuilder::new("adjustsp", f_unary_imm, 12)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let imm: i64 = imm.into();
+                    let sp = RU::r13.into();
+                    let scratch = RU::r12.into();
+                    put_movw_movt(imm as u32, scratch, sink);
+                    put_a32(bits, rn(sp) | rd(sp) | rm(scratch), sink);
+                "#,
+            ),
+    );
+
+    // Pushes and pops of a single register.
+    recipes.push(
+        EncodingRecipeBuilder::new("push", f_unary, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(RU::r13.into()) | rd(in_reg0) | 4, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("pop", f_nullary, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(RU::r13.into()) | rd(out_reg0) | 4, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("dpush", f_unary, 4)
+            .operands_in(vec![d])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(RU::r13.into()) | dd(in_reg0) | 2, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("dpop", f_nullary, 4)
+            .operands_out(vec![d])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, rn(RU::r13.into()) | dd(out_reg0) | 2, sink);"),
+    );
+
+    // Branches, with a 24-bit word offset relative to the instruction address plus 8.
+    recipes.push(
+        EncodingRecipeBuilder::new("B", f_jump, 4)
+            .branch_range((8, 26))
+            .clobbers_flags(false)
+            .emit("put_b(bits, func.offsets[destination].into(), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Bcond", f_branch_int, 4)
+            .operands_in(vec![reg_nzcv])
+            .branch_range((8, 26))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let bits = with_cond(bits, icc2cond(cond));
+                    put_b(bits, func.offsets[destination].into(), sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Bfcond", f_branch_float, 4)
+            .operands_in(vec![reg_nzcv])
+            .branch_range((8, 26))
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_branch_float),
+            ))
+            .emit(
+                r#"
+                    let bits = with_cond(bits, fcc2cond(cond));
+                    put_b(bits, func.offsets[destination].into(), sink);
+                "#,
+            ),
+    );
+
+    // Compare a register with zero and branch. The encoding bits are for the conditional branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("brz", f_branch, 8)
+            .operands_in(vec![gpr])
+            .branch_range((12, 26))
+            .emit(
+                r#"
+                    // cmp rn, #0
+                    sink.put4(0xe350_0000 | rn(in_reg0));
+                    put_b(bits, func.offsets[destination].into(), sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("BX", f_indirect_jump, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, 0xff << 12 | rm(in_reg0), sink);"),
+    );
+
+    // Jump tables: the table is emitted right after the function body, and its distance from the
+    // `add` instruction is materialized with a `movw`.
+    recipes.push(
+        EncodingRecipeBuilder::new("jt_base", f_branch_table_base, 8)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // No reloc is needed here as the jump table is emitted directly after
+                    // the function body.
+                    let disp = func.jt_offsets[table] - (sink.offset() + 12);
+                    debug_assert!(disp < 1 << 16);
+                    put_movw(disp, out_reg0, sink);
+                    put_a32(bits, rn(RU::r15.into()) | rd(out_reg0) | rm(out_reg0), sink);
+                "#,
+            ),
+    );
+
+    {
+        let format = formats.get(f_branch_table_entry);
+        recipes.push(
+            EncodingRecipeBuilder::new("jt_entry", f_branch_table_entry, 4)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_field_equal(
+                    format,
+                    "imm",
+                    "4".into(),
+                ))
+                .emit("put_a32(bits, rn(in_reg1) | rd(out_reg0) | rm(in_reg0), sink);"),
+        );
+    }
+
+    // Calls and returns.
+    recipes.push(
+        EncodingRecipeBuilder::new("BL", f_call, 4)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.reloc_external(Reloc::Arm32Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        -8);
+                    put_b(bits, i64::from(sink.offset() + 8), sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("BLX", f_call_indirect, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_a32(bits, 0xff << 12 | rm(in_reg0), sink);"),
+    );
+
+    // Returns always branch to %r14, which is provided as a special-purpose link argument.
+    recipes.push(
+        EncodingRecipeBuilder::new("ret", f_multiary, 4)
+            .clobbers_flags(false)
+            .emit("put_a32(bits, 0xff << 12 | rm(RU::r14.into()), sink);"),
+    );
+
+    // Absolute addresses are loaded from a literal that is branched over.
+    recipes.push(
+        EncodingRecipeBuilder::new("fnaddr", f_func_addr, 12)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_literal_load(out_reg0, sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("gvaddr", f_unary_global_value, 12)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_literal_load(out_reg0, sink);
+                    sink.reloc_external(Reloc::Abs4,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // Traps.
+    recipes.push(
+        EncodingRecipeBuilder::new("udf", f_trap, 4)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_a32(bits, 0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("bkpt", f_nullary, 4)
+            .clobbers_flags(false)
+            .emit("put_a32(bits, 0, sink);"),
+    );
+
+    // Conditional branch over an `udf`. The encoding bits are for the branch.
+    recipes.push(
+        EncodingRecipeBuilder::new("trapif", f_int_cond_trap, 8)
+            .operands_in(vec![reg_nzcv])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let bits = with_cond(bits, icc2cond(cond.inverse()));
+                    put_b(bits, i64::from(sink.offset() + 8), sink);
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put4(UDF);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("trapff", f_float_cond_trap, 8)
+            .operands_in(vec![reg_nzcv])
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_float_cond_trap),
+            ))
+            .emit(
+                r#"
+                    let bits = with_cond(bits, fcc2cond(cond.inverse()));
+                    put_b(bits, i64::from(sink.offset() + 8), sink);
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put4(UDF);
+                "#,
+            ),
+    );
+
+    recipes
+}
@@ -0,0 +1,809 @@
+use crate::cdsl::encodings::{Encoding, EncodingBuilder};
+use crate::cdsl::instructions::{
+    InstSpec, InstructionGroup, InstructionPredicate, InstructionPredicateRegistry,
+};
+use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
+use crate::cdsl::types::LaneType;
+
+use crate::shared::types::Bool::B1;
+use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Int::{I16, I32, I64, I8};
+use crate::shared::Definitions as SharedDefinitions;
+
+use super::recipes::RecipeGroup;
+
+fn enc(inst: impl Into<InstSpec>, recipe: EncodingRecipeNumber, bits: u16) -> EncodingBuilder {
+    EncodingBuilder::new(inst.into(), recipe, bits)
+}
+
+pub struct PerCpuModeEncodings<'defs> {
+    pub inst_pred_reg: InstructionPredicateRegistry,
+    pub enc64: Vec<Encoding>,
+    recipes: &'defs Recipes,
+}
+
+impl<'defs> PerCpuModeEncodings<'defs> {
+    fn new(recipes: &'defs Recipes) -> Self {
+        Self {
+            inst_pred_reg: InstructionPredicateRegistry::new(),
+            enc64: Vec::new(),
+            recipes,
+        }
+    }
+    fn add64(&mut self, encoding: EncodingBuilder) {
+        self.enc64
+            .push(encoding.build(self.recipes, &mut self.inst_pred_reg));
+    }
+}
+
+// All A64 instructions are 32 bits wide, but the encbits only have 16 bits. The functions below
+// take a full instruction template with all the operand fields cleared, and pack the bits that
+// aren't provided by the recipe into the encbits. They are unpacked by the `put_*` functions in
+// `binemit.rs`.
+
+/// Encbits for instructions with three registers Rd, Rn and Rm, whose operand fields are the
+/// low 5 bits of each 16-bit half: `w[31:21] | w[14:10] << 11`.
+fn rrr_bits(w: u32) -> u16 {
+    assert_eq!(
+        w & 0x001f_83ff,
+        0,
+        "{:#010x} can't use the rrr_bits layout",
+        w
+    );
+    ((w >> 21) | ((w >> 10) & 0x1f) << 11) as u16
+}
+
+/// Encbits for the floating point instructions with two registers Rd and Rn:
+/// `w[31] << 15 | w[28:15] << 1 | w[14]`.
+fn rr_bits(w: u32) -> u16 {
+    assert_eq!(
+        w & 0x6000_3fff,
+        0,
+        "{:#010x} can't use the rr_bits layout",
+        w
+    );
+    ((w >> 31) << 15 | ((w >> 15) & 0x3fff) << 1 | ((w >> 14) & 1)) as u16
+}
+
+/// Encbits for the conditional select instructions: `w[31:21] | w[11:10] << 11`.
+fn csel_bits(w: u32) -> u16 {
+    assert_eq!(
+        w & 0x001f_f3ff,
+        0,
+        "{:#010x} can't use the csel_bits layout",
+        w
+    );
+    ((w >> 21) | ((w >> 10) & 0x3) << 11) as u16
+}
+
+/// Encbits for the instructions where the recipe computes the whole low half: `w[31:16]`.
+fn hi_bits(w: u32) -> u16 {
+    assert_eq!(w & 0xffff, 0, "{:#010x} can't use the hi_bits layout", w);
+    (w >> 16) as u16
+}
+
+/// The scaled unsigned offset form of a load or store can be turned into the unscaled signed
+/// offset form by clearing bit 24.
+fn unscaled(w: u32) -> u32 {
+    assert!(w & (1 << 24) != 0);
+    w & !(1 << 24)
+}
+
+pub(crate) fn define<'defs>(
+    shared_defs: &'defs SharedDefinitions,
+    arm64: &InstructionGroup,
+    recipes: &'defs RecipeGroup,
+) -> PerCpuModeEncodings<'defs> {
+    // Instructions shorthands.
+    let shared = &shared_defs.instructions;
+
+    let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
+    let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let band = shared.by_name("band");
+    let band_not = shared.by_name("band_not");
+    let bconst = shared.by_name("bconst");
+    let bint = shared.by_name("bint");
+    let bitcast = shared.by_name("bitcast");
+    let bnot = shared.by_name("bnot");
+    let bor = shared.by_name("bor");
+    let bor_not = shared.by_name("bor_not");
+    let brff = shared.by_name("brff");
+    let brif = shared.by_name("brif");
+    let brnz = shared.by_name("brnz");
+    let brz = shared.by_name("brz");
+    let bxor = shared.by_name("bxor");
+    let bxor_not = shared.by_name("bxor_not");
+    let call = shared.by_name("call");
+    let call_indirect = shared.by_name("call_indirect");
+    let ceil = shared.by_name("ceil");
+    let clz = shared.by_name("clz");
+    let copy = shared.by_name("copy");
+    let copy_nop = shared.by_name("copy_nop");
+    let copy_special = shared.by_name("copy_special");
+    let copy_to_ssa = shared.by_name("copy_to_ssa");
+    let ctz = shared.by_name("ctz");
+    let debugtrap = shared.by_name("debugtrap");
+    let fabs = shared.by_name("fabs");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fcvt_from_uint = shared.by_name("fcvt_from_uint");
+    let fcvt_to_sint_sat = shared.by_name("fcvt_to_sint_sat");
+    let fcvt_to_uint_sat = shared.by_name("fcvt_to_uint_sat");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
+    let ffcmp = shared.by_name("ffcmp");
+    let fill = shared.by_name("fill");
+    let fill_nop = shared.by_name("fill_nop");
+    let floor = shared.by_name("floor");
+    let fmax = shared.by_name("fmax");
+    let fmin = shared.by_name("fmin");
+    let fmul = shared.by_name("fmul");
+    let fneg = shared.by_name("fneg");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
+    let func_addr = shared.by_name("func_addr");
+    let iadd = shared.by_name("iadd");
+    let iadd_ifcin = shared.by_name("iadd_ifcin");
+    let iadd_ifcout = shared.by_name("iadd_ifcout");
+    let iadd_imm = shared.by_name("iadd_imm");
+    let icmp = shared.by_name("icmp");
+    let icmp_imm = shared.by_name("icmp_imm");
+    let iconst = shared.by_name("iconst");
+    let ifcmp = shared.by_name("ifcmp");
+    let ifcmp_imm = shared.by_name("ifcmp_imm");
+    let imul = shared.by_name("imul");
+    let indirect_jump_table_br = shared.by_name("indirect_jump_table_br");
+    let ireduce = shared.by_name("ireduce");
+    let ishl = shared.by_name("ishl");
+    let ishl_imm = shared.by_name("ishl_imm");
+    let istore16 = shared.by_name("istore16");
+    let istore32 = shared.by_name("istore32");
+    let istore8 = shared.by_name("istore8");
+    let isub = shared.by_name("isub");
+    let isub_ifbin = shared.by_name("isub_ifbin");
+    let isub_ifbout = shared.by_name("isub_ifbout");
+    let jump = shared.by_name("jump");
+    let jump_table_base = shared.by_name("jump_table_base");
+    let jump_table_entry = shared.by_name("jump_table_entry");
+    let load = shared.by_name("load");
+    let nearest = shared.by_name("nearest");
+    let regfill = shared.by_name("regfill");
+    let regmove = shared.by_name("regmove");
+    let regspill = shared.by_name("regspill");
+    let resumable_trap = shared.by_name("resumable_trap");
+    let return_ = shared.by_name("return");
+    let rotr = shared.by_name("rotr");
+    let rotr_imm = shared.by_name("rotr_imm");
+    let selectif = shared.by_name("selectif");
+    let sextend = shared.by_name("sextend");
+    let sload16 = shared.by_name("sload16");
+    let sload32 = shared.by_name("sload32");
+    let sload8 = shared.by_name("sload8");
+    let smulhi = shared.by_name("smulhi");
+    let spill = shared.by_name("spill");
+    let sqrt = shared.by_name("sqrt");
+    let sshr = shared.by_name("sshr");
+    let sshr_imm = shared.by_name("sshr_imm");
+    let stack_addr = shared.by_name("stack_addr");
+    let store = shared.by_name("store");
+    let symbol_value = shared.by_name("symbol_value");
+    let trap = shared.by_name("trap");
+    let trapff = shared.by_name("trapff");
+    let trapif = shared.by_name("trapif");
+    let trapnz = shared.by_name("trapnz");
+    let trapz = shared.by_name("trapz");
+    let trueff = shared.by_name("trueff");
+    let trueif = shared.by_name("trueif");
+    let trunc = shared.by_name("trunc");
+    let uextend = shared.by_name("uextend");
+    let uload16 = shared.by_name("uload16");
+    let uload32 = shared.by_name("uload32");
+    let uload8 = shared.by_name("uload8");
+    let umulhi = shared.by_name("umulhi");
+    let ushr = shared.by_name("ushr");
+    let ushr_imm = shared.by_name("ushr_imm");
+
+    // arm64 specific instructions.
+    let arm64_pop = arm64.by_name("arm64_pop");
+    let arm64_pop_pair = arm64.by_name("arm64_pop_pair");
+    let arm64_push = arm64.by_name("arm64_push");
+    let arm64_push_pair = arm64.by_name("arm64_push_pair");
+    let arm64_sdiv = arm64.by_name("arm64_sdiv");
+    let arm64_udiv = arm64.by_name("arm64_udiv");
+
+    // Recipes shorthands, prefixed with r_.
+    let r_adjustsp_imm12 = recipes.by_name("adjustsp_imm12");
+    let r_adjustsp_imm24 = recipes.by_name("adjustsp_imm24");
+    let r_b = recipes.by_name("B");
+    let r_bcond = recipes.by_name("Bcond");
+    let r_bconst = recipes.by_name("bconst");
+    let r_bfcond = recipes.by_name("Bfcond");
+    let r_bl = recipes.by_name("BL");
+    let r_blr = recipes.by_name("BLR");
+    let r_brk = recipes.by_name("brk");
+    let r_cbz = recipes.by_name("CBZ");
+    let r_copysp = recipes.by_name("copysp");
+    let r_copytossa = recipes.by_name("copytossa");
+    let r_ctz = recipes.by_name("ctz");
+    let r_f_r = recipes.by_name("fR");
+    let r_f_rmov = recipes.by_name("fRmov");
+    let r_f_rr = recipes.by_name("fRR");
+    let r_f_to_g = recipes.by_name("FtoG");
+    let r_fcmp = recipes.by_name("fcmp");
+    let r_fcopytossa = recipes.by_name("fcopytossa");
+    let r_ffcmp = recipes.by_name("ffcmp");
+    let r_ffill = recipes.by_name("ffill");
+    let r_ffillnull = recipes.by_name("ffillnull");
+    let r_fill = recipes.by_name("fill");
+    let r_fillnull = recipes.by_name("fillnull");
+    let r_fld = recipes.by_name("fld");
+    let r_fldu = recipes.by_name("fldu");
+    let r_fnaddr = recipes.by_name("fnaddr");
+    let r_fpop = recipes.by_name("fpop");
+    let r_fpop_pair = recipes.by_name("fpop_pair");
+    let r_fpush = recipes.by_name("fpush");
+    let r_fpush_pair = recipes.by_name("fpush_pair");
+    let r_fregfill = recipes.by_name("fregfill");
+    let r_fregspill = recipes.by_name("fregspill");
+    let r_fspill = recipes.by_name("fspill");
+    let r_fst = recipes.by_name("fst");
+    let r_fstu = recipes.by_name("fstu");
+    let r_g_to_f = recipes.by_name("GtoF");
+    let r_gvaddr = recipes.by_name("gvaddr");
+    let r_icmp = recipes.by_name("icmp");
+    let r_icmp_imm = recipes.by_name("icmp_imm");
+    let r_iconst = recipes.by_name("iconst");
+    let r_ifcmp = recipes.by_name("ifcmp");
+    let r_ifcmp_imm = recipes.by_name("ifcmp_imm");
+    let r_indirect_br = recipes.by_name("indirect_br");
+    let r_jt_base = recipes.by_name("jt_base");
+    let r_jt_entry = recipes.by_name("jt_entry");
+    let r_ld = recipes.by_name("ld");
+    let r_ldu = recipes.by_name("ldu");
+    let r_lsl_imm = recipes.by_name("lsl_imm");
+    let r_null = recipes.by_name("null");
+    let r_pop = recipes.by_name("pop");
+    let r_pop_pair = recipes.by_name("pop_pair");
+    let r_push = recipes.by_name("push");
+    let r_push_pair = recipes.by_name("push_pair");
+    let r_r = recipes.by_name("R");
+    let r_r_ifcin = recipes.by_name("R_ifcin");
+    let r_r_ifcout = recipes.by_name("R_ifcout");
+    let r_ri12 = recipes.by_name("Ri12");
+    let r_rm = recipes.by_name("Rm");
+    let r_rmov = recipes.by_name("Rmov");
+    let r_rn = recipes.by_name("Rn");
+    let r_regfill = recipes.by_name("regfill");
+    let r_regspill = recipes.by_name("regspill");
+    let r_ret = recipes.by_name("ret");
+    let r_ror_imm = recipes.by_name("ror_imm");
+    let r_selectif = recipes.by_name("selectif");
+    let r_shr_imm = recipes.by_name("shr_imm");
+    let r_spaddr = recipes.by_name("spaddr");
+    let r_spill = recipes.by_name("spill");
+    let r_st = recipes.by_name("st");
+    let r_stacknull = recipes.by_name("stacknull");
+    let r_stu = recipes.by_name("stu");
+    let r_trueff = recipes.by_name("trueff");
+    let r_trueif = recipes.by_name("trueif");
+    let r_trapff = recipes.by_name("trapff");
+    let r_trapif = recipes.by_name("trapif");
+    let r_trapz = recipes.by_name("trapz");
+    let r_udf = recipes.by_name("udf");
+
+    // Predicates shorthands.
+    let formats = &shared_defs.format_registry;
+    let f_call = formats.get(formats.by_name("Call"));
+    let f_load = formats.get(formats.by_name("Load"));
+    let f_store = formats.get(formats.by_name("Store"));
+
+    // Definitions.
+    let mut e = PerCpuModeEncodings::new(&recipes.recipes);
+
+    // Integer arithmetic and logical instructions with (shifted) registers, and data-processing
+    // instructions with two sources. The first template is the 32-bit `w` form, the second the
+    // 64-bit `x` form.
+    for &(inst, w, x) in &[
+        (iadd, 0x0b00_0000, 0x8b00_0000),
+        (isub, 0x4b00_0000, 0xcb00_0000),
+        (band, 0x0a00_0000, 0x8a00_0000),
+        (bor, 0x2a00_0000, 0xaa00_0000),
+        (bxor, 0x4a00_0000, 0xca00_0000),
+        (band_not, 0x0a20_0000, 0x8a20_0000),
+        (bor_not, 0x2a20_0000, 0xaa20_0000),
+        (bxor_not, 0x4a20_0000, 0xca20_0000),
+        (imul, 0x1b00_7c00, 0x9b00_7c00),
+        (arm64_udiv, 0x1ac0_0800, 0x9ac0_0800),
+        (arm64_sdiv, 0x1ac0_0c00, 0x9ac0_0c00),
+    ] {
+        e.add64(enc(inst.bind(I32), r_r, rrr_bits(w)));
+        e.add64(enc(inst.bind(I64), r_r, rrr_bits(x)));
+    }
+
+    // Booleans are 0 or 1, so the 32-bit logical instructions work on them.
+    for &(inst, w) in &[(band, 0x0a00_0000), (bor, 0x2a00_0000), (bxor, 0x4a00_0000)] {
+        e.add64(enc(inst.bind(B1), r_r, rrr_bits(w)));
+    }
+
+    e.add64(enc(umulhi.bind(I64), r_r, rrr_bits(0x9bc0_7c00)));
+    e.add64(enc(smulhi.bind(I64), r_r, rrr_bits(0x9b40_7c00)));
+
+    // Additions and subtractions producing or consuming a carry or borrow in the flags. The
+    // borrow of the subtractions is the inverted carry flag.
+    for &(inst, w, x) in &[
+        (iadd_ifcout, 0x2b00_0000, 0xab00_0000),
+        (isub_ifbout, 0x6b00_0000, 0xeb00_0000),
+    ] {
+        e.add64(enc(inst.bind(I32), r_r_ifcout, rrr_bits(w)));
+        e.add64(enc(inst.bind(I64), r_r_ifcout, rrr_bits(x)));
+    }
+    for &(inst, w, x) in &[
+        (iadd_ifcin, 0x1a00_0000, 0x9a00_0000),
+        (isub_ifbin, 0x5a00_0000, 0xda00_0000),
+    ] {
+        e.add64(enc(inst.bind(I32), r_r_ifcin, rrr_bits(w)));
+        e.add64(enc(inst.bind(I64), r_r_ifcin, rrr_bits(x)));
+    }
+
+    // Additions with an immediate.
+    e.add64(enc(iadd_imm.bind(I32), r_ri12, hi_bits(0x1100_0000)));
+    e.add64(enc(iadd_imm.bind(I64), r_ri12, hi_bits(0x9100_0000)));
+
+    // Dynamic shifts use the amount modulo the register width, like the clif instructions.
+    for &(inst, w, x) in &[
+        (ishl, 0x1ac0_2000, 0x9ac0_2000),
+        (ushr, 0x1ac0_2400, 0x9ac0_2400),
+        (sshr, 0x1ac0_2800, 0x9ac0_2800),
+        (rotr, 0x1ac0_2c00, 0x9ac0_2c00),
+    ] {
+        e.add64(enc(inst.bind(I32).bind(I32), r_r, rrr_bits(w)));
+        e.add64(enc(inst.bind(I32).bind(I64), r_r, rrr_bits(w)));
+        e.add64(enc(inst.bind(I64).bind(I64), r_r, rrr_bits(x)));
+        e.add64(enc(inst.bind(I64).bind(I32), r_r, rrr_bits(x)));
+    }
+
+    // Immediate shifts are aliases of the bitfield move and extract instructions.
+    e.add64(enc(ishl_imm.bind(I32), r_lsl_imm, hi_bits(0x5300_0000)));
+    e.add64(enc(ishl_imm.bind(I64), r_lsl_imm, hi_bits(0xd340_0000)));
+    e.add64(enc(ushr_imm.bind(I32), r_shr_imm, hi_bits(0x5300_0000)));
+    e.add64(enc(ushr_imm.bind(I64), r_shr_imm, hi_bits(0xd340_0000)));
+    e.add64(enc(sshr_imm.bind(I32), r_shr_imm, hi_bits(0x1300_0000)));
+    e.add64(enc(sshr_imm.bind(I64), r_shr_imm, hi_bits(0x9340_0000)));
+    e.add64(enc(rotr_imm.bind(I32), r_ror_imm, hi_bits(0x1380_0000)));
+    e.add64(enc(rotr_imm.bind(I64), r_ror_imm, hi_bits(0x93c0_0000)));
+
+    // Bit counting.
+    e.add64(enc(clz.bind(I32), r_rn, rrr_bits(0x5ac0_1000)));
+    e.add64(enc(clz.bind(I64), r_rn, rrr_bits(0xdac0_1000)));
+    e.add64(enc(ctz.bind(I32), r_ctz, rrr_bits(0x5ac0_1000)));
+    e.add64(enc(ctz.bind(I64), r_ctz, rrr_bits(0xdac0_1000)));
+
+    // `mvn` is an alias of `orn` with the zero register.
+    e.add64(enc(bnot.bind(I32), r_rm, rrr_bits(0x2a20_0000)));
+    e.add64(enc(bnot.bind(I64), r_rm, rrr_bits(0xaa20_0000)));
+
+    // Sign extensions are aliases of `sbfm`, zero extensions of `ubfm`. Writing a `w` register
+    // clears the high 32 bits, so the 32-bit forms also extend to 64 bits.
+    for &(from, w, x) in &[
+        (I8, 0x1300_1c00, 0x9340_1c00),
+        (I16, 0x1300_3c00, 0x9340_3c00),
+    ] {
+        e.add64(enc(sextend.bind(I32).bind(from), r_rn, rrr_bits(w)));
+        e.add64(enc(sextend.bind(I64).bind(from), r_rn, rrr_bits(x)));
+    }
+    e.add64(enc(
+        sextend.bind(I64).bind(I32),
+        r_rn,
+        rrr_bits(0x9340_7c00),
+    ));
+    for &(from, w) in &[(I8, 0x5300_1c00), (I16, 0x5300_3c00)] {
+        e.add64(enc(uextend.bind(I32).bind(from), r_rn, rrr_bits(w)));
+        e.add64(enc(uextend.bind(I64).bind(from), r_rn, rrr_bits(w)));
+    }
+    e.add64(enc(
+        uextend.bind(I64).bind(I32),
+        r_rm,
+        rrr_bits(0x2a00_0000),
+    ));
+
+    // The 32-bit instructions ignore the high bits of their operands, so reductions are free.
+    for &(to, from) in &[(I32, I64), (I16, I32), (I16, I64), (I8, I32), (I8, I64)] {
+        e.add64(enc(ireduce.bind(to).bind(from), r_null, 0));
+    }
+    e.add64(enc(bint.bind(I32).bind(B1), r_null, 0));
+    e.add64(enc(bint.bind(I64).bind(B1), r_null, 0));
+
+    // Constants.
+    e.add64(enc(iconst.bind(I32), r_iconst, hi_bits(0x5280_0000)));
+    e.add64(enc(iconst.bind(I64), r_iconst, hi_bits(0xd280_0000)));
+    e.add64(enc(bconst.bind(B1), r_bconst, hi_bits(0x5280_0000)));
+
+    // Integer comparisons, as a `subs` to the zero register.
+    e.add64(enc(icmp.bind(I32), r_icmp, rrr_bits(0x6b00_0000)));
+    e.add64(enc(icmp.bind(I64), r_icmp, rrr_bits(0xeb00_0000)));
+    e.add64(enc(icmp_imm.bind(I32), r_icmp_imm, hi_bits(0x7100_0000)));
+    e.add64(enc(icmp_imm.bind(I64), r_icmp_imm, hi_bits(0xf100_0000)));
+    e.add64(enc(ifcmp.bind(I32), r_ifcmp, rrr_bits(0x6b00_0000)));
+    e.add64(enc(ifcmp.bind(I64), r_ifcmp, rrr_bits(0xeb00_0000)));
+    e.add64(enc(ifcmp_imm.bind(I32), r_ifcmp_imm, hi_bits(0x7100_0000)));
+    e.add64(enc(ifcmp_imm.bind(I64), r_ifcmp_imm, hi_bits(0xf100_0000)));
+
+    // Floating point comparisons.
+    e.add64(enc(fcmp.bind(F32), r_fcmp, rrr_bits(0x1e20_2000)));
+    e.add64(enc(fcmp.bind(F64), r_fcmp, rrr_bits(0x1e60_2000)));
+    e.add64(enc(ffcmp.bind(F32), r_ffcmp, rrr_bits(0x1e20_2000)));
+    e.add64(enc(ffcmp.bind(F64), r_ffcmp, rrr_bits(0x1e60_2000)));
+
+    // Using the flags.
+    e.add64(enc(trueif, r_trueif, 0));
+    e.add64(enc(trueff, r_trueff, 0));
+    e.add64(enc(selectif.bind(I32), r_selectif, csel_bits(0x1a80_0000)));
+    e.add64(enc(selectif.bind(I64), r_selectif, csel_bits(0x9a80_0000)));
+
+    // Floating point arithmetic.
+    for &(inst, s, d) in &[
+        (fadd, 0x1e20_2800, 0x1e60_2800),
+        (fsub, 0x1e20_3800, 0x1e60_3800),
+        (fmul, 0x1e20_0800, 0x1e60_0800),
+        (fdiv, 0x1e20_1800, 0x1e60_1800),
+        (fmax, 0x1e20_4800, 0x1e60_4800),
+        (fmin, 0x1e20_5800, 0x1e60_5800),
+    ] {
+        e.add64(enc(inst.bind(F32), r_f_r, rrr_bits(s)));
+        e.add64(enc(inst.bind(F64), r_f_r, rrr_bits(d)));
+    }
+
+    // Bitwise operations on floats use the 8-byte vector forms of the SIMD instructions.
+    for &(inst, w) in &[
+        (band, 0x0e20_1c00),
+        (band_not, 0x0e60_1c00),
+        (bor, 0x0ea0_1c00),
+        (bxor, 0x2e20_1c00),
+    ] {
+        e.add64(enc(inst.bind(F32), r_f_r, rrr_bits(w)));
+        e.add64(enc(inst.bind(F64), r_f_r, rrr_bits(w)));
+    }
+
+    for &(inst, s, d) in &[
+        (fabs, 0x1e20_c000, 0x1e60_c000),
+        (fneg, 0x1e21_4000, 0x1e61_4000),
+        (sqrt, 0x1e21_c000, 0x1e61_c000),
+        (nearest, 0x1e24_4000, 0x1e64_4000),
+        (ceil, 0x1e24_c000, 0x1e64_c000),
+        (floor, 0x1e25_4000, 0x1e65_4000),
+        (trunc, 0x1e25_c000, 0x1e65_c000),
+    ] {
+        e.add64(enc(inst.bind(F32), r_f_rr, rr_bits(s)));
+        e.add64(enc(inst.bind(F64), r_f_rr, rr_bits(d)));
+    }
+
+    e.add64(enc(
+        fpromote.bind(F64).bind(F32),
+        r_f_rr,
+        rr_bits(0x1e22_c000),
+    ));
+    e.add64(enc(
+        fdemote.bind(F32).bind(F64),
+        r_f_rr,
+        rr_bits(0x1e62_4000),
+    ));
+
+    // Conversions between integers and floats.
+    for &(inst, ws, xs, wd, xd) in &[
+        (
+            fcvt_from_sint,
+            0x1e22_0000,
+            0x9e22_0000,
+            0x1e62_0000,
+            0x9e62_0000,
+        ),
+        (
+            fcvt_from_uint,
+            0x1e23_0000,
+            0x9e23_0000,
+            0x1e63_0000,
+            0x9e63_0000,
+        ),
+    ] {
+        e.add64(enc(inst.bind(F32).bind(I32), r_g_to_f, rr_bits(ws)));
+        e.add64(enc(inst.bind(F32).bind(I64), r_g_to_f, rr_bits(xs)));
+        e.add64(enc(inst.bind(F64).bind(I32), r_g_to_f, rr_bits(wd)));
+        e.add64(enc(inst.bind(F64).bind(I64), r_g_to_f, rr_bits(xd)));
+    }
+
+    // The `fcvtz*` instructions saturate, and convert NaN to 0.
+    for &(inst, ws, xs, wd, xd) in &[
+        (
+            fcvt_to_sint_sat,
+            0x1e38_0000,
+            0x9e38_0000,
+            0x1e78_0000,
+            0x9e78_0000,
+        ),
+        (
+            fcvt_to_uint_sat,
+            0x1e39_0000,
+            0x9e39_0000,
+            0x1e79_0000,
+            0x9e79_0000,
+        ),
+    ] {
+        e.add64(enc(inst.bind(I32).bind(F32), r_f_to_g, rr_bits(ws)));
+        e.add64(enc(inst.bind(I64).bind(F32), r_f_to_g, rr_bits(xs)));
+        e.add64(enc(inst.bind(I32).bind(F64), r_f_to_g, rr_bits(wd)));
+        e.add64(enc(inst.bind(I64).bind(F64), r_f_to_g, rr_bits(xd)));
+    }
+
+    e.add64(enc(
+        bitcast.bind(F32).bind(I32),
+        r_g_to_f,
+        rr_bits(0x1e27_0000),
+    ));
+    e.add64(enc(
+        bitcast.bind(F64).bind(I64),
+        r_g_to_f,
+        rr_bits(0x9e67_0000),
+    ));
+    e.add64(enc(
+        bitcast.bind(I32).bind(F32),
+        r_f_to_g,
+        rr_bits(0x1e26_0000),
+    ));
+    e.add64(enc(
+        bitcast.bind(I64).bind(F64),
+        r_f_to_g,
+        rr_bits(0x9e66_0000),
+    ));
+
+    // Loads and stores. The scaled form takes an unsigned 12-bit offset in multiples of the
+    // access size, which is encoded in the top two bits of the template. Other offsets use the
+    // unscaled form, with a signed 9-bit offset.
+    let scaled_offset = |format, w: u32| {
+        let scale = (w >> 30) as usize;
+        InstructionPredicate::new_is_unsigned_int(format, "offset", 12 + scale, scale)
+    };
+
+    for &(inst, ty, w) in &[
+        (load, I32, 0xb940_0000),
+        (load, I64, 0xf940_0000),
+        (uload8, I32, 0x3940_0000),
+        (uload8, I64, 0x3940_0000),
+        (sload8, I32, 0x39c0_0000),
+        (sload8, I64, 0x3980_0000),
+        (uload16, I32, 0x7940_0000),
+        (uload16, I64, 0x7940_0000),
+        (sload16, I32, 0x79c0_0000),
+        (sload16, I64, 0x7980_0000),
+    ] {
+        e.add64(
+            enc(inst.bind(ty).bind_any(), r_ld, hi_bits(w))
+                .inst_predicate(scaled_offset(f_load, w)),
+        );
+        e.add64(enc(inst.bind(ty).bind_any(), r_ldu, hi_bits(unscaled(w))));
+    }
+
+    // The 32-bit extending loads only produce i64 values, they are bound to the address type.
+    for &(inst, w) in &[(uload32, 0xb940_0000), (sload32, 0xb980_0000)] {
+        e.add64(enc(inst.bind(I64), r_ld, hi_bits(w)).inst_predicate(scaled_offset(f_load, w)));
+        e.add64(enc(inst.bind(I64), r_ldu, hi_bits(unscaled(w))));
+    }
+
+    for &(ty, w) in &[(F32, 0xbd40_0000), (F64, 0xfd40_0000)] {
+        e.add64(
+            enc(load.bind(ty).bind_any(), r_fld, hi_bits(w))
+                .inst_predicate(scaled_offset(f_load, w)),
+        );
+        e.add64(enc(load.bind(ty).bind_any(), r_fldu, hi_bits(unscaled(w))));
+    }
+
+    for &(inst, ty, w) in &[
+        (store, I32, 0xb900_0000),
+        (store, I64, 0xf900_0000),
+        (istore8, I32, 0x3900_0000),
+        (istore8, I64, 0x3900_0000),
+        (istore16, I32, 0x7900_0000),
+        (istore16, I64, 0x7900_0000),
+    ] {
+        e.add64(
+            enc(inst.bind(ty).bind_any(), r_st, hi_bits(w))
+                .inst_predicate(scaled_offset(f_store, w)),
+        );
+        e.add64(enc(inst.bind(ty).bind_any(), r_stu, hi_bits(unscaled(w))));
+    }
+
+    let w = 0xb900_0000;
+    e.add64(
+        enc(istore32.bind(I64).bind_any(), r_st, hi_bits(w))
+            .inst_predicate(scaled_offset(f_store, w)),
+    );
+    e.add64(enc(
+        istore32.bind(I64).bind_any(),
+        r_stu,
+        hi_bits(unscaled(w)),
+    ));
+
+    for &(ty, w) in &[(F32, 0xbd00_0000), (F64, 0xfd00_0000)] {
+        e.add64(
+            enc(store.bind(ty).bind_any(), r_fst, hi_bits(w))
+                .inst_predicate(scaled_offset(f_store, w)),
+        );
+        e.add64(enc(store.bind(ty).bind_any(), r_fstu, hi_bits(unscaled(w))));
+    }
+
+    // Spill and fill. The narrow types are spilled as 32-bit values.
+    for &ty in &[LaneType::from(I8), I16.into(), I32.into(), B1.into()] {
+        e.add64(enc(spill.bind(ty), r_spill, hi_bits(0xb900_0000)));
+        e.add64(enc(regspill.bind(ty), r_regspill, hi_bits(0xb900_0000)));
+        e.add64(enc(fill.bind(ty), r_fill, hi_bits(0xb940_0000)));
+        e.add64(enc(regfill.bind(ty), r_regfill, hi_bits(0xb940_0000)));
+        e.add64(enc(fill_nop.bind(ty), r_fillnull, 0));
+    }
+    e.add64(enc(spill.bind(I64), r_spill, hi_bits(0xf900_0000)));
+    e.add64(enc(regspill.bind(I64), r_regspill, hi_bits(0xf900_0000)));
+    e.add64(enc(fill.bind(I64), r_fill, hi_bits(0xf940_0000)));
+    e.add64(enc(regfill.bind(I64), r_regfill, hi_bits(0xf940_0000)));
+    e.add64(enc(fill_nop.bind(I64), r_fillnull, 0));
+
+    for &(ty, st, ld) in &[
+        (F32, 0xbd00_0000, 0xbd40_0000),
+        (F64, 0xfd00_0000, 0xfd40_0000),
+    ] {
+        e.add64(enc(spill.bind(ty), r_fspill, hi_bits(st)));
+        e.add64(enc(regspill.bind(ty), r_fregspill, hi_bits(st)));
+        e.add64(enc(fill.bind(ty), r_ffill, hi_bits(ld)));
+        e.add64(enc(regfill.bind(ty), r_fregfill, hi_bits(ld)));
+        e.add64(enc(fill_nop.bind(ty), r_ffillnull, 0));
+    }
+
+    // Stack-slot-to-the-same-stack-slot copy, which is guaranteed to turn into a no-op.
+    for &ty in &[
+        LaneType::from(I64),
+        I32.into(),
+        I16.into(),
+        I8.into(),
+        B1.into(),
+    ] {
+        e.add64(enc(copy_nop.bind(ty), r_stacknull, 0));
+    }
+    for &ty in &[F64, F32] {
+        e.add64(enc(copy_nop.bind(ty), r_stacknull, 0));
+    }
+
+    // Register copies: `mov` is an alias of `orr` with the zero register, and `fmov` copies
+    // floating point registers.
+    for &ty in &[LaneType::from(I8), I16.into(), I32.into(), B1.into()] {
+        e.add64(enc(copy.bind(ty), r_rm, rrr_bits(0x2a00_0000)));
+        e.add64(enc(regmove.bind(ty), r_rmov, rrr_bits(0x2a00_0000)));
+        e.add64(enc(
+            copy_to_ssa.bind(ty),
+            r_copytossa,
+            rrr_bits(0x2a00_0000),
+        ));
+    }
+    e.add64(enc(copy.bind(I64), r_rm, rrr_bits(0xaa00_0000)));
+    e.add64(enc(regmove.bind(I64), r_rmov, rrr_bits(0xaa00_0000)));
+    e.add64(enc(
+        copy_to_ssa.bind(I64),
+        r_copytossa,
+        rrr_bits(0xaa00_0000),
+    ));
+
+    for &(ty, w) in &[(F32, 0x1e20_4000), (F64, 0x1e60_4000)] {
+        e.add64(enc(copy.bind(ty), r_f_rr, rr_bits(w)));
+        e.add64(enc(regmove.bind(ty), r_f_rmov, rr_bits(w)));
+        e.add64(enc(copy_to_ssa.bind(ty), r_fcopytossa, rr_bits(w)));
+    }
+
+    // Stack pointer manipulations, used in the prologue and epilogues.
+    e.add64(enc(stack_addr.bind(I64), r_spaddr, hi_bits(0x9100_0000)));
+    e.add64(enc(copy_special, r_copysp, hi_bits(0x9100_0000)));
+    e.add64(enc(
+        adjust_sp_down_imm,
+        r_adjustsp_imm12,
+        hi_bits(0xd100_0000),
+    ));
+    e.add64(enc(
+        adjust_sp_down_imm,
+        r_adjustsp_imm24,
+        hi_bits(0xd100_0000),
+    ));
+    e.add64(enc(
+        adjust_sp_up_imm,
+        r_adjustsp_imm12,
+        hi_bits(0x9100_0000),
+    ));
+    e.add64(enc(
+        adjust_sp_up_imm,
+        r_adjustsp_imm24,
+        hi_bits(0x9100_0000),
+    ));
+
+    e.add64(enc(
+        arm64_push_pair.bind(I64),
+        r_push_pair,
+        hi_bits(0xa9bf_0000),
+    ));
+    e.add64(enc(
+        arm64_push_pair.bind(F64),
+        r_fpush_pair,
+        hi_bits(0x6dbf_0000),
+    ));
+    e.add64(enc(
+        arm64_pop_pair.bind(I64),
+        r_pop_pair,
+        hi_bits(0xa8c1_0000),
+    ));
+    e.add64(enc(
+        arm64_pop_pair.bind(F64),
+        r_fpop_pair,
+        hi_bits(0x6cc1_0000),
+    ));
+    e.add64(enc(arm64_push.bind(I64), r_push, hi_bits(0xf81f_0000)));
+    e.add64(enc(arm64_push.bind(F64), r_fpush, hi_bits(0xfc1f_0000)));
+    e.add64(enc(arm64_pop.bind(I64), r_pop, hi_bits(0xf841_0000)));
+    e.add64(enc(arm64_pop.bind(F64), r_fpop, hi_bits(0xfc41_0000)));
+
+    // Control flow.
+    e.add64(enc(jump, r_b, hi_bits(0x1400_0000)));
+    e.add64(enc(brif, r_bcond, hi_bits(0x5400_0000)));
+    e.add64(enc(brff, r_bfcond, hi_bits(0x5400_0000)));
+
+    for &(inst, w, x) in &[
+        (brz, 0x3400_0000, 0xb400_0000),
+        (brnz, 0x3500_0000, 0xb500_0000),
+    ] {
+        e.add64(enc(inst.bind(I32), r_cbz, hi_bits(w)));
+        e.add64(enc(inst.bind(B1), r_cbz, hi_bits(w)));
+        e.add64(enc(inst.bind(I64), r_cbz, hi_bits(x)));
+    }
+
+    // Jump tables.
+    e.add64(enc(
+        jump_table_base.bind(I64),
+        r_jt_base,
+        hi_bits(0x1000_0000),
+    ));
+    e.add64(enc(
+        jump_table_entry.bind(I64),
+        r_jt_entry,
+        rrr_bits(0xb8a0_7800),
+    ));
+    e.add64(enc(
+        indirect_jump_table_br.bind(I64),
+        r_indirect_br,
+        hi_bits(0xd61f_0000),
+    ));
+
+    // Calls. The `bl` instruction has a range of +/- 128 MB, so it's only used for colocated
+    // functions. Other calls are legalized to a `func_addr` and an indirect call.
+    let is_colocated_func = InstructionPredicate::new_is_colocated_func(f_call, "func_ref");
+    e.add64(enc(call, r_bl, hi_bits(0x9400_0000)).inst_predicate(is_colocated_func));
+    e.add64(enc(call_indirect.bind(I64), r_blr, hi_bits(0xd63f_0000)));
+
+    // Returns branch to %x30, which is provided by a special-purpose `link` return value that
+    // is added by legalize_signature().
+    e.add64(enc(return_, r_ret, hi_bits(0xd65f_0000)));
+
+    e.add64(enc(func_addr.bind(I64), r_fnaddr, 0));
+    e.add64(enc(symbol_value.bind(I64), r_gvaddr, 0));
+
+    // Traps.
+    e.add64(enc(trap, r_udf, 0));
+    e.add64(enc(resumable_trap, r_udf, 0));
+    e.add64(enc(debugtrap, r_brk, hi_bits(0xd420_0000)));
+    e.add64(enc(trapif, r_trapif, hi_bits(0x5400_0000)));
+    e.add64(enc(trapff, r_trapff, hi_bits(0x5400_0000)));
+
+    // Conditional traps branch over an `udf` with the inverted test.
+    for &(inst, w, x) in &[
+        (trapz, 0x3500_0000, 0xb500_0000),
+        (trapnz, 0x3400_0000, 0xb400_0000),
+    ] {
+        e.add64(enc(inst.bind(I32), r_trapz, hi_bits(w)));
+        e.add64(enc(inst.bind(B1), r_trapz, hi_bits(w)));
+        e.add64(enc(inst.bind(I64), r_trapz, hi_bits(x)));
+    }
+
+    e
+}
@@ -0,0 +1,130 @@
+#![allow(non_snake_case)]
+
+use crate::cdsl::formats::FormatRegistry;
+use crate::cdsl::instructions::{
+    AllInstructions, InstructionBuilder as Inst, InstructionGroup, InstructionGroupBuilder,
+};
+use crate::cdsl::operands::{create_operand as operand, create_operand_doc as operand_doc};
+use crate::cdsl::typevar::{TypeSetBuilder, TypeVar};
+
+pub(crate) fn define(
+    mut all_instructions: &mut AllInstructions,
+    format_registry: &FormatRegistry,
+) -> InstructionGroup {
+    let mut ig = InstructionGroupBuilder::new(
+        "arm64",
+        "arm64 specific instruction set",
+        &mut all_instructions,
+        format_registry,
+    );
+
+    let iWord = &TypeVar::new(
+        "iWord",
+        "A scalar integer machine word",
+        TypeSetBuilder::new().ints(32..64).build(),
+    );
+    let n = &operand_doc("n", iWord, "Numerator");
+    let d = &operand_doc("d", iWord, "Denominator");
+    let q = &operand_doc("q", iWord, "Quotient");
+
+    ig.push(
+        Inst::new(
+            "arm64_udiv",
+            r#"
+        Unsigned division without traps.
+
+        Interpret the bits in `n` and `d` as unsigned numbers and divide them.
+        Unlike `udiv`, this never traps: division by zero produces zero.
+        "#,
+        )
+        .operands_in(vec![n, d])
+        .operands_out(vec![q]),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm64_sdiv",
+            r#"
+        Signed division without traps.
+
+        Interpret the bits in `n` and `d` as signed numbers and divide them,
+        rounding towards zero. Unlike `sdiv`, this never traps: division by
+        zero produces zero, and `INT_MIN / -1` wraps to `INT_MIN`.
+        "#,
+        )
+        .operands_in(vec![n, d])
+        .operands_out(vec![q]),
+    );
+
+    let Any64 = &TypeVar::new(
+        "Any64",
+        "A 64-bit integer or floating point register value",
+        TypeSetBuilder::new().ints(64..64).floats(64..64).build(),
+    );
+    let x = &operand("x", Any64);
+    let y = &operand("y", Any64);
+
+    ig.push(
+        Inst::new(
+            "arm64_push_pair",
+            r#"
+    Pushes a pair of values onto the stack.
+
+    Decrements the stack pointer by 16 bytes and stores `x` and `y` at the
+    new top of the stack, `x` at the lower address. This is implemented for
+    i64 and f64 values.
+    "#,
+        )
+        .operands_in(vec![x, y])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm64_pop_pair",
+            r#"
+    Pops a pair of values from the stack.
+
+    Loads `x` and `y` from the top of the stack, `x` from the lower address,
+    and then increments the stack pointer by 16 bytes. This is implemented
+    for i64 and f64 values.
+    "#,
+        )
+        .operands_out(vec![x, y])
+        .other_side_effects(true)
+        .can_load(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm64_push",
+            r#"
+    Pushes a value onto the stack.
+
+    Decrements the stack pointer by 16 bytes to keep it aligned and stores
+    `x` at the new top of the stack.
+    "#,
+        )
+        .operands_in(vec![x])
+        .other_side_effects(true)
+        .can_store(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "arm64_pop",
+            r#"
+    Pops a value from the stack.
+
+    Loads `x` from the top of the stack and then increments the stack
+    pointer by 16 bytes.
+    "#,
+        )
+        .operands_out(vec![x])
+        .other_side_effects(true)
+        .can_load(true),
+    );
+
+    ig.build()
+}
@@ -0,0 +1,231 @@
+use crate::cdsl::ast::{var, ExprBuilder, Literal};
+use crate::cdsl::xform::TransformGroupBuilder;
+use crate::shared::types::Int::{I32, I64};
+use crate::shared::Definitions as SharedDefinitions;
+
+pub(crate) fn define(shared: &mut SharedDefinitions) {
+    let mut group = TransformGroupBuilder::new(
+        "arm64_expand",
+        r#"
+    Legalize instructions by expansion.
+
+    Use arm64-specific instructions if needed."#,
+    )
+    .isa("arm64")
+    .chain_with(shared.transform_groups.by_name("expand_flags").id);
+
+    // List of instructions.
+    let insts = &shared.instructions;
+    let band = insts.by_name("band");
+    let bor = insts.by_name("bor");
+    let fcmp = insts.by_name("fcmp");
+    let fcvt_to_sint = insts.by_name("fcvt_to_sint");
+    let fcvt_to_uint = insts.by_name("fcvt_to_uint");
+    let iadd = insts.by_name("iadd");
+    let iconst = insts.by_name("iconst");
+    let imul = insts.by_name("imul");
+    let ireduce = insts.by_name("ireduce");
+    let irsub_imm = insts.by_name("irsub_imm");
+    let isub = insts.by_name("isub");
+    let istore8 = insts.by_name("istore8");
+    let istore16 = insts.by_name("istore16");
+    let istore32 = insts.by_name("istore32");
+    let load = insts.by_name("load");
+    let popcnt = insts.by_name("popcnt");
+    let rotl = insts.by_name("rotl");
+    let rotr = insts.by_name("rotr");
+    let sdiv = insts.by_name("sdiv");
+    let sextend = insts.by_name("sextend");
+    let sload8 = insts.by_name("sload8");
+    let sload16 = insts.by_name("sload16");
+    let sload32 = insts.by_name("sload32");
+    let smulhi = insts.by_name("smulhi");
+    let srem = insts.by_name("srem");
+    let sshr_imm = insts.by_name("sshr_imm");
+    let store = insts.by_name("store");
+    let udiv = insts.by_name("udiv");
+    let uextend = insts.by_name("uextend");
+    let uload8 = insts.by_name("uload8");
+    let uload16 = insts.by_name("uload16");
+    let uload32 = insts.by_name("uload32");
+    let umulhi = insts.by_name("umulhi");
+    let urem = insts.by_name("urem");
+    let ushr_imm = insts.by_name("ushr_imm");
+
+    // The arm64 division instructions don't trap, so the legalization inserts the checks.
+    group.custom_legalize(sdiv, "expand_sdivrem");
+    group.custom_legalize(srem, "expand_sdivrem");
+    group.custom_legalize(udiv, "expand_udivrem");
+    group.custom_legalize(urem, "expand_udivrem");
+
+    // Conversions from float to int can trap.
+    group.custom_legalize(fcvt_to_sint, "expand_fcvt_to_int");
+    group.custom_legalize(fcvt_to_uint, "expand_fcvt_to_int");
+
+    // Loads and stores with an offset that doesn't fit in the addressing mode.
+    for &inst in &[
+        load, uload8, sload8, uload16, sload16, uload32, sload32, store, istore8, istore16,
+        istore32,
+    ] {
+        group.custom_legalize(inst, "expand_mem_offset");
+    }
+
+    let imm = &shared.imm;
+
+    let a = var("a");
+    let a1 = var("a1");
+    let a2 = var("a2");
+    let x = var("x");
+    let x1 = var("x1");
+    let y = var("y");
+    let y1 = var("y1");
+
+    // The 32-bit high multiplications use a full 64-bit multiplication.
+    let imm64_32 = Literal::constant(&imm.imm64, 32);
+    group.legalize(
+        def!(a = umulhi.I32(x, y)),
+        vec![
+            def!(x1 = uextend.I64(x)),
+            def!(y1 = uextend.I64(y)),
+            def!(a1 = imul(x1, y1)),
+            def!(a2 = ushr_imm(a1, imm64_32)),
+            def!(a = ireduce.I32(a2)),
+        ],
+    );
+    group.legalize(
+        def!(a = smulhi.I32(x, y)),
+        vec![
+            def!(x1 = sextend.I64(x)),
+            def!(y1 = sextend.I64(y)),
+            def!(a1 = imul(x1, y1)),
+            def!(a2 = sshr_imm(a1, imm64_32)),
+            def!(a = ireduce.I32(a2)),
+        ],
+    );
+
+    // There is no rotate-left instruction, rotate right by the negated amount instead.
+    let imm64_0 = Literal::constant(&imm.imm64, 0);
+    group.legalize(
+        def!(a = rotl(x, y)),
+        vec![def!(a1 = irsub_imm(y, imm64_0)), def!(a = rotr(x, a1))],
+    );
+
+    // Floating point condition codes.
+    //
+    // A single `fcmp` followed by a condition check supports all the condition codes except
+    // `one` and `ueq`, which need two tests.
+    let floatcc_one = Literal::enumerator_for(&imm.floatcc, "one");
+    let floatcc_ueq = Literal::enumerator_for(&imm.floatcc, "ueq");
+    let floatcc_ord = Literal::enumerator_for(&imm.floatcc, "ord");
+    let floatcc_uno = Literal::enumerator_for(&imm.floatcc, "uno");
+    let floatcc_eq = Literal::enumerator_for(&imm.floatcc, "eq");
+    let floatcc_ne = Literal::enumerator_for(&imm.floatcc, "ne");
+
+    group.legalize(
+        def!(a = fcmp(floatcc_one, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_ord, x, y)),
+            def!(a2 = fcmp(floatcc_ne, x, y)),
+            def!(a = band(a1, a2)),
+        ],
+    );
+    group.legalize(
+        def!(a = fcmp(floatcc_ueq, x, y)),
+        vec![
+            def!(a1 = fcmp(floatcc_uno, x, y)),
+            def!(a2 = fcmp(floatcc_eq, x, y)),
+            def!(a = bor(a1, a2)),
+        ],
+    );
+
+    // Population count using only general purpose registers.
+    let qv1 = var("qv1");
+    let qv3 = var("qv3");
+    let qv4 = var("qv4");
+    let qv5 = var("qv5");
+    let qv6 = var("qv6");
+    let qv7 = var("qv7");
+    let qv8 = var("qv8");
+    let qv9 = var("qv9");
+    let qv10 = var("qv10");
+    let qv11 = var("qv11");
+    let qv12 = var("qv12");
+    let qv13 = var("qv13");
+    let qv14 = var("qv14");
+    let qv15 = var("qv15");
+    let qv16 = var("qv16");
+    let qc77 = var("qc77");
+    #[allow(non_snake_case)]
+    let qc0F = var("qc0F");
+    let qc01 = var("qc01");
+
+    let imm64_1 = Literal::constant(&imm.imm64, 1);
+    let imm64_4 = Literal::constant(&imm.imm64, 4);
+    group.legalize(
+        def!(qv16 = popcnt.I64(qv1)),
+        vec![
+            def!(qv3 = ushr_imm(qv1, imm64_1)),
+            def!(qc77 = iconst(Literal::constant(&imm.imm64, 0x7777777777777777))),
+            def!(qv4 = band(qv3, qc77)),
+            def!(qv5 = isub(qv1, qv4)),
+            def!(qv6 = ushr_imm(qv4, imm64_1)),
+            def!(qv7 = band(qv6, qc77)),
+            def!(qv8 = isub(qv5, qv7)),
+            def!(qv9 = ushr_imm(qv7, imm64_1)),
+            def!(qv10 = band(qv9, qc77)),
+            def!(qv11 = isub(qv8, qv10)),
+            def!(qv12 = ushr_imm(qv11, imm64_4)),
+            def!(qv13 = iadd(qv11, qv12)),
+            def!(qc0F = iconst(Literal::constant(&imm.imm64, 0x0F0F0F0F0F0F0F0F))),
+            def!(qv14 = band(qv13, qc0F)),
+            def!(qc01 = iconst(Literal::constant(&imm.imm64, 0x0101010101010101))),
+            def!(qv15 = imul(qv14, qc01)),
+            def!(qv16 = ushr_imm(qv15, Literal::constant(&imm.imm64, 56))),
+        ],
+    );
+
+    let lv1 = var("lv1");
+    let lv3 = var("lv3");
+    let lv4 = var("lv4");
+    let lv5 = var("lv5");
+    let lv6 = var("lv6");
+    let lv7 = var("lv7");
+    let lv8 = var("lv8");
+    let lv9 = var("lv9");
+    let lv10 = var("lv10");
+    let lv11 = var("lv11");
+    let lv12 = var("lv12");
+    let lv13 = var("lv13");
+    let lv14 = var("lv14");
+    let lv15 = var("lv15");
+    let lv16 = var("lv16");
+    let lc77 = var("lc77");
+    #[allow(non_snake_case)]
+    let lc0F = var("lc0F");
+    let lc01 = var("lc01");
+
+    group.legalize(
+        def!(lv16 = popcnt.I32(lv1)),
+        vec![
+            def!(lv3 = ushr_imm(lv1, imm64_1)),
+            def!(lc77 = iconst(Literal::constant(&imm.imm64, 0x77777777))),
+            def!(lv4 = band(lv3, lc77)),
+            def!(lv5 = isub(lv1, lv4)),
+            def!(lv6 = ushr_imm(lv4, imm64_1)),
+            def!(lv7 = band(lv6, lc77)),
+            def!(lv8 = isub(lv5, lv7)),
+            def!(lv9 = ushr_imm(lv7, imm64_1)),
+            def!(lv10 = band(lv9, lc77)),
+            def!(lv11 = isub(lv8, lv10)),
+            def!(lv12 = ushr_imm(lv11, imm64_4)),
+            def!(lv13 = iadd(lv11, lv12)),
+            def!(lc0F = iconst(Literal::constant(&imm.imm64, 0x0F0F0F0F))),
+            def!(lv14 = band(lv13, lc0F)),
+            def!(lc01 = iconst(Literal::constant(&imm.imm64, 0x01010101))),
+            def!(lv15 = imul(lv14, lc01)),
+            def!(lv16 = ushr_imm(lv15, Literal::constant(&imm.imm64, 24))),
+        ],
+    );
+
+    group.build_and_add_to(&mut shared.transform_groups);
+}
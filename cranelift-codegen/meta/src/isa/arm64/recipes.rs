@@ -0,0 +1,1101 @@
+use std::collections::HashMap;
+
+use crate::cdsl::ast::Literal;
+use crate::cdsl::formats::{FormatRegistry, InstructionFormat};
+use crate::cdsl::instructions::InstructionPredicate;
+use crate::cdsl::recipes::{
+    EncodingRecipeBuilder, EncodingRecipeNumber, OperandConstraint, Recipes, Register, Stack,
+};
+use crate::cdsl::regs::IsaRegs;
+use crate::shared::Definitions as SharedDefinitions;
+
+/// An helper to create recipes and use them when defining the arm64 encodings.
+pub struct RecipeGroup<'formats> {
+    /// Memoized format registry, to pass it to the builders.
+    formats: &'formats FormatRegistry,
+
+    /// The actualy list of recipes explicitly created in this file.
+    pub recipes: Recipes,
+
+    /// Provides fast lookup from a name to an encoding recipe.
+    name_to_recipe: HashMap<String, EncodingRecipeNumber>,
+}
+
+impl<'formats> RecipeGroup<'formats> {
+    fn new(formats: &'formats FormatRegistry) -> Self {
+        Self {
+            formats,
+            recipes: Recipes::new(),
+            name_to_recipe: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, builder: EncodingRecipeBuilder) {
+        assert!(
+            self.name_to_recipe.get(&builder.name).is_none(),
+            format!("arm64 recipe '{}' created twice", builder.name)
+        );
+        let name = builder.name.clone();
+        let number = self.recipes.push(builder.build(self.formats));
+        self.name_to_recipe.insert(name, number);
+    }
+
+    pub fn by_name(&self, name: &str) -> EncodingRecipeNumber {
+        let number = *self
+            .name_to_recipe
+            .get(name)
+            .expect(&format!("unknown arm64 recipe name {}", name));
+        number
+    }
+
+    pub fn collect(self) -> Recipes {
+        self.recipes
+    }
+}
+
+/// Returns a predicate checking that the `cond` field is one of the given condition codes.
+fn supported_floatccs_predicate(
+    supported_cc: &[Literal],
+    format: &InstructionFormat,
+) -> InstructionPredicate {
+    supported_cc
+        .iter()
+        .fold(InstructionPredicate::new(), |pred, literal| {
+            pred.or(InstructionPredicate::new_is_field_equal(
+                format,
+                "cond",
+                literal.to_rust_code(),
+            ))
+        })
+}
+
+pub(crate) fn define<'formats>(
+    shared_defs: &'formats SharedDefinitions,
+    regs: &IsaRegs,
+) -> RecipeGroup<'formats> {
+    let formats = &shared_defs.format_registry;
+
+    // The set of floating point condition codes that can be tested with a single condition after
+    // an `fcmp` instruction. The `one` and `ueq` conditions need two tests.
+    let floatcc = &shared_defs.imm.floatcc;
+    let supported_floatccs: Vec<Literal> = [
+        "ord", "uno", "eq", "ne", "lt", "le", "gt", "ge", "ult", "ule", "ugt", "uge",
+    ]
+    .iter()
+    .map(|name| Literal::enumerator_for(floatcc, name))
+    .collect();
+
+    // Format shorthands.
+    let f_binary = formats.by_name("Binary");
+    let f_binary_imm = formats.by_name("BinaryImm");
+    let f_branch = formats.by_name("Branch");
+    let f_branch_float = formats.by_name("BranchFloat");
+    let f_branch_int = formats.by_name("BranchInt");
+    let f_branch_table_base = formats.by_name("BranchTableBase");
+    let f_branch_table_entry = formats.by_name("BranchTableEntry");
+    let f_call = formats.by_name("Call");
+    let f_call_indirect = formats.by_name("CallIndirect");
+    let f_cond_trap = formats.by_name("CondTrap");
+    let f_copy_special = formats.by_name("CopySpecial");
+    let f_copy_to_ssa = formats.by_name("CopyToSsa");
+    let f_float_compare = formats.by_name("FloatCompare");
+    let f_float_cond = formats.by_name("FloatCond");
+    let f_float_cond_trap = formats.by_name("FloatCondTrap");
+    let f_func_addr = formats.by_name("FuncAddr");
+    let f_indirect_jump = formats.by_name("IndirectJump");
+    let f_int_compare = formats.by_name("IntCompare");
+    let f_int_compare_imm = formats.by_name("IntCompareImm");
+    let f_int_cond = formats.by_name("IntCond");
+    let f_int_cond_trap = formats.by_name("IntCondTrap");
+    let f_int_select = formats.by_name("IntSelect");
+    let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
+    let f_multiary = formats.by_name("MultiAry");
+    let f_nullary = formats.by_name("NullAry");
+    let f_reg_fill = formats.by_name("RegFill");
+    let f_reg_move = formats.by_name("RegMove");
+    let f_reg_spill = formats.by_name("RegSpill");
+    let f_stack_load = formats.by_name("StackLoad");
+    let f_store = formats.by_name("Store");
+    let f_ternary = formats.by_name("Ternary");
+    let f_trap = formats.by_name("Trap");
+    let f_unary = formats.by_name("Unary");
+    let f_unary_bool = formats.by_name("UnaryBool");
+    let f_unary_global_value = formats.by_name("UnaryGlobalValue");
+    let f_unary_imm = formats.by_name("UnaryImm");
+
+    // Register classes shorthands.
+    let gpr = regs.class_by_name("GPR");
+    let fpr = regs.class_by_name("FPR");
+    let flag = regs.class_by_name("FLAG");
+
+    // Specific register shorthands.
+    let reg_nzcv = Register::new(flag, regs.regunit_by_name(flag, "nzcv"));
+
+    // Stack operand shorthands.
+    let stack_gpr = Stack::new(gpr);
+    let stack_fpr = Stack::new(fpr);
+
+    // Definitions.
+    let mut recipes = RecipeGroup::new(&shared_defs.format_registry);
+
+    // A null unary instruction that takes a GPR register. Can be used for identity copies and
+    // no-op conversions.
+    recipes.push(
+        EncodingRecipeBuilder::new("null", f_unary, 0)
+            .operands_in(vec![gpr])
+            .operands_out(vec![0])
+            .emit(""),
+    );
+
+    // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
+    recipes.push(
+        EncodingRecipeBuilder::new("stacknull", f_unary, 0)
+            .operands_in(vec![stack_gpr])
+            .operands_out(vec![stack_gpr])
+            .emit(""),
+    );
+
+    // No-op fills, created by late-stage redundant-fill removal.
+    recipes.push(
+        EncodingRecipeBuilder::new("fillnull", f_unary, 0)
+            .operands_in(vec![stack_gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+    recipes.push(
+        EncodingRecipeBuilder::new("ffillnull", f_unary, 0)
+            .operands_in(vec![stack_fpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(""),
+    );
+
+    // Three-register instructions: integer arithmetic with shifted registers, data-processing
+    // (2 source), multiplications.
+    recipes.push(
+        EncodingRecipeBuilder::new("R", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Same as R, but also producing or consuming a carry in the flags.
+    recipes.push(
+        EncodingRecipeBuilder::new("R_ifcout", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_nzcv),
+            ])
+            .emit("put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("R_ifcin", f_ternary, 4)
+            .operands_in(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_nzcv),
+            ])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Same for floating point and SIMD registers.
+    recipes.push(
+        EncodingRecipeBuilder::new("fR", f_binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // R-type with a single source register in Rn, e.g. `clz`, `rbit` and the extensions.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rn", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, in_reg0, 0, out_reg0, sink);"),
+    );
+
+    // Count trailing zeros as `rbit` followed by `clz`. The encoding bits are for the `clz`.
+    recipes.push(
+        EncodingRecipeBuilder::new("ctz", f_unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // rbit shares the opcode of clz, except for bit 12.
+                    put_rrr(bits & !(1 << 13), in_reg0, 0, out_reg0, sink);
+                    put_rrr(bits, out_reg0, 0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // R-type with a single source register in Rm and the zero register in Rn, e.g. `mov` and
+    // `mvn`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rm", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, 31, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same as Rm, but for a regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rmov", f_reg_move, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, 31, src, dst, sink);"),
+    );
+
+    // Same as Rm, but for a copy-to-SSA.
+    recipes.push(
+        EncodingRecipeBuilder::new("copytossa", f_copy_to_ssa, 4)
+            // No operands_in to mention, because a source register is specified directly.
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rrr(bits, 31, src, out_reg0, sink);"),
+    );
+
+    // Floating point data-processing (1 source), e.g. `fabs`, `fsqrt` and `fmov`.
+    recipes.push(
+        EncodingRecipeBuilder::new("fRR", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_rr(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same as fRR, but for a regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("fRmov", f_reg_move, 4)
+            .operands_in(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_rr(bits, src, dst, sink);"),
+    );
+
+    // Same as fRR, but for a copy-to-SSA.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcopytossa", f_copy_to_ssa, 4)
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_rr(bits, src, out_reg0, sink);"),
+    );
+
+    // Conversions and moves from a GPR to a FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("GtoF", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_rr(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Conversions and moves from a FPR to a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FtoG", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_rr(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // Add/subtract with a 12-bit unsigned immediate.
+    {
+        let format = formats.get(f_binary_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("Ri12", f_binary_imm, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 12, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_hi(bits, (imm as u32) << 10 | rn(in_reg0) | rd(out_reg0), sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Shift left by an immediate, as an alias of `ubfm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("lsl_imm", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let width = if bits & SF_BITS != 0 { 64 } else { 32 };
+                    let imm: i64 = imm.into();
+                    let shift = (imm as u32) & (width - 1);
+                    let immr = (width - shift) & (width - 1);
+                    let imms = width - 1 - shift;
+                    put_hi(bits, immr << 16 | imms << 10 | rn(in_reg0) | rd(out_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Shift right by an immediate, as an alias of `ubfm` or `sbfm`.
+    recipes.push(
+        EncodingRecipeBuilder::new("shr_imm", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let width = if bits & SF_BITS != 0 { 64 } else { 32 };
+                    let imm: i64 = imm.into();
+                    let shift = (imm as u32) & (width - 1);
+                    put_hi(bits, shift << 16 | (width - 1) << 10 | rn(in_reg0) | rd(out_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Rotate right by an immediate, as an alias of `extr`.
+    recipes.push(
+        EncodingRecipeBuilder::new("ror_imm", f_binary_imm, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let width = if bits & SF_BITS != 0 { 64 } else { 32 };
+                    let imm: i64 = imm.into();
+                    let shift = (imm as u32) & (width - 1);
+                    let rm = u32::from(in_reg0) & 0x1f;
+                    put_hi(bits, rm << 16 | shift << 10 | rn(in_reg0) | rd(out_reg0), sink);
+                "#,
+            ),
+    );
+
+    // Integer constants, materialized by a sequence of `movz`/`movn` and `movk` instructions.
+    recipes.push(
+        EncodingRecipeBuilder::new("iconst", f_unary_imm, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_iconst")
+            .emit("put_iconst(bits, imm.into(), out_reg0, sink);"),
+    );
+
+    // Boolean constants are 0 or 1 in a GPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("bconst", f_unary_bool, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, u32::from(imm) << 5 | rd(out_reg0), sink);"),
+    );
+
+    // Integer comparisons producing a boolean: `cmp` followed by `cset`.
+    recipes.push(
+        EncodingRecipeBuilder::new("icmp", f_int_compare, 8)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_rrr(bits, in_reg0, in_reg1, 31, sink);
+                    put_cset(icc2cond(cond), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    {
+        let format = formats.get(f_int_compare_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("icmp_imm", f_int_compare_imm, 8)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 12, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_hi(bits, (imm as u32) << 10 | rn(in_reg0) | 31, sink);
+                        put_cset(icc2cond(cond), out_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Floating point comparisons producing a boolean: `fcmp` followed by `cset`.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcmp", f_float_compare, 8)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_float_compare),
+            ))
+            .emit(
+                r#"
+                    put_rrr(bits, in_reg0, in_reg1, 0, sink);
+                    put_cset(fcc2cond(cond), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Comparisons producing CPU flags.
+    recipes.push(
+        EncodingRecipeBuilder::new("ifcmp", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![reg_nzcv])
+            .emit("put_rrr(bits, in_reg0, in_reg1, 31, sink);"),
+    );
+
+    {
+        let format = formats.get(f_binary_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("ifcmp_imm", f_binary_imm, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![reg_nzcv])
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 12, 0,
+                ))
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_hi(bits, (imm as u32) << 10 | rn(in_reg0) | 31, sink);
+                    "#,
+                ),
+        );
+    }
+
+    recipes.push(
+        EncodingRecipeBuilder::new("ffcmp", f_binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![reg_nzcv])
+            .emit("put_rrr(bits, in_reg0, in_reg1, 0, sink);"),
+    );
+
+    // Test flags and set a register to 0 or 1.
+    recipes.push(
+        EncodingRecipeBuilder::new("trueif", f_int_cond, 4)
+            .operands_in(vec![reg_nzcv])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_cset(icc2cond(cond), out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("trueff", f_float_cond, 4)
+            .operands_in(vec![reg_nzcv])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_float_cond),
+            ))
+            .emit("put_cset(fcc2cond(cond), out_reg0, sink);"),
+    );
+
+    // Conditional select using CPU flags.
+    recipes.push(
+        EncodingRecipeBuilder::new("selectif", f_int_select, 4)
+            .operands_in(vec![
+                OperandConstraint::FixedReg(reg_nzcv),
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::RegClass(gpr),
+            ])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_csel(bits, in_reg1, in_reg2, icc2cond(cond), out_reg0, sink);"),
+    );
+
+    // Loads with a scaled, unsigned 12-bit offset. The access size is in the top bits of the
+    // instruction, the offset predicate is added to the encodings.
+    recipes.push(
+        EncodingRecipeBuilder::new("ld", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_mem_scaled(bits, offset.into(), in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fld", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_mem_scaled(bits, offset.into(), in_reg0, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Loads with an unscaled, signed 9-bit offset.
+    {
+        let format = formats.get(f_load);
+        recipes.push(
+            EncodingRecipeBuilder::new("ldu", f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 9, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem_unscaled(bits, offset.into(), in_reg0, out_reg0, sink);
+                    "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new("fldu", f_load, 4)
+                .operands_in(vec![gpr])
+                .operands_out(vec![fpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 9, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem_unscaled(bits, offset.into(), in_reg0, out_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Stores with a scaled, unsigned 12-bit offset.
+    recipes.push(
+        EncodingRecipeBuilder::new("st", f_store, 4)
+            .operands_in(vec![gpr, gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_mem_scaled(bits, offset.into(), in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fst", f_store, 4)
+            .operands_in(vec![fpr, gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    put_mem_scaled(bits, offset.into(), in_reg1, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Stores with an unscaled, signed 9-bit offset.
+    {
+        let format = formats.get(f_store);
+        recipes.push(
+            EncodingRecipeBuilder::new("stu", f_store, 4)
+                .operands_in(vec![gpr, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 9, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem_unscaled(bits, offset.into(), in_reg1, in_reg0, sink);
+                    "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new("fstu", f_store, 4)
+                .operands_in(vec![fpr, gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_signed_int(
+                    format, "offset", 9, 0,
+                ))
+                .emit(
+                    r#"
+                        if !flags.notrap() {
+                            sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                        }
+                        put_mem_unscaled(bits, offset.into(), in_reg1, in_reg0, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Spills and fills, relative to the stack pointer. Offsets that don't fit in the scaled
+    // 12-bit immediate are computed in the scratch register %x16 first.
+    recipes.push(
+        EncodingRecipeBuilder::new("spill", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![stack_gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_stack_access(bits, out_stk0, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fspill", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![stack_fpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    put_stack_access(bits, out_stk0, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regspill", f_reg_spill, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    put_stack_access(bits, dst, src, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fregspill", f_reg_spill, 4)
+            .operands_in(vec![fpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let dst = StackRef::sp(dst, &func.stack_slots);
+                    put_stack_access(bits, dst, src, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fill", f_unary, 4)
+            .operands_in(vec![stack_gpr])
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit("put_stack_access(bits, in_stk0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("ffill", f_unary, 4)
+            .operands_in(vec![stack_fpr])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit("put_stack_access(bits, in_stk0, out_reg0, sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("regfill", f_reg_fill, 4)
+            .operands_in(vec![stack_gpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    put_stack_access(bits, src, dst, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fregfill", f_reg_fill, 4)
+            .operands_in(vec![stack_fpr])
+            .clobbers_flags(false)
+            .compute_size("size_for_stack_access")
+            .emit(
+                r#"
+                    let src = StackRef::sp(src, &func.stack_slots);
+                    put_stack_access(bits, src, dst, sink);
+                "#,
+            ),
+    );
+
+    // Address of a stack slot: `add rd, sp, #lo` followed by `add rd, rd, #hi, lsl #12`.
+    recipes.push(
+        EncodingRecipeBuilder::new("spaddr", f_stack_load, 8)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let sp = StackRef::sp(stack_slot, &func.stack_slots);
+                    let imm: i32 = offset.into();
+                    let offset = sp.offset.checked_add(imm).unwrap();
+                    debug_assert!(offset >= 0 && offset < (1 << 24));
+                    let offset = offset as u32;
+                    put_hi(bits, (offset & 0xfff) << 10 | rn(RU::x31.into()) | rd(out_reg0), sink);
+                    put_hi(
+                        bits | SH_BITS,
+                        (offset >> 12) << 10 | rn(out_reg0) | rd(out_reg0),
+                        sink,
+                    );
+                "#,
+            ),
+    );
+
+    // Stack pointer manipulations, used in the prologue and epilogues.
+    recipes.push(
+        EncodingRecipeBuilder::new("copysp", f_copy_special, 4)
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rn(src) | rd(dst), sink);"),
+    );
+
+    {
+        let format = formats.get(f_unary_imm);
+        recipes.push(
+            EncodingRecipeBuilder::new("adjustsp_imm12", f_unary_imm, 4)
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 12, 0,
+                ))
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        put_hi(bits, (imm as u32) << 10 | rn(RU::x31.into()) | rd(RU::x31.into()), sink);
+                    "#,
+                ),
+        );
+
+        recipes.push(
+            EncodingRecipeBuilder::new("adjustsp_imm24", f_unary_imm, 8)
+                .inst_predicate(InstructionPredicate::new_is_unsigned_int(
+                    format, "imm", 24, 0,
+                ))
+                .clobbers_flags(false)
+                .emit(
+                    r#"
+                        let imm: i64 = imm.into();
+                        let imm = imm as u32;
+                        let sp = rn(RU::x31.into()) | rd(RU::x31.into());
+                        put_hi(bits | SH_BITS, (imm >> 12) << 10 | sp, sink);
+                        put_hi(bits, (imm & 0xfff) << 10 | sp, sink);
+                    "#,
+                ),
+        );
+    }
+
+    // Pushes and pops of a pair of registers, with a pre-indexed store and a post-indexed load.
+    recipes.push(
+        EncodingRecipeBuilder::new("push_pair", f_binary, 4)
+            .operands_in(vec![gpr, gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rt2(in_reg1) | rn(RU::x31.into()) | rd(in_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpush_pair", f_binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rt2(in_reg1) | rn(RU::x31.into()) | rd(in_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("pop_pair", f_nullary, 4)
+            .operands_out(vec![gpr, gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rt2(out_reg1) | rn(RU::x31.into()) | rd(out_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpop_pair", f_nullary, 4)
+            .operands_out(vec![fpr, fpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rt2(out_reg1) | rn(RU::x31.into()) | rd(out_reg0), sink);"),
+    );
+
+    // Pushes and pops of a single register, keeping the stack pointer 16-byte aligned.
+    recipes.push(
+        EncodingRecipeBuilder::new("push", f_unary, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, 0xc00 | rn(RU::x31.into()) | rd(in_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpush", f_unary, 4)
+            .operands_in(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, 0xc00 | rn(RU::x31.into()) | rd(in_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("pop", f_nullary, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, 0x400 | rn(RU::x31.into()) | rd(out_reg0), sink);"),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("fpop", f_nullary, 4)
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, 0x400 | rn(RU::x31.into()) | rd(out_reg0), sink);"),
+    );
+
+    // Branches.
+    recipes.push(
+        EncodingRecipeBuilder::new("B", f_jump, 4)
+            .branch_range((0, 28))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_b(bits, disp, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Bcond", f_branch_int, 4)
+            .operands_in(vec![reg_nzcv])
+            .branch_range((0, 21))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_bcond(bits, disp, icc2cond(cond), sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("Bfcond", f_branch_float, 4)
+            .operands_in(vec![reg_nzcv])
+            .branch_range((0, 21))
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_branch_float),
+            ))
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_bcond(bits, disp, fcc2cond(cond), sink);
+                "#,
+            ),
+    );
+
+    // Compare a register with zero and branch: `cbz` and `cbnz`.
+    recipes.push(
+        EncodingRecipeBuilder::new("CBZ", f_branch, 4)
+            .operands_in(vec![gpr])
+            .branch_range((0, 21))
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    let dest = i64::from(func.offsets[destination]);
+                    let disp = dest - i64::from(sink.offset());
+                    put_cbz(bits, disp, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("indirect_br", f_indirect_jump, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rn(in_reg0), sink);"),
+    );
+
+    // Jump tables: `adr` of the table, which is emitted right after the function body, and a
+    // sign-extending load of the relative entry.
+    recipes.push(
+        EncodingRecipeBuilder::new("jt_base", f_branch_table_base, 4)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    // No reloc is needed here as the jump table is emitted directly after
+                    // the function body.
+                    let disp = i64::from(func.jt_offsets[table]) - i64::from(sink.offset());
+                    put_adr(bits, disp, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    {
+        let format = formats.get(f_branch_table_entry);
+        recipes.push(
+            EncodingRecipeBuilder::new("jt_entry", f_branch_table_entry, 4)
+                .operands_in(vec![gpr, gpr])
+                .operands_out(vec![gpr])
+                .clobbers_flags(false)
+                .inst_predicate(InstructionPredicate::new_is_field_equal(
+                    format,
+                    "imm",
+                    "4".into(),
+                ))
+                .emit("put_rrr(bits, in_reg1, in_reg0, out_reg0, sink);"),
+        );
+    }
+
+    // Calls and returns.
+    recipes.push(
+        EncodingRecipeBuilder::new("BL", f_call, 4)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.reloc_external(Reloc::Arm64Call,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    put_b(bits, 0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("BLR", f_call_indirect, 4)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rn(in_reg0), sink);"),
+    );
+
+    // Returns always branch to %x30, which is provided as a special-purpose link argument.
+    recipes.push(
+        EncodingRecipeBuilder::new("ret", f_multiary, 4)
+            .clobbers_flags(false)
+            .emit("put_hi(bits, rn(RU::x30.into()), sink);"),
+    );
+
+    // Absolute addresses are loaded from a literal that is branched over.
+    recipes.push(
+        EncodingRecipeBuilder::new("fnaddr", f_func_addr, 16)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_literal_load(out_reg0, sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.dfg.ext_funcs[func_ref].name,
+                                        0);
+                    sink.put8(0);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("gvaddr", f_unary_global_value, 16)
+            .operands_out(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_literal_load(out_reg0, sink);
+                    sink.reloc_external(Reloc::Abs8,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put8(0);
+                "#,
+            ),
+    );
+
+    // Traps.
+    recipes.push(
+        EncodingRecipeBuilder::new("udf", f_trap, 4)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    sink.trap(code, func.srclocs[inst]);
+                    put_hi(bits, 0, sink);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("brk", f_nullary, 4)
+            .clobbers_flags(false)
+            .emit("put_hi(bits, 0, sink);"),
+    );
+
+    // Conditional branch over a `udf`.
+    recipes.push(
+        EncodingRecipeBuilder::new("trapif", f_int_cond_trap, 8)
+            .operands_in(vec![reg_nzcv])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_bcond(bits, 8, icc2cond(cond.inverse()), sink);
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    recipes.push(
+        EncodingRecipeBuilder::new("trapff", f_float_cond_trap, 8)
+            .operands_in(vec![reg_nzcv])
+            .clobbers_flags(false)
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_floatccs,
+                formats.get(f_float_cond_trap),
+            ))
+            .emit(
+                r#"
+                    put_bcond(bits, 8, fcc2cond(cond.inverse()), sink);
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // Compare a register with zero and branch over a `udf`. The encoding bits are for the
+    // inverted test.
+    recipes.push(
+        EncodingRecipeBuilder::new("trapz", f_cond_trap, 8)
+            .operands_in(vec![gpr])
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    put_cbz(bits, 8, in_reg0, sink);
+                    sink.trap(code, func.srclocs[inst]);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    recipes
+}
@@ -7,7 +7,7 @@ use crate::cdsl::recipes::{EncodingRecipeNumber, Recipes};
 use crate::cdsl::settings::SettingGroup;
 
 use crate::shared::types::Bool::B1;
-use crate::shared::types::Float::{F32, F64};
+use crate::shared::types::Float::{self, F32, F64};
 use crate::shared::types::Int::{I16, I32, I64, I8};
 use crate::shared::types::Reference::{R32, R64};
 use crate::shared::Definitions as SharedDefinitions;
@@ -101,6 +101,35 @@ fn lui_bits() -> u16 {
     0b01101
 }
 
+fn load_fp_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b00001 | (funct3 << 5)
+}
+
+fn store_fp_bits(funct3: u16) -> u16 {
+    assert!(funct3 <= 0b111);
+    0b01001 | (funct3 << 5)
+}
+
+// The OP-FP instructions have funct7 = funct5 << 2 | fmt, where fmt is 0b00 for single and 0b01
+// for double precision. Their funct3 field holds the rounding mode for arithmetic, which is
+// always RNE (0b000), and selects the operation for sign injection and comparisons.
+fn opfp_bits(funct3: u16, funct5: u16, ty: Float) -> u16 {
+    assert!(funct3 <= 0b111);
+    assert!(funct5 <= 0b11111);
+    let fmt = match ty {
+        F32 => 0b00,
+        F64 => 0b01,
+    };
+    0b10100 | (funct3 << 5) | (((funct5 << 2) | fmt) << 8)
+}
+
+// OP-FP instructions with a fixed rs2 field, as used by the `Rff`, `Rgf` and `Rfg` recipes. The
+// rs2 field takes the place of funct3 in the encoding bits.
+fn opfp_unary_bits(rs2: u16, funct5: u16, ty: Float) -> u16 {
+    opfp_bits(rs2, funct5, ty)
+}
+
 pub(crate) fn define<'defs>(
     shared_defs: &'defs SharedDefinitions,
     isa_settings: &SettingGroup,
@@ -118,48 +147,93 @@ pub(crate) fn define<'defs>(
     let brnz = shared.by_name("brnz");
     let bxor = shared.by_name("bxor");
     let bxor_imm = shared.by_name("bxor_imm");
+    let bitcast = shared.by_name("bitcast");
     let call = shared.by_name("call");
     let call_indirect = shared.by_name("call_indirect");
     let copy = shared.by_name("copy");
     let copy_nop = shared.by_name("copy_nop");
     let copy_to_ssa = shared.by_name("copy_to_ssa");
+    let fabs = shared.by_name("fabs");
+    let fadd = shared.by_name("fadd");
+    let fcmp = shared.by_name("fcmp");
+    let fcopysign = shared.by_name("fcopysign");
+    let fcvt_from_sint = shared.by_name("fcvt_from_sint");
+    let fcvt_from_uint = shared.by_name("fcvt_from_uint");
+    let fdemote = shared.by_name("fdemote");
+    let fdiv = shared.by_name("fdiv");
     let fill = shared.by_name("fill");
     let fill_nop = shared.by_name("fill_nop");
+    let fmul = shared.by_name("fmul");
+    let fneg = shared.by_name("fneg");
+    let fpromote = shared.by_name("fpromote");
+    let fsub = shared.by_name("fsub");
     let iadd = shared.by_name("iadd");
     let iadd_imm = shared.by_name("iadd_imm");
     let iconst = shared.by_name("iconst");
     let icmp = shared.by_name("icmp");
     let icmp_imm = shared.by_name("icmp_imm");
     let imul = shared.by_name("imul");
+    let ireduce = shared.by_name("ireduce");
     let ishl = shared.by_name("ishl");
     let ishl_imm = shared.by_name("ishl_imm");
+    let istore16 = shared.by_name("istore16");
+    let istore32 = shared.by_name("istore32");
+    let istore8 = shared.by_name("istore8");
     let isub = shared.by_name("isub");
     let jump = shared.by_name("jump");
+    let load = shared.by_name("load");
     let regmove = shared.by_name("regmove");
+    let sextend = shared.by_name("sextend");
+    let sload16 = shared.by_name("sload16");
+    let sload32 = shared.by_name("sload32");
+    let sload8 = shared.by_name("sload8");
     let spill = shared.by_name("spill");
+    let sqrt = shared.by_name("sqrt");
     let sshr = shared.by_name("sshr");
     let sshr_imm = shared.by_name("sshr_imm");
+    let store = shared.by_name("store");
+    let uextend = shared.by_name("uextend");
+    let uload16 = shared.by_name("uload16");
+    let uload32 = shared.by_name("uload32");
+    let uload8 = shared.by_name("uload8");
     let ushr = shared.by_name("ushr");
     let ushr_imm = shared.by_name("ushr_imm");
     let return_ = shared.by_name("return");
 
     // Recipes shorthands, prefixed with r_.
     let r_copytossa = recipes.by_name("copytossa");
+    let r_fcopytossa = recipes.by_name("fcopytossa");
+    let r_ffillnull = recipes.by_name("ffillnull");
     let r_fillnull = recipes.by_name("fillnull");
+    let r_fload = recipes.by_name("Fload");
+    let r_fp_fi = recipes.by_name("FPfi");
+    let r_fp_sp = recipes.by_name("FPsp");
+    let r_frmov = recipes.by_name("Frmov");
+    let r_fstore = recipes.by_name("Fstore");
     let r_icall = recipes.by_name("Icall");
     let r_icopy = recipes.by_name("Icopy");
     let r_ii = recipes.by_name("Ii");
     let r_iicmp = recipes.by_name("Iicmp");
     let r_iret = recipes.by_name("Iret");
+    let r_iload = recipes.by_name("Iload");
     let r_irmov = recipes.by_name("Irmov");
+    let r_iuext32 = recipes.by_name("Iuext32");
     let r_iz = recipes.by_name("Iz");
     let r_gp_sp = recipes.by_name("GPsp");
     let r_gp_fi = recipes.by_name("GPfi");
     let r_r = recipes.by_name("R");
+    let r_rf = recipes.by_name("Rf");
+    let r_rfcmp = recipes.by_name("Rfcmp");
+    let r_rfcmpr = recipes.by_name("Rfcmpr");
+    let r_rff = recipes.by_name("Rff");
+    let r_rfg = recipes.by_name("Rfg");
+    let r_rfsgnj = recipes.by_name("Rfsgnj");
+    let r_rgf = recipes.by_name("Rgf");
     let r_ricmp = recipes.by_name("Ricmp");
     let r_rshamt = recipes.by_name("Rshamt");
     let r_sb = recipes.by_name("SB");
     let r_sb_zero = recipes.by_name("SBzero");
+    let r_sstore = recipes.by_name("Sstore");
     let r_stacknull = recipes.by_name("stacknull");
     let r_u = recipes.by_name("U");
     let r_uj = recipes.by_name("UJ");
@@ -167,6 +241,8 @@ pub(crate) fn define<'defs>(
 
     // Predicates shorthands.
     let use_m = isa_settings.predicate_by_name("use_m");
+    let use_f = isa_settings.predicate_by_name("use_f");
+    let use_d = isa_settings.predicate_by_name("use_d");
 
     // Definitions.
     let mut e = PerCpuModeEncodings::new(&recipes.recipes);
@@ -292,6 +368,164 @@ pub(crate) fn define<'defs>(
     e.add64(enc(imul.bind(I64), r_r, op_bits(0b000, 0b00000001)).isa_predicate(use_m));
     e.add64(enc(imul.bind(I32), r_r, op32_bits(0b000, 0b00000001)).isa_predicate(use_m));
 
+    // Loads and stores. The address operand is a pointer-sized integer.
+    for &(inst, f3) in &[
+        (load, 0b010),
+        (sload8, 0b000),
+        (uload8, 0b100),
+        (sload16, 0b001),
+        (uload16, 0b101),
+    ] {
+        e.add32(enc(inst.bind(I32).bind_any(), r_iload, load_bits(f3)));
+        e.add64(enc(inst.bind(I32).bind_any(), r_iload, load_bits(f3)));
+    }
+    for &(inst, f3) in &[
+        (load, 0b011),
+        (sload8, 0b000),
+        (uload8, 0b100),
+        (sload16, 0b001),
+        (uload16, 0b101),
+    ] {
+        e.add64(enc(inst.bind(I64).bind_any(), r_iload, load_bits(f3)));
+    }
+    e.add64(enc(sload32.bind(I64), r_iload, load_bits(0b010)));
+    e.add64(enc(uload32.bind(I64), r_iload, load_bits(0b110)));
+    for &(inst, f3) in &[(store, 0b010), (istore8, 0b000), (istore16, 0b001)] {
+        e.add32(enc(inst.bind(I32).bind_any(), r_sstore, store_bits(f3)));
+        e.add64(enc(inst.bind(I32).bind_any(), r_sstore, store_bits(f3)));
+    }
+    for &(inst, f3) in &[
+        (store, 0b011),
+        (istore8, 0b000),
+        (istore16, 0b001),
+        (istore32, 0b010),
+    ] {
+        e.add64(enc(inst.bind(I64).bind_any(), r_sstore, store_bits(f3)));
+    }
+
+    // Extensions and reductions between 32 and 64 bits in RV64. A 32-bit value is always kept
+    // sign-extended in its 64-bit register, which is what `addiw rd, rs, 0` produces.
+    e.add64(enc(
+        sextend.bind(I64).bind(I32),
+        r_icopy,
+        opimm32_bits(0b000, 0),
+    ));
+    e.add64(enc(
+        ireduce.bind(I32).bind(I64),
+        r_icopy,
+        opimm32_bits(0b000, 0),
+    ));
+    e.add64(enc(
+        uextend.bind(I64).bind(I32),
+        r_iuext32,
+        opimm_bits(0b001, 0),
+    ));
+
+    // "F" and "D" Standard Extensions for Single- and Double-Precision Floating-Point.
+    // Gated by the `use_f` and `use_d` flags.
+    for &(ty, use_fd, f3) in &[(F32, use_f, 0b010), (F64, use_d, 0b011)] {
+        e.add32(enc(load.bind(ty).bind_any(), r_fload, load_fp_bits(f3)).isa_predicate(use_fd));
+        e.add64(enc(load.bind(ty).bind_any(), r_fload, load_fp_bits(f3)).isa_predicate(use_fd));
+        e.add32(enc(store.bind(ty).bind_any(), r_fstore, store_fp_bits(f3)).isa_predicate(use_fd));
+        e.add64(enc(store.bind(ty).bind_any(), r_fstore, store_fp_bits(f3)).isa_predicate(use_fd));
+
+        for &(inst, f3, f5) in &[
+            (fadd, 0b000, 0b00000),
+            (fsub, 0b000, 0b00001),
+            (fmul, 0b000, 0b00010),
+            (fdiv, 0b000, 0b00011),
+            (fcopysign, 0b000, 0b00100),
+        ] {
+            e.add32(enc(inst.bind(ty), r_rf, opfp_bits(f3, f5, ty)).isa_predicate(use_fd));
+            e.add64(enc(inst.bind(ty), r_rf, opfp_bits(f3, f5, ty)).isa_predicate(use_fd));
+        }
+
+        // `fsgnjn` and `fsgnjx` with both sources the same compute `fneg` and `fabs`.
+        for &(inst, f3) in &[(fneg, 0b001), (fabs, 0b010)] {
+            let bits = opfp_bits(f3, 0b00100, ty);
+            e.add32(enc(inst.bind(ty), r_rfsgnj, bits).isa_predicate(use_fd));
+            e.add64(enc(inst.bind(ty), r_rfsgnj, bits).isa_predicate(use_fd));
+        }
+
+        let bits = opfp_unary_bits(0, 0b01011, ty);
+        e.add32(enc(sqrt.bind(ty), r_rff, bits).isa_predicate(use_fd));
+        e.add64(enc(sqrt.bind(ty), r_rff, bits).isa_predicate(use_fd));
+
+        // Integer to floating point conversions. The 64-bit integer variants only exist in RV64.
+        for &(inst, rs2_32, rs2_64) in &[(fcvt_from_sint, 0, 2), (fcvt_from_uint, 1, 3)] {
+            let bits = opfp_unary_bits(rs2_32, 0b11010, ty);
+            e.add32(enc(inst.bind(ty).bind(I32), r_rgf, bits).isa_predicate(use_fd));
+            e.add64(enc(inst.bind(ty).bind(I32), r_rgf, bits).isa_predicate(use_fd));
+            let bits = opfp_unary_bits(rs2_64, 0b11010, ty);
+            e.add64(enc(inst.bind(ty).bind(I64), r_rgf, bits).isa_predicate(use_fd));
+        }
+    }
+
+    // Conversions between single and double precision.
+    let bits = opfp_unary_bits(0, 0b01000, F64);
+    e.add32(enc(fpromote.bind(F64).bind(F32), r_rff, bits).isa_predicate(use_d));
+    e.add64(enc(fpromote.bind(F64).bind(F32), r_rff, bits).isa_predicate(use_d));
+    let bits = opfp_unary_bits(1, 0b01000, F32);
+    e.add32(enc(fdemote.bind(F32).bind(F64), r_rff, bits).isa_predicate(use_d));
+    e.add64(enc(fdemote.bind(F32).bind(F64), r_rff, bits).isa_predicate(use_d));
+
+    // Moves of the raw bits between the register banks: `fmv.x.w`, `fmv.w.x`, and their 64-bit
+    // counterparts `fmv.x.d` and `fmv.d.x` in RV64.
+    let bits = opfp_unary_bits(0, 0b11100, F32);
+    e.add32(enc(bitcast.bind(I32).bind(F32), r_rfg, bits).isa_predicate(use_f));
+    e.add64(enc(bitcast.bind(I32).bind(F32), r_rfg, bits).isa_predicate(use_f));
+    let bits = opfp_unary_bits(0, 0b11110, F32);
+    e.add32(enc(bitcast.bind(F32).bind(I32), r_rgf, bits).isa_predicate(use_f));
+    e.add64(enc(bitcast.bind(F32).bind(I32), r_rgf, bits).isa_predicate(use_f));
+    let bits = opfp_unary_bits(0, 0b11100, F64);
+    e.add64(enc(bitcast.bind(I64).bind(F64), r_rfg, bits).isa_predicate(use_d));
+    let bits = opfp_unary_bits(0, 0b11110, F64);
+    e.add64(enc(bitcast.bind(F64).bind(I64), r_rgf, bits).isa_predicate(use_d));
+
+    // Floating point comparisons. Only the ordered `eq`, `lt` and `le` exist as instructions;
+    // `gt` and `ge` swap the operands. The remaining condition codes have no encoding.
+    {
+        let mut var_pool = VarPool::new();
+
+        // Helper that creates an instruction predicate for an instruction in the fcmp family.
+        let mut fcmp_instp = |bound_inst: &BoundInstruction,
+                              floatcc_field: &'static str|
+         -> InstructionPredicateNode {
+            let x = var_pool.create("x");
+            let y = var_pool.create("y");
+            let cc = Literal::enumerator_for(&shared_defs.imm.floatcc, floatcc_field);
+            Apply::new(
+                bound_inst.clone().into(),
+                vec![Expr::Literal(cc), Expr::Var(x), Expr::Var(y)],
+            )
+            .inst_predicate(&shared_defs.format_registry, &var_pool)
+            .unwrap()
+        };
+
+        for &(ty, use_fd) in &[(F32, use_f), (F64, use_d)] {
+            let fcmp_ty = fcmp.bind(ty);
+            for &(cond, recipe, f3) in &[
+                ("eq", r_rfcmp, 0b010),
+                ("lt", r_rfcmp, 0b001),
+                ("le", r_rfcmp, 0b000),
+                ("gt", r_rfcmpr, 0b001),
+                ("ge", r_rfcmpr, 0b000),
+            ] {
+                let bits = opfp_bits(f3, 0b10100, ty);
+                e.add32(
+                    enc(fcmp_ty.clone(), recipe, bits)
+                        .inst_predicate(fcmp_instp(&fcmp_ty, cond))
+                        .isa_predicate(use_fd),
+                );
+                e.add64(
+                    enc(fcmp_ty.clone(), recipe, bits)
+                        .inst_predicate(fcmp_instp(&fcmp_ty, cond))
+                        .isa_predicate(use_fd),
+                );
+            }
+        }
+    }
+
     // Control flow.
 
     // Unconditional branches.
@@ -371,6 +605,15 @@ pub(crate) fn define<'defs>(
     e.add64(enc(fill.bind(I32), r_gp_fi, load_bits(0b010)));
     e.add64(enc(fill.bind(I64), r_gp_fi, load_bits(0b011)));
 
+    e.add32(enc(spill.bind(F32), r_fp_sp, store_fp_bits(0b010)).isa_predicate(use_f));
+    e.add64(enc(spill.bind(F32), r_fp_sp, store_fp_bits(0b010)).isa_predicate(use_f));
+    e.add32(enc(spill.bind(F64), r_fp_sp, store_fp_bits(0b011)).isa_predicate(use_d));
+    e.add64(enc(spill.bind(F64), r_fp_sp, store_fp_bits(0b011)).isa_predicate(use_d));
+    e.add32(enc(fill.bind(F32), r_fp_fi, load_fp_bits(0b010)).isa_predicate(use_f));
+    e.add64(enc(fill.bind(F32), r_fp_fi, load_fp_bits(0b010)).isa_predicate(use_f));
+    e.add32(enc(fill.bind(F64), r_fp_fi, load_fp_bits(0b011)).isa_predicate(use_d));
+    e.add64(enc(fill.bind(F64), r_fp_fi, load_fp_bits(0b011)).isa_predicate(use_d));
+
     // No-op fills, created by late-stage redundant-fill removal.
     for &ty in &[I64, I32] {
         e.add64(enc(fill_nop.bind(ty), r_fillnull, 0));
@@ -378,6 +621,10 @@ pub(crate) fn define<'defs>(
     }
     e.add64(enc(fill_nop.bind(B1), r_fillnull, 0));
     e.add32(enc(fill_nop.bind(B1), r_fillnull, 0));
+    for &ty in &[F64, F32] {
+        e.add64(enc(fill_nop.bind(ty), r_ffillnull, 0));
+        e.add32(enc(fill_nop.bind(ty), r_ffillnull, 0));
+    }
 
     // Register copies.
     e.add32(enc(copy.bind(I32), r_icopy, opimm_bits(0b000, 0)));
@@ -393,6 +640,17 @@ pub(crate) fn define<'defs>(
     e.add32(enc(regmove.bind(B1), r_irmov, opimm_bits(0b000, 0)));
     e.add64(enc(regmove.bind(B1), r_irmov, opimm_bits(0b000, 0)));
 
+    // FPR copies are `fsgnj rd, rs, rs`.
+    for &(ty, use_fd) in &[(F32, use_f), (F64, use_d)] {
+        let bits = opfp_bits(0b000, 0b00100, ty);
+        e.add32(enc(copy.bind(ty), r_rfsgnj, bits).isa_predicate(use_fd));
+        e.add64(enc(copy.bind(ty), r_rfsgnj, bits).isa_predicate(use_fd));
+        e.add32(enc(regmove.bind(ty), r_frmov, bits).isa_predicate(use_fd));
+        e.add64(enc(regmove.bind(ty), r_frmov, bits).isa_predicate(use_fd));
+        e.add32(enc(copy_to_ssa.bind(ty), r_fcopytossa, bits).isa_predicate(use_fd));
+        e.add64(enc(copy_to_ssa.bind(ty), r_fcopytossa, bits).isa_predicate(use_fd));
+    }
+
     // Stack-slot-to-the-same-stack-slot copy, which is guaranteed to turn
     // into a no-op.
     // The same encoding is generated for both the 64- and 32-bit architectures.
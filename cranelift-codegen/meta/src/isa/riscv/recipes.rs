@@ -64,16 +64,20 @@ pub(crate) fn define<'formats>(
     let f_call = formats.by_name("Call");
     let f_call_indirect = formats.by_name("CallIndirect");
     let f_copy_to_ssa = formats.by_name("CopyToSsa");
+    let f_float_compare = formats.by_name("FloatCompare");
     let f_int_compare = formats.by_name("IntCompare");
     let f_int_compare_imm = formats.by_name("IntCompareImm");
     let f_jump = formats.by_name("Jump");
+    let f_load = formats.by_name("Load");
     let f_multiary = formats.by_name("MultiAry");
     let f_regmove = formats.by_name("RegMove");
+    let f_store = formats.by_name("Store");
     let f_unary = formats.by_name("Unary");
     let f_unary_imm = formats.by_name("UnaryImm");
 
     // Register classes shorthands.
     let gpr = regs.class_by_name("GPR");
+    let fpr = regs.class_by_name("FPR");
 
     // Definitions.
     let mut recipes = RecipeGroup::new(&shared_defs.format_registry);
@@ -256,12 +260,95 @@ pub(crate) fn define<'formats>(
             ),
     );
 
-    // Spill of a GPR.
+    // I-type loads. The address is `rs1 + offset`.
+    let format = formats.get(f_load);
+    recipes.push(
+        EncodingRecipeBuilder::new("Iload", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    let offset: i32 = offset.into();
+                    put_i(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Same for a load into an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Fload", f_load, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    let offset: i32 = offset.into();
+                    put_i(bits, in_reg0, offset.into(), out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // S-type stores. The stored value is the first operand and the address is the second.
+    let format = formats.get(f_store);
+    recipes.push(
+        EncodingRecipeBuilder::new("Sstore", f_store, 4)
+            .operands_in(vec![gpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    let offset: i32 = offset.into();
+                    put_s(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Same for a store of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("Fstore", f_store, 4)
+            .operands_in(vec![fpr, gpr])
+            .inst_predicate(InstructionPredicate::new_is_signed_int(
+                format, "offset", 12, 0,
+            ))
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    let offset: i32 = offset.into();
+                    put_s(bits, in_reg1, offset.into(), in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Spill of a GPR. Stack slots are addressed with a 12-bit offset from the stack pointer.
     recipes.push(
         EncodingRecipeBuilder::new("GPsp", f_unary, 4)
             .operands_in(vec![gpr])
             .operands_out(vec![Stack::new(gpr)])
-            .emit("unimplemented!();"),
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let offset = i64::from(out_stk0.offset);
+                    debug_assert!(is_signed_int(offset, 12, 0), "spill offset {}", offset);
+                    put_s(bits, stk_base(out_stk0.base), offset, in_reg0, sink);
+                "#,
+            ),
     );
 
     // Fill of a GPR.
@@ -269,7 +356,127 @@ pub(crate) fn define<'formats>(
         EncodingRecipeBuilder::new("GPfi", f_unary, 4)
             .operands_in(vec![Stack::new(gpr)])
             .operands_out(vec![gpr])
-            .emit("unimplemented!();"),
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let offset = i64::from(in_stk0.offset);
+                    debug_assert!(is_signed_int(offset, 12, 0), "fill offset {}", offset);
+                    put_i(bits, stk_base(in_stk0.base), offset, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Spill of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FPsp", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![Stack::new(fpr)])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let offset = i64::from(out_stk0.offset);
+                    debug_assert!(is_signed_int(offset, 12, 0), "spill offset {}", offset);
+                    put_s(bits, stk_base(out_stk0.base), offset, in_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Fill of an FPR.
+    recipes.push(
+        EncodingRecipeBuilder::new("FPfi", f_unary, 4)
+            .operands_in(vec![Stack::new(fpr)])
+            .operands_out(vec![fpr])
+            .emit(
+                r#"
+                    sink.trap(TrapCode::StackOverflow, func.srclocs[inst]);
+                    let offset = i64::from(in_stk0.offset);
+                    debug_assert!(is_signed_int(offset, 12, 0), "fill offset {}", offset);
+                    put_i(bits, stk_base(in_stk0.base), offset, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // Zero-extension of the low 32 bits of a register, as `slli rd, rs, 32; srli rd, rd, 32`.
+    // The encoding bits are those of the `slli`; the `srli` only differs in funct3.
+    recipes.push(
+        EncodingRecipeBuilder::new("Iuext32", f_unary, 8)
+            .operands_in(vec![gpr])
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    put_rshamt(bits, in_reg0, 32, out_reg0, sink);
+                    put_rshamt(bits | (0b100 << 5), out_reg0, 32, out_reg0, sink);
+                "#,
+            ),
+    );
+
+    // R-type floating point binary arithmetic. The funct3 field holds the rounding mode.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rf", f_binary, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // R-type sign injection with both sources set to the same register. This implements copies,
+    // `fneg` and `fabs` with `fsgnj`, `fsgnjn` and `fsgnjx` respectively.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfsgnj", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, in_reg0, in_reg0, out_reg0, sink);"),
+    );
+
+    // Same for an FPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("Frmov", f_regmove, 4)
+            .operands_in(vec![fpr])
+            .emit("put_r(bits, src, src, dst, sink);"),
+    );
+
+    // Same for copy-to-SSA -- FPR regmove.
+    recipes.push(
+        EncodingRecipeBuilder::new("fcopytossa", f_copy_to_ssa, 4)
+            // No operands_in to mention, because a source register is specified directly.
+            .operands_out(vec![fpr])
+            .emit("put_r(bits, src, src, out_reg0, sink);"),
+    );
+
+    // R-type unary operations with a fixed rs2, such as conversions and moves between register
+    // banks. The encoding bits are `opcode[6:2] | (rs2 << 5) | (funct7 << 8)`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rff", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![fpr])
+            .emit("put_r_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+    recipes.push(
+        EncodingRecipeBuilder::new("Rgf", f_unary, 4)
+            .operands_in(vec![gpr])
+            .operands_out(vec![fpr])
+            .emit("put_r_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfg", f_unary, 4)
+            .operands_in(vec![fpr])
+            .operands_out(vec![gpr])
+            .emit("put_r_unary(bits, in_reg0, out_reg0, sink);"),
+    );
+
+    // R-type encoding of a floating point comparison.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcmp", f_float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg0, in_reg1, out_reg0, sink);"),
+    );
+
+    // Same with swapped operands, so `flt` and `fle` can implement `gt` and `ge`.
+    recipes.push(
+        EncodingRecipeBuilder::new("Rfcmpr", f_float_compare, 4)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![gpr])
+            .emit("put_r(bits, in_reg1, in_reg0, out_reg0, sink);"),
     );
 
     // Stack-slot to same stack-slot copy, which is guaranteed to turn into a no-op.
@@ -288,6 +495,13 @@ pub(crate) fn define<'formats>(
             .clobbers_flags(false)
             .emit(""),
     );
+    recipes.push(
+        EncodingRecipeBuilder::new("ffillnull", f_unary, 0)
+            .operands_in(vec![Stack::new(fpr)])
+            .operands_out(vec![fpr])
+            .clobbers_flags(false)
+            .emit(""),
+    );
 
     recipes
 }
@@ -2,6 +2,7 @@
 
 use std::collections::HashMap;
 
+use crate::cdsl::ast::Literal;
 use crate::cdsl::encodings::{Encoding, EncodingBuilder};
 use crate::cdsl::instructions::{
     InstSpec, Instruction, InstructionGroup, InstructionPredicate, InstructionPredicateNode,
@@ -186,6 +187,22 @@ impl PerCpuModeEncodings {
         });
     }
 
+    /// Add encodings for `inst.r32` to X86_32.
+    /// Add encodings for `inst.r64` to X86_64 with a REX.W prefix.
+    fn enc_r32_r64_instp(
+        &mut self,
+        inst: &Instruction,
+        template: Template,
+        instp: InstructionPredicateNode,
+    ) {
+        self.enc32_func(inst.bind_ref(R32), template.nonrex(), |builder| {
+            builder.inst_predicate(instp.clone())
+        });
+        self.enc64_func(inst.bind_ref(R64), template.rex().w(), |builder| {
+            builder.inst_predicate(instp)
+        });
+    }
+
     /// Add encodings for `inst.r32` to X86_32.
     /// Add encodings for `inst.r64` to X86_64 with a REX.W prefix.
     fn enc_r32_r64_rex_only(&mut self, inst: impl Into<InstSpec>, template: Template) {
@@ -279,6 +296,13 @@ impl PerCpuModeEncodings {
         }
     }
 
+    /// Add encodings for `inst.r32` to X86_32.
+    /// Add encodings for `inst.r64` to X86_64 with a REX.W prefix.
+    fn enc_r32_r64_ld_st(&mut self, inst: &Instruction, template: Template) {
+        self.enc32(inst.clone().bind_ref(R32).bind_any(), template.clone());
+        self.enc64(inst.clone().bind_ref(R64).bind_any(), template.rex().w());
+    }
+
     /// Add the same encoding/recipe pairing to both X86_32 and X86_64
     fn enc_32_64_rec(
         &mut self,
@@ -342,6 +366,9 @@ pub(crate) fn define(
     let adjust_sp_down = shared.by_name("adjust_sp_down");
     let adjust_sp_down_imm = shared.by_name("adjust_sp_down_imm");
     let adjust_sp_up_imm = shared.by_name("adjust_sp_up_imm");
+    let atomic_cas = shared.by_name("atomic_cas");
+    let atomic_load = shared.by_name("atomic_load");
+    let atomic_rmw = shared.by_name("atomic_rmw");
     let band = shared.by_name("band");
     let band_imm = shared.by_name("band_imm");
     let band_not = shared.by_name("band_not");
@@ -374,6 +401,7 @@ pub(crate) fn define(
     let fcvt_from_sint = shared.by_name("fcvt_from_sint");
     let fdemote = shared.by_name("fdemote");
     let fdiv = shared.by_name("fdiv");
+    let fence = shared.by_name("fence");
     let ffcmp = shared.by_name("ffcmp");
     let fill = shared.by_name("fill");
     let fill_nop = shared.by_name("fill_nop");
@@ -446,6 +474,7 @@ pub(crate) fn define(
     let store = shared.by_name("store");
     let store_complex = shared.by_name("store_complex");
     let symbol_value = shared.by_name("symbol_value");
+    let tls_value = shared.by_name("tls_value");
     let trap = shared.by_name("trap");
     let trapff = shared.by_name("trapff");
     let trapif = shared.by_name("trapif");
@@ -461,11 +490,13 @@ pub(crate) fn define(
     let uload8 = shared.by_name("uload8");
     let uload8_complex = shared.by_name("uload8_complex");
     let ushr = shared.by_name("ushr");
+    let uadd_sat = shared.by_name("uadd_sat");
     let ushr_imm = shared.by_name("ushr_imm");
     let vconst = shared.by_name("vconst");
     let x86_bsf = x86.by_name("x86_bsf");
     let x86_bsr = x86.by_name("x86_bsr");
     let x86_cvtt2si = x86.by_name("x86_cvtt2si");
+    let x86_elf_tls_get_addr = x86.by_name("x86_elf_tls_get_addr");
     let x86_fmax = x86.by_name("x86_fmax");
     let x86_fmin = x86.by_name("x86_fmin");
     let x86_insertps = x86.by_name("x86_insertps");
@@ -488,6 +519,10 @@ pub(crate) fn define(
     let rec_adjustsp_id = r.template("adjustsp_id");
     let rec_allones_fnaddr4 = r.template("allones_fnaddr4");
     let rec_allones_fnaddr8 = r.template("allones_fnaddr8");
+    let rec_atomic_cas = r.template("atomic_cas");
+    let rec_atomic_cas_abcd = r.template("atomic_cas_abcd");
+    let rec_atomic_rmw = r.template("atomic_rmw");
+    let rec_atomic_rmw_abcd = r.template("atomic_rmw_abcd");
     let rec_brfb = r.template("brfb");
     let rec_brfd = r.template("brfd");
     let rec_brib = r.template("brib");
@@ -500,6 +535,7 @@ pub(crate) fn define(
     let rec_copysp = r.template("copysp");
     let rec_div = r.template("div");
     let rec_debugtrap = r.recipe("debugtrap");
+    let rec_f_ib = r.template("f_ib");
     let rec_f32imm_z = r.template("f32imm_z");
     let rec_f64imm_z = r.template("f64imm_z");
     let rec_fa = r.template("fa");
@@ -507,6 +543,7 @@ pub(crate) fn define(
     let rec_fa_ib = r.template("fa_ib");
     let rec_fcmp = r.template("fcmp");
     let rec_fcscc = r.template("fcscc");
+    let rec_fence = r.template("fence");
     let rec_ffillnull = r.recipe("ffillnull");
     let rec_ffillSib32 = r.template("ffillSib32");
     let rec_fillnull = r.recipe("fillnull");
@@ -533,6 +570,8 @@ pub(crate) fn define(
     let rec_furm = r.template("furm");
     let rec_furm_reg_to_ssa = r.template("furm_reg_to_ssa");
     let rec_furmi_rnd = r.template("furmi_rnd");
+    let rec_elf_tls_get_addr = r.recipe("elf_tls_get_addr");
+    let rec_elf_tls_le = r.template("elf_tls_le");
     let rec_get_pinned_reg = r.recipe("get_pinned_reg");
     let rec_got_fnaddr8 = r.template("got_fnaddr8");
     let rec_got_gvaddr8 = r.template("got_gvaddr8");
@@ -557,6 +596,7 @@ pub(crate) fn define(
     let rec_null = r.recipe("null");
     let rec_null_fpr = r.recipe("null_fpr");
     let rec_pcrel_fnaddr8 = r.template("pcrel_fnaddr8");
+    let rec_pfcmp = r.template("pfcmp");
     let rec_pcrel_gvaddr8 = r.template("pcrel_gvaddr8");
     let rec_popq = r.template("popq");
     let rec_pu_id = r.template("pu_id");
@@ -861,6 +901,11 @@ pub(crate) fn define(
             recipe.opcodes(vec![0x8b]),
             is_load_complex_length_two.clone(),
         );
+        e.enc_r32_r64_instp(
+            load_complex,
+            recipe.opcodes(vec![0x8b]),
+            is_load_complex_length_two.clone(),
+        );
         e.enc_x86_64_instp(
             uload32_complex,
             recipe.opcodes(vec![0x8b]),
@@ -906,6 +951,11 @@ pub(crate) fn define(
             recipe.opcodes(vec![0x89]),
             is_store_complex_length_three.clone(),
         );
+        e.enc_r32_r64_instp(
+            store_complex,
+            recipe.opcodes(vec![0x89]),
+            is_store_complex_length_three.clone(),
+        );
         e.enc_x86_64_instp(
             istore32_complex,
             recipe.opcodes(vec![0x89]),
@@ -942,6 +992,7 @@ pub(crate) fn define(
 
     for recipe in &[rec_st, rec_stDisp8, rec_stDisp32] {
         e.enc_i32_i64_ld_st(store, true, recipe.opcodes(vec![0x89]));
+        e.enc_r32_r64_ld_st(store, recipe.opcodes(vec![0x89]));
         e.enc_x86_64(istore32.bind(I64).bind_any(), recipe.opcodes(vec![0x89]));
         e.enc_i32_i64_ld_st(istore16, false, recipe.opcodes(vec![0x66, 0x89]));
     }
@@ -973,6 +1024,7 @@ pub(crate) fn define(
 
     for recipe in &[rec_ld, rec_ldDisp8, rec_ldDisp32] {
         e.enc_i32_i64_ld_st(load, true, recipe.opcodes(vec![0x8b]));
+        e.enc_r32_r64_ld_st(load, recipe.opcodes(vec![0x8b]));
         e.enc_x86_64(uload32.bind(I64), recipe.opcodes(vec![0x8b]));
         e.enc64(sload32.bind(I64), recipe.opcodes(vec![0x63]).rex().w());
         e.enc_i32_i64_ld_st(uload16, true, recipe.opcodes(vec![0x0f, 0xb7]));
@@ -981,6 +1033,79 @@ pub(crate) fn define(
         e.enc_i32_i64_ld_st(sload8, true, recipe.opcodes(vec![0x0f, 0xbe]));
     }
 
+    // Atomic memory accesses.
+    //
+    // Loads are plain `mov` instructions because `atomic_store` is followed by a fence. The
+    // other read-modify-write operations are legalized into `lock xadd` or a `lock cmpxchg` loop.
+    for recipe in &[rec_ld, rec_ldDisp8, rec_ldDisp32] {
+        e.enc_i32_i64_ld_st(atomic_load, true, recipe.opcodes(vec![0x8b]));
+        e.enc_both(
+            atomic_load.bind(I16).bind_any(),
+            recipe.opcodes(vec![0x0f, 0xb7]),
+        );
+        e.enc_both(
+            atomic_load.bind(I8).bind_any(),
+            recipe.opcodes(vec![0x0f, 0xb6]),
+        );
+    }
+
+    let f_atomic_rmw = formats.get(formats.by_name("AtomicRmw"));
+    let imm = &shared_defs.imm;
+    for &(op, opcodes, opcodes_byte) in &[
+        ("add", &[0x0f, 0xc1][..], &[0x0f, 0xc0][..]),
+        ("xchg", &[0x87][..], &[0x86][..]),
+    ] {
+        let is_op = InstructionPredicate::new_is_field_equal(
+            f_atomic_rmw,
+            "op",
+            Literal::enumerator_for(&imm.atomic_rmw_op, op).to_rust_code(),
+        );
+        let mut opcodes_word = vec![0x66];
+        opcodes_word.extend(opcodes);
+
+        e.enc_both_instp(
+            atomic_rmw.bind(I32).bind_any(),
+            rec_atomic_rmw.opcodes(opcodes.to_vec()),
+            is_op.clone(),
+        );
+        e.enc64_instp(
+            atomic_rmw.bind(I64).bind_any(),
+            rec_atomic_rmw.opcodes(opcodes.to_vec()).rex().w(),
+            is_op.clone(),
+        );
+        e.enc_both_instp(
+            atomic_rmw.bind(I16).bind_any(),
+            rec_atomic_rmw.opcodes(opcodes_word),
+            is_op.clone(),
+        );
+        e.enc_both_instp(
+            atomic_rmw.bind(I8).bind_any(),
+            rec_atomic_rmw_abcd.opcodes(opcodes_byte.to_vec()),
+            is_op,
+        );
+    }
+
+    e.enc_both(
+        atomic_cas.bind(I32).bind_any(),
+        rec_atomic_cas.opcodes(vec![0x0f, 0xb1]),
+    );
+    e.enc64(
+        atomic_cas.bind(I64).bind_any(),
+        rec_atomic_cas.opcodes(vec![0x0f, 0xb1]).rex().w(),
+    );
+    e.enc_both(
+        atomic_cas.bind(I16).bind_any(),
+        rec_atomic_cas.opcodes(vec![0x66, 0x0f, 0xb1]),
+    );
+    e.enc_both(
+        atomic_cas.bind(I8).bind_any(),
+        rec_atomic_cas_abcd.opcodes(vec![0x0f, 0xb0]),
+    );
+
+    // Fence as mfence.
+    e.enc32(fence, rec_fence.opcodes(vec![0x0f, 0xae]).rrr(6));
+    e.enc64(fence, rec_fence.opcodes(vec![0x0f, 0xae]).rrr(6));
+
     e.enc_i32_i64(fill, rec_fillSib32.opcodes(vec![0x8b]));
     e.enc_i32_i64(regfill, rec_regfill32.opcodes(vec![0x8b]));
     e.enc_r32_r64_rex_only(fill, rec_fillSib32.opcodes(vec![0x8b]));
@@ -1302,6 +1427,19 @@ pub(crate) fn define(
         is_pic,
     );
 
+    // Thread local storage addresses. Only non-PIC code can use the local-exec model, for
+    // colocated symbols; other TLS symbols are legalized to `x86_elf_tls_get_addr`.
+    e.enc64_func(
+        tls_value.bind(I64),
+        rec_elf_tls_le.opcodes(vec![0x81]).rex().w(),
+        |encoding| {
+            encoding
+                .isa_predicate(not_is_pic)
+                .inst_predicate(InstructionPredicate::new_is_colocated_data(formats))
+        },
+    );
+    e.enc64_rec(x86_elf_tls_get_addr, rec_elf_tls_get_addr, 0);
+
     // Stack addresses.
     //
     // TODO: Add encoding rules for stack_load and stack_store, so that they
@@ -1899,6 +2037,146 @@ pub(crate) fn define(
         e.enc_32_64_maybe_isap(instruction, template, None); // from SSE
     }
 
+    // SIMD register movement: store, load, spill, fill and copy 128-bit vectors with MOVUPS and
+    // MOVAPS, which do not depend on the lane type.
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        e.enc_both(
+            fill.bind_vector_from_lane(ty, sse_vector_size),
+            rec_ffillSib32.opcodes(vec![0x0f, 0x10]),
+        );
+        e.enc_both(
+            regfill.bind_vector_from_lane(ty, sse_vector_size),
+            rec_fregfill32.opcodes(vec![0x0f, 0x10]),
+        );
+        e.enc_both(
+            spill.bind_vector_from_lane(ty, sse_vector_size),
+            rec_fspillSib32.opcodes(vec![0x0f, 0x11]),
+        );
+        e.enc_both(
+            regspill.bind_vector_from_lane(ty, sse_vector_size),
+            rec_fregspill32.opcodes(vec![0x0f, 0x11]),
+        );
+
+        e.enc_both(
+            copy.bind_vector_from_lane(ty, sse_vector_size),
+            rec_furm.opcodes(vec![0x0f, 0x28]),
+        );
+        // See the scalar `regmove` encodings about the REX forms.
+        e.enc32(
+            regmove.bind_vector_from_lane(ty, sse_vector_size),
+            rec_frmov.opcodes(vec![0x0f, 0x28]),
+        );
+        e.enc64(
+            regmove.bind_vector_from_lane(ty, sse_vector_size),
+            rec_frmov.opcodes(vec![0x0f, 0x28]).rex(),
+        );
+        e.enc_both_rex_only(
+            copy_to_ssa.bind_vector_from_lane(ty, sse_vector_size),
+            rec_furm_reg_to_ssa.opcodes(vec![0x0f, 0x10]),
+        );
+
+        e.enc_32_64_rec(
+            fill_nop.bind_vector_from_lane(ty, sse_vector_size),
+            rec_ffillnull,
+            0,
+        );
+        e.enc_32_64_rec(
+            copy_nop.bind_vector_from_lane(ty, sse_vector_size),
+            rec_stacknull,
+            0,
+        );
+    }
+
+    // SIMD floating point arithmetic, from SSE (packed single) and SSE2 (packed double).
+    for &(inst, opc) in &[
+        (fadd, 0x58),
+        (fsub, 0x5c),
+        (fmul, 0x59),
+        (fdiv, 0x5e),
+        (x86_fmin, 0x5d),
+        (x86_fmax, 0x5f),
+    ] {
+        e.enc_both(
+            inst.bind_vector_from_lane(F32, sse_vector_size),
+            rec_fa.opcodes(vec![0x0f, opc]),
+        );
+        e.enc_both(
+            inst.bind_vector_from_lane(F64, sse_vector_size),
+            rec_fa.opcodes(vec![0x66, 0x0f, opc]),
+        );
+    }
+    e.enc_both(
+        sqrt.bind_vector_from_lane(F32, sse_vector_size),
+        rec_furm.opcodes(vec![0x0f, 0x51]),
+    );
+    e.enc_both(
+        sqrt.bind_vector_from_lane(F64, sse_vector_size),
+        rec_furm.opcodes(vec![0x66, 0x0f, 0x51]),
+    );
+
+    // SIMD floating point comparisons with CMPPS and CMPPD. This only covers the condition codes
+    // supported by the `pfcmp` recipe.
+    e.enc_both(
+        fcmp.bind_vector_from_lane(F32, sse_vector_size),
+        rec_pfcmp.opcodes(vec![0x0f, 0xc2]),
+    );
+    e.enc_both(
+        fcmp.bind_vector_from_lane(F64, sse_vector_size),
+        rec_pfcmp.opcodes(vec![0x66, 0x0f, 0xc2]),
+    );
+
+    // SIMD conversions: CVTDQ2PS and CVTTPS2DQ, from SSE2. The latter produces 0x80000000 for NaN
+    // and out of range lanes, like the scalar `x86_cvtt2si`.
+    e.enc_both(
+        fcvt_from_sint
+            .bind_vector_from_lane(F32, sse_vector_size)
+            .bind_vector_from_lane(I32, sse_vector_size),
+        rec_furm.opcodes(vec![0x0f, 0x5b]),
+    );
+    e.enc_both(
+        x86_cvtt2si
+            .bind_vector_from_lane(I32, sse_vector_size)
+            .bind_vector_from_lane(F32, sse_vector_size),
+        rec_furm.opcodes(vec![0xf3, 0x0f, 0x5b]),
+    );
+
+    // SIMD bitwise operations on all 128-bit vectors: PAND, POR, PXOR and PANDN from SSE2.
+    for ty in ValueType::all_lane_types().filter(allowed_simd_type) {
+        for &(inst, opc) in &[(band, 0xdb), (bor, 0xeb), (bxor, 0xef)] {
+            e.enc_both(
+                inst.bind_vector_from_lane(ty, sse_vector_size),
+                rec_fa.opcodes(vec![0x66, 0x0f, opc]),
+            );
+        }
+        // Like `andnps`, `pandn(x,y)` computes `~x&y`.
+        e.enc_both(
+            band_not.bind_vector_from_lane(ty, sse_vector_size),
+            rec_fax.opcodes(vec![0x66, 0x0f, 0xdf]),
+        );
+    }
+
+    // SIMD shifts by an immediate, from SSE2: PSLL*, PSRL* and PSRA* share an opcode for each
+    // lane size, and are distinguished by the ModR/M reg field.
+    for &(lane_type, opc) in &[(I16, 0x71), (I32, 0x72), (I64, 0x73)] {
+        let shl = ishl_imm.bind_vector_from_lane(lane_type, sse_vector_size);
+        e.enc_both(shl, rec_f_ib.opcodes(vec![0x66, 0x0f, opc]).rrr(6));
+        let ushr = ushr_imm.bind_vector_from_lane(lane_type, sse_vector_size);
+        e.enc_both(ushr, rec_f_ib.opcodes(vec![0x66, 0x0f, opc]).rrr(2));
+        // There is no PSRAQ before AVX-512.
+        if lane_type != I64 {
+            let sshr = sshr_imm.bind_vector_from_lane(lane_type, sse_vector_size);
+            e.enc_both(sshr, rec_f_ib.opcodes(vec![0x66, 0x0f, opc]).rrr(4));
+        }
+    }
+
+    // SIMD saturating unsigned addition: PADDUSB and PADDUSW, from SSE2.
+    for &(lane_type, opc) in &[(I8, 0xdc), (I16, 0xdd)] {
+        e.enc_both(
+            uadd_sat.bind_vector_from_lane(lane_type, sse_vector_size),
+            rec_fa.opcodes(vec![0x66, 0x0f, opc]),
+        );
+    }
+
     // Reference type instructions
 
     // Null references implemented as iconst 0.
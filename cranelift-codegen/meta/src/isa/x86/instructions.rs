@@ -7,6 +7,7 @@ use crate::cdsl::instructions::{
 use crate::cdsl::operands::{create_operand as operand, create_operand_doc as operand_doc};
 use crate::cdsl::types::ValueType;
 use crate::cdsl::typevar::{Interval, TypeSetBuilder, TypeVar};
+use crate::shared::entities::EntityRefs;
 use crate::shared::immediates::Immediates;
 use crate::shared::types;
 
@@ -14,6 +15,7 @@ pub(crate) fn define(
     mut all_instructions: &mut AllInstructions,
     format_registry: &FormatRegistry,
     immediates: &Immediates,
+    entities: &EntityRefs,
 ) -> InstructionGroup {
     let mut ig = InstructionGroupBuilder::new(
         "x86",
@@ -387,5 +389,30 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    let i64_t = &TypeVar::new(
+        "i64_t",
+        "A scalar 64bit integer",
+        TypeSetBuilder::new().ints(64..64).build(),
+    );
+
+    let GV = &operand("GV", &entities.global_value);
+    let addr = &operand("addr", i64_t);
+
+    ig.push(
+        Inst::new(
+            "x86_elf_tls_get_addr",
+            r#"
+        Compute the address of the TLS symbol GV using the ELF general-dynamic
+        model.
+
+        This calls `__tls_get_addr`, so all caller-saved registers are
+        clobbered.
+        "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![addr])
+        .clobbers_all_regs(true),
+    );
+
     ig.build()
 }
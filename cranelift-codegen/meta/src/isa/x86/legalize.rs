@@ -3,7 +3,7 @@ use crate::cdsl::instructions::InstructionGroup;
 use crate::cdsl::types::ValueType;
 use crate::cdsl::xform::TransformGroupBuilder;
 use crate::shared::types::Float::F64;
-use crate::shared::types::Int::{I32, I64};
+use crate::shared::types::Int::{I128, I32, I64};
 use crate::shared::Definitions as SharedDefinitions;
 
 pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &InstructionGroup) {
@@ -19,6 +19,8 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
 
     // List of instructions.
     let insts = &shared.instructions;
+    let atomic_rmw = insts.by_name("atomic_rmw");
+    let atomic_store = insts.by_name("atomic_store");
     let band = insts.by_name("band");
     let bor = insts.by_name("bor");
     let clz = insts.by_name("clz");
@@ -31,12 +33,15 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let fcvt_to_uint = insts.by_name("fcvt_to_uint");
     let fcvt_to_sint_sat = insts.by_name("fcvt_to_sint_sat");
     let fcvt_to_uint_sat = insts.by_name("fcvt_to_uint_sat");
+    let fence = insts.by_name("fence");
     let fmax = insts.by_name("fmax");
     let fmin = insts.by_name("fmin");
     let iadd = insts.by_name("iadd");
+    let iconcat = insts.by_name("iconcat");
     let iconst = insts.by_name("iconst");
     let imul = insts.by_name("imul");
     let insertlane = insts.by_name("insertlane");
+    let isplit = insts.by_name("isplit");
     let isub = insts.by_name("isub");
     let popcnt = insts.by_name("popcnt");
     let raw_bitcast = insts.by_name("raw_bitcast");
@@ -46,6 +51,9 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     let smulhi = insts.by_name("smulhi");
     let splat = insts.by_name("splat");
     let srem = insts.by_name("srem");
+    let store = insts.by_name("store");
+    let swizzle = insts.by_name("swizzle");
+    let tls_value = insts.by_name("tls_value");
     let udiv = insts.by_name("udiv");
     let umulhi = insts.by_name("umulhi");
     let ushr_imm = insts.by_name("ushr_imm");
@@ -150,6 +158,27 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     group.custom_legalize(fcvt_to_sint_sat, "expand_fcvt_to_sint_sat");
     group.custom_legalize(fcvt_to_uint_sat, "expand_fcvt_to_uint_sat");
 
+    // TLS accesses that can't use the local-exec model call `__tls_get_addr`.
+    group.custom_legalize(tls_value, "expand_tls_value");
+
+    // SIMD swizzle, which needs out of range lane indices to select zero. It only operates on
+    // i8x16, so it is legalized as a monomorphic instruction.
+    group.custom_legalize(swizzle, "expand_swizzle");
+
+    // Atomic memory accesses.
+    //
+    // Sequentially consistent stores need a fence after them so that they can't be reordered
+    // with a later load. The read-modify-write operations without a locked x86 instruction are
+    // rewritten in terms of `lock xadd` or a `lock cmpxchg` loop, which modifies the CFG.
+    let flags = var("flags");
+    let p = var("p");
+    let offset = var("offset");
+    group.legalize(
+        def!(atomic_store(flags, x, p, offset)),
+        vec![def!(store(flags, x, p, offset)), def!(fence())],
+    );
+    group.custom_legalize(atomic_rmw, "expand_atomic_rmw");
+
     // Count leading and trailing zeroes, for baseline x86_64
     let c_minus_one = var("c_minus_one");
     let c_thirty_one = var("c_thirty_one");
@@ -307,6 +336,31 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     .isa("x86")
     .chain_with(shared.transform_groups.by_name("narrow_flags").id);
 
+    // 128-bit multiplication, where the carries out of the product of the low halves come from
+    // `umulhi`. Other targets call a library routine instead.
+    let xl = var("xl");
+    let xh = var("xh");
+    let yl = var("yl");
+    let yh = var("yh");
+    let al = var("al");
+    let ah = var("ah");
+    let a3 = var("a3");
+    let a4 = var("a4");
+    narrow.legalize(
+        def!(a = imul.I128(x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(a1 = imul(xh, yl)),
+            def!(a2 = imul(xl, yh)),
+            def!(a3 = iadd(a1, a2)),
+            def!(a4 = umulhi(xl, yl)),
+            def!(ah = iadd(a3, a4)),
+            def!(al = imul(xl, yl)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
     // SIMD
     let uimm8_zero = Literal::constant(&imm.uimm8, 0x00);
     let uimm8_one = Literal::constant(&imm.uimm8, 0x01);
@@ -383,5 +437,30 @@ pub(crate) fn define(shared: &mut SharedDefinitions, x86_instructions: &Instruct
     narrow.custom_legalize(extractlane, "convert_extractlane");
     narrow.custom_legalize(insertlane, "convert_insertlane");
 
+    // SIMD floating point operations whose semantics differ from the SSE instructions for NaNs,
+    // signed zeros and out of range values.
+    narrow.custom_legalize(fmin, "expand_minmax_vector");
+    narrow.custom_legalize(fmax, "expand_minmax_vector");
+    narrow.custom_legalize(fcvt_to_sint_sat, "expand_fcvt_to_sint_sat_vector");
+
     narrow.build_and_add_to(&mut shared.transform_groups);
+
+    let mut widen = TransformGroupBuilder::new(
+        "x86_widen",
+        r#"
+    Legalize instructions by widening.
+
+    Use x86-specific instructions if needed."#,
+    )
+    .isa("x86")
+    .chain_with(shared.transform_groups.by_name("widen").id);
+
+    // Atomic memory accesses can't be widened, since that would access the neighbouring bytes.
+    widen.legalize(
+        def!(atomic_store(flags, x, p, offset)),
+        vec![def!(store(flags, x, p, offset)), def!(fence())],
+    );
+    widen.custom_legalize(atomic_rmw, "expand_atomic_rmw");
+
+    widen.build_and_add_to(&mut shared.transform_groups);
 }
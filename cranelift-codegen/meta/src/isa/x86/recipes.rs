@@ -338,6 +338,14 @@ pub(crate) fn define<'shared>(
         .map(|name| Literal::enumerator_for(floatcc, name))
         .collect();
 
+    // The packed comparisons (`cmpps` and `cmppd`) support a different set of condition codes,
+    // selected by an immediate byte.
+    let supported_packed_floatccs: Vec<Literal> =
+        ["eq", "lt", "le", "uno", "ne", "uge", "ugt", "ord"]
+            .iter()
+            .map(|name| Literal::enumerator_for(floatcc, name))
+            .collect();
+
     let formats = &shared_defs.format_registry;
 
     // Register classes shorthands.
@@ -358,6 +366,8 @@ pub(crate) fn define<'shared>(
     let stack_fpr32 = Stack::new(fpr);
 
     // Format shorthands, prefixed with f_.
+    let f_atomic_cas = formats.by_name("AtomicCas");
+    let f_atomic_rmw = formats.by_name("AtomicRmw");
     let f_binary = formats.by_name("Binary");
     let f_binary_imm = formats.by_name("BinaryImm");
     let f_branch = formats.by_name("Branch");
@@ -566,6 +576,38 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // XX /r with FPR ins and outs. A form with a byte immediate selecting the packed comparison
+    // predicate.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("pfcmp", f_float_compare, 2)
+            .operands_in(vec![fpr, fpr])
+            .operands_out(vec![0])
+            .inst_predicate(supported_floatccs_predicate(
+                &supported_packed_floatccs,
+                formats.get(f_float_compare),
+            ))
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, rex2(in_reg1, in_reg0), sink);
+                    modrm_rr(in_reg1, in_reg0, sink);
+                    // The comparison predicate, which sets all bits of the lanes where it holds.
+                    use crate::ir::condcodes::FloatCC::*;
+                    let imm = match cond {
+                        Equal                         => 0x00, // EQ
+                        LessThan                      => 0x01, // LT
+                        LessThanOrEqual               => 0x02, // LT|EQ
+                        Unordered                     => 0x03, // UN
+                        NotEqual                      => 0x04, // UN|LT|GT
+                        UnorderedOrGreaterThanOrEqual => 0x05, // UN|GT|EQ
+                        UnorderedOrGreaterThan        => 0x06, // UN|GT
+                        Ordered                       => 0x07, // EQ|LT|GT
+                        _ => panic!("{} not supported by pfcmp", cond),
+                    };
+                    sink.put1(imm);
+                "#,
+            ),
+    );
+
     // XX /r with FPR ins and outs. A form with a byte immediate.
     {
         let format = formats.get(f_insert_lane);
@@ -864,6 +906,22 @@ pub(crate) fn define<'shared>(
                 ),
         );
 
+        // XX /n ib with 8-bit immediate sign-extended, FPR in and out (e.g. for vector shifts).
+        recipes.add_template_recipe(
+            EncodingRecipeBuilder::new("f_ib", f_binary_imm, 2)
+                .operands_in(vec![fpr])
+                .operands_out(vec![0])
+                .inst_predicate(InstructionPredicate::new_is_signed_int(format, "imm", 8, 0))
+                .emit(
+                    r#"
+                        {{PUT_OP}}(bits, rex1(in_reg0), sink);
+                        modrm_r_bits(in_reg0, bits, sink);
+                        let imm: i64 = imm.into();
+                        sink.put1(imm as u8);
+                    "#,
+                ),
+        );
+
         // XX /n id with 32-bit immediate sign-extended.
         recipes.add_template_recipe(
             EncodingRecipeBuilder::new("r_id", f_binary_imm, 5)
@@ -1296,6 +1354,65 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // Thread local storage addresses.
+
+    // ELF local-exec model: read the thread pointer from %fs:0 and add the offset of the symbol
+    // from it. This is only valid in an executable, for symbols it defines.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("elf_tls_le", f_unary_global_value, 14)
+            .operands_out(vec![gpr])
+            .emit(
+                r#"
+                    // mov %fs:0, %out_reg0
+                    sink.put1(0x64);
+                    sink.put1(rex2(0, out_reg0) | 0b1000); // REX.W
+                    sink.put1(0x8b);
+                    modrm_sib(out_reg0, sink);
+                    sink.put1(0x25); // Absolute disp32 with no base or index.
+                    sink.put4(0);
+
+                    // add $gv@tpoff, %out_reg0
+                    {{PUT_OP}}(bits, rex1(out_reg0), sink);
+                    modrm_r_bits(out_reg0, bits, sink);
+                    sink.reloc_external(Reloc::ElfX86_64TpOff32,
+                                        &func.global_values[global_value].symbol_name(),
+                                        0);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
+    // ELF general-dynamic model: call `__tls_get_addr` with the address of the symbol's GOT
+    // entries in %rdi. The redundant prefixes pad the sequence to the exact form that linkers
+    // recognize when relaxing it to a cheaper model.
+    recipes.add_recipe(
+        EncodingRecipeBuilder::new("elf_tls_get_addr", f_unary_global_value, 16)
+            .operands_out(vec![reg_rax])
+            .emit(
+                r#"
+                    // data16 lea gv@tlsgd(%rip), %rdi
+                    sink.put1(0x66);
+                    sink.put1(0b0100_1000); // REX.W
+                    sink.put1(0x8d);
+                    modrm_riprel(RU::rdi as RegUnit, sink);
+                    sink.reloc_external(Reloc::ElfX86_64TlsGd,
+                                        &func.global_values[global_value].symbol_name(),
+                                        -4);
+                    sink.put4(0);
+
+                    // data16 data16 rex.W call __tls_get_addr@plt
+                    sink.put1(0x66);
+                    sink.put1(0x66);
+                    sink.put1(0b0100_1000); // REX.W
+                    sink.put1(0xe8);
+                    sink.reloc_external(Reloc::X86CallPLTRel4,
+                                        &ExternalName::LibCall(LibCall::ElfTlsGetAddr),
+                                        -4);
+                    sink.put4(0);
+                "#,
+            ),
+    );
+
     // Stack addresses.
     //
     // TODO Alternative forms for 8-bit immediates, when applicable.
@@ -1872,6 +1989,145 @@ pub(crate) fn define<'shared>(
             ),
     );
 
+    // Atomic recipes.
+
+    // LOCK XX /r register-indirect read-modify-write with the operand tied to the output.
+    let atomic_rmw = recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("atomic_rmw", f_atomic_rmw, 2)
+            .operands_in(vec![gpr, gpr])
+            .operands_out(vec![1])
+            .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg1), sink);
+                    if needs_sib_byte(in_reg0) {
+                        modrm_sib(in_reg1, sink);
+                        sib_noindex(in_reg0, sink);
+                    } else if needs_offset(in_reg0) {
+                        modrm_disp8(in_reg0, in_reg1, sink);
+                        sink.put1(0);
+                    } else {
+                        modrm_rm(in_reg0, in_reg1, sink);
+                    }
+                "#,
+            ),
+    );
+
+    // LOCK XX /r register-indirect read-modify-write with the operand tied to the output.
+    // Only ABCD allowed for the operand. This is for byte operations with no REX.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("atomic_rmw_abcd", f_atomic_rmw, 2)
+                .operands_in(vec![gpr, abcd])
+                .operands_out(vec![1])
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+                .emit(
+                    r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg1), sink);
+                    if needs_sib_byte(in_reg0) {
+                        modrm_sib(in_reg1, sink);
+                        sib_noindex(in_reg0, sink);
+                    } else if needs_offset(in_reg0) {
+                        modrm_disp8(in_reg0, in_reg1, sink);
+                        sink.put1(0);
+                    } else {
+                        modrm_rm(in_reg0, in_reg1, sink);
+                    }
+                "#,
+                ),
+            formats,
+            regs,
+        )
+        .when_prefixed(atomic_rmw),
+    );
+
+    // LOCK XX /r register-indirect compare and exchange.
+    // The expected value and the result are in %rax.
+    let atomic_cas = recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("atomic_cas", f_atomic_cas, 2)
+            .operands_in(vec![
+                OperandConstraint::RegClass(gpr),
+                OperandConstraint::FixedReg(reg_rax),
+                OperandConstraint::RegClass(gpr),
+            ])
+            .operands_out(vec![reg_rax])
+            .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+            .emit(
+                r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg2), sink);
+                    if needs_sib_byte(in_reg0) {
+                        modrm_sib(in_reg2, sink);
+                        sib_noindex(in_reg0, sink);
+                    } else if needs_offset(in_reg0) {
+                        modrm_disp8(in_reg0, in_reg2, sink);
+                        sink.put1(0);
+                    } else {
+                        modrm_rm(in_reg0, in_reg2, sink);
+                    }
+                "#,
+            ),
+    );
+
+    // LOCK XX /r register-indirect compare and exchange.
+    // Only ABCD allowed for the replacement value. This is for byte operations with no REX.
+    recipes.add_template(
+        Template::new(
+            EncodingRecipeBuilder::new("atomic_cas_abcd", f_atomic_cas, 2)
+                .operands_in(vec![
+                    OperandConstraint::RegClass(gpr),
+                    OperandConstraint::FixedReg(reg_rax),
+                    OperandConstraint::RegClass(abcd),
+                ])
+                .operands_out(vec![reg_rax])
+                .compute_size("size_plus_maybe_sib_or_offset_for_in_reg_0")
+                .emit(
+                    r#"
+                    if !flags.notrap() {
+                        sink.trap(TrapCode::HeapOutOfBounds, func.srclocs[inst]);
+                    }
+                    sink.put1(0xf0);
+                    {{PUT_OP}}(bits, rex2(in_reg0, in_reg2), sink);
+                    if needs_sib_byte(in_reg0) {
+                        modrm_sib(in_reg2, sink);
+                        sib_noindex(in_reg0, sink);
+                    } else if needs_offset(in_reg0) {
+                        modrm_disp8(in_reg0, in_reg2, sink);
+                        sink.put1(0);
+                    } else {
+                        modrm_rm(in_reg0, in_reg2, sink);
+                    }
+                "#,
+                ),
+            formats,
+            regs,
+        )
+        .when_prefixed(atomic_cas),
+    );
+
+    // XX /n with a register ModR/M byte and no operands, for fences.
+    recipes.add_template_recipe(
+        EncodingRecipeBuilder::new("fence", f_nullary, 1)
+            .clobbers_flags(false)
+            .emit(
+                r#"
+                    {{PUT_OP}}(bits, BASE_REX, sink);
+                    modrm_r_bits(0, bits, sink);
+                "#,
+            ),
+    );
+
     // Load recipes.
 
     {
@@ -148,6 +148,22 @@ pub(crate) fn define(imm: &Immediates, entities: &EntityRefs) -> FormatRegistry
             .varargs()
             .imm(&imm.offset32),
     );
+    registry.insert(
+        Builder::new("AtomicRmw")
+            .imm(&imm.memflags)
+            .imm(&imm.atomic_rmw_op)
+            .value()
+            .value()
+            .typevar_operand(1),
+    );
+    registry.insert(
+        Builder::new("AtomicCas")
+            .imm(&imm.memflags)
+            .value()
+            .value()
+            .value()
+            .typevar_operand(1),
+    );
     registry.insert(
         Builder::new("StackLoad")
             .imm(&entities.stack_slot)
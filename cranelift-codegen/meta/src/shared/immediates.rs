@@ -58,6 +58,12 @@ pub(crate) struct Immediates {
     /// Flags for memory operations like `load` and `store`.
     pub memflags: OperandKind,
 
+    /// The operation performed by an atomic read-modify-write instruction.
+    ///
+    /// This enumerated operand kind is used for the `atomic_rmw` instruction and corresponds to
+    /// the `ir::AtomicRmwOp` Rust type.
+    pub atomic_rmw_op: OperandKind,
+
     /// A register unit in the current target ISA.
     pub regunit: OperandKind,
 
@@ -153,6 +159,21 @@ impl Immediates {
                 .rust_type("ir::MemFlags")
                 .build(),
 
+            atomic_rmw_op: {
+                let mut atomic_rmw_op_values = HashMap::new();
+                atomic_rmw_op_values.insert("add", "Add");
+                atomic_rmw_op_values.insert("sub", "Sub");
+                atomic_rmw_op_values.insert("and", "And");
+                atomic_rmw_op_values.insert("or", "Or");
+                atomic_rmw_op_values.insert("xor", "Xor");
+                atomic_rmw_op_values.insert("xchg", "Xchg");
+                Builder::new_enum("atomic_rmw_op", atomic_rmw_op_values)
+                    .doc("An atomic read-modify-write operation.")
+                    .default_member("op")
+                    .rust_type("ir::AtomicRmwOp")
+                    .build()
+            },
+
             regunit: Builder::new_imm("regunit")
                 .doc("A register unit in the target ISA")
                 .rust_type("isa::RegUnit")
@@ -162,6 +183,7 @@ impl Immediates {
                 let mut trapcode_values = HashMap::new();
                 trapcode_values.insert("stk_ovf", "StackOverflow");
                 trapcode_values.insert("heap_oob", "HeapOutOfBounds");
+                trapcode_values.insert("heap_misaligned", "HeapMisaligned");
                 trapcode_values.insert("int_ovf", "IntegerOverflow");
                 trapcode_values.insert("int_divz", "IntegerDivisionByZero");
                 Builder::new_enum("trapcode", trapcode_values)
@@ -112,6 +112,7 @@ pub(crate) fn define(
             .ints(Interval::All)
             .floats(Interval::All)
             .simd_lanes(Interval::All)
+            .refs(Interval::All)
             .build(),
     );
 
@@ -855,6 +856,96 @@ pub(crate) fn define(
         .can_store(true),
     );
 
+    let AtomicMem = &TypeVar::new(
+        "AtomicMem",
+        "An integer type that can be accessed atomically",
+        TypeSetBuilder::new().ints(8..64).build(),
+    );
+    let x = &operand_doc("x", AtomicMem, "Value to be stored");
+    let a = &operand_doc("a", AtomicMem, "Value loaded");
+    let e = &operand_doc("e", AtomicMem, "Expected value");
+    let Op = &operand("Op", &imm.atomic_rmw_op);
+
+    ig.push(
+        Inst::new(
+            "atomic_load",
+            r#"
+        Atomically load from memory at ``p + Offset``.
+
+        The load is sequentially consistent with respect to all other atomic
+        instructions.
+        "#,
+        )
+        .operands_in(vec![MemFlags, p, Offset])
+        .operands_out(vec![a])
+        .can_load(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_store",
+            r#"
+        Atomically store ``x`` to memory at ``p + Offset``.
+
+        The store is sequentially consistent with respect to all other atomic
+        instructions.
+        "#,
+        )
+        .operands_in(vec![MemFlags, x, p, Offset])
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_rmw",
+            r#"
+        Atomically read, modify and write the memory at ``p``.
+
+        The value in memory is combined with ``x`` according to ``Op`` and
+        the result is stored back. The value that was in memory before the
+        operation is returned.
+        "#,
+        )
+        .operands_in(vec![MemFlags, Op, p, x])
+        .operands_out(vec![a])
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_cas",
+            r#"
+        Atomically compare the memory at ``p`` with ``e`` and replace it with
+        ``x`` if they are equal.
+
+        The value that was in memory before the operation is returned,
+        whether or not the exchange took place.
+        "#,
+        )
+        .operands_in(vec![MemFlags, p, e, x])
+        .operands_out(vec![a])
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "fence",
+            r#"
+        A memory fence.
+
+        No memory access before the fence can be reordered with a memory
+        access after it.
+        "#,
+        )
+        .other_side_effects(true),
+    );
+
     let x = &operand_doc("x", Mem, "Value to be stored");
     let a = &operand_doc("a", Mem, "Value loaded");
     let Offset = &operand_doc("Offset", &imm.offset32, "In-bounds offset into stack slot");
@@ -935,6 +1026,20 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    ig.push(
+        Inst::new(
+            "tls_value",
+            r#"
+        Compute the value of global GV, which is a TLS (thread local storage) value.
+
+        The result is the address of the current thread's instance of the
+        symbol.
+        "#,
+        )
+        .operands_in(vec![GV])
+        .operands_out(vec![a]),
+    );
+
     let HeapOffset = &TypeVar::new(
         "HeapOffset",
         "An unsigned heap offset",
@@ -1494,7 +1599,37 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    let I8x16 = &TypeVar::new(
+        "I8x16",
+        "A SIMD vector type consisting of 16 lanes of 8-bit integers",
+        TypeSetBuilder::new()
+            .ints(8..8)
+            .simd_lanes(16..16)
+            .includes_scalars(false)
+            .build(),
+    );
+    let x = &operand_doc("x", I8x16, "Vector to modify by re-arranging lanes");
+    let y = &operand_doc("y", I8x16, "Mask for re-arranging lanes");
+    let a = &operand("a", I8x16);
+
+    ig.push(
+        Inst::new(
+            "swizzle",
+            r#"
+        Vector swizzle.
+
+        Returns a new vector with byte-width lanes selected from the lanes of the first input
+        vector ``x`` specified in the second input vector ``y``. The indices ``i`` in range
+        ``[0, 15]`` select the ``i``-th element of ``x``. For indices outside of the range the
+        resulting lane is 0. Note that this operates on byte-width lanes.
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
     let x = &operand("x", &TxN.lane_of());
+    let a = &operand("a", TxN);
 
     ig.push(
         Inst::new(
@@ -1650,6 +1785,21 @@ pub(crate) fn define(
         .operands_out(vec![a]),
     );
 
+    ig.push(
+        Inst::new(
+            "uadd_sat",
+            r#"
+        Add with unsigned saturation.
+
+        This is similar to `iadd` but the operands are interpreted as unsigned integers and their
+        summed result, instead of wrapping, will be saturated to the highest unsigned integer for
+        the controlling type (e.g. `0xFF` for i8).
+        "#,
+        )
+        .operands_in(vec![x, y])
+        .operands_out(vec![a]),
+    );
+
     ig.push(
         Inst::new(
             "isub",
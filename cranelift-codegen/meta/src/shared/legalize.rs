@@ -268,6 +268,192 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
         ],
     );
 
+    narrow.custom_legalize(br_icmp, "expand_br_icmp");
+
+    // Shifts and rotates of split values select between the results for amounts below and
+    // above the width of the halves, which can't be expressed in the meta language.
+    for &op in &[ishl, ushr, sshr, rotl, rotr] {
+        narrow.custom_legalize(op, "narrow_shift");
+    }
+
+    let intcc_eq = Literal::enumerator_for(&imm.intcc, "eq");
+    let intcc_ne = Literal::enumerator_for(&imm.intcc, "ne");
+    narrow.legalize(
+        def!(a = icmp.I128(intcc_eq, x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(b1 = icmp(intcc_eq, xl, yl)),
+            def!(b2 = icmp(intcc_eq, xh, yh)),
+            def!(a = band(b1, b2)),
+        ],
+    );
+    narrow.legalize(
+        def!(a = icmp.I128(intcc_ne, x, y)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!((yl, yh) = isplit(y)),
+            def!(b1 = icmp(intcc_ne, xl, yl)),
+            def!(b2 = icmp(intcc_ne, xh, yh)),
+            def!(a = bor(b1, b2)),
+        ],
+    );
+
+    // Ordered comparisons are decided by the high halves, unless they are equal, in which case
+    // the low halves are compared as unsigned numbers.
+    for &(cc, high_cc, low_cc) in &[
+        ("slt", "slt", "ult"),
+        ("sle", "slt", "ule"),
+        ("sgt", "sgt", "ugt"),
+        ("sge", "sgt", "uge"),
+        ("ult", "ult", "ult"),
+        ("ule", "ult", "ule"),
+        ("ugt", "ugt", "ugt"),
+        ("uge", "ugt", "uge"),
+    ] {
+        let cc = Literal::enumerator_for(&imm.intcc, cc);
+        let high_cc = Literal::enumerator_for(&imm.intcc, high_cc);
+        let low_cc = Literal::enumerator_for(&imm.intcc, low_cc);
+        narrow.legalize(
+            def!(a = icmp.I128(cc, x, y)),
+            vec![
+                def!((xl, xh) = isplit(x)),
+                def!((yl, yh) = isplit(y)),
+                def!(b1 = icmp(high_cc, xh, yh)),
+                def!(b2 = icmp(intcc_eq, xh, yh)),
+                def!(b3 = icmp(low_cc, xl, yl)),
+                def!(b4 = band(b2, b3)),
+                def!(a = bor(b1, b4)),
+            ],
+        );
+    }
+
+    // Operations with immediate operands, whose immediates are sign-extended to 128 bits by
+    // `iconst`.
+    for &(inst_imm, inst) in &[
+        (iadd_imm, iadd),
+        (imul_imm, imul),
+        (sdiv_imm, sdiv),
+        (udiv_imm, udiv),
+        (srem_imm, srem),
+        (urem_imm, urem),
+        (band_imm, band),
+        (bor_imm, bor),
+        (bxor_imm, bxor),
+    ] {
+        narrow.legalize(
+            def!(a = inst_imm.I128(x, y)),
+            vec![def!(a1 = iconst.I128(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = irsub_imm.I128(y, x)),
+        vec![def!(a1 = iconst.I128(x)), def!(a = isub(a1, y))],
+    );
+
+    for &(inst_imm, inst) in &[
+        (rotl_imm, rotl),
+        (rotr_imm, rotr),
+        (ishl_imm, ishl),
+        (sshr_imm, sshr),
+        (ushr_imm, ushr),
+    ] {
+        narrow.legalize(
+            def!(a = inst_imm.I128(x, y)),
+            vec![def!(a1 = iconst.I64(y)), def!(a = inst(x, a1))],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = icmp_imm.I128(cc, x, y)),
+        vec![def!(a1 = iconst.I128(y)), def!(a = icmp(cc, x, a1))],
+    );
+
+    let imm64_0 = Literal::constant(&imm.imm64, 0);
+    let imm64_63 = Literal::constant(&imm.imm64, 63);
+    let imm64_64 = Literal::constant(&imm.imm64, 64);
+
+    // Extensions to 128 bits fill the high half with zeros or copies of the sign bit.
+    let uextend_i128_i64 = uextend.bind(I128).bind(I64);
+    narrow.legalize(
+        def!(a = uextend_i128_i64(x)),
+        vec![def!(ah = iconst.I64(imm64_0)), def!(a = iconcat(x, ah))],
+    );
+    let sextend_i128_i64 = sextend.bind(I128).bind(I64);
+    narrow.legalize(
+        def!(a = sextend_i128_i64(x)),
+        vec![def!(ah = sshr_imm(x, imm64_63)), def!(a = iconcat(x, ah))],
+    );
+    for &int_ty in &[I8, I16, I32] {
+        let bound = uextend.bind(I128).bind(int_ty);
+        narrow.legalize(
+            def!(a = bound(x)),
+            vec![
+                def!(al = uextend.I64(x)),
+                def!(ah = iconst.I64(imm64_0)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+        let bound = sextend.bind(I128).bind(int_ty);
+        narrow.legalize(
+            def!(a = bound(x)),
+            vec![
+                def!(al = sextend.I64(x)),
+                def!(ah = sshr_imm(al, imm64_63)),
+                def!(a = iconcat(al, ah)),
+            ],
+        );
+    }
+
+    narrow.legalize(
+        def!(a = bint.I128(b)),
+        vec![
+            def!(al = bint.I64(b)),
+            def!(ah = iconst.I64(imm64_0)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
+    // Bit counts are at most 128, so their high half is zero.
+    narrow.legalize(
+        def!(a = popcnt.I128(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(e1 = popcnt(xl)),
+            def!(e2 = popcnt(xh)),
+            def!(al = iadd(e1, e2)),
+            def!(ah = iconst.I64(imm64_0)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+    narrow.legalize(
+        def!(a = clz.I128(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(e1 = clz(xh)),
+            def!(e2 = clz(xl)),
+            def!(e3 = iadd_imm(e2, imm64_64)),
+            def!(c = icmp_imm(intcc_eq, xh, imm64_0)),
+            def!(al = select(c, e3, e1)),
+            def!(ah = iconst.I64(imm64_0)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+    narrow.legalize(
+        def!(a = ctz.I128(x)),
+        vec![
+            def!((xl, xh) = isplit(x)),
+            def!(e1 = ctz(xl)),
+            def!(e2 = ctz(xh)),
+            def!(e3 = iadd_imm(e2, imm64_64)),
+            def!(c = icmp_imm(intcc_eq, xl, imm64_0)),
+            def!(al = select(c, e3, e1)),
+            def!(ah = iconst.I64(imm64_0)),
+            def!(a = iconcat(al, ah)),
+        ],
+    );
+
     // Widen instructions with one input operand.
     for &op in &[bnot, popcnt] {
         for &int_ty in &[I8, I16] {
@@ -542,6 +728,25 @@ pub(crate) fn define(insts: &InstructionGroup, imm: &Immediates) -> TransformGro
         ],
     );
 
+    // Reductions of 128-bit values only need their low half.
+    let ireduce_i64_i128 = ireduce.bind(I64).bind(I128);
+    expand.legalize(
+        def!(a = ireduce_i64_i128(x)),
+        vec![def!((a, xh) = isplit(x))],
+    );
+    let ireduce_i32_i128 = ireduce.bind(I32).bind(I128);
+    expand.legalize(
+        def!(a = ireduce_i32_i128(x)),
+        vec![def!((xl, xh) = isplit(x)), def!(a = ireduce(xl))],
+    );
+    for &int_ty in &[I8, I16] {
+        let bound = ireduce.bind(int_ty).bind(I128);
+        widen.legalize(
+            def!(a = bound(x)),
+            vec![def!((xl, xh) = isplit(x)), def!(a = ireduce(xl))],
+        );
+    }
+
     // Expansions for fcvt_from_{u,s}int for smaller integer types.
     // These use expand and not widen because the controlling type variable for
     // these instructions are f32/f64, which are legalized as part of the expand
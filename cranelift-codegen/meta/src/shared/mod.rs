@@ -1,6 +1,6 @@
 //! Shared definitions for the Cranelift intermediate language.
 
-mod entities;
+pub mod entities;
 pub mod formats;
 pub mod immediates;
 pub mod instructions;
@@ -21,6 +21,7 @@ pub(crate) struct Definitions {
     pub all_instructions: AllInstructions,
     pub instructions: InstructionGroup,
     pub imm: Immediates,
+    pub entities: EntityRefs,
     pub format_registry: FormatRegistry,
     pub transform_groups: TransformGroups,
 }
@@ -44,6 +45,7 @@ pub(crate) fn define() -> Definitions {
         all_instructions,
         instructions,
         imm: immediates,
+        entities,
         format_registry,
         transform_groups,
     }
@@ -56,6 +56,11 @@ pub enum Reloc {
     Arm64Call,
     /// RISC-V call target
     RiscvCall,
+
+    /// Elf x86_64 32 bit signed PC relative offset to two GOT entries for GD symbol.
+    ElfX86_64TlsGd,
+    /// Elf x86_64 32 bit signed offset of a symbol from the thread pointer, for the LE model.
+    ElfX86_64TpOff32,
 }
 
 impl fmt::Display for Reloc {
@@ -71,6 +76,9 @@ impl fmt::Display for Reloc {
             Reloc::X86CallPLTRel4 => write!(f, "CallPLTRel4"),
             Reloc::X86GOTPCRel4 => write!(f, "GOTPCRel4"),
             Reloc::Arm32Call | Reloc::Arm64Call | Reloc::RiscvCall => write!(f, "Call"),
+
+            Reloc::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
+            Reloc::ElfX86_64TpOff32 => write!(f, "ElfX86_64TpOff32"),
         }
     }
 }
@@ -155,6 +163,38 @@ pub trait CodeSink {
     fn add_stackmap(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa);
 }
 
+/// Type of the frame unwind information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameUnwindKind {
+    /// Windows x64 unwind information, i.e. an `UNWIND_INFO` structure as found in `.xdata` and
+    /// referenced from a `RUNTIME_FUNCTION` entry in `.pdata`.
+    Fastcall,
+    /// DWARF call frame information, as found in an `.eh_frame` section and understood by
+    /// libunwind and libgcc.
+    Libunwind,
+}
+
+/// Offset in frame unwind information buffer.
+pub type FrameUnwindOffset = usize;
+
+/// Sink for frame unwind information.
+pub trait FrameUnwindSink {
+    /// Get the current position.
+    fn offset(&self) -> FrameUnwindOffset;
+
+    /// Add bytes to the unwind information buffer.
+    fn bytes(&mut self, _: &[u8]);
+
+    /// Reserve space for at least `len` more bytes.
+    fn reserve(&mut self, _len: usize) {}
+
+    /// Add a relocation referencing the start of the function at the given offset.
+    fn reloc(&mut self, _: Reloc, _: FrameUnwindOffset);
+
+    /// Record the offset of the main structure describing the function, e.g. the FDE.
+    fn set_entry_offset(&mut self, _: FrameUnwindOffset);
+}
+
 /// Report a bad encoding error.
 #[cold]
 pub fn bad_encoding(func: &Function, inst: Inst) -> ! {
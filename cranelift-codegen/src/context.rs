@@ -10,8 +10,8 @@
 //! single ISA instance.
 
 use crate::binemit::{
-    relax_branches, shrink_instructions, CodeInfo, MemoryCodeSink, RelocSink, StackmapSink,
-    TrapSink,
+    relax_branches, shrink_instructions, CodeInfo, FrameUnwindKind, FrameUnwindSink,
+    MemoryCodeSink, RelocSink, StackmapSink, TrapSink,
 };
 use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
@@ -21,14 +21,17 @@ use crate::isa::TargetIsa;
 use crate::legalize_function;
 use crate::licm::do_licm;
 use crate::loop_analysis::LoopAnalysis;
+use crate::loop_unroll::do_loop_unrolling;
 use crate::nan_canonicalization::do_nan_canonicalization;
 use crate::postopt::do_postopt;
+use crate::redundant_memory::do_redundant_memory_elimination;
 use crate::redundant_reload_remover::RedundantReloadRemover;
 use crate::regalloc;
 use crate::result::CodegenResult;
 use crate::settings::{FlagsOrIsa, OptLevel};
 use crate::simple_gvn::do_simple_gvn;
 use crate::simple_preopt::do_preopt;
+use crate::strength_reduction::do_strength_reduction;
 use crate::timing;
 use crate::unreachable_code::eliminate_unreachable_code;
 use crate::value_label::{build_value_labels_ranges, ComparableSourceLoc, ValueLabelsRanges};
@@ -136,6 +139,12 @@ impl Context {
         if isa.flags().opt_level() != OptLevel::Fastest {
             self.preopt(isa)?;
         }
+        if isa.flags().opt_level() == OptLevel::Best {
+            self.compute_domtree();
+            self.compute_loop_analysis();
+            self.strength_reduction(isa)?;
+            self.unroll_loops(isa)?;
+        }
         if isa.flags().enable_nan_canonicalization() {
             self.canonicalize_nans(isa)?;
         }
@@ -148,6 +157,7 @@ impl Context {
             self.compute_loop_analysis();
             self.licm(isa)?;
             self.simple_gvn(isa)?;
+            self.redundant_memory_elimination(isa)?;
         }
         self.compute_domtree();
         self.eliminate_unreachable_code(isa)?;
@@ -191,6 +201,20 @@ impl Context {
         sink.info
     }
 
+    /// Emit unwind information.
+    ///
+    /// Requires that the function layout be calculated (see `relax_branches`).
+    ///
+    /// Only some calling conventions are supported; for the others nothing is written to `sink`.
+    pub fn emit_unwind_info(
+        &self,
+        isa: &dyn TargetIsa,
+        kind: FrameUnwindKind,
+        sink: &mut dyn FrameUnwindSink,
+    ) {
+        isa.emit_unwind_info(&self.func, kind, sink);
+    }
+
     /// Run the verifier on the function.
     ///
     /// Also check that the dominator tree and control flow graph are consistent with the function.
@@ -300,6 +324,15 @@ impl Context {
         self.verify_if(fisa)
     }
 
+    /// Eliminate redundant loads and dead stores in the function.
+    pub fn redundant_memory_elimination<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        do_redundant_memory_elimination(&mut self.func, &self.cfg, &self.domtree);
+        self.verify_if(fisa)
+    }
+
     /// Perform LICM on the function.
     pub fn licm(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         do_licm(
@@ -312,6 +345,34 @@ impl Context {
         self.verify_if(isa)
     }
 
+    /// Reduce the strength of expressions derived from the induction variables of loops.
+    pub fn strength_reduction<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        do_strength_reduction(
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+        );
+        self.verify_if(fisa)
+    }
+
+    /// Unroll small counted loops.
+    pub fn unroll_loops<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        do_loop_unrolling(
+            &mut self.func,
+            &mut self.cfg,
+            &mut self.domtree,
+            &mut self.loop_analysis,
+        );
+        self.verify_if(fisa)
+    }
+
     /// Perform unreachable code elimination.
     pub fn eliminate_unreachable_code<'a, FOI>(&mut self, fisa: FOI) -> CodegenResult<()>
     where
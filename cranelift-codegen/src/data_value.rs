@@ -0,0 +1,205 @@
+//! Runtime values of Cranelift IR types.
+//!
+//! A `DataValue` is what a CLIF value holds while a function is running: it is produced by
+//! interpreting or executing Cranelift IR rather than by compiling it.
+
+use crate::ir::immediates::{Ieee32, Ieee64};
+use crate::ir::{types, Type};
+use core::fmt::{self, Display, Formatter};
+use std::vec::Vec;
+
+/// A concrete value of a CLIF type.
+///
+/// Booleans of every width are represented by `B`; integers and floats carry their width in the
+/// variant. Vectors are kept as their little-endian bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataValue {
+    /// A boolean of any width.
+    B(bool),
+    /// An 8-bit integer.
+    I8(i8),
+    /// A 16-bit integer.
+    I16(i16),
+    /// A 32-bit integer.
+    I32(i32),
+    /// A 64-bit integer.
+    I64(i64),
+    /// A 128-bit integer.
+    I128(i128),
+    /// A 32-bit float.
+    F32(Ieee32),
+    /// A 64-bit float.
+    F64(Ieee64),
+    /// A 128-bit vector.
+    V128([u8; 16]),
+}
+
+impl DataValue {
+    /// Create an integer value of type `ty` from the low bits of `imm`.
+    ///
+    /// Returns `None` if `ty` is not a scalar integer type.
+    pub fn from_integer(imm: i128, ty: Type) -> Option<Self> {
+        Some(match ty {
+            types::I8 => DataValue::I8(imm as i8),
+            types::I16 => DataValue::I16(imm as i16),
+            types::I32 => DataValue::I32(imm as i32),
+            types::I64 => DataValue::I64(imm as i64),
+            types::I128 => DataValue::I128(imm),
+            _ => return None,
+        })
+    }
+
+    /// Get the sign-extended value of an integer, or `None` if this is not an integer.
+    pub fn to_integer(self) -> Option<i128> {
+        Some(match self {
+            DataValue::I8(x) => i128::from(x),
+            DataValue::I16(x) => i128::from(x),
+            DataValue::I32(x) => i128::from(x),
+            DataValue::I64(x) => i128::from(x),
+            DataValue::I128(x) => x,
+            _ => return None,
+        })
+    }
+
+    /// Get the little-endian bytes of this value when stored in memory as a `ty`.
+    ///
+    /// Booleans are stored as all ones or all zeros.
+    pub fn to_le_bytes(self, ty: Type) -> Vec<u8> {
+        let bits: u128 = match self {
+            DataValue::B(b) => {
+                if b {
+                    !0
+                } else {
+                    0
+                }
+            }
+            DataValue::F32(x) => u128::from(x.bits()),
+            DataValue::F64(x) => u128::from(x.bits()),
+            DataValue::V128(bytes) => return bytes.to_vec(),
+            _ => self.to_integer().unwrap() as u128,
+        };
+        bits.to_le_bytes()[..ty.bytes() as usize].to_vec()
+    }
+
+    /// Read a value of type `ty` from its little-endian bytes in memory.
+    ///
+    /// Booleans are true if any of their bits are set.
+    pub fn from_le_bytes(bytes: &[u8], ty: Type) -> Self {
+        let mut buffer = [0; 16];
+        buffer[..bytes.len()].copy_from_slice(bytes);
+        if ty.is_vector() {
+            return DataValue::V128(buffer);
+        }
+        let bits = u128::from_le_bytes(buffer);
+        match ty {
+            types::F32 => DataValue::F32(Ieee32::with_bits(bits as u32)),
+            types::F64 => DataValue::F64(Ieee64::with_bits(bits as u64)),
+            _ if ty.is_bool() => DataValue::B(bits != 0),
+            _ => Self::from_integer(bits as i128, ty)
+                .unwrap_or_else(|| panic!("{} has no in-memory representation", ty)),
+        }
+    }
+
+    /// Does this value fit in a value of type `ty`?
+    ///
+    /// Booleans match any boolean type; everything else must match the type exactly, except that
+    /// `V128` matches any 128-bit vector type.
+    pub fn is_type(&self, ty: Type) -> bool {
+        match *self {
+            DataValue::B(_) => ty.is_bool(),
+            DataValue::I8(_) => ty == types::I8,
+            DataValue::I16(_) => ty == types::I16,
+            DataValue::I32(_) => ty == types::I32,
+            DataValue::I64(_) => ty == types::I64,
+            DataValue::I128(_) => ty == types::I128,
+            DataValue::F32(_) => ty == types::F32,
+            DataValue::F64(_) => ty == types::F64,
+            DataValue::V128(_) => ty.is_vector() && ty.bits() == 128,
+        }
+    }
+}
+
+impl Display for DataValue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            DataValue::B(x) => write!(f, "{}", x),
+            DataValue::I8(x) => write!(f, "{}", x),
+            DataValue::I16(x) => write!(f, "{}", x),
+            DataValue::I32(x) => write!(f, "{}", x),
+            DataValue::I64(x) => write!(f, "{}", x),
+            DataValue::I128(x) => write!(f, "{}", x),
+            DataValue::F32(x) => write!(f, "{}", x),
+            DataValue::F64(x) => write!(f, "{}", x),
+            DataValue::V128(ref bytes) => {
+                write!(f, "[")?;
+                for (i, byte) in bytes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", byte)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+macro_rules! from_data {
+    ($ty:ty, $variant:ident) => {
+        impl From<$ty> for DataValue {
+            fn from(x: $ty) -> Self {
+                DataValue::$variant(x.into())
+            }
+        }
+    };
+}
+
+from_data!(bool, B);
+from_data!(i8, I8);
+from_data!(i16, I16);
+from_data!(i32, I32);
+from_data!(i64, I64);
+from_data!(i128, I128);
+from_data!(f32, F32);
+from_data!(f64, F64);
+from_data!(Ieee32, F32);
+from_data!(Ieee64, F64);
+from_data!([u8; 16], V128);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            DataValue::from_integer(0x1ff, types::I8),
+            Some(DataValue::I8(-1))
+        );
+        assert_eq!(DataValue::from_integer(1, types::F32), None);
+        assert_eq!(DataValue::I16(-2).to_integer(), Some(-2));
+        assert_eq!(DataValue::B(true).to_integer(), None);
+        assert!(DataValue::B(false).is_type(types::B8));
+        assert!(!DataValue::I32(0).is_type(types::I64));
+    }
+
+    #[test]
+    fn bytes() {
+        let value = DataValue::I16(-2);
+        assert_eq!(value.to_le_bytes(types::I16), [0xfe, 0xff]);
+        assert_eq!(DataValue::from_le_bytes(&[0xfe, 0xff], types::I16), value);
+        assert_eq!(DataValue::B(true).to_le_bytes(types::B8), [0xff]);
+        assert_eq!(
+            DataValue::from_le_bytes(&[0, 0, 0x80, 0x3f], types::F32),
+            DataValue::from(1.0f32)
+        );
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(DataValue::from(-3i32).to_string(), "-3");
+        assert_eq!(DataValue::from(true).to_string(), "true");
+        assert_eq!(DataValue::from(1.5f32).to_string(), "0x1.800000p0");
+    }
+}
@@ -0,0 +1,147 @@
+//! Detection of the basic induction variables of loops.
+//!
+//! A basic induction variable is a parameter of a loop header which every back edge passes
+//! the same constant increment of, like the counter `v1` in:
+//!
+//! ```clif
+//! ebb1(v1: i32):
+//!     v2 = iadd_imm v1, 4
+//!     brnz v0, ebb1(v2)
+//! ```
+//!
+//! Its value in every iteration is thus known from its value on entry to the loop.
+
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{DataFlowGraph, Function, Inst, InstructionData, Opcode, Value, ValueDef};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use std::vec::Vec;
+
+/// The branches to the header of a loop.
+pub struct LoopEdges {
+    /// The branches entering the loop from outside of it.
+    pub entries: Vec<Inst>,
+    /// The branches from inside the loop back to its header.
+    pub back_edges: Vec<Inst>,
+}
+
+impl LoopEdges {
+    /// Find the branches to the header of `lp`.
+    ///
+    /// Returns `None` when the loop can't be entered, or when its header is the destination of
+    /// a jump table, since no arguments can be passed along such edges.
+    pub fn new(
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        loop_analysis: &LoopAnalysis,
+        lp: Loop,
+    ) -> Option<Self> {
+        let mut edges = Self {
+            entries: Vec::new(),
+            back_edges: Vec::new(),
+        };
+        for pred in cfg.pred_iter(loop_analysis.loop_header(lp)) {
+            if let BranchInfo::Table(..) = func.dfg.analyze_branch(pred.inst) {
+                return None;
+            }
+            if loop_analysis.is_in_loop(pred.ebb, lp) {
+                edges.back_edges.push(pred.inst);
+            } else {
+                edges.entries.push(pred.inst);
+            }
+        }
+        if edges.entries.is_empty() {
+            None
+        } else {
+            Some(edges)
+        }
+    }
+}
+
+/// A basic induction variable of a loop.
+pub struct InductionVariable {
+    /// The index of the variable among the parameters of the loop header.
+    pub index: usize,
+    /// The header parameter holding the variable.
+    pub param: Value,
+    /// The constant added to the variable in every iteration.
+    pub step: i64,
+}
+
+/// Find the basic induction variables of the loop with the header branches `edges`.
+pub fn find_induction_variables(
+    func: &Function,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+    edges: &LoopEdges,
+) -> Vec<InductionVariable> {
+    let header = loop_analysis.loop_header(lp);
+    let mut ivs = Vec::new();
+    for (index, &param) in func.dfg.ebb_params(header).iter().enumerate() {
+        if !func.dfg.value_type(param).is_int() {
+            continue;
+        }
+        let mut steps = edges.back_edges.iter().map(|&branch| {
+            let arg = branch_args(&func.dfg, branch)[index];
+            increment(&func.dfg, arg, param)
+        });
+        let step = match steps.next() {
+            Some(Some(step)) if step != 0 => step,
+            _ => continue,
+        };
+        if steps.all(|other| other == Some(step)) {
+            ivs.push(InductionVariable { index, param, step });
+        }
+    }
+    ivs
+}
+
+/// Get the arguments that `branch` passes to its destination EBB.
+pub fn branch_args(dfg: &DataFlowGraph, branch: Inst) -> &[Value] {
+    match dfg.analyze_branch(branch) {
+        BranchInfo::SingleDest(_, args) => args,
+        _ => panic!("{} has no single destination", dfg[branch].opcode()),
+    }
+}
+
+/// Get the value of `value` if it is an integer constant.
+pub fn constant_value(dfg: &DataFlowGraph, value: Value) -> Option<i64> {
+    match dfg.value_def(dfg.resolve_aliases(value)) {
+        ValueDef::Result(inst, _) => match dfg[inst] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => Some(imm.into()),
+            _ => None,
+        },
+        ValueDef::Param(..) => None,
+    }
+}
+
+/// Get the constant that is added to `base` to compute `value`, if there is one.
+fn increment(dfg: &DataFlowGraph, value: Value, base: Value) -> Option<i64> {
+    let inst = match dfg.value_def(dfg.resolve_aliases(value)) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(..) => return None,
+    };
+    match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } if dfg.resolve_aliases(arg) == base => Some(imm.into()),
+        InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        } => {
+            if dfg.resolve_aliases(args[0]) == base {
+                constant_value(dfg, args[1])
+            } else if dfg.resolve_aliases(args[1]) == base {
+                constant_value(dfg, args[0])
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
@@ -55,6 +55,12 @@ impl Signature {
     pub fn special_param_index(&self, purpose: ArgumentPurpose) -> Option<usize> {
         self.params.iter().rposition(|arg| arg.purpose == purpose)
     }
+
+    /// Does this signature take a `StructReturn` pointer to the memory its values are returned in?
+    pub fn uses_struct_return_param(&self) -> bool {
+        self.special_param_index(ArgumentPurpose::StructReturn)
+            .is_some()
+    }
 }
 
 /// Wrapper type capable of displaying a `Signature` with correct register names.
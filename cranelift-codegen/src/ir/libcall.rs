@@ -46,6 +46,20 @@ pub enum LibCall {
     Memset,
     /// libc.memmove
     Memmove,
+
+    /// imul.i128
+    MulI128,
+    /// udiv.i128
+    UdivI128,
+    /// sdiv.i128
+    SdivI128,
+    /// urem.i128
+    UremI128,
+    /// srem.i128
+    SremI128,
+
+    /// Elf __tls_get_addr
+    ElfTlsGetAddr,
 }
 
 impl fmt::Display for LibCall {
@@ -71,6 +85,14 @@ impl FromStr for LibCall {
             "Memcpy" => Ok(LibCall::Memcpy),
             "Memset" => Ok(LibCall::Memset),
             "Memmove" => Ok(LibCall::Memmove),
+
+            "MulI128" => Ok(LibCall::MulI128),
+            "UdivI128" => Ok(LibCall::UdivI128),
+            "SdivI128" => Ok(LibCall::SdivI128),
+            "UremI128" => Ok(LibCall::UremI128),
+            "SremI128" => Ok(LibCall::SremI128),
+
+            "ElfTlsGetAddr" => Ok(LibCall::ElfTlsGetAddr),
             _ => Err(()),
         }
     }
@@ -97,6 +119,14 @@ impl LibCall {
                 Opcode::Nearest => LibCall::NearestF64,
                 _ => return None,
             },
+            types::I128 => match opcode {
+                Opcode::Imul => LibCall::MulI128,
+                Opcode::Udiv => LibCall::UdivI128,
+                Opcode::Sdiv => LibCall::SdivI128,
+                Opcode::Urem => LibCall::UremI128,
+                Opcode::Srem => LibCall::SremI128,
+                _ => return None,
+            },
             _ => return None,
         })
     }
@@ -220,5 +250,6 @@ mod tests {
     #[test]
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
+        assert_eq!("SdivI128".parse(), Ok(LibCall::SdivI128));
     }
 }
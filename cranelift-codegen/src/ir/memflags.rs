@@ -6,9 +6,49 @@ enum FlagBit {
     Notrap,
     Aligned,
     Readonly,
+    Heap,
+    Table,
+    Vmctx,
+    Stack,
 }
 
-const NAMES: [&str; 3] = ["notrap", "aligned", "readonly"];
+const NAMES: [&str; 7] = [
+    "notrap", "aligned", "readonly", "heap", "table", "vmctx", "stack",
+];
+
+/// The flag bits of all the alias regions.
+const REGION_BITS: u8 = (1 << FlagBit::Heap as usize)
+    | (1 << FlagBit::Table as usize)
+    | (1 << FlagBit::Vmctx as usize)
+    | (1 << FlagBit::Stack as usize);
+
+/// A disjoint region of memory accessed by loads and stores.
+///
+/// Accesses in different alias regions never refer to the same memory, which lets optimizations
+/// reorder or remove them with respect to each other. Accesses without an alias region may alias
+/// anything.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum AliasRegion {
+    /// The linear memory of a WebAssembly-like heap.
+    Heap,
+    /// The elements of a table.
+    Table,
+    /// The VM context and the runtime data it refers to, like global variables.
+    Vmctx,
+    /// The stack slots of the current function.
+    Stack,
+}
+
+impl AliasRegion {
+    fn bit(self) -> FlagBit {
+        match self {
+            AliasRegion::Heap => FlagBit::Heap,
+            AliasRegion::Table => FlagBit::Table,
+            AliasRegion::Vmctx => FlagBit::Vmctx,
+            AliasRegion::Stack => FlagBit::Stack,
+        }
+    }
+}
 
 /// Flags for memory operations like load/store.
 ///
@@ -47,11 +87,16 @@ impl MemFlags {
 
     /// Set a flag bit by name.
     ///
-    /// Returns true if the flag was found and set, false for an unknown flag name.
+    /// Returns true if the flag was found and set, false for an unknown flag name or for an
+    /// alias region when the flags already have a different one.
     pub fn set_by_name(&mut self, name: &str) -> bool {
         match NAMES.iter().position(|&s| s == name) {
             Some(bit) => {
-                self.bits |= 1 << bit;
+                let mask = 1 << bit;
+                if mask & REGION_BITS != 0 && self.bits & REGION_BITS & !mask != 0 {
+                    return false;
+                }
+                self.bits |= mask;
                 true
             }
             None => false,
@@ -103,6 +148,29 @@ impl MemFlags {
     pub fn set_readonly(&mut self) {
         self.set(FlagBit::Readonly)
     }
+
+    /// Get the alias region of the accessed memory, if it is known.
+    pub fn alias_region(self) -> Option<AliasRegion> {
+        [
+            AliasRegion::Heap,
+            AliasRegion::Table,
+            AliasRegion::Vmctx,
+            AliasRegion::Stack,
+        ]
+        .iter()
+        .cloned()
+        .find(|region| self.read(region.bit()))
+    }
+
+    /// Set the alias region of the accessed memory, replacing any previous one.
+    ///
+    /// The behavior is undefined if the memory accessed is not part of `region`.
+    pub fn set_alias_region(&mut self, region: Option<AliasRegion>) {
+        self.bits &= !REGION_BITS;
+        if let Some(region) = region {
+            self.set(region.bit());
+        }
+    }
 }
 
 impl fmt::Display for MemFlags {
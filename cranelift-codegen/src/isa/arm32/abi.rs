@@ -1,34 +1,69 @@
-//! ARM ABI implementation.
-//! This is from the RISC-V target and will need to be updated for ARM32.
+//! ARM 32-bit ABI implementation.
+//!
+//! This module implements the hard-float variant of the AAPCS procedure call standard, where
+//! floating point arguments are passed in the VFP registers.
 
-use super::registers::{D, GPR, Q, S};
+use super::registers::{D, GPR, RU, S};
 use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
-use crate::ir::{self, AbiParam, ArgumentExtension, ArgumentLoc, Type};
-use crate::isa::RegClass;
+use crate::cursor::{Cursor, CursorPosition, EncCursor};
+use crate::ir;
+use crate::ir::immediates::Imm64;
+use crate::ir::types::{F64, I32};
+use crate::ir::{AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, ValueLoc};
+use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
 use crate::regalloc::RegisterSet;
+use crate::result::CodegenResult;
+use crate::settings as shared_settings;
+use crate::stack_layout::layout_stack;
 use core::i32;
-use target_lexicon::Triple;
+use std::vec::Vec;
+
+/// Number of general purpose registers used for passing arguments: `%r0-%r3`.
+const GPR_ARG_LIMIT: usize = 4;
+
+/// Number of single precision registers used for passing floating point arguments: `%s0-%s15`,
+/// which overlap `%d0-%d7`.
+const FPR_ARG_LIMIT: usize = 16;
+
+/// Callee-saved general purpose registers. `%r11` is the frame pointer, it is saved separately.
+static CALLEE_SAVED_GPRS: [RU; 7] = [RU::r4, RU::r5, RU::r6, RU::r7, RU::r8, RU::r9, RU::r10];
+
+/// Callee-saved floating point registers: `%d8-%d15`, which overlap `%s16-%s31`.
+static CALLEE_SAVED_FPRS: [RU; 8] = [
+    RU::s16,
+    RU::s18,
+    RU::s20,
+    RU::s22,
+    RU::s24,
+    RU::s26,
+    RU::s28,
+    RU::s30,
+];
 
 struct Args {
-    pointer_bits: u8,
-    pointer_bytes: u8,
-    pointer_type: Type,
-    regs: u32,
-    reg_limit: u32,
+    gpr_used: usize,
+    /// The single precision argument registers that are used, as a bit mask. A double precision
+    /// register uses two bits.
+    fpr_used: u32,
     offset: u32,
 }
 
 impl Args {
-    fn new(bits: u8) -> Self {
+    fn new() -> Self {
         Self {
-            pointer_bits: bits,
-            pointer_bytes: bits / 8,
-            pointer_type: Type::int(u16::from(bits)).unwrap(),
-            regs: 0,
-            reg_limit: 8,
+            gpr_used: 0,
+            fpr_used: 0,
             offset: 0,
         }
     }
+
+    /// Find the first free run of `count` single precision registers, aligned to `count`.
+    fn find_fpr(&self, count: usize) -> Option<usize> {
+        let mask = (1 << count) - 1;
+        (0..FPR_ARG_LIMIT)
+            .step_by(count)
+            .find(|&i| self.fpr_used & (mask << i) == 0)
+    }
 }
 
 impl ArgAssigner for Args {
@@ -39,67 +74,290 @@ impl ArgAssigner for Args {
 
         let ty = arg.value_type;
 
-        // Check for a legal type.
-        // SIMD instructions are currently no implemented, so break down vectors
+        // SIMD isn't supported yet, so break all vectors down.
         if ty.is_vector() {
             return ValueConversion::VectorSplit.into();
         }
 
-        // Large integers and booleans are broken down to fit in a register.
-        if !ty.is_float() && ty.bits() > u16::from(self.pointer_bits) {
-            // Align registers and stack to a multiple of two pointers.
-            self.regs = align(self.regs, 2);
-            self.offset = align(self.offset, 2 * u32::from(self.pointer_bytes));
+        // Large integers and booleans are broken down to fit in a register. They are passed in an
+        // even-numbered pair of registers, or in a doubleword-aligned stack location, but never
+        // split between the registers and the stack.
+        if !ty.is_float() && ty.bits() > 32 {
+            self.gpr_used = (self.gpr_used + 1) & !1;
+            if self.gpr_used >= GPR_ARG_LIMIT {
+                self.offset = align(self.offset, 8);
+            }
             return ValueConversion::IntSplit.into();
         }
 
-        // Small integers are extended to the size of a pointer register.
-        if ty.is_int() && ty.bits() < u16::from(self.pointer_bits) {
+        // Small integers are extended to the size of a register.
+        if ty.is_int() && ty.bits() < 32 {
             match arg.extension {
                 ArgumentExtension::None => {}
-                ArgumentExtension::Uext => return ValueConversion::Uext(self.pointer_type).into(),
-                ArgumentExtension::Sext => return ValueConversion::Sext(self.pointer_type).into(),
+                ArgumentExtension::Uext => return ValueConversion::Uext(I32).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(I32).into(),
             }
         }
 
-        if self.regs < self.reg_limit {
-            // Assign to a register.
-            let reg = GPR.unit(10 + self.regs as usize);
-            self.regs += 1;
-            ArgumentLoc::Reg(reg).into()
-        } else {
-            // Assign a stack location.
+        if ty.is_float() {
+            // Single precision arguments can back-fill a register left free by the alignment of
+            // a double precision argument.
+            let count = (ty.bits() / 32) as usize;
+            if let Some(i) = self.find_fpr(count) {
+                self.fpr_used |= ((1 << count) - 1) << i;
+                let reg = if count == 1 { S.unit(i) } else { D.unit(i / 2) };
+                return ArgumentLoc::Reg(reg).into();
+            }
+
+            // Once a floating point argument is passed on the stack, the remaining ones are too.
+            self.fpr_used = (1 << FPR_ARG_LIMIT) - 1;
+            let size = ty.bytes();
+            self.offset = align(self.offset, size);
             let loc = ArgumentLoc::Stack(self.offset as i32);
-            self.offset += u32::from(self.pointer_bytes);
+            self.offset += size;
             debug_assert!(self.offset <= i32::MAX as u32);
-            loc.into()
+            return loc.into();
         }
+
+        if self.gpr_used < GPR_ARG_LIMIT {
+            let reg = GPR.unit(self.gpr_used);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
+        }
+
+        // Assign a stack location.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += 4;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
     }
 }
 
 /// Legalize `sig`.
-pub fn legalize_signature(sig: &mut ir::Signature, triple: &Triple, _current: bool) {
-    let bits = triple.pointer_width().unwrap().bits();
-
-    let mut args = Args::new(bits);
+pub fn legalize_signature(
+    sig: &mut ir::Signature,
+    _flags: &shared_settings::Flags,
+    _current: bool,
+) {
+    let mut args = Args::new();
     legalize_args(&mut sig.params, &mut args);
+
+    let mut rets = Args::new();
+    legalize_args(&mut sig.returns, &mut rets);
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
-        GPR
-    } else {
+    if ty.is_float() {
         match ty.bits() {
             32 => S,
-            64 => D,
-            128 => Q,
-            _ => panic!("Unexpected {} ABI type for arm32", ty),
+            _ => D,
         }
+    } else {
+        GPR
     }
 }
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    regs.take(GPR, RU::r11 as RegUnit); // Frame pointer.
+    regs.take(GPR, RU::r12 as RegUnit); // Scratch register for large stack offsets.
+    regs.take(GPR, RU::r13 as RegUnit); // Stack pointer.
+    regs.take(GPR, RU::r15 as RegUnit); // Program counter.
+
+    // Only `%d0-%d15` are available on the VFPv3-D16 variant.
+    for i in 16..32 {
+        regs.take(D, D.unit(i));
+    }
+    regs
+}
+
+/// Get the set of callee-saved registers that are used.
+///
+/// The floating point registers are noted as the first `S` register of the `D` register they
+/// are part of.
+fn callee_saved_regs_used(func: &ir::Function) -> RegisterSet {
+    let mut all_callee_saved = RegisterSet::empty();
+    for reg in &CALLEE_SAVED_GPRS {
+        all_callee_saved.free(GPR, *reg as RegUnit);
+    }
+    for reg in &CALLEE_SAVED_FPRS {
+        all_callee_saved.free(S, *reg as RegUnit);
+    }
+
+    let mut used = RegisterSet::empty();
+    let mut note_used = |ru: RegUnit| {
+        let (rc, ru) = if GPR.contains(ru) {
+            (GPR, ru)
+        } else if S.contains(ru) {
+            (S, ru & !1)
+        } else {
+            return;
+        };
+        if !used.is_avail(rc, ru) {
+            used.free(rc, ru);
+        }
+    };
+
+    for value_loc in func.locations.values() {
+        if let ValueLoc::Reg(ru) = *value_loc {
+            note_used(ru);
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::instructions::InstructionData::RegMove { dst, .. }
+                | ir::instructions::InstructionData::RegFill { dst, .. } => note_used(dst),
+                _ => (),
+            }
+        }
+    }
+
+    used.intersect(&all_callee_saved);
+    used
+}
+
+/// Insert a prologue and epilogues, and lay out the stack frame.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    match func.signature.call_conv {
+        // For now, just translate fast and cold as system_v.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV => {
+            system_v_prologue_epilogue(func, isa)
+        }
+        call_conv => unimplemented!("{} calling convention on arm32", call_conv),
+    }
+}
+
+/// Insert an AAPCS prologue and epilogue.
+///
+/// The link register and the frame pointer are pushed first, so the frame pointer points to
+/// the frame record. They are followed by the callee-saved registers. The stack pointer stays
+/// 8-byte aligned.
+fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    let stack_align = 8;
+
+    let csrs = callee_saved_regs_used(func);
+    let gprs: Vec<RegUnit> = csrs.iter(GPR).collect();
+    let fprs: Vec<RegUnit> = csrs.iter(S).collect();
+
+    // The reserved stack area is composed of the frame record and the callee-saved registers.
+    let csr_stack_size = (4 * (2 + gprs.len()) + 8 * fprs.len()) as i32;
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size);
+
+    // Add the frame record and the CSRs to the function signature, in the order they are pushed.
+    let link_arg = AbiParam::special_reg(I32, ArgumentPurpose::Link, RU::r14 as RegUnit);
+    func.signature.params.push(link_arg);
+    func.signature.returns.push(link_arg);
+
+    let fp_arg = AbiParam::special_reg(I32, ArgumentPurpose::FramePointer, RU::r11 as RegUnit);
+    func.signature.params.push(fp_arg);
+    func.signature.returns.push(fp_arg);
+
+    for &csr in &gprs {
+        let csr_arg = AbiParam::special_reg(I32, ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+    for &csr in &fprs {
+        let csr_arg = AbiParam::special_reg(F64, ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
+    // Set up the cursor and insert the prologue
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_prologue(&mut pos, local_stack_size, &gprs, &fprs);
+
+    // Reset the cursor and insert the epilogue
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    insert_epilogues(&mut pos, local_stack_size, &gprs, &fprs);
+
+    Ok(())
+}
+
+/// Append a parameter for the register `reg` to the entry EBB, and push it.
+fn push_reg(pos: &mut EncCursor, ty: ir::Type, reg: RegUnit) {
+    let ebb = pos.current_ebb().expect("missing ebb under cursor");
+    let param = pos.func.dfg.append_ebb_param(ebb, ty);
+    pos.func.locations[param] = ValueLoc::Reg(reg);
+    pos.ins().arm32_push(param);
+}
+
+/// Insert the prologue for a given function.
+fn insert_prologue(pos: &mut EncCursor, stack_size: i64, gprs: &[RegUnit], fprs: &[RegUnit]) {
+    push_reg(pos, I32, RU::r14 as RegUnit);
+    push_reg(pos, I32, RU::r11 as RegUnit);
+    pos.ins()
+        .copy_special(RU::r13 as RegUnit, RU::r11 as RegUnit);
+
+    for &reg in gprs {
+        push_reg(pos, I32, reg);
+    }
+    for &reg in fprs {
+        push_reg(pos, F64, reg);
+    }
+
+    // Allocate stack frame storage.
+    if stack_size > 0 {
+        pos.ins().adjust_sp_down_imm(Imm64::new(stack_size));
+    }
+}
+
+/// Find all `return` instructions and insert epilogues before them.
+fn insert_epilogues(pos: &mut EncCursor, stack_size: i64, gprs: &[RegUnit], fprs: &[RegUnit]) {
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                insert_epilogue(inst, stack_size, pos, gprs, fprs);
+            }
+        }
+    }
+}
+
+/// Pop a register, inserting the instruction before the previously inserted ones, and pass it
+/// to the `return` instruction `inst`.
+fn pop_reg(pos: &mut EncCursor, inst: ir::Inst, ty: ir::Type, reg: RegUnit) {
+    let x = pos.ins().arm32_pop(ty);
+    pos.prev_inst();
+
+    pos.func.locations[x] = ValueLoc::Reg(reg);
+    pos.func.dfg.append_inst_arg(inst, x);
+}
+
+/// Insert an epilogue given a specific `return` instruction.
+fn insert_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    gprs: &[RegUnit],
+    fprs: &[RegUnit],
+) {
+    if stack_size > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
+    }
+
+    // Pop all the callee-saved registers, stepping backward each time to preserve the correct
+    // order. The return arguments must follow the order of the signature.
+    pop_reg(pos, inst, I32, RU::r14 as RegUnit);
+    pop_reg(pos, inst, I32, RU::r11 as RegUnit);
+    for &reg in gprs {
+        pop_reg(pos, inst, I32, reg);
+    }
+    for &reg in fprs {
+        pop_reg(pos, inst, F64, reg);
+    }
 }
@@ -1,8 +1,406 @@
 //! Emitting binary ARM32 machine code.
 
-use crate::binemit::{bad_encoding, CodeSink};
-use crate::ir::{Function, Inst};
-use crate::isa::TargetIsa;
+use super::registers::RU;
+use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::ir::condcodes::{CondCode, FloatCC, IntCC};
+use crate::ir::{Function, Inst, InstructionData, TrapCode};
+use crate::isa::{RegUnit, StackBaseMask, StackRef, TargetIsa};
 use crate::regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm32.rs"));
+
+/// The bits selecting the shift type of the `mov` instructions with a shifted register, in the
+/// `a32_bits` layout.
+const SHIFT_TYPE_BITS: u16 = 0x6 << 8;
+
+/// The bits turning the `add` immediate instruction into a `sub`, in the `a32_bits` layout.
+const ADD_SUB_BITS: u16 = 0x0c;
+
+/// The `U` bit selecting a positive offset in the loads and stores, in the `a32_bits` layout.
+const U_BITS: u16 = 0x08;
+
+/// Unconditional branch `b`, in the `branch_bits` layout.
+const BRANCH_BITS: u16 = 0xea0;
+
+/// The permanently undefined instruction.
+const UDF: u32 = 0xe7f0_00f0;
+
+/// The `al` condition, for instructions that are always executed.
+const COND_AL: u32 = 0xe;
+
+/// Register operand field for `Rd` and `Rt`.
+fn rd(reg: RegUnit) -> u32 {
+    (u32::from(reg) & 0xf) << 12
+}
+
+/// Register operand field for `Rn`.
+fn rn(reg: RegUnit) -> u32 {
+    (u32::from(reg) & 0xf) << 16
+}
+
+/// Register operand field for `Rs`.
+fn rs(reg: RegUnit) -> u32 {
+    (u32::from(reg) & 0xf) << 8
+}
+
+/// Register operand field for `Rm`.
+fn rm(reg: RegUnit) -> u32 {
+    u32::from(reg) & 0xf
+}
+
+// The VFP register numbers are split in two fields: a 4-bit field next to the core register
+// fields, and a single bit elsewhere in the instruction. For the S registers the single bit is
+// the low bit of the register number, for the D registers the high bit. The S registers are
+// the first 32 register units, and the D registers are made of pairs of units.
+
+/// Register operand field for a destination S register: `Vd:D`.
+fn sd(reg: RegUnit) -> u32 {
+    let n = u32::from(reg);
+    (n >> 1) << 12 | (n & 1) << 22
+}
+
+/// Register operand field for a first source S register: `Vn:N`.
+fn sn(reg: RegUnit) -> u32 {
+    let n = u32::from(reg);
+    (n >> 1) << 16 | (n & 1) << 7
+}
+
+/// Register operand field for a second source S register: `Vm:M`.
+fn sm(reg: RegUnit) -> u32 {
+    let n = u32::from(reg);
+    n >> 1 | (n & 1) << 5
+}
+
+/// Register operand field for a destination D register: `D:Vd`.
+fn dd(reg: RegUnit) -> u32 {
+    let n = u32::from(reg) >> 1;
+    (n & 0xf) << 12 | (n >> 4) << 22
+}
+
+/// Register operand field for a first source D register: `N:Vn`.
+fn dn(reg: RegUnit) -> u32 {
+    let n = u32::from(reg) >> 1;
+    (n & 0xf) << 16 | (n >> 4) << 7
+}
+
+/// Register operand field for a second source D register: `M:Vm`.
+fn dm(reg: RegUnit) -> u32 {
+    let n = u32::from(reg) >> 1;
+    n & 0xf | (n >> 4) << 5
+}
+
+/// Conditionally executed instructions.
+///
+///   31   27     19      15      11     7      3
+///   cond opcode operand operand opcode opcode operand
+///   28     20      16      12       8      4       0
+///
+/// Encoding bits: `w[27:20] | w[7:4] << 8 | w[11:8] << 12`.
+fn put_a32_cond<CS: CodeSink + ?Sized>(bits: u16, cond: u32, lo: u32, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = cond << 28;
+    i |= (bits & 0xff) << 20;
+    i |= ((bits >> 8) & 0xf) << 4;
+    i |= ((bits >> 12) & 0xf) << 8;
+    sink.put4(i | lo);
+}
+
+/// Instructions that are always executed, see `put_a32_cond`.
+fn put_a32<CS: CodeSink + ?Sized>(bits: u16, lo: u32, sink: &mut CS) {
+    put_a32_cond(bits, COND_AL, lo, sink);
+}
+
+/// VFP data-processing instructions with a single operand.
+///
+///   31         19   15 11     7      3
+///   1110111010 opc2 Vd opcode opcode Vm
+///           16   12  8      4      0
+///
+/// Encoding bits: `w[19:16] | w[7:4] << 8 | w[11:8] << 12`.
+fn put_vfp_rr<CS: CodeSink + ?Sized>(bits: u16, lo: u32, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = 0xeeb0_0000;
+    i |= (bits & 0xf) << 16;
+    i |= ((bits >> 8) & 0xf) << 4;
+    i |= ((bits >> 12) & 0xf) << 8;
+    sink.put4(i | lo);
+}
+
+/// `vmrs APSR_nzcv, fpscr`, copying the floating point flags to the CPU flags.
+fn put_vmrs<CS: CodeSink + ?Sized>(sink: &mut CS) {
+    sink.put4(0xeef1_fa10);
+}
+
+/// Set `rd` to 1 when `cond` holds, and to 0 otherwise:
+///
+///   mov rd, #0
+///   mov<cond> rd, #1
+fn put_cset<CS: CodeSink + ?Sized>(cond: u32, rd: RegUnit, sink: &mut CS) {
+    sink.put4(0xe3a0_0000 | self::rd(rd));
+    sink.put4(cond << 28 | 0x03a0_0001 | self::rd(rd));
+}
+
+/// Replace the condition of a branch in the `branch_bits` layout.
+fn with_cond(bits: u16, cond: u32) -> u16 {
+    bits & 0xff | (cond << 8) as u16
+}
+
+/// Branches with a 24-bit word offset, relative to the address of the instruction plus 8.
+///
+///   31   27     23
+///   cond opcode imm24
+///   28     24       0
+///
+/// Encoding bits: `w[31:20]`.
+fn put_b<CS: CodeSink + ?Sized>(bits: u16, dest: i64, sink: &mut CS) {
+    let disp = dest - i64::from(sink.offset() + 8);
+    debug_assert!(disp & 3 == 0 && (-(1 << 25)..(1 << 25)).contains(&disp));
+    sink.put4(u32::from(bits) << 20 | (disp >> 2) as u32 & 0xff_ffff);
+}
+
+/// Loads and stores of words and bytes with a 12-bit offset.
+///
+///   31   27     23 22     19 15 11
+///   cond opcode U  opcode Rn Rt imm12
+///   28     24   23     20 16 12     0
+///
+/// The `rt` argument is the `Rt` operand field.
+fn put_mem12<CS: CodeSink + ?Sized>(bits: u16, offset: i64, rn: RegUnit, rt: u32, sink: &mut CS) {
+    let (bits, imm) = if offset < 0 {
+        (bits & !U_BITS, -offset)
+    } else {
+        (bits, offset)
+    };
+    debug_assert!(imm < (1 << 12));
+    put_a32(bits, self::rn(rn) | rt | imm as u32, sink);
+}
+
+/// Loads and stores of half words and signed bytes with an 8-bit offset, which is split in two
+/// fields.
+///
+///   31   27     23 22     19 15 11     7      3
+///   cond opcode U  opcode Rn Rt imm4H opcode imm4L
+///   28     24   23     20 16 12     8      4     0
+fn put_mem8<CS: CodeSink + ?Sized>(
+    bits: u16,
+    offset: i64,
+    rn: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let (bits, imm) = if offset < 0 {
+        (bits & !U_BITS, -offset as u32)
+    } else {
+        (bits, offset as u32)
+    };
+    debug_assert!(imm < (1 << 8));
+    put_a32(
+        bits,
+        self::rn(rn) | rd(rt) | (imm >> 4) << 8 | imm & 0xf,
+        sink,
+    );
+}
+
+/// VFP loads and stores with an 8-bit word offset.
+///
+///   31   27     23 22 21     19 15 11     7
+///   cond opcode U  D  opcode Rn Vd opcode imm8
+///   28     24   23 22     20 16 12      8    0
+///
+/// The `vd` argument is the `Vd:D` operand field.
+fn put_vmem<CS: CodeSink + ?Sized>(bits: u16, offset: i64, rn: RegUnit, vd: u32, sink: &mut CS) {
+    let (bits, imm) = if offset < 0 {
+        (bits & !U_BITS, -offset as u32)
+    } else {
+        (bits, offset as u32)
+    };
+    debug_assert!(imm & 3 == 0 && imm < (1 << 10));
+    put_a32(bits, self::rn(rn) | vd | imm >> 2, sink);
+}
+
+/// Is this the encoding of a VFP load or store?
+fn is_vfp_mem(bits: u16) -> bool {
+    bits & 0xe0 == 0xc0
+}
+
+/// Can a stack slot at `offset` from the stack pointer be accessed directly by a load or store
+/// with the given encoding bits?
+pub(crate) fn stack_offset_fits(bits: u16, offset: i32) -> bool {
+    if is_vfp_mem(bits) {
+        offset >= 0 && offset & 3 == 0 && offset < (1 << 10)
+    } else {
+        (0..(1 << 12)).contains(&offset)
+    }
+}
+
+/// Loads and stores of a register to a stack slot. The `rt` argument is the operand field of
+/// the register.
+///
+/// The address of the slots whose offset doesn't fit in the instruction is computed in the
+/// scratch register %r12 first.
+fn put_stack_access<CS: CodeSink + ?Sized>(bits: u16, stk: StackRef, rt: u32, sink: &mut CS) {
+    let sp = RU::r13.into();
+    let offset = stk.offset;
+    let base = if stack_offset_fits(bits, offset) {
+        sp
+    } else {
+        let scratch = RU::r12.into();
+        put_movw_movt(offset as u32, scratch, sink);
+        // add r12, r12, sp
+        sink.put4(0xe080_0000 | rn(scratch) | rd(scratch) | rm(sp));
+        scratch
+    };
+    let offset = if base == sp { offset.into() } else { 0 };
+    if is_vfp_mem(bits) {
+        put_vmem(bits, offset, base, rt, sink);
+    } else {
+        put_mem12(bits, offset, base, rt, sink);
+    }
+}
+
+/// Encode `imm` as a modified immediate: an 8-bit value rotated right by an even amount.
+///
+/// Returns the 12-bit operand field with the rotation in the high 4 bits, or `None` if the
+/// value can't be encoded.
+fn arm_imm(imm: u32) -> Option<u32> {
+    (0..16).find_map(|rot| {
+        let imm8 = imm.rotate_left(2 * rot);
+        if imm8 < (1 << 8) {
+            Some(rot << 8 | imm8)
+        } else {
+            None
+        }
+    })
+}
+
+/// `movw rd, #imm16`.
+fn put_movw<CS: CodeSink + ?Sized>(imm: u32, rd: RegUnit, sink: &mut CS) {
+    debug_assert!(imm < (1 << 16));
+    sink.put4(0xe300_0000 | (imm >> 12) << 16 | self::rd(rd) | imm & 0xfff);
+}
+
+/// `movt rd, #imm16`, setting the high half word of `rd`.
+fn put_movt<CS: CodeSink + ?Sized>(imm: u32, rd: RegUnit, sink: &mut CS) {
+    debug_assert!(imm < (1 << 16));
+    sink.put4(0xe340_0000 | (imm >> 12) << 16 | self::rd(rd) | imm & 0xfff);
+}
+
+/// Materialize `imm` with a `movw` and `movt` pair.
+fn put_movw_movt<CS: CodeSink + ?Sized>(imm: u32, rd: RegUnit, sink: &mut CS) {
+    put_movw(imm & 0xffff, rd, sink);
+    put_movt(imm >> 16, rd, sink);
+}
+
+/// Size of the instructions materializing `imm`.
+pub(crate) fn iconst_size(imm: u32) -> u8 {
+    if arm_imm(imm).is_some() || arm_imm(!imm).is_some() || imm >> 16 == 0 {
+        4
+    } else {
+        8
+    }
+}
+
+/// Materialize an integer constant with a `mov` or `mvn` of a modified immediate when possible,
+/// and with a `movw` and optional `movt` otherwise.
+fn put_iconst<CS: CodeSink + ?Sized>(imm: u32, rd: RegUnit, sink: &mut CS) {
+    if let Some(enc) = arm_imm(imm) {
+        sink.put4(0xe3a0_0000 | self::rd(rd) | enc);
+    } else if let Some(enc) = arm_imm(!imm) {
+        sink.put4(0xe3e0_0000 | self::rd(rd) | enc);
+    } else {
+        put_movw(imm & 0xffff, rd, sink);
+        if imm >> 16 != 0 {
+            put_movt(imm >> 16, rd, sink);
+        }
+    }
+}
+
+/// Size of the instructions computing the address at `offset` from the stack pointer.
+pub(crate) fn sp_offset_size(offset: u32) -> u8 {
+    if arm_imm(offset).is_some() {
+        4
+    } else {
+        12
+    }
+}
+
+/// Compute the address at `offset` from the stack pointer into `rd`.
+fn put_sp_offset<CS: CodeSink + ?Sized>(offset: u32, rd: RegUnit, sink: &mut CS) {
+    let sp = RU::r13.into();
+    if let Some(enc) = arm_imm(offset) {
+        // add rd, sp, #offset
+        sink.put4(0xe280_0000 | rn(sp) | self::rd(rd) | enc);
+    } else {
+        put_movw_movt(offset, rd, sink);
+        // add rd, rd, sp
+        sink.put4(0xe080_0000 | rn(rd) | self::rd(rd) | rm(sp));
+    }
+}
+
+/// Load an absolute address from a literal placed right after the instructions:
+///
+///   ldr rd, [pc]
+///   b #12
+///   .word address
+///
+/// The caller emits the 4-byte literal.
+fn put_literal_load<CS: CodeSink + ?Sized>(rd: RegUnit, sink: &mut CS) {
+    sink.put4(0xe59f_0000 | self::rd(rd));
+    sink.put4(0xea00_0000);
+}
+
+/// Convert an integer condition code to the A32 condition encoding.
+fn icc2cond(cond: IntCC) -> u32 {
+    use crate::ir::condcodes::IntCC::*;
+    match cond {
+        Equal => 0b0000,
+        NotEqual => 0b0001,
+        UnsignedGreaterThanOrEqual => 0b0010,
+        UnsignedLessThan => 0b0011,
+        UnsignedGreaterThan => 0b1000,
+        UnsignedLessThanOrEqual => 0b1001,
+        SignedGreaterThanOrEqual => 0b1010,
+        SignedLessThan => 0b1011,
+        SignedGreaterThan => 0b1100,
+        SignedLessThanOrEqual => 0b1101,
+    }
+}
+
+/// Convert a floating point condition code to the A32 condition encoding, as it is set by a
+/// `vcmp` instruction followed by a `vmrs`.
+///
+/// The `one` and `ueq` conditions can't be tested with a single condition, they are legalized.
+fn fcc2cond(cond: FloatCC) -> u32 {
+    use crate::ir::condcodes::FloatCC::*;
+    match cond {
+        Equal => 0b0000,                         // eq
+        NotEqual => 0b0001,                      // ne
+        LessThan => 0b0100,                      // mi
+        LessThanOrEqual => 0b1001,               // ls
+        GreaterThan => 0b1100,                   // gt
+        GreaterThanOrEqual => 0b1010,            // ge
+        Ordered => 0b0111,                       // vc
+        Unordered => 0b0110,                     // vs
+        UnorderedOrLessThan => 0b1011,           // lt
+        UnorderedOrLessThanOrEqual => 0b1101,    // le
+        UnorderedOrGreaterThan => 0b1000,        // hi
+        UnorderedOrGreaterThanOrEqual => 0b0101, // pl
+        OrderedNotEqual | UnorderedOrEqual => panic!("{} can't be tested directly", cond),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::arm_imm;
+
+    #[test]
+    fn modified_immediates() {
+        assert_eq!(arm_imm(0), Some(0));
+        assert_eq!(arm_imm(0xff), Some(0xff));
+        assert_eq!(arm_imm(0x100), Some(0xc01));
+        assert_eq!(arm_imm(0xff00_0000), Some(0x4ff));
+        assert_eq!(arm_imm(0xf000_000f), Some(0x2ff));
+        assert_eq!(arm_imm(0x101), None);
+        assert_eq!(arm_imm(0xffff), None);
+    }
+}
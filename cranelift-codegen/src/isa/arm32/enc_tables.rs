@@ -1,10 +1,393 @@
 //! Encoding tables for ARM32 ISA.
 
-use crate::ir;
+use super::binemit::{iconst_size, sp_offset_size, stack_offset_fits};
+use super::registers::*;
+use crate::bitset::BitSet;
+use crate::cursor::{Cursor, FuncCursor};
+use crate::flowgraph::ControlFlowGraph;
+use crate::ir::condcodes::{FloatCC, IntCC};
+use crate::ir::immediates::Offset32;
+use crate::ir::{self, Function, Inst, InstBuilder};
 use crate::isa;
 use crate::isa::constraints::*;
 use crate::isa::enc_tables::*;
-use crate::isa::encoding::RecipeSizing;
+use crate::isa::encoding::{base_size, RecipeSizing};
+use crate::isa::{StackRef, TargetIsa};
+use crate::legalizer::split;
+use crate::predicates;
+use crate::regalloc::RegDiversions;
+use std::vec::Vec;
 
 include!(concat!(env!("OUT_DIR"), "/encoding-arm32.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-arm32.rs"));
+
+/// Size of the instructions materializing an integer constant.
+fn size_for_iconst(
+    _sizing: &RecipeSizing,
+    inst: Inst,
+    _divert: &RegDiversions,
+    func: &Function,
+) -> u8 {
+    match func.dfg[inst] {
+        ir::InstructionData::UnaryImm { imm, .. } => {
+            let imm: i64 = imm.into();
+            iconst_size(imm as u32)
+        }
+        _ => panic!("Need iconst: {}", func.dfg.display_inst(inst, None)),
+    }
+}
+
+/// Size of a spill or fill, which needs the address to be computed first when the stack offset
+/// doesn't fit in the load or store instruction.
+fn size_for_stack_access(
+    sizing: &RecipeSizing,
+    inst: Inst,
+    divert: &RegDiversions,
+    func: &Function,
+) -> u8 {
+    let ss = match func.dfg[inst] {
+        ir::InstructionData::RegSpill { dst, .. } => dst,
+        ir::InstructionData::RegFill { src, .. } => src,
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::Spill,
+            ..
+        } => divert.stack(func.dfg.first_result(inst), &func.locations),
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::Fill,
+            arg,
+        } => divert.stack(arg, &func.locations),
+        _ => panic!(
+            "Need a spill or fill: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+    let offset = StackRef::sp(ss, &func.stack_slots).offset;
+    if stack_offset_fits(func.encodings[inst].bits(), offset) {
+        sizing.base_size
+    } else {
+        sizing.base_size + 12
+    }
+}
+
+/// Size of a `stack_addr`, which needs a `movw` and `movt` pair when the offset isn't a
+/// modified immediate.
+fn size_for_spaddr(
+    _sizing: &RecipeSizing,
+    inst: Inst,
+    _divert: &RegDiversions,
+    func: &Function,
+) -> u8 {
+    match func.dfg[inst] {
+        ir::InstructionData::StackLoad {
+            stack_slot, offset, ..
+        } => {
+            let sp = StackRef::sp(stack_slot, &func.stack_slots);
+            let offset: i32 = offset.into();
+            sp_offset_size(sp.offset.checked_add(offset).unwrap() as u32)
+        }
+        _ => panic!("Need stack_addr: {}", func.dfg.display_inst(inst, None)),
+    }
+}
+
+/// If the value's definition is a constant immediate, returns its unpacked value, or None
+/// otherwise.
+fn maybe_iconst_imm(pos: &FuncCursor, value: ir::Value) -> Option<i64> {
+    if let ir::ValueDef::Result(inst, _) = &pos.func.dfg.value_def(value) {
+        if let ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } = &pos.func.dfg[*inst]
+        {
+            let value: i64 = (*imm).into();
+            Some(value)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+/// Expand the `sdiv` and `srem` instructions using `arm32_sdiv`.
+///
+/// The arm32 division doesn't trap, so the division by zero and overflow checks are explicit.
+/// The remainder is computed from the quotient.
+fn expand_sdivrem(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (x, y, is_srem) = match func.dfg[inst] {
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Sdiv,
+            args,
+        } => (args[0], args[1], false),
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Srem,
+            args,
+        } => (args[0], args[1], true),
+        _ => panic!("Need sdiv/srem: {}", func.dfg.display_inst(inst, None)),
+    };
+    let result = func.dfg.first_result(inst);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    pos.func.dfg.clear_results(inst);
+
+    let (could_be_zero, could_be_minus_one) = if let Some(imm) = maybe_iconst_imm(&pos, y) {
+        (imm == 0, imm == -1)
+    } else {
+        (true, true)
+    };
+
+    if could_be_zero {
+        pos.ins().trapz(y, ir::TrapCode::IntegerDivisionByZero);
+    }
+
+    // `INT_MIN / -1` overflows. The remainder is 0 in that case, which is computed correctly
+    // from the wrapped quotient.
+    if could_be_minus_one && !is_srem {
+        let is_m1 = pos.ins().icmp_imm(IntCC::Equal, y, -1);
+        let is_min = pos
+            .ins()
+            .icmp_imm(IntCC::Equal, x, i64::from(i32::min_value()));
+        let overflow = pos.ins().band(is_m1, is_min);
+        pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+    }
+
+    if is_srem {
+        let quot = pos.ins().arm32_sdiv(x, y);
+        let prod = pos.ins().imul(quot, y);
+        pos.ins().with_result(result).isub(x, prod);
+    } else {
+        pos.ins().with_result(result).arm32_sdiv(x, y);
+    }
+    pos.remove_inst();
+}
+
+/// Expand the `udiv` and `urem` instructions using `arm32_udiv`.
+fn expand_udivrem(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (x, y, is_urem) = match func.dfg[inst] {
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Udiv,
+            args,
+        } => (args[0], args[1], false),
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Urem,
+            args,
+        } => (args[0], args[1], true),
+        _ => panic!("Need udiv/urem: {}", func.dfg.display_inst(inst, None)),
+    };
+    let result = func.dfg.first_result(inst);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    pos.func.dfg.clear_results(inst);
+
+    let could_be_zero = maybe_iconst_imm(&pos, y).map_or(true, |imm| imm == 0);
+    if could_be_zero {
+        pos.ins().trapz(y, ir::TrapCode::IntegerDivisionByZero);
+    }
+
+    if is_urem {
+        let quot = pos.ins().arm32_udiv(x, y);
+        let prod = pos.ins().imul(quot, y);
+        pos.ins().with_result(result).isub(x, prod);
+    } else {
+        pos.ins().with_result(result).arm32_udiv(x, y);
+    }
+    pos.remove_inst();
+}
+
+/// Expand the `fcvt_to_sint` and `fcvt_to_uint` instructions to 32-bit integers.
+///
+/// The `vcvt` instructions saturate and convert NaN to 0, so the values that can't be
+/// represented are checked explicitly before the saturating conversion.
+fn expand_fcvt_to_int(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    use crate::ir::immediates::{Ieee32, Ieee64};
+
+    let (x, is_signed) = match func.dfg[inst] {
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::FcvtToSint,
+            arg,
+        } => (arg, true),
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::FcvtToUint,
+            arg,
+        } => (arg, false),
+        _ => panic!(
+            "Need fcvt_to_sint/fcvt_to_uint: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+    let xty = func.dfg.value_type(x);
+    let result = func.dfg.first_result(inst);
+    let ty = func.dfg.value_type(result);
+    let output_bits = ty.lane_bits();
+    assert!(
+        output_bits <= 32,
+        "Can't legalize {}",
+        func.dfg.display_inst(inst, None)
+    );
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    pos.func.dfg.clear_results(inst);
+
+    // Check for NaN.
+    let is_nan = pos.ins().ffcmp(x, x);
+    pos.ins().trapff(
+        FloatCC::Unordered,
+        is_nan,
+        ir::TrapCode::BadConversionToInteger,
+    );
+
+    // Check the lower bound. Floats can represent `INT_MIN - 1` exactly for the narrower integer
+    // types, so there are values less than `INT_MIN` that still convert to it.
+    let (lower_cc, lower) = match (xty, is_signed) {
+        (ir::types::F32, true) if output_bits < 32 => (
+            FloatCC::LessThanOrEqual,
+            pos.ins()
+                .f32const(Ieee32::fcvt_to_sint_negative_overflow(output_bits)),
+        ),
+        (ir::types::F32, true) => (
+            FloatCC::LessThan,
+            pos.ins().f32const(Ieee32::pow2(output_bits - 1).neg()),
+        ),
+        (ir::types::F64, true) => (
+            FloatCC::LessThanOrEqual,
+            pos.ins()
+                .f64const(Ieee64::fcvt_to_sint_negative_overflow(output_bits)),
+        ),
+        (ir::types::F32, false) => (
+            FloatCC::LessThanOrEqual,
+            pos.ins().f32const(Ieee32::with_float(-1.0)),
+        ),
+        (ir::types::F64, false) => (
+            FloatCC::LessThanOrEqual,
+            pos.ins().f64const(Ieee64::with_float(-1.0)),
+        ),
+        _ => panic!("Can't convert {}", xty),
+    };
+    let flags = pos.ins().ffcmp(x, lower);
+    pos.ins()
+        .trapff(lower_cc, flags, ir::TrapCode::IntegerOverflow);
+
+    // Check the upper bound, which is a power of two and exactly representable.
+    let upper_bits = if is_signed {
+        output_bits - 1
+    } else {
+        output_bits
+    };
+    let upper = match xty {
+        ir::types::F32 => pos.ins().f32const(Ieee32::pow2(upper_bits)),
+        ir::types::F64 => pos.ins().f64const(Ieee64::pow2(upper_bits)),
+        _ => panic!("Can't convert {}", xty),
+    };
+    let flags = pos.ins().ffcmp(x, upper);
+    pos.ins().trapff(
+        FloatCC::GreaterThanOrEqual,
+        flags,
+        ir::TrapCode::IntegerOverflow,
+    );
+
+    // The value is in range, the saturating conversion is exact.
+    if is_signed {
+        pos.ins().with_result(result).fcvt_to_sint_sat(ty, x);
+    } else {
+        pos.ins().with_result(result).fcvt_to_uint_sat(ty, x);
+    }
+    pos.remove_inst();
+}
+
+/// Expand an `ireduce` from 64 bits, which is the low half of the split value.
+fn expand_ireduce(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let arg = match func.dfg[inst] {
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::Ireduce,
+            arg,
+        } => func.dfg.resolve_aliases(arg),
+        _ => panic!("Need ireduce: {}", func.dfg.display_inst(inst, None)),
+    };
+    let result = func.dfg.first_result(inst);
+    assert_eq!(
+        func.dfg.value_type(arg),
+        ir::types::I64,
+        "Can't legalize {}",
+        func.dfg.display_inst(inst, None)
+    );
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    pos.func.dfg.clear_results(inst);
+    pos.remove_inst();
+
+    let curpos = pos.position();
+    let srcloc = pos.srcloc();
+    let (lo, _) = split::isplit(pos.func, cfg, curpos, srcloc, arg);
+    pos.func.dfg.change_to_alias(result, lo);
+}
+
+/// Expand loads and stores whose offset can't be encoded in the instruction, by adding the
+/// offset to the address first.
+fn expand_mem_offset(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (addr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Load { arg, offset, .. } => (arg, offset),
+        ir::InstructionData::Store { args, offset, .. } => (args[1], offset),
+        _ => panic!(
+            "Need a load or a store: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+    let offset: i64 = offset.into();
+    assert!(
+        offset != 0,
+        "Can't legalize {}",
+        func.dfg.display_inst(inst, None)
+    );
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let new_addr = pos.ins().iadd_imm(addr, offset);
+
+    match pos.func.dfg[inst] {
+        ir::InstructionData::Load {
+            ref mut arg,
+            ref mut offset,
+            ..
+        } => {
+            *arg = new_addr;
+            *offset = Offset32::new(0);
+        }
+        ir::InstructionData::Store {
+            ref mut args,
+            ref mut offset,
+            ..
+        } => {
+            args[1] = new_addr;
+            *offset = Offset32::new(0);
+        }
+        _ => unreachable!(),
+    }
+}
@@ -1,30 +1,357 @@
 //! ARM 64 ABI implementation.
+//!
+//! This module implements the AAPCS64 procedure call standard, as used by the System V ABI on
+//! Linux and other Unix-like systems.
 
-use super::registers::{FPR, GPR};
+use super::registers::{FPR, GPR, RU};
+use crate::abi::{legalize_args, ArgAction, ArgAssigner, ValueConversion};
+use crate::cursor::{Cursor, CursorPosition, EncCursor};
 use crate::ir;
-use crate::isa::RegClass;
+use crate::ir::immediates::Imm64;
+use crate::ir::types::{F64, I64};
+use crate::ir::{AbiParam, ArgumentExtension, ArgumentLoc, ArgumentPurpose, InstBuilder, ValueLoc};
+use crate::isa::{CallConv, RegClass, RegUnit, TargetIsa};
 use crate::regalloc::RegisterSet;
+use crate::result::{CodegenError, CodegenResult};
 use crate::settings as shared_settings;
+use crate::stack_layout::layout_stack;
+use core::i32;
+use std::vec::Vec;
+
+/// Number of registers used for passing integer and floating point arguments: `%x0-%x7` and
+/// `%v0-%v7`.
+const ARG_REG_LIMIT: usize = 8;
+
+/// Callee-saved general purpose registers.
+static CALLEE_SAVED_GPRS: [RU; 10] = [
+    RU::x19,
+    RU::x20,
+    RU::x21,
+    RU::x22,
+    RU::x23,
+    RU::x24,
+    RU::x25,
+    RU::x26,
+    RU::x27,
+    RU::x28,
+];
+
+/// Callee-saved floating point registers. Only the low 64 bits of `%v8-%v15` need to be
+/// preserved.
+static CALLEE_SAVED_FPRS: [RU; 8] = [
+    RU::v8,
+    RU::v9,
+    RU::v10,
+    RU::v11,
+    RU::v12,
+    RU::v13,
+    RU::v14,
+    RU::v15,
+];
+
+struct Args {
+    gpr_used: usize,
+    fpr_used: usize,
+    offset: u32,
+}
+
+impl Args {
+    fn new() -> Self {
+        Self {
+            gpr_used: 0,
+            fpr_used: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl ArgAssigner for Args {
+    fn assign(&mut self, arg: &AbiParam) -> ArgAction {
+        fn align(value: usize, to: usize) -> usize {
+            (value + to - 1) & !(to - 1)
+        }
+
+        let ty = arg.value_type;
+
+        // SIMD isn't supported yet, so break all vectors down.
+        if ty.is_vector() {
+            return ValueConversion::VectorSplit.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register. They are passed in an
+        // even-numbered pair of registers.
+        if !ty.is_float() && ty.bits() > 64 {
+            self.gpr_used = align(self.gpr_used, 2);
+            return ValueConversion::IntSplit.into();
+        }
+
+        // Small integers are extended to the size of a register.
+        if ty.is_int() && ty.bits() < 64 {
+            match arg.extension {
+                ArgumentExtension::None => {}
+                ArgumentExtension::Uext => return ValueConversion::Uext(I64).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(I64).into(),
+            }
+        }
+
+        if ty.is_float() {
+            if self.fpr_used < ARG_REG_LIMIT {
+                let reg = FPR.unit(self.fpr_used);
+                self.fpr_used += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
+        } else if self.gpr_used < ARG_REG_LIMIT {
+            let reg = GPR.unit(self.gpr_used);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
+        }
+
+        // Assign a stack location. Each argument takes at least 8 bytes.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += 8;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
+}
 
 /// Legalize `sig`.
 pub fn legalize_signature(
-    _sig: &mut ir::Signature,
+    sig: &mut ir::Signature,
     _flags: &shared_settings::Flags,
     _current: bool,
 ) {
-    unimplemented!()
+    let mut args = Args::new();
+    legalize_args(&mut sig.params, &mut args);
+
+    let mut rets = Args::new();
+    legalize_args(&mut sig.returns, &mut rets);
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() {
-        GPR
-    } else {
+    if ty.is_float() {
         FPR
+    } else {
+        GPR
     }
 }
 
 /// Get the set of allocatable registers for `func`.
 pub fn allocatable_registers(_func: &ir::Function) -> RegisterSet {
-    unimplemented!()
+    let mut regs = RegisterSet::new();
+    regs.take(GPR, RU::x16 as RegUnit); // Scratch register for large stack offsets.
+    regs.take(GPR, RU::x18 as RegUnit); // Platform register.
+    regs.take(GPR, RU::x29 as RegUnit); // Frame pointer.
+    regs.take(GPR, RU::x31 as RegUnit); // Stack pointer or zero register.
+    regs
+}
+
+/// Get the set of callee-saved registers that are used.
+fn callee_saved_regs_used(func: &ir::Function) -> RegisterSet {
+    let mut all_callee_saved = RegisterSet::empty();
+    for reg in &CALLEE_SAVED_GPRS {
+        all_callee_saved.free(GPR, *reg as RegUnit);
+    }
+    for reg in &CALLEE_SAVED_FPRS {
+        all_callee_saved.free(FPR, *reg as RegUnit);
+    }
+
+    let mut used = RegisterSet::empty();
+    for value_loc in func.locations.values() {
+        // All the arm64 registers have a single unit, so the GPR class can be used to note any
+        // of them.
+        if let ValueLoc::Reg(ru) = *value_loc {
+            if !used.is_avail(GPR, ru) {
+                used.free(GPR, ru);
+            }
+        }
+    }
+
+    // regmove and regfill instructions may temporarily divert values into other registers,
+    // and these are not reflected in `func.locations`.
+    for ebb in &func.layout {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::instructions::InstructionData::RegMove { dst, .. }
+                | ir::instructions::InstructionData::RegFill { dst, .. } => {
+                    if !used.is_avail(GPR, dst) {
+                        used.free(GPR, dst);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    used.intersect(&all_callee_saved);
+    used
+}
+
+/// Insert a prologue and epilogues, and lay out the stack frame.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    match func.signature.call_conv {
+        // For now, just translate fast and cold as system_v.
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV => {
+            system_v_prologue_epilogue(func, isa)
+        }
+        call_conv => unimplemented!("{} calling convention on arm64", call_conv),
+    }
+}
+
+/// Insert an AAPCS64 prologue and epilogue.
+///
+/// The frame record made of the frame pointer and the link register is pushed first, followed
+/// by the callee-saved registers, in pairs when possible. The stack pointer stays 16-byte
+/// aligned.
+fn system_v_prologue_epilogue(func: &mut ir::Function, isa: &dyn TargetIsa) -> CodegenResult<()> {
+    let stack_align = 16;
+
+    let csrs = callee_saved_regs_used(func);
+    let gprs: Vec<RegUnit> = csrs.iter(GPR).collect();
+    let fprs: Vec<RegUnit> = csrs.iter(FPR).collect();
+
+    // The reserved stack area is composed of the frame record and the callee-saved registers,
+    // each pushed pair taking 16 bytes.
+    let csr_stack_size = (16 + 16 * ((gprs.len() + 1) / 2 + (fprs.len() + 1) / 2)) as i32;
+    func.create_stack_slot(ir::StackSlotData {
+        kind: ir::StackSlotKind::IncomingArg,
+        size: csr_stack_size as u32,
+        offset: Some(-csr_stack_size),
+    });
+
+    let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
+    let local_stack_size = i64::from(total_stack_size - csr_stack_size);
+
+    // The stack adjustment is encoded in two 12-bit immediates at most.
+    if local_stack_size >= 1 << 24 {
+        return Err(CodegenError::ImplLimitExceeded);
+    }
+
+    // Add the frame record and the CSRs to the function signature.
+    let fp_arg = AbiParam::special_reg(I64, ArgumentPurpose::FramePointer, RU::x29 as RegUnit);
+    func.signature.params.push(fp_arg);
+    func.signature.returns.push(fp_arg);
+
+    let link_arg = AbiParam::special_reg(I64, ArgumentPurpose::Link, RU::x30 as RegUnit);
+    func.signature.params.push(link_arg);
+    func.signature.returns.push(link_arg);
+
+    for &csr in &gprs {
+        let csr_arg = AbiParam::special_reg(I64, ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+    for &csr in &fprs {
+        let csr_arg = AbiParam::special_reg(F64, ArgumentPurpose::CalleeSaved, csr);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+    }
+
+    // Set up the cursor and insert the prologue
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    insert_prologue(&mut pos, local_stack_size, &gprs, &fprs);
+
+    // Reset the cursor and insert the epilogue
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    insert_epilogues(&mut pos, local_stack_size, &gprs, &fprs);
+
+    Ok(())
+}
+
+/// Append a parameter for the register `reg` to the entry EBB.
+fn append_reg_param(pos: &mut EncCursor, ty: ir::Type, reg: RegUnit) -> ir::Value {
+    let ebb = pos.current_ebb().expect("missing ebb under cursor");
+    let param = pos.func.dfg.append_ebb_param(ebb, ty);
+    pos.func.locations[param] = ValueLoc::Reg(reg);
+    param
+}
+
+/// Push the registers `regs` of type `ty` in pairs, and the last one alone if their number is
+/// odd.
+fn push_regs(pos: &mut EncCursor, ty: ir::Type, regs: &[RegUnit]) {
+    for pair in regs.chunks(2) {
+        let x = append_reg_param(pos, ty, pair[0]);
+        if let Some(&reg) = pair.get(1) {
+            let y = append_reg_param(pos, ty, reg);
+            pos.ins().arm64_push_pair(x, y);
+        } else {
+            pos.ins().arm64_push(x);
+        }
+    }
+}
+
+/// Insert the prologue for a given function.
+fn insert_prologue(pos: &mut EncCursor, stack_size: i64, gprs: &[RegUnit], fprs: &[RegUnit]) {
+    let fp = append_reg_param(pos, I64, RU::x29 as RegUnit);
+    let lr = append_reg_param(pos, I64, RU::x30 as RegUnit);
+    pos.ins().arm64_push_pair(fp, lr);
+    pos.ins()
+        .copy_special(RU::x31 as RegUnit, RU::x29 as RegUnit);
+
+    push_regs(pos, I64, gprs);
+    push_regs(pos, F64, fprs);
+
+    // Allocate stack frame storage.
+    if stack_size > 0 {
+        pos.ins().adjust_sp_down_imm(Imm64::new(stack_size));
+    }
+}
+
+/// Find all `return` instructions and insert epilogues before them.
+fn insert_epilogues(pos: &mut EncCursor, stack_size: i64, gprs: &[RegUnit], fprs: &[RegUnit]) {
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        if let Some(inst) = pos.current_inst() {
+            if pos.func.dfg[inst].opcode().is_return() {
+                insert_epilogue(inst, stack_size, pos, gprs, fprs);
+            }
+        }
+    }
+}
+
+/// Pop a pair of registers, inserting the instruction before the previously inserted ones, and
+/// pass them to the `return` instruction `inst`.
+fn pop_pair(pos: &mut EncCursor, inst: ir::Inst, ty: ir::Type, x_reg: RegUnit, y_reg: RegUnit) {
+    let (x, y) = pos.ins().arm64_pop_pair(ty);
+    pos.prev_inst();
+
+    pos.func.locations[x] = ValueLoc::Reg(x_reg);
+    pos.func.locations[y] = ValueLoc::Reg(y_reg);
+    pos.func.dfg.append_inst_arg(inst, x);
+    pos.func.dfg.append_inst_arg(inst, y);
+}
+
+/// Pop the registers `regs` of type `ty` pushed by `push_regs`, in reverse order.
+fn pop_regs(pos: &mut EncCursor, inst: ir::Inst, ty: ir::Type, regs: &[RegUnit]) {
+    for pair in regs.chunks(2) {
+        if let Some(&reg) = pair.get(1) {
+            pop_pair(pos, inst, ty, pair[0], reg);
+        } else {
+            let x = pos.ins().arm64_pop(ty);
+            pos.prev_inst();
+
+            pos.func.locations[x] = ValueLoc::Reg(pair[0]);
+            pos.func.dfg.append_inst_arg(inst, x);
+        }
+    }
+}
+
+/// Insert an epilogue given a specific `return` instruction.
+fn insert_epilogue(
+    inst: ir::Inst,
+    stack_size: i64,
+    pos: &mut EncCursor,
+    gprs: &[RegUnit],
+    fprs: &[RegUnit],
+) {
+    if stack_size > 0 {
+        pos.ins().adjust_sp_up_imm(Imm64::new(stack_size));
+    }
+
+    // Pop all the callee-saved registers, stepping backward each time to preserve the correct
+    // order. The return arguments must follow the order of the signature.
+    pop_pair(pos, inst, I64, RU::x29 as RegUnit, RU::x30 as RegUnit);
+    pop_regs(pos, inst, I64, gprs);
+    pop_regs(pos, inst, F64, fprs);
 }
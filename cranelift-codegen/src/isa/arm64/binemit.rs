@@ -1,8 +1,335 @@
 //! Emitting binary ARM64 machine code.
 
-use crate::binemit::{bad_encoding, CodeSink};
-use crate::ir::{Function, Inst};
-use crate::isa::TargetIsa;
+use super::registers::RU;
+use crate::binemit::{bad_encoding, CodeSink, Reloc};
+use crate::ir::condcodes::{CondCode, FloatCC, IntCC};
+use crate::ir::{Function, Inst, InstructionData, TrapCode};
+use crate::isa::{RegUnit, StackBaseMask, StackRef, TargetIsa};
 use crate::regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm64.rs"));
+
+/// The `sf` bit selecting the 64-bit form of an instruction, in the `hi_bits` layout.
+pub(crate) const SF_BITS: u16 = 1 << 15;
+
+/// The `sh` bit of the add/subtract immediate instructions shifting the immediate left by 12,
+/// in the `hi_bits` layout.
+const SH_BITS: u16 = 1 << 6;
+
+/// Register operand field for `Rd` and `Rt`.
+fn rd(reg: RegUnit) -> u32 {
+    u32::from(reg) & 0x1f
+}
+
+/// Register operand field for `Rn`.
+fn rn(reg: RegUnit) -> u32 {
+    (u32::from(reg) & 0x1f) << 5
+}
+
+/// Register operand field for `Rt2` in the load/store pair instructions.
+fn rt2(reg: RegUnit) -> u32 {
+    (u32::from(reg) & 0x1f) << 10
+}
+
+/// Instructions with three register operands.
+///
+///   31    20 15     9  4
+///   opcode Rm opcode Rn Rd
+///       21 16     10  5  0
+///
+/// Encoding bits: `w[31:21] | w[14:10] << 11`. Bit 15 of the instruction is always 0.
+fn put_rrr<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = (bits & 0x7ff) << 21;
+    i |= ((bits >> 11) & 0x1f) << 10;
+    i |= (u32::from(rm) & 0x1f) << 16;
+    i |= self::rn(rn);
+    i |= self::rd(rd);
+    sink.put4(i);
+}
+
+/// Floating point instructions with two register operands.
+///
+///   31 30  28     14     9  4
+///   sf 00  opcode 0000.. Rn Rd
+///   31     15          10  5  0
+///
+/// Encoding bits: `w[31] << 15 | w[28:15] << 1 | w[14]`.
+fn put_rr<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let mut i = (bits >> 15) << 31;
+    i |= ((bits >> 1) & 0x3fff) << 15;
+    i |= (bits & 1) << 14;
+    i |= self::rn(rn);
+    i |= self::rd(rd);
+    sink.put4(i);
+}
+
+/// Conditional select instructions.
+///
+///   31    20 15   11 9  4
+///   opcode Rm cond op Rn Rd
+///       21 16   12 10  5  0
+///
+/// Encoding bits: `w[31:21] | w[11:10] << 11`.
+fn put_csel<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    cond: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let mut i = (bits & 0x7ff) << 21;
+    i |= ((bits >> 11) & 0x3) << 10;
+    i |= (u32::from(rm) & 0x1f) << 16;
+    i |= cond << 12;
+    i |= self::rn(rn);
+    i |= self::rd(rd);
+    sink.put4(i);
+}
+
+/// `cset rd, cond`, which is an alias of `csinc wd, wzr, wzr, !cond`.
+fn put_cset<CS: CodeSink + ?Sized>(cond: u32, rd: RegUnit, sink: &mut CS) {
+    sink.put4(0x1a9f_07e0 | (cond ^ 1) << 12 | self::rd(rd));
+}
+
+/// Instructions where the low half word is computed by the recipe.
+///
+/// Encoding bits: `w[31:16]`.
+fn put_hi<CS: CodeSink + ?Sized>(bits: u16, lo: u32, sink: &mut CS) {
+    sink.put4(u32::from(bits) << 16 | lo);
+}
+
+/// Unconditional branches with a 26-bit word offset.
+///
+///   31     25
+///   opcode imm26
+///       26     0
+fn put_b<CS: CodeSink + ?Sized>(bits: u16, disp: i64, sink: &mut CS) {
+    debug_assert!(disp & 3 == 0 && (-(1 << 27)..(1 << 27)).contains(&disp));
+    put_hi(bits, (disp >> 2) as u32 & 0x3ff_ffff, sink);
+}
+
+/// Conditional branches with a 19-bit word offset.
+///
+///   31     23    4 3
+///   opcode imm19 0 cond
+///       24     5      0
+fn put_bcond<CS: CodeSink + ?Sized>(bits: u16, disp: i64, cond: u32, sink: &mut CS) {
+    debug_assert!(disp & 3 == 0 && (-(1 << 20)..(1 << 20)).contains(&disp));
+    put_hi(bits, ((disp >> 2) as u32 & 0x7_ffff) << 5 | cond, sink);
+}
+
+/// Compare and branch instructions with a 19-bit word offset.
+///
+///   31     23    4
+///   opcode imm19 Rt
+///       24     5  0
+fn put_cbz<CS: CodeSink + ?Sized>(bits: u16, disp: i64, rt: RegUnit, sink: &mut CS) {
+    debug_assert!(disp & 3 == 0 && (-(1 << 20)..(1 << 20)).contains(&disp));
+    put_hi(bits, ((disp >> 2) as u32 & 0x7_ffff) << 5 | rd(rt), sink);
+}
+
+/// PC-relative address computation with a 21-bit byte offset.
+///
+///   31 30    28     23    4
+///   op immlo opcode immhi Rd
+///   31    29     24     5  0
+fn put_adr<CS: CodeSink + ?Sized>(bits: u16, disp: i64, rd: RegUnit, sink: &mut CS) {
+    debug_assert!((-(1 << 20)..(1 << 20)).contains(&disp));
+    let disp = disp as u32;
+    let mut i = (disp & 3) << 29;
+    i |= ((disp >> 2) & 0x7_ffff) << 5;
+    i |= self::rd(rd);
+    put_hi(bits, i, sink);
+}
+
+/// Loads and stores with a scaled unsigned 12-bit offset.
+///
+///   31   29     21    9  4
+///   size opcode imm12 Rn Rt
+///   30        22    10  5  0
+///
+/// The offset is scaled by the access size in bits 31:30.
+fn put_mem_scaled<CS: CodeSink + ?Sized>(
+    bits: u16,
+    offset: i64,
+    rn: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let scale = bits >> 14;
+    debug_assert!(offset >= 0 && offset & ((1 << scale) - 1) == 0);
+    let imm12 = (offset >> scale) as u32;
+    debug_assert!(imm12 < (1 << 12));
+    put_hi(bits, imm12 << 10 | self::rn(rn) | rd(rt), sink);
+}
+
+/// Loads and stores with an unscaled signed 9-bit offset.
+///
+///   31     20   11 9  4
+///   opcode imm9 00 Rn Rt
+///       21   12 10  5  0
+fn put_mem_unscaled<CS: CodeSink + ?Sized>(
+    bits: u16,
+    offset: i64,
+    rn: RegUnit,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    debug_assert!(offset >= -(1 << 8) && offset < (1 << 8));
+    put_hi(
+        bits,
+        (offset as u32 & 0x1ff) << 12 | self::rn(rn) | rd(rt),
+        sink,
+    );
+}
+
+/// Can a stack slot at `offset` from the stack pointer be accessed directly with the scaled
+/// unsigned offset form of a load or store with the given encoding bits?
+pub(crate) fn stack_offset_fits(bits: u16, offset: i32) -> bool {
+    let scale = bits >> 14;
+    offset >= 0 && offset & ((1 << scale) - 1) == 0 && (offset >> scale) < (1 << 12)
+}
+
+/// Loads and stores of a register to a stack slot.
+///
+/// Offsets that don't fit in the instruction are split: the high 12 bits are added to the stack
+/// pointer into the scratch register %x16 first.
+fn put_stack_access<CS: CodeSink + ?Sized>(bits: u16, stk: StackRef, rt: RegUnit, sink: &mut CS) {
+    let sp = RU::x31 as RegUnit;
+    let offset = stk.offset;
+    if stack_offset_fits(bits, offset) {
+        put_mem_scaled(bits, offset.into(), sp, rt, sink);
+    } else {
+        debug_assert!(offset >= 0 && offset < (1 << 24));
+        let scratch = RU::x16 as RegUnit;
+        // add x16, sp, #hi, lsl #12
+        put_hi(
+            0x9140,
+            ((offset as u32) >> 12) << 10 | rn(sp) | rd(scratch),
+            sink,
+        );
+        put_mem_scaled(bits, (offset & 0xfff).into(), scratch, rt, sink);
+    }
+}
+
+/// Load an absolute address from a literal placed right after the instructions:
+///
+///   ldr rd, #8
+///   b #12
+///   .quad address
+///
+/// The caller emits the 8-byte literal.
+fn put_literal_load<CS: CodeSink + ?Sized>(rd: RegUnit, sink: &mut CS) {
+    sink.put4(0x5800_0040 | self::rd(rd));
+    sink.put4(0x1400_0003);
+}
+
+/// Plan the `movz`/`movn` and `movk` instructions materializing `imm`.
+///
+/// Returns the value to be materialized, the number of 16-bit half words it is made of, and the
+/// half word value that doesn't need a `movk`: 0 when starting from a `movz`, or 0xffff when
+/// starting from a `movn`.
+fn iconst_plan(imm: i64, is_64: bool) -> (u64, u32, u64) {
+    let (value, halfwords) = if is_64 {
+        (imm as u64, 4)
+    } else {
+        (u64::from(imm as u32), 2)
+    };
+    let count = |hw: u64| {
+        (0..halfwords)
+            .filter(|i| (value >> (16 * i)) & 0xffff == hw)
+            .count()
+    };
+    let skip = if count(0xffff) > count(0) { 0xffff } else { 0 };
+    (value, halfwords, skip)
+}
+
+/// Number of instructions needed to materialize `imm`.
+pub(crate) fn iconst_size(imm: i64, is_64: bool) -> u8 {
+    let (value, halfwords, skip) = iconst_plan(imm, is_64);
+    let insts = (0..halfwords)
+        .filter(|i| (value >> (16 * i)) & 0xffff != skip)
+        .count();
+    4 * insts.max(1) as u8
+}
+
+/// Materialize an integer constant with a `movz` or `movn` followed by `movk` instructions.
+///
+///   31 30  28     22 20     4
+///   sf opc opcode hw imm16 Rd
+///   31  29     23 21     5  0
+///
+/// Encoding bits: `w[31:16]` of the `movz` instruction.
+fn put_iconst<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let movz = u32::from(bits) << 16;
+    let movn = movz & !(1 << 30);
+    let movk = movz | (1 << 29);
+    let (value, halfwords, skip) = iconst_plan(imm, bits & SF_BITS != 0);
+
+    let mut first = true;
+    for i in 0..halfwords {
+        let hw = (value >> (16 * i)) & 0xffff;
+        if hw == skip {
+            continue;
+        }
+        let (op, imm16) = if !first {
+            (movk, hw)
+        } else if skip == 0 {
+            (movz, hw)
+        } else {
+            (movn, !hw & 0xffff)
+        };
+        sink.put4(op | i << 21 | (imm16 as u32) << 5 | self::rd(rd));
+        first = false;
+    }
+
+    if first {
+        // All the half words are 0 or all are 0xffff.
+        let op = if skip == 0 { movz } else { movn };
+        sink.put4(op | self::rd(rd));
+    }
+}
+
+/// Convert an integer condition code to the A64 condition encoding.
+fn icc2cond(cond: IntCC) -> u32 {
+    use crate::ir::condcodes::IntCC::*;
+    match cond {
+        Equal => 0b0000,
+        NotEqual => 0b0001,
+        UnsignedGreaterThanOrEqual => 0b0010,
+        UnsignedLessThan => 0b0011,
+        UnsignedGreaterThan => 0b1000,
+        UnsignedLessThanOrEqual => 0b1001,
+        SignedGreaterThanOrEqual => 0b1010,
+        SignedLessThan => 0b1011,
+        SignedGreaterThan => 0b1100,
+        SignedLessThanOrEqual => 0b1101,
+    }
+}
+
+/// Convert a floating point condition code to the A64 condition encoding, as it is set by an
+/// `fcmp` instruction.
+///
+/// The `one` and `ueq` conditions can't be tested with a single condition, they are legalized.
+fn fcc2cond(cond: FloatCC) -> u32 {
+    use crate::ir::condcodes::FloatCC::*;
+    match cond {
+        Equal => 0b0000,                         // eq
+        NotEqual => 0b0001,                      // ne
+        LessThan => 0b0100,                      // mi
+        LessThanOrEqual => 0b1001,               // ls
+        GreaterThan => 0b1100,                   // gt
+        GreaterThanOrEqual => 0b1010,            // ge
+        Ordered => 0b0111,                       // vc
+        Unordered => 0b0110,                     // vs
+        UnorderedOrLessThan => 0b1011,           // lt
+        UnorderedOrLessThanOrEqual => 0b1101,    // le
+        UnorderedOrGreaterThan => 0b1000,        // hi
+        UnorderedOrGreaterThanOrEqual => 0b0101, // pl
+        OrderedNotEqual | UnorderedOrEqual => panic!("{} can't be tested directly", cond),
+    }
+}
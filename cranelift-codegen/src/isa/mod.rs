@@ -251,6 +251,11 @@ pub trait TargetIsa: fmt::Display + Sync {
         false
     }
 
+    /// Can thread-local global values be accessed on this target?
+    fn supports_tls(&self) -> bool {
+        false
+    }
+
     /// Get a data structure describing the registers in this ISA.
     fn register_info(&self) -> RegInfo;
 
@@ -324,6 +329,11 @@ pub trait TargetIsa: fmt::Display + Sync {
     /// that `legalize_signature()` produces in `ArgumentLoc::Reg` entries.
     fn regclass_for_abi_type(&self, ty: ir::Type) -> RegClass;
 
+    /// Get the DWARF register number of the register unit `reg`, if it has one.
+    fn map_dwarf_register(&self, _reg: RegUnit) -> Option<u16> {
+        None
+    }
+
     /// Get the set of allocatable registers that can be used when compiling `func`.
     ///
     /// This set excludes reserved registers like the stack pointer and other special-purpose
@@ -371,4 +381,16 @@ pub trait TargetIsa: fmt::Display + Sync {
 
     /// Emit a whole function into memory.
     fn emit_function_to_memory(&self, func: &ir::Function, sink: &mut binemit::MemoryCodeSink);
+
+    /// Emit unwind information for the given function.
+    ///
+    /// Only some calling conventions are supported; for the others nothing is emitted.
+    fn emit_unwind_info(
+        &self,
+        _func: &ir::Function,
+        _kind: binemit::FrameUnwindKind,
+        _sink: &mut dyn binemit::FrameUnwindSink,
+    ) {
+        // No-op by default
+    }
 }
@@ -3,6 +3,11 @@
 //! This module implements the RISC-V calling convention through the primary `legalize_signature()`
 //! entry point.
 //!
+//! Floating point values follow the hard-float ABIs (LP64D for RV64, ILP32D for RV32): integers
+//! are passed in `a0`-`a7` and floats in `fa0`-`fa7`, with separate counts for the two register
+//! files. Once a register file is exhausted its arguments go on the stack; the psABI rule that
+//! moves floats to the free integer registers first isn't implemented.
+//!
 //! This doesn't support the soft-float ABI at the moment.
 
 use super::registers::{FPR, GPR};
@@ -20,6 +25,8 @@ struct Args {
     pointer_type: Type,
     regs: u32,
     reg_limit: u32,
+    fpr_regs: u32,
+    fpr_limit: u32,
     offset: u32,
 }
 
@@ -31,6 +38,8 @@ impl Args {
             pointer_type: Type::int(u16::from(bits)).unwrap(),
             regs: 0,
             reg_limit: if enable_e { 6 } else { 8 },
+            fpr_regs: 0,
+            fpr_limit: 8,
             offset: 0,
         }
     }
@@ -67,19 +76,22 @@ impl ArgAssigner for Args {
             }
         }
 
-        if self.regs < self.reg_limit {
-            // Assign to a register.
-            let reg = if ty.is_float() {
-                FPR.unit(10 + self.regs as usize)
-            } else {
-                GPR.unit(10 + self.regs as usize)
-            };
+        if ty.is_float() && self.fpr_regs < self.fpr_limit {
+            // Assign to a floating point register.
+            let reg = FPR.unit(10 + self.fpr_regs as usize);
+            self.fpr_regs += 1;
+            ArgumentLoc::Reg(reg).into()
+        } else if !ty.is_float() && self.regs < self.reg_limit {
+            // Assign to an integer register.
+            let reg = GPR.unit(10 + self.regs as usize);
             self.regs += 1;
             ArgumentLoc::Reg(reg).into()
         } else {
-            // Assign a stack location.
+            // Assign a stack location, naturally aligned and at least pointer-sized.
+            let size = u32::from(self.pointer_bytes).max(ty.bytes());
+            self.offset = align(self.offset, size);
             let loc = ArgumentLoc::Stack(self.offset as i32);
-            self.offset += u32::from(self.pointer_bytes);
+            self.offset += size;
             debug_assert!(self.offset <= i32::MAX as u32);
             loc.into()
         }
@@ -1,8 +1,8 @@
 //! Emitting binary RISC-V machine code.
 
 use crate::binemit::{bad_encoding, CodeSink, Reloc};
-use crate::ir::{Function, Inst, InstructionData};
-use crate::isa::{RegUnit, StackBaseMask, StackRef, TargetIsa};
+use crate::ir::{Function, Inst, InstructionData, TrapCode};
+use crate::isa::{RegUnit, StackBase, StackBaseMask, StackRef, TargetIsa};
 use crate::predicates::is_signed_int;
 use crate::regalloc::RegDiversions;
 use core::u32;
@@ -37,6 +37,17 @@ fn put_r<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, rs2: RegUnit, rd: RegUn
     sink.put4(i);
 }
 
+/// R-type instructions with a fixed rs2 field and no funct3, such as the floating point
+/// conversions and moves.
+///
+/// Encoding bits: `opcode[6:2] | (rs2 << 5) | (funct7 << 8)`.
+///
+/// The funct3 field holds the rounding mode of the conversions, which is always RNE (0b000).
+fn put_r_unary<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let rs2 = (bits >> 5) & 0x7;
+    put_r(bits & !0xe0, rs1, rs2, rd, sink);
+}
+
 /// R-type instructions with a shift amount instead of rs2.
 ///
 ///   31     25    19  14     11 6
@@ -98,6 +109,35 @@ fn put_i<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rd: RegUnit,
     sink.put4(i);
 }
 
+/// S-type store instructions.
+///
+///   31  24  19  14     11  6
+///   imm rs2 rs1 funct3 imm opcode
+///    25  20  15     12   7      0
+///
+/// The stored value is in rs2, and the address is `rs1 + imm`.
+///
+/// Encoding bits: `opcode[6:2] | (funct3 << 5)`
+fn put_s<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, imm: i64, rs2: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let opcode5 = bits & 0x1f;
+    let funct3 = (bits >> 5) & 0x7;
+    let rs1 = u32::from(rs1) & 0x1f;
+    let rs2 = u32::from(rs2) & 0x1f;
+    let imm = imm as u32;
+
+    // 0-6: opcode
+    let mut i = 0x3;
+    i |= opcode5 << 2;
+    i |= (imm & 0x1f) << 7;
+    i |= funct3 << 12;
+    i |= rs1 << 15;
+    i |= rs2 << 20;
+    i |= ((imm >> 5) & 0x7f) << 25;
+
+    sink.put4(i);
+}
+
 /// U-type instructions.
 ///
 ///   31  11 6
@@ -180,3 +220,12 @@ fn put_uj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS
 
     sink.put4(i);
 }
+
+/// Get the register holding the base of a stack reference.
+fn stk_base(base: StackBase) -> RegUnit {
+    match base {
+        // %x2 is the stack pointer.
+        StackBase::SP => 2,
+        _ => unimplemented!("riscv stack base {:?}", base),
+    }
+}
@@ -37,6 +37,9 @@ struct Args {
     pointer_type: ir::Type,
     gpr: &'static [RU],
     gpr_used: usize,
+    /// The number of upcoming arguments which must be passed on the stack: the halves of a
+    /// 128-bit integer that doesn't fit in the remaining registers.
+    stack_halves: u8,
     fpr_limit: usize,
     fpr_used: usize,
     offset: u32,
@@ -69,6 +72,7 @@ impl Args {
             pointer_type: ir::Type::int(u16::from(bits)).unwrap(),
             gpr,
             gpr_used: 0,
+            stack_halves: 0,
             fpr_limit,
             fpr_used: 0,
             offset,
@@ -95,6 +99,16 @@ impl ArgAssigner for Args {
 
         // Large integers and booleans are broken down to fit in a register.
         if !ty.is_float() && ty.bits() > u16::from(self.pointer_bits) {
+            // The System V x86-64 ABI passes 128-bit integers in two registers, or in a 16-byte
+            // aligned stack slot if only one register is left.
+            if self.pointer_bits == 64
+                && ty.bits() == 128
+                && !self.call_conv.extends_windows_fastcall()
+                && self.gpr_used + 2 > self.gpr.len()
+            {
+                self.stack_halves = 2;
+                self.offset = (self.offset + 15) & !15;
+            }
             return ValueConversion::IntSplit.into();
         }
 
@@ -126,7 +140,7 @@ impl ArgAssigner for Args {
         }
 
         // Try to use a GPR.
-        if !ty.is_float() && self.gpr_used < self.gpr.len() {
+        if !ty.is_float() && self.gpr_used < self.gpr.len() && self.stack_halves == 0 {
             let reg = self.gpr[self.gpr_used] as RegUnit;
             self.gpr_used += 1;
             return ArgumentLoc::Reg(reg).into();
@@ -150,6 +164,7 @@ impl ArgAssigner for Args {
         }
 
         // Assign a stack location.
+        self.stack_halves = self.stack_halves.saturating_sub(1);
         let loc = ArgumentLoc::Stack(self.offset as i32);
         self.offset += u32::from(self.pointer_bytes);
         debug_assert!(self.offset <= i32::MAX as u32);
@@ -216,6 +231,38 @@ pub fn legalize_signature(
         isa_flags,
     );
     legalize_args(&mut sig.returns, &mut rets);
+
+    // When the return values don't all fit in registers, they are all returned in memory instead.
+    // The caller passes the address of a return area in a `StructReturn` parameter, and the callee
+    // hands the same address back in the first return register.
+    let returns_in_memory = sig
+        .returns
+        .iter()
+        .any(|ret| ret.purpose == ArgumentPurpose::Normal && !ret.location.is_reg());
+    if returns_in_memory && !sig.uses_struct_return_param() {
+        let pointer_type = args.pointer_type;
+        sig.params.push(AbiParam::special(
+            pointer_type,
+            ArgumentPurpose::StructReturn,
+        ));
+        legalize_args(&mut sig.params, &mut args);
+
+        sig.returns
+            .retain(|ret| ret.purpose != ArgumentPurpose::Normal);
+        sig.returns.push(AbiParam::special(
+            pointer_type,
+            ArgumentPurpose::StructReturn,
+        ));
+        let mut rets = Args::new(
+            bits,
+            regs,
+            fpr_limit,
+            sig.call_conv,
+            shared_flags,
+            isa_flags,
+        );
+        legalize_args(&mut sig.returns, &mut rets);
+    }
 }
 
 /// Get register class for a type appearing in a legalized signature.
@@ -13,6 +13,7 @@ use crate::isa::encoding::base_size;
 use crate::isa::encoding::RecipeSizing;
 use crate::isa::RegUnit;
 use crate::isa::{self, TargetIsa};
+use crate::legalizer::split;
 use crate::predicates;
 use crate::regalloc::RegDiversions;
 
@@ -384,6 +385,68 @@ fn expand_minmax(
     cfg.recompute_ebb(pos.func, done);
 }
 
+/// Expand the `fmin` and `fmax` instructions on vectors using the x86 `x86_fmin` and `x86_fmax`
+/// instructions, without branching.
+fn expand_minmax_vector(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (x, y, opcode) = match func.dfg[inst] {
+        ir::InstructionData::Binary {
+            opcode: opcode @ ir::Opcode::Fmin,
+            args,
+        }
+        | ir::InstructionData::Binary {
+            opcode: opcode @ ir::Opcode::Fmax,
+            args,
+        } => (args[0], args[1], opcode),
+        _ => panic!("Expected fmin/fmax: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let (int_ty, nan_fraction_shift) = match ty {
+        F32X4 => (I32X4, 10),
+        F64X2 => (I64X2, 13),
+        _ => panic!("Can't expand {} for {}", opcode, ty),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // The native instructions return their second operand when the operands compare equal or
+    // unordered, so we compute them with both operand orders. The lanes where the results differ
+    // are those with a NaN operand or with zeros of different signs.
+    let value = if opcode == ir::Opcode::Fmin {
+        let forward = pos.ins().x86_fmin(x, y);
+        let backward = pos.ins().x86_fmin(y, x);
+        // OR-ing the results keeps a NaN, and makes fmin(0.0, -0.0) -> -0.0.
+        pos.ins().bor(forward, backward)
+    } else {
+        let forward = pos.ins().x86_fmax(x, y);
+        let backward = pos.ins().x86_fmax(y, x);
+        // The difference is either a NaN or a sign bit (-0.0). Merging it into the result keeps a
+        // NaN, and subtracting it again propagates the NaN, makes fmax(0.0, -0.0) -> -0.0 - -0.0 ->
+        // 0.0, and leaves the other lanes unchanged.
+        let difference = pos.ins().bxor(forward, backward);
+        let merged = pos.ins().bor(backward, difference);
+        pos.ins().fsub(merged, difference)
+    };
+
+    // Canonicalize the NaN lanes: set all their bits, then clear the fraction bits below the
+    // quiet bit.
+    let is_nan = pos.ins().fcmp(FloatCC::Unordered, value, value);
+    let is_nan_mask = pos.ins().raw_bitcast(ty, is_nan);
+    let value = pos.ins().bor(value, is_nan_mask);
+    let is_nan_int = pos.ins().raw_bitcast(int_ty, is_nan);
+    let nan_fraction_mask = pos.ins().ushr_imm(is_nan_int, nan_fraction_shift);
+    let nan_fraction_mask = pos.ins().raw_bitcast(ty, nan_fraction_mask);
+    pos.func
+        .dfg
+        .replace(inst)
+        .band_not(value, nan_fraction_mask);
+}
+
 /// x86 has no unsigned-to-float conversions. We handle the easy case of zero-extending i32 to
 /// i64 with a pattern, the rest needs more code.
 fn expand_fcvt_from_uint(
@@ -695,6 +758,50 @@ fn expand_fcvt_to_sint_sat(
     cfg.recompute_ebb(pos.func, done_ebb);
 }
 
+/// Expand the `fcvt_to_sint_sat` instruction on vectors using the x86 `x86_cvtt2si` instruction,
+/// without branching.
+fn expand_fcvt_to_sint_sat_vector(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let x = match func.dfg[inst] {
+        ir::InstructionData::Unary {
+            opcode: ir::Opcode::FcvtToSintSat,
+            arg,
+        } => arg,
+        _ => panic!(
+            "Need fcvt_to_sint_sat: {}",
+            func.dfg.display_inst(inst, None)
+        ),
+    };
+    let xty = func.dfg.value_type(x);
+    let ty = func.dfg.ctrl_typevar(inst);
+    if xty != F32X4 || ty != I32X4 {
+        panic!("Can't convert {} to {}", xty, ty);
+    }
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Replace the NaN lanes with 0.0, which is converted to 0.
+    let not_nan = pos.ins().fcmp(FloatCC::Equal, x, x);
+    let not_nan = pos.ins().raw_bitcast(F32X4, not_nan);
+    let x = pos.ins().band(x, not_nan);
+
+    // The sign bit of each lane is set when the input is positive.
+    let positive = pos.ins().bxor(not_nan, x);
+    let positive = pos.ins().raw_bitcast(I32X4, positive);
+
+    // The `x86_cvtt2si` instruction produces INT_MIN for out of range lanes, which is only the
+    // saturated value for negative inputs. Flip all the bits of the positive ones to INT_MAX.
+    let cvtt2si = pos.ins().x86_cvtt2si(I32X4, x);
+    let overflow = pos.ins().band(positive, cvtt2si);
+    let overflow = pos.ins().sshr_imm(overflow, 31);
+    pos.func.dfg.replace(inst).bxor(cvtt2si, overflow);
+}
+
 fn expand_fcvt_to_uint(
     inst: ir::Inst,
     func: &mut ir::Function,
@@ -1012,3 +1119,136 @@ fn convert_insertlane(
         }
     }
 }
+
+/// Expand the `swizzle` instruction using the x86 `x86_pshufb` instruction.
+fn expand_swizzle(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary {
+            opcode: ir::Opcode::Swizzle,
+            args,
+        } => (args[0], args[1]),
+        _ => panic!("Need swizzle: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // PSHUFB selects 0 for the lanes whose index has its top bit set, and otherwise only uses the
+    // low four bits of the index. Adding 0x70 with unsigned saturation sets the top bit of all
+    // the indices above 15, and keeps the low four bits of the others.
+    let overflow = pos.func.dfg.constants.insert(vec![0x70; 16]);
+    let overflow = pos.ins().vconst(I8X16, overflow);
+    let indices = pos.ins().uadd_sat(y, overflow);
+    pos.func.dfg.replace(inst).x86_pshufb(x, indices);
+}
+
+/// Expand the `atomic_rmw` operations that don't have a single locked x86 instruction.
+fn expand_atomic_rmw(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let (flags, op, p, x) = match func.dfg[inst] {
+        ir::InstructionData::AtomicRmw {
+            opcode: ir::Opcode::AtomicRmw,
+            flags,
+            op,
+            args,
+        } => (flags, op, args[0], args[1]),
+        _ => panic!("Need atomic_rmw: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let bitwise_opc = match op {
+        ir::AtomicRmwOp::Sub => {
+            // Subtraction is a `lock xadd` of the negated operand.
+            let neg = pos.ins().irsub_imm(x, 0);
+            pos.func
+                .dfg
+                .replace(inst)
+                .atomic_rmw(flags, ir::AtomicRmwOp::Add, p, neg);
+            return;
+        }
+        ir::AtomicRmwOp::And => ir::Opcode::Band,
+        ir::AtomicRmwOp::Or => ir::Opcode::Bor,
+        ir::AtomicRmwOp::Xor => ir::Opcode::Bxor,
+        ir::AtomicRmwOp::Add | ir::AtomicRmwOp::Xchg => {
+            panic!("atomic_rmw {} should have an encoding", op)
+        }
+    };
+
+    // The bitwise operations are retried with `lock cmpxchg` until the memory wasn't modified by
+    // another thread between the load and the exchange:
+    //
+    //     v0 = load p
+    //     jump loop_ebb(v0)
+    // loop_ebb(result):
+    //     v1 = band result, x
+    //     v2 = atomic_cas p, result, v1
+    //     v3 = icmp ne v2, result
+    //     brnz v3, loop_ebb(v2)
+    //     jump done
+    // done:
+    let old_ebb = pos.func.layout.pp_ebb(inst);
+    let loop_ebb = pos.func.dfg.make_ebb();
+    let done = pos.func.dfg.make_ebb();
+
+    // The value observed by the successful exchange is the result of the instruction.
+    let result = pos.func.dfg.first_result(inst);
+    let ty = pos.func.dfg.value_type(result);
+    pos.func.dfg.clear_results(inst);
+    pos.func.dfg.attach_ebb_param(loop_ebb, result);
+
+    let initial = pos.ins().load(ty, flags, p, 0);
+    pos.ins().jump(loop_ebb, &[initial]);
+
+    pos.insert_ebb(loop_ebb);
+    let modified_inst = pos.ins().Binary(bitwise_opc, ty, result, x).0;
+    let modified = pos.func.dfg.first_result(modified_inst);
+    let actual = pos.ins().atomic_cas(flags, p, result, modified);
+    let changed = pos.ins().icmp(IntCC::NotEqual, actual, result);
+    pos.ins().brnz(changed, loop_ebb, &[actual]);
+    // Recycle the original instruction as a jump.
+    pos.func.dfg.replace(inst).jump(done, &[]);
+
+    // Finally insert a label for the completion.
+    pos.next_inst();
+    pos.insert_ebb(done);
+
+    cfg.recompute_ebb(pos.func, old_ebb);
+    cfg.recompute_ebb(pos.func, loop_ebb);
+    cfg.recompute_ebb(pos.func, done);
+}
+
+/// Expand a `tls_value` that can't use the local-exec model into a call to `__tls_get_addr`,
+/// following the ELF general-dynamic model.
+fn expand_tls_value(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &dyn TargetIsa,
+) {
+    let global_value = match func.dfg[inst] {
+        ir::InstructionData::UnaryGlobalValue {
+            opcode: ir::Opcode::TlsValue,
+            global_value,
+        } => global_value,
+        _ => panic!("Need tls_value: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    // The verifier rejects thread-local global values on other targets.
+    assert!(
+        isa.supports_tls(),
+        "TLS isn't supported on {}",
+        isa.triple()
+    );
+    func.dfg.replace(inst).x86_elf_tls_get_addr(global_value);
+}
@@ -0,0 +1,418 @@
+//! Support for FDE data generation.
+
+use crate::binemit::{FrameUnwindOffset, FrameUnwindSink, Reloc};
+use crate::ir::{Function, InstructionData, Opcode, ValueLoc};
+use crate::isa::{CallConv, RegUnit, TargetIsa};
+use std::vec::Vec;
+
+use super::registers::{FPR, GPR, RU};
+
+/// DWARF register number of the return address column for x86-64.
+const RA_REG: u8 = 16;
+
+/// DWARF register number of `%rsp`.
+const SP_REG: u8 = 7;
+
+/// DWARF register number of `%rbp`.
+const FP_REG: u8 = 6;
+
+/// Size of a pointer (and of a stack slot) on x86-64.
+const WORD_SIZE: i64 = 8;
+
+/// Data alignment factor used in the CIE; all CFA offsets are multiples of it.
+const DATA_ALIGN: i64 = -WORD_SIZE;
+
+// Call frame instructions, see the DWARF 4 specification, section 6.4.2.
+const DW_CFA_NOP: u8 = 0x00;
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_REMEMBER_STATE: u8 = 0x0a;
+const DW_CFA_RESTORE_STATE: u8 = 0x0b;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+const DW_CFA_DEF_CFA_REGISTER: u8 = 0x0d;
+const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+
+// Pointer encodings, see the LSB specification for `.eh_frame`.
+const DW_EH_PE_SDATA4: u8 = 0x0b;
+const DW_EH_PE_PCREL: u8 = 0x10;
+
+/// Map an x86-64 register unit to its DWARF register number.
+pub fn map_dwarf_register(reg: RegUnit) -> Option<u16> {
+    // The GPR register units follow the hardware encoding:
+    // rax, rcx, rdx, rbx, rsp, rbp, rsi, rdi, r8 .. r15.
+    const X86_GP_REG_MAP: [u16; 16] = [0, 2, 1, 3, 7, 6, 4, 5, 8, 9, 10, 11, 12, 13, 14, 15];
+    // DWARF register number of `%xmm0`.
+    const X86_XMM0: u16 = 17;
+    if GPR.contains(reg) {
+        Some(X86_GP_REG_MAP[(reg - GPR.first) as usize])
+    } else if FPR.contains(reg) {
+        Some(X86_XMM0 + (reg - FPR.first))
+    } else {
+        None
+    }
+}
+
+/// Map an x86-64 general purpose register unit to its DWARF register number.
+fn map_reg(reg: RegUnit) -> u8 {
+    debug_assert!(GPR.contains(reg), "not a general purpose register");
+    map_dwarf_register(reg).unwrap() as u8
+}
+
+fn put_uleb128(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn put_sleb128(buf: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Pad `buf`, whose entry started at `start`, with `DW_CFA_nop` so that the entry ends on
+/// an address-size boundary.
+fn pad_entry(buf: &mut Vec<u8>, start: usize) {
+    while (buf.len() - start) % WORD_SIZE as usize != 0 {
+        buf.push(DW_CFA_NOP);
+    }
+}
+
+/// Patch the 4-byte length field of the entry starting at `start`.
+fn patch_length(buf: &mut [u8], start: usize) {
+    let len = (buf.len() - start - 4) as u32;
+    buf[start..start + 4].copy_from_slice(&len.to_le_bytes());
+}
+
+/// Builder for the call frame instructions of a single FDE.
+struct CallFrameInstructions {
+    buf: Vec<u8>,
+    offset: u32,
+}
+
+impl CallFrameInstructions {
+    fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            offset: 0,
+        }
+    }
+
+    /// Move the current location to code offset `to`.
+    fn advance_to(&mut self, to: u32) {
+        debug_assert!(to >= self.offset);
+        let delta = to - self.offset;
+        if delta == 0 {
+            // Nothing to do.
+        } else if delta < 0x40 {
+            self.buf.push(DW_CFA_ADVANCE_LOC | delta as u8);
+        } else if delta <= 0xff {
+            self.buf.push(DW_CFA_ADVANCE_LOC1);
+            self.buf.push(delta as u8);
+        } else if delta <= 0xffff {
+            self.buf.push(DW_CFA_ADVANCE_LOC2);
+            self.buf.extend_from_slice(&(delta as u16).to_le_bytes());
+        } else {
+            self.buf.push(DW_CFA_ADVANCE_LOC4);
+            self.buf.extend_from_slice(&delta.to_le_bytes());
+        }
+        self.offset = to;
+    }
+
+    fn def_cfa(&mut self, reg: u8, offset: i64) {
+        self.buf.push(DW_CFA_DEF_CFA);
+        put_uleb128(&mut self.buf, u64::from(reg));
+        put_uleb128(&mut self.buf, offset as u64);
+    }
+
+    fn def_cfa_register(&mut self, reg: u8) {
+        self.buf.push(DW_CFA_DEF_CFA_REGISTER);
+        put_uleb128(&mut self.buf, u64::from(reg));
+    }
+
+    fn def_cfa_offset(&mut self, offset: i64) {
+        self.buf.push(DW_CFA_DEF_CFA_OFFSET);
+        put_uleb128(&mut self.buf, offset as u64);
+    }
+
+    /// Register `reg` is saved at `CFA + offset`.
+    fn offset(&mut self, reg: u8, offset: i64) {
+        debug_assert!(reg < 0x40 && offset % DATA_ALIGN == 0);
+        self.buf.push(DW_CFA_OFFSET | reg);
+        put_uleb128(&mut self.buf, (offset / DATA_ALIGN) as u64);
+    }
+
+    fn remember_state(&mut self) {
+        self.buf.push(DW_CFA_REMEMBER_STATE);
+    }
+
+    fn restore_state(&mut self) {
+        self.buf.push(DW_CFA_RESTORE_STATE);
+    }
+}
+
+/// Write a CIE describing the frame state on entry to a function.
+fn write_cie(buf: &mut Vec<u8>) {
+    let start = buf.len();
+    // Length, patched below.
+    buf.extend_from_slice(&[0; 4]);
+    // CIE id.
+    buf.extend_from_slice(&[0; 4]);
+    // Version.
+    buf.push(1);
+    // Augmentation: "zR" announces the augmentation data holding the FDE pointer encoding.
+    buf.extend_from_slice(b"zR\0");
+    // Code alignment factor.
+    put_uleb128(buf, 1);
+    // Data alignment factor.
+    put_sleb128(buf, DATA_ALIGN);
+    // Return address register.
+    put_uleb128(buf, u64::from(RA_REG));
+    // Augmentation data: its length, then the FDE pointer encoding.
+    put_uleb128(buf, 1);
+    buf.push(DW_EH_PE_PCREL | DW_EH_PE_SDATA4);
+
+    // Initial instructions: on entry, the CFA is `%rsp + 8` and the return address is saved
+    // just below it.
+    let mut insts = CallFrameInstructions::new();
+    insts.def_cfa(SP_REG, WORD_SIZE);
+    insts.offset(RA_REG, -WORD_SIZE);
+    buf.extend_from_slice(&insts.buf);
+
+    pad_entry(buf, start);
+    patch_length(buf, start);
+}
+
+/// Compute the call frame instructions describing the prologues and epilogues inserted by
+/// `abi::prologue_epilogue`, and return them along with the size of the function's code.
+fn frame_instructions(func: &Function, isa: &dyn TargetIsa) -> (CallFrameInstructions, u32) {
+    let encinfo = isa.encoding_info();
+    let entry = func.layout.entry_block();
+    let mut insts = CallFrameInstructions::new();
+    let mut code_size = 0;
+    // Offset from the CFA of the most recently pushed word, initially the return address.
+    let mut push_offset = -WORD_SIZE;
+    let mut in_epilogue = false;
+    // End of a return instruction preceded by an epilogue, after which the frame state from
+    // before the epilogue must be restored if there is more code.
+    let mut pending_restore = None;
+
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+            if size == 0 {
+                continue;
+            }
+            if let Some(at) = pending_restore.take() {
+                insts.advance_to(at);
+                insts.restore_state();
+            }
+
+            let end = offset + size;
+            code_size = end;
+            match func.dfg[inst] {
+                InstructionData::Unary {
+                    opcode: Opcode::X86Push,
+                    arg,
+                } if Some(ebb) == entry => {
+                    let reg = match func.locations[arg] {
+                        ValueLoc::Reg(reg) => map_reg(reg),
+                        _ => panic!("pushed value is not in a register"),
+                    };
+                    push_offset -= WORD_SIZE;
+                    insts.advance_to(end);
+                    if reg == FP_REG {
+                        // `push %rbp`: the frame pointer is not established yet, so the CFA
+                        // is still computed from `%rsp`.
+                        insts.def_cfa_offset(-push_offset);
+                    }
+                    insts.offset(reg, push_offset);
+                }
+                InstructionData::CopySpecial { src, dst, .. }
+                    if Some(ebb) == entry
+                        && src == RU::rsp as RegUnit
+                        && dst == RU::rbp as RegUnit =>
+                {
+                    // `mov %rsp, %rbp`: from now on the CFA is computed from the frame pointer.
+                    insts.advance_to(end);
+                    insts.def_cfa_register(FP_REG);
+                }
+                InstructionData::NullAry {
+                    opcode: Opcode::X86Pop,
+                } => {
+                    let result = func.dfg.first_result(inst);
+                    if func.locations[result] == ValueLoc::Reg(RU::rbp as RegUnit) {
+                        // `pop %rbp`: the frame pointer is the caller's again, so the CFA
+                        // must be computed from `%rsp` until the function returns.
+                        insts.advance_to(end);
+                        insts.remember_state();
+                        insts.def_cfa(SP_REG, WORD_SIZE);
+                        in_epilogue = true;
+                    }
+                }
+                ref data if data.opcode().is_return() && in_epilogue => {
+                    pending_restore = Some(end);
+                    in_epilogue = false;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    (insts, code_size)
+}
+
+/// Emit DWARF call frame information (a CIE followed by an FDE) in the `.eh_frame` format for
+/// `func` into `sink`.
+///
+/// The FDE's initial location is a 4-byte PC-relative pointer to the start of the function,
+/// reported to the sink as an `X86PCRel4` relocation; the sink's entry offset is set to the start
+/// of the FDE.
+pub fn emit_fde(func: &Function, isa: &dyn TargetIsa, sink: &mut dyn FrameUnwindSink) {
+    debug_assert!(isa.pointer_bits() == 64);
+    match func.signature.call_conv {
+        CallConv::Fast | CallConv::Cold | CallConv::SystemV => (),
+        _ => return,
+    }
+
+    let (insts, code_size) = frame_instructions(func, isa);
+
+    let mut buf = Vec::new();
+    write_cie(&mut buf);
+
+    let fde_start = buf.len();
+    // Length, patched below.
+    buf.extend_from_slice(&[0; 4]);
+    // CIE pointer: the distance from this field back to the start of the CIE.
+    let cie_pointer = (buf.len() as u32).to_le_bytes();
+    buf.extend_from_slice(&cie_pointer);
+    // Initial location, relocated to the address of the function relative to this field.
+    let pc_begin = buf.len();
+    buf.extend_from_slice(&[0; 4]);
+    // Address range.
+    buf.extend_from_slice(&code_size.to_le_bytes());
+    // Augmentation data length.
+    put_uleb128(&mut buf, 0);
+    buf.extend_from_slice(&insts.buf);
+    pad_entry(&mut buf, fde_start);
+    patch_length(&mut buf, fde_start);
+
+    let base: FrameUnwindOffset = sink.offset();
+    sink.reserve(buf.len());
+    sink.bytes(&buf);
+    sink.reloc(Reloc::X86PCRel4, base + pc_begin);
+    sink.set_entry_offset(base + fde_start);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::FrameUnwindKind;
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{ExternalName, InstBuilder, Signature, StackSlotData, StackSlotKind};
+    use crate::isa;
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use target_lexicon::triple;
+
+    struct SimpleUnwindSink(pub Vec<u8>, pub usize, pub Vec<(Reloc, usize)>);
+    impl FrameUnwindSink for SimpleUnwindSink {
+        fn offset(&self) -> FrameUnwindOffset {
+            self.0.len()
+        }
+        fn bytes(&mut self, b: &[u8]) {
+            self.0.extend_from_slice(b);
+        }
+        fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+            self.2.push((r, off));
+        }
+        fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+            self.1 = off;
+        }
+    }
+
+    fn create_function(call_conv: CallConv, stack_slot: Option<StackSlotData>) -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+
+        let ebb0 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().return_(&[]);
+
+        if let Some(stack_slot) = stack_slot {
+            func.stack_slots.push(stack_slot);
+        }
+
+        func
+    }
+
+    #[test]
+    fn test_simple_func() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .expect("expect x86 ISA")
+            .finish(settings::Flags::new(settings::builder()));
+
+        let mut context = Context::for_function(create_function(
+            CallConv::SystemV,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 64)),
+        ));
+        context.compile(&*isa).expect("expected compilation");
+
+        let mut sink = SimpleUnwindSink(Vec::new(), 0, Vec::new());
+        context.emit_unwind_info(&*isa, FrameUnwindKind::Libunwind, &mut sink);
+
+        assert_eq!(sink.1, 24);
+        assert_eq!(sink.2, vec![(Reloc::X86PCRel4, 32)]);
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            // CIE: length, id, version, "zR", code and data alignment factors, RA register,
+            // augmentation data, initial instructions and padding.
+            20, 0, 0, 0,  0, 0, 0, 0,  1,  b'z', b'R', 0,  1,  0x78,  16,  1, 0x1b,
+            0x0c, 7, 8,  0x90, 1,
+            0, 0,
+            // FDE: length, CIE pointer, initial location, address range and augmentation
+            // data length.
+            28, 0, 0, 0,  28, 0, 0, 0,  0, 0, 0, 0,  16, 0, 0, 0,  0,
+            // push %rbp
+            0x42, 0x0e, 16, 0x86, 2,
+            // mov %rsp, %rbp
+            0x43, 0x0d, 6,
+            // pop %rbp
+            0x4a, 0x0a, 0x0c, 7, 8,
+            // Padding.
+            0, 0,
+        ];
+        assert_eq!(sink.0, expected);
+    }
+
+    #[test]
+    fn test_unsupported_call_conv() {
+        let isa = isa::lookup(triple!("x86_64"))
+            .expect("expect x86 ISA")
+            .finish(settings::Flags::new(settings::builder()));
+
+        let mut context = Context::for_function(create_function(CallConv::WindowsFastcall, None));
+        context.compile(&*isa).expect("expected compilation");
+
+        let mut sink = SimpleUnwindSink(Vec::new(), 0, Vec::new());
+        context.emit_unwind_info(&*isa, FrameUnwindKind::Libunwind, &mut sink);
+
+        assert!(sink.0.is_empty());
+    }
+}
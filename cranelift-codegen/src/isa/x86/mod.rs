@@ -3,23 +3,25 @@
 mod abi;
 mod binemit;
 mod enc_tables;
+mod fde;
 mod registers;
 pub mod settings;
+mod unwind;
 
 use super::super::settings as shared_settings;
 #[cfg(feature = "testing_hooks")]
 use crate::binemit::CodeSink;
-use crate::binemit::{emit_function, MemoryCodeSink};
+use crate::binemit::{emit_function, FrameUnwindKind, FrameUnwindSink, MemoryCodeSink};
 use crate::ir;
 use crate::isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use crate::isa::Builder as IsaBuilder;
-use crate::isa::{EncInfo, RegClass, RegInfo, TargetIsa};
+use crate::isa::{EncInfo, RegClass, RegInfo, RegUnit, TargetIsa};
 use crate::regalloc;
 use crate::result::CodegenResult;
 use crate::timing;
 use core::fmt;
 use std::boxed::Box;
-use target_lexicon::{PointerWidth, Triple};
+use target_lexicon::{BinaryFormat, PointerWidth, Triple};
 
 #[allow(dead_code)]
 struct Isa {
@@ -77,6 +79,12 @@ impl TargetIsa for Isa {
         true
     }
 
+    fn supports_tls(&self) -> bool {
+        // Only the ELF general-dynamic and local-exec models for x86-64 are implemented.
+        self.triple.binary_format == BinaryFormat::Elf
+            && self.triple.pointer_width() == Ok(PointerWidth::U64)
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -119,6 +127,14 @@ impl TargetIsa for Isa {
         abi::regclass_for_abi_type(ty)
     }
 
+    fn map_dwarf_register(&self, reg: RegUnit) -> Option<u16> {
+        if self.pointer_bits() == 64 {
+            fde::map_dwarf_register(reg)
+        } else {
+            None
+        }
+    }
+
     fn allocatable_registers(&self, _func: &ir::Function) -> regalloc::RegisterSet {
         abi::allocatable_registers(&self.triple, &self.shared_flags)
     }
@@ -142,6 +158,26 @@ impl TargetIsa for Isa {
         let _tt = timing::prologue_epilogue();
         abi::prologue_epilogue(func, self)
     }
+
+    fn emit_unwind_info(
+        &self,
+        func: &ir::Function,
+        kind: FrameUnwindKind,
+        sink: &mut dyn FrameUnwindSink,
+    ) {
+        match kind {
+            FrameUnwindKind::Fastcall => {
+                if self.pointer_bits() == 64 {
+                    unwind::emit_unwind_info(func, self, sink);
+                }
+            }
+            FrameUnwindKind::Libunwind => {
+                if self.pointer_bits() == 64 {
+                    fde::emit_fde(func, self, sink);
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Display for Isa {
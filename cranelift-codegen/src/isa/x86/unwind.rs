@@ -0,0 +1,342 @@
+//! Windows x64 unwind information generation.
+
+use crate::binemit::FrameUnwindSink;
+use crate::ir::{Function, InstructionData, Opcode, ValueLoc};
+use crate::isa::{CallConv, RegUnit, TargetIsa};
+use std::vec::Vec;
+
+use super::registers::{GPR, RU};
+
+/// Maximum (inclusive) size of a "small" stack allocation.
+const SMALL_ALLOC_MAX_SIZE: u32 = 128;
+
+/// Maximum (inclusive) size of a "large" stack allocation that can be represented in 16 bits.
+const LARGE_ALLOC_16BIT_MAX_SIZE: u32 = 524_280;
+
+// Unwind operation codes.
+const UWOP_PUSH_NONVOL: u8 = 0;
+const UWOP_ALLOC_LARGE: u8 = 1;
+const UWOP_ALLOC_SMALL: u8 = 2;
+const UWOP_SET_FPREG: u8 = 3;
+
+/// Version of the `UNWIND_INFO` structure emitted here.
+const UNWIND_INFO_VERSION: u8 = 1;
+
+/// Map an x86-64 general purpose register unit to its number in unwind codes, which is its
+/// hardware encoding.
+fn gpr_number(reg: RegUnit) -> u8 {
+    debug_assert!(GPR.contains(reg), "not a general purpose register");
+    (reg - GPR.first) as u8
+}
+
+/// An unwind code, describing the effect of a single prologue instruction.
+///
+/// The offsets are those of the end of the instruction from the start of the function.
+#[derive(Debug, PartialEq, Eq)]
+enum UnwindCode {
+    PushRegister { offset: u8, reg: u8 },
+    SetFramePointer { offset: u8 },
+    StackAlloc { offset: u8, size: u32 },
+}
+
+impl UnwindCode {
+    /// Number of 2-byte slots used by this unwind code.
+    fn node_count(&self) -> usize {
+        match *self {
+            UnwindCode::StackAlloc { size, .. } => {
+                if size <= SMALL_ALLOC_MAX_SIZE {
+                    1
+                } else if size <= LARGE_ALLOC_16BIT_MAX_SIZE {
+                    2
+                } else {
+                    3
+                }
+            }
+            _ => 1,
+        }
+    }
+
+    fn emit(&self, buf: &mut Vec<u8>) {
+        match *self {
+            UnwindCode::PushRegister { offset, reg } => {
+                buf.push(offset);
+                buf.push(UWOP_PUSH_NONVOL | reg << 4);
+            }
+            UnwindCode::SetFramePointer { offset } => {
+                buf.push(offset);
+                buf.push(UWOP_SET_FPREG);
+            }
+            UnwindCode::StackAlloc { offset, size } => {
+                debug_assert!(size % 8 == 0, "stack allocations must be a multiple of 8");
+                buf.push(offset);
+                if size <= SMALL_ALLOC_MAX_SIZE {
+                    buf.push(UWOP_ALLOC_SMALL | (((size - 8) / 8) as u8) << 4);
+                } else if size <= LARGE_ALLOC_16BIT_MAX_SIZE {
+                    buf.push(UWOP_ALLOC_LARGE);
+                    buf.extend_from_slice(&((size / 8) as u16).to_le_bytes());
+                } else {
+                    buf.push(UWOP_ALLOC_LARGE | 1 << 4);
+                    buf.extend_from_slice(&size.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// Convert the offset of the end of a prologue instruction to the 8-bit form used in unwind codes.
+fn prologue_offset(offset: u32) -> u8 {
+    assert!(
+        offset <= 0xff,
+        "prologue too large for Windows unwind information"
+    );
+    offset as u8
+}
+
+/// Collect the unwind codes describing the prologue inserted by `abi::prologue_epilogue`, and
+/// return them in prologue order along with the prologue size and the frame register, if any.
+fn prologue_unwind_codes(
+    func: &Function,
+    isa: &dyn TargetIsa,
+) -> (Vec<UnwindCode>, u8, Option<u8>) {
+    let mut codes = Vec::new();
+    let mut prologue_size = 0;
+    let mut frame_register = None;
+
+    let entry = match func.layout.entry_block() {
+        Some(ebb) => ebb,
+        None => return (codes, prologue_size, frame_register),
+    };
+
+    // Size of the stack frame passed to the probestack function in `%rax`.
+    let mut probe_size = None;
+
+    for (offset, inst, size) in func.inst_offsets(entry, &isa.encoding_info()) {
+        let end = offset + size;
+        match func.dfg[inst] {
+            InstructionData::Unary {
+                opcode: Opcode::X86Push,
+                arg,
+            } => {
+                let reg = match func.locations[arg] {
+                    ValueLoc::Reg(reg) => gpr_number(reg),
+                    _ => panic!("pushed value is not in a register"),
+                };
+                prologue_size = prologue_offset(end);
+                codes.push(UnwindCode::PushRegister {
+                    offset: prologue_size,
+                    reg,
+                });
+            }
+            InstructionData::CopySpecial { src, dst, .. }
+                if src == RU::rsp as RegUnit && dst == RU::rbp as RegUnit =>
+            {
+                prologue_size = prologue_offset(end);
+                frame_register = Some(gpr_number(dst));
+                codes.push(UnwindCode::SetFramePointer {
+                    offset: prologue_size,
+                });
+            }
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => {
+                let result = func.dfg.first_result(inst);
+                if func.locations[result] == ValueLoc::Reg(RU::rax as RegUnit) {
+                    probe_size = Some(imm.into());
+                }
+            }
+            InstructionData::UnaryImm {
+                opcode: Opcode::AdjustSpDownImm,
+                imm,
+            } => {
+                let imm: i64 = imm.into();
+                prologue_size = prologue_offset(end);
+                codes.push(UnwindCode::StackAlloc {
+                    offset: prologue_size,
+                    size: imm as u32,
+                });
+                // The stack allocation ends the prologue.
+                break;
+            }
+            InstructionData::Unary {
+                opcode: Opcode::AdjustSpDown,
+                ..
+            } => {
+                let size: i64 = probe_size.expect("unknown stack probe size");
+                prologue_size = prologue_offset(end);
+                codes.push(UnwindCode::StackAlloc {
+                    offset: prologue_size,
+                    size: size as u32,
+                });
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    (codes, prologue_size, frame_register)
+}
+
+/// Emit the Windows x64 `UNWIND_INFO` structure describing the prologue of `func` into `sink`.
+///
+/// The epilogues inserted by `abi::prologue_epilogue` follow the form expected by the Windows
+/// unwinder, so they need no description. The sink's entry offset is set to the start of the
+/// structure, which must be referenced by the function's `RUNTIME_FUNCTION` entry.
+pub fn emit_unwind_info(func: &Function, isa: &dyn TargetIsa, sink: &mut dyn FrameUnwindSink) {
+    debug_assert!(isa.pointer_bits() == 64);
+    if func.signature.call_conv != CallConv::WindowsFastcall {
+        return;
+    }
+
+    let (codes, prologue_size, frame_register) = prologue_unwind_codes(func, isa);
+    let node_count: usize = codes.iter().map(UnwindCode::node_count).sum();
+    assert!(node_count <= 0xff, "too many unwind codes");
+
+    let mut buf = vec![
+        UNWIND_INFO_VERSION,
+        prologue_size,
+        node_count as u8,
+        // The frame pointer is established without an offset from the stack pointer.
+        frame_register.unwrap_or(0),
+    ];
+
+    // Unwind codes are stored in the reverse order of the prologue instructions.
+    for code in codes.iter().rev() {
+        code.emit(&mut buf);
+    }
+
+    // The array of unwind codes always has an even number of slots.
+    if node_count % 2 != 0 {
+        buf.extend_from_slice(&[0; 2]);
+    }
+
+    let offset = sink.offset();
+    sink.reserve(buf.len());
+    sink.bytes(&buf);
+    sink.set_entry_offset(offset);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::{FrameUnwindKind, FrameUnwindOffset, Reloc};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{ExternalName, InstBuilder, Signature, StackSlotData, StackSlotKind};
+    use crate::isa;
+    use crate::settings;
+    use crate::Context;
+    use core::str::FromStr;
+    use target_lexicon::triple;
+
+    struct SimpleUnwindSink(pub Vec<u8>, pub usize, pub Vec<(Reloc, usize)>);
+    impl FrameUnwindSink for SimpleUnwindSink {
+        fn offset(&self) -> FrameUnwindOffset {
+            self.0.len()
+        }
+        fn bytes(&mut self, b: &[u8]) {
+            self.0.extend_from_slice(b);
+        }
+        fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+            self.2.push((r, off));
+        }
+        fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+            self.1 = off;
+        }
+    }
+
+    fn create_function(call_conv: CallConv, stack_slot: Option<StackSlotData>) -> Function {
+        let mut func =
+            Function::with_name_signature(ExternalName::user(0, 0), Signature::new(call_conv));
+
+        let ebb0 = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        pos.ins().return_(&[]);
+
+        if let Some(stack_slot) = stack_slot {
+            func.stack_slots.push(stack_slot);
+        }
+
+        func
+    }
+
+    fn unwind_info(func: Function) -> Vec<u8> {
+        let isa = isa::lookup(triple!("x86_64"))
+            .expect("expect x86 ISA")
+            .finish(settings::Flags::new(settings::builder()));
+
+        let mut context = Context::for_function(func);
+        context.compile(&*isa).expect("expected compilation");
+
+        let mut sink = SimpleUnwindSink(Vec::new(), 0, Vec::new());
+        context.emit_unwind_info(&*isa, FrameUnwindKind::Fastcall, &mut sink);
+        assert_eq!(sink.1, 0);
+        assert!(sink.2.is_empty());
+        sink.0
+    }
+
+    #[test]
+    fn test_unsupported_call_conv() {
+        assert!(unwind_info(create_function(CallConv::SystemV, None)).is_empty());
+    }
+
+    #[test]
+    fn test_small_alloc() {
+        let func = create_function(
+            CallConv::WindowsFastcall,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 64)),
+        );
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            // Version and flags, prologue size, unwind code count, frame register.
+            1, 9, 3, 5,
+            // sub $96, %rsp
+            9, UWOP_ALLOC_SMALL | 11 << 4,
+            // mov %rsp, %rbp
+            5, UWOP_SET_FPREG,
+            // push %rbp
+            2, UWOP_PUSH_NONVOL | 5 << 4,
+            // Padding.
+            0, 0,
+        ];
+        assert_eq!(unwind_info(func), expected);
+    }
+
+    #[test]
+    fn test_medium_alloc() {
+        let func = create_function(
+            CallConv::WindowsFastcall,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 10000)),
+        );
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            1, 27, 4, 5,
+            // sub %rax, %rsp, after calling the probestack function
+            27, UWOP_ALLOC_LARGE, 0xe6, 0x04,
+            5, UWOP_SET_FPREG,
+            2, UWOP_PUSH_NONVOL | 5 << 4,
+        ];
+        assert_eq!(unwind_info(func), expected);
+    }
+
+    #[test]
+    fn test_large_alloc() {
+        let func = create_function(
+            CallConv::WindowsFastcall,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 1_000_000)),
+        );
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            1, 27, 5, 5,
+            // sub %rax, %rsp, after calling the probestack function
+            27, UWOP_ALLOC_LARGE | 1 << 4, 0x60, 0x42, 0x0f, 0x00,
+            5, UWOP_SET_FPREG,
+            2, UWOP_PUSH_NONVOL | 5 << 4,
+            0, 0,
+        ];
+        assert_eq!(unwind_info(func), expected);
+    }
+}
@@ -14,6 +14,10 @@
 //! 2. The `handle_call_abi` and `handle_return_abi` functions rewrite call and return instructions
 //!    to match the new ABI signatures.
 //!
+//! When the legalized signature returns values in memory through a `StructReturn` parameter, the
+//! original signature is kept around so calls and returns can find the return values in the
+//! return area.
+//!
 //! Between the two phases, preamble signatures and call/return arguments don't match. This
 //! intermediate state doesn't type check.
 
@@ -23,7 +27,7 @@ use crate::flowgraph::ControlFlowGraph;
 use crate::ir::instructions::CallInfo;
 use crate::ir::{
     AbiParam, ArgumentLoc, ArgumentPurpose, DataFlowGraph, Ebb, Function, Inst, InstBuilder,
-    SigRef, Signature, Type, Value, ValueLoc,
+    MemFlags, SigRef, Signature, StackSlotData, StackSlotKind, Type, Value, ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::legalizer::split::{isplit, vsplit};
@@ -36,9 +40,13 @@ use std::vec::Vec;
 /// change the entry block arguments, calls, or return instructions, so this can leave the function
 /// in a state with type discrepancies.
 pub fn legalize_signatures(func: &mut Function, isa: &dyn TargetIsa) {
-    legalize_signature(&mut func.signature, true, isa);
-    for sig_data in func.dfg.signatures.values_mut() {
-        legalize_signature(sig_data, false, isa);
+    if let Some(old) = legalize_signature(&mut func.signature, true, isa) {
+        func.old_signature = Some(old);
+    }
+    for (sig_ref, sig_data) in func.dfg.signatures.iter_mut() {
+        if let Some(old) = legalize_signature(sig_data, false, isa) {
+            func.dfg.old_signatures[sig_ref] = Some(old);
+        }
     }
 
     if let Some(entry) = func.layout.entry_block() {
@@ -56,8 +64,51 @@ pub fn legalize_libcall_signature(signature: &mut Signature, isa: &dyn TargetIsa
 /// Legalize the given signature.
 ///
 /// `current` is true if this is the signature for the current function.
-fn legalize_signature(signature: &mut Signature, current: bool, isa: &dyn TargetIsa) {
+///
+/// Returns the original signature if the legalized one returns values in memory through a new
+/// `StructReturn` parameter.
+fn legalize_signature(
+    signature: &mut Signature,
+    current: bool,
+    isa: &dyn TargetIsa,
+) -> Option<Signature> {
+    if signature.uses_struct_return_param() {
+        isa.legalize_signature(signature, current);
+        return None;
+    }
+    let old = signature.clone();
     isa.legalize_signature(signature, current);
+    if signature.uses_struct_return_param() {
+        Some(old)
+    } else {
+        None
+    }
+}
+
+/// Compute the layout of the return area used by a function whose signature before legalization
+/// was `sig`.
+///
+/// Returns the offset of each return value in the return area, and the size of the area. Every
+/// value is naturally aligned.
+fn return_area_layout(sig: &Signature) -> (Vec<u32>, u32) {
+    let mut size = 0;
+    let offsets = sig
+        .returns
+        .iter()
+        .map(|ret| {
+            debug_assert_eq!(
+                ret.purpose,
+                ArgumentPurpose::Normal,
+                "Can't return special-purpose value {} in memory",
+                ret
+            );
+            let bytes = ret.value_type.bytes();
+            let offset = (size + bytes - 1) & !(bytes - 1);
+            size = offset + bytes;
+            offset
+        })
+        .collect();
+    (offsets, size)
 }
 
 /// Legalize the entry block parameters after `func`'s signature has been legalized.
@@ -528,6 +579,10 @@ pub fn handle_call_abi(mut inst: Inst, func: &mut Function, cfg: &ControlFlowGra
         Err(s) => s,
     };
 
+    if pos.func.dfg.old_signatures[sig_ref].is_some() {
+        legalize_return_area_call(pos, sig_ref);
+    }
+
     // OK, we need to fix the call arguments to match the ABI signature.
     let abi_args = pos.func.dfg.signatures[sig_ref].params.len();
     legalize_inst_arguments(pos, cfg, abi_args, |func, abi_arg| {
@@ -565,6 +620,10 @@ pub fn handle_return_abi(inst: Inst, func: &mut Function, cfg: &ControlFlowGraph
         return false;
     }
 
+    if func.old_signature.is_some() {
+        store_return_area_values(inst, func);
+    }
+
     // Count the special-purpose return values (`link`, `sret`, and `vmctx`) that were appended to
     // the legalized signature.
     let special_args = func
@@ -639,6 +698,71 @@ pub fn handle_return_abi(inst: Inst, func: &mut Function, cfg: &ControlFlowGraph
     true
 }
 
+/// Allocate a return area for the call at `pos` to a function returning its values in memory.
+///
+/// The address of a new stack slot is passed as the `StructReturn` argument, and the original
+/// results of the call are loaded from it after the call. The only result left on the call is the
+/// returned address, as in the legalized signature.
+fn legalize_return_area_call(pos: &mut FuncCursor, sig_ref: SigRef) {
+    let call = pos
+        .current_inst()
+        .expect("Cursor must point to a call instruction");
+    let (offsets, size) = return_area_layout(
+        pos.func.dfg.old_signatures[sig_ref]
+            .as_ref()
+            .expect("Signature doesn't return in memory"),
+    );
+    let sig = &pos.func.dfg.signatures[sig_ref];
+    let ptr_type = sig.params[sig
+        .special_param_index(ArgumentPurpose::StructReturn)
+        .expect("No sret parameter")]
+    .value_type;
+
+    let ss = pos
+        .func
+        .create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+    let area = pos.ins().stack_addr(ptr_type, ss, 0);
+    pos.func.dfg.append_inst_arg(call, area);
+
+    let results = pos.func.dfg.detach_results(call);
+    pos.func.dfg.append_result(call, ptr_type);
+    pos.goto_after_inst(call);
+    for (i, offset) in offsets.into_iter().enumerate() {
+        let res = results.get(i, &pos.func.dfg.value_lists).unwrap();
+        let ty = pos.func.dfg.value_type(res);
+        pos.ins()
+            .with_result(res)
+            .load(ty, MemFlags::trusted(), area, offset as i32);
+    }
+    pos.goto_inst(call);
+}
+
+/// Store the values returned by the return instruction `inst` into the return area of a function
+/// returning its values in memory, and remove them from the instruction.
+fn store_return_area_values(inst: Inst, func: &mut Function) {
+    let (offsets, _) = return_area_layout(
+        func.old_signature
+            .as_ref()
+            .expect("Function doesn't return in memory"),
+    );
+    let area = func
+        .special_param(ArgumentPurpose::StructReturn)
+        .expect("No sret parameter");
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let mut vlist = pos.func.dfg[inst]
+        .take_value_list()
+        .expect("Return must have a value list");
+    for (i, offset) in offsets.into_iter().enumerate() {
+        let value = vlist.get(i, &pos.func.dfg.value_lists).unwrap();
+        pos.ins()
+            .store(MemFlags::trusted(), value, area, offset as i32);
+    }
+    vlist.clear(&mut pos.func.dfg.value_lists);
+    pos.func.dfg[inst].put_value_list(vlist);
+}
+
 /// Assign stack slots to incoming function parameters on the stack.
 ///
 /// Values that are passed into the function on the stack must be assigned to an `IncomingArg`
@@ -111,6 +111,7 @@ fn load_addr(
 
     // Global-value loads are always notrap and aligned. They may be readonly.
     let mut mflags = ir::MemFlags::trusted();
+    mflags.set_alias_region(Some(ir::AliasRegion::Vmctx));
     if readonly {
         mflags.set_readonly();
     }
@@ -125,5 +126,9 @@ fn load_addr(
 /// Expand a `global_value` instruction for a symbolic name global.
 fn symbol(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalValue, isa: &dyn TargetIsa) {
     let ptr_ty = isa.pointer_type();
-    func.dfg.replace(inst).symbol_value(ptr_ty, gv);
+    if func.global_values[gv].is_tls() {
+        func.dfg.replace(inst).tls_value(ptr_ty, gv);
+    } else {
+        func.dfg.replace(inst).symbol_value(ptr_ty, gv);
+    }
 }
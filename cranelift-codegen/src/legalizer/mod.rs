@@ -16,7 +16,8 @@
 use crate::bitset::BitSet;
 use crate::cursor::{Cursor, FuncCursor};
 use crate::flowgraph::ControlFlowGraph;
-use crate::ir::types::{I32, I64};
+use crate::ir::condcodes::IntCC;
+use crate::ir::types::{I128, I32, I64};
 use crate::ir::{self, InstBuilder, MemFlags};
 use crate::isa::TargetIsa;
 use crate::predicates;
@@ -29,7 +30,7 @@ mod call;
 mod globalvalue;
 mod heap;
 mod libcall;
-mod split;
+pub(crate) mod split;
 mod table;
 
 use self::call::expand_call;
@@ -532,7 +533,8 @@ fn expand_stack_load(
     let addr = pos.ins().stack_addr(addr_ty, stack_slot, offset);
 
     // Stack slots are required to be accessible and aligned.
-    let mflags = MemFlags::trusted();
+    let mut mflags = MemFlags::trusted();
+    mflags.set_alias_region(Some(ir::AliasRegion::Stack));
     pos.func.dfg.replace(inst).load(ty, mflags, addr, 0);
 }
 
@@ -567,6 +569,7 @@ fn expand_stack_store(
     // Stack slots are required to be accessible and aligned.
     mflags.set_notrap();
     mflags.set_aligned();
+    mflags.set_alias_region(Some(ir::AliasRegion::Stack));
     pos.func.dfg.replace(inst).store(mflags, val, addr, 0);
 }
 
@@ -598,7 +601,9 @@ fn narrow_load(
         small_ty,
         flags,
         ptr,
-        offset.try_add_i64(8).expect("load offset overflow"),
+        offset
+            .try_add_i64(small_ty.bytes().into())
+            .expect("load offset overflow"),
     );
     pos.func.dfg.replace(inst).iconcat(al, ah);
 }
@@ -623,13 +628,21 @@ fn narrow_store(
         _ => panic!("Expected store: {}", pos.func.dfg.display_inst(inst, None)),
     };
 
+    let small_ty = pos
+        .func
+        .dfg
+        .value_type(val)
+        .half_width()
+        .expect("Can't narrow store");
     let (al, ah) = pos.ins().isplit(val);
     pos.ins().store(flags, al, ptr, offset);
     pos.ins().store(
         flags,
         ah,
         ptr,
-        offset.try_add_i64(8).expect("store offset overflow"),
+        offset
+            .try_add_i64(small_ty.bytes().into())
+            .expect("store offset overflow"),
     );
     pos.remove_inst();
 }
@@ -662,6 +675,108 @@ fn narrow_iconst(
         pos.func.dfg.replace(inst).iconcat(low, high);
         return;
     }
+    if ty == I128 {
+        // The immediate is sign-extended to 128 bits.
+        let low = pos.ins().iconst(I64, imm);
+        let high = pos.ins().iconst(I64, imm >> 63);
+        pos.func.dfg.replace(inst).iconcat(low, high);
+        return;
+    }
 
     unimplemented!("missing encoding or legalization for iconst.{:?}", ty);
 }
+
+/// Split a shift or rotate into operations on the halves of its argument.
+fn narrow_shift(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &dyn TargetIsa,
+) {
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let (opcode, x, amt) = match pos.func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("Expected shift: {}", pos.func.dfg.display_inst(inst, None)),
+    };
+
+    let ty = pos.func.dfg.ctrl_typevar(inst);
+    let half_ty = match ty.half_width() {
+        Some(half_ty) if !ty.is_vector() => half_ty,
+        _ => panic!(
+            "Can't narrow shift: {}",
+            pos.func.dfg.display_inst(inst, None)
+        ),
+    };
+    let half_bits = i64::from(half_ty.bits());
+
+    // Rotate by shifting in both directions. When `amt` is zero, both shifts are by zero, as the
+    // amount is masked to the width of `x`.
+    if opcode == ir::Opcode::Rotl || opcode == ir::Opcode::Rotr {
+        let inv = pos.ins().irsub_imm(amt, 2 * half_bits);
+        let (left, right) = if opcode == ir::Opcode::Rotl {
+            (amt, inv)
+        } else {
+            (inv, amt)
+        };
+        let a1 = pos.ins().ishl(x, left);
+        let a2 = pos.ins().ushr(x, right);
+        pos.func.dfg.replace(inst).bor(a1, a2);
+        return;
+    }
+
+    // Compute the amount modulo the width of `x`, as a value of the halves' type.
+    let amt_ty = pos.func.dfg.value_type(amt);
+    let amt = if amt_ty.bits() > half_ty.bits() {
+        pos.ins().ireduce(half_ty, amt)
+    } else if amt_ty.bits() < half_ty.bits() {
+        pos.ins().uextend(half_ty, amt)
+    } else {
+        amt
+    };
+    let amt = pos.ins().band_imm(amt, 2 * half_bits - 1);
+
+    let (xl, xh) = pos.ins().isplit(x);
+    let zero = pos.ins().iconst(half_ty, 0);
+    // Amounts of at least `half_bits` move one half entirely into the other. As the amount of
+    // shifts is masked, shifting a half by `amt` then shifts it by `amt - half_bits`.
+    let large = pos
+        .ins()
+        .icmp_imm(IntCC::UnsignedGreaterThanOrEqual, amt, half_bits);
+    // Smaller amounts move the bits shifted out of one half into the other, except that shifting
+    // by `half_bits - 0` is a shift by zero.
+    let is_zero = pos.ins().icmp_imm(IntCC::Equal, amt, 0);
+    let inv = pos.ins().irsub_imm(amt, half_bits);
+
+    let (al, ah) = match opcode {
+        ir::Opcode::Ishl => {
+            let low = pos.ins().ishl(xl, amt);
+            let high = pos.ins().ishl(xh, amt);
+            let carry = pos.ins().ushr(xl, inv);
+            let carry = pos.ins().select(is_zero, zero, carry);
+            let high = pos.ins().bor(high, carry);
+            let al = pos.ins().select(large, zero, low);
+            let ah = pos.ins().select(large, low, high);
+            (al, ah)
+        }
+        ir::Opcode::Ushr | ir::Opcode::Sshr => {
+            let low = pos.ins().ushr(xl, amt);
+            let (high, fill) = if opcode == ir::Opcode::Sshr {
+                let high = pos.ins().sshr(xh, amt);
+                let sign = pos.ins().sshr_imm(xh, half_bits - 1);
+                (high, sign)
+            } else {
+                (pos.ins().ushr(xh, amt), zero)
+            };
+            let carry = pos.ins().ishl(xh, inv);
+            let carry = pos.ins().select(is_zero, zero, carry);
+            let low = pos.ins().bor(low, carry);
+            let al = pos.ins().select(large, high, low);
+            let ah = pos.ins().select(large, fill, high);
+            (al, ah)
+        }
+        _ => panic!("Expected shift: {}", pos.func.dfg.display_inst(inst, None)),
+    };
+    pos.func.dfg.replace(inst).iconcat(al, ah);
+}
@@ -66,6 +66,7 @@ pub use cranelift_entity as entity;
 pub mod binemit;
 pub mod cfg_printer;
 pub mod cursor;
+pub mod data_value;
 pub mod dbg;
 pub mod dominator_tree;
 pub mod flowgraph;
@@ -87,13 +88,16 @@ mod context;
 mod dce;
 mod divconst_magic_numbers;
 mod fx;
+mod induction_variables;
 mod iterators;
 mod legalizer;
 mod licm;
+mod loop_unroll;
 mod nan_canonicalization;
 mod partition_slice;
 mod postopt;
 mod predicates;
+mod redundant_memory;
 mod redundant_reload_remover;
 mod ref_slice;
 mod regalloc;
@@ -102,6 +106,7 @@ mod scoped_hash_map;
 mod simple_gvn;
 mod simple_preopt;
 mod stack_layout;
+mod strength_reduction;
 mod topo_order;
 mod unreachable_code;
 mod value_label;
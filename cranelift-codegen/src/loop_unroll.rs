@@ -0,0 +1,345 @@
+//! Unrolling of small counted loops.
+//!
+//! This pass handles loops made of a single EBB which ends with the only two branches in it: one
+//! back to the header and one out of the loop. Such a loop is counted when the conditional one
+//! compares a basic induction variable with a constant and the variable starts at the same
+//! constant on all entries of the loop, so that the number of iterations before it exits is known.
+//!
+//! Loops that stay small with all of their iterations laid out in sequence are unrolled fully,
+//! which removes the back edge. Larger ones are unrolled by a factor dividing their trip count,
+//! so that only the last copy of the body needs to branch.
+//!
+//! The copies of the body are inserted before the original instructions, which are left to
+//! compute the last iteration. The header gets new parameters for the first copy, and the values
+//! of its original parameters are those passed to the last one. Since the original values keep
+//! the same meaning for the rest of the function, no uses outside of the loop need to change.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::induction_variables::{
+    branch_args, constant_value, find_induction_variables, LoopEdges,
+};
+use crate::ir::condcodes::{CondCode, IntCC};
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{
+    Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value, ValueDef, ValueList,
+};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use crate::timing;
+use std::vec::Vec;
+
+/// The largest number of instructions an unrolled loop body may have.
+const MAX_UNROLLED_SIZE: usize = 128;
+
+/// The factors loops are unrolled by partially, in order of preference.
+const PARTIAL_UNROLL_FACTORS: [u64; 3] = [8, 4, 2];
+
+/// The largest trip count which is searched for.
+const MAX_TRIP_COUNT: u64 = 1 << 16;
+
+/// A loop which exits after a known number of iterations.
+struct CountedLoop {
+    /// The single EBB of the loop.
+    header: Ebb,
+    /// The instructions of the loop body, which are all but the branches at the end.
+    body: Vec<Inst>,
+    /// The branch back to the header.
+    back_edge: Inst,
+    /// The branch out of the loop.
+    exit: Inst,
+    /// The number of times the body is executed on each entry of the loop.
+    trip_count: u64,
+}
+
+/// Unroll the small counted loops of `func`.
+///
+/// The CFG, dominator tree and loop analysis are recomputed when any loop is unrolled.
+pub fn do_loop_unrolling(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
+    loop_analysis: &mut LoopAnalysis,
+) {
+    let _tt = timing::loop_unroll();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    let mut changed = false;
+    for lp in loop_analysis.loops() {
+        let counted = match CountedLoop::new(func, cfg, loop_analysis, lp) {
+            Some(counted) => counted,
+            None => continue,
+        };
+        let size = counted.body.len() as u64;
+        if counted.trip_count * size <= MAX_UNROLLED_SIZE as u64 {
+            counted.unroll(func, counted.trip_count);
+            counted.remove_back_edge(func);
+        } else if let Some(&factor) = PARTIAL_UNROLL_FACTORS.iter().find(|&&factor| {
+            factor < counted.trip_count
+                && counted.trip_count % factor == 0
+                && factor * size <= MAX_UNROLLED_SIZE as u64
+        }) {
+            counted.unroll(func, factor);
+        } else {
+            continue;
+        }
+        changed = true;
+    }
+
+    if changed {
+        cfg.compute(func);
+        domtree.compute(func, cfg);
+        loop_analysis.compute(func, cfg, domtree);
+    }
+}
+
+impl CountedLoop {
+    /// Get the trip count and the shape of `lp`, if it is a counted loop of a single EBB.
+    fn new(
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        loop_analysis: &LoopAnalysis,
+        lp: Loop,
+    ) -> Option<Self> {
+        let header = loop_analysis.loop_header(lp);
+        if func
+            .layout
+            .ebbs()
+            .any(|ebb| ebb != header && loop_analysis.is_in_loop(ebb, lp))
+        {
+            return None;
+        }
+
+        // The EBB must end with a conditional branch followed by a jump, one of them going back
+        // to the header.
+        let last = func.layout.last_inst(header)?;
+        let branch = func.layout.prev_inst(last)?;
+        if func.dfg[last].opcode() != Opcode::Jump {
+            return None;
+        }
+        let (branch_dest, jump_dest) = match (
+            func.dfg.analyze_branch(branch),
+            func.dfg.analyze_branch(last),
+        ) {
+            (BranchInfo::SingleDest(branch_dest, _), BranchInfo::SingleDest(jump_dest, _)) => {
+                (branch_dest, jump_dest)
+            }
+            _ => return None,
+        };
+        let (back_edge, exit) = if branch_dest == header && jump_dest != header {
+            (branch, last)
+        } else if jump_dest == header && branch_dest != header {
+            (last, branch)
+        } else {
+            return None;
+        };
+        let body: Vec<Inst> = func
+            .layout
+            .ebb_insts(header)
+            .take_while(|&inst| inst != branch)
+            .collect();
+        if body.iter().any(|&inst| func.dfg[inst].opcode().is_branch()) {
+            return None;
+        }
+
+        let edges = LoopEdges::new(func, cfg, loop_analysis, lp)?;
+        let ivs = find_induction_variables(func, loop_analysis, lp, &edges);
+
+        // The loop continues when the comparison has the outcome that takes the branch to the
+        // header.
+        let (cond, lhs, rhs, taken) = comparison(func, branch)?;
+        let continues = taken == (back_edge == branch);
+
+        // The compared value is either the induction variable or its increment.
+        let compared = func.dfg.resolve_aliases(lhs);
+        let (iv, offset) = ivs.iter().find_map(|iv| {
+            let increment = func
+                .dfg
+                .resolve_aliases(branch_args(&func.dfg, back_edge)[iv.index]);
+            if compared == iv.param {
+                Some((iv, 0))
+            } else if compared == increment {
+                Some((iv, iv.step))
+            } else {
+                None
+            }
+        })?;
+
+        // The variable must start at the same constant on all entries.
+        let mut inits = edges
+            .entries
+            .iter()
+            .map(|&entry| constant_value(&func.dfg, branch_args(&func.dfg, entry)[iv.index]));
+        let init = inits.next()??;
+        if !inits.all(|other| other == Some(init)) {
+            return None;
+        }
+
+        let bits = u32::from(func.dfg.value_type(iv.param).bits());
+        if bits > 64 {
+            return None;
+        }
+        let mut value = init;
+        for trip_count in 1..=MAX_TRIP_COUNT {
+            if compare(cond, value.wrapping_add(offset), rhs, bits) != continues {
+                return Some(Self {
+                    header,
+                    body,
+                    back_edge,
+                    exit,
+                    trip_count,
+                });
+            }
+            value = value.wrapping_add(iv.step);
+        }
+        None
+    }
+
+    /// Lay out `factor - 1` copies of the body before it, each computing the next iteration.
+    fn unroll(&self, func: &mut Function, factor: u64) {
+        let back_args: Vec<Value> = branch_args(&func.dfg, self.back_edge)
+            .iter()
+            .map(|&arg| func.dfg.resolve_aliases(arg))
+            .collect();
+
+        // The first copy starts with new header parameters.
+        let params = func.dfg.ebb_params(self.header).to_vec();
+        let mut values = FxHashMap::default();
+        for &param in &params {
+            let ty = func.dfg.value_type(param);
+            let new_param = func.dfg.replace_ebb_param(param, ty);
+            values.insert(param, new_param);
+        }
+
+        let mut pos = FuncCursor::new(func).at_inst(self.body[0]);
+        for _ in 1..factor {
+            for &inst in &self.body {
+                let args: Vec<Value> = pos
+                    .func
+                    .dfg
+                    .inst_args(inst)
+                    .iter()
+                    .map(|&arg| map_value(&values, pos.func.dfg.resolve_aliases(arg)))
+                    .collect();
+                let mut data = pos.func.dfg[inst].clone();
+                if data.take_value_list().is_some() {
+                    data.put_value_list(ValueList::from_slice(
+                        &args,
+                        &mut pos.func.dfg.value_lists,
+                    ));
+                }
+                let copy = pos.func.dfg.make_inst(data);
+                pos.func.dfg.inst_args_mut(copy).copy_from_slice(&args);
+                let ctrl_typevar = pos.func.dfg.ctrl_typevar(inst);
+                pos.func.dfg.make_inst_results(copy, ctrl_typevar);
+                pos.insert_inst(copy);
+                // Only functions with source locations get an entry per instruction.
+                let srcloc = pos.func.srclocs[inst];
+                if !srcloc.is_default() {
+                    pos.func.srclocs[copy] = srcloc;
+                }
+                for (&result, &copied) in pos
+                    .func
+                    .dfg
+                    .inst_results(inst)
+                    .iter()
+                    .zip(pos.func.dfg.inst_results(copy))
+                {
+                    values.insert(result, copied);
+                }
+            }
+
+            // The next copy starts with the values passed back to the header.
+            let next: Vec<Value> = back_args
+                .iter()
+                .map(|&arg| map_value(&values, arg))
+                .collect();
+            for (&param, &value) in params.iter().zip(&next) {
+                values.insert(param, value);
+            }
+        }
+
+        // The original body computes the last iteration.
+        for &param in &params {
+            func.dfg.change_to_alias(param, values[&param]);
+        }
+    }
+
+    /// Replace the branches at the end of the loop by a jump out of it.
+    fn remove_back_edge(&self, func: &mut Function) {
+        if self.exit == func.layout.last_inst(self.header).unwrap() {
+            func.layout.remove_inst(self.back_edge);
+            return;
+        }
+        let (dest, args) = match func.dfg.analyze_branch(self.exit) {
+            BranchInfo::SingleDest(dest, args) => (dest, args.to_vec()),
+            _ => panic!("the loop exit has a single destination"),
+        };
+        func.layout.remove_inst(self.back_edge);
+        let mut pos = FuncCursor::new(func).at_inst(self.exit);
+        pos.ins().jump(dest, &args);
+        pos.remove_inst();
+    }
+}
+
+/// Get the value replacing `value` in the current copy of the loop body.
+fn map_value(values: &FxHashMap<Value, Value>, value: Value) -> Value {
+    *values.get(&value).unwrap_or(&value)
+}
+
+/// Get the comparison a conditional branch depends on, as the condition, its operand, its
+/// constant right hand side, and the outcome of the comparison which takes the branch.
+fn comparison(func: &Function, branch: Inst) -> Option<(IntCC, Value, i64, bool)> {
+    let dfg = &func.dfg;
+    let (cond, lhs, rhs, taken) = match dfg[branch] {
+        InstructionData::BranchIcmp { cond, ref args, .. } => {
+            let args = args.as_slice(&dfg.value_lists);
+            (cond, args[0], args[1], true)
+        }
+        InstructionData::Branch {
+            opcode, ref args, ..
+        } => {
+            let taken = opcode == Opcode::Brnz;
+            let arg = dfg.resolve_aliases(args.first(&dfg.value_lists)?);
+            let inst = match dfg.value_def(arg) {
+                ValueDef::Result(inst, _) => inst,
+                ValueDef::Param(..) => return None,
+            };
+            match dfg[inst] {
+                InstructionData::IntCompare { cond, args, .. } => (cond, args[0], args[1], taken),
+                InstructionData::IntCompareImm { cond, arg, imm, .. } => {
+                    return Some((cond, arg, imm.into(), taken));
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    match (constant_value(dfg, lhs), constant_value(dfg, rhs)) {
+        (None, Some(rhs)) => Some((cond, lhs, rhs, taken)),
+        (Some(lhs), None) => Some((cond.reverse(), rhs, lhs, taken)),
+        _ => None,
+    }
+}
+
+/// Compare the `bits` wide integers `lhs` and `rhs`.
+fn compare(cond: IntCC, lhs: i64, rhs: i64, bits: u32) -> bool {
+    let shift = 64 - bits;
+    let (slhs, srhs) = ((lhs << shift) >> shift, (rhs << shift) >> shift);
+    let (ulhs, urhs) = ((lhs as u64) << shift, (rhs as u64) << shift);
+    match cond {
+        IntCC::Equal => ulhs == urhs,
+        IntCC::NotEqual => ulhs != urhs,
+        IntCC::SignedLessThan => slhs < srhs,
+        IntCC::SignedGreaterThanOrEqual => slhs >= srhs,
+        IntCC::SignedGreaterThan => slhs > srhs,
+        IntCC::SignedLessThanOrEqual => slhs <= srhs,
+        IntCC::UnsignedLessThan => ulhs < urhs,
+        IntCC::UnsignedGreaterThanOrEqual => ulhs >= urhs,
+        IntCC::UnsignedGreaterThan => ulhs > urhs,
+        IntCC::UnsignedLessThanOrEqual => ulhs <= urhs,
+    }
+}
@@ -180,6 +180,12 @@ struct MemOpInfo {
 }
 
 fn optimize_complex_addresses(pos: &mut EncCursor, inst: Inst, isa: &dyn TargetIsa) {
+    // Atomic accesses have no complex forms, and their encodings only take a plain address.
+    match pos.func.dfg[inst].opcode() {
+        Opcode::AtomicLoad | Opcode::AtomicStore => return,
+        _ => {}
+    }
+
     // Look for simple loads and stores we can optimize.
     let info = match pos.func.dfg[inst] {
         InstructionData::Load {
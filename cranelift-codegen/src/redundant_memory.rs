@@ -0,0 +1,459 @@
+//! Redundant load elimination and dead store elimination.
+//!
+//! Memory accesses are related to each other through the *memory state* they observe: the last
+//! instruction which may have written to their alias region. Two loads of the same address and
+//! type which observe the same memory state read the same value, and so does a load observing
+//! the state left by a store to its address. The memory states at the top of each EBB are found
+//! by a data flow analysis over the CFG, and the dominator tree tells which of these earlier
+//! accesses are available at a load.
+//!
+//! A store is dead when every path from it reaches a store overwriting the same location before
+//! anything can read it, or when it writes to a stack slot whose address never escapes and which
+//! is not read again before returning.
+//!
+//! Only plain `load`, `store`, `stack_load` and `stack_store` instructions are optimized. Any other
+//! instruction which may write to memory, like calls and atomic operations, invalidates
+//! everything known about memory.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::entity::EntitySet;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::FxHashMap;
+use crate::ir::{
+    AliasRegion, DataFlowGraph, Ebb, Function, Inst, InstructionData, Opcode, StackSlot, Type,
+    Value,
+};
+use crate::timing;
+use cranelift_entity::{EntityRef, SecondaryMap};
+use std::vec::Vec;
+
+/// The number of memory states tracked for alias regions: one per region, and one for the
+/// accesses without an alias region. Each stack slot whose address doesn't escape has its own
+/// memory state after these.
+const NUM_REGION_STATES: usize = 5;
+
+/// Get the index of the memory state observed by accesses to `region`.
+fn region_state(region: Option<AliasRegion>) -> usize {
+    match region {
+        None => 0,
+        Some(AliasRegion::Heap) => 1,
+        Some(AliasRegion::Table) => 2,
+        Some(AliasRegion::Vmctx) => 3,
+        Some(AliasRegion::Stack) => 4,
+    }
+}
+
+/// The last write to some part of memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum MemoryState {
+    /// Nothing was written since the function was entered.
+    Entry,
+    /// The writes reaching the top of an EBB differ between its predecessors.
+    BeforeEbb(Ebb),
+    /// The last write was this instruction.
+    Store(Inst),
+}
+
+/// The memory states of all the alias regions and private stack slots at some program point.
+type MemoryStates = Vec<MemoryState>;
+
+/// A location in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Address {
+    /// An address computed by a value, with an offset.
+    Pointer(Value, i32),
+    /// An offset into a stack slot.
+    Slot(StackSlot, i32),
+}
+
+/// A load or store which can be optimized.
+#[derive(Clone, Copy, Debug)]
+struct Access {
+    address: Address,
+    /// The type of the value loaded or stored.
+    ty: Type,
+    region: Option<AliasRegion>,
+    /// Can this access trap?
+    can_trap: bool,
+    readonly: bool,
+}
+
+impl Access {
+    /// Get the optimizable `load`, `stack_load`, `store` or `stack_store` performed by `inst`.
+    fn of(dfg: &DataFlowGraph, inst: Inst) -> Option<Self> {
+        let access = match dfg[inst] {
+            InstructionData::Load {
+                opcode: Opcode::Load,
+                flags,
+                arg,
+                offset,
+            } => Self {
+                address: Address::Pointer(dfg.resolve_aliases(arg), offset.into()),
+                ty: dfg.value_type(dfg.first_result(inst)),
+                region: flags.alias_region(),
+                can_trap: !flags.notrap(),
+                readonly: flags.readonly(),
+            },
+            InstructionData::Store {
+                opcode: Opcode::Store,
+                flags,
+                args,
+                offset,
+            } => Self {
+                address: Address::Pointer(dfg.resolve_aliases(args[1]), offset.into()),
+                ty: dfg.value_type(args[0]),
+                region: flags.alias_region(),
+                can_trap: !flags.notrap(),
+                readonly: false,
+            },
+            InstructionData::StackLoad {
+                opcode: Opcode::StackLoad,
+                stack_slot,
+                offset,
+            } => Self {
+                address: Address::Slot(stack_slot, offset.into()),
+                ty: dfg.value_type(dfg.first_result(inst)),
+                region: Some(AliasRegion::Stack),
+                can_trap: false,
+                readonly: false,
+            },
+            InstructionData::StackStore {
+                opcode: Opcode::StackStore,
+                arg,
+                stack_slot,
+                offset,
+            } => Self {
+                address: Address::Slot(stack_slot, offset.into()),
+                ty: dfg.value_type(arg),
+                region: Some(AliasRegion::Stack),
+                can_trap: false,
+                readonly: false,
+            },
+            _ => return None,
+        };
+        Some(access)
+    }
+
+    /// Is this access in a stack slot whose address never escapes?
+    fn is_private(&self, escaping: &EntitySet<StackSlot>) -> bool {
+        match self.address {
+            Address::Slot(slot, _) => !escaping.contains(slot),
+            Address::Pointer(..) => false,
+        }
+    }
+
+    /// Get the index of the memory state observed by this access.
+    fn state(&self, escaping: &EntitySet<StackSlot>) -> usize {
+        match self.address {
+            Address::Slot(slot, _) if !escaping.contains(slot) => NUM_REGION_STATES + slot.index(),
+            _ => region_state(self.region),
+        }
+    }
+
+    /// Are the bytes accessed by `self` and `other` known to be distinct?
+    fn is_disjoint(&self, other: &Self, escaping: &EntitySet<StackSlot>) -> bool {
+        if let (Some(a), Some(b)) = (self.region, other.region) {
+            if a != b {
+                return true;
+            }
+        }
+        let ranges_disjoint = |a: i32, b: i32| {
+            let (a, b) = (i64::from(a), i64::from(b));
+            a + i64::from(self.ty.bytes()) <= b || b + i64::from(other.ty.bytes()) <= a
+        };
+        match (self.address, other.address) {
+            (Address::Pointer(p, a), Address::Pointer(q, b)) => p == q && ranges_disjoint(a, b),
+            (Address::Slot(s, a), Address::Slot(t, b)) => s != t || ranges_disjoint(a, b),
+            (Address::Slot(..), Address::Pointer(..)) => self.is_private(escaping),
+            (Address::Pointer(..), Address::Slot(..)) => other.is_private(escaping),
+        }
+    }
+}
+
+/// The memory written by an instruction.
+enum Write {
+    Nothing,
+    Region(AliasRegion),
+    Anything,
+}
+
+/// Get the memory written by `inst`.
+fn written_memory(dfg: &DataFlowGraph, inst: Inst) -> Write {
+    let opcode = dfg[inst].opcode();
+    if opcode.is_call() || opcode.other_side_effects() {
+        return Write::Anything;
+    }
+    if !opcode.can_store() {
+        return Write::Nothing;
+    }
+    match dfg[inst] {
+        InstructionData::Store { flags, .. } | InstructionData::StoreComplex { flags, .. } => {
+            match flags.alias_region() {
+                Some(region) => Write::Region(region),
+                None => Write::Anything,
+            }
+        }
+        InstructionData::StackStore { .. } => Write::Region(AliasRegion::Stack),
+        _ => Write::Anything,
+    }
+}
+
+/// Update `states` with the effects of `inst` on memory.
+fn update_states(
+    states: &mut MemoryStates,
+    dfg: &DataFlowGraph,
+    escaping: &EntitySet<StackSlot>,
+    inst: Inst,
+) {
+    if let Some(access) = Access::of(dfg, inst) {
+        // Nothing else can write to a private stack slot.
+        if access.is_private(escaping) && dfg[inst].opcode().can_store() {
+            states[access.state(escaping)] = MemoryState::Store(inst);
+            return;
+        }
+    }
+    match written_memory(dfg, inst) {
+        Write::Nothing => {}
+        Write::Region(region) => {
+            // Accesses without an alias region may alias any store.
+            states[region_state(None)] = MemoryState::Store(inst);
+            states[region_state(Some(region))] = MemoryState::Store(inst);
+        }
+        Write::Anything => {
+            for state in &mut states[..NUM_REGION_STATES] {
+                *state = MemoryState::Store(inst);
+            }
+        }
+    }
+}
+
+/// Compute the memory states at the top of each reachable EBB.
+fn compute_entry_states(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    escaping: &EntitySet<StackSlot>,
+) -> SecondaryMap<Ebb, Option<MemoryStates>> {
+    let mut entry_states: SecondaryMap<Ebb, Option<MemoryStates>> = SecondaryMap::new();
+    let mut exit_states: SecondaryMap<Ebb, Option<MemoryStates>> = SecondaryMap::new();
+    let entry_block = func.layout.entry_block();
+
+    // Iterate in reverse post-order until reaching a fixed point. EBBs whose predecessors haven't
+    // been visited yet optimistically assume they agree with the other ones.
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &ebb in domtree.cfg_postorder().iter().rev() {
+            let mut states = if Some(ebb) == entry_block {
+                vec![MemoryState::Entry; NUM_REGION_STATES + func.stack_slots.next_key().index()]
+            } else {
+                let mut meet: Option<MemoryStates> = None;
+                for pred in cfg.pred_iter(ebb) {
+                    let pred_states = match exit_states[pred.ebb] {
+                        Some(ref states) => states,
+                        None => continue,
+                    };
+                    meet = Some(match meet {
+                        None => pred_states.clone(),
+                        Some(mut states) => {
+                            for (state, pred_state) in states.iter_mut().zip(pred_states) {
+                                if state != pred_state {
+                                    *state = MemoryState::BeforeEbb(ebb);
+                                }
+                            }
+                            states
+                        }
+                    });
+                }
+                meet.expect("reachable EBB without visited predecessors")
+            };
+            if entry_states[ebb].as_ref() == Some(&states) {
+                continue;
+            }
+            entry_states[ebb] = Some(states.clone());
+
+            for inst in func.layout.ebb_insts(ebb) {
+                update_states(&mut states, &func.dfg, escaping, inst);
+            }
+            if exit_states[ebb].as_ref() != Some(&states) {
+                exit_states[ebb] = Some(states);
+                changed = true;
+            }
+        }
+    }
+    entry_states
+}
+
+/// The key identifying the value available at a memory location.
+type LocationKey = (Address, Type, MemoryState);
+
+/// Eliminate redundant loads and stores in `func`.
+pub fn do_redundant_memory_elimination(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+) {
+    let _tt = timing::redundant_memory();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+
+    // Stack slots whose address is taken may also be accessed through pointers.
+    let mut escaping = EntitySet::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let InstructionData::StackLoad {
+                opcode: Opcode::StackAddr,
+                stack_slot,
+                ..
+            } = func.dfg[inst]
+            {
+                escaping.insert(stack_slot);
+            }
+        }
+    }
+    let entry_states = compute_entry_states(func, cfg, domtree, &escaping);
+    remove_redundant_accesses(func, domtree, &escaping, &entry_states);
+
+    let mut dead_stores = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if is_dead_store(func, cfg, &escaping, inst) {
+                dead_stores.push(inst);
+            }
+        }
+    }
+    for inst in dead_stores {
+        func.layout.remove_inst(inst);
+    }
+}
+
+/// Forward stored values to loads, and remove loads of values which are already available as
+/// well as stores of values which memory already holds.
+fn remove_redundant_accesses(
+    func: &mut Function,
+    domtree: &DominatorTree,
+    escaping: &EntitySet<StackSlot>,
+    entry_states: &SecondaryMap<Ebb, Option<MemoryStates>>,
+) {
+    // The instruction and value of the last access to each location, which are only available
+    // where that instruction dominates.
+    let mut available: FxHashMap<LocationKey, (Inst, Value)> = FxHashMap::default();
+    let mut pos = FuncCursor::new(func);
+
+    for &ebb in domtree.cfg_postorder().iter().rev() {
+        let mut states = entry_states[ebb].clone().unwrap();
+        pos.goto_top(ebb);
+        while let Some(inst) = pos.next_inst() {
+            let access = match Access::of(&pos.func.dfg, inst) {
+                Some(access) => access,
+                None => {
+                    update_states(&mut states, &pos.func.dfg, escaping, inst);
+                    continue;
+                }
+            };
+            let state = if access.readonly {
+                MemoryState::Entry
+            } else {
+                states[access.state(escaping)]
+            };
+            let key = (access.address, access.ty, state);
+            let known = available
+                .get(&key)
+                .filter(|&&(def, _)| domtree.dominates(def, inst, &pos.func.layout))
+                .map(|&(_, value)| value);
+
+            if pos.func.dfg[inst].opcode().can_load() {
+                let result = pos.func.dfg.first_result(inst);
+                match known {
+                    Some(value) => {
+                        pos.func.dfg.clear_results(inst);
+                        pos.func.dfg.change_to_alias(result, value);
+                        pos.remove_inst_and_step_back();
+                    }
+                    None => {
+                        available.insert(key, (inst, result));
+                    }
+                }
+            } else {
+                let value = pos
+                    .func
+                    .dfg
+                    .resolve_aliases(pos.func.dfg.inst_args(inst)[0]);
+                if known.map(|known| pos.func.dfg.resolve_aliases(known)) == Some(value) {
+                    // Memory already holds the stored value.
+                    pos.remove_inst_and_step_back();
+                    continue;
+                }
+                update_states(&mut states, &pos.func.dfg, escaping, inst);
+                let key = (access.address, access.ty, MemoryState::Store(inst));
+                available.insert(key, (inst, value));
+            }
+        }
+    }
+}
+
+/// Is `inst` a store whose value can never be read?
+fn is_dead_store(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    escaping: &EntitySet<StackSlot>,
+    inst: Inst,
+) -> bool {
+    let store = match Access::of(&func.dfg, inst) {
+        Some(access) if func.dfg[inst].opcode().can_store() => access,
+        _ => return false,
+    };
+    // Nothing can observe a stack slot whose address doesn't escape once the function traps or
+    // returns.
+    let private = store.is_private(escaping);
+
+    // Follow the only path from the store, through jumps to EBBs with a single predecessor.
+    let mut visited = EntitySet::new();
+    let mut next = func.layout.next_inst(inst);
+    while let Some(current) = next {
+        let opcode = func.dfg[current].opcode();
+        if let Some(access) = Access::of(&func.dfg, current) {
+            let is_store = opcode.can_store();
+            // A wider store that traps may do so without writing anything, leaving the bytes of
+            // the dead store visible.
+            if is_store
+                && access.address == store.address
+                && (access.ty.bytes() == store.ty.bytes()
+                    || (access.ty.bytes() > store.ty.bytes() && (private || !access.can_trap)))
+            {
+                return true;
+            }
+            if !is_store && !access.readonly && !access.is_disjoint(&store, escaping) {
+                return false;
+            }
+            // Memory is observable after a trap. If the dead store could trap, removing it would
+            // also let other stores happen before the overwriting store traps instead.
+            if !private && (access.can_trap || (is_store && store.can_trap)) {
+                return false;
+            }
+        } else if opcode.is_return() {
+            return private;
+        } else if opcode == Opcode::Jump {
+            let dest = func.dfg[current].branch_destination().unwrap();
+            if cfg.pred_iter(dest).count() != 1 || visited.contains(dest) {
+                return false;
+            }
+            visited.insert(dest);
+            next = func.layout.first_inst(dest);
+            continue;
+        } else if opcode.is_branch() || opcode.is_terminator() {
+            return false;
+        } else if !private
+            && (opcode.can_load()
+                || opcode.can_store()
+                || opcode.can_trap()
+                || opcode.is_call()
+                || opcode.other_side_effects())
+        {
+            return false;
+        }
+        next = func.layout.next_inst(current);
+    }
+    false
+}
@@ -0,0 +1,268 @@
+//! Strength reduction of induction variable expressions.
+//!
+//! Loops walking through memory typically compute an address like `base + iv * scale` in every
+//! iteration, where `iv` is a basic induction variable and `base` is loop invariant. Such an
+//! expression is an induction variable of its own: it starts at `base + init * scale` and grows
+//! by `step * scale` in every iteration. This pass turns it into a new parameter of the loop
+//! header, replacing a multiplication in the loop with an addition.
+//!
+//! Multiplications of induction variables by constants are reduced the same way when their
+//! results are used for anything else than such addresses.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::dominator_tree::DominatorTree;
+use crate::flowgraph::ControlFlowGraph;
+use crate::fx::{FxHashMap, FxHashSet};
+use crate::induction_variables::{
+    branch_args, constant_value, find_induction_variables, InductionVariable, LoopEdges,
+};
+use crate::ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value, ValueDef};
+use crate::loop_analysis::{Loop, LoopAnalysis};
+use crate::timing;
+use std::vec::Vec;
+
+/// An expression `base + iv * scale` computed in a loop.
+struct Expression {
+    /// The instruction computing the expression.
+    inst: Inst,
+    /// The index of the induction variable in the list of the loop's variables.
+    iv: usize,
+    /// The constant the induction variable is multiplied by.
+    scale: i64,
+    /// The loop invariant value added to the product, if any.
+    base: Option<Value>,
+}
+
+/// Reduce the strength of the expressions derived from induction variables in all loops.
+///
+/// The CFG, dominator tree and loop analysis remain valid.
+pub fn do_strength_reduction(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+) {
+    let _tt = timing::strength_reduction();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    for lp in loop_analysis.loops() {
+        let edges = match LoopEdges::new(func, cfg, loop_analysis, lp) {
+            Some(edges) => edges,
+            None => continue,
+        };
+        let ivs = find_induction_variables(func, loop_analysis, lp, &edges);
+        if ivs.is_empty() {
+            continue;
+        }
+        let header = loop_analysis.loop_header(lp);
+        for expr in find_expressions(func, domtree, loop_analysis, lp, &edges, &ivs) {
+            reduce(func, header, &edges, &ivs[expr.iv], &expr);
+        }
+    }
+}
+
+/// Find the expressions derived from the induction variables `ivs` of `lp` which are worth
+/// reducing.
+fn find_expressions(
+    func: &Function,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+    edges: &LoopEdges,
+    ivs: &[InductionVariable],
+) -> Vec<Expression> {
+    let dfg = &func.dfg;
+    let insts: Vec<Inst> = func
+        .layout
+        .ebbs()
+        .filter(|&ebb| loop_analysis.is_in_loop(ebb, lp))
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .collect();
+
+    // First the products of induction variables and constants.
+    let mut products = FxHashMap::default();
+    for &inst in &insts {
+        if let Some((value, scale)) = scaled_value(func, inst) {
+            if let Some(iv) = ivs.iter().position(|iv| iv.param == value) {
+                products.insert(dfg.first_result(inst), (iv, scale));
+            }
+        }
+    }
+
+    // Then the sums of those products and loop invariant values.
+    let mut expressions = Vec::new();
+    let mut sums = FxHashSet::default();
+    for &inst in &insts {
+        if let InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        } = dfg[inst]
+        {
+            let args = [dfg.resolve_aliases(args[0]), dfg.resolve_aliases(args[1])];
+            for &(product, base) in &[(args[0], args[1]), (args[1], args[0])] {
+                if let Some(&(iv, scale)) = products.get(&product) {
+                    if is_invariant(func, domtree, loop_analysis, lp, edges, base) {
+                        expressions.push(Expression {
+                            inst,
+                            iv,
+                            scale,
+                            base: Some(base),
+                        });
+                        sums.insert(inst);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Products only used by the sums above would just become dead.
+    let mut used = FxHashSet::default();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if !sums.contains(&inst) {
+                used.extend(
+                    dfg.inst_args(inst)
+                        .iter()
+                        .map(|&arg| dfg.resolve_aliases(arg)),
+                );
+            }
+        }
+    }
+    for &inst in &insts {
+        let result = match dfg.inst_results(inst).first() {
+            Some(&result) => result,
+            None => continue,
+        };
+        if let Some(&(iv, scale)) = products.get(&result) {
+            if used.contains(&result) {
+                expressions.push(Expression {
+                    inst,
+                    iv,
+                    scale,
+                    base: None,
+                });
+            }
+        }
+    }
+    expressions
+}
+
+/// If `inst` multiplies a value by a constant other than 0 or 1, get the value and the constant.
+fn scaled_value(func: &Function, inst: Inst) -> Option<(Value, i64)> {
+    let dfg = &func.dfg;
+    if !dfg.ctrl_typevar(inst).is_int() {
+        return None;
+    }
+    let (value, scale) = match dfg[inst] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::ImulImm,
+            arg,
+            imm,
+        } => (arg, imm.into()),
+        InstructionData::BinaryImm {
+            opcode: Opcode::IshlImm,
+            arg,
+            imm,
+        } => {
+            let bits = i64::from(dfg.ctrl_typevar(inst).bits());
+            let amount: i64 = imm.into();
+            (arg, 1i64.wrapping_shl((amount & (bits - 1)) as u32))
+        }
+        InstructionData::Binary {
+            opcode: Opcode::Imul,
+            args,
+        } => match (constant_value(dfg, args[0]), constant_value(dfg, args[1])) {
+            (None, Some(scale)) => (args[0], scale),
+            (Some(scale), None) => (args[1], scale),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    if scale == 0 || scale == 1 {
+        None
+    } else {
+        Some((dfg.resolve_aliases(value), scale))
+    }
+}
+
+/// Is `value` defined outside of `lp`, and available on all of its `edges` entering it?
+fn is_invariant(
+    func: &Function,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+    edges: &LoopEdges,
+    value: Value,
+) -> bool {
+    let def = func.dfg.value_def(value);
+    let ebb = match def {
+        ValueDef::Result(inst, _) => func.layout.inst_ebb(inst).unwrap(),
+        ValueDef::Param(ebb, _) => ebb,
+    };
+    !loop_analysis.is_in_loop(ebb, lp)
+        && edges
+            .entries
+            .iter()
+            .all(|&entry| domtree.dominates(def, entry, &func.layout))
+}
+
+/// Replace `expr` by a new parameter of the loop `header`, which is computed from the initial
+/// value of `iv` on the entries of the loop and incremented along with it.
+fn reduce(
+    func: &mut Function,
+    header: Ebb,
+    edges: &LoopEdges,
+    iv: &InductionVariable,
+    expr: &Expression,
+) {
+    let value = func.dfg.first_result(expr.inst);
+    let ty = func.dfg.value_type(value);
+    let param = func.dfg.append_ebb_param(header, ty);
+
+    for &entry in &edges.entries {
+        let init = branch_args(&func.dfg, entry)[iv.index];
+        let mut pos = FuncCursor::new(func).at_inst(entry);
+        let start = match (constant_value(&pos.func.dfg, init), expr.base) {
+            (Some(0), Some(base)) => base,
+            (Some(init), Some(base)) => pos.ins().iadd_imm(base, init.wrapping_mul(expr.scale)),
+            (Some(init), None) => pos.ins().iconst(ty, init.wrapping_mul(expr.scale)),
+            (None, base) => {
+                let product = pos.ins().imul_imm(init, expr.scale);
+                match base {
+                    Some(base) => pos.ins().iadd(base, product),
+                    None => product,
+                }
+            }
+        };
+        pos.func.dfg.append_inst_arg(entry, start);
+    }
+
+    // Each increment of the induction variable gets a matching increment of the new one.
+    let mut increments = FxHashMap::default();
+    for &back_edge in &edges.back_edges {
+        let next = func
+            .dfg
+            .resolve_aliases(branch_args(&func.dfg, back_edge)[iv.index]);
+        let increment = match increments.get(&next) {
+            Some(&increment) => increment,
+            None => {
+                let inst = match func.dfg.value_def(next) {
+                    ValueDef::Result(inst, _) => inst,
+                    ValueDef::Param(..) => panic!("{} is not an increment of {}", next, iv.param),
+                };
+                let mut pos = FuncCursor::new(func).after_inst(inst);
+                let increment = pos.ins().iadd_imm(param, iv.step.wrapping_mul(expr.scale));
+                increments.insert(next, increment);
+                increment
+            }
+        };
+        func.dfg.append_inst_arg(back_edge, increment);
+    }
+
+    func.dfg.clear_results(expr.inst);
+    func.dfg.change_to_alias(value, param);
+    func.layout.remove_inst(expr.inst);
+}
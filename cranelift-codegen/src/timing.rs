@@ -61,6 +61,9 @@ define_passes! {
     legalize: "Legalization",
     gvn: "Global value numbering",
     licm: "Loop invariant code motion",
+    strength_reduction: "Induction variable strength reduction",
+    loop_unroll: "Loop unrolling",
+    redundant_memory: "Redundant load and dead store elimination",
     unreachable_code: "Remove unreachable blocks",
 
     regalloc: "Register allocation",
@@ -366,6 +366,18 @@ impl<'a> Verifier<'a> {
                         }
                     }
                 }
+                ir::GlobalValueData::Symbol { tls: true, .. } => {
+                    if let Some(isa) = self.isa {
+                        if !isa.supports_tls() {
+                            report!(
+                                errors,
+                                gv,
+                                "thread-local global value isn't supported on {}",
+                                isa.triple()
+                            );
+                        }
+                    }
+                }
                 _ => {}
             }
         }
@@ -716,6 +728,8 @@ impl<'a> Verifier<'a> {
             | IntSelect { .. }
             | Load { .. }
             | Store { .. }
+            | AtomicRmw { .. }
+            | AtomicCas { .. }
             | RegMove { .. }
             | CopySpecial { .. }
             | CopyToSsa { .. }
@@ -1749,7 +1763,9 @@ impl<'a> Verifier<'a> {
         // If this is some sort of a store instruction, get the memflags, else, just return.
         let memflags = match *inst_data {
             ir::InstructionData::Store { flags, .. }
-            | ir::InstructionData::StoreComplex { flags, .. } => flags,
+            | ir::InstructionData::StoreComplex { flags, .. }
+            | ir::InstructionData::AtomicRmw { flags, .. }
+            | ir::InstructionData::AtomicCas { flags, .. } => flags,
             _ => return Ok(()),
         };
 
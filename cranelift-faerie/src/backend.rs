@@ -3,18 +3,19 @@
 use crate::container;
 use crate::traps::{FaerieTrapManifest, FaerieTrapSink};
 use cranelift_codegen::binemit::{
-    Addend, CodeOffset, NullStackmapSink, NullTrapSink, Reloc, RelocSink, Stackmap, StackmapSink,
+    Addend, CodeOffset, FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, NullStackmapSink,
+    NullTrapSink, Reloc, RelocSink, Stackmap, StackmapSink,
 };
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{self, binemit, ir};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleError,
-    ModuleNamespace, ModuleResult,
+    Backend, DataContext, DataDescription, DataId, DebugRelocTarget, DebugSection, FuncId, Init,
+    Linkage, ModuleError, ModuleNamespace, ModuleResult,
 };
 use faerie;
 use failure::Error;
 use std::fs::File;
-use target_lexicon::Triple;
+use target_lexicon::{BinaryFormat, Triple};
 
 #[derive(Debug)]
 /// Setting to enable collection of traps. Setting this to `Enabled` in
@@ -77,6 +78,10 @@ pub struct FaerieBackend {
     artifact: faerie::Artifact,
     trap_manifest: Option<FaerieTrapManifest>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
+    /// Contents of the `.eh_frame` section.
+    eh_frame: Vec<u8>,
+    /// Relocations in the `.eh_frame` section, with the names of the functions they refer to.
+    eh_frame_relocs: Vec<(String, Reloc, FrameUnwindOffset)>,
 }
 
 pub struct FaerieCompiledFunction {
@@ -116,6 +121,8 @@ impl Backend for FaerieBackend {
                 FaerieTrapCollection::Disabled => None,
             },
             libcall_names: builder.libcall_names,
+            eh_frame: Vec::new(),
+            eh_frame_relocs: Vec::new(),
         }
     }
 
@@ -135,11 +142,18 @@ impl Backend for FaerieBackend {
         name: &str,
         linkage: Linkage,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
-    ) {
+    ) -> ModuleResult<()> {
+        if tls {
+            return Err(ModuleError::Backend(
+                "Faerie doesn't yet support TLS".to_owned(),
+            ));
+        }
         self.artifact
             .declare(name, translate_data_linkage(linkage, writable, align))
             .expect("inconsistent declarations");
+        Ok(())
     }
 
     fn define_function(
@@ -190,6 +204,17 @@ impl Backend for FaerieBackend {
             }
         }
 
+        if self.isa.triple().binary_format == BinaryFormat::Elf {
+            let mut unwind_sink = FaerieUnwindSink::default();
+            ctx.emit_unwind_info(&*self.isa, FrameUnwindKind::Libunwind, &mut unwind_sink);
+            let base = self.eh_frame.len();
+            self.eh_frame.extend_from_slice(&unwind_sink.data);
+            for (reloc, offset) in unwind_sink.relocs {
+                self.eh_frame_relocs
+                    .push((name.to_string(), reloc, base + offset));
+            }
+        }
+
         // because `define` will take ownership of code, this is our last chance
         let code_length = code.len() as u32;
 
@@ -200,15 +225,32 @@ impl Backend for FaerieBackend {
         Ok(FaerieCompiledFunction { code_length })
     }
 
+    fn define_function_lazily(
+        &mut self,
+        _id: FuncId,
+        _name: &str,
+        _signature: &ir::Signature,
+    ) -> ModuleResult<FaerieCompiledFunction> {
+        Err(ModuleError::Backend(
+            "Faerie can't compile functions lazily".to_owned(),
+        ))
+    }
+
+    fn take_lazy_compilation_errors(&mut self) -> Vec<(FuncId, ModuleError)> {
+        Vec::new()
+    }
+
     fn define_data(
         &mut self,
         _id: DataId,
         name: &str,
         _writable: bool,
+        tls: bool,
         _align: Option<u8>,
         data_ctx: &DataContext,
         namespace: &ModuleNamespace<Self>,
     ) -> ModuleResult<FaerieCompiledData> {
+        assert!(!tls, "Faerie doesn't yet support TLS");
         let &DataDescription {
             ref init,
             ref function_decls,
@@ -294,6 +336,12 @@ impl Backend for FaerieBackend {
         // Nothing to do.
     }
 
+    fn free_function(&mut self, _id: FuncId, _func: &FaerieCompiledFunction) -> ModuleResult<()> {
+        Err(ModuleError::Backend(
+            "Faerie can't discard a function once it has been written".to_owned(),
+        ))
+    }
+
     fn finalize_data(
         &mut self,
         _id: DataId,
@@ -311,7 +359,10 @@ impl Backend for FaerieBackend {
         // Nothing to do.
     }
 
-    fn finish(self) -> FaerieProduct {
+    fn finish(mut self) -> FaerieProduct {
+        if !self.eh_frame.is_empty() {
+            self.define_eh_frame();
+        }
         FaerieProduct {
             artifact: self.artifact,
             trap_manifest: self.trap_manifest,
@@ -319,6 +370,35 @@ impl Backend for FaerieBackend {
     }
 }
 
+impl FaerieBackend {
+    /// Define the `.eh_frame` section holding the unwind information of all the functions.
+    fn define_eh_frame(&mut self) {
+        const EH_FRAME: &str = ".eh_frame";
+        self.artifact
+            .declare(EH_FRAME, faerie::Decl::section(faerie::SectionKind::Data))
+            .expect("faerie declaration of .eh_frame");
+        self.artifact
+            .define(EH_FRAME, std::mem::replace(&mut self.eh_frame, Vec::new()))
+            .expect("inconsistent declaration");
+        for (name, reloc, offset) in &self.eh_frame_relocs {
+            let (raw_reloc, raw_addend) = container::raw_relocation(*reloc, self.isa.triple());
+            self.artifact
+                .link_with(
+                    faerie::Link {
+                        from: EH_FRAME,
+                        to: name,
+                        at: *offset as u64,
+                    },
+                    faerie::Reloc::Raw {
+                        reloc: raw_reloc,
+                        addend: raw_addend as i32,
+                    },
+                )
+                .expect("faerie relocation error");
+        }
+    }
+}
+
 /// This is the output of `Module`'s
 /// [`finish`](../cranelift_module/struct.Module.html#method.finish) function.
 /// It provides functions for writing out the object file to memory or a file.
@@ -336,6 +416,44 @@ impl FaerieProduct {
         &self.artifact.name
     }
 
+    /// Add the debug sections produced by a `DebugContext` to the artifact.
+    ///
+    /// The functions they describe must have been defined in the module.
+    pub fn add_debug_sections(&mut self, sections: &[DebugSection]) -> Result<(), Error> {
+        for section in sections {
+            self.artifact.declare_with(
+                section.name,
+                faerie::Decl::section(faerie::SectionKind::Debug),
+                section.data.clone(),
+            )?;
+        }
+        for section in sections {
+            for reloc in &section.relocs {
+                let to = match reloc.target {
+                    DebugRelocTarget::Function(ref name) => name.as_str(),
+                    DebugRelocTarget::Section(name) => name,
+                };
+                let size = match reloc.reloc {
+                    Reloc::Abs4 => 4,
+                    Reloc::Abs8 => 8,
+                    _ => unimplemented!(),
+                };
+                self.artifact.link_with(
+                    faerie::Link {
+                        from: section.name,
+                        to,
+                        at: u64::from(reloc.offset),
+                    },
+                    faerie::Reloc::Debug {
+                        size,
+                        addend: reloc.addend as i32,
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     /// Call `emit` on the faerie `Artifact`, producing bytes in memory.
     pub fn emit(&self) -> Result<Vec<u8>, Error> {
         self.artifact.emit()
@@ -460,6 +578,34 @@ impl<'a> RelocSink for FaerieRelocSink<'a> {
 }
 
 #[allow(dead_code)]
+#[derive(Default)]
+struct FaerieUnwindSink {
+    data: Vec<u8>,
+    relocs: Vec<(Reloc, FrameUnwindOffset)>,
+}
+
+impl FrameUnwindSink for FaerieUnwindSink {
+    fn offset(&self) -> FrameUnwindOffset {
+        self.data.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.data.extend_from_slice(b);
+    }
+
+    fn reserve(&mut self, len: usize) {
+        self.data.reserve(len)
+    }
+
+    fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+        self.relocs.push((r, off));
+    }
+
+    fn set_entry_offset(&mut self, _: FrameUnwindOffset) {
+        // Nothing to do: the whole buffer goes into `.eh_frame`.
+    }
+}
+
 struct FaerieStackmapSink<'a> {
     artifact: &'a mut faerie::Artifact,
     namespace: &'a ModuleNamespace<'a, FaerieBackend>,
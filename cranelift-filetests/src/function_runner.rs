@@ -1,10 +1,20 @@
 use core::mem;
 use cranelift_codegen::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
-use cranelift_codegen::ir::Function;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::{
+    types, AbiParam, ExternalName, Function, InstBuilder, MemFlags, Signature, Type, Value,
+};
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{settings, Context};
 use cranelift_native::builder as host_isa_builder;
-use memmap::MmapMut;
+use cranelift_reader::RunCommand;
+use memmap::{Mmap, MmapMut};
+
+/// Size of the slot each argument and result occupies in the buffer passed to a trampoline; large
+/// enough for any scalar or 128-bit vector.
+const VALUE_SIZE: usize = 16;
 
 /// Run a function on a host
 pub struct FunctionRunner {
@@ -31,65 +41,287 @@ impl FunctionRunner {
         FunctionRunner::with_host_isa(function, flags)
     }
 
-    /// Compile and execute a single function, expecting a boolean to be returned; a 'true' value is
-    /// interpreted as a successful test execution and mapped to Ok whereas a 'false' value is
-    /// interpreted as a failed test and mapped to Err.
-    pub fn run(&self) -> Result<(), String> {
-        let func = self.function.clone();
-        if !(func.signature.params.is_empty()
-            && func.signature.returns.len() == 1
-            && func.signature.returns.first().unwrap().value_type.is_bool())
-        {
+    /// Compile the function, along with a trampoline that can call it with any arguments, to
+    /// executable memory.
+    pub fn compile(&self) -> Result<CompiledFunction, String> {
+        let signature = self.function.signature.clone();
+        if signature.call_conv != self.isa.default_call_conv() {
             return Err(String::from(
-                "Functions must have a signature like: () -> boolean",
+                "Functions only run on the host's default calling convention; remove the specified calling convention in the function signature to use the host's default.",
             ));
         }
 
-        if func.signature.call_conv != self.isa.default_call_conv() {
-            return Err(String::from(
-                "Functions only run on the host's default calling convention; remove the specified calling convention in the function signature to use the host's default.",
+        let code = compile(self.function.clone(), self.isa.as_ref())?;
+        let trampoline = compile(
+            make_trampoline(&signature, self.isa.as_ref()),
+            self.isa.as_ref(),
+        )?;
+        Ok(CompiledFunction {
+            signature,
+            code,
+            trampoline,
+        })
+    }
+
+    /// Compile the function once and execute each of `commands` against it, stopping at the
+    /// first one that fails.
+    pub fn run(&self, commands: &[RunCommand]) -> Result<(), String> {
+        let compiled = self.compile()?;
+        for command in commands {
+            command.run(|args| compiled.call(args))?;
+        }
+        Ok(())
+    }
+}
+
+/// A function compiled by a `FunctionRunner`, ready to be called on the host.
+pub struct CompiledFunction {
+    signature: Signature,
+    code: Mmap,
+    trampoline: Mmap,
+}
+
+impl CompiledFunction {
+    /// Call the function with `args`, which must match its signature, and return its results.
+    pub fn call(&self, args: &[DataValue]) -> Result<Vec<DataValue>, String> {
+        let params = &self.signature.params;
+        if args.len() != params.len() {
+            return Err(format!(
+                "Expected {} arguments, found {}",
+                params.len(),
+                args.len()
             ));
         }
+        for (arg, param) in args.iter().zip(params) {
+            if !arg.is_type(param.value_type) {
+                return Err(format!(
+                    "Argument {} does not have type {}",
+                    arg, param.value_type
+                ));
+            }
+        }
 
-        // set up the context
-        let mut context = Context::new();
-        context.func = func;
-
-        // compile and encode the result to machine code
-        let relocs = &mut NullRelocSink {};
-        let traps = &mut NullTrapSink {};
-        let stackmaps = &mut NullStackmapSink {};
-        let code_info = context
-            .compile(self.isa.as_ref())
-            .map_err(|e| e.to_string())?;
-        let mut code_page =
-            MmapMut::map_anon(code_info.total_size as usize).map_err(|e| e.to_string())?;
-
-        unsafe {
-            context.emit_to_memory(
-                self.isa.as_ref(),
-                code_page.as_mut_ptr(),
-                relocs,
-                traps,
-                stackmaps,
-            );
-        };
+        // Arguments and results are passed through the same buffer, one slot per value.
+        let returns = &self.signature.returns;
+        let mut values = vec![0u128; params.len().max(returns.len())];
+        for ((slot, arg), param) in values.iter_mut().zip(args).zip(params) {
+            let mut bytes = [0; VALUE_SIZE];
+            let arg = arg.to_le_bytes(param.value_type);
+            bytes[..arg.len()].copy_from_slice(&arg);
+            *slot = u128::from_le_bytes(bytes);
+        }
+
+        let trampoline: extern "C" fn(*const u8, *mut u128) =
+            unsafe { mem::transmute(self.trampoline.as_ptr()) };
+        trampoline(self.code.as_ptr(), values.as_mut_ptr());
+
+        Ok(values
+            .iter()
+            .zip(returns)
+            .map(|(slot, ret)| {
+                let ty = ret.value_type;
+                DataValue::from_le_bytes(&slot.to_le_bytes()[..ty.bytes() as usize], ty)
+            })
+            .collect())
+    }
+}
+
+/// Compile `func` and copy its machine code to executable memory.
+fn compile(func: Function, isa: &dyn TargetIsa) -> Result<Mmap, String> {
+    // set up the context
+    let mut context = Context::new();
+    context.func = func;
 
-        let code_page = code_page.make_exec().map_err(|e| e.to_string())?;
-        let callable_fn: fn() -> bool = unsafe { mem::transmute(code_page.as_ptr()) };
+    // compile and encode the result to machine code
+    let relocs = &mut NullRelocSink {};
+    let traps = &mut NullTrapSink {};
+    let stackmaps = &mut NullStackmapSink {};
+    let code_info = context.compile(isa).map_err(|e| e.to_string())?;
+    let mut code_page =
+        MmapMut::map_anon(code_info.total_size as usize).map_err(|e| e.to_string())?;
+
+    unsafe {
+        context.emit_to_memory(isa, code_page.as_mut_ptr(), relocs, traps, stackmaps);
+    };
+
+    code_page.make_exec().map_err(|e| e.to_string())
+}
+
+/// Build a function that calls a function with `signature`, reading its arguments from and
+/// writing its results to a buffer of `VALUE_SIZE` slots.
+///
+/// The trampoline itself takes the address of the callee and the address of the buffer, using the
+/// host's default calling convention.
+fn make_trampoline(signature: &Signature, isa: &dyn TargetIsa) -> Function {
+    let pointer_type = isa.pointer_type();
+    let mut trampoline_sig = Signature::new(isa.default_call_conv());
+    trampoline_sig.params.push(AbiParam::new(pointer_type));
+    trampoline_sig.params.push(AbiParam::new(pointer_type));
+
+    let mut func =
+        Function::with_name_signature(ExternalName::testcase("trampoline"), trampoline_sig);
+    // Wide booleans can't be converted to integers on every backend, but they are passed in
+    // integer registers, so the callee is called as if it used integers instead.
+    let mut callee_sig = signature.clone();
+    for param in callee_sig
+        .params
+        .iter_mut()
+        .chain(callee_sig.returns.iter_mut())
+    {
+        param.value_type = call_type(param.value_type);
+    }
+    let callee_sig = func.import_signature(callee_sig);
+    let ebb = func.dfg.make_ebb();
+    let callee = func.dfg.append_ebb_param(ebb, pointer_type);
+    let buffer = func.dfg.append_ebb_param(ebb, pointer_type);
 
-        // execute
-        match callable_fn() {
-            true => Ok(()),
-            false => Err(format!("Failed: {}", context.func.name.to_string())),
+    let mut pos = FuncCursor::new(&mut func);
+    pos.insert_ebb(ebb);
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+
+    let args: Vec<Value> = signature
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, param)| {
+            let offset = (i * VALUE_SIZE) as i32;
+            load_value(&mut pos, param.value_type, flags, buffer, offset)
+        })
+        .collect();
+
+    let call = pos.ins().call_indirect(callee_sig, callee, &args);
+    let results = pos.func.dfg.inst_results(call).to_vec();
+    for (i, (result, ret)) in results.into_iter().zip(&signature.returns).enumerate() {
+        let offset = (i * VALUE_SIZE) as i32;
+        store_value(&mut pos, ret.value_type, result, flags, buffer, offset);
+    }
+    pos.ins().return_(&[]);
+    func
+}
+
+/// Load a value of type `ty` from `buffer + offset`.
+///
+/// Booleans can't be loaded directly, so they are loaded as integers of the same width. Vectors
+/// are assembled lane by lane and `i128` values from two halves, since not every backend can load
+/// them in one go.
+fn load_value(
+    pos: &mut FuncCursor,
+    ty: Type,
+    flags: MemFlags,
+    buffer: Value,
+    offset: i32,
+) -> Value {
+    if ty.is_vector() {
+        let lane_type = memory_type(ty.lane_type());
+        let lane_size = lane_type.bytes() as i32;
+        let first = pos.ins().load(lane_type, flags, buffer, offset);
+        let mut vector = pos.ins().splat(memory_type(ty), first);
+        for lane in 1..ty.lane_count() {
+            let lane_offset = offset + i32::from(lane) * lane_size;
+            let value = pos.ins().load(lane_type, flags, buffer, lane_offset);
+            vector = pos.ins().insertlane(vector, lane as u8, value);
         }
+        return if ty.lane_type().is_bool() {
+            pos.ins().raw_bitcast(ty, vector)
+        } else {
+            vector
+        };
+    }
+    if ty == types::I128 {
+        let low = pos.ins().load(types::I64, flags, buffer, offset);
+        let high = pos.ins().load(types::I64, flags, buffer, offset + 8);
+        return pos.ins().iconcat(low, high);
+    }
+    if ty == types::B1 {
+        let value = pos.ins().uload8(types::I32, flags, buffer, offset);
+        return pos.ins().icmp_imm(IntCC::NotEqual, value, 0);
+    }
+    if ty.is_bool() {
+        // The bits of wide booleans are all set or all clear, so they are sign extended.
+        let int_ty = call_type(ty);
+        return match ty.bytes() {
+            1 => pos.ins().sload8(int_ty, flags, buffer, offset),
+            2 => pos.ins().sload16(int_ty, flags, buffer, offset),
+            _ => pos.ins().load(int_ty, flags, buffer, offset),
+        };
+    }
+    pos.ins().load(ty, flags, buffer, offset)
+}
+
+/// Store `value` of type `ty` to `buffer + offset`, the inverse of `load_value`.
+fn store_value(
+    pos: &mut FuncCursor,
+    ty: Type,
+    value: Value,
+    flags: MemFlags,
+    buffer: Value,
+    offset: i32,
+) {
+    if ty.is_vector() {
+        let vector = if ty.lane_type().is_bool() {
+            pos.ins().raw_bitcast(memory_type(ty), value)
+        } else {
+            value
+        };
+        let lane_size = memory_type(ty.lane_type()).bytes() as i32;
+        for lane in 0..ty.lane_count() {
+            let lane_offset = offset + i32::from(lane) * lane_size;
+            let value = pos.ins().extractlane(vector, lane as u8);
+            pos.ins().store(flags, value, buffer, lane_offset);
+        }
+        return;
+    }
+    if ty == types::I128 {
+        let (low, high) = pos.ins().isplit(value);
+        pos.ins().store(flags, low, buffer, offset);
+        pos.ins().store(flags, high, buffer, offset + 8);
+        return;
+    }
+    if ty.is_bool() {
+        // Wide booleans are already integers here, see `call_type`.
+        let value = if ty == types::B1 {
+            pos.ins().bint(types::I32, value)
+        } else {
+            value
+        };
+        match memory_type(ty).bytes() {
+            1 => pos.ins().istore8(flags, value, buffer, offset),
+            2 => pos.ins().istore16(flags, value, buffer, offset),
+            _ => pos.ins().store(flags, value, buffer, offset),
+        };
+        return;
+    }
+    pos.ins().store(flags, value, buffer, offset);
+}
+
+/// Get the type a trampoline passes a value of type `ty` to the callee as.
+///
+/// Scalar booleans wider than `b1` become integers of at least 32 bits, which are held in the same
+/// registers.
+fn call_type(ty: Type) -> Type {
+    match ty {
+        types::B8 | types::B16 | types::B32 => types::I32,
+        types::B64 => types::I64,
+        _ => ty,
+    }
+}
+
+/// Get the type a value of type `ty` is loaded and stored as by a trampoline.
+fn memory_type(ty: Type) -> Type {
+    let lane = ty.lane_type();
+    if lane.is_bool() {
+        let int = Type::int(lane.bits().max(8)).unwrap();
+        int.by(ty.lane_count()).unwrap()
+    } else {
+        ty
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use cranelift_reader::{parse_test, ParseOptions};
+    use cranelift_reader::{parse_run_command, parse_test, ParseOptions};
 
     #[test]
     fn nop() {
@@ -101,16 +333,20 @@ mod test {
                 nop
                 v1 = bconst.b8 true
                 return v1
-            }",
+            }
+            ; run",
         );
 
         // extract function
         let test_file = parse_test(code.as_str(), ParseOptions::default()).unwrap();
         assert_eq!(1, test_file.functions.len());
-        let function = test_file.functions[0].0.clone();
+        let (function, details) = &test_file.functions[0];
+        let command = parse_run_command(details.comments[0].text, function)
+            .unwrap()
+            .unwrap();
 
         // execute function
-        let runner = FunctionRunner::with_default_host_isa(function);
-        runner.run().unwrap() // will panic if execution fails
+        let runner = FunctionRunner::with_default_host_isa(function.clone());
+        runner.run(&[command]).unwrap() // will panic if execution fails
     }
 }
@@ -23,7 +23,7 @@
     )
 )]
 
-pub use crate::function_runner::FunctionRunner;
+pub use crate::function_runner::{CompiledFunction, FunctionRunner};
 use crate::runner::TestRunner;
 use cranelift_codegen::timing;
 use cranelift_reader::TestCommand;
@@ -42,11 +42,15 @@ mod test_cat;
 mod test_compile;
 mod test_dce;
 mod test_domtree;
+mod test_inline;
+mod test_interpret;
 mod test_legalizer;
 mod test_licm;
+mod test_mem2reg;
 mod test_postopt;
 mod test_preopt;
 mod test_print_cfg;
+mod test_redundant_memory;
 mod test_regalloc;
 mod test_rodata;
 mod test_run;
@@ -54,6 +58,8 @@ mod test_safepoint;
 mod test_shrink;
 mod test_simple_gvn;
 mod test_simple_preopt;
+mod test_strength_reduction;
+mod test_unroll;
 mod test_verifier;
 
 /// The result of running the test in a file.
@@ -123,15 +129,21 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
         "rodata" => test_rodata::subtest(parsed),
         "dce" => test_dce::subtest(parsed),
         "domtree" => test_domtree::subtest(parsed),
+        "inline" => test_inline::subtest(parsed),
+        "interpret" => test_interpret::subtest(parsed),
         "legalizer" => test_legalizer::subtest(parsed),
         "licm" => test_licm::subtest(parsed),
+        "mem2reg" => test_mem2reg::subtest(parsed),
         "postopt" => test_postopt::subtest(parsed),
         "simple_preopt" => test_simple_preopt::subtest(parsed),
         "print-cfg" => test_print_cfg::subtest(parsed),
+        "redundant-memory" => test_redundant_memory::subtest(parsed),
         "regalloc" => test_regalloc::subtest(parsed),
         "run" => test_run::subtest(parsed),
         "shrink" => test_shrink::subtest(parsed),
         "simple-gvn" => test_simple_gvn::subtest(parsed),
+        "strength-reduction" => test_strength_reduction::subtest(parsed),
+        "unroll" => test_unroll::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
         "safepoint" => test_safepoint::subtest(parsed),
@@ -100,30 +100,29 @@ pub fn run(path: &Path, passes: Option<&[String]>, target: Option<&str>) -> Test
     tests.sort_by_key(|st| (st.is_mutating(), st.needs_verifier()));
 
     // Expand the tests into (test, flags, isa) tuples.
-    let mut tuples = test_tuples(&tests, &testfile.isa_spec, flags)?;
+    let tuples = test_tuples(&tests, &testfile.isa_spec, flags)?;
 
-    // Isolate the last test in the hope that this is the only mutating test.
-    // If so, we can completely avoid cloning functions.
-    let last_tuple = match tuples.pop() {
-        None => return Err("no test commands found".to_string()),
-        Some(t) => t,
-    };
+    if tuples.is_empty() {
+        return Err("no test commands found".to_string());
+    }
+
+    // Keep all of the functions around while testing each of them, so that tests can look at the
+    // other functions in the file. Mutating tests clone the function they work on.
+    let (functions, details): (Vec<_>, Vec<_>) = testfile.functions.into_iter().unzip();
 
-    for (func, details) in testfile.functions {
+    for (func, details) in functions.iter().zip(details) {
         let mut context = Context {
             preamble_comments: &testfile.preamble_comments,
             details,
+            functions: &functions,
             verified: false,
             flags,
             isa: None,
         };
 
         for tuple in &tuples {
-            run_one_test(*tuple, Cow::Borrowed(&func), &mut context)?;
+            run_one_test(*tuple, Cow::Borrowed(func), &mut context)?;
         }
-        // Run the last test with an owned function which means it won't need to clone it before
-        // mutating.
-        run_one_test(last_tuple, Cow::Owned(func), &mut context)?;
     }
 
     Ok(started.elapsed())
@@ -17,6 +17,9 @@ pub struct Context<'a> {
     /// Additional details about the function from the parser.
     pub details: Details<'a>,
 
+    /// All of the functions in the test file, in order.
+    pub functions: &'a [Function],
+
     /// Was the function verified before running this test?
     pub verified: bool,
 
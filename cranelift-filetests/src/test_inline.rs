@@ -0,0 +1,57 @@
+//! Test command for testing the inlining pass.
+//!
+//! The `inline` test command inlines calls between all the functions of the file, then sends the
+//! resulting version of each function to `filecheck`.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use cranelift_codegen::ir::Function;
+use cranelift_preopt::{inline_functions, CallGraph, InlineLimits};
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+use std::cell::RefCell;
+
+struct TestInline {
+    /// The functions of the file with calls inlined, or the error inlining them gave. A new test is
+    /// created for each file, so this is computed once, when checking its first function.
+    inlined: RefCell<Option<Result<CallGraph, String>>>,
+}
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "inline");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestInline {
+            inlined: RefCell::new(None),
+        }))
+    }
+}
+
+impl SubTest for TestInline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let mut cache = self.inlined.borrow_mut();
+        let graph = cache
+            .get_or_insert_with(|| {
+                let mut graph = CallGraph::new(context.functions.to_vec());
+                inline_functions(&mut graph, &InlineLimits::default(), context.flags_or_isa())
+                    .map_err(|e| e.to_string())?;
+                Ok(graph)
+            })
+            .as_ref()
+            .map_err(Clone::clone)?;
+
+        let inlined = graph
+            .get(&func.name)
+            .ok_or_else(|| format!("{} is missing from the call graph", func.name))?;
+        let text = inlined.display(context.isa).to_string();
+        run_filecheck(&text, context)
+    }
+}
@@ -0,0 +1,60 @@
+//! Test command for interpreting CLIF files and verifying their results
+//!
+//! The `interpret` test command executes the `run` comments of each function by interpreting it,
+//! without compiling it. Calls may refer to any other function in the file.
+
+use crate::subtest::{Context, SubTest, SubtestResult};
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::ir;
+use cranelift_interpreter::{Interpreter, Outcome};
+use cranelift_reader::{parse_run_command, TestCommand};
+use std::borrow::Cow;
+
+struct TestInterpret;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "interpret");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestInterpret))
+    }
+}
+
+impl SubTest for TestInterpret {
+    fn name(&self) -> &'static str {
+        "interpret"
+    }
+
+    fn is_mutating(&self) -> bool {
+        false
+    }
+
+    fn needs_isa(&self) -> bool {
+        false
+    }
+
+    fn run(&self, func: Cow<ir::Function>, context: &Context) -> SubtestResult<()> {
+        for comment in context.details.comments.iter() {
+            if let Some(command) =
+                parse_run_command(comment.text, &func).map_err(|e| e.to_string())?
+            {
+                command.run(|args| interpret(&func, context.functions, args))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Interpret a call to `func` with `args`, returning its results.
+fn interpret(
+    func: &ir::Function,
+    functions: &[ir::Function],
+    args: &[DataValue],
+) -> Result<Vec<DataValue>, String> {
+    let mut interpreter = Interpreter::new(functions.iter().collect());
+    match interpreter.call(func, args).map_err(|e| e.to_string())? {
+        Outcome::Return(values) => Ok(values),
+        Outcome::Trap(code) => Err(format!("{} trapped with {}", func.name, code)),
+    }
+}
@@ -0,0 +1,45 @@
+//! Test command for testing the stack slot promotion pass.
+//!
+//! The `mem2reg` test command runs each function through the pass which promotes stack slots to
+//! SSA values.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use crate::subtest::{run_filecheck, Context, SubTest, SubtestResult};
+use cranelift_codegen;
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::print_errors::pretty_error;
+use cranelift_preopt::promote_stack_slots;
+use cranelift_reader::TestCommand;
+use std::borrow::Cow;
+
+struct TestMem2Reg;
+
+pub fn subtest(parsed: &TestCommand) -> SubtestResult<Box<dyn SubTest>> {
+    assert_eq!(parsed.command, "mem2reg");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestMem2Reg))
+    }
+}
+
+impl SubTest for TestMem2Reg {
+    fn name(&self) -> &'static str {
+        "mem2reg"
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> SubtestResult<()> {
+        let mut comp_ctx = cranelift_codegen::Context::for_function(func.into_owned());
+
+        promote_stack_slots(&mut comp_ctx, context.flags_or_isa())
+            .map_err(|e| pretty_error(&comp_ctx.func, context.isa, Into::into(e)))?;
+
+        let text = comp_ctx.func.display(context.isa).to_string();
+        run_filecheck(&text, context)
+    }
+}
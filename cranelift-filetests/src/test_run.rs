@@ -1,12 +1,13 @@
 //! Test command for running CLIF files and verifying their results
 //!
-//! The `run` test command compiles each function on the host machine and executes it
+//! The `run` test command compiles each function with `run` comments on the host machine and
+//! executes the run commands against it.
 
 use crate::function_runner::FunctionRunner;
 use crate::subtest::{Context, SubTest, SubtestResult};
 use cranelift_codegen;
 use cranelift_codegen::ir;
-use cranelift_reader::TestCommand;
+use cranelift_reader::{parse_run_command, TestCommand};
 use std::borrow::Cow;
 
 struct TestRun;
@@ -34,13 +35,18 @@ impl SubTest for TestRun {
     }
 
     fn run(&self, func: Cow<ir::Function>, context: &Context) -> SubtestResult<()> {
+        let mut commands = Vec::new();
         for comment in context.details.comments.iter() {
-            if comment.text.contains("run") {
-                let runner =
-                    FunctionRunner::with_host_isa(func.clone().into_owned(), context.flags.clone());
-                runner.run()?
+            if let Some(command) =
+                parse_run_command(comment.text, &func).map_err(|e| e.to_string())?
+            {
+                commands.push(command);
             }
         }
-        Ok(())
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let runner = FunctionRunner::with_host_isa(func.into_owned(), context.flags.clone());
+        runner.run(&commands)
     }
 }
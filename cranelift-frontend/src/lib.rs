@@ -195,10 +195,12 @@ use hashmap_core::HashMap;
 use std::collections::HashMap;
 
 pub use crate::frontend::FunctionBuilder;
+pub use crate::mem2reg::promote_stack_slots;
 pub use crate::switch::Switch;
 pub use crate::variable::Variable;
 
 mod frontend;
+mod mem2reg;
 mod ssa;
 mod switch;
 mod variable;
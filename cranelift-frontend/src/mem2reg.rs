@@ -0,0 +1,241 @@
+//! Promotion of stack slots to SSA values.
+//!
+//! Frontends often lower local variables to `explicit_slot` stack slots which are only accessed
+//! with `stack_load` and `stack_store`. As long as the address of such a slot is never taken with
+//! `stack_addr`, nothing but these instructions can read or write it, so its contents can be
+//! tracked as SSA values instead.
+//!
+//! Each field of a promotable slot, identified by its offset and type, becomes a variable of the
+//! `SSABuilder`: stores define the variable and loads use it, which adds EBB parameters where
+//! different definitions merge. A slot is only promoted when its fields don't overlap, so that
+//! every access reads or writes exactly one variable.
+
+use crate::ssa::SSABuilder;
+use crate::HashMap;
+use crate::Variable;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::entity::{EntityRef, EntitySet, SecondaryMap};
+use cranelift_codegen::ir::instructions::BranchInfo;
+use cranelift_codegen::ir::{
+    Ebb, Function, InstructionData, Opcode, StackSlot, StackSlotKind, Type,
+};
+use std::vec::Vec;
+
+/// Promote the `explicit_slot` stack slots of `func` whose address never escapes to SSA values.
+///
+/// The promoted slots are left in the function, but they are no longer accessed. A load of a slot
+/// that isn't stored to on every path before it reads zero.
+pub fn promote_stack_slots(func: &mut Function) {
+    let variables = find_variables(func);
+    if variables.is_empty() {
+        return;
+    }
+
+    let mut ssa = SSABuilder::new();
+    // Every EBB gets a header block, even those that are not in the layout, since sealing visits
+    // all of them.
+    for index in 0..func.dfg.num_ebbs() {
+        ssa.declare_ebb_header_block(Ebb::new(index));
+    }
+
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    let mut pos = FuncCursor::new(func);
+    for ebb in ebbs {
+        let mut block = ssa.header_block(ebb);
+        pos.goto_top(ebb);
+        while let Some(inst) = pos.next_inst() {
+            match pos.func.dfg[inst] {
+                InstructionData::StackLoad {
+                    opcode: Opcode::StackLoad,
+                    stack_slot,
+                    offset,
+                } => {
+                    if let Some(&var) = variables.get(&(stack_slot, offset.into())) {
+                        let result = pos.func.dfg.first_result(inst);
+                        let ty = pos.func.dfg.value_type(result);
+                        let (value, _) = ssa.use_var(pos.func, var, ty, block);
+                        pos.func.dfg.clear_results(inst);
+                        pos.func.dfg.change_to_alias(result, value);
+                        pos.remove_inst_and_step_back();
+                    }
+                }
+                InstructionData::StackStore {
+                    arg,
+                    stack_slot,
+                    offset,
+                    ..
+                } => {
+                    if let Some(&var) = variables.get(&(stack_slot, offset.into())) {
+                        let value = pos.func.dfg.resolve_aliases(arg);
+                        ssa.def_var(var, value, block);
+                        pos.remove_inst_and_step_back();
+                    }
+                }
+                _ => match pos.func.dfg.analyze_branch(inst) {
+                    BranchInfo::NotABranch => {}
+                    BranchInfo::SingleDest(dest, _) => {
+                        ssa.declare_ebb_predecessor(dest, block, inst);
+                        block = ssa.declare_ebb_body_block(block);
+                    }
+                    BranchInfo::Table(table, default) => {
+                        // The same EBB may appear several times in a jump table, but it only has
+                        // one predecessor per branch.
+                        let mut unique = EntitySet::<Ebb>::new();
+                        for &dest in pos.func.jump_tables[table]
+                            .iter()
+                            .chain(default.iter())
+                            .filter(|&&dest| unique.insert(dest))
+                        {
+                            ssa.declare_ebb_predecessor(dest, block, inst);
+                        }
+                        block = ssa.declare_ebb_body_block(block);
+                    }
+                },
+            }
+        }
+    }
+
+    ssa.seal_all_ebb_header_blocks(func);
+}
+
+/// Find the fields of the promotable stack slots, and assign a variable to each of them.
+///
+/// The fields are keyed by their stack slot and offset, since the fields of a promotable slot
+/// never overlap.
+fn find_variables(func: &Function) -> HashMap<(StackSlot, i32), Variable> {
+    let mut fields = SecondaryMap::<StackSlot, Vec<(i32, Type)>>::new();
+    let mut escaping = EntitySet::<StackSlot>::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                InstructionData::StackLoad {
+                    opcode: Opcode::StackLoad,
+                    stack_slot,
+                    offset,
+                } => {
+                    let ty = func.dfg.ctrl_typevar(inst);
+                    fields[stack_slot].push((offset.into(), ty));
+                }
+                InstructionData::StackStore {
+                    arg,
+                    stack_slot,
+                    offset,
+                    ..
+                } => {
+                    let ty = func.dfg.value_type(arg);
+                    fields[stack_slot].push((offset.into(), ty));
+                }
+                InstructionData::StackLoad { stack_slot, .. }
+                | InstructionData::RegSpill {
+                    dst: stack_slot, ..
+                }
+                | InstructionData::RegFill {
+                    src: stack_slot, ..
+                } => {
+                    escaping.insert(stack_slot);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut variables = HashMap::new();
+    for (slot, data) in func.stack_slots.iter() {
+        if data.kind != StackSlotKind::ExplicitSlot || escaping.contains(slot) {
+            continue;
+        }
+        let slot_fields = &mut fields[slot];
+        slot_fields.sort_unstable_by_key(|&(offset, ty)| (offset, ty.bits()));
+        slot_fields.dedup();
+        if !are_disjoint(slot_fields, data.size) {
+            continue;
+        }
+        for &(offset, _) in slot_fields.iter() {
+            let var = Variable::new(variables.len());
+            variables.insert((slot, offset), var);
+        }
+    }
+    variables
+}
+
+/// Check that the sorted `fields` of a slot of `size` bytes are within bounds and don't overlap.
+fn are_disjoint(fields: &[(i32, Type)], size: u32) -> bool {
+    let mut end = 0;
+    for &(offset, ty) in fields {
+        if i64::from(offset) < end {
+            return false;
+        }
+        end = i64::from(offset) + i64::from(ty.bytes());
+    }
+    end <= i64::from(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::promote_stack_slots;
+    use cranelift_codegen::cursor::{Cursor, FuncCursor};
+    use cranelift_codegen::ir::types::*;
+    use cranelift_codegen::ir::{Function, InstBuilder, StackSlotData, StackSlotKind, ValueDef};
+
+    #[test]
+    fn promote_loop_counter() {
+        let mut func = Function::new();
+        let ss0 = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 4));
+        let ss1 = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let mut cur = FuncCursor::new(&mut func);
+
+        // ebb0:
+        //    stack_store 10, ss0
+        //    jump ebb1
+        cur.insert_ebb(ebb0);
+        let ten = cur.ins().iconst(I32, 10);
+        cur.ins().stack_store(ten, ss0, 0);
+        cur.ins().jump(ebb1, &[]);
+
+        // ebb1:
+        //    stack_store (stack_load ss0) - 1, ss0
+        //    brnz (stack_load ss0), ebb1
+        //    stack_addr ss1
+        //    stack_store 10, ss1+4
+        //    jump ebb2
+        cur.insert_ebb(ebb1);
+        let counter = cur.ins().stack_load(I32, ss0, 0);
+        let next = cur.ins().iadd_imm(counter, -1);
+        cur.ins().stack_store(next, ss0, 0);
+        let reload = cur.ins().stack_load(I32, ss0, 0);
+        cur.ins().brnz(reload, ebb1, &[]);
+        cur.ins().stack_addr(I64, ss1, 0);
+        cur.ins().stack_store(ten, ss1, 4);
+        cur.ins().jump(ebb2, &[]);
+
+        // ebb2:
+        //    return stack_load ss0
+        cur.insert_ebb(ebb2);
+        let result = cur.ins().stack_load(I32, ss0, 0);
+        cur.ins().return_(&[result]);
+
+        promote_stack_slots(&mut func);
+
+        // The loop counter becomes a parameter of `ebb1`, and later loads read the decremented value.
+        let param = func.dfg.resolve_aliases(counter);
+        assert_eq!(func.dfg.value_def(param), ValueDef::Param(ebb1, 0));
+        assert_eq!(func.dfg.resolve_aliases(reload), next);
+        assert_eq!(func.dfg.resolve_aliases(result), next);
+
+        // Only the escaping slot is still accessed.
+        let mut accesses = 0;
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if func.dfg[inst].opcode().can_load() || func.dfg[inst].opcode().can_store() {
+                    accesses += 1;
+                }
+            }
+        }
+        assert_eq!(accesses, 1);
+        assert_eq!(func.dfg.ebb_params(ebb0).len(), 0);
+        assert_eq!(func.dfg.ebb_params(ebb2).len(), 0);
+    }
+}
@@ -0,0 +1,46 @@
+//! The set of functions an interpreter can call.
+
+use cranelift_codegen::ir::{ExternalName, Function};
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+/// A collection of functions, indexed by name.
+///
+/// Calls are resolved by the printed form of the callee's `ExternalName`, so `call fn0(...)` with
+/// `fn0 = %foo(...)` invokes the function that was added as `%foo`.
+#[derive(Default)]
+pub struct FunctionStore<'a> {
+    functions: HashMap<String, &'a Function>,
+}
+
+impl<'a> FunctionStore<'a> {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `function` to the store under its own name, replacing any function of the same name.
+    pub fn add(&mut self, function: &'a Function) {
+        self.functions.insert(function.name.to_string(), function);
+    }
+
+    /// Look up a function by the printed form of its name, e.g. `%foo` or `u0:1`.
+    pub fn get_by_name(&self, name: &str) -> Option<&'a Function> {
+        self.functions.get(name).cloned()
+    }
+
+    /// Look up the function called through `name`.
+    pub fn get(&self, name: &ExternalName) -> Option<&'a Function> {
+        self.get_by_name(&name.to_string())
+    }
+}
+
+impl<'a> FromIterator<&'a Function> for FunctionStore<'a> {
+    fn from_iter<I: IntoIterator<Item = &'a Function>>(functions: I) -> Self {
+        let mut store = Self::new();
+        for function in functions {
+            store.add(function);
+        }
+        store
+    }
+}
@@ -0,0 +1,60 @@
+//! The state of a single function invocation.
+
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::entity::SecondaryMap;
+use cranelift_codegen::ir::{Function, StackSlot, Value};
+
+/// The values and stack slots of a function while it is being interpreted.
+pub struct Frame<'a> {
+    /// The function being interpreted.
+    pub function: &'a Function,
+    values: SecondaryMap<Value, Option<DataValue>>,
+    stack_slots: SecondaryMap<StackSlot, u64>,
+}
+
+impl<'a> Frame<'a> {
+    /// Create a frame for `function` with no values defined yet.
+    pub fn new(function: &'a Function) -> Self {
+        Self {
+            function,
+            values: SecondaryMap::new(),
+            stack_slots: SecondaryMap::new(),
+        }
+    }
+
+    /// Get the current value of `value`, looking through aliases.
+    ///
+    /// Panics if `value` hasn't been defined yet, which can't happen in a verified function.
+    pub fn get(&self, value: Value) -> DataValue {
+        let value = self.function.dfg.resolve_aliases(value);
+        self.values[value].unwrap_or_else(|| panic!("{} used before it was defined", value))
+    }
+
+    /// Get the current values of `values`.
+    pub fn get_all(&self, values: &[Value]) -> Vec<DataValue> {
+        values.iter().map(|&v| self.get(v)).collect()
+    }
+
+    /// Define `value`.
+    pub fn set(&mut self, value: Value, data: DataValue) {
+        self.values[value] = Some(data);
+    }
+
+    /// Define each of `values` with the corresponding entry of `data`.
+    pub fn set_all(&mut self, values: &[Value], data: Vec<DataValue>) {
+        debug_assert_eq!(values.len(), data.len());
+        for (&value, data) in values.iter().zip(data) {
+            self.set(value, data);
+        }
+    }
+
+    /// Get the address of `slot` in memory.
+    pub fn stack_slot(&self, slot: StackSlot) -> u64 {
+        self.stack_slots[slot]
+    }
+
+    /// Record the address `slot` was allocated at.
+    pub fn set_stack_slot(&mut self, slot: StackSlot, addr: u64) {
+        self.stack_slots[slot] = addr;
+    }
+}
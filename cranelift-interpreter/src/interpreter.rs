@@ -0,0 +1,974 @@
+//! Execution of Cranelift IR functions.
+
+use crate::environment::FunctionStore;
+use crate::frame::Frame;
+use crate::memory::Memory;
+use crate::value::*;
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::instructions::BranchInfo;
+use cranelift_codegen::ir::{
+    types, ArgumentPurpose, Ebb, Function, GlobalValue, GlobalValueData, Inst, InstructionData,
+    Opcode, TrapCode, Type,
+};
+use std::fmt::{self, Display, Formatter};
+
+/// How a function call finished.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// The function returned these values.
+    Return(Vec<DataValue>),
+    /// The function trapped with this code.
+    Trap(TrapCode),
+}
+
+/// A reason a function couldn't be interpreted.
+#[derive(Debug, PartialEq)]
+pub enum InterpreterError {
+    /// The named function isn't in the interpreter's `FunctionStore`.
+    UnknownFunction(String),
+    /// A function was called with arguments that don't match its signature.
+    InvalidArguments(String),
+    /// The function uses an instruction or entity the interpreter doesn't implement.
+    Unsupported(String),
+}
+
+impl Display for InterpreterError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            InterpreterError::UnknownFunction(ref name) => write!(f, "unknown function {}", name),
+            InterpreterError::InvalidArguments(ref msg) => write!(f, "invalid arguments: {}", msg),
+            InterpreterError::Unsupported(ref what) => write!(f, "unsupported: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for InterpreterError {}
+
+/// What to do after interpreting an instruction.
+enum ControlFlow {
+    /// Continue with the next instruction in the EBB.
+    Continue,
+    /// Continue at the start of an EBB with these parameters.
+    Jump(Ebb, Vec<DataValue>),
+    /// Return these values from the current function.
+    Return(Vec<DataValue>),
+    /// Trap out of the current function and all of its callers.
+    Trap(TrapCode),
+}
+
+/// Interprets the functions of a `FunctionStore`.
+///
+/// All calls share a single address space: stack slots are allocated when their function is
+/// called, and heaps are backed by `min_size` zeroed bytes the first time any function accesses
+/// them. Heaps are identified by their number, so `heap0` refers to the same memory in every
+/// function, much like the linear memory of a WebAssembly module.
+pub struct Interpreter<'a> {
+    functions: FunctionStore<'a>,
+    memory: Memory,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create an interpreter able to call the functions in `functions`.
+    pub fn new(functions: FunctionStore<'a>) -> Self {
+        Self {
+            functions,
+            memory: Memory::default(),
+        }
+    }
+
+    /// Call the function named `name`, e.g. `%foo`, with `args`.
+    pub fn call_by_name(
+        &mut self,
+        name: &str,
+        args: &[DataValue],
+    ) -> Result<Outcome, InterpreterError> {
+        let function = self
+            .functions
+            .get_by_name(name)
+            .ok_or_else(|| InterpreterError::UnknownFunction(name.to_string()))?;
+        self.call(function, args)
+    }
+
+    /// Call `function` with `args`.
+    ///
+    /// The function is expected to have passed the verifier.
+    pub fn call(
+        &mut self,
+        function: &'a Function,
+        args: &[DataValue],
+    ) -> Result<Outcome, InterpreterError> {
+        let params = &function.signature.params;
+        if args.len() != params.len()
+            || args
+                .iter()
+                .zip(params)
+                .any(|(arg, param)| !arg.is_type(param.value_type))
+        {
+            let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+            return Err(InterpreterError::InvalidArguments(format!(
+                "{}{} called with ({})",
+                function.name,
+                function.signature,
+                args.join(", ")
+            )));
+        }
+
+        let mark = self.memory.stack_mark();
+        let outcome = self.execute(function, args);
+        self.memory.release_stack(mark);
+        outcome
+    }
+
+    /// Run the body of `function` in a fresh frame.
+    fn execute(
+        &mut self,
+        function: &'a Function,
+        args: &[DataValue],
+    ) -> Result<Outcome, InterpreterError> {
+        let mut frame = Frame::new(function);
+        for (slot, data) in function.stack_slots.iter() {
+            let addr = self.memory.allocate_stack(data.size, 16);
+            frame.set_stack_slot(slot, addr);
+        }
+
+        let mut ebb = function.layout.entry_block().ok_or_else(|| {
+            InterpreterError::Unsupported(format!("{} has no body", function.name))
+        })?;
+        let mut params = args.to_vec();
+        loop {
+            frame.set_all(function.dfg.ebb_params(ebb), params);
+            let mut next = None;
+            for inst in function.layout.ebb_insts(ebb) {
+                match self.step(&mut frame, inst)? {
+                    ControlFlow::Continue => {}
+                    ControlFlow::Jump(dest, args) => {
+                        next = Some((dest, args));
+                        break;
+                    }
+                    ControlFlow::Return(values) => return Ok(Outcome::Return(values)),
+                    ControlFlow::Trap(code) => return Ok(Outcome::Trap(code)),
+                }
+            }
+            let (dest, args) = next.unwrap_or_else(|| panic!("{} has no terminator", ebb));
+            ebb = dest;
+            params = args;
+        }
+    }
+
+    /// Interpret a single instruction.
+    fn step(&mut self, frame: &mut Frame<'a>, inst: Inst) -> Result<ControlFlow, InterpreterError> {
+        let function = frame.function;
+        let dfg = &function.dfg;
+        let data = &dfg[inst];
+        let opcode = data.opcode();
+        let args = frame.get_all(dfg.inst_args(inst));
+        let results = dfg.inst_results(inst);
+        let ctrl_ty = dfg.ctrl_typevar(inst);
+        let ty = results.first().map_or(ctrl_ty, |&r| dfg.value_type(r));
+        let unsupported =
+            || InterpreterError::Unsupported(dfg.display_inst(inst, None).to_string());
+
+        if (ctrl_ty.is_vector() && !supports_vectors(opcode)) || ctrl_ty.is_flags() {
+            return Err(unsupported());
+        }
+
+        // The right-hand operand of a binary instruction, which may be an immediate.
+        let rhs = || match *data {
+            InstructionData::BinaryImm { imm, .. } | InstructionData::IntCompareImm { imm, .. } => {
+                let imm: i64 = imm.into();
+                make_int(imm.into(), ctrl_ty)
+            }
+            _ => args[1],
+        };
+        let trap = |code| Ok(ControlFlow::Trap(code));
+
+        let value = match opcode {
+            Opcode::Nop | Opcode::Fence | Opcode::Debugtrap => return Ok(ControlFlow::Continue),
+
+            Opcode::Jump | Opcode::Fallthrough => {
+                if let BranchInfo::SingleDest(dest, vargs) = dfg.analyze_branch(inst) {
+                    return Ok(ControlFlow::Jump(dest, frame.get_all(vargs)));
+                }
+                unreachable!()
+            }
+            Opcode::Brz | Opcode::Brnz | Opcode::BrIcmp => {
+                let taken = match *data {
+                    InstructionData::BranchIcmp { cond, .. } => icmp(cond, args[0], args[1]),
+                    _ => truthy(args[0]) == (opcode == Opcode::Brnz),
+                };
+                if let BranchInfo::SingleDest(dest, vargs) = dfg.analyze_branch(inst) {
+                    if taken {
+                        return Ok(ControlFlow::Jump(dest, frame.get_all(vargs)));
+                    }
+                }
+                return Ok(ControlFlow::Continue);
+            }
+            Opcode::BrTable => {
+                if let InstructionData::BranchTable {
+                    destination, table, ..
+                } = *data
+                {
+                    let entries = function.jump_tables[table].as_slice();
+                    let index = uint(args[0]);
+                    let dest = if index < entries.len() as u128 {
+                        entries[index as usize]
+                    } else {
+                        destination
+                    };
+                    return Ok(ControlFlow::Jump(dest, Vec::new()));
+                }
+                unreachable!()
+            }
+            Opcode::Return | Opcode::FallthroughReturn => return Ok(ControlFlow::Return(args)),
+            Opcode::Call => {
+                if let InstructionData::Call { func_ref, .. } = *data {
+                    let name = &dfg.ext_funcs[func_ref].name;
+                    let callee = self
+                        .functions
+                        .get(name)
+                        .ok_or_else(|| InterpreterError::UnknownFunction(name.to_string()))?;
+                    return Ok(match self.call(callee, &args)? {
+                        Outcome::Return(values) => {
+                            frame.set_all(results, values);
+                            ControlFlow::Continue
+                        }
+                        Outcome::Trap(code) => ControlFlow::Trap(code),
+                    });
+                }
+                unreachable!()
+            }
+
+            Opcode::Trap | Opcode::ResumableTrap => return trap(trap_code(data)),
+            Opcode::Trapz | Opcode::Trapnz => {
+                if truthy(args[0]) == (opcode == Opcode::Trapnz) {
+                    return trap(trap_code(data));
+                }
+                return Ok(ControlFlow::Continue);
+            }
+
+            Opcode::Iconst => make_int(imm(data).into(), ty),
+            Opcode::Bconst => match *data {
+                InstructionData::UnaryBool { imm, .. } => DataValue::B(imm),
+                _ => unreachable!(),
+            },
+            Opcode::F32const => match *data {
+                InstructionData::UnaryIeee32 { imm, .. } => DataValue::F32(imm),
+                _ => unreachable!(),
+            },
+            Opcode::F64const => match *data {
+                InstructionData::UnaryIeee64 { imm, .. } => DataValue::F64(imm),
+                _ => unreachable!(),
+            },
+            Opcode::Vconst => match *data {
+                InstructionData::UnaryImm128 { imm, .. } => {
+                    let mut bytes = [0; 16];
+                    let constant = dfg.constants.get(imm);
+                    bytes[..constant.len()].copy_from_slice(constant);
+                    DataValue::V128(bytes)
+                }
+                _ => unreachable!(),
+            },
+            Opcode::Copy | Opcode::Spill | Opcode::Fill => args[0],
+            Opcode::Select => {
+                if truthy(args[0]) {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
+
+            // Integer arithmetic.
+            Opcode::Iadd | Opcode::IaddImm => make_int(int(args[0]).wrapping_add(int(rhs())), ty),
+            Opcode::Isub => make_int(int(args[0]).wrapping_sub(int(args[1])), ty),
+            Opcode::IrsubImm => make_int(int(rhs()).wrapping_sub(int(args[0])), ty),
+            Opcode::Imul | Opcode::ImulImm => make_int(int(args[0]).wrapping_mul(int(rhs())), ty),
+            Opcode::Umulhi => {
+                let (x, y) = (uint(args[0]), uint(args[1]));
+                if ty.bits() == 128 {
+                    make_int(mul_high(x, y) as i128, ty)
+                } else {
+                    make_int((x * y >> ty.bits()) as i128, ty)
+                }
+            }
+            Opcode::Smulhi => {
+                let (x, y) = (int(args[0]), int(args[1]));
+                if ty.bits() == 128 {
+                    let high = mul_high(x as u128, y as u128)
+                        .wrapping_sub(if x < 0 { y as u128 } else { 0 })
+                        .wrapping_sub(if y < 0 { x as u128 } else { 0 });
+                    make_int(high as i128, ty)
+                } else {
+                    make_int(x * y >> ty.bits(), ty)
+                }
+            }
+            Opcode::Udiv | Opcode::UdivImm | Opcode::Urem | Opcode::UremImm => {
+                let (x, y) = (uint(args[0]), uint(rhs()));
+                if y == 0 {
+                    return trap(TrapCode::IntegerDivisionByZero);
+                }
+                let div = opcode == Opcode::Udiv || opcode == Opcode::UdivImm;
+                make_int(if div { x / y } else { x % y } as i128, ty)
+            }
+            Opcode::Sdiv | Opcode::SdivImm | Opcode::Srem | Opcode::SremImm => {
+                let (x, y) = (int(args[0]), int(rhs()));
+                if y == 0 {
+                    return trap(TrapCode::IntegerDivisionByZero);
+                }
+                if opcode == Opcode::Sdiv || opcode == Opcode::SdivImm {
+                    if x == int_min(ty) && y == -1 {
+                        return trap(TrapCode::IntegerOverflow);
+                    }
+                    make_int(x / y, ty)
+                } else {
+                    make_int(x.wrapping_rem(y), ty)
+                }
+            }
+            Opcode::IaddCin | Opcode::IaddCout | Opcode::IaddCarry => {
+                let carry_in = opcode != Opcode::IaddCout && truthy(args[2]);
+                let (x, y) = (uint(args[0]), uint(args[1]));
+                let (sum, overflow) = x.overflowing_add(y);
+                let (sum, overflow_in) = sum.overflowing_add(carry_in as u128);
+                if opcode == Opcode::IaddCin {
+                    make_int(sum as i128, ty)
+                } else {
+                    let carry = overflow || overflow_in || sum & !mask(ty.bits()) != 0;
+                    frame.set_all(
+                        results,
+                        vec![make_int(sum as i128, ty), DataValue::B(carry)],
+                    );
+                    return Ok(ControlFlow::Continue);
+                }
+            }
+            Opcode::IsubBin | Opcode::IsubBout | Opcode::IsubBorrow => {
+                let borrow_in = opcode != Opcode::IsubBout && truthy(args[2]);
+                let (x, y) = (uint(args[0]), uint(args[1]));
+                let difference = x.wrapping_sub(y).wrapping_sub(borrow_in as u128);
+                if opcode == Opcode::IsubBin {
+                    make_int(difference as i128, ty)
+                } else {
+                    let borrow = x < y || (x == y && borrow_in);
+                    frame.set_all(
+                        results,
+                        vec![make_int(difference as i128, ty), DataValue::B(borrow)],
+                    );
+                    return Ok(ControlFlow::Continue);
+                }
+            }
+
+            // Bitwise operations, which also apply to booleans, floats and vectors.
+            Opcode::Band
+            | Opcode::BandImm
+            | Opcode::Bor
+            | Opcode::BorImm
+            | Opcode::Bxor
+            | Opcode::BxorImm
+            | Opcode::BandNot
+            | Opcode::BorNot
+            | Opcode::BxorNot => {
+                let y = rhs();
+                let op = |x: u128, y: u128| match opcode {
+                    Opcode::Band | Opcode::BandImm => x & y,
+                    Opcode::Bor | Opcode::BorImm => x | y,
+                    Opcode::Bxor | Opcode::BxorImm => x ^ y,
+                    Opcode::BandNot => x & !y,
+                    Opcode::BorNot => x | !y,
+                    _ => x ^ !y,
+                };
+                match (args[0], y) {
+                    (DataValue::B(x), DataValue::B(y)) => {
+                        DataValue::B(op(x as u128, y as u128) & 1 != 0)
+                    }
+                    (x, y) => from_bits(op(to_bits(x), to_bits(y)), ty),
+                }
+            }
+            Opcode::Bnot => match args[0] {
+                DataValue::B(x) => DataValue::B(!x),
+                x => from_bits(!to_bits(x), ty),
+            },
+
+            // Shifts and rotates take the shift amount modulo the type width.
+            Opcode::Ishl
+            | Opcode::IshlImm
+            | Opcode::Ushr
+            | Opcode::UshrImm
+            | Opcode::Sshr
+            | Opcode::SshrImm
+            | Opcode::Rotl
+            | Opcode::RotlImm
+            | Opcode::Rotr
+            | Opcode::RotrImm => {
+                let bits = u32::from(ty.bits());
+                let amount = (uint(rhs()) % u128::from(bits)) as u32;
+                let x = uint(args[0]);
+                let result = match opcode {
+                    Opcode::Ishl | Opcode::IshlImm => x << amount,
+                    Opcode::Ushr | Opcode::UshrImm => x >> amount,
+                    Opcode::Sshr | Opcode::SshrImm => (int(args[0]) >> amount) as u128,
+                    _ if amount == 0 => x,
+                    Opcode::Rotl | Opcode::RotlImm => x << amount | x >> (bits - amount),
+                    _ => x >> amount | x << (bits - amount),
+                };
+                make_int(result as i128, ty)
+            }
+
+            Opcode::Bitrev => make_int(
+                (uint(args[0]).reverse_bits() >> (128 - ty.bits())) as i128,
+                ty,
+            ),
+            Opcode::Clz => make_int(leading_zeros(uint(args[0]), ty), ty),
+            Opcode::Cls => {
+                let x = int(args[0]);
+                let magnitude = if x < 0 { !x } else { x };
+                make_int(leading_zeros(magnitude as u128, ty) - 1, ty)
+            }
+            Opcode::Ctz => {
+                let x = uint(args[0]);
+                let zeros = if x == 0 {
+                    u32::from(ty.bits())
+                } else {
+                    x.trailing_zeros()
+                };
+                make_int(zeros.into(), ty)
+            }
+            Opcode::Popcnt => make_int(uint(args[0]).count_ones().into(), ty),
+
+            Opcode::Icmp | Opcode::IcmpImm => DataValue::B(icmp(int_cond(data), args[0], rhs())),
+
+            // Conversions.
+            Opcode::Bint => make_int(truthy(args[0]) as i128, ty),
+            Opcode::Bmask => make_int(-(truthy(args[0]) as i128), ty),
+            Opcode::Breduce | Opcode::Bextend => DataValue::B(truthy(args[0])),
+            Opcode::Ireduce | Opcode::Sextend => make_int(int(args[0]), ty),
+            Opcode::Uextend => make_int(uint(args[0]) as i128, ty),
+            Opcode::Isplit => {
+                let x = int(args[0]);
+                frame.set_all(results, vec![make_int(x, ty), make_int(x >> ty.bits(), ty)]);
+                return Ok(ControlFlow::Continue);
+            }
+            Opcode::Iconcat => {
+                let half = u32::from(ctrl_ty.bits());
+                make_int((uint(args[0]) | uint(args[1]) << half) as i128, ty)
+            }
+            Opcode::Bitcast | Opcode::RawBitcast => from_bits(to_bits(args[0]), ty),
+
+            // Vector lanes.
+            Opcode::Splat | Opcode::ScalarToVector => {
+                let lane = args[0].to_le_bytes(ty.lane_type());
+                let lanes = if opcode == Opcode::Splat {
+                    ty.lane_count() as usize
+                } else {
+                    1
+                };
+                let mut bytes = [0; 16];
+                for chunk in bytes.chunks_mut(lane.len()).take(lanes) {
+                    chunk.copy_from_slice(&lane);
+                }
+                DataValue::V128(bytes)
+            }
+            Opcode::Extractlane => match (data, args[0]) {
+                (&InstructionData::ExtractLane { lane, .. }, DataValue::V128(bytes)) => {
+                    let size = ty.bytes() as usize;
+                    let start = usize::from(lane) * size;
+                    DataValue::from_le_bytes(&bytes[start..start + size], ty)
+                }
+                _ => unreachable!(),
+            },
+            Opcode::Insertlane => match (data, args[0]) {
+                (&InstructionData::InsertLane { lane, .. }, DataValue::V128(mut bytes)) => {
+                    let value = args[1].to_le_bytes(ty.lane_type());
+                    let start = usize::from(lane) * value.len();
+                    bytes[start..start + value.len()].copy_from_slice(&value);
+                    DataValue::V128(bytes)
+                }
+                _ => unreachable!(),
+            },
+
+            // Floating point arithmetic.
+            Opcode::Fadd => make_float(float(args[0]) + float(args[1]), ty),
+            Opcode::Fsub => make_float(float(args[0]) - float(args[1]), ty),
+            Opcode::Fmul => make_float(float(args[0]) * float(args[1]), ty),
+            Opcode::Fdiv => make_float(float(args[0]) / float(args[1]), ty),
+            Opcode::Sqrt => make_float(float(args[0]).sqrt(), ty),
+            Opcode::Fma => {
+                let (x, y, z) = (float(args[0]), float(args[1]), float(args[2]));
+                if ty == types::F32 {
+                    make_float(f64::from((x as f32).mul_add(y as f32, z as f32)), ty)
+                } else {
+                    make_float(x.mul_add(y, z), ty)
+                }
+            }
+            Opcode::Fmin => make_float(fmin(float(args[0]), float(args[1])), ty),
+            Opcode::Fmax => make_float(fmax(float(args[0]), float(args[1])), ty),
+            Opcode::Ceil => make_float(float(args[0]).ceil(), ty),
+            Opcode::Floor => make_float(float(args[0]).floor(), ty),
+            Opcode::Trunc => make_float(float(args[0]).trunc(), ty),
+            Opcode::Nearest => make_float(nearest(float(args[0])), ty),
+            Opcode::Fneg | Opcode::Fabs | Opcode::Fcopysign => {
+                let sign = 1 << (ty.bits() - 1);
+                let x = to_bits(args[0]);
+                let bits = match opcode {
+                    Opcode::Fneg => x ^ sign,
+                    Opcode::Fabs => x & !sign,
+                    _ => x & !sign | to_bits(args[1]) & sign,
+                };
+                from_bits(bits, ty)
+            }
+            Opcode::Fcmp => DataValue::B(fcmp(float_cond(data), float(args[0]), float(args[1]))),
+            Opcode::Fpromote | Opcode::Fdemote => make_float(float(args[0]), ty),
+            Opcode::FcvtFromSint | Opcode::FcvtFromUint => {
+                // Round directly to the result type; going through `f64` could round twice.
+                let x = args[0];
+                let (narrow, wide) = if opcode == Opcode::FcvtFromSint {
+                    (int(x) as f32, int(x) as f64)
+                } else {
+                    (uint(x) as f32, uint(x) as f64)
+                };
+                if ty == types::F32 {
+                    make_float(narrow.into(), ty)
+                } else {
+                    make_float(wide, ty)
+                }
+            }
+            Opcode::FcvtToSint | Opcode::FcvtToUint => {
+                let x = float(args[0]);
+                if x.is_nan() {
+                    return trap(TrapCode::BadConversionToInteger);
+                }
+                let (min, max) = int_range(ty, opcode == Opcode::FcvtToSint);
+                let x = x.trunc();
+                if x < min || x >= max {
+                    return trap(TrapCode::IntegerOverflow);
+                }
+                make_int(float_to_int(x), ty)
+            }
+            Opcode::FcvtToSintSat | Opcode::FcvtToUintSat => {
+                let x = float(args[0]);
+                let signed = opcode == Opcode::FcvtToSintSat;
+                let (min, max) = int_range(ty, signed);
+                let x = x.trunc();
+                if x.is_nan() {
+                    make_int(0, ty)
+                } else if x < min {
+                    make_int(if signed { int_min(ty) } else { 0 }, ty)
+                } else if x >= max {
+                    let max = if signed { !int_min(ty) } else { -1 };
+                    make_int(max, ty)
+                } else {
+                    make_int(float_to_int(x), ty)
+                }
+            }
+
+            // Memory.
+            Opcode::Load
+            | Opcode::LoadComplex
+            | Opcode::Uload8
+            | Opcode::Uload8Complex
+            | Opcode::Sload8
+            | Opcode::Sload8Complex
+            | Opcode::Uload16
+            | Opcode::Uload16Complex
+            | Opcode::Sload16
+            | Opcode::Sload16Complex
+            | Opcode::Uload32
+            | Opcode::Uload32Complex
+            | Opcode::Sload32
+            | Opcode::Sload32Complex
+            | Opcode::StackLoad => {
+                let addr = match *data {
+                    InstructionData::StackLoad {
+                        stack_slot, offset, ..
+                    } => address(frame.stack_slot(stack_slot), offset.into()),
+                    _ => address(args.iter().map(|&a| uint(a) as u64).sum(), offset(data)),
+                };
+                let mem_ty = match opcode {
+                    Opcode::Uload8 | Opcode::Uload8Complex => types::I8,
+                    Opcode::Sload8 | Opcode::Sload8Complex => types::I8,
+                    Opcode::Uload16 | Opcode::Uload16Complex => types::I16,
+                    Opcode::Sload16 | Opcode::Sload16Complex => types::I16,
+                    Opcode::Uload32 | Opcode::Uload32Complex => types::I32,
+                    Opcode::Sload32 | Opcode::Sload32Complex => types::I32,
+                    _ => ty,
+                };
+                let value = match self.memory.read(addr, mem_ty.bytes() as usize) {
+                    Some(bytes) => DataValue::from_le_bytes(bytes, mem_ty),
+                    None => return trap(TrapCode::HeapOutOfBounds),
+                };
+                match opcode {
+                    Opcode::Uload8
+                    | Opcode::Uload8Complex
+                    | Opcode::Uload16
+                    | Opcode::Uload16Complex
+                    | Opcode::Uload32
+                    | Opcode::Uload32Complex => make_int(uint(value) as i128, ty),
+                    Opcode::Sload8
+                    | Opcode::Sload8Complex
+                    | Opcode::Sload16
+                    | Opcode::Sload16Complex
+                    | Opcode::Sload32
+                    | Opcode::Sload32Complex => make_int(int(value), ty),
+                    _ => value,
+                }
+            }
+            Opcode::Store
+            | Opcode::StoreComplex
+            | Opcode::Istore8
+            | Opcode::Istore8Complex
+            | Opcode::Istore16
+            | Opcode::Istore16Complex
+            | Opcode::Istore32
+            | Opcode::Istore32Complex
+            | Opcode::StackStore => {
+                let addr = match *data {
+                    InstructionData::StackStore {
+                        stack_slot, offset, ..
+                    } => address(frame.stack_slot(stack_slot), offset.into()),
+                    _ => address(
+                        args[1..].iter().map(|&a| uint(a) as u64).sum(),
+                        offset(data),
+                    ),
+                };
+                let mem_ty = match opcode {
+                    Opcode::Istore8 | Opcode::Istore8Complex => types::I8,
+                    Opcode::Istore16 | Opcode::Istore16Complex => types::I16,
+                    Opcode::Istore32 | Opcode::Istore32Complex => types::I32,
+                    _ => dfg.value_type(dfg.inst_args(inst)[0]),
+                };
+                let bytes = args[0].to_le_bytes(mem_ty);
+                if self.memory.write(addr, &bytes).is_none() {
+                    return trap(TrapCode::HeapOutOfBounds);
+                }
+                return Ok(ControlFlow::Continue);
+            }
+            Opcode::StackAddr => match *data {
+                InstructionData::StackLoad {
+                    stack_slot, offset, ..
+                } => make_int(
+                    address(frame.stack_slot(stack_slot), offset.into()).into(),
+                    ty,
+                ),
+                _ => unreachable!(),
+            },
+            Opcode::HeapAddr => match *data {
+                InstructionData::HeapAddr { heap, imm, .. } => {
+                    let min_size = function.heaps[heap].min_size.into();
+                    let (base, size) = self.memory.heap(heap, min_size).ok_or_else(unsupported)?;
+                    let index = uint(args[0]);
+                    let access_size: u32 = imm.into();
+                    if index + u128::from(access_size) > u128::from(size) {
+                        return trap(TrapCode::HeapOutOfBounds);
+                    }
+                    make_int((u128::from(base) + index) as i128, ty)
+                }
+                _ => unreachable!(),
+            },
+            Opcode::GlobalValue => match *data {
+                InstructionData::UnaryGlobalValue { global_value, .. } => {
+                    match self.global_value(frame, global_value)? {
+                        Some(value) => value,
+                        None => return trap(TrapCode::HeapOutOfBounds),
+                    }
+                }
+                _ => unreachable!(),
+            },
+
+            _ => return Err(unsupported()),
+        };
+
+        frame.set(results[0], value);
+        Ok(ControlFlow::Continue)
+    }
+
+    /// Compute the value of `gv`, or `None` if loading it faulted.
+    fn global_value(
+        &self,
+        frame: &Frame<'a>,
+        gv: GlobalValue,
+    ) -> Result<Option<DataValue>, InterpreterError> {
+        let function = frame.function;
+        Ok(match function.global_values[gv] {
+            GlobalValueData::VMContext => {
+                match function.special_param(ArgumentPurpose::VMContext) {
+                    Some(vmctx) => Some(frame.get(vmctx)),
+                    None => {
+                        return Err(InterpreterError::Unsupported(format!(
+                            "{} without a vmctx parameter",
+                            gv
+                        )))
+                    }
+                }
+            }
+            GlobalValueData::Load {
+                base,
+                offset,
+                global_type,
+                ..
+            } => match self.global_value(frame, base)? {
+                Some(base) => {
+                    let addr = address(uint(base) as u64, offset.into());
+                    self.memory
+                        .read(addr, global_type.bytes() as usize)
+                        .map(|bytes| DataValue::from_le_bytes(bytes, global_type))
+                }
+                None => None,
+            },
+            GlobalValueData::IAddImm {
+                base,
+                offset,
+                global_type,
+            } => self.global_value(frame, base)?.map(|base| {
+                let offset: i64 = offset.into();
+                make_int(int(base).wrapping_add(offset.into()), global_type)
+            }),
+            GlobalValueData::Symbol { .. } => {
+                return Err(InterpreterError::Unsupported(format!(
+                    "{} = {}",
+                    gv, function.global_values[gv]
+                )))
+            }
+        })
+    }
+}
+
+/// Can `opcode` operate on vectors?
+fn supports_vectors(opcode: Opcode) -> bool {
+    match opcode {
+        Opcode::Band
+        | Opcode::Bor
+        | Opcode::Bxor
+        | Opcode::Bnot
+        | Opcode::BandNot
+        | Opcode::BorNot
+        | Opcode::BxorNot
+        | Opcode::Copy
+        | Opcode::Select
+        | Opcode::Load
+        | Opcode::LoadComplex
+        | Opcode::Store
+        | Opcode::StoreComplex
+        | Opcode::StackLoad
+        | Opcode::StackStore
+        | Opcode::Vconst
+        | Opcode::Splat
+        | Opcode::ScalarToVector
+        | Opcode::Extractlane
+        | Opcode::Insertlane
+        | Opcode::RawBitcast => true,
+        _ => false,
+    }
+}
+
+/// Add a signed offset to an address.
+fn address(base: u64, offset: i64) -> u64 {
+    base.wrapping_add(offset as u64)
+}
+
+/// Count the leading zeros of `x` as an integer of type `ty`.
+fn leading_zeros(x: u128, ty: Type) -> i128 {
+    i128::from(x.leading_zeros()) - (128 - i128::from(ty.bits()))
+}
+
+/// Get the range of integers of type `ty` as floats: the minimum is inclusive and the maximum
+/// exclusive. Both bounds are powers of two, so they are exact.
+fn int_range(ty: Type, signed: bool) -> (f64, f64) {
+    let bits = i32::from(ty.bits());
+    if signed {
+        (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1))
+    } else {
+        (0.0, 2f64.powi(bits))
+    }
+}
+
+/// Convert an integral float known to be in range to an integer.
+fn float_to_int(x: f64) -> i128 {
+    if x < 0.0 {
+        x as i128
+    } else {
+        x as u128 as i128
+    }
+}
+
+fn imm(data: &InstructionData) -> i64 {
+    match *data {
+        InstructionData::UnaryImm { imm, .. } => imm.into(),
+        _ => unreachable!(),
+    }
+}
+
+fn offset(data: &InstructionData) -> i64 {
+    match *data {
+        InstructionData::Load { offset, .. }
+        | InstructionData::LoadComplex { offset, .. }
+        | InstructionData::Store { offset, .. }
+        | InstructionData::StoreComplex { offset, .. } => offset.into(),
+        _ => unreachable!(),
+    }
+}
+
+fn int_cond(data: &InstructionData) -> IntCC {
+    match *data {
+        InstructionData::IntCompare { cond, .. } | InstructionData::IntCompareImm { cond, .. } => {
+            cond
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn float_cond(data: &InstructionData) -> FloatCC {
+    match *data {
+        InstructionData::FloatCompare { cond, .. } => cond,
+        _ => unreachable!(),
+    }
+}
+
+fn trap_code(data: &InstructionData) -> TrapCode {
+    match *data {
+        InstructionData::Trap { code, .. } | InstructionData::CondTrap { code, .. } => code,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_reader::parse_functions;
+
+    fn call(code: &str, name: &str, args: &[DataValue]) -> Outcome {
+        let functions = parse_functions(code).unwrap();
+        let mut interpreter = Interpreter::new(functions.iter().collect());
+        interpreter.call_by_name(name, args).unwrap()
+    }
+
+    #[test]
+    fn loop_and_arithmetic() {
+        let code = "
+            function %sum(i32) -> i32 {
+            ebb0(v0: i32):
+                v1 = iconst.i32 0
+                jump ebb1(v0, v1)
+            ebb1(v2: i32, v3: i32):
+                brz v2, ebb2
+                v4 = iadd v3, v2
+                v5 = iadd_imm v2, -1
+                jump ebb1(v5, v4)
+            ebb2:
+                return v3
+            }";
+        assert_eq!(
+            call(code, "%sum", &[DataValue::I32(10)]),
+            Outcome::Return(vec![DataValue::I32(55)])
+        );
+    }
+
+    #[test]
+    fn calls_and_traps() {
+        let code = "
+            function %div(i64, i64) -> i64 {
+            ebb0(v0: i64, v1: i64):
+                v2 = sdiv v0, v1
+                return v2
+            }
+
+            function %caller(i64) -> i64 {
+                fn0 = %div(i64, i64) -> i64
+            ebb0(v0: i64):
+                v1 = iconst.i64 100
+                v2 = call fn0(v1, v0)
+                return v2
+            }";
+        assert_eq!(
+            call(code, "%caller", &[DataValue::I64(-7)]),
+            Outcome::Return(vec![DataValue::I64(-14)])
+        );
+        assert_eq!(
+            call(code, "%caller", &[DataValue::I64(0)]),
+            Outcome::Trap(TrapCode::IntegerDivisionByZero)
+        );
+        assert_eq!(
+            call(
+                code,
+                "%div",
+                &[DataValue::I64(i64::min_value()), DataValue::I64(-1)]
+            ),
+            Outcome::Trap(TrapCode::IntegerOverflow)
+        );
+    }
+
+    #[test]
+    fn memory() {
+        let code = "
+            function %mem(i64, i64 vmctx) -> i32, i8 {
+                ss0 = explicit_slot 8
+                gv0 = vmctx
+                heap0 = static gv0, min 0x100, bound 0x1000, offset_guard 0
+            ebb0(v0: i64, v10: i64):
+                v1 = iconst.i32 0x1234_5678
+                stack_store v1, ss0+4
+                v2 = stack_addr.i64 ss0
+                v3 = uload8.i32 v2+4
+                v4 = heap_addr.i64 heap0, v0, 4
+                store v1, v4
+                v5 = load.i32 v4
+                v6 = iadd v3, v5
+                v7 = iconst.i32 -1
+                istore8 v7, v4+2
+                v9 = load.i8 v4+2
+                return v6, v9
+            }";
+        assert_eq!(
+            call(code, "%mem", &[DataValue::I64(0xfc), DataValue::I64(0)]),
+            Outcome::Return(vec![DataValue::I32(0x1234_56f0), DataValue::I8(-1)])
+        );
+        assert_eq!(
+            call(code, "%mem", &[DataValue::I64(0xfd), DataValue::I64(0)]),
+            Outcome::Trap(TrapCode::HeapOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let code = "
+            function %f(f32, f64) -> f32, i32, b1 {
+            ebb0(v0: f32, v1: f64):
+                v2 = fpromote.f64 v0
+                v3 = fadd v1, v2
+                v4 = fdemote.f32 v3
+                v5 = fcvt_to_sint.i32 v3
+                v6 = fcmp uno v1, v1
+                return v4, v5, v6
+            }";
+        assert_eq!(
+            call(
+                code,
+                "%f",
+                &[DataValue::from(1.5f32), DataValue::from(-4.0f64)]
+            ),
+            Outcome::Return(vec![
+                DataValue::from(-2.5f32),
+                DataValue::I32(-2),
+                DataValue::B(false)
+            ])
+        );
+        assert_eq!(
+            call(
+                code,
+                "%f",
+                &[DataValue::from(1.5f32), DataValue::from(std::f64::NAN)]
+            ),
+            Outcome::Trap(TrapCode::BadConversionToInteger)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let functions = parse_functions("function %f(i32) {\nebb0(v0: i32):\n return\n}").unwrap();
+        let mut interpreter = Interpreter::new(functions.iter().collect());
+        assert_eq!(
+            interpreter.call_by_name("%g", &[]),
+            Err(InterpreterError::UnknownFunction("%g".to_string()))
+        );
+        assert!(interpreter
+            .call_by_name("%f", &[DataValue::I64(0)])
+            .is_err());
+    }
+}
@@ -0,0 +1,32 @@
+//! Cranelift IR interpreter.
+//!
+//! This crate executes Cranelift IR functions directly over their data flow graph, without
+//! compiling them. Since nothing is lowered to a target ISA, the interpreter observes the
+//! semantics of the IR exactly as written, before legalization.
+
+#![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
+#![warn(unused_import_braces)]
+#![cfg_attr(feature = "clippy", plugin(clippy(conf_file = "../../clippy.toml")))]
+#![cfg_attr(feature = "cargo-clippy", allow(clippy::new_without_default))]
+#![cfg_attr(
+    feature = "cargo-clippy",
+    warn(
+        clippy::mut_mut,
+        clippy::nonminimal_bool,
+        clippy::option_map_unwrap_or,
+        clippy::option_map_unwrap_or_else,
+        clippy::print_stdout,
+        clippy::unicode_not_nfc,
+        clippy::use_self
+    )
+)]
+
+mod environment;
+mod frame;
+mod interpreter;
+mod memory;
+mod value;
+
+pub use crate::environment::FunctionStore;
+pub use crate::interpreter::{Interpreter, InterpreterError, Outcome};
+pub use cranelift_codegen::data_value::DataValue;
@@ -0,0 +1,91 @@
+//! The interpreter's address space.
+//!
+//! Memory is split into two regions: heaps live in the lower one and are never freed, while stack
+//! slots are carved out of the upper one as frames are pushed and released as they are popped.
+//! The first page of the address space is never mapped, so null pointers always fault.
+
+use cranelift_codegen::ir::Heap;
+use std::collections::HashMap;
+
+/// Lowest address of the heap region.
+const HEAP_BASE: u64 = 0x1000;
+
+/// Lowest address of the stack region, which also bounds the size of the heap region.
+const STACK_BASE: u64 = 0x8000_0000;
+
+/// Byte-addressable memory shared by all frames of an interpreter.
+#[derive(Default)]
+pub struct Memory {
+    heap: Vec<u8>,
+    stack: Vec<u8>,
+    heaps: HashMap<Heap, (u64, u64)>,
+}
+
+impl Memory {
+    /// Get the base address and size of `heap`, allocating `size` zeroed bytes for it the first
+    /// time it is used.
+    ///
+    /// Returns `None` if the heap doesn't fit in the heap region.
+    pub fn heap(&mut self, heap: Heap, size: u64) -> Option<(u64, u64)> {
+        if let Some(&bounds) = self.heaps.get(&heap) {
+            return Some(bounds);
+        }
+        let base = HEAP_BASE + self.heap.len() as u64;
+        if base.checked_add(size)? > STACK_BASE {
+            return None;
+        }
+        self.heap.resize(self.heap.len() + size as usize, 0);
+        self.heaps.insert(heap, (base, size));
+        Some((base, size))
+    }
+
+    /// Get a mark to pass to `release_stack` when the current frame is popped.
+    pub fn stack_mark(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Allocate `size` zeroed bytes of stack aligned to `align`, returning their address.
+    pub fn allocate_stack(&mut self, size: u32, align: u32) -> u64 {
+        let align = align as usize;
+        let start = (self.stack.len() + align - 1) & !(align - 1);
+        self.stack.resize(start + size as usize, 0);
+        STACK_BASE + start as u64
+    }
+
+    /// Free all stack allocated since `mark` was taken.
+    pub fn release_stack(&mut self, mark: usize) {
+        self.stack.truncate(mark);
+    }
+
+    /// Find the region and offset backing `size` bytes at `addr`.
+    fn locate(&self, addr: u64, size: usize) -> Option<(bool, usize)> {
+        let end = addr.checked_add(size as u64)?;
+        if addr >= STACK_BASE {
+            if end - STACK_BASE <= self.stack.len() as u64 {
+                return Some((true, (addr - STACK_BASE) as usize));
+            }
+        } else if addr >= HEAP_BASE && end - HEAP_BASE <= self.heap.len() as u64 {
+            return Some((false, (addr - HEAP_BASE) as usize));
+        }
+        None
+    }
+
+    /// Read `size` bytes at `addr`, or `None` if any of them are unmapped.
+    pub fn read(&self, addr: u64, size: usize) -> Option<&[u8]> {
+        let (stack, offset) = self.locate(addr, size)?;
+        let region = if stack { &self.stack } else { &self.heap };
+        Some(&region[offset..offset + size])
+    }
+
+    /// Write `bytes` at `addr`, or return `None` if any of them are unmapped.
+    pub fn write(&mut self, addr: u64, bytes: &[u8]) -> Option<()> {
+        let (stack, offset) = self.locate(addr, bytes.len())?;
+        let region = if stack {
+            &mut self.stack
+        } else {
+            &mut self.heap
+        };
+        region[offset..offset + bytes.len()].copy_from_slice(bytes);
+        Some(())
+    }
+}
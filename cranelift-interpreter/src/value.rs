@@ -0,0 +1,202 @@
+//! Helpers for computing with `DataValue`s.
+//!
+//! Integers of every width are widened to 128 bits to operate on them and truncated back to their
+//! type afterwards, which gives wrapping semantics for free. Floats are computed in `f64`: every
+//! `f32` is exactly representable as an `f64`, and rounding an `f64` sum, difference, product,
+//! quotient or square root of two `f32`s gives the correctly rounded `f32` result.
+
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::immediates::{Ieee32, Ieee64};
+use cranelift_codegen::ir::{types, Type};
+
+/// Get the sign-extended value of an integer.
+pub fn int(value: DataValue) -> i128 {
+    value
+        .to_integer()
+        .unwrap_or_else(|| panic!("expected an integer, found {}", value))
+}
+
+/// Get the zero-extended value of an integer.
+pub fn uint(value: DataValue) -> u128 {
+    let bits = match value {
+        DataValue::I8(_) => 8,
+        DataValue::I16(_) => 16,
+        DataValue::I32(_) => 32,
+        DataValue::I64(_) => 64,
+        _ => 128,
+    };
+    int(value) as u128 & mask(bits)
+}
+
+/// Get a mask of the low `bits` bits.
+pub fn mask(bits: u16) -> u128 {
+    !0 >> (128 - bits)
+}
+
+/// Get the smallest signed integer of type `ty`.
+pub fn int_min(ty: Type) -> i128 {
+    i128::min_value() >> (128 - ty.bits())
+}
+
+/// Truncate `x` to an integer of type `ty`.
+pub fn make_int(x: i128, ty: Type) -> DataValue {
+    DataValue::from_integer(x, ty).unwrap_or_else(|| panic!("{} is not an integer type", ty))
+}
+
+/// Interpret `value` as a condition: booleans as themselves, integers as non-zero.
+pub fn truthy(value: DataValue) -> bool {
+    match value {
+        DataValue::B(b) => b,
+        _ => int(value) != 0,
+    }
+}
+
+/// Get the bits of an integer, float or vector.
+pub fn to_bits(value: DataValue) -> u128 {
+    match value {
+        DataValue::F32(x) => u128::from(x.bits()),
+        DataValue::F64(x) => u128::from(x.bits()),
+        DataValue::V128(bytes) => u128::from_le_bytes(bytes),
+        _ => uint(value),
+    }
+}
+
+/// Build a value of type `ty` from its bits.
+pub fn from_bits(bits: u128, ty: Type) -> DataValue {
+    DataValue::from_le_bytes(&bits.to_le_bytes()[..ty.bytes() as usize], ty)
+}
+
+/// Get the value of a float.
+pub fn float(value: DataValue) -> f64 {
+    match value {
+        DataValue::F32(x) => f64::from(f32::from_bits(x.bits())),
+        DataValue::F64(x) => f64::from_bits(x.bits()),
+        _ => panic!("expected a float, found {}", value),
+    }
+}
+
+/// Round `x` to a float of type `ty`.
+pub fn make_float(x: f64, ty: Type) -> DataValue {
+    match ty {
+        types::F32 => DataValue::F32(Ieee32::with_float(x as f32)),
+        types::F64 => DataValue::F64(Ieee64::with_float(x)),
+        _ => panic!("{} is not a float type", ty),
+    }
+}
+
+/// Compare two integers.
+pub fn icmp(cond: IntCC, x: DataValue, y: DataValue) -> bool {
+    let (sx, sy) = (int(x), int(y));
+    let (ux, uy) = (uint(x), uint(y));
+    match cond {
+        IntCC::Equal => ux == uy,
+        IntCC::NotEqual => ux != uy,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => ux < uy,
+        IntCC::UnsignedGreaterThanOrEqual => ux >= uy,
+        IntCC::UnsignedGreaterThan => ux > uy,
+        IntCC::UnsignedLessThanOrEqual => ux <= uy,
+    }
+}
+
+/// Compare two floats.
+pub fn fcmp(cond: FloatCC, x: f64, y: f64) -> bool {
+    let unordered = x.is_nan() || y.is_nan();
+    match cond {
+        FloatCC::Ordered => !unordered,
+        FloatCC::Unordered => unordered,
+        FloatCC::Equal => x == y,
+        FloatCC::NotEqual => x != y,
+        FloatCC::OrderedNotEqual => !unordered && x != y,
+        FloatCC::UnorderedOrEqual => unordered || x == y,
+        FloatCC::LessThan => x < y,
+        FloatCC::LessThanOrEqual => x <= y,
+        FloatCC::GreaterThan => x > y,
+        FloatCC::GreaterThanOrEqual => x >= y,
+        FloatCC::UnorderedOrLessThan => unordered || x < y,
+        FloatCC::UnorderedOrLessThanOrEqual => unordered || x <= y,
+        FloatCC::UnorderedOrGreaterThan => unordered || x > y,
+        FloatCC::UnorderedOrGreaterThanOrEqual => unordered || x >= y,
+    }
+}
+
+/// Get the high half of the 256-bit product of `x` and `y`.
+pub fn mul_high(x: u128, y: u128) -> u128 {
+    let (x0, x1) = (x & mask(64), x >> 64);
+    let (y0, y1) = (y & mask(64), y >> 64);
+    let low = x0 * y0;
+    let mid1 = x1 * y0 + (low >> 64);
+    let mid2 = x0 * y1 + (mid1 & mask(64));
+    x1 * y1 + (mid1 >> 64) + (mid2 >> 64)
+}
+
+/// The minimum of two floats, propagating NaNs and ordering -0.0 before 0.0.
+pub fn fmin(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        x + y
+    } else if x == y {
+        if x.is_sign_negative() {
+            x
+        } else {
+            y
+        }
+    } else {
+        x.min(y)
+    }
+}
+
+/// The maximum of two floats, propagating NaNs and ordering -0.0 before 0.0.
+pub fn fmax(x: f64, y: f64) -> f64 {
+    if x.is_nan() || y.is_nan() {
+        x + y
+    } else if x == y {
+        if x.is_sign_negative() {
+            y
+        } else {
+            x
+        }
+    } else {
+        x.max(y)
+    }
+}
+
+/// Round to the nearest integer, with ties going to the even one.
+pub fn nearest(x: f64) -> f64 {
+    let rounded = x.round();
+    if (rounded - x).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        rounded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(uint(DataValue::I8(-1)), 0xff);
+        assert_eq!(int(DataValue::I8(-1)), -1);
+        assert_eq!(int_min(types::I16), -0x8000);
+        assert_eq!(make_int(0x1_0000_0001, types::I32), DataValue::I32(1));
+        assert_eq!(mul_high(!0, !0), !0 - 1);
+        assert_eq!(mul_high(1 << 64, 1 << 64), 1);
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(nearest(2.5), 2.0);
+        assert_eq!(nearest(-3.5), -4.0);
+        assert_eq!(nearest(1.4), 1.0);
+        assert!(fmin(-0.0, 0.0).is_sign_negative());
+        assert!(fmax(-0.0, 0.0).is_sign_positive());
+        assert!(fmin(1.0, std::f64::NAN).is_nan());
+        assert!(fcmp(FloatCC::UnorderedOrEqual, std::f64::NAN, 1.0));
+        assert!(!fcmp(FloatCC::OrderedNotEqual, std::f64::NAN, 1.0));
+    }
+}
@@ -4,6 +4,7 @@ use crate::DataContext;
 use crate::DataId;
 use crate::FuncId;
 use crate::Linkage;
+use crate::ModuleError;
 use crate::ModuleNamespace;
 use crate::ModuleResult;
 use core::marker;
@@ -14,6 +15,7 @@ use cranelift_codegen::{binemit, ir};
 use std::borrow::ToOwned;
 use std::boxed::Box;
 use std::string::String;
+use std::vec::Vec;
 
 /// A `Backend` implements the functionality needed to support a `Module`.
 ///
@@ -61,14 +63,17 @@ where
     fn declare_function(&mut self, id: FuncId, name: &str, linkage: Linkage);
 
     /// Declare a data object.
+    ///
+    /// Returns an error if the backend can't emit such a data object.
     fn declare_data(
         &mut self,
         id: DataId,
         name: &str,
         linkage: Linkage,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
-    );
+    ) -> ModuleResult<()>;
 
     /// Define a function, producing the function body from the given `Context`.
     ///
@@ -82,6 +87,19 @@ where
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction>;
 
+    /// Define a function whose body is only produced and compiled when it is first called.
+    /// Backends which can't compile functions lazily return an error.
+    fn define_function_lazily(
+        &mut self,
+        id: FuncId,
+        name: &str,
+        signature: &ir::Signature,
+    ) -> ModuleResult<Self::CompiledFunction>;
+
+    /// Take the errors compiling functions defined with `define_function_lazily` ran into since
+    /// the last call.
+    fn take_lazy_compilation_errors(&mut self) -> Vec<(FuncId, ModuleError)>;
+
     /// Define a zero-initialized data object of the given size.
     ///
     /// Data objects must be declared before being defined.
@@ -90,6 +108,7 @@ where
         id: DataId,
         name: &str,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
         data_ctx: &DataContext,
         namespace: &ModuleNamespace<Self>,
@@ -97,6 +116,8 @@ where
 
     /// Write the address of `what` into the data for `data` at `offset`. `data` must refer to a
     /// defined data object.
+    ///
+    /// `what` must have been imported into the `DataContext` that `data` was defined with.
     fn write_data_funcaddr(
         &mut self,
         data: &mut Self::CompiledData,
@@ -106,6 +127,8 @@ where
 
     /// Write the address of `what` plus `addend` into the data for `data` at `offset`. `data` must
     /// refer to a defined data object.
+    ///
+    /// `what` must have been imported into the `DataContext` that `data` was defined with.
     fn write_data_dataaddr(
         &mut self,
         data: &mut Self::CompiledData,
@@ -126,6 +149,10 @@ where
     /// Return the finalized artifact from the backend, if relevant.
     fn get_finalized_function(&self, func: &Self::CompiledFunction) -> Self::FinalizedFunction;
 
+    /// Discard the definition of a function, releasing any resources it holds, so that it can be
+    /// defined again. Backends which can't discard definitions return an error.
+    fn free_function(&mut self, id: FuncId, func: &Self::CompiledFunction) -> ModuleResult<()>;
+
     /// Perform all outstanding relocations on the given data object. This requires all
     /// `Local` and `Export` entities referenced to be defined.
     fn finalize_data(
@@ -162,5 +189,13 @@ pub fn default_libcall_names() -> Box<dyn Fn(ir::LibCall) -> String> {
         ir::LibCall::Memcpy => "memcpy".to_owned(),
         ir::LibCall::Memset => "memset".to_owned(),
         ir::LibCall::Memmove => "memmove".to_owned(),
+
+        ir::LibCall::MulI128 => "__multi3".to_owned(),
+        ir::LibCall::UdivI128 => "__udivti3".to_owned(),
+        ir::LibCall::SdivI128 => "__divti3".to_owned(),
+        ir::LibCall::UremI128 => "__umodti3".to_owned(),
+        ir::LibCall::SremI128 => "__modti3".to_owned(),
+
+        ir::LibCall::ElfTlsGetAddr => "__tls_get_addr".to_owned(),
     })
 }
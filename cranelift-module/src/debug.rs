@@ -0,0 +1,805 @@
+//! Generation of DWARF debugging information for the functions defined in a module.
+//!
+//! A `DebugContext` records, for each function, which source positions its code comes from and
+//! where its labeled values live, as computed by `Context::build_value_labels_ranges`. From
+//! those it produces the `.debug_info`, `.debug_abbrev`, `.debug_line`, `.debug_loc` and
+//! `.debug_ranges` sections, which backends write into their output along with the relocations
+//! they contain.
+
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::entity::{entity_impl, EntityRef, PrimaryMap};
+use cranelift_codegen::ir::{self, SourceLoc, ValueLabel, ValueLoc};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::Context;
+use std::borrow::ToOwned;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// An opaque reference to a source file registered with a `DebugContext`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(u32);
+entity_impl!(FileId, "file");
+
+/// A position in a source file.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SourcePosition {
+    /// The file containing the position.
+    pub file: FileId,
+    /// The line number, starting at 1.
+    pub line: u32,
+    /// The column number, starting at 1, or 0 if unknown.
+    pub column: u32,
+}
+
+/// A source-level variable, whose value is tracked by a `ValueLabel`.
+#[derive(Clone, Debug)]
+pub struct DebugVariable {
+    /// The label attached to the values of the variable.
+    pub label: ValueLabel,
+    /// The name of the variable.
+    pub name: String,
+    /// The type of the variable.
+    pub ty: ir::Type,
+}
+
+/// The target of a relocation in a debug section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DebugRelocTarget {
+    /// The function with the given symbol name.
+    Function(String),
+    /// The start of the given debug section.
+    Section(&'static str),
+}
+
+/// A relocation in a debug section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DebugReloc {
+    /// The offset of the relocated field in the section.
+    pub offset: u32,
+    /// The kind of relocation, `Abs4` or `Abs8`.
+    pub reloc: Reloc,
+    /// What the relocated field refers to.
+    pub target: DebugRelocTarget,
+    /// The addend to add to the address of the target.
+    pub addend: i64,
+}
+
+/// The contents of a debug section.
+#[derive(Clone, Debug)]
+pub struct DebugSection {
+    /// The name of the section, e.g. `.debug_info`.
+    pub name: &'static str,
+    /// The contents of the section.
+    pub data: Vec<u8>,
+    /// The relocations to apply to the contents.
+    pub relocs: Vec<DebugReloc>,
+}
+
+const DEBUG_INFO: &str = ".debug_info";
+const DEBUG_ABBREV: &str = ".debug_abbrev";
+const DEBUG_LINE: &str = ".debug_line";
+const DEBUG_LOC: &str = ".debug_loc";
+const DEBUG_RANGES: &str = ".debug_ranges";
+
+/// The DWARF version of the emitted sections.
+const DWARF_VERSION: u16 = 4;
+
+/// DW_LANG_C99, the default source language.
+const DW_LANG_C99: u16 = 0x0c;
+
+// Tags, attributes and forms.
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_BASE_TYPE: u8 = 0x24;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+const DW_TAG_VARIABLE: u8 = 0x34;
+const DW_CHILDREN_NO: u8 = 0;
+const DW_CHILDREN_YES: u8 = 1;
+const DW_AT_LOCATION: u8 = 0x02;
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_BYTE_SIZE: u8 = 0x0b;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_AT_LANGUAGE: u8 = 0x13;
+const DW_AT_COMP_DIR: u8 = 0x1b;
+const DW_AT_PRODUCER: u8 = 0x25;
+const DW_AT_ENCODING: u8 = 0x3e;
+const DW_AT_EXTERNAL: u8 = 0x3f;
+const DW_AT_FRAME_BASE: u8 = 0x40;
+const DW_AT_TYPE: u8 = 0x49;
+const DW_AT_RANGES: u8 = 0x55;
+const DW_FORM_ADDR: u8 = 0x01;
+const DW_FORM_DATA2: u8 = 0x05;
+const DW_FORM_DATA4: u8 = 0x06;
+const DW_FORM_STRING: u8 = 0x08;
+const DW_FORM_DATA1: u8 = 0x0b;
+const DW_FORM_REF4: u8 = 0x13;
+const DW_FORM_SEC_OFFSET: u8 = 0x17;
+const DW_FORM_EXPRLOC: u8 = 0x18;
+const DW_FORM_FLAG_PRESENT: u8 = 0x19;
+
+// Base type encodings.
+const DW_ATE_BOOLEAN: u8 = 0x02;
+const DW_ATE_FLOAT: u8 = 0x04;
+const DW_ATE_SIGNED: u8 = 0x05;
+const DW_ATE_UNSIGNED: u8 = 0x08;
+
+// Location expression operations.
+const DW_OP_REG0: u8 = 0x50;
+const DW_OP_REGX: u8 = 0x90;
+const DW_OP_FBREG: u8 = 0x91;
+const DW_OP_CALL_FRAME_CFA: u8 = 0x9c;
+
+// Line number program opcodes.
+const DW_LNS_COPY: u8 = 0x01;
+const DW_LNS_ADVANCE_PC: u8 = 0x02;
+const DW_LNS_ADVANCE_LINE: u8 = 0x03;
+const DW_LNS_SET_FILE: u8 = 0x04;
+const DW_LNS_SET_COLUMN: u8 = 0x05;
+const DW_LNE_END_SEQUENCE: u8 = 0x01;
+const DW_LNE_SET_ADDRESS: u8 = 0x02;
+
+/// Line number program parameters. Only standard opcodes are used, so they merely need to be
+/// consistent.
+const LINE_BASE: i8 = -5;
+const LINE_RANGE: u8 = 14;
+const OPCODE_BASE: u8 = 13;
+const STANDARD_OPCODE_LENGTHS: [u8; 12] = [0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+
+// Abbreviation codes.
+const ABBREV_COMPILE_UNIT: u8 = 1;
+const ABBREV_BASE_TYPE: u8 = 2;
+const ABBREV_SUBPROGRAM: u8 = 3;
+const ABBREV_VARIABLE: u8 = 4;
+const ABBREV_VARIABLE_NO_LOCATION: u8 = 5;
+
+/// A range of code offsets in which a variable lives at the location described by a DWARF
+/// expression.
+struct VariableRange {
+    start: u32,
+    end: u32,
+    expr: Vec<u8>,
+}
+
+struct VariableDebugInfo {
+    name: String,
+    ty: ir::Type,
+    ranges: Vec<VariableRange>,
+}
+
+struct FunctionDebugInfo {
+    name: String,
+    code_size: u32,
+    /// Code offsets at which the source position changes, in increasing order.
+    lines: Vec<(u32, SourcePosition)>,
+    variables: Vec<VariableDebugInfo>,
+}
+
+/// Collects debugging information about the functions of a module, and produces the DWARF
+/// sections describing them.
+pub struct DebugContext {
+    producer: String,
+    name: String,
+    comp_dir: String,
+    language: u16,
+    address_size: u8,
+    files: PrimaryMap<FileId, String>,
+    functions: Vec<FunctionDebugInfo>,
+}
+
+impl DebugContext {
+    /// Create a new `DebugContext` for a compilation unit called `name`, compiled in the
+    /// directory `comp_dir` by `producer`, targeting the little-endian `isa`.
+    pub fn new(isa: &dyn TargetIsa, producer: &str, name: &str, comp_dir: &str) -> Self {
+        Self {
+            producer: producer.to_owned(),
+            name: name.to_owned(),
+            comp_dir: comp_dir.to_owned(),
+            language: DW_LANG_C99,
+            address_size: isa.pointer_bytes(),
+            files: PrimaryMap::new(),
+            functions: Vec::new(),
+        }
+    }
+
+    /// Set the `DW_LANG_*` source language of the compilation unit. The default is C99.
+    pub fn set_language(&mut self, language: u16) {
+        self.language = language;
+    }
+
+    /// Register a source file, whose path is either absolute or relative to the compilation
+    /// directory.
+    pub fn add_file(&mut self, path: &str) -> FileId {
+        self.files.push(path.to_owned())
+    }
+
+    /// Record the debugging information of the function with the symbol `name`, which has been
+    /// compiled in `ctx`.
+    ///
+    /// `source_position` maps the source locations attached to the function's instructions to
+    /// positions in the files registered with `add_file`. The locations of `variables` are
+    /// computed from the value labels collected by `Function::collect_debug_info`.
+    pub fn define_function(
+        &mut self,
+        name: &str,
+        ctx: &Context,
+        isa: &dyn TargetIsa,
+        source_position: &dyn Fn(SourceLoc) -> Option<SourcePosition>,
+        variables: &[DebugVariable],
+    ) {
+        let func = &ctx.func;
+        let encinfo = isa.encoding_info();
+
+        let mut ebbs = func.layout.ebbs().collect::<Vec<_>>();
+        ebbs.sort_by_key(|ebb| func.offsets[*ebb]);
+
+        let mut code_size = 0;
+        let mut lines: Vec<(u32, SourcePosition)> = Vec::new();
+        for ebb in ebbs {
+            for (offset, inst, size) in func.inst_offsets(ebb, &encinfo) {
+                code_size = offset + size;
+                let srcloc = func.srclocs[inst];
+                if size == 0 || srcloc.is_default() {
+                    continue;
+                }
+                if let Some(position) = source_position(srcloc) {
+                    if lines.last().map(|&(_, last)| last) != Some(position) {
+                        lines.push((offset, position));
+                    }
+                }
+            }
+        }
+
+        let value_ranges = ctx
+            .build_value_labels_ranges(isa)
+            .expect("value label ranges");
+        let variables = variables
+            .iter()
+            .map(|var| {
+                let mut ranges = Vec::new();
+                if let Some(var_ranges) = value_ranges.get(&var.label) {
+                    for range in var_ranges {
+                        if let Some(expr) = location_expression(&range.loc, func, isa) {
+                            ranges.push(VariableRange {
+                                start: range.start,
+                                end: range.end,
+                                expr,
+                            });
+                        }
+                    }
+                }
+                ranges.sort_by_key(|range| range.start);
+                VariableDebugInfo {
+                    name: var.name.clone(),
+                    ty: var.ty,
+                    ranges,
+                }
+            })
+            .collect();
+
+        self.functions.push(FunctionDebugInfo {
+            name: name.to_owned(),
+            code_size,
+            lines,
+            variables,
+        });
+    }
+
+    /// Produce the DWARF sections describing the functions recorded so far.
+    pub fn emit(&self) -> Vec<DebugSection> {
+        let mut sections = Vec::new();
+        sections.push(self.emit_abbrev());
+        sections.push(self.emit_line());
+        sections.push(self.emit_ranges());
+        let (loc, loc_offsets) = self.emit_loc();
+        sections.push(loc);
+        sections.push(self.emit_info(&loc_offsets));
+        sections
+    }
+
+    fn emit_abbrev(&self) -> DebugSection {
+        let mut w = SectionWriter::new(DEBUG_ABBREV, self.address_size);
+        let mut abbrev = |code: u8, tag: u8, children: u8, attrs: &[(u8, u8)]| {
+            w.uleb128(u64::from(code));
+            w.uleb128(u64::from(tag));
+            w.u8(children);
+            for &(name, form) in attrs {
+                w.uleb128(u64::from(name));
+                w.uleb128(u64::from(form));
+            }
+            w.u8(0);
+            w.u8(0);
+        };
+        abbrev(
+            ABBREV_COMPILE_UNIT,
+            DW_TAG_COMPILE_UNIT,
+            DW_CHILDREN_YES,
+            &[
+                (DW_AT_PRODUCER, DW_FORM_STRING),
+                (DW_AT_LANGUAGE, DW_FORM_DATA2),
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_COMP_DIR, DW_FORM_STRING),
+                (DW_AT_STMT_LIST, DW_FORM_SEC_OFFSET),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_RANGES, DW_FORM_SEC_OFFSET),
+            ],
+        );
+        abbrev(
+            ABBREV_BASE_TYPE,
+            DW_TAG_BASE_TYPE,
+            DW_CHILDREN_NO,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_ENCODING, DW_FORM_DATA1),
+                (DW_AT_BYTE_SIZE, DW_FORM_DATA1),
+            ],
+        );
+        abbrev(
+            ABBREV_SUBPROGRAM,
+            DW_TAG_SUBPROGRAM,
+            DW_CHILDREN_YES,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_EXTERNAL, DW_FORM_FLAG_PRESENT),
+                (DW_AT_LOW_PC, DW_FORM_ADDR),
+                (DW_AT_HIGH_PC, DW_FORM_DATA4),
+                (DW_AT_FRAME_BASE, DW_FORM_EXPRLOC),
+            ],
+        );
+        abbrev(
+            ABBREV_VARIABLE,
+            DW_TAG_VARIABLE,
+            DW_CHILDREN_NO,
+            &[
+                (DW_AT_NAME, DW_FORM_STRING),
+                (DW_AT_TYPE, DW_FORM_REF4),
+                (DW_AT_LOCATION, DW_FORM_SEC_OFFSET),
+            ],
+        );
+        abbrev(
+            ABBREV_VARIABLE_NO_LOCATION,
+            DW_TAG_VARIABLE,
+            DW_CHILDREN_NO,
+            &[(DW_AT_NAME, DW_FORM_STRING), (DW_AT_TYPE, DW_FORM_REF4)],
+        );
+        w.u8(0);
+        w.finish()
+    }
+
+    fn emit_line(&self) -> DebugSection {
+        let mut w = SectionWriter::new(DEBUG_LINE, self.address_size);
+        let unit_length = w.reserve_u32();
+        w.u16(DWARF_VERSION);
+        let header_length = w.reserve_u32();
+        // Minimum instruction length, maximum operations per instruction, default `is_stmt`.
+        w.u8(1);
+        w.u8(1);
+        w.u8(1);
+        w.u8(LINE_BASE as u8);
+        w.u8(LINE_RANGE);
+        w.u8(OPCODE_BASE);
+        w.bytes(&STANDARD_OPCODE_LENGTHS);
+        // No include directories besides the compilation directory.
+        w.u8(0);
+        for path in self.files.values() {
+            w.string(path);
+            // Directory index, modification time and length.
+            w.uleb128(0);
+            w.uleb128(0);
+            w.uleb128(0);
+        }
+        w.u8(0);
+        w.patch_length(header_length);
+
+        for func in &self.functions {
+            if func.lines.is_empty() {
+                continue;
+            }
+
+            w.u8(0);
+            w.uleb128(1 + u64::from(self.address_size));
+            w.u8(DW_LNE_SET_ADDRESS);
+            w.address(DebugRelocTarget::Function(func.name.clone()), 0);
+
+            let mut address = 0;
+            let mut file = 1;
+            let mut line = 1;
+            let mut column = 0;
+            for &(offset, position) in &func.lines {
+                if offset != address {
+                    w.u8(DW_LNS_ADVANCE_PC);
+                    w.uleb128(u64::from(offset - address));
+                    address = offset;
+                }
+                let position_file = position.file.index() as u64 + 1;
+                if position_file != file {
+                    w.u8(DW_LNS_SET_FILE);
+                    w.uleb128(position_file);
+                    file = position_file;
+                }
+                if position.line != line {
+                    w.u8(DW_LNS_ADVANCE_LINE);
+                    w.sleb128(i64::from(position.line) - i64::from(line));
+                    line = position.line;
+                }
+                if position.column != column {
+                    w.u8(DW_LNS_SET_COLUMN);
+                    w.uleb128(u64::from(position.column));
+                    column = position.column;
+                }
+                w.u8(DW_LNS_COPY);
+            }
+
+            w.u8(DW_LNS_ADVANCE_PC);
+            w.uleb128(u64::from(func.code_size - address));
+            w.u8(0);
+            w.uleb128(1);
+            w.u8(DW_LNE_END_SEQUENCE);
+        }
+
+        w.patch_length(unit_length);
+        w.finish()
+    }
+
+    fn emit_ranges(&self) -> DebugSection {
+        let mut w = SectionWriter::new(DEBUG_RANGES, self.address_size);
+        for func in &self.functions {
+            w.base_address(&func.name);
+            w.offset_address(0);
+            w.offset_address(func.code_size);
+        }
+        w.offset_address(0);
+        w.offset_address(0);
+        w.finish()
+    }
+
+    /// Emit the location lists of all variables, and return the offset of each list, in the
+    /// order of the functions and their variables.
+    fn emit_loc(&self) -> (DebugSection, Vec<Option<u32>>) {
+        let mut w = SectionWriter::new(DEBUG_LOC, self.address_size);
+        let mut offsets = Vec::new();
+        for func in &self.functions {
+            for var in &func.variables {
+                if var.ranges.is_empty() {
+                    offsets.push(None);
+                    continue;
+                }
+                offsets.push(Some(w.offset()));
+                w.base_address(&func.name);
+                for range in &var.ranges {
+                    w.offset_address(range.start);
+                    w.offset_address(range.end);
+                    w.u16(range.expr.len() as u16);
+                    w.bytes(&range.expr);
+                }
+                w.offset_address(0);
+                w.offset_address(0);
+            }
+        }
+        (w.finish(), offsets)
+    }
+
+    fn emit_info(&self, loc_offsets: &[Option<u32>]) -> DebugSection {
+        let mut w = SectionWriter::new(DEBUG_INFO, self.address_size);
+        let unit_length = w.reserve_u32();
+        w.u16(DWARF_VERSION);
+        w.section_offset(DEBUG_ABBREV, 0);
+        w.u8(self.address_size);
+
+        w.uleb128(u64::from(ABBREV_COMPILE_UNIT));
+        w.string(&self.producer);
+        w.u16(self.language);
+        w.string(&self.name);
+        w.string(&self.comp_dir);
+        w.section_offset(DEBUG_LINE, 0);
+        // The addresses in location and range lists are relative to the base address selection
+        // entries they start with.
+        w.offset_address(0);
+        w.section_offset(DEBUG_RANGES, 0);
+
+        // Base types of the variables, with their offsets from the start of the unit.
+        let mut types: Vec<(ir::Type, u32)> = Vec::new();
+        for var in self.functions.iter().flat_map(|func| &func.variables) {
+            if types.iter().any(|&(ty, _)| ty == var.ty) {
+                continue;
+            }
+            types.push((var.ty, w.offset()));
+            w.uleb128(u64::from(ABBREV_BASE_TYPE));
+            w.string(&var.ty.to_string());
+            w.u8(base_type_encoding(var.ty));
+            w.u8(var.ty.bytes().max(1) as u8);
+        }
+
+        let mut loc_offsets = loc_offsets.iter();
+        for func in &self.functions {
+            w.uleb128(u64::from(ABBREV_SUBPROGRAM));
+            w.string(&func.name);
+            w.address(DebugRelocTarget::Function(func.name.clone()), 0);
+            w.u32(func.code_size);
+            // The frame base is the canonical frame address, which stack slot offsets are
+            // relative to.
+            w.uleb128(1);
+            w.u8(DW_OP_CALL_FRAME_CFA);
+
+            for var in &func.variables {
+                let type_offset = types
+                    .iter()
+                    .find(|&&(ty, _)| ty == var.ty)
+                    .map(|&(_, offset)| offset)
+                    .unwrap();
+                match *loc_offsets.next().unwrap() {
+                    Some(loc_offset) => {
+                        w.uleb128(u64::from(ABBREV_VARIABLE));
+                        w.string(&var.name);
+                        w.u32(type_offset);
+                        w.section_offset(DEBUG_LOC, loc_offset);
+                    }
+                    None => {
+                        w.uleb128(u64::from(ABBREV_VARIABLE_NO_LOCATION));
+                        w.string(&var.name);
+                        w.u32(type_offset);
+                    }
+                }
+            }
+            w.u8(0);
+        }
+        w.u8(0);
+
+        w.patch_length(unit_length);
+        w.finish()
+    }
+}
+
+/// Get the `DW_ATE_*` encoding of a base type.
+fn base_type_encoding(ty: ir::Type) -> u8 {
+    if ty.is_float() {
+        DW_ATE_FLOAT
+    } else if ty.is_bool() {
+        DW_ATE_BOOLEAN
+    } else if ty.is_int() {
+        DW_ATE_SIGNED
+    } else {
+        DW_ATE_UNSIGNED
+    }
+}
+
+/// Build the DWARF expression describing the value location `loc` in `func`.
+fn location_expression(
+    loc: &ValueLoc,
+    func: &ir::Function,
+    isa: &dyn TargetIsa,
+) -> Option<Vec<u8>> {
+    let mut expr = Vec::new();
+    match *loc {
+        ValueLoc::Reg(reg) => {
+            let regno = isa.map_dwarf_register(reg)?;
+            if regno < 32 {
+                expr.push(DW_OP_REG0 + regno as u8);
+            } else {
+                expr.push(DW_OP_REGX);
+                write_uleb128(&mut expr, u64::from(regno));
+            }
+        }
+        ValueLoc::Stack(ss) => {
+            let offset = func.stack_slots[ss].offset?;
+            expr.push(DW_OP_FBREG);
+            write_sleb128(&mut expr, i64::from(offset));
+        }
+        ValueLoc::Unassigned => return None,
+    }
+    Some(expr)
+}
+
+fn write_uleb128(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_sleb128(buf: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Little-endian writer of the contents of a debug section.
+struct SectionWriter {
+    name: &'static str,
+    address_size: u8,
+    data: Vec<u8>,
+    relocs: Vec<DebugReloc>,
+}
+
+impl SectionWriter {
+    fn new(name: &'static str, address_size: u8) -> Self {
+        Self {
+            name,
+            address_size,
+            data: Vec::new(),
+            relocs: Vec::new(),
+        }
+    }
+
+    fn offset(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn uleb128(&mut self, value: u64) {
+        write_uleb128(&mut self.data, value);
+    }
+
+    fn sleb128(&mut self, value: i64) {
+        write_sleb128(&mut self.data, value);
+    }
+
+    /// Write a null-terminated string.
+    fn string(&mut self, value: &str) {
+        self.bytes(value.as_bytes());
+        self.u8(0);
+    }
+
+    /// Write a plain address-sized value.
+    fn offset_address(&mut self, value: u32) {
+        if self.address_size == 8 {
+            self.bytes(&u64::from(value).to_le_bytes());
+        } else {
+            self.u32(value);
+        }
+    }
+
+    /// Write a relocated address.
+    fn address(&mut self, target: DebugRelocTarget, addend: i64) {
+        let reloc = if self.address_size == 8 {
+            Reloc::Abs8
+        } else {
+            Reloc::Abs4
+        };
+        self.relocs.push(DebugReloc {
+            offset: self.offset(),
+            reloc,
+            target,
+            addend,
+        });
+        self.offset_address(0);
+    }
+
+    /// Write a base address selection entry for a location or range list, making the following
+    /// entries relative to the start of the function `name`.
+    fn base_address(&mut self, name: &str) {
+        if self.address_size == 8 {
+            self.bytes(&(!0u64).to_le_bytes());
+        } else {
+            self.u32(!0u32);
+        }
+        self.address(DebugRelocTarget::Function(name.to_owned()), 0);
+    }
+
+    /// Write a relocated offset into another debug section.
+    fn section_offset(&mut self, section: &'static str, offset: u32) {
+        self.relocs.push(DebugReloc {
+            offset: self.offset(),
+            reloc: Reloc::Abs4,
+            target: DebugRelocTarget::Section(section),
+            addend: i64::from(offset),
+        });
+        self.u32(0);
+    }
+
+    /// Reserve space for a 4-byte length field, to be patched by `patch_length`.
+    fn reserve_u32(&mut self) -> u32 {
+        let offset = self.offset();
+        self.u32(0);
+        offset
+    }
+
+    /// Patch the length field at `offset` with the number of bytes written after it.
+    fn patch_length(&mut self, offset: u32) {
+        let start = offset as usize;
+        let length = (self.data.len() - start - 4) as u32;
+        self.data[start..start + 4].copy_from_slice(&length.to_le_bytes());
+    }
+
+    fn finish(self) -> DebugSection {
+        DebugSection {
+            name: self.name,
+            data: self.data,
+            relocs: self.relocs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uleb128(value: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_uleb128(&mut buf, value);
+        buf
+    }
+
+    fn sleb128(value: i64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_sleb128(&mut buf, value);
+        buf
+    }
+
+    #[test]
+    fn leb128() {
+        assert_eq!(uleb128(0), [0x00]);
+        assert_eq!(uleb128(127), [0x7f]);
+        assert_eq!(uleb128(128), [0x80, 0x01]);
+        assert_eq!(uleb128(624_485), [0xe5, 0x8e, 0x26]);
+        assert_eq!(sleb128(0), [0x00]);
+        assert_eq!(sleb128(63), [0x3f]);
+        assert_eq!(sleb128(64), [0xc0, 0x00]);
+        assert_eq!(sleb128(-1), [0x7f]);
+        assert_eq!(sleb128(-32), [0x60]);
+        assert_eq!(sleb128(-65), [0xbf, 0x7f]);
+    }
+
+    #[test]
+    fn section_writer() {
+        let mut w = SectionWriter::new(DEBUG_INFO, 8);
+        let length = w.reserve_u32();
+        w.u16(DWARF_VERSION);
+        w.section_offset(DEBUG_ABBREV, 4);
+        w.address(DebugRelocTarget::Function("f".to_string()), 0);
+        w.patch_length(length);
+        let section = w.finish();
+
+        #[rustfmt::skip]
+        let expected: &[u8] = &[
+            14, 0, 0, 0,
+            4, 0,
+            0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(section.data, expected);
+        assert_eq!(
+            section.relocs,
+            [
+                DebugReloc {
+                    offset: 6,
+                    reloc: Reloc::Abs4,
+                    target: DebugRelocTarget::Section(DEBUG_ABBREV),
+                    addend: 4,
+                },
+                DebugReloc {
+                    offset: 10,
+                    reloc: Reloc::Abs8,
+                    target: DebugRelocTarget::Function("f".to_string()),
+                    addend: 0,
+                },
+            ]
+        );
+    }
+}
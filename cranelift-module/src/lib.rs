@@ -34,10 +34,15 @@ use std::collections::{hash_map, HashMap};
 
 mod backend;
 mod data_context;
+mod debug;
 mod module;
 
 pub use crate::backend::{default_libcall_names, Backend};
 pub use crate::data_context::{DataContext, DataDescription, Init};
+pub use crate::debug::{
+    DebugContext, DebugReloc, DebugRelocTarget, DebugSection, DebugVariable, FileId,
+    SourcePosition,
+};
 pub use crate::module::{
     DataId, FuncId, FuncOrDataId, Linkage, Module, ModuleError, ModuleFunction, ModuleNamespace,
     ModuleResult,
@@ -186,6 +186,7 @@ pub struct DataDeclaration {
     pub name: String,
     pub linkage: Linkage,
     pub writable: bool,
+    pub tls: bool,
     pub align: Option<u8>,
 }
 
@@ -204,10 +205,21 @@ impl<B> ModuleData<B>
 where
     B: Backend,
 {
-    fn merge(&mut self, linkage: Linkage, writable: bool, align: Option<u8>) {
+    fn merge(
+        &mut self,
+        linkage: Linkage,
+        writable: bool,
+        tls: bool,
+        align: Option<u8>,
+    ) -> Result<(), ModuleError> {
+        // A data object can't change from normal to thread-local or the other way around.
+        if self.decl.tls != tls {
+            return Err(ModuleError::IncompatibleDeclaration(self.decl.name.clone()));
+        }
         self.decl.linkage = Linkage::merge(self.decl.linkage, linkage);
         self.decl.writable = self.decl.writable || writable;
         self.decl.align = self.decl.align.max(align);
+        Ok(())
     }
 }
 
@@ -458,6 +470,7 @@ where
         name: &str,
         linkage: Linkage,
         writable: bool,
+        tls: bool,
         align: Option<u8>, // An alignment bigger than 128 is unlikely
     ) -> ModuleResult<DataId> {
         // TODO: Can we avoid allocating names so often?
@@ -466,14 +479,15 @@ where
             Occupied(entry) => match *entry.get() {
                 FuncOrDataId::Data(id) => {
                     let existing = &mut self.contents.data_objects[id];
-                    existing.merge(linkage, writable, align);
+                    existing.merge(linkage, writable, tls, align)?;
                     self.backend.declare_data(
                         id,
                         name,
                         existing.decl.linkage,
                         existing.decl.writable,
+                        existing.decl.tls,
                         existing.decl.align,
-                    );
+                    )?;
                     Ok(id)
                 }
 
@@ -482,18 +496,21 @@ where
                 }
             },
             Vacant(entry) => {
-                let id = self.contents.data_objects.push(ModuleData {
+                // The backend may reject the data object, which must not be declared then.
+                let id = self.contents.data_objects.next_key();
+                self.backend
+                    .declare_data(id, name, linkage, writable, tls, align)?;
+                self.contents.data_objects.push(ModuleData {
                     decl: DataDeclaration {
                         name: name.to_owned(),
                         linkage,
                         writable,
+                        tls,
                         align,
                     },
                     compiled: None,
                 });
                 entry.insert(FuncOrDataId::Data(id));
-                self.backend
-                    .declare_data(id, name, linkage, writable, align);
                 Ok(id)
             }
         }
@@ -524,6 +541,7 @@ where
             name: ir::ExternalName::user(1, data.as_u32()),
             offset: ir::immediates::Imm64::new(0),
             colocated,
+            tls: decl.tls,
         })
     }
 
@@ -546,6 +564,79 @@ where
         &mut self,
         func: FuncId,
         ctx: &mut Context,
+    ) -> ModuleResult<binemit::CodeOffset> {
+        let total_size = self.compile_function(func, ctx)?;
+        self.define_compiled_function(func, ctx, total_size)
+    }
+
+    /// Define a function whose body is only produced and compiled when it is first called, by a
+    /// callback the backend is configured with. This avoids compiling functions which are never
+    /// called.
+    ///
+    /// Backends which can't compile functions lazily return an error.
+    pub fn define_function_lazily(&mut self, func: FuncId) -> ModuleResult<()> {
+        let info = &self.contents.functions[func];
+        if info.compiled.is_some() {
+            return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
+        }
+        if !info.decl.linkage.is_definable() {
+            return Err(ModuleError::InvalidImportDefinition(info.decl.name.clone()));
+        }
+
+        let compiled = Some(self.backend.define_function_lazily(
+            func,
+            &info.decl.name,
+            &info.decl.signature,
+        )?);
+
+        self.contents.functions[func].compiled = compiled;
+        self.functions_to_finalize.push(func);
+        Ok(())
+    }
+
+    /// Take the errors that functions defined with `define_function_lazily` ran into when they
+    /// were compiled on their first call, since the last call to this. Calls to a function which
+    /// failed to compile trap.
+    pub fn take_lazy_compilation_errors(&mut self) -> Vec<(FuncId, ModuleError)> {
+        self.backend.take_lazy_compilation_errors()
+    }
+
+    /// Replace the definition of a function, producing the new function body from the given
+    /// `Context`. If the function isn't defined yet, this is the same as `define_function`.
+    ///
+    /// Whether code referring to the function picks up the new body depends on the backend;
+    /// backends which can't discard definitions return an error.
+    ///
+    /// Note: After calling this function the given `Context` will contain the compiled function.
+    pub fn redefine_function(
+        &mut self,
+        func: FuncId,
+        ctx: &mut Context,
+    ) -> ModuleResult<binemit::CodeOffset> {
+        // Compile first, so that the old definition survives compilation errors.
+        let total_size = self.compile_function(func, ctx)?;
+        self.free_function(func)?;
+        self.define_compiled_function(func, ctx, total_size)
+    }
+
+    /// Discard the definition of a function, releasing the resources the backend holds for it.
+    /// The function remains declared and may be defined again. Does nothing if the function
+    /// isn't defined.
+    ///
+    /// Backends which can't discard definitions return an error.
+    pub fn free_function(&mut self, func: FuncId) -> ModuleResult<()> {
+        if let Some(compiled) = &self.contents.functions[func].compiled {
+            self.backend.free_function(func, compiled)?;
+            self.contents.functions[func].compiled = None;
+            self.functions_to_finalize.retain(|&f| f != func);
+        }
+        Ok(())
+    }
+
+    fn compile_function(
+        &self,
+        func: FuncId,
+        ctx: &mut Context,
     ) -> ModuleResult<binemit::CodeOffset> {
         let CodeInfo { total_size, .. } = ctx.compile(self.backend.isa()).map_err(|e| {
             info!(
@@ -555,6 +646,15 @@ where
             );
             ModuleError::Compilation(e)
         })?;
+        Ok(total_size)
+    }
+
+    fn define_compiled_function(
+        &mut self,
+        func: FuncId,
+        ctx: &Context,
+        total_size: binemit::CodeOffset,
+    ) -> ModuleResult<binemit::CodeOffset> {
         let info = &self.contents.functions[func];
         if info.compiled.is_some() {
             return Err(ModuleError::DuplicateDefinition(info.decl.name.clone()));
@@ -592,6 +692,7 @@ where
                 data,
                 &info.decl.name,
                 info.decl.writable,
+                info.decl.tls,
                 info.decl.align,
                 data_ctx,
                 &ModuleNamespace::<B> {
@@ -606,6 +707,8 @@ where
 
     /// Write the address of `what` into the data for `data` at `offset`. `data` must refer to a
     /// defined data object.
+    ///
+    /// `what` must have been imported into the `DataContext` that `data` was defined with.
     pub fn write_data_funcaddr(&mut self, data: DataId, offset: usize, what: ir::FuncRef) {
         let info = &mut self.contents.data_objects[data];
         debug_assert!(
@@ -624,6 +727,8 @@ where
 
     /// Write the address of `what` plus `addend` into the data for `data` at `offset`. `data` must
     /// refer to a defined data object.
+    ///
+    /// `what` must have been imported into the `DataContext` that `data` was defined with.
     pub fn write_data_dataaddr(
         &mut self,
         data: DataId,
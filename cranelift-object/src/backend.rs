@@ -2,19 +2,23 @@
 
 use crate::traps::{ObjectTrapSink, ObjectTrapSite};
 use cranelift_codegen::binemit::{
-    Addend, CodeOffset, NullStackmapSink, NullTrapSink, Reloc, RelocSink,
+    Addend, CodeOffset, FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink, NullStackmapSink,
+    NullTrapSink, Reloc, RelocSink,
 };
-use cranelift_codegen::entity::SecondaryMap;
+use cranelift_codegen::entity::{PrimaryMap, SecondaryMap};
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{self, binemit, ir};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleNamespace,
-    ModuleResult,
+    Backend, DataContext, DataDescription, DataId, DebugRelocTarget, DebugSection, FuncId, Init,
+    Linkage, ModuleError, ModuleNamespace, ModuleResult,
 };
-use object::write::{Object, Relocation, SectionId, StandardSection, Symbol, SymbolId};
-use object::{RelocationEncoding, RelocationKind, SymbolKind, SymbolScope};
+use goblin::elf::reloc;
+use object::write::{
+    Object, Relocation, SectionId, StandardSection, StandardSegment, Symbol, SymbolId,
+};
+use object::{RelocationEncoding, RelocationKind, SectionKind, SymbolKind, SymbolScope};
 use std::collections::HashMap;
-use target_lexicon::PointerWidth;
+use target_lexicon::{BinaryFormat, PointerWidth};
 
 #[derive(Debug)]
 /// Setting to enable collection of traps. Setting this to `Enabled` in
@@ -82,6 +86,10 @@ pub struct ObjectBackend {
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
     collect_traps: ObjectTrapCollection,
     function_alignment: u64,
+    eh_frame: Option<SectionId>,
+    tdata: Option<SectionId>,
+    pdata: Option<SectionId>,
+    xdata: Option<SectionId>,
 }
 
 impl Backend for ObjectBackend {
@@ -112,6 +120,10 @@ impl Backend for ObjectBackend {
             libcall_names: builder.libcall_names,
             collect_traps: builder.collect_traps,
             function_alignment: builder.function_alignment,
+            eh_frame: None,
+            tdata: None,
+            pdata: None,
+            xdata: None,
         }
     }
 
@@ -146,8 +158,23 @@ impl Backend for ObjectBackend {
         name: &str,
         linkage: Linkage,
         _writable: bool,
+        tls: bool,
         _align: Option<u8>,
-    ) {
+    ) -> ModuleResult<()> {
+        let kind = if tls {
+            // Only ELF thread-local data is supported so far.
+            let format = self.isa.triple().binary_format;
+            if format != BinaryFormat::Elf {
+                return Err(ModuleError::Backend(format!(
+                    "ObjectBackend doesn't support thread-local data for {} objects",
+                    format
+                )));
+            }
+            SymbolKind::Tls
+        } else {
+            SymbolKind::Data
+        };
+
         let (scope, weak) = translate_linkage(linkage);
 
         if let Some(data) = self.data_objects[id] {
@@ -159,13 +186,14 @@ impl Backend for ObjectBackend {
                 name: name.as_bytes().to_vec(),
                 value: 0,
                 size: 0,
-                kind: SymbolKind::Data,
+                kind,
                 scope,
                 weak,
                 section: None,
             });
             self.data_objects[id] = Some(symbol_id);
         }
+        Ok(())
     }
 
     fn define_function(
@@ -204,12 +232,39 @@ impl Backend for ObjectBackend {
             };
         }
 
+        // No ISA emits EBB relocations yet, so there's no way to tell how they should be written.
+        if let Some(&reloc) = reloc_sink.ebb_relocs.first() {
+            return Err(ModuleError::Backend(format!(
+                "ObjectBackend doesn't support EBB relocation {}",
+                reloc
+            )));
+        }
+
         let symbol = self.functions[func_id].unwrap();
         let section = self.object.section_id(StandardSection::Text);
         let offset = self
             .object
             .add_symbol_data(symbol, section, &code, self.function_alignment);
         self.traps[func_id] = trap_sink.sites;
+
+        match self.isa.triple().binary_format {
+            BinaryFormat::Elf => {
+                let mut unwind_sink = ObjectUnwindSink::default();
+                ctx.emit_unwind_info(&*self.isa, FrameUnwindKind::Libunwind, &mut unwind_sink);
+                if !unwind_sink.data.is_empty() {
+                    self.add_eh_frame(symbol, &unwind_sink);
+                }
+            }
+            BinaryFormat::Coff => {
+                let mut unwind_sink = ObjectUnwindSink::default();
+                ctx.emit_unwind_info(&*self.isa, FrameUnwindKind::Fastcall, &mut unwind_sink);
+                if !unwind_sink.data.is_empty() {
+                    self.add_pdata(symbol, code_size, &unwind_sink);
+                }
+            }
+            _ => {}
+        }
+
         Ok(ObjectCompiledFunction {
             offset,
             size: code_size,
@@ -218,11 +273,27 @@ impl Backend for ObjectBackend {
         })
     }
 
+    fn define_function_lazily(
+        &mut self,
+        _id: FuncId,
+        _name: &str,
+        _signature: &ir::Signature,
+    ) -> ModuleResult<ObjectCompiledFunction> {
+        Err(ModuleError::Backend(
+            "ObjectBackend can't compile functions lazily".to_owned(),
+        ))
+    }
+
+    fn take_lazy_compilation_errors(&mut self) -> Vec<(FuncId, ModuleError)> {
+        Vec::new()
+    }
+
     fn define_data(
         &mut self,
         data_id: DataId,
         _name: &str,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
         data_ctx: &DataContext,
         _namespace: &ModuleNamespace<Self>,
@@ -249,11 +320,7 @@ impl Backend for ObjectBackend {
             }
         }
 
-        let reloc_size = match self.isa.triple().pointer_width().unwrap() {
-            PointerWidth::U16 => 16,
-            PointerWidth::U32 => 32,
-            PointerWidth::U64 => 64,
-        };
+        let reloc_size = self.pointer_reloc_size();
         let mut relocs = Vec::new();
         for &(offset, id) in function_relocs {
             relocs.push(RelocRecord {
@@ -277,13 +344,19 @@ impl Backend for ObjectBackend {
         }
 
         let symbol = self.data_objects[data_id].unwrap();
-        let section = self.object.section_id(if writable {
-            StandardSection::Data
-        } else if relocs.is_empty() {
-            StandardSection::ReadOnlyData
+        let section = if tls {
+            self.tdata_section()
         } else {
-            StandardSection::ReadOnlyDataWithRel
-        });
+            self.object.section_id(if writable {
+                StandardSection::Data
+            } else if relocs.is_empty() && function_decls.is_empty() && data_decls.is_empty() {
+                // Imported functions and data objects may still be referenced later through
+                // `write_data_funcaddr` and `write_data_dataaddr`.
+                StandardSection::ReadOnlyData
+            } else {
+                StandardSection::ReadOnlyDataWithRel
+            })
+        };
         let offset =
             self.object
                 .add_symbol_data(symbol, section, &data, u64::from(align.unwrap_or(1)));
@@ -291,26 +364,44 @@ impl Backend for ObjectBackend {
             offset,
             section,
             relocs,
+            function_decls: function_decls.clone(),
+            data_decls: data_decls.clone(),
         })
     }
 
     fn write_data_funcaddr(
         &mut self,
-        _data: &mut ObjectCompiledData,
-        _offset: usize,
-        _what: ir::FuncRef,
+        data: &mut ObjectCompiledData,
+        offset: usize,
+        what: ir::FuncRef,
     ) {
-        unimplemented!()
+        let size = self.pointer_reloc_size();
+        data.relocs.push(RelocRecord {
+            offset: offset as CodeOffset,
+            name: data.function_decls[what].clone(),
+            kind: RelocationKind::Absolute,
+            encoding: RelocationEncoding::Generic,
+            size,
+            addend: 0,
+        });
     }
 
     fn write_data_dataaddr(
         &mut self,
-        _data: &mut ObjectCompiledData,
-        _offset: usize,
-        _what: ir::GlobalValue,
-        _usize: binemit::Addend,
+        data: &mut ObjectCompiledData,
+        offset: usize,
+        what: ir::GlobalValue,
+        addend: binemit::Addend,
     ) {
-        unimplemented!()
+        let size = self.pointer_reloc_size();
+        data.relocs.push(RelocRecord {
+            offset: offset as CodeOffset,
+            name: data.data_decls[what].clone(),
+            kind: RelocationKind::Absolute,
+            encoding: RelocationEncoding::Generic,
+            size,
+            addend,
+        });
     }
 
     fn finalize_function(
@@ -350,6 +441,12 @@ impl Backend for ObjectBackend {
         // Nothing to do.
     }
 
+    fn free_function(&mut self, _id: FuncId, _func: &ObjectCompiledFunction) -> ModuleResult<()> {
+        Err(ModuleError::Backend(
+            "ObjectBackend can't discard a function once it has been written".to_owned(),
+        ))
+    }
+
     fn finalize_data(
         &mut self,
         _id: DataId,
@@ -402,6 +499,97 @@ impl Backend for ObjectBackend {
 }
 
 impl ObjectBackend {
+    /// Size in bits of a relocation holding a pointer on the target.
+    fn pointer_reloc_size(&self) -> u8 {
+        match self.isa.triple().pointer_width().unwrap() {
+            PointerWidth::U16 => 16,
+            PointerWidth::U32 => 32,
+            PointerWidth::U64 => 64,
+        }
+    }
+
+    /// Append the unwind information of the function `symbol` to the `.xdata` section, and the
+    /// function's entry referencing it to the `.pdata` section.
+    fn add_pdata(&mut self, symbol: SymbolId, code_size: u32, unwind: &ObjectUnwindSink) {
+        let object = &mut self.object;
+        let xdata = *self.xdata.get_or_insert_with(|| {
+            object.add_section(Vec::new(), b".xdata".to_vec(), SectionKind::ReadOnlyData)
+        });
+        let pdata = *self.pdata.get_or_insert_with(|| {
+            object.add_section(Vec::new(), b".pdata".to_vec(), SectionKind::ReadOnlyData)
+        });
+
+        let xdata_offset = object.append_section_data(xdata, &unwind.data, 4);
+        let xdata_symbol = object.section_symbol(xdata);
+
+        // A `RUNTIME_FUNCTION` entry holds the image-relative addresses of the start and end of
+        // the function and of its `UNWIND_INFO` structure.
+        let pdata_offset = object.append_section_data(pdata, &[0; 12], 4);
+        let targets = [
+            (symbol, 0),
+            (symbol, i64::from(code_size)),
+            (
+                xdata_symbol,
+                (xdata_offset + unwind.entry_offset as u64) as i64,
+            ),
+        ];
+        for (i, &(target, addend)) in targets.iter().enumerate() {
+            object
+                .add_relocation(
+                    pdata,
+                    Relocation {
+                        offset: pdata_offset + 4 * i as u64,
+                        size: 32,
+                        kind: RelocationKind::ImageOffset,
+                        encoding: RelocationEncoding::Generic,
+                        symbol: target,
+                        addend,
+                    },
+                )
+                .unwrap();
+        }
+    }
+
+    /// Return the section holding initialized thread-local data, creating it if necessary.
+    ///
+    /// Thread-local data objects are only declared for ELF objects.
+    fn tdata_section(&mut self) -> SectionId {
+        debug_assert_eq!(self.isa.triple().binary_format, BinaryFormat::Elf);
+        let object = &mut self.object;
+        *self.tdata.get_or_insert_with(|| {
+            object.add_section(Vec::new(), b".tdata".to_vec(), SectionKind::Tls)
+        })
+    }
+
+    /// Append the unwind information of the function `symbol` to the `.eh_frame` section.
+    fn add_eh_frame(&mut self, symbol: SymbolId, unwind: &ObjectUnwindSink) {
+        let object = &mut self.object;
+        let section = *self.eh_frame.get_or_insert_with(|| {
+            object.add_section(Vec::new(), b".eh_frame".to_vec(), SectionKind::ReadOnlyData)
+        });
+        let offset = object.append_section_data(section, &unwind.data, 8);
+        for &(reloc, reloc_offset) in &unwind.relocs {
+            let (kind, size) = match reloc {
+                Reloc::Abs8 => (RelocationKind::Absolute, 64),
+                Reloc::X86PCRel4 => (RelocationKind::Relative, 32),
+                _ => unimplemented!("unwind relocation {}", reloc),
+            };
+            object
+                .add_relocation(
+                    section,
+                    Relocation {
+                        offset: offset + reloc_offset as u64,
+                        size,
+                        kind,
+                        encoding: RelocationEncoding::Generic,
+                        symbol,
+                        addend: 0,
+                    },
+                )
+                .unwrap();
+        }
+    }
+
     // This should only be called during finalization because it creates
     // symbols for missing libcalls.
     fn get_symbol(
@@ -468,6 +656,10 @@ pub struct ObjectCompiledData {
     offset: u64,
     section: SectionId,
     relocs: Vec<RelocRecord>,
+    /// The declarations of the `DataContext` the data was defined with, which the `FuncRef`s
+    /// and `GlobalValue`s passed to `write_data_funcaddr` and `write_data_dataaddr` refer to.
+    function_decls: PrimaryMap<ir::FuncRef, ir::ExternalName>,
+    data_decls: PrimaryMap<ir::GlobalValue, ir::ExternalName>,
 }
 
 /// This is the output of `Module`'s
@@ -498,6 +690,81 @@ impl ObjectProduct {
         self.data_objects[id].unwrap()
     }
 
+    /// Add the debug sections produced by a `DebugContext` to the object.
+    ///
+    /// The functions they describe must have been defined in the module.
+    pub fn add_debug_sections(&mut self, sections: &[DebugSection]) -> Result<(), String> {
+        let format = self.object.format();
+        let segment = self.object.segment_name(StandardSegment::Debug).to_vec();
+
+        let mut section_ids = HashMap::new();
+        for section in sections {
+            let name = match format {
+                BinaryFormat::Macho => format!("__{}", &section.name[1..]),
+                _ => section.name.to_string(),
+            };
+            let id =
+                self.object
+                    .add_section(segment.clone(), name.into_bytes(), SectionKind::Debug);
+            let mut data = section.data.clone();
+            if format == BinaryFormat::Macho {
+                // Mach-O debug sections refer to each other with plain offsets.
+                for reloc in &section.relocs {
+                    if let DebugRelocTarget::Section(_) = reloc.target {
+                        let offset = reloc.offset as usize;
+                        data[offset..offset + 4]
+                            .copy_from_slice(&(reloc.addend as u32).to_le_bytes());
+                    }
+                }
+            }
+            self.object.append_section_data(id, &data, 1);
+            section_ids.insert(section.name, id);
+        }
+
+        for section in sections {
+            let id = section_ids[section.name];
+            for reloc in &section.relocs {
+                let (symbol, kind) = match reloc.target {
+                    DebugRelocTarget::Function(ref name) => {
+                        let symbol = self
+                            .object
+                            .symbol_id(name.as_bytes())
+                            .ok_or_else(|| format!("undefined function {}", name))?;
+                        (symbol, RelocationKind::Absolute)
+                    }
+                    DebugRelocTarget::Section(name) => {
+                        let kind = match format {
+                            BinaryFormat::Macho => continue,
+                            BinaryFormat::Coff => RelocationKind::SectionOffset,
+                            _ => RelocationKind::Absolute,
+                        };
+                        let target = *section_ids
+                            .get(name)
+                            .ok_or_else(|| format!("missing debug section {}", name))?;
+                        (self.object.section_symbol(target), kind)
+                    }
+                };
+                let size = match reloc.reloc {
+                    Reloc::Abs4 => 32,
+                    Reloc::Abs8 => 64,
+                    _ => unimplemented!(),
+                };
+                self.object.add_relocation(
+                    id,
+                    Relocation {
+                        offset: u64::from(reloc.offset),
+                        size,
+                        kind,
+                        encoding: RelocationEncoding::Generic,
+                        symbol,
+                        addend: reloc.addend,
+                    },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     /// Write the object bytes in memory.
     #[inline]
     pub fn emit(self) -> Result<Vec<u8>, String> {
@@ -515,14 +782,44 @@ struct RelocRecord {
     addend: Addend,
 }
 
+#[derive(Default)]
+struct ObjectUnwindSink {
+    data: Vec<u8>,
+    relocs: Vec<(Reloc, FrameUnwindOffset)>,
+    entry_offset: FrameUnwindOffset,
+}
+
+impl FrameUnwindSink for ObjectUnwindSink {
+    fn offset(&self) -> FrameUnwindOffset {
+        self.data.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.data.extend_from_slice(b);
+    }
+
+    fn reserve(&mut self, len: usize) {
+        self.data.reserve(len)
+    }
+
+    fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+        self.relocs.push((r, off));
+    }
+
+    fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+        self.entry_offset = off;
+    }
+}
+
 #[derive(Default)]
 struct ObjectRelocSink {
     relocs: Vec<RelocRecord>,
+    ebb_relocs: Vec<Reloc>,
 }
 
 impl RelocSink for ObjectRelocSink {
-    fn reloc_ebb(&mut self, _offset: CodeOffset, _reloc: Reloc, _ebb_offset: CodeOffset) {
-        unimplemented!();
+    fn reloc_ebb(&mut self, _offset: CodeOffset, reloc: Reloc, _ebb_offset: CodeOffset) {
+        self.ebb_relocs.push(reloc);
     }
 
     fn reloc_external(
@@ -545,6 +842,16 @@ impl RelocSink for ObjectRelocSink {
                 32,
             ),
             Reloc::X86GOTPCRel4 => (RelocationKind::GotRelative, RelocationEncoding::Generic, 32),
+            Reloc::ElfX86_64TlsGd => (
+                RelocationKind::Other(reloc::R_X86_64_TLSGD),
+                RelocationEncoding::Generic,
+                32,
+            ),
+            Reloc::ElfX86_64TpOff32 => (
+                RelocationKind::Other(reloc::R_X86_64_TPOFF32),
+                RelocationEncoding::Generic,
+                32,
+            ),
             // FIXME
             _ => unimplemented!(),
         };
@@ -0,0 +1,238 @@
+use cranelift_codegen::ir::*;
+use cranelift_codegen::{isa, settings, Context};
+use cranelift_frontend::*;
+use cranelift_module::*;
+use cranelift_object::*;
+use object::{Object, ObjectSection, RelocationKind, RelocationTarget, SymbolKind};
+
+fn object_module(triple: &str) -> Module<ObjectBackend> {
+    let isa = isa::lookup_by_name(triple)
+        .unwrap()
+        .finish(settings::Flags::new(settings::builder()));
+    Module::new(
+        ObjectBuilder::new(
+            isa,
+            "test".to_string(),
+            ObjectTrapCollection::Disabled,
+            default_libcall_names(),
+        )
+        .unwrap(),
+    )
+}
+
+fn define_simple_function(module: &mut Module<ObjectBackend>) -> FuncId {
+    let sig = module.make_signature();
+    let func_id = module
+        .declare_function("abc", Linkage::Local, &sig)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = {
+        let func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(func);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        bcx.ins().return_(&[]);
+        bcx.seal_all_blocks();
+        bcx.finalize()
+    };
+    module.define_function(func_id, &mut ctx).unwrap();
+
+    func_id
+}
+
+/// Define a read-only `vtable` holding the address of `abc` and the address of `data` plus 4,
+/// both written after the data object was defined.
+fn define_vtable(module: &mut Module<ObjectBackend>) {
+    let func_id = define_simple_function(module);
+
+    let data_id = module
+        .declare_data("data", Linkage::Local, true, false, None)
+        .unwrap();
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(16);
+    module.define_data(data_id, &data_ctx).unwrap();
+
+    let vtable_id = module
+        .declare_data("vtable", Linkage::Export, false, false, Some(8))
+        .unwrap();
+    let mut data_ctx = DataContext::new();
+    data_ctx.define_zeroinit(16);
+    let func_ref = module.declare_func_in_data(func_id, &mut data_ctx);
+    let data_gv = module.declare_data_in_data(data_id, &mut data_ctx);
+    module.define_data(vtable_id, &data_ctx).unwrap();
+
+    module.write_data_funcaddr(vtable_id, 0, func_ref);
+    module.write_data_dataaddr(vtable_id, 8, data_gv, 4);
+    module.finalize_definitions();
+}
+
+#[test]
+fn write_data_addresses_elf() {
+    let mut module = object_module("x86_64-unknown-linux-gnu");
+    define_vtable(&mut module);
+    let bytes = module.finish().emit().unwrap();
+
+    let file = object::File::parse(&bytes).unwrap();
+    let section = file.section_by_name(".rodata").unwrap();
+    let mut relocs: Vec<_> = section
+        .relocations()
+        .map(|(offset, reloc)| {
+            let name = match reloc.target() {
+                RelocationTarget::Symbol(index) => file.symbol_by_index(index).unwrap().name(),
+                RelocationTarget::Section(_) => None,
+            };
+            (offset, reloc.kind(), reloc.size(), name, reloc.addend())
+        })
+        .collect();
+    relocs.sort_by_key(|r| r.0);
+    assert_eq!(
+        relocs,
+        [
+            (0, RelocationKind::Absolute, 64, Some("abc"), 0),
+            (8, RelocationKind::Absolute, 64, Some("data"), 4),
+        ]
+    );
+}
+
+#[test]
+fn write_data_addresses_macho_and_coff() {
+    for &triple in &["x86_64-apple-darwin", "x86_64-pc-windows-msvc"] {
+        let mut module = object_module(triple);
+        define_vtable(&mut module);
+        module.finish().emit().unwrap();
+    }
+}
+
+/// Define a function returning the value of a thread-local `counter`.
+fn define_tls_reader(module: &mut Module<ObjectBackend>, linkage: Linkage) {
+    let data_id = module
+        .declare_data("counter", linkage, true, true, None)
+        .unwrap();
+    if linkage.is_definable() {
+        let mut data_ctx = DataContext::new();
+        data_ctx.define(vec![1, 0, 0, 0, 0, 0, 0, 0].into_boxed_slice());
+        module.define_data(data_id, &data_ctx).unwrap();
+    }
+
+    let mut sig = module.make_signature();
+    sig.returns.push(AbiParam::new(types::I64));
+    let func_id = module
+        .declare_function("read_counter", Linkage::Export, &sig)
+        .unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func = {
+        let mut func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+        let gv = module.declare_data_in_func(data_id, &mut func);
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(func);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let addr = bcx.ins().global_value(types::I64, gv);
+        let value = bcx.ins().load(types::I64, MemFlags::trusted(), addr, 0);
+        bcx.ins().return_(&[value]);
+        bcx.seal_all_blocks();
+        bcx.finalize()
+    };
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions();
+}
+
+/// Collect the relocations of the `.text` section as `(kind, target name, addend)`.
+fn text_relocs(file: &object::File) -> Vec<(RelocationKind, Option<String>, i64)> {
+    let section = file.section_by_name(".text").unwrap();
+    section
+        .relocations()
+        .map(|(_, reloc)| {
+            let name = match reloc.target() {
+                RelocationTarget::Symbol(index) => file
+                    .symbol_by_index(index)
+                    .unwrap()
+                    .name()
+                    .map(str::to_string),
+                RelocationTarget::Section(_) => None,
+            };
+            (reloc.kind(), name, reloc.addend())
+        })
+        .collect()
+}
+
+#[test]
+fn tls_local_exec_elf() {
+    let mut module = object_module("x86_64-unknown-linux-gnu");
+    define_tls_reader(&mut module, Linkage::Local);
+    let bytes = module.finish().emit().unwrap();
+
+    let file = object::File::parse(&bytes).unwrap();
+    let tdata = file.section_by_name(".tdata").unwrap();
+    assert_eq!(tdata.data(), &[1, 0, 0, 0, 0, 0, 0, 0][..]);
+    let counter = file
+        .symbols()
+        .map(|(_, symbol)| symbol)
+        .find(|symbol| symbol.name() == Some("counter"))
+        .unwrap();
+    assert_eq!(counter.kind(), SymbolKind::Tls);
+
+    // R_X86_64_TPOFF32
+    assert_eq!(
+        text_relocs(&file),
+        [(RelocationKind::Other(23), Some("counter".to_string()), 0)]
+    );
+}
+
+#[test]
+fn tls_general_dynamic_elf() {
+    let mut module = object_module("x86_64-unknown-linux-gnu");
+    define_tls_reader(&mut module, Linkage::Import);
+    let bytes = module.finish().emit().unwrap();
+
+    let file = object::File::parse(&bytes).unwrap();
+    // R_X86_64_TLSGD
+    assert_eq!(
+        text_relocs(&file),
+        [
+            (RelocationKind::Other(19), Some("counter".to_string()), -4),
+            (
+                RelocationKind::PltRelative,
+                Some("__tls_get_addr".to_string()),
+                -4
+            ),
+        ]
+    );
+}
+
+#[test]
+fn tls_macho_and_coff_unsupported() {
+    for &triple in &["x86_64-apple-darwin", "x86_64-pc-windows-msvc"] {
+        let mut module = object_module(triple);
+        match module.declare_data("counter", Linkage::Local, true, true, None) {
+            Err(ModuleError::Backend(_)) => {}
+            _ => panic!("thread-local data was declared for {}", triple),
+        }
+        // The rejected name is still free.
+        module
+            .declare_data("counter", Linkage::Local, true, false, None)
+            .unwrap();
+        module.finish().emit().unwrap();
+    }
+}
+
+#[test]
+fn redeclare_data_with_different_tls() {
+    let mut module = object_module("x86_64-unknown-linux-gnu");
+    module
+        .declare_data("counter", Linkage::Local, true, true, None)
+        .unwrap();
+    match module.declare_data("counter", Linkage::Local, true, false, None) {
+        Err(ModuleError::IncompatibleDeclaration(name)) => assert_eq!(name, "counter"),
+        _ => panic!("thread-local data was redeclared as normal data"),
+    }
+
+    module
+        .declare_data("data", Linkage::Local, true, false, None)
+        .unwrap();
+    match module.declare_data("data", Linkage::Local, true, true, None) {
+        Err(ModuleError::IncompatibleDeclaration(name)) => assert_eq!(name, "data"),
+        _ => panic!("normal data was redeclared as thread-local data"),
+    }
+}
@@ -0,0 +1,552 @@
+//! Inline calls between the functions of a module.
+//!
+//! Cranelift compiles every function on its own, so inlining has to happen before that, with all
+//! the functions that may call each other at hand. A `CallGraph` holds them, keyed by their
+//! `ExternalName`, which is the name callers import them by.
+//!
+//! Inlining a call splits the EBB containing it in two: the call is replaced by a jump to a copy
+//! of the callee's body, whose `return` instructions jump to the second half with the returned
+//! values as EBB arguments. Every entity the callee refers to is copied into the caller along the
+//! way, so the caller must not have been legalized yet.
+
+use cranelift_codegen::ir::{
+    self, ArgumentPurpose, Constant, Ebb, ExtFuncData, ExternalName, FuncRef, Function,
+    GlobalValue, GlobalValueData, Heap, HeapStyle, Inst, InstructionData, JumpTable, JumpTableData,
+    Opcode, SigRef, StackSlot, StackSlotKind, Table, Value, ValueList,
+};
+use cranelift_entity::packed_option::ReservedValue;
+use cranelift_entity::{EntityRef, SecondaryMap};
+use std::vec::Vec;
+
+/// Limits on how much code inlining may duplicate.
+#[derive(Clone, Debug)]
+pub struct InlineLimits {
+    /// Only inline functions with at most this many instructions.
+    pub max_callee_size: usize,
+    /// Stop inlining into a function once it has this many instructions.
+    pub max_caller_size: usize,
+}
+
+impl Default for InlineLimits {
+    fn default() -> Self {
+        Self {
+            max_callee_size: 32,
+            max_caller_size: 2048,
+        }
+    }
+}
+
+/// A set of functions that call each other.
+///
+/// Functions are keyed by their `ExternalName`: a call to an imported function with the same name
+/// as one in the graph is a call to that function, and may be inlined. Functions that are
+/// mutually recursive are never inlined into each other.
+pub struct CallGraph {
+    functions: Vec<Function>,
+    names: Vec<ExternalName>,
+    /// The strongly connected component of the call graph each function belongs to. Components
+    /// are numbered so that callees come before their callers.
+    components: Vec<usize>,
+}
+
+impl CallGraph {
+    /// Build the call graph of `functions`.
+    pub fn new(functions: Vec<Function>) -> Self {
+        let names = functions.iter().map(|func| func.name.clone()).collect();
+        let mut graph = Self {
+            functions,
+            names,
+            components: Vec::new(),
+        };
+        let callees = (0..graph.functions.len())
+            .map(|caller| {
+                graph.functions[caller]
+                    .dfg
+                    .ext_funcs
+                    .values()
+                    .filter_map(|ext_func| graph.index(&ext_func.name))
+                    .collect()
+            })
+            .collect::<Vec<Vec<usize>>>();
+        graph.components = strongly_connected_components(&callees);
+        graph
+    }
+
+    /// Get the function named `name`.
+    pub fn get(&self, name: &ExternalName) -> Option<&Function> {
+        self.index(name).map(|index| &self.functions[index])
+    }
+
+    /// Get all the functions in the graph.
+    pub fn functions(&self) -> &[Function] {
+        &self.functions
+    }
+
+    /// Take the functions out of the graph.
+    pub fn into_functions(self) -> Vec<Function> {
+        self.functions
+    }
+
+    /// Get the indices of the functions in an order where callees come before their callers.
+    pub(crate) fn bottom_up(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.functions.len()).collect();
+        order.sort_by_key(|&index| self.components[index]);
+        order
+    }
+
+    /// Get mutable access to the function at `index`, as returned by `bottom_up`.
+    pub(crate) fn function_mut(&mut self, index: usize) -> &mut Function {
+        &mut self.functions[index]
+    }
+
+    fn index(&self, name: &ExternalName) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+}
+
+/// Number the strongly connected components of the graph with the edges `successors`, in reverse
+/// topological order, with Tarjan's algorithm.
+///
+/// The depth first search uses an explicit stack, since call chains can get deep.
+fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<usize> {
+    const UNVISITED: usize = usize::max_value();
+    let count = successors.len();
+    let mut components = vec![UNVISITED; count];
+    let mut indices = vec![UNVISITED; count];
+    let mut lowlinks = vec![0; count];
+    let mut on_stack = vec![false; count];
+    let mut stack = Vec::new();
+    let mut next_index = 0;
+    let mut next_component = 0;
+
+    for root in 0..count {
+        if indices[root] != UNVISITED {
+            continue;
+        }
+        // Each frame is a node and the number of its successors visited so far.
+        let mut frames = vec![(root, 0)];
+        while let Some(&mut (node, ref mut visited)) = frames.last_mut() {
+            if *visited == 0 {
+                indices[node] = next_index;
+                lowlinks[node] = next_index;
+                next_index += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            }
+            if let Some(&succ) = successors[node].get(*visited) {
+                *visited += 1;
+                if indices[succ] == UNVISITED {
+                    frames.push((succ, 0));
+                } else if on_stack[succ] {
+                    lowlinks[node] = lowlinks[node].min(indices[succ]);
+                }
+                continue;
+            }
+
+            frames.pop();
+            if let Some(&(parent, _)) = frames.last() {
+                lowlinks[parent] = lowlinks[parent].min(lowlinks[node]);
+            }
+            if lowlinks[node] == indices[node] {
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member] = false;
+                    components[member] = next_component;
+                    if member == node {
+                        break;
+                    }
+                }
+                next_component += 1;
+            }
+        }
+    }
+    components
+}
+
+/// Inline calls in `func` to functions of `graph`, within `limits`.
+///
+/// Calls from a function of the graph to another function of the same strongly connected
+/// component are left alone, as are calls whose signature doesn't match the callee's.
+pub fn inline_calls(func: &mut Function, graph: &CallGraph, limits: &InlineLimits) {
+    let component = graph.index(&func.name).map(|index| graph.components[index]);
+    let mut size = function_size(func);
+
+    // Only look at the calls that were there originally: the callees have had their own calls
+    // inlined already if they were worth it.
+    let mut calls = Vec::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            if let InstructionData::Call { func_ref, .. } = func.dfg[inst] {
+                calls.push((inst, func_ref));
+            }
+        }
+    }
+
+    for (call, func_ref) in calls {
+        let callee_index = match graph.index(&func.dfg.ext_funcs[func_ref].name) {
+            Some(index) => index,
+            None => continue,
+        };
+        if Some(graph.components[callee_index]) == component {
+            continue;
+        }
+        let callee = &graph.functions[callee_index];
+        let callee_size = function_size(callee);
+        if callee_size > limits.max_callee_size || size + callee_size > limits.max_caller_size {
+            continue;
+        }
+        if inline_call(func, call, callee) {
+            size += callee_size;
+        }
+    }
+}
+
+/// Count the instructions of `func`.
+fn function_size(func: &Function) -> usize {
+    func.layout
+        .ebbs()
+        .map(|ebb| func.layout.ebb_insts(ebb).count())
+        .sum()
+}
+
+/// Replace `call` in `func` with the body of `callee`, if possible.
+fn inline_call(func: &mut Function, call: Inst, callee: &Function) -> bool {
+    let entry = match callee.layout.entry_block() {
+        Some(entry) => entry,
+        None => return false,
+    };
+    let args = func.dfg.inst_args(call).to_vec();
+    let sig = &func.dfg.signatures[func.dfg.call_signature(call).unwrap()];
+    if !same_types(&sig.params, &callee.signature.params)
+        || !same_types(&sig.returns, &callee.signature.returns)
+    {
+        return false;
+    }
+    if callee
+        .stack_slots
+        .values()
+        .any(|slot| slot.kind != StackSlotKind::ExplicitSlot)
+    {
+        return false;
+    }
+
+    // The callee's `vmctx` global value refers to its own VM context parameter, so it can only be
+    // mapped to the caller's if the call passes it along.
+    let uses_vmctx = callee.global_values.values().any(|gv| match gv {
+        GlobalValueData::VMContext => true,
+        _ => false,
+    });
+    let vmctx = if uses_vmctx {
+        let index = callee
+            .signature
+            .special_param_index(ArgumentPurpose::VMContext);
+        let caller_vmctx = func.special_param(ArgumentPurpose::VMContext);
+        match (index, caller_vmctx) {
+            (Some(index), Some(caller_vmctx))
+                if func.dfg.resolve_aliases(args[index]) == caller_vmctx =>
+            {
+                Some(vmctx_global_value(func))
+            }
+            _ => return false,
+        }
+    } else {
+        None
+    };
+
+    // A callee made of a single EBB ending in its only return can be spliced in place of the
+    // call. Otherwise the caller's EBB is split after the call, and the results of the call become
+    // the parameters of the second half, which the callee's returns jump to.
+    let straight_line = callee.layout.ebbs().nth(1).is_none()
+        && callee
+            .layout
+            .last_inst(entry)
+            .map_or(false, |inst| callee.dfg[inst].opcode().is_return());
+    let after = if straight_line {
+        None
+    } else {
+        let continuation = match func.layout.next_inst(call) {
+            Some(next) => next,
+            None => return false,
+        };
+        let after = func.dfg.make_ebb();
+        func.layout.split_ebb(after, continuation);
+        Some(after)
+    };
+
+    let results = func.dfg.inst_results(call).to_vec();
+    func.dfg.clear_results(call);
+    let mut inliner = Inliner::new(func, callee);
+    inliner.copy_entities(vmctx);
+    let returned = inliner.copy_body(call, &args, after);
+
+    match after {
+        Some(after) => {
+            for result in results {
+                let ty = func.dfg.value_type(result);
+                let param = func.dfg.append_ebb_param(after, ty);
+                func.dfg.change_to_alias(result, param);
+            }
+        }
+        None => {
+            for (result, value) in results.into_iter().zip(returned) {
+                func.dfg.change_to_alias(result, value);
+            }
+        }
+    }
+    func.layout.remove_inst(call);
+    true
+}
+
+/// Do two lists of parameters have the same value types?
+fn same_types(a: &[ir::AbiParam], b: &[ir::AbiParam]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.value_type == b.value_type)
+}
+
+/// Get the `vmctx` global value of `func`, creating it if necessary.
+fn vmctx_global_value(func: &mut Function) -> GlobalValue {
+    let existing = func.global_values.iter().find(|(_, gv)| match gv {
+        GlobalValueData::VMContext => true,
+        _ => false,
+    });
+    match existing {
+        Some((gv, _)) => gv,
+        None => func.create_global_value(GlobalValueData::VMContext),
+    }
+}
+
+/// Create a map from the entities of a callee to those of a caller.
+fn entity_map<K: EntityRef + ReservedValue>() -> SecondaryMap<K, K> {
+    SecondaryMap::with_default(K::reserved_value())
+}
+
+/// Copies the body of a callee into a caller, keeping track of how the callee's entities map to
+/// the caller's.
+struct Inliner<'a> {
+    func: &'a mut Function,
+    callee: &'a Function,
+    ebbs: SecondaryMap<Ebb, Ebb>,
+    values: SecondaryMap<Value, Value>,
+    global_values: SecondaryMap<GlobalValue, GlobalValue>,
+    heaps: SecondaryMap<Heap, Heap>,
+    tables: SecondaryMap<Table, Table>,
+    stack_slots: SecondaryMap<StackSlot, StackSlot>,
+    signatures: SecondaryMap<SigRef, SigRef>,
+    func_refs: SecondaryMap<FuncRef, FuncRef>,
+    jump_tables: SecondaryMap<JumpTable, JumpTable>,
+    constants: SecondaryMap<Constant, Constant>,
+}
+
+impl<'a> Inliner<'a> {
+    fn new(func: &'a mut Function, callee: &'a Function) -> Self {
+        Self {
+            func,
+            callee,
+            ebbs: entity_map(),
+            values: entity_map(),
+            global_values: entity_map(),
+            heaps: entity_map(),
+            tables: entity_map(),
+            stack_slots: entity_map(),
+            signatures: entity_map(),
+            func_refs: entity_map(),
+            jump_tables: entity_map(),
+            constants: entity_map(),
+        }
+    }
+
+    /// Copy everything but the EBBs, values and jump tables of the callee into the caller,
+    /// mapping its `vmctx` global value to `vmctx`.
+    fn copy_entities(&mut self, vmctx: Option<GlobalValue>) {
+        let callee = self.callee;
+        for (gv, data) in callee.global_values.iter() {
+            self.global_values[gv] = match data {
+                GlobalValueData::VMContext => vmctx.unwrap(),
+                _ => self.func.create_global_value(data.clone()),
+            };
+        }
+        // Global values can refer to any other, so bases are only known once all are copied.
+        for (gv, data) in callee.global_values.iter() {
+            let copy = self.global_values[gv];
+            match *data {
+                GlobalValueData::Load { base, .. } | GlobalValueData::IAddImm { base, .. } => {
+                    let mapped = self.global_values[base];
+                    match self.func.global_values[copy] {
+                        GlobalValueData::Load { ref mut base, .. }
+                        | GlobalValueData::IAddImm { ref mut base, .. } => *base = mapped,
+                        _ => unreachable!(),
+                    }
+                }
+                GlobalValueData::VMContext | GlobalValueData::Symbol { .. } => {}
+            }
+        }
+
+        for (heap, data) in callee.heaps.iter() {
+            let mut copy = data.clone();
+            copy.base = self.global_values[copy.base];
+            if let HeapStyle::Dynamic { ref mut bound_gv } = copy.style {
+                *bound_gv = self.global_values[*bound_gv];
+            }
+            self.heaps[heap] = self.func.create_heap(copy);
+        }
+        for (table, data) in callee.tables.iter() {
+            let mut copy = data.clone();
+            copy.base_gv = self.global_values[copy.base_gv];
+            copy.bound_gv = self.global_values[copy.bound_gv];
+            self.tables[table] = self.func.create_table(copy);
+        }
+        for (slot, data) in callee.stack_slots.iter() {
+            self.stack_slots[slot] = self.func.create_stack_slot(data.clone());
+        }
+        for (sig, data) in callee.dfg.signatures.iter() {
+            self.signatures[sig] = self.func.import_signature(data.clone());
+        }
+        for (func_ref, data) in callee.dfg.ext_funcs.iter() {
+            self.func_refs[func_ref] = self.func.import_function(ExtFuncData {
+                name: data.name.clone(),
+                signature: self.signatures[data.signature],
+                colocated: data.colocated,
+            });
+        }
+        for (&constant, data) in callee.dfg.constants.iter() {
+            self.constants[constant] = self.func.dfg.constants.insert(data.clone());
+        }
+    }
+
+    /// Copy the body of the callee in place of `call`, which passes it `args`.
+    ///
+    /// The entry EBB of the callee is inserted before `call`, and the other EBBs before `after`,
+    /// to which returns jump. Without `after`, the callee must be a single EBB ending with a
+    /// return, which is left out; the values it returns are returned instead.
+    fn copy_body(&mut self, call: Inst, args: &[Value], after: Option<Ebb>) -> Vec<Value> {
+        let callee = self.callee;
+        let entry = callee.layout.entry_block().unwrap();
+        for ebb in callee.layout.ebbs() {
+            if ebb == entry {
+                for (&param, &arg) in callee.dfg.ebb_params(ebb).iter().zip(args) {
+                    self.values[param] = arg;
+                }
+                continue;
+            }
+            let copy = self.func.dfg.make_ebb();
+            self.func.layout.insert_ebb(copy, after.unwrap());
+            for &param in callee.dfg.ebb_params(ebb) {
+                let ty = callee.dfg.value_type(param);
+                self.values[param] = self.func.dfg.append_ebb_param(copy, ty);
+            }
+            self.ebbs[ebb] = copy;
+        }
+        for (table, data) in callee.jump_tables.iter() {
+            let mut copy = JumpTableData::with_capacity(data.len());
+            for &ebb in data.iter() {
+                copy.push_entry(self.ebbs[ebb]);
+            }
+            self.jump_tables[table] = self.func.create_jump_table(copy);
+        }
+
+        // Values may be used before they are defined in layout order, so instructions are all
+        // created first and get their arguments mapped afterwards.
+        let srcloc = self.func.srclocs[call];
+        let mut copies = Vec::new();
+        let mut returned = Vec::new();
+        for ebb in callee.layout.ebbs() {
+            for inst in callee.layout.ebb_insts(ebb) {
+                let data = match (&callee.dfg[inst], after) {
+                    (InstructionData::MultiAry { opcode, .. }, None) if opcode.is_return() => {
+                        returned.extend_from_slice(callee.dfg.inst_args(inst));
+                        continue;
+                    }
+                    _ => self.copy_instruction(inst, after),
+                };
+                let copy = self.func.dfg.make_inst(data);
+                if ebb == entry {
+                    self.func.layout.insert_inst(copy, call);
+                } else {
+                    self.func.layout.append_inst(copy, self.ebbs[ebb]);
+                }
+                // Only functions with source locations get an entry per instruction.
+                if !srcloc.is_default() {
+                    self.func.srclocs[copy] = srcloc;
+                }
+                self.func
+                    .dfg
+                    .make_inst_results(copy, callee.dfg.ctrl_typevar(inst));
+                let results = callee.dfg.inst_results(inst);
+                for (&result, &copied) in results.iter().zip(self.func.dfg.inst_results(copy)) {
+                    self.values[result] = copied;
+                }
+                copies.push(copy);
+            }
+        }
+        for copy in copies {
+            for arg in self.func.dfg.inst_args_mut(copy) {
+                *arg = self.values[callee.dfg.resolve_aliases(*arg)];
+            }
+        }
+        returned
+            .into_iter()
+            .map(|value| self.values[callee.dfg.resolve_aliases(value)])
+            .collect()
+    }
+
+    /// Copy the data of the callee's instruction `inst`, with everything but its arguments mapped
+    /// to the caller's entities. Returns become jumps to `after`.
+    fn copy_instruction(&mut self, inst: Inst, after: Option<Ebb>) -> InstructionData {
+        let callee = self.callee;
+        let mut data = callee.dfg[inst].clone();
+        if let Some(args) = data.take_value_list() {
+            let args = args.as_slice(&callee.dfg.value_lists);
+            let copy = ValueList::from_slice(args, &mut self.func.dfg.value_lists);
+            data.put_value_list(copy);
+        }
+        if let Some(destination) = data.branch_destination_mut() {
+            *destination = self.ebbs[*destination];
+        }
+
+        match data {
+            InstructionData::MultiAry { opcode, args } if opcode.is_return() => {
+                return InstructionData::Jump {
+                    opcode: Opcode::Jump,
+                    destination: after.unwrap(),
+                    args,
+                }
+            }
+            InstructionData::UnaryGlobalValue {
+                ref mut global_value,
+                ..
+            } => *global_value = self.global_values[*global_value],
+            InstructionData::UnaryImm128 { ref mut imm, .. } => *imm = self.constants[*imm],
+            InstructionData::BranchTable {
+                ref mut destination,
+                ref mut table,
+                ..
+            } => {
+                *destination = self.ebbs[*destination];
+                *table = self.jump_tables[*table];
+            }
+            InstructionData::BranchTableEntry { ref mut table, .. }
+            | InstructionData::BranchTableBase { ref mut table, .. }
+            | InstructionData::IndirectJump { ref mut table, .. } => {
+                *table = self.jump_tables[*table]
+            }
+            InstructionData::Call {
+                ref mut func_ref, ..
+            }
+            | InstructionData::FuncAddr {
+                ref mut func_ref, ..
+            } => *func_ref = self.func_refs[*func_ref],
+            InstructionData::CallIndirect {
+                ref mut sig_ref, ..
+            } => *sig_ref = self.signatures[*sig_ref],
+            InstructionData::StackLoad {
+                ref mut stack_slot, ..
+            }
+            | InstructionData::StackStore {
+                ref mut stack_slot, ..
+            } => *stack_slot = self.stack_slots[*stack_slot],
+            InstructionData::HeapAddr { ref mut heap, .. } => *heap = self.heaps[*heap],
+            InstructionData::TableAddr { ref mut table, .. } => *table = self.tables[*table],
+            _ => {}
+        }
+        data
+    }
+}
@@ -27,9 +27,15 @@ extern crate alloc as std;
 #[macro_use]
 extern crate std;
 
-mod constant_folding;
+mod inline;
+mod sccp;
 
-use cranelift_codegen::{isa::TargetIsa, settings::FlagsOrIsa, CodegenResult, Context};
+pub use crate::inline::{CallGraph, InlineLimits};
+
+use core::mem;
+use cranelift_codegen::{
+    ir::Function, isa::TargetIsa, settings::FlagsOrIsa, CodegenResult, Context,
+};
 
 /// Optimize the function with available optimizations.
 ///
@@ -38,17 +44,80 @@ use cranelift_codegen::{isa::TargetIsa, settings::FlagsOrIsa, CodegenResult, Con
 /// if it's not used.
 pub fn optimize(ctx: &mut Context, isa: &dyn TargetIsa) -> CodegenResult<()> {
     ctx.verify_if(isa)?;
+    promote_stack_slots(ctx, isa)?;
     fold_constants(ctx, isa)?;
 
     Ok(())
 }
 
-/// Fold constants
+/// Promote stack slots whose address never escapes to SSA values.
+///
+/// The `stack_load` and `stack_store` instructions accessing such slots are removed, and the
+/// stored values are passed along as EBB parameters where they merge.
+pub fn promote_stack_slots<'a, FOI>(ctx: &mut Context, fisa: FOI) -> CodegenResult<()>
+where
+    FOI: Into<FlagsOrIsa<'a>>,
+{
+    cranelift_frontend::promote_stack_slots(&mut ctx.func);
+    // Critical edges out of jump tables may have been split.
+    ctx.cfg.clear();
+    ctx.domtree.clear();
+    ctx.loop_analysis.clear();
+    ctx.verify_if(fisa)?;
+    Ok(())
+}
+
+/// Fold constants with sparse conditional constant propagation.
+///
+/// Constants are propagated through EBB parameters, branches on constant conditions are folded,
+/// and EBBs which become unreachable are removed.
 pub fn fold_constants<'a, FOI>(ctx: &mut Context, fisa: FOI) -> CodegenResult<()>
 where
     FOI: Into<FlagsOrIsa<'a>>,
 {
-    constant_folding::fold_constants(&mut ctx.func);
+    sccp::propagate_constants(&mut ctx.func);
+    // Folding branches changes the CFG, so the analyses computed before are stale.
+    ctx.cfg.clear();
+    ctx.domtree.clear();
+    ctx.loop_analysis.clear();
+    ctx.verify_if(fisa)?;
+    Ok(())
+}
+
+/// Inline calls in the function of `ctx` to functions of `graph`, within `limits`.
+pub fn inline_calls<'a, FOI>(
+    ctx: &mut Context,
+    graph: &CallGraph,
+    limits: &InlineLimits,
+    fisa: FOI,
+) -> CodegenResult<()>
+where
+    FOI: Into<FlagsOrIsa<'a>>,
+{
+    inline::inline_calls(&mut ctx.func, graph, limits);
     ctx.verify_if(fisa)?;
     Ok(())
 }
+
+/// Inline calls between the functions of `graph`, within `limits`.
+///
+/// Functions are processed callees first, so calls are inlined along with whatever their callees
+/// had inlined into them already.
+pub fn inline_functions<'a, FOI>(
+    graph: &mut CallGraph,
+    limits: &InlineLimits,
+    fisa: FOI,
+) -> CodegenResult<()>
+where
+    FOI: Into<FlagsOrIsa<'a>>,
+{
+    let fisa = fisa.into();
+    for index in graph.bottom_up() {
+        let func = mem::replace(graph.function_mut(index), Function::new());
+        let mut ctx = Context::for_function(func);
+        let result = inline_calls(&mut ctx, graph, limits, fisa);
+        *graph.function_mut(index) = ctx.func;
+        result?;
+    }
+    Ok(())
+}
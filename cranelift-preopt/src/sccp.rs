@@ -0,0 +1,830 @@
+//! Sparse conditional constant propagation.
+//!
+//! This is the algorithm of Wegman and Zadeck: values start out undefined and only ever move down
+//! the lattice, to a constant and then to overdefined, while EBBs are only analyzed once a branch
+//! to them may be taken. Constants therefore flow through EBB parameters, and branches whose
+//! conditions are constant only make their actual destination reachable.
+//!
+//! Once the analysis has converged, constant values are materialized, constant EBB parameters
+//! are removed, branches with constant conditions become jumps or disappear, and EBBs that can't
+//! be reached are deleted.
+//!
+//! Only scalar integers of up to 64 bits, booleans and floats are tracked. Instructions that would
+//! trap are never folded, and neither are float operations producing a NaN, since its payload
+//! depends on the target.
+
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::flowgraph::ControlFlowGraph;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::immediates::{Ieee32, Ieee64};
+use cranelift_codegen::ir::instructions::BranchInfo;
+use cranelift_codegen::ir::{
+    types, DataFlowGraph, Ebb, Function, Inst, InstBuilder, InstructionData, JumpTableData, Opcode,
+    Type, Value,
+};
+use cranelift_entity::SecondaryMap;
+use std::vec::Vec;
+
+/// What is known about a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Lattice {
+    /// No definition of the value has been reached yet.
+    Undefined,
+    /// The value is always this constant: the zero-extended bits of an integer, 0 or 1 for a
+    /// boolean, or the bits of a float.
+    Constant(u64),
+    /// The value may vary.
+    Overdefined,
+}
+
+impl Default for Lattice {
+    fn default() -> Self {
+        Lattice::Undefined
+    }
+}
+
+impl Lattice {
+    fn meet(self, other: Self) -> Self {
+        match (self, other) {
+            (Lattice::Undefined, x) | (x, Lattice::Undefined) => x,
+            (Lattice::Constant(a), Lattice::Constant(b)) if a == b => self,
+            _ => Lattice::Overdefined,
+        }
+    }
+}
+
+/// Propagate constants through `func`, fold branches on them and remove the code they make
+/// unreachable.
+pub fn propagate_constants(func: &mut Function) {
+    let mut sccp = Sccp::new(func);
+    sccp.solve();
+    let Sccp {
+        values,
+        executable,
+        reached,
+        ..
+    } = sccp;
+    rewrite(func, &values, &executable, &reached);
+}
+
+struct Sccp<'a> {
+    func: &'a Function,
+    values: SecondaryMap<Value, Lattice>,
+    executable: SecondaryMap<Ebb, bool>,
+    reached: SecondaryMap<Inst, bool>,
+    /// The instructions using each value.
+    users: SecondaryMap<Value, Vec<Inst>>,
+    /// Instructions from which to continue scanning the EBBs that contain them.
+    flow_worklist: Vec<Inst>,
+    /// Values whose users need to be visited again.
+    value_worklist: Vec<Value>,
+}
+
+impl<'a> Sccp<'a> {
+    fn new(func: &'a Function) -> Self {
+        let mut users: SecondaryMap<Value, Vec<Inst>> = SecondaryMap::new();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                for &arg in func.dfg.inst_args(inst) {
+                    users[func.dfg.resolve_aliases(arg)].push(inst);
+                }
+            }
+        }
+        Self {
+            func,
+            values: SecondaryMap::new(),
+            executable: SecondaryMap::new(),
+            reached: SecondaryMap::new(),
+            users,
+            flow_worklist: Vec::new(),
+            value_worklist: Vec::new(),
+        }
+    }
+
+    fn solve(&mut self) {
+        let entry = match self.func.layout.entry_block() {
+            Some(entry) => entry,
+            None => return,
+        };
+        for &param in self.func.dfg.ebb_params(entry) {
+            self.values[param] = Lattice::Overdefined;
+        }
+        self.mark_executable(entry);
+
+        loop {
+            if let Some(inst) = self.flow_worklist.pop() {
+                self.scan(inst);
+            } else if let Some(value) = self.value_worklist.pop() {
+                for i in 0..self.users[value].len() {
+                    let inst = self.users[value][i];
+                    if self.reached[inst] && self.visit(inst) {
+                        self.continue_after(inst);
+                    }
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn lattice(&self, value: Value) -> Lattice {
+        self.values[self.func.dfg.resolve_aliases(value)]
+    }
+
+    fn lower(&mut self, value: Value, lattice: Lattice) {
+        let old = self.values[value];
+        let new = if tracked(self.func.dfg.value_type(value)) {
+            old.meet(lattice)
+        } else {
+            Lattice::Overdefined
+        };
+        if new != old {
+            self.values[value] = new;
+            self.value_worklist.push(value);
+        }
+    }
+
+    fn mark_executable(&mut self, ebb: Ebb) {
+        if !self.executable[ebb] {
+            self.executable[ebb] = true;
+            if let Some(first) = self.func.layout.first_inst(ebb) {
+                self.flow_worklist.push(first);
+            }
+        }
+    }
+
+    /// Scan an EBB from `inst` until reaching a branch that can't fall through.
+    fn scan(&mut self, inst: Inst) {
+        let mut next = Some(inst);
+        while let Some(inst) = next {
+            if self.reached[inst] {
+                return;
+            }
+            self.reached[inst] = true;
+            if !self.visit(inst) {
+                return;
+            }
+            next = self.func.layout.next_inst(inst);
+        }
+    }
+
+    /// Continue scanning after `inst`, now that control may fall through it.
+    fn continue_after(&mut self, inst: Inst) {
+        if let Some(next) = self.func.layout.next_inst(inst) {
+            if !self.reached[next] {
+                self.flow_worklist.push(next);
+            }
+        }
+    }
+
+    /// Update what is known about the effects of `inst`, returning whether control may fall
+    /// through it.
+    fn visit(&mut self, inst: Inst) -> bool {
+        let dfg = &self.func.dfg;
+        let opcode = dfg[inst].opcode();
+        if opcode.is_branch() {
+            return self.visit_branch(inst);
+        }
+
+        let results = dfg.inst_results(inst);
+        if results.len() == 1 {
+            let result = results[0];
+            let lattice = self.evaluate(inst);
+            self.lower(result, lattice);
+        } else {
+            for i in 0..results.len() {
+                let result = self.func.dfg.inst_results(inst)[i];
+                self.lower(result, Lattice::Overdefined);
+            }
+        }
+        !opcode.is_terminator()
+    }
+
+    fn visit_branch(&mut self, inst: Inst) -> bool {
+        let dfg = &self.func.dfg;
+        match branch_condition(dfg, inst, |v| self.lattice(v)) {
+            Condition::Unknown => false,
+            Condition::Taken(taken) => {
+                match dfg.analyze_branch(inst) {
+                    BranchInfo::SingleDest(..) if taken => self.mark_edge(inst),
+                    _ => {}
+                }
+                !taken
+            }
+            Condition::Index(index) => {
+                if let BranchInfo::Table(table, default) = dfg.analyze_branch(inst) {
+                    let dest = self.func.jump_tables[table]
+                        .as_slice()
+                        .get(index as usize)
+                        .cloned()
+                        .or(default);
+                    if let Some(dest) = dest {
+                        self.mark_executable(dest);
+                    }
+                }
+                false
+            }
+            Condition::Varying => {
+                match dfg.analyze_branch(inst) {
+                    BranchInfo::SingleDest(..) => self.mark_edge(inst),
+                    BranchInfo::Table(table, default) => {
+                        for i in 0..self.func.jump_tables[table].len() {
+                            let dest = self.func.jump_tables[table].as_slice()[i];
+                            self.mark_executable(dest);
+                        }
+                        if let Some(default) = default {
+                            self.mark_executable(default);
+                        }
+                    }
+                    BranchInfo::NotABranch => {}
+                }
+                !dfg[inst].opcode().is_terminator()
+            }
+        }
+    }
+
+    /// Take the branch `inst` to a single destination, passing its arguments along.
+    fn mark_edge(&mut self, inst: Inst) {
+        let dfg = &self.func.dfg;
+        let dest = dfg[inst].branch_destination().unwrap();
+        let args = dfg.inst_variable_args(inst);
+        for i in 0..args.len() {
+            let param = self.func.dfg.ebb_params(dest)[i];
+            let arg = self.lattice(self.func.dfg.inst_variable_args(inst)[i]);
+            self.lower(param, arg);
+        }
+        self.mark_executable(dest);
+    }
+
+    /// Evaluate the single result of `inst`.
+    fn evaluate(&self, inst: Inst) -> Lattice {
+        let dfg = &self.func.dfg;
+        let data = &dfg[inst];
+        if data.opcode() == Opcode::Select {
+            let args = dfg.inst_args(inst);
+            return match self.lattice(args[0]) {
+                Lattice::Constant(c) => self.lattice(args[if c != 0 { 1 } else { 2 }]),
+                Lattice::Overdefined => self.lattice(args[1]).meet(self.lattice(args[2])),
+                Lattice::Undefined => Lattice::Undefined,
+            };
+        }
+
+        let mut args = Vec::new();
+        for &arg in dfg.inst_args(inst) {
+            match self.lattice(arg) {
+                Lattice::Constant(c) => args.push(c),
+                Lattice::Undefined => return Lattice::Undefined,
+                Lattice::Overdefined => return Lattice::Overdefined,
+            }
+        }
+        match fold(dfg, inst, &args) {
+            Some(c) => Lattice::Constant(c),
+            None => Lattice::Overdefined,
+        }
+    }
+}
+
+/// The outcome of a branch instruction.
+enum Condition {
+    /// Nothing is known about the condition yet.
+    Unknown,
+    /// A conditional branch is always taken, or never.
+    Taken(bool),
+    /// A jump table branch always goes to the same entry.
+    Index(u64),
+    /// The branch may go either way.
+    Varying,
+}
+
+fn branch_condition<F: Fn(Value) -> Lattice>(
+    dfg: &DataFlowGraph,
+    inst: Inst,
+    lattice: F,
+) -> Condition {
+    let args = dfg.inst_args(inst);
+    let constant = |lattice: Lattice| match lattice {
+        Lattice::Undefined => Err(Condition::Unknown),
+        Lattice::Constant(c) => Ok(c),
+        Lattice::Overdefined => Err(Condition::Varying),
+    };
+    let outcome = match dfg[inst] {
+        InstructionData::Jump { .. } => return Condition::Taken(true),
+        InstructionData::Branch { opcode, .. } => constant(lattice(args[0])).map(|c| {
+            let zero = c == 0;
+            Condition::Taken(if opcode == Opcode::Brz { zero } else { !zero })
+        }),
+        InstructionData::BranchIcmp { cond, .. } => {
+            let ty = dfg.value_type(args[0]);
+            constant(lattice(args[0])).and_then(|x| {
+                constant(lattice(args[1])).map(|y| Condition::Taken(compare_ints(cond, x, y, ty)))
+            })
+        }
+        InstructionData::BranchTable {
+            opcode: Opcode::BrTable,
+            ..
+        } => constant(lattice(args[0])).map(Condition::Index),
+        _ => Err(Condition::Varying),
+    };
+    match outcome {
+        Ok(condition) | Err(condition) => condition,
+    }
+}
+
+/// Can values of type `ty` be constants?
+fn tracked(ty: Type) -> bool {
+    ty.is_bool() || ty == types::F32 || ty == types::F64 || (ty.is_int() && ty.bits() <= 64)
+}
+
+/// Truncate `bits` to the width of the integer type `ty`.
+fn truncate(bits: u64, ty: Type) -> u64 {
+    if ty.bits() >= 64 {
+        bits
+    } else {
+        bits & ((1 << ty.bits()) - 1)
+    }
+}
+
+/// Sign-extend the integer `bits` of type `ty`.
+fn sign_extend(bits: u64, ty: Type) -> i64 {
+    let shift = 64 - ty.bits();
+    ((bits << shift) as i64) >> shift
+}
+
+fn compare_ints(cond: IntCC, x: u64, y: u64, ty: Type) -> bool {
+    let (sx, sy) = (sign_extend(x, ty), sign_extend(y, ty));
+    match cond {
+        IntCC::Equal => x == y,
+        IntCC::NotEqual => x != y,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => x < y,
+        IntCC::UnsignedGreaterThanOrEqual => x >= y,
+        IntCC::UnsignedGreaterThan => x > y,
+        IntCC::UnsignedLessThanOrEqual => x <= y,
+    }
+}
+
+/// Fold `inst`, whose arguments are the constants `args`, if it doesn't trap.
+fn fold(dfg: &DataFlowGraph, inst: Inst, args: &[u64]) -> Option<u64> {
+    let data = &dfg[inst];
+    let result_ty = dfg.value_type(dfg.first_result(inst));
+    if !tracked(result_ty) {
+        return None;
+    }
+    let arg_ty = dfg.inst_args(inst).first().map(|&arg| dfg.value_type(arg));
+
+    let folded = match *data {
+        InstructionData::UnaryImm { imm, .. } => {
+            let imm: i64 = imm.into();
+            imm as u64
+        }
+        InstructionData::UnaryBool { imm, .. } => imm as u64,
+        InstructionData::UnaryIeee32 { imm, .. } => u64::from(imm.bits()),
+        InstructionData::UnaryIeee64 { imm, .. } => imm.bits(),
+        InstructionData::Unary { opcode, .. } => fold_unary(opcode, args[0], arg_ty?, result_ty)?,
+        InstructionData::Binary { opcode, .. } => fold_binary(opcode, args[0], args[1], result_ty)?,
+        InstructionData::BinaryImm { opcode, imm, .. } => {
+            let imm: i64 = imm.into();
+            let opcode = match opcode {
+                Opcode::IaddImm => Opcode::Iadd,
+                Opcode::ImulImm => Opcode::Imul,
+                Opcode::UdivImm => Opcode::Udiv,
+                Opcode::SdivImm => Opcode::Sdiv,
+                Opcode::UremImm => Opcode::Urem,
+                Opcode::SremImm => Opcode::Srem,
+                Opcode::IrsubImm => {
+                    return Some(truncate((imm as u64).wrapping_sub(args[0]), result_ty))
+                }
+                Opcode::BandImm => Opcode::Band,
+                Opcode::BorImm => Opcode::Bor,
+                Opcode::BxorImm => Opcode::Bxor,
+                Opcode::IshlImm => Opcode::Ishl,
+                Opcode::UshrImm => Opcode::Ushr,
+                Opcode::SshrImm => Opcode::Sshr,
+                Opcode::RotlImm => Opcode::Rotl,
+                Opcode::RotrImm => Opcode::Rotr,
+                _ => return None,
+            };
+            fold_binary(opcode, args[0], truncate(imm as u64, result_ty), result_ty)?
+        }
+        InstructionData::IntCompare { cond, .. } => {
+            compare_ints(cond, args[0], args[1], arg_ty?) as u64
+        }
+        InstructionData::IntCompareImm { cond, imm, .. } => {
+            let imm: i64 = imm.into();
+            let ty = arg_ty?;
+            compare_ints(cond, args[0], truncate(imm as u64, ty), ty) as u64
+        }
+        InstructionData::FloatCompare { cond, .. } => {
+            let ordering = if arg_ty? == types::F32 {
+                f32::from_bits(args[0] as u32).partial_cmp(&f32::from_bits(args[1] as u32))
+            } else {
+                f64::from_bits(args[0]).partial_cmp(&f64::from_bits(args[1]))
+            };
+            compare_floats(cond, ordering) as u64
+        }
+        _ => return None,
+    };
+    Some(if result_ty.is_int() {
+        truncate(folded, result_ty)
+    } else {
+        folded
+    })
+}
+
+fn fold_unary(opcode: Opcode, x: u64, arg_ty: Type, ty: Type) -> Option<u64> {
+    let bits = u64::from(ty.bits());
+    Some(match opcode {
+        Opcode::Copy => x,
+        Opcode::Bnot if ty.is_int() => !x,
+        Opcode::Clz if ty.is_int() => u64::from((x << (64 - bits)).leading_zeros()).min(bits),
+        Opcode::Ctz if ty.is_int() => u64::from(x.trailing_zeros()).min(bits),
+        Opcode::Popcnt if ty.is_int() => u64::from(x.count_ones()),
+        Opcode::Uextend | Opcode::Ireduce => x,
+        Opcode::Sextend => sign_extend(x, arg_ty) as u64,
+        Opcode::Bint | Opcode::Bextend | Opcode::Breduce => x,
+        Opcode::Bmask => {
+            if x != 0 {
+                !0
+            } else {
+                0
+            }
+        }
+        _ => return fold_float_unary(opcode, x, arg_ty, ty),
+    })
+}
+
+fn fold_binary(opcode: Opcode, x: u64, y: u64, ty: Type) -> Option<u64> {
+    if !ty.is_int() {
+        return fold_float_binary(opcode, x, y, ty);
+    }
+    let bits = u32::from(ty.bits());
+    let (sx, sy) = (sign_extend(x, ty), sign_extend(y, ty));
+    let amount = (y % u64::from(bits)) as u32;
+    let rotate = |left: bool| {
+        let amount = if left { amount } else { (bits - amount) % bits };
+        if amount == 0 {
+            x
+        } else {
+            (x << amount) | (x >> (bits - amount))
+        }
+    };
+    Some(match opcode {
+        Opcode::Iadd => x.wrapping_add(y),
+        Opcode::Isub => x.wrapping_sub(y),
+        Opcode::Imul => x.wrapping_mul(y),
+        Opcode::Umulhi => ((u128::from(x) * u128::from(y)) >> bits) as u64,
+        Opcode::Smulhi => ((i128::from(sx) * i128::from(sy)) >> bits) as u64,
+        Opcode::Udiv => x.checked_div(y)?,
+        Opcode::Urem => x.checked_rem(y)?,
+        // Dividing the smallest integer by -1 overflows and traps, and so does the remainder.
+        Opcode::Sdiv if sx != sign_extend(1 << (bits - 1), ty) || sy != -1 => {
+            sx.checked_div(sy)? as u64
+        }
+        Opcode::Srem if sx != sign_extend(1 << (bits - 1), ty) || sy != -1 => {
+            sx.checked_rem(sy)? as u64
+        }
+        Opcode::Band => x & y,
+        Opcode::Bor => x | y,
+        Opcode::Bxor => x ^ y,
+        Opcode::BandNot => x & !y,
+        Opcode::BorNot => x | !y,
+        Opcode::BxorNot => x ^ !y,
+        Opcode::Ishl => x << amount,
+        Opcode::Ushr => x >> amount,
+        Opcode::Sshr => (sx >> amount) as u64,
+        Opcode::Rotl => rotate(true),
+        Opcode::Rotr => rotate(false),
+        _ => return None,
+    })
+}
+
+fn compare_floats(cond: FloatCC, ordering: Option<core::cmp::Ordering>) -> bool {
+    use core::cmp::Ordering::*;
+    match (cond, ordering) {
+        (FloatCC::Ordered, o) => o.is_some(),
+        (FloatCC::Unordered, o) => o.is_none(),
+        (FloatCC::Equal, o) => o == Some(Equal),
+        (FloatCC::NotEqual, o) => o != Some(Equal),
+        (FloatCC::OrderedNotEqual, o) => o.is_some() && o != Some(Equal),
+        (FloatCC::UnorderedOrEqual, o) => o.is_none() || o == Some(Equal),
+        (FloatCC::LessThan, o) => o == Some(Less),
+        (FloatCC::LessThanOrEqual, o) => o == Some(Less) || o == Some(Equal),
+        (FloatCC::GreaterThan, o) => o == Some(Greater),
+        (FloatCC::GreaterThanOrEqual, o) => o == Some(Greater) || o == Some(Equal),
+        (FloatCC::UnorderedOrLessThan, o) => o != Some(Greater) && o != Some(Equal),
+        (FloatCC::UnorderedOrLessThanOrEqual, o) => o != Some(Greater),
+        (FloatCC::UnorderedOrGreaterThan, o) => o != Some(Less) && o != Some(Equal),
+        (FloatCC::UnorderedOrGreaterThanOrEqual, o) => o != Some(Less),
+    }
+}
+
+/// A float of either width, to fold operations on both with the same code.
+///
+/// Rust's float arithmetic follows IEEE 754 with rounding to nearest, which is what Cranelift
+/// specifies too.
+#[derive(Clone, Copy)]
+enum Float {
+    F32(f32),
+    F64(f64),
+}
+
+impl Float {
+    fn new(bits: u64, ty: Type) -> Option<Self> {
+        match ty {
+            types::F32 => Some(Float::F32(f32::from_bits(bits as u32))),
+            types::F64 => Some(Float::F64(f64::from_bits(bits))),
+            _ => None,
+        }
+    }
+
+    fn is_nan(self) -> bool {
+        match self {
+            Float::F32(x) => x.is_nan(),
+            Float::F64(x) => x.is_nan(),
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Float::F32(x) => f64::from(x),
+            Float::F64(x) => x,
+        }
+    }
+
+    /// Get the bits of this float, unless it is a NaN.
+    fn bits(self) -> Option<u64> {
+        if self.is_nan() {
+            return None;
+        }
+        Some(match self {
+            Float::F32(x) => u64::from(x.to_bits()),
+            Float::F64(x) => x.to_bits(),
+        })
+    }
+}
+
+/// Mask of the sign bit of a float type.
+fn sign_bit(ty: Type) -> u64 {
+    1 << (ty.bits() - 1)
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(clippy::float_arithmetic))]
+fn fold_float_binary(opcode: Opcode, x: u64, y: u64, ty: Type) -> Option<u64> {
+    // The sign operations only move bits around, so they can even be applied to NaNs.
+    match opcode {
+        Opcode::Fcopysign => return Some((x & !sign_bit(ty)) | (y & sign_bit(ty))),
+        _ => {}
+    }
+    let (a, b) = (Float::new(x, ty)?, Float::new(y, ty)?);
+    if a.is_nan() || b.is_nan() {
+        return None;
+    }
+    let result = match (opcode, a, b) {
+        (Opcode::Fadd, Float::F32(a), Float::F32(b)) => Float::F32(a + b),
+        (Opcode::Fadd, Float::F64(a), Float::F64(b)) => Float::F64(a + b),
+        (Opcode::Fsub, Float::F32(a), Float::F32(b)) => Float::F32(a - b),
+        (Opcode::Fsub, Float::F64(a), Float::F64(b)) => Float::F64(a - b),
+        (Opcode::Fmul, Float::F32(a), Float::F32(b)) => Float::F32(a * b),
+        (Opcode::Fmul, Float::F64(a), Float::F64(b)) => Float::F64(a * b),
+        (Opcode::Fdiv, Float::F32(a), Float::F32(b)) => Float::F32(a / b),
+        (Opcode::Fdiv, Float::F64(a), Float::F64(b)) => Float::F64(a / b),
+        (Opcode::Fmin, _, _) | (Opcode::Fmax, _, _) => {
+            // -0.0 is smaller than 0.0 here, unlike for comparisons.
+            let a_first = if a.as_f64() == b.as_f64() {
+                (x & sign_bit(ty) != 0) == (opcode == Opcode::Fmin)
+            } else {
+                (a.as_f64() < b.as_f64()) == (opcode == Opcode::Fmin)
+            };
+            return Some(if a_first { x } else { y });
+        }
+        _ => return None,
+    };
+    result.bits()
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(clippy::float_arithmetic))]
+fn fold_float_unary(opcode: Opcode, x: u64, arg_ty: Type, ty: Type) -> Option<u64> {
+    match opcode {
+        Opcode::Fneg => return Some(x ^ sign_bit(ty)),
+        Opcode::Fabs => return Some(x & !sign_bit(ty)),
+        Opcode::FcvtFromSint | Opcode::FcvtFromUint => {
+            let signed = opcode == Opcode::FcvtFromSint;
+            let result = match (ty, signed) {
+                (types::F32, true) => Float::F32(sign_extend(x, arg_ty) as f32),
+                (types::F32, false) => Float::F32(x as f32),
+                (types::F64, true) => Float::F64(sign_extend(x, arg_ty) as f64),
+                (types::F64, false) => Float::F64(x as f64),
+                _ => return None,
+            };
+            return result.bits();
+        }
+        _ => {}
+    }
+
+    let a = Float::new(x, arg_ty)?;
+    if a.is_nan() {
+        return None;
+    }
+    match opcode {
+        Opcode::Fpromote => Float::F64(a.as_f64()).bits(),
+        Opcode::Fdemote => Float::F32(a.as_f64() as f32).bits(),
+        Opcode::FcvtToSint | Opcode::FcvtToUint | Opcode::FcvtToSintSat | Opcode::FcvtToUintSat => {
+            let a = a.as_f64();
+            let signed = opcode == Opcode::FcvtToSint || opcode == Opcode::FcvtToSintSat;
+            let saturating = opcode == Opcode::FcvtToSintSat || opcode == Opcode::FcvtToUintSat;
+            // Bounds of the values which truncate to an integer of type `ty`, exclusive.
+            let width = ty.bits() as i32;
+            let (low, high) = if signed {
+                (-pow2(width - 1) - 1.0, pow2(width - 1))
+            } else {
+                (-1.0, pow2(width))
+            };
+            if a <= low || a >= high {
+                if !saturating {
+                    return None;
+                }
+                return Some(match (signed, a <= low) {
+                    (true, true) => 1 << (width - 1),
+                    (true, false) => (1 << (width - 1)) - 1,
+                    (false, true) => 0,
+                    (false, false) => !0,
+                });
+            }
+            // `as` truncates towards zero, and the value is within range.
+            Some(if signed { a as i64 as u64 } else { a as u64 })
+        }
+        _ => None,
+    }
+}
+
+/// Compute `2^exponent` exactly.
+fn pow2(exponent: i32) -> f64 {
+    f64::from_bits(((1023 + exponent) as u64) << 52)
+}
+
+/// Apply the results of the analysis to `func`.
+fn rewrite(
+    func: &mut Function,
+    values: &SecondaryMap<Value, Lattice>,
+    executable: &SecondaryMap<Ebb, bool>,
+    reached: &SecondaryMap<Inst, bool>,
+) {
+    let cfg = ControlFlowGraph::with_function(func);
+    let mut pos = FuncCursor::new(func);
+    while let Some(ebb) = pos.next_ebb() {
+        if !executable[ebb] {
+            continue;
+        }
+
+        pos.goto_top(ebb);
+        while let Some(inst) = pos.next_inst() {
+            if !reached[inst] {
+                continue;
+            }
+            let opcode = pos.func.dfg[inst].opcode();
+            if opcode.is_branch() {
+                fold_branch(&mut pos, inst, values);
+                continue;
+            }
+
+            let results = pos.func.dfg.inst_results(inst);
+            if results.len() != 1 || is_constant(opcode) {
+                continue;
+            }
+            if let Lattice::Constant(c) = values[results[0]] {
+                let ty = pos.func.dfg.value_type(results[0]);
+                replace_with_constant(&mut pos.func.dfg, inst, ty, c);
+            }
+        }
+    }
+
+    // Parameters are only replaced now, since folding branches looks conditions up through
+    // aliases, and the analysis knows nothing about the constants inserted here.
+    let mut pos = FuncCursor::new(func);
+    while let Some(ebb) = pos.next_ebb() {
+        if !executable[ebb] {
+            continue;
+        }
+
+        // Replace constant EBB parameters by constants at the top of the EBB, removing the
+        // corresponding arguments of all the branches to it.
+        if Some(ebb) != pos.func.layout.entry_block() {
+            let params = pos.func.dfg.ebb_params(ebb).to_vec();
+            for (index, &param) in params.iter().enumerate().rev() {
+                let constant = match values[param] {
+                    Lattice::Constant(c) => c,
+                    _ => continue,
+                };
+                for pred in cfg.pred_iter(ebb) {
+                    let mut args = pos.func.dfg[pred.inst].take_value_list().unwrap();
+                    let fixed = pos.func.dfg[pred.inst]
+                        .opcode()
+                        .constraints()
+                        .num_fixed_value_arguments();
+                    args.remove(fixed + index, &mut pos.func.dfg.value_lists);
+                    pos.func.dfg[pred.inst].put_value_list(args);
+                }
+                pos.func.dfg.remove_ebb_param(param);
+                pos.goto_first_insertion_point(ebb);
+                let ty = pos.func.dfg.value_type(param);
+                let value = materialize(&mut pos, ty, constant);
+                pos.func.dfg.change_to_alias(param, value);
+            }
+        }
+    }
+
+    // Delete the EBBs that can't be reached, like `eliminate_unreachable_code` does.
+    let mut pos = FuncCursor::new(func);
+    while let Some(ebb) = pos.next_ebb() {
+        if executable[ebb] {
+            continue;
+        }
+        pos.prev_ebb();
+        while let Some(inst) = pos.func.layout.first_inst(ebb) {
+            pos.func.layout.remove_inst(inst);
+        }
+        pos.func.layout.remove_ebb(ebb);
+    }
+
+    // A jump table branching to a removed EBB was only used by branches which have been folded,
+    // since all the entries of the others are executable.
+    for table in func.jump_tables.values_mut() {
+        if table.iter().any(|&ebb| !executable[ebb]) {
+            *table = JumpTableData::new();
+        }
+    }
+}
+
+/// Replace a branch whose outcome is known by a jump, or remove it.
+fn fold_branch(pos: &mut FuncCursor, inst: Inst, values: &SecondaryMap<Value, Lattice>) {
+    let condition = branch_condition(&pos.func.dfg, inst, |v| {
+        values[pos.func.dfg.resolve_aliases(v)]
+    });
+    let dest = match condition {
+        Condition::Taken(false) => {
+            pos.remove_inst_and_step_back();
+            return;
+        }
+        Condition::Taken(true) => {
+            if pos.func.dfg[inst].opcode() == Opcode::Jump {
+                return;
+            }
+            pos.func.dfg[inst].branch_destination().unwrap()
+        }
+        Condition::Index(index) => match pos.func.dfg.analyze_branch(inst) {
+            BranchInfo::Table(table, default) => pos.func.jump_tables[table]
+                .as_slice()
+                .get(index as usize)
+                .cloned()
+                .or(default)
+                .unwrap(),
+            _ => return,
+        },
+        Condition::Unknown | Condition::Varying => return,
+    };
+
+    let args = pos.func.dfg.inst_variable_args(inst).to_vec();
+    pos.func.dfg.replace(inst).jump(dest, &args);
+    // The rest of the EBB can't be reached anymore.
+    while let Some(next) = pos.func.layout.next_inst(inst) {
+        pos.func.layout.remove_inst(next);
+    }
+}
+
+fn is_constant(opcode: Opcode) -> bool {
+    match opcode {
+        Opcode::Iconst | Opcode::Bconst | Opcode::F32const | Opcode::F64const => true,
+        _ => false,
+    }
+}
+
+/// Insert an instruction computing the constant `c` of type `ty`.
+fn materialize(pos: &mut FuncCursor, ty: Type, c: u64) -> Value {
+    match ty {
+        types::F32 => pos.ins().f32const(Ieee32::with_bits(c as u32)),
+        types::F64 => pos.ins().f64const(Ieee64::with_bits(c)),
+        _ if ty.is_bool() => pos.ins().bconst(ty, c != 0),
+        _ => pos.ins().iconst(ty, sign_extend(c, ty)),
+    }
+}
+
+/// Replace `inst` by an instruction computing the constant `c` of type `ty`.
+fn replace_with_constant(dfg: &mut DataFlowGraph, inst: Inst, ty: Type, c: u64) {
+    match ty {
+        types::F32 => {
+            dfg.replace(inst).f32const(Ieee32::with_bits(c as u32));
+        }
+        types::F64 => {
+            dfg.replace(inst).f64const(Ieee64::with_bits(c));
+        }
+        _ if ty.is_bool() => {
+            dfg.replace(inst).bconst(ty, c != 0);
+        }
+        _ => {
+            dfg.replace(inst).iconst(ty, sign_extend(c, ty));
+        }
+    }
+}
@@ -3,9 +3,11 @@
 use crate::error::{Location, ParseError, ParseResult};
 use crate::isaspec;
 use crate::lexer::{LexError, Lexer, LocatedError, LocatedToken, Token};
+use crate::run_command::{Comparison, Invocation, RunCommand};
 use crate::sourcemap::SourceMap;
 use crate::testcommand::TestCommand;
 use crate::testfile::{Comment, Details, Feature, TestFile};
+use cranelift_codegen::data_value::DataValue;
 use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir;
 use cranelift_codegen::ir::entities::AnyEntity;
@@ -111,6 +113,17 @@ pub fn parse_test<'a>(text: &'a str, options: ParseOptions<'a>) -> ParseResult<T
     })
 }
 
+/// Parse the text of a comment attached to `func` as a run command.
+///
+/// Returns `None` if the comment isn't a run command at all, and an error if it is one but its
+/// invocation or expected values don't match the signature of `func`.
+pub fn parse_run_command(text: &str, func: &Function) -> ParseResult<Option<RunCommand>> {
+    let _tt = timing::parse_text();
+    // The comment text includes its leading `;`, which would make the lexer skip all of it.
+    let mut parser = Parser::new(text.trim_start_matches(';'));
+    parser.parse_run_command(func)
+}
+
 pub struct Parser<'a> {
     lex: Lexer<'a>,
 
@@ -202,6 +215,7 @@ impl<'a> Context<'a> {
                 name: ExternalName::testcase(""),
                 offset: Imm64::new(0),
                 colocated: false,
+                tls: false,
             });
         }
         self.function.global_values[gv] = data;
@@ -867,6 +881,144 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Parse a run command for `func`, or return `None` if this isn't one.
+    ///
+    /// run-command ::= "run"
+    ///               | "run" ":" invocation comparison value { "," value }
+    /// invocation  ::= name "(" [ value { "," value } ] ")"
+    /// comparison  ::= "==" | "!="
+    fn parse_run_command(&mut self, func: &Function) -> ParseResult<Option<RunCommand>> {
+        if self.token() != Some(Token::Identifier("run")) {
+            return Ok(None);
+        }
+        self.consume();
+
+        let sig = &func.signature;
+        match self.token() {
+            None => {
+                // The bare form only makes sense for functions returning a single boolean.
+                if !(sig.params.is_empty()
+                    && sig.returns.len() == 1
+                    && sig.returns[0].value_type.is_bool())
+                {
+                    return err!(
+                        self.loc,
+                        "a bare run command requires a signature like: () -> b1"
+                    );
+                }
+                return Ok(Some(RunCommand {
+                    invocation: Invocation {
+                        func: func.name.to_string(),
+                        args: Vec::new(),
+                    },
+                    comparison: Comparison::Equals,
+                    expected: vec![DataValue::B(true)],
+                }));
+            }
+            Some(Token::Colon) => self.consume(),
+            Some(_) => return Ok(None),
+        };
+
+        let name = self.parse_external_name()?;
+        if name != func.name {
+            return err!(
+                self.loc,
+                "run command invokes {}, but is attached to {}",
+                name,
+                func.name
+            );
+        }
+        self.match_token(Token::LPar, "expected '(' before the arguments")?;
+        let mut args = Vec::with_capacity(sig.params.len());
+        for (i, param) in sig.params.iter().enumerate() {
+            if i > 0 {
+                self.match_token(Token::Comma, "expected ',' between arguments")?;
+            }
+            args.push(self.parse_data_value(param.value_type)?);
+        }
+        self.match_token(
+            Token::RPar,
+            &format!("expected ')' after {} arguments", sig.params.len()),
+        )?;
+
+        let comparison = match self.token() {
+            Some(Token::Equal) => {
+                self.consume();
+                self.match_token(Token::Equal, "expected '=='")?;
+                Comparison::Equals
+            }
+            Some(Token::Not) => {
+                self.consume();
+                self.match_token(Token::Equal, "expected '!='")?;
+                Comparison::NotEquals
+            }
+            _ => return err!(self.loc, "expected a comparison: '==' or '!='"),
+        };
+
+        let mut expected = Vec::with_capacity(sig.returns.len());
+        for (i, ret) in sig.returns.iter().enumerate() {
+            if i > 0 {
+                self.match_token(Token::Comma, "expected ',' between expected values")?;
+            }
+            expected.push(self.parse_data_value(ret.value_type)?);
+        }
+        if self.token().is_some() {
+            return err!(
+                self.loc,
+                "expected {} values after the comparison",
+                sig.returns.len()
+            );
+        }
+
+        Ok(Some(RunCommand {
+            invocation: Invocation {
+                func: func.name.to_string(),
+                args,
+            },
+            comparison,
+            expected,
+        }))
+    }
+
+    /// Parse a literal value of type `ty`: an integer, a float, a boolean or a bracketed list of
+    /// lanes for vectors, e.g. `[1 2 3 4]`.
+    fn parse_data_value(&mut self, ty: Type) -> ParseResult<DataValue> {
+        if ty.is_vector() {
+            if ty.bits() != 128 {
+                return err!(self.loc, "only 128-bit vectors are supported, not {}", ty);
+            }
+            self.match_token(Token::LBracket, "expected '[' before the vector lanes")?;
+            let lane_type = ty.lane_type();
+            let mut bytes = [0; 16];
+            let lane_size = lane_type.bytes() as usize;
+            for lane in bytes.chunks_mut(lane_size) {
+                let value = self.parse_data_value(lane_type)?;
+                lane.copy_from_slice(&value.to_le_bytes(lane_type));
+            }
+            self.match_token(
+                Token::RBracket,
+                &format!("expected ']' after {} lanes", ty.lane_count()),
+            )?;
+            return Ok(DataValue::V128(bytes));
+        }
+
+        match ty {
+            F32 => Ok(self.match_ieee32("expected a 32-bit float")?.into()),
+            F64 => Ok(self.match_ieee64("expected a 64-bit float")?.into()),
+            _ if ty.is_bool() => Ok(self.match_bool("expected a boolean")?.into()),
+            _ if ty.is_int() => {
+                let imm: i64 = self.match_imm64("expected an integer")?.into();
+                // Accept both the signed and the unsigned range of narrow types.
+                let bits = ty.bits();
+                if bits < 64 && (imm < -(1 << (bits - 1)) || imm >= 1 << bits) {
+                    return err!(self.loc, "{} does not fit in {}", imm, ty);
+                }
+                Ok(DataValue::from_integer(i128::from(imm), ty).unwrap())
+            }
+            _ => err!(self.loc, "cannot parse a value of type {}", ty),
+        }
+    }
+
     /// Parse a list of test command passes specified in command line.
     pub fn parse_cmdline_passes(&mut self, passes: &'a [String]) -> Vec<TestCommand<'a>> {
         let mut list = Vec::new();
@@ -1399,12 +1551,14 @@ impl<'a> Parser<'a> {
             }
             "symbol" => {
                 let colocated = self.optional(Token::Identifier("colocated"));
+                let tls = self.optional(Token::Identifier("tls"));
                 let name = self.parse_external_name()?;
                 let offset = self.optional_offset_imm64()?;
                 GlobalValueData::Symbol {
                     name,
                     offset,
                     colocated,
+                    tls,
                 }
             }
             other => return err!(self.loc, "Unknown global value kind '{}'", other),
@@ -2617,6 +2771,32 @@ impl<'a> Parser<'a> {
                 }
             }
 
+            InstructionFormat::AtomicRmw => {
+                let flags = self.optional_memflags();
+                let op = self.match_enum("expected atomic read-modify-write operation")?;
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let arg = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicRmw {
+                    opcode,
+                    flags,
+                    op,
+                    args: [addr, arg],
+                }
+            }
+            InstructionFormat::AtomicCas => {
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let expected = self.match_value("expected SSA value operand")?;
+                self.match_token(Token::Comma, "expected ',' between operands")?;
+                let replacement = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicCas {
+                    opcode,
+                    flags,
+                    args: [addr, expected, replacement],
+                }
+            }
             InstructionFormat::StoreComplex => {
                 let flags = self.optional_memflags();
                 let src = self.match_value("expected SSA value operand")?;
@@ -3237,4 +3417,70 @@ mod tests {
         cannot_parse_as_uimm128!("1 2 3", I32X4);
         cannot_parse_as_uimm128!(" ", F32X4);
     }
+
+    #[test]
+    fn run_command() {
+        let func = Parser::new(
+            "function %add(i32, i8) -> i32, b1 {
+            ebb0(v0: i32, v1: i8):
+                v2 = bconst.b1 true
+                return v0, v2
+            }",
+        )
+        .parse_function(None)
+        .unwrap()
+        .0;
+        let parse = |text| parse_run_command(text, &func);
+
+        let command = parse("; run: %add(1, -2) == 3, true").unwrap().unwrap();
+        assert_eq!(command.to_string(), "%add(1, -2) == 3, true");
+        assert_eq!(
+            command.invocation.args,
+            [DataValue::I32(1), DataValue::I8(-2)]
+        );
+        assert_eq!(command.expected, [DataValue::I32(3), DataValue::B(true)]);
+        let command = parse("; run: %add(1, 255) != 0, false").unwrap().unwrap();
+        assert_eq!(command.comparison, Comparison::NotEquals);
+
+        // Comments that aren't run commands are ignored.
+        assert!(parse("; runs in constant time").unwrap().is_none());
+        assert!(parse("; run this").unwrap().is_none());
+
+        assert!(parse("; run").is_err());
+        assert!(parse("; run: %sub(1, 2) == 3, true").is_err());
+        assert!(parse("; run: %add(1) == 3, true").is_err());
+        assert!(parse("; run: %add(1, 256) == 3, true").is_err());
+        assert!(parse("; run: %add(1, 2) == 3").is_err());
+        assert!(parse("; run: %add(1, 2) == 3, true, 4").is_err());
+        assert!(parse("; run: %add(1, 2) = 3, true").is_err());
+    }
+
+    #[test]
+    fn run_command_values() {
+        let func = Parser::new(
+            "function %test(f32x4, i16x8) -> f64 {
+            ebb0(v0: f32x4, v1: i16x8):
+                v2 = f64const 0x1.0
+                return v2
+            }",
+        )
+        .parse_function(None)
+        .unwrap()
+        .0;
+        let command = parse_run_command(
+            "; run: %test([0x1.0 0.0 -0x1.0 +NaN], [1 2 3 4 5 6 7 -1]) == 0x1.8p1",
+            &func,
+        )
+        .unwrap()
+        .unwrap();
+        match command.invocation.args[1] {
+            DataValue::V128(bytes) => {
+                assert_eq!(bytes[..2], [1, 0]);
+                assert_eq!(bytes[14..], [0xff, 0xff]);
+            }
+            _ => panic!("expected a vector"),
+        }
+        assert_eq!(command.expected, [DataValue::from(3.0f64)]);
+        assert!(parse_run_command("; run: %test([0x1.0], [1]) == 0.0", &func).is_err());
+    }
 }
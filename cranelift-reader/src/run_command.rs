@@ -0,0 +1,157 @@
+//! Run commands.
+//!
+//! Functions in a `.clif` file can be annotated with *run commands*: comments that describe a call
+//! to the function and the values it is expected to return. The general syntax is:
+//!
+//! <pre>
+//! ; run: %<i>fn</i>(<i>arg</i>, ...) == <i>value</i>, ...
+//! ; run: %<i>fn</i>(<i>arg</i>, ...) != <i>value</i>, ...
+//! ; run
+//! </pre>
+//!
+//! The bare `; run` form is shorthand for `; run: %fn() == true`, and can only be used on
+//! functions with a signature like `() -> b1`.
+//!
+//! The parser checks the arguments and expected values against the signature of the function; it
+//! is up to the caller to actually invoke it, see `RunCommand::run`.
+
+use cranelift_codegen::data_value::DataValue;
+use std::fmt::{self, Display, Formatter};
+
+/// A run command appearing in a test file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RunCommand {
+    /// The call to make.
+    pub invocation: Invocation,
+    /// How to compare the returned values to `expected`.
+    pub comparison: Comparison,
+    /// The values the call is expected to return, one per return value of the function.
+    pub expected: Vec<DataValue>,
+}
+
+/// A call to a function, with its arguments.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Invocation {
+    /// The name of the function to call, including the leading `%`.
+    pub func: String,
+    /// The arguments to pass, one per parameter of the function.
+    pub args: Vec<DataValue>,
+}
+
+/// A comparison between the returned and expected values of a run command.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Comparison {
+    /// The returned values must all equal the expected ones: `==`.
+    Equals,
+    /// At least one returned value must differ from the expected one: `!=`.
+    NotEquals,
+}
+
+impl RunCommand {
+    /// Run the command by calling `invoke` with the arguments of the invocation and comparing its
+    /// results to the expected values.
+    ///
+    /// Errors returned by `invoke` are passed through unchanged.
+    pub fn run<F>(&self, invoke: F) -> Result<(), String>
+    where
+        F: FnOnce(&[DataValue]) -> Result<Vec<DataValue>, String>,
+    {
+        let actual = invoke(&self.invocation.args)?;
+        let matches = actual == self.expected;
+        if matches == (self.comparison == Comparison::Equals) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Failed test: {}, actual: {}",
+                self,
+                DisplayValues(&actual)
+            ))
+        }
+    }
+}
+
+impl Display for RunCommand {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.invocation,
+            self.comparison,
+            DisplayValues(&self.expected)
+        )
+    }
+}
+
+impl Display for Invocation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}(", self.func)?;
+        for (i, arg) in self.args.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Comparison::Equals => write!(f, "=="),
+            Comparison::NotEquals => write!(f, "!="),
+        }
+    }
+}
+
+/// Display a comma-separated list of values.
+struct DisplayValues<'a>(&'a [DataValue]);
+
+impl<'a> Display for DisplayValues<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, value) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(comparison: Comparison, expected: i32) -> RunCommand {
+        RunCommand {
+            invocation: Invocation {
+                func: "%add".to_string(),
+                args: vec![DataValue::I32(1), DataValue::I32(2)],
+            },
+            comparison,
+            expected: vec![DataValue::I32(expected)],
+        }
+    }
+
+    fn add(args: &[DataValue]) -> Result<Vec<DataValue>, String> {
+        match args {
+            [DataValue::I32(x), DataValue::I32(y)] => Ok(vec![DataValue::I32(x + y)]),
+            _ => Err("bad arguments".to_string()),
+        }
+    }
+
+    #[test]
+    fn run() {
+        assert_eq!(command(Comparison::Equals, 3).run(add), Ok(()));
+        assert_eq!(command(Comparison::NotEquals, 4).run(add), Ok(()));
+        assert_eq!(
+            command(Comparison::Equals, 4).run(add),
+            Err("Failed test: %add(1, 2) == 4, actual: 3".to_string())
+        );
+        assert_eq!(
+            command(Comparison::Equals, 3).run(|_| Err("trap".to_string())),
+            Err("trap".to_string())
+        );
+    }
+}
@@ -1,22 +1,28 @@
 //! Defines `SimpleJITBackend`.
 
+use crate::lazy::{self, LazyCompiler};
 use crate::memory::Memory;
+use crate::perf::{self, Profiler};
 use cranelift_codegen::binemit::{
-    Addend, CodeOffset, NullTrapSink, Reloc, RelocSink, Stackmap, StackmapSink,
+    Addend, CodeInfo, CodeOffset, FrameUnwindKind, FrameUnwindOffset, FrameUnwindSink,
+    NullTrapSink, Reloc, RelocSink, Stackmap, StackmapSink,
 };
+use cranelift_codegen::entity::SecondaryMap;
 use cranelift_codegen::isa::TargetIsa;
-use cranelift_codegen::{self, ir, settings};
+use cranelift_codegen::{self, ir, settings, Context};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleNamespace,
-    ModuleResult,
+    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleError,
+    ModuleNamespace, ModuleResult,
 };
 use cranelift_native;
 #[cfg(not(windows))]
 use libc;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::ffi::CString;
-use std::io::Write;
+use std::mem;
 use std::ptr;
+use std::rc::Rc;
 use target_lexicon::PointerWidth;
 #[cfg(windows)]
 use winapi;
@@ -24,12 +30,17 @@ use winapi;
 const EXECUTABLE_DATA_ALIGNMENT: u8 = 0x10;
 const WRITABLE_DATA_ALIGNMENT: u8 = 0x8;
 const READONLY_DATA_ALIGNMENT: u8 = 0x1;
+const UNWIND_INFO_ALIGNMENT: usize = 0x8;
 
 /// A builder for `SimpleJITBackend`.
 pub struct SimpleJITBuilder {
     isa: Box<dyn TargetIsa>,
     symbols: HashMap<String, *const u8>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
+    hotswap: bool,
+    lazy_compile: Option<Box<dyn FnMut(FuncId) -> ir::Function>>,
+    perf_map: bool,
+    jitdump: bool,
 }
 
 impl SimpleJITBuilder {
@@ -70,6 +81,10 @@ impl SimpleJITBuilder {
             isa,
             symbols,
             libcall_names,
+            hotswap: false,
+            lazy_compile: None,
+            perf_map: ::std::env::var_os("PERF_BUILDID_DIR").is_some(),
+            jitdump: false,
         }
     }
 
@@ -108,6 +123,63 @@ impl SimpleJITBuilder {
         }
         self
     }
+
+    /// Enable or disable hot code replacement, which is disabled by default.
+    ///
+    /// With hotswapping enabled, all references to a function defined in the module go through a
+    /// stub which jumps to the function's current body. `Module::redefine_function` then makes
+    /// existing code call the new body once it is finalized, and `Module::free_function` releases
+    /// the memory of a function's body. This costs an indirect jump per call, and is only
+    /// supported on x86: elsewhere, defining a function returns an error.
+    pub fn hotswap(&mut self, enabled: bool) -> &mut Self {
+        self.hotswap = enabled;
+        self
+    }
+
+    /// Register the callback which produces the IR of functions defined with
+    /// `Module::define_function_lazily`.
+    ///
+    /// Such functions are only compiled when they are first called: their stub initially jumps to
+    /// a trampoline which invokes `compile` with the function's id, compiles and links the
+    /// returned function, and then calls it. Later calls go directly to the compiled function.
+    /// If the function can't be compiled, the call traps instead, and the error is reported by
+    /// `Module::take_lazy_compilation_errors`.
+    /// This is only supported on x86: elsewhere, `Module::define_function_lazily` returns an
+    /// error.
+    ///
+    /// `compile` is invoked while the JIT'd code runs, so it mustn't use the `Module`, except for
+    /// building IR with methods like `declare_func_in_func`, and calling lazily compiled functions
+    /// which aren't compiled yet from it traps. Lazily compiled functions must not be called from
+    /// multiple threads at once.
+    pub fn lazy_compilation<F>(&mut self, compile: F) -> &mut Self
+    where
+        F: FnMut(FuncId) -> ir::Function + 'static,
+    {
+        self.lazy_compile = Some(Box::new(compile));
+        self
+    }
+
+    /// Enable or disable writing the address range and name of each finalized function to
+    /// `/tmp/perf-<pid>.map`, where Linux `perf` looks for the symbols of JIT'd code.
+    ///
+    /// This is enabled by default when the `PERF_BUILDID_DIR` environment variable is set, as it
+    /// is when running under `perf record`. It has no effect on other platforms.
+    pub fn perf_map(&mut self, enabled: bool) -> &mut Self {
+        self.perf_map = enabled;
+        self
+    }
+
+    /// Enable or disable writing each finalized function, including its code and the `SourceLoc`s
+    /// of its instructions, to a `jit-<pid>.dump` file in the current directory, which is
+    /// disabled by default.
+    ///
+    /// `perf inject --jit` uses this file to let `perf report` and `perf annotate` look into JIT'd
+    /// code; record with `perf record -k 1`. As `SourceLoc`s are opaque, they are reported as line
+    /// numbers in a file named after the function. It has no effect on other platforms.
+    pub fn jitdump(&mut self, enabled: bool) -> &mut Self {
+        self.jitdump = enabled;
+        self
+    }
 }
 
 /// A `SimpleJITBackend` implements `Backend` and emits code and data into memory where it can be
@@ -115,12 +187,26 @@ impl SimpleJITBuilder {
 ///
 /// See the `SimpleJITBuilder` for a convenient way to construct `SimpleJITBackend` instances.
 pub struct SimpleJITBackend {
-    isa: Box<dyn TargetIsa>,
+    isa: Rc<dyn TargetIsa>,
     symbols: HashMap<String, *const u8>,
-    libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
+    libcall_names: Rc<dyn Fn(ir::LibCall) -> String>,
     code_memory: Memory,
     readonly_memory: Memory,
     writable_memory: Memory,
+    hotswap: bool,
+    function_stubs: SecondaryMap<FuncId, Option<FunctionStub>>,
+    /// The state of lazy compilation, if a callback was registered for it. Lazy trampolines refer
+    /// to it, so it lives at a fixed address.
+    lazy: Option<Rc<RefCell<LazyCompiler>>>,
+    profiler: Rc<RefCell<Profiler>>,
+}
+
+/// The stable entry point of a function when hotswapping is enabled: code which jumps to the
+/// address stored in `slot`, which is the function's current body.
+#[derive(Clone, Copy)]
+struct FunctionStub {
+    code: *const u8,
+    slot: *mut *const u8,
 }
 
 /// A record of a relocation to perform.
@@ -139,9 +225,16 @@ struct StackmapRecord {
 }
 
 pub struct SimpleJITCompiledFunction {
-    code: *mut u8,
-    size: usize,
+    pub(crate) code: *mut u8,
+    pub(crate) size: usize,
     relocs: Vec<RelocRecord>,
+    /// The address other code calls: the function's stub if hotswapping is enabled or the
+    /// function is compiled lazily, otherwise `code`.
+    entry: *const u8,
+    /// The unwind information registered for the function, if any.
+    pub(crate) unwind_info: Option<*const u8>,
+    /// The offsets at which the function's source location changes, if the profiler needs them.
+    pub(crate) srclocs: Vec<(CodeOffset, ir::SourceLoc)>,
 }
 
 pub struct SimpleJITCompiledData {
@@ -152,10 +245,7 @@ pub struct SimpleJITCompiledData {
 
 impl SimpleJITBackend {
     fn lookup_symbol(&self, name: &str) -> *const u8 {
-        match self.symbols.get(name) {
-            Some(&ptr) => ptr,
-            None => lookup_with_dlsym(name),
-        }
+        lookup_symbol(&self.symbols, name)
     }
 
     fn get_definition(
@@ -168,7 +258,7 @@ impl SimpleJITBackend {
                 if namespace.is_function(name) {
                     let (def, name_str, _signature) = namespace.get_function_definition(&name);
                     match def {
-                        Some(compiled) => compiled.code,
+                        Some(compiled) => compiled.entry,
                         None => self.lookup_symbol(name_str),
                     }
                 } else {
@@ -186,6 +276,53 @@ impl SimpleJITBackend {
             _ => panic!("invalid ExternalName {}", name),
         }
     }
+
+    /// Return the stub of function `id`, creating it if it doesn't exist yet. Its slot is null
+    /// until the function is finalized.
+    ///
+    /// Stubs, and thus hotswapping and lazy compilation, are only supported on x86.
+    fn function_stub(&mut self, id: FuncId) -> ModuleResult<FunctionStub> {
+        if let Some(stub) = self.function_stubs[id] {
+            return Ok(stub);
+        }
+        if self.isa.name() != "x86" {
+            return Err(ModuleError::Backend(format!(
+                "SimpleJIT doesn't support hotswapping or lazy compilation on {}",
+                self.isa.name()
+            )));
+        }
+
+        let slot = self
+            .writable_memory
+            .allocate(mem::size_of::<*const u8>(), WRITABLE_DATA_ALIGNMENT)
+            .expect("TODO: handle OOM etc.") as *mut *const u8;
+        unsafe { slot.write(ptr::null()) };
+
+        let mut stub = Vec::new();
+        match self.isa.triple().pointer_width().unwrap() {
+            PointerWidth::U16 => unreachable!("x86 has no 16-bit pointers"),
+            PointerWidth::U32 => {
+                // jmp *slot
+                stub.extend_from_slice(&[0xff, 0x25]);
+                stub.extend_from_slice(&(slot as u32).to_le_bytes());
+            }
+            PointerWidth::U64 => {
+                // movabs $slot, %r11; jmp *(%r11)
+                stub.extend_from_slice(&[0x49, 0xbb]);
+                stub.extend_from_slice(&(slot as u64).to_le_bytes());
+                stub.extend_from_slice(&[0x41, 0xff, 0x23]);
+            }
+        }
+        let code = self
+            .code_memory
+            .allocate(stub.len(), EXECUTABLE_DATA_ALIGNMENT)
+            .expect("TODO: handle OOM etc.");
+        unsafe { ptr::copy_nonoverlapping(stub.as_ptr(), code, stub.len()) };
+
+        let stub = FunctionStub { code, slot };
+        self.function_stubs[id] = Some(stub);
+        Ok(stub)
+    }
 }
 
 impl<'simple_jit_backend> Backend for SimpleJITBackend {
@@ -209,13 +346,36 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
 
     /// Create a new `SimpleJITBackend`.
     fn new(builder: SimpleJITBuilder) -> Self {
+        let isa = Rc::from(builder.isa);
+        let libcall_names = Rc::from(builder.libcall_names);
+        let symbols = builder.symbols;
+        let hotswap = builder.hotswap;
+        let profiler = Rc::new(RefCell::new(Profiler::new(
+            &*isa,
+            builder.perf_map,
+            builder.jitdump,
+        )));
+        let lazy = builder.lazy_compile.map(|compile| {
+            Rc::new(RefCell::new(LazyCompiler::new(
+                Rc::clone(&isa),
+                symbols.clone(),
+                Rc::clone(&libcall_names),
+                hotswap,
+                Rc::clone(&profiler),
+                compile,
+            )))
+        });
         Self {
-            isa: builder.isa,
-            symbols: builder.symbols,
-            libcall_names: builder.libcall_names,
+            isa,
+            symbols,
+            libcall_names,
             code_memory: Memory::new(),
             readonly_memory: Memory::new(),
             writable_memory: Memory::new(),
+            hotswap,
+            function_stubs: SecondaryMap::new(),
+            lazy,
+            profiler,
         }
     }
 
@@ -223,76 +383,117 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         &*self.isa
     }
 
-    fn declare_function(&mut self, _id: FuncId, _name: &str, _linkage: Linkage) {
-        // Nothing to do.
+    fn declare_function(&mut self, id: FuncId, name: &str, _linkage: Linkage) {
+        if let Some(lazy) = &self.lazy {
+            lazy.borrow_mut().declare_function(id, name);
+        }
     }
 
     fn declare_data(
         &mut self,
-        _id: DataId,
-        _name: &str,
+        id: DataId,
+        name: &str,
         _linkage: Linkage,
         _writable: bool,
+        tls: bool,
         _align: Option<u8>,
-    ) {
-        // Nothing to do.
+    ) -> ModuleResult<()> {
+        if tls {
+            return Err(ModuleError::Backend(
+                "SimpleJIT doesn't yet support TLS".to_owned(),
+            ));
+        }
+        if let Some(lazy) = &self.lazy {
+            lazy.borrow_mut().declare_data(id, name);
+        }
+        Ok(())
     }
 
     fn define_function(
         &mut self,
-        _id: FuncId,
-        name: &str,
+        id: FuncId,
+        _name: &str,
         ctx: &cranelift_codegen::Context,
         _namespace: &ModuleNamespace<Self>,
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction> {
-        let size = code_size as usize;
-        let ptr = self
-            .code_memory
-            .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
-            .expect("TODO: handle OOM etc.");
-
-        if cfg!(target_os = "linux") && ::std::env::var_os("PERF_BUILDID_DIR").is_some() {
-            let mut map_file = ::std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(format!("/tmp/perf-{}.map", ::std::process::id()))
-                .unwrap();
-
-            let _ = writeln!(map_file, "{:x} {:x} {}", ptr as usize, code_size, name);
+        let stub = if self.hotswap {
+            Some(self.function_stub(id)?)
+        } else {
+            None
+        };
+        let mut func = emit_function(
+            &*self.isa,
+            &mut self.code_memory,
+            self.hotswap,
+            self.profiler.borrow().wants_srclocs(),
+            ctx,
+            code_size,
+        );
+        if let Some(stub) = stub {
+            func.entry = stub.code;
+        }
+        if let Some(lazy) = &self.lazy {
+            lazy.borrow_mut().define_function(id, func.entry);
         }
+        Ok(func)
+    }
 
-        let mut reloc_sink = SimpleJITRelocSink::new();
-        // Ignore traps for now. For now, frontends should just avoid generating code
-        // that traps.
-        let mut trap_sink = NullTrapSink {};
-        let mut stackmap_sink = SimpleJITStackmapSink::new();
-        unsafe {
-            ctx.emit_to_memory(
-                &*self.isa,
-                ptr,
-                &mut reloc_sink,
-                &mut trap_sink,
-                &mut stackmap_sink,
-            )
+    fn define_function_lazily(
+        &mut self,
+        id: FuncId,
+        _name: &str,
+        signature: &ir::Signature,
+    ) -> ModuleResult<Self::CompiledFunction> {
+        let lazy = match &self.lazy {
+            Some(lazy) => Rc::clone(lazy),
+            None => {
+                return Err(ModuleError::Backend(
+                    "SimpleJIT needs a callback registered with `lazy_compilation` to compile \
+                     functions lazily"
+                        .to_owned(),
+                ))
+            }
         };
 
-        Ok(Self::CompiledFunction {
-            code: ptr,
-            size,
-            relocs: reloc_sink.relocs,
-        })
+        let stub = self.function_stub(id)?;
+        let trampoline = lazy::trampoline(&*self.isa, &lazy, id, signature);
+        let mut ctx = Context::for_function(trampoline);
+        let CodeInfo { total_size, .. } =
+            ctx.compile(&*self.isa).map_err(ModuleError::Compilation)?;
+        let mut func = emit_function(
+            &*self.isa,
+            &mut self.code_memory,
+            self.hotswap,
+            self.profiler.borrow().wants_srclocs(),
+            &ctx,
+            total_size,
+        );
+        func.entry = stub.code;
+        lazy.borrow_mut()
+            .define_function_lazily(id, stub.code, stub.slot);
+        Ok(func)
+    }
+
+    fn take_lazy_compilation_errors(&mut self) -> Vec<(FuncId, ModuleError)> {
+        match &self.lazy {
+            Some(lazy) => lazy.borrow_mut().take_errors(),
+            None => Vec::new(),
+        }
     }
 
     fn define_data(
         &mut self,
-        _id: DataId,
+        id: DataId,
         _name: &str,
         writable: bool,
+        tls: bool,
         align: Option<u8>,
         data: &DataContext,
         _namespace: &ModuleNamespace<Self>,
     ) -> ModuleResult<Self::CompiledData> {
+        assert!(!tls, "SimpleJIT doesn't yet support TLS");
+
         let &DataDescription {
             ref init,
             ref function_decls,
@@ -324,6 +525,9 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
                 unsafe { ptr::copy_nonoverlapping(src, storage, size) };
             }
         }
+        if let Some(lazy) = &self.lazy {
+            lazy.borrow_mut().define_data(id, storage);
+        }
 
         let reloc = match self.isa.triple().pointer_width().unwrap() {
             PointerWidth::U16 => panic!(),
@@ -376,56 +580,51 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
 
     fn finalize_function(
         &mut self,
-        _id: FuncId,
+        id: FuncId,
         func: &Self::CompiledFunction,
         namespace: &ModuleNamespace<Self>,
     ) -> Self::FinalizedFunction {
-        use std::ptr::write_unaligned;
-
-        for &RelocRecord {
-            reloc,
-            offset,
-            ref name,
-            addend,
-        } in &func.relocs
-        {
-            let ptr = func.code;
-            debug_assert!((offset as usize) < func.size);
-            let at = unsafe { ptr.offset(offset as isize) };
-            let base = self.get_definition(namespace, name);
-            // TODO: Handle overflow.
-            let what = unsafe { base.offset(addend as isize) };
-            match reloc {
-                Reloc::Abs4 => {
-                    // TODO: Handle overflow.
-                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
-                    unsafe {
-                        write_unaligned(at as *mut u32, what as u32)
-                    };
-                }
-                Reloc::Abs8 => {
-                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
-                    unsafe {
-                        write_unaligned(at as *mut u64, what as u64)
-                    };
-                }
-                Reloc::X86PCRel4 | Reloc::X86CallPCRel4 => {
-                    // TODO: Handle overflow.
-                    let pcrel = ((what as isize) - (at as isize)) as i32;
-                    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
-                    unsafe {
-                        write_unaligned(at as *mut i32, pcrel)
-                    };
-                }
-                Reloc::X86GOTPCRel4 | Reloc::X86CallPLTRel4 => panic!("unexpected PIC relocation"),
-                _ => unimplemented!(),
-            }
+        unsafe { relocate_function(func, |name| self.get_definition(namespace, name)) };
+        let name = &namespace
+            .get_function_decl(&ir::ExternalName::user(0, id.as_u32()))
+            .name;
+        self.profiler
+            .borrow_mut()
+            .record_function(name, func.code, func.size, &func.srclocs);
+        if let Some(stub) = self.function_stubs[id] {
+            // Route calls through the stub to the new body.
+            unsafe { stub.slot.write(func.code) };
         }
-        func.code
+        func.entry
     }
 
     fn get_finalized_function(&self, func: &Self::CompiledFunction) -> Self::FinalizedFunction {
-        func.code
+        func.entry
+    }
+
+    fn free_function(&mut self, id: FuncId, func: &Self::CompiledFunction) -> ModuleResult<()> {
+        if !self.hotswap {
+            return Err(ModuleError::Backend(
+                "SimpleJIT can only free functions with hotswapping enabled".to_owned(),
+            ));
+        }
+        let stub = self.function_stubs[id].expect("hotswappable functions have a stub");
+        unsafe {
+            // Calls through the stub fault until the function is defined and finalized again,
+            // rather than running freed code.
+            stub.slot.write(ptr::null());
+            #[cfg(not(windows))]
+            {
+                if let Some(unwind_info) = func.unwind_info {
+                    __deregister_frame(unwind_info);
+                }
+            }
+            self.code_memory.free(func.code);
+        }
+        if let Some(lazy) = &self.lazy {
+            lazy.borrow_mut().free_function(id);
+        }
+        Ok(())
     }
 
     fn finalize_data(
@@ -488,6 +687,179 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
     fn finish(self) {}
 }
 
+/// Emit the compiled function in `ctx` into `memory`, along with its unwind information. With
+/// `standalone`, the function gets pages of its own, so that it can be freed. With `srclocs`, the
+/// source locations of its code are collected for the profiler.
+pub(crate) fn emit_function(
+    isa: &dyn TargetIsa,
+    memory: &mut Memory,
+    standalone: bool,
+    srclocs: bool,
+    ctx: &Context,
+    code_size: u32,
+) -> SimpleJITCompiledFunction {
+    let size = code_size as usize;
+
+    // The unwind information is placed right after the code, which keeps its PC-relative
+    // references to the function in range.
+    let mut unwind_sink = SimpleJITUnwindSink::default();
+    if cfg!(not(windows)) {
+        ctx.emit_unwind_info(isa, FrameUnwindKind::Libunwind, &mut unwind_sink);
+    }
+    let unwind_offset = (size + UNWIND_INFO_ALIGNMENT - 1) & !(UNWIND_INFO_ALIGNMENT - 1);
+    let alloc_size = if unwind_sink.data.is_empty() {
+        size
+    } else {
+        // Leave room for the zero terminator.
+        unwind_offset + unwind_sink.data.len() + 4
+    };
+
+    let ptr = if standalone {
+        memory.allocate_standalone(alloc_size)
+    } else {
+        memory.allocate(alloc_size, EXECUTABLE_DATA_ALIGNMENT)
+    }
+    .expect("TODO: handle OOM etc.");
+
+    let mut reloc_sink = SimpleJITRelocSink::new();
+    // Ignore traps for now. For now, frontends should just avoid generating code
+    // that traps.
+    let mut trap_sink = NullTrapSink {};
+    let mut stackmap_sink = SimpleJITStackmapSink::new();
+    unsafe {
+        ctx.emit_to_memory(
+            isa,
+            ptr,
+            &mut reloc_sink,
+            &mut trap_sink,
+            &mut stackmap_sink,
+        )
+    };
+
+    #[cfg(not(windows))]
+    let unwind_info = if unwind_sink.data.is_empty() {
+        None
+    } else {
+        Some(unsafe { register_unwind_info(ptr, unwind_offset, &unwind_sink) })
+    };
+    #[cfg(windows)]
+    let unwind_info = None;
+
+    let srclocs = if srclocs {
+        perf::function_srclocs(isa, &ctx.func)
+    } else {
+        Vec::new()
+    };
+
+    SimpleJITCompiledFunction {
+        code: ptr,
+        size,
+        relocs: reloc_sink.relocs,
+        entry: ptr,
+        unwind_info,
+        srclocs,
+    }
+}
+
+/// Perform the relocations of `func`, with `get_address` providing the addresses of the names
+/// they refer to.
+pub(crate) unsafe fn relocate_function<F>(func: &SimpleJITCompiledFunction, get_address: F)
+where
+    F: Fn(&ir::ExternalName) -> *const u8,
+{
+    use std::ptr::write_unaligned;
+
+    for &RelocRecord {
+        reloc,
+        offset,
+        ref name,
+        addend,
+    } in &func.relocs
+    {
+        let ptr = func.code;
+        debug_assert!((offset as usize) < func.size);
+        let at = ptr.offset(offset as isize);
+        let base = get_address(name);
+        // TODO: Handle overflow.
+        let what = base.offset(addend as isize);
+        match reloc {
+            Reloc::Abs4 => {
+                // TODO: Handle overflow.
+                #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                write_unaligned(at as *mut u32, what as u32);
+            }
+            Reloc::Abs8 => {
+                #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                write_unaligned(at as *mut u64, what as u64);
+            }
+            Reloc::X86PCRel4 | Reloc::X86CallPCRel4 => {
+                // TODO: Handle overflow.
+                let pcrel = ((what as isize) - (at as isize)) as i32;
+                #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                write_unaligned(at as *mut i32, pcrel);
+            }
+            Reloc::X86GOTPCRel4 | Reloc::X86CallPLTRel4 => panic!("unexpected PIC relocation"),
+            _ => unimplemented!(),
+        }
+    }
+}
+
+/// Look up `name` in `symbols`, falling back to the symbols of the current process.
+pub(crate) fn lookup_symbol(symbols: &HashMap<String, *const u8>, name: &str) -> *const u8 {
+    match symbols.get(name) {
+        Some(&ptr) => ptr,
+        None => lookup_with_dlsym(name),
+    }
+}
+
+#[cfg(not(windows))]
+extern "C" {
+    // libunwind's (and libgcc's) entry points for registering frames of dynamically generated
+    // code.
+    fn __register_frame(fde: *const u8);
+    pub(crate) fn __deregister_frame(fde: *const u8);
+}
+
+/// Copy the unwind information of the function at `code` to `code + offset`, resolve its
+/// references to the function, and register it with the system unwinder.
+///
+/// Returns the registered pointer, which must be passed to `__deregister_frame` before the
+/// function's code memory is freed.
+#[cfg(not(windows))]
+unsafe fn register_unwind_info(
+    code: *mut u8,
+    offset: usize,
+    unwind: &SimpleJITUnwindSink,
+) -> *const u8 {
+    use std::ptr::write_unaligned;
+
+    let start = code.add(offset);
+    ptr::copy_nonoverlapping(unwind.data.as_ptr(), start, unwind.data.len());
+    // Terminate the frame table with a zero-length entry.
+    ptr::write_bytes(start.add(unwind.data.len()), 0, 4);
+
+    for &(reloc, offset) in &unwind.relocs {
+        let at = start.add(offset);
+        match reloc {
+            Reloc::X86PCRel4 => {
+                let pcrel = ((code as isize) - (at as isize)) as i32;
+                #[cfg_attr(feature = "cargo-clippy", allow(clippy::cast_ptr_alignment))]
+                write_unaligned(at as *mut i32, pcrel);
+            }
+            _ => unimplemented!(),
+        }
+    }
+
+    // libunwind's `__register_frame` takes a single FDE, while libgcc's takes a whole frame table.
+    let registered = if cfg!(target_os = "macos") {
+        start.add(unwind.entry_offset)
+    } else {
+        start
+    };
+    __register_frame(registered);
+    registered
+}
+
 #[cfg(not(windows))]
 fn lookup_with_dlsym(name: &str) -> *const u8 {
     let c_str = CString::new(name).unwrap();
@@ -586,6 +958,35 @@ impl RelocSink for SimpleJITRelocSink {
     }
 }
 
+#[derive(Default)]
+struct SimpleJITUnwindSink {
+    data: Vec<u8>,
+    relocs: Vec<(Reloc, FrameUnwindOffset)>,
+    entry_offset: FrameUnwindOffset,
+}
+
+impl FrameUnwindSink for SimpleJITUnwindSink {
+    fn offset(&self) -> FrameUnwindOffset {
+        self.data.len()
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.data.extend_from_slice(b);
+    }
+
+    fn reserve(&mut self, len: usize) {
+        self.data.reserve(len)
+    }
+
+    fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+        self.relocs.push((r, off));
+    }
+
+    fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+        self.entry_offset = off;
+    }
+}
+
 struct SimpleJITStackmapSink {
     pub stackmaps: Vec<StackmapRecord>,
 }
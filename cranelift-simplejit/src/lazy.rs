@@ -0,0 +1,347 @@
+//! Compilation of functions on their first call.
+
+#[cfg(not(windows))]
+use crate::backend::__deregister_frame;
+use crate::backend::{emit_function, lookup_symbol, relocate_function, SimpleJITCompiledFunction};
+use crate::memory::Memory;
+use crate::perf::Profiler;
+use cranelift_codegen::binemit::CodeInfo;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::entity::SecondaryMap;
+use cranelift_codegen::ir::{types, AbiParam, InstBuilder};
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::{ir, Context};
+use cranelift_module::{DataId, FuncId, ModuleError, ModuleResult};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::{mem, ptr};
+
+/// The name of a function or data object, and its address if it's defined.
+#[derive(Clone, Default)]
+struct Symbol {
+    name: String,
+    address: Option<*const u8>,
+}
+
+/// A function defined with `Module::define_function_lazily`.
+struct LazyFunction {
+    /// The slot of the function's stub, which initially holds the address of its trampoline.
+    slot: *mut *const u8,
+    /// The function's body, once it has been compiled.
+    body: Option<SimpleJITCompiledFunction>,
+}
+
+/// Everything needed to compile and link a function while the JIT'd code is running, when the
+/// `Module` isn't available.
+pub(crate) struct LazyCompiler {
+    isa: Rc<dyn TargetIsa>,
+    symbols: HashMap<String, *const u8>,
+    libcall_names: Rc<dyn Fn(ir::LibCall) -> String>,
+    hotswap: bool,
+    profiler: Rc<RefCell<Profiler>>,
+    compile: Box<dyn FnMut(FuncId) -> ir::Function>,
+    code_memory: Memory,
+    functions: SecondaryMap<FuncId, Symbol>,
+    data_objects: SecondaryMap<DataId, Symbol>,
+    lazy_functions: HashMap<FuncId, LazyFunction>,
+    /// The errors compiling functions ran into, which are reported through the `Module`.
+    errors: Vec<(FuncId, ModuleError)>,
+}
+
+impl LazyCompiler {
+    pub fn new(
+        isa: Rc<dyn TargetIsa>,
+        symbols: HashMap<String, *const u8>,
+        libcall_names: Rc<dyn Fn(ir::LibCall) -> String>,
+        hotswap: bool,
+        profiler: Rc<RefCell<Profiler>>,
+        compile: Box<dyn FnMut(FuncId) -> ir::Function>,
+    ) -> Self {
+        Self {
+            isa,
+            symbols,
+            libcall_names,
+            hotswap,
+            profiler,
+            compile,
+            code_memory: Memory::new(),
+            functions: SecondaryMap::new(),
+            data_objects: SecondaryMap::new(),
+            lazy_functions: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn declare_function(&mut self, id: FuncId, name: &str) {
+        self.functions[id].name = name.to_owned();
+    }
+
+    pub fn declare_data(&mut self, id: DataId, name: &str) {
+        self.data_objects[id].name = name.to_owned();
+    }
+
+    /// Record that function `id` is defined, and is called at `entry`.
+    pub fn define_function(&mut self, id: FuncId, entry: *const u8) {
+        self.functions[id].address = Some(entry);
+    }
+
+    /// Record that function `id` is compiled lazily, and is called at `entry`, a stub which jumps
+    /// through `slot`.
+    pub fn define_function_lazily(&mut self, id: FuncId, entry: *const u8, slot: *mut *const u8) {
+        self.functions[id].address = Some(entry);
+        self.lazy_functions
+            .insert(id, LazyFunction { slot, body: None });
+    }
+
+    pub fn define_data(&mut self, id: DataId, storage: *const u8) {
+        self.data_objects[id].address = Some(storage);
+    }
+
+    /// Forget the definition of function `id`, and free its lazily compiled body, if any.
+    pub fn free_function(&mut self, id: FuncId) {
+        self.functions[id].address = None;
+        if let Some(LazyFunction {
+            body: Some(body), ..
+        }) = self.lazy_functions.remove(&id)
+        {
+            unsafe {
+                #[cfg(not(windows))]
+                {
+                    if let Some(unwind_info) = body.unwind_info {
+                        __deregister_frame(unwind_info);
+                    }
+                }
+                self.code_memory.free(body.code);
+            }
+        }
+    }
+
+    /// Take the errors compiling functions ran into since the last call.
+    pub fn take_errors(&mut self) -> Vec<(FuncId, ModuleError)> {
+        mem::replace(&mut self.errors, Vec::new())
+    }
+
+    /// Compile and link function `id`, make its stub jump to it, and return its address.
+    fn compile_function(&mut self, id: FuncId) -> ModuleResult<*const u8> {
+        let mut ctx = Context::for_function((self.compile)(id));
+        let CodeInfo { total_size, .. } =
+            ctx.compile(&*self.isa).map_err(ModuleError::Compilation)?;
+        let body = emit_function(
+            &*self.isa,
+            &mut self.code_memory,
+            self.hotswap,
+            self.profiler.borrow().wants_srclocs(),
+            &ctx,
+            total_size,
+        );
+        unsafe { relocate_function(&body, |name| self.get_address(name)) };
+        self.profiler.borrow_mut().record_function(
+            &self.functions[id].name,
+            body.code,
+            body.size,
+            &body.srclocs,
+        );
+        self.code_memory.set_readable_and_executable();
+
+        let code = body.code;
+        let lazy = self
+            .lazy_functions
+            .get_mut(&id)
+            .expect("function must be defined lazily");
+        unsafe { lazy.slot.write(code) };
+        lazy.body = Some(body);
+        Ok(code)
+    }
+
+    fn get_address(&self, name: &ir::ExternalName) -> *const u8 {
+        let symbol = match *name {
+            ir::ExternalName::User {
+                namespace: 0,
+                index,
+            } => &self.functions[FuncId::from_u32(index)],
+            ir::ExternalName::User {
+                namespace: 1,
+                index,
+            } => &self.data_objects[DataId::from_u32(index)],
+            ir::ExternalName::LibCall(libcall) => {
+                return lookup_symbol(&self.symbols, &(self.libcall_names)(libcall));
+            }
+            _ => panic!("invalid ExternalName {}", name),
+        };
+        symbol
+            .address
+            .unwrap_or_else(|| lookup_symbol(&self.symbols, &symbol.name))
+    }
+}
+
+/// Called by the trampoline of a lazily compiled function on its first call. Returns null if the
+/// function couldn't be compiled, which makes the trampoline trap.
+///
+/// Unwinding from here into the JIT'd code is undefined behavior, so errors and panics are
+/// recorded instead.
+extern "C" fn compile_lazily(compiler: *const RefCell<LazyCompiler>, id: u32) -> *const u8 {
+    let compiler = unsafe { &*compiler };
+    let id = FuncId::from_u32(id);
+    // The compiler is already borrowed if the `compile` callback called a function which isn't
+    // compiled yet.
+    let mut compiler = match compiler.try_borrow_mut() {
+        Ok(compiler) => compiler,
+        Err(_) => return ptr::null(),
+    };
+    let error = match panic::catch_unwind(AssertUnwindSafe(|| compiler.compile_function(id))) {
+        Ok(Ok(code)) => return code,
+        Ok(Err(error)) => error,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+                .or_else(|| payload.downcast_ref::<&str>().cloned())
+                .unwrap_or("unknown panic");
+            ModuleError::Backend(format!(
+                "lazily compiling function {} panicked: {}",
+                compiler.functions[id].name, message
+            ))
+        }
+    };
+    compiler.errors.push((id, error));
+    ptr::null()
+}
+
+/// Build the trampoline of function `id`, which has the signature `signature`: it has `compiler`
+/// compile the function, and then calls it with its own arguments, or traps if it couldn't be
+/// compiled.
+pub(crate) fn trampoline(
+    isa: &dyn TargetIsa,
+    compiler: &Rc<RefCell<LazyCompiler>>,
+    id: FuncId,
+    signature: &ir::Signature,
+) -> ir::Function {
+    let pointer_type = isa.pointer_type();
+    let mut func = ir::Function::with_name_signature(
+        ir::ExternalName::user(0, id.as_u32()),
+        signature.clone(),
+    );
+
+    let mut compile_sig = ir::Signature::new(isa.default_call_conv());
+    compile_sig.params.push(AbiParam::new(pointer_type));
+    compile_sig.params.push(AbiParam::new(types::I32));
+    compile_sig.returns.push(AbiParam::new(pointer_type));
+    let compile_sig = func.import_signature(compile_sig);
+    let callee_sig = func.import_signature(signature.clone());
+
+    let ebb = func.dfg.make_ebb();
+    let args: Vec<_> = signature
+        .params
+        .iter()
+        .map(|param| func.dfg.append_ebb_param(ebb, param.value_type))
+        .collect();
+
+    let mut pos = FuncCursor::new(&mut func);
+    pos.insert_ebb(ebb);
+    let compiler: *const RefCell<LazyCompiler> = &**compiler;
+    let compiler = pos.ins().iconst(pointer_type, compiler as usize as i64);
+    let id = pos.ins().iconst(types::I32, i64::from(id.as_u32()));
+    let compile_lazily = pos
+        .ins()
+        .iconst(pointer_type, compile_lazily as usize as i64);
+    let call = pos
+        .ins()
+        .call_indirect(compile_sig, compile_lazily, &[compiler, id]);
+    let callee = pos.func.dfg.first_result(call);
+    pos.ins().trapz(callee, ir::TrapCode::User(0));
+    let call = pos.ins().call_indirect(callee_sig, callee, &args);
+    let results = pos.func.dfg.inst_results(call).to_vec();
+    pos.ins().return_(&results);
+    func
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::isa::{self, CallConv};
+    use cranelift_codegen::{settings, CodegenError};
+    use std::cell::Cell;
+
+    fn lazy_compiler<F>(compile: F) -> RefCell<LazyCompiler>
+    where
+        F: FnMut(FuncId) -> ir::Function + 'static,
+    {
+        let isa: Rc<dyn TargetIsa> = Rc::from(
+            isa::lookup_by_name("x86_64")
+                .unwrap()
+                .finish(settings::Flags::new(settings::builder())),
+        );
+        let profiler = Rc::new(RefCell::new(Profiler::new(&*isa, false, false)));
+        RefCell::new(LazyCompiler::new(
+            isa,
+            HashMap::new(),
+            Rc::from(cranelift_module::default_libcall_names()),
+            false,
+            profiler,
+            Box::new(compile),
+        ))
+    }
+
+    /// A function which is supposed to return an `i64`, but doesn't.
+    fn invalid_function(id: FuncId) -> ir::Function {
+        let mut sig = ir::Signature::new(CallConv::SystemV);
+        sig.returns.push(AbiParam::new(types::I64));
+        let mut func =
+            ir::Function::with_name_signature(ir::ExternalName::user(0, id.as_u32()), sig);
+        let ebb = func.dfg.make_ebb();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb);
+        pos.ins().return_(&[]);
+        func
+    }
+
+    #[test]
+    fn invalid_ir() {
+        let compiler = lazy_compiler(invalid_function);
+        assert!(compile_lazily(&compiler, 0).is_null());
+        let errors = compiler.borrow_mut().take_errors();
+        match errors.as_slice() {
+            [(id, ModuleError::Compilation(CodegenError::Verifier(_)))] => {
+                assert_eq!(*id, FuncId::from_u32(0))
+            }
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+        assert!(compiler.borrow_mut().take_errors().is_empty());
+    }
+
+    #[test]
+    fn panicking_callback() {
+        let compiler = lazy_compiler(|_| panic!("no IR"));
+        compiler
+            .borrow_mut()
+            .declare_function(FuncId::from_u32(0), "answer");
+        assert!(compile_lazily(&compiler, 0).is_null());
+        let errors = compiler.borrow_mut().take_errors();
+        match errors.as_slice() {
+            [(_, ModuleError::Backend(message))] => {
+                assert_eq!(message, "lazily compiling function answer panicked: no IR")
+            }
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn reentrant_compilation() {
+        let pointer = Rc::new(Cell::new(ptr::null::<RefCell<LazyCompiler>>()));
+        let inner = Rc::clone(&pointer);
+        let compiler = lazy_compiler(move |id| {
+            // The compiler is busy, so the nested call fails.
+            assert!(compile_lazily(inner.get(), 1).is_null());
+            invalid_function(id)
+        });
+        pointer.set(&compiler);
+        assert!(compile_lazily(&compiler, 0).is_null());
+        let errors = compiler.borrow_mut().take_errors();
+        match errors.as_slice() {
+            [(_, ModuleError::Compilation(_))] => {}
+            errors => panic!("unexpected errors: {:?}", errors),
+        }
+    }
+}
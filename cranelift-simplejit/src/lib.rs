@@ -24,7 +24,9 @@
 )]
 
 mod backend;
+mod lazy;
 mod memory;
+mod perf;
 
 pub use crate::backend::{SimpleJITBackend, SimpleJITBuilder};
 
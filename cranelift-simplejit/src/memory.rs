@@ -103,6 +103,33 @@ impl PtrLen {
             Err(errno::errno().to_string())
         }
     }
+
+    /// Release the memory pointed to by this `PtrLen`.
+    #[cfg(all(not(target_os = "windows"), feature = "selinux-fix"))]
+    unsafe fn free(self) {
+        // Unmapping the memory doesn't depend on its protection.
+        drop(self.map);
+    }
+
+    #[cfg(all(not(target_os = "windows"), not(feature = "selinux-fix")))]
+    unsafe fn free(self) {
+        if self.len != 0 {
+            // The allocator may write its own bookkeeping into freed memory.
+            region::protect(self.ptr, self.len, region::Protection::ReadWrite)
+                .expect("unable to make memory writable");
+            libc::free(self.ptr as *mut libc::c_void);
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    unsafe fn free(self) {
+        use winapi::um::memoryapi::VirtualFree;
+        use winapi::um::winnt::MEM_RELEASE;
+
+        if !self.ptr.is_null() {
+            VirtualFree(self.ptr as *mut _, 0, MEM_RELEASE);
+        }
+    }
 }
 
 /// JIT memory manager. This manages pages of suitably aligned and
@@ -152,6 +179,32 @@ impl Memory {
         Ok(self.current.ptr)
     }
 
+    /// Allocate `size` bytes in pages of their own, so that they can be released with `free`
+    /// without affecting any other allocation.
+    pub fn allocate_standalone(&mut self, size: usize) -> Result<*mut u8, String> {
+        let allocation = PtrLen::with_size(size)?;
+        let ptr = allocation.ptr;
+        self.allocations.push(allocation);
+        Ok(ptr)
+    }
+
+    /// Release the memory of an allocation made with `allocate_standalone`.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may use the memory at `ptr` anymore.
+    pub unsafe fn free(&mut self, ptr: *mut u8) {
+        let index = self
+            .allocations
+            .iter()
+            .position(|allocation| allocation.ptr == ptr)
+            .expect("can only free standalone allocations");
+        if index < self.executable {
+            self.executable -= 1;
+        }
+        self.allocations.remove(index).free();
+    }
+
     /// Set all memory allocated in this `Memory` up to now as readable and executable.
     pub fn set_readable_and_executable(&mut self) {
         self.finish_current();
@@ -0,0 +1,226 @@
+//! Reporting of JIT'd functions to the Linux `perf` profiler.
+//!
+//! `perf` can name JIT'd functions with a `/tmp/perf-<pid>.map` file, which lists their address
+//! ranges. The jitdump format additionally records their code and source locations, so that
+//! `perf annotate` works; see `tools/perf/Documentation/jitdump-specification.txt` in the Linux
+//! sources.
+
+use cranelift_codegen::binemit::CodeOffset;
+use cranelift_codegen::ir;
+use cranelift_codegen::isa::TargetIsa;
+use std::fs::File;
+use std::io::Write;
+use std::slice;
+use target_lexicon::PointerWidth;
+
+/// Writes a perf map and a jitdump file, if enabled.
+pub(crate) struct Profiler {
+    perf_map: Option<File>,
+    jitdump: Option<JitDump>,
+}
+
+impl Profiler {
+    pub fn new(isa: &dyn TargetIsa, perf_map: bool, jitdump: bool) -> Self {
+        if !cfg!(target_os = "linux") {
+            return Self {
+                perf_map: None,
+                jitdump: None,
+            };
+        }
+        let perf_map = if perf_map {
+            Some(
+                ::std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(format!("/tmp/perf-{}.map", ::std::process::id()))
+                    .expect("unable to open the perf map"),
+            )
+        } else {
+            None
+        };
+        let jitdump = if jitdump {
+            Some(JitDump::new(isa))
+        } else {
+            None
+        };
+        Self { perf_map, jitdump }
+    }
+
+    /// Whether `record_function` makes use of source locations.
+    pub fn wants_srclocs(&self) -> bool {
+        self.jitdump.is_some()
+    }
+
+    /// Record the function `name`, whose code is at `code`, and whose code at each offset in
+    /// `srclocs` comes from the associated source location.
+    pub fn record_function(
+        &mut self,
+        name: &str,
+        code: *const u8,
+        size: usize,
+        srclocs: &[(CodeOffset, ir::SourceLoc)],
+    ) {
+        if let Some(perf_map) = &mut self.perf_map {
+            let _ = writeln!(perf_map, "{:x} {:x} {}", code as usize, size, name);
+        }
+        if let Some(jitdump) = &mut self.jitdump {
+            jitdump.record_function(name, code, size, srclocs);
+        }
+    }
+}
+
+/// Collect the offsets in the code of `func` at which its source location changes, for
+/// `Profiler::record_function`.
+pub(crate) fn function_srclocs(
+    isa: &dyn TargetIsa,
+    func: &ir::Function,
+) -> Vec<(CodeOffset, ir::SourceLoc)> {
+    let encinfo = isa.encoding_info();
+    let mut srclocs = Vec::new();
+    let mut last = ir::SourceLoc::default();
+    for ebb in func.layout.ebbs() {
+        for (offset, inst, _) in func.inst_offsets(ebb, &encinfo) {
+            let srcloc = func.srclocs[inst];
+            if !srcloc.is_default() && srcloc != last {
+                srclocs.push((offset, srcloc));
+                last = srcloc;
+            }
+        }
+    }
+    srclocs
+}
+
+const JITDUMP_MAGIC: u32 = 0x4A69_5444;
+const JITDUMP_VERSION: u32 = 1;
+const JIT_CODE_LOAD: u32 = 0;
+const JIT_CODE_DEBUG_INFO: u32 = 2;
+
+/// A `jit-<pid>.dump` file in the current directory.
+struct JitDump {
+    file: File,
+    code_index: u64,
+}
+
+impl JitDump {
+    fn new(isa: &dyn TargetIsa) -> Self {
+        let pid = ::std::process::id();
+        let mut file =
+            File::create(format!("jit-{}.dump", pid)).expect("unable to create the jitdump file");
+
+        // `perf record` only notices the file if it is mapped as executable.
+        #[cfg(target_os = "linux")]
+        unsafe {
+            use std::os::unix::io::AsRawFd;
+            libc::mmap(
+                ::std::ptr::null_mut(),
+                region::page::size(),
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            );
+        }
+
+        let mut header = Vec::with_capacity(40);
+        header.extend_from_slice(&JITDUMP_MAGIC.to_ne_bytes());
+        header.extend_from_slice(&JITDUMP_VERSION.to_ne_bytes());
+        header.extend_from_slice(&40u32.to_ne_bytes());
+        header.extend_from_slice(&elf_machine(isa).to_ne_bytes());
+        header.extend_from_slice(&0u32.to_ne_bytes());
+        header.extend_from_slice(&pid.to_ne_bytes());
+        header.extend_from_slice(&timestamp().to_ne_bytes());
+        header.extend_from_slice(&0u64.to_ne_bytes());
+        let _ = file.write_all(&header);
+
+        Self {
+            file,
+            code_index: 0,
+        }
+    }
+
+    fn record_function(
+        &mut self,
+        name: &str,
+        code: *const u8,
+        size: usize,
+        srclocs: &[(CodeOffset, ir::SourceLoc)],
+    ) {
+        let timestamp = timestamp();
+
+        // Source locations must precede the code they describe. As `SourceLoc`s are opaque, they
+        // are reported as line numbers in a file named after the function.
+        if !srclocs.is_empty() {
+            let mut record = Vec::new();
+            record.extend_from_slice(&(code as u64).to_ne_bytes());
+            record.extend_from_slice(&(srclocs.len() as u64).to_ne_bytes());
+            for &(offset, srcloc) in srclocs {
+                record.extend_from_slice(&(code as u64 + u64::from(offset)).to_ne_bytes());
+                record.extend_from_slice(&srcloc.bits().to_ne_bytes());
+                record.extend_from_slice(&0u32.to_ne_bytes());
+                record.extend_from_slice(name.as_bytes());
+                record.push(0);
+            }
+            self.write_record(JIT_CODE_DEBUG_INFO, timestamp, &record);
+        }
+
+        let mut record = Vec::new();
+        record.extend_from_slice(&::std::process::id().to_ne_bytes());
+        record.extend_from_slice(&thread_id().to_ne_bytes());
+        record.extend_from_slice(&(code as u64).to_ne_bytes());
+        record.extend_from_slice(&(code as u64).to_ne_bytes());
+        record.extend_from_slice(&(size as u64).to_ne_bytes());
+        record.extend_from_slice(&self.code_index.to_ne_bytes());
+        record.extend_from_slice(name.as_bytes());
+        record.push(0);
+        record.extend_from_slice(unsafe { slice::from_raw_parts(code, size) });
+        self.write_record(JIT_CODE_LOAD, timestamp, &record);
+        self.code_index += 1;
+    }
+
+    fn write_record(&mut self, id: u32, timestamp: u64, body: &[u8]) {
+        let mut header = Vec::with_capacity(16);
+        header.extend_from_slice(&id.to_ne_bytes());
+        header.extend_from_slice(&(16 + body.len() as u32).to_ne_bytes());
+        header.extend_from_slice(&timestamp.to_ne_bytes());
+        let _ = self.file.write_all(&header);
+        let _ = self.file.write_all(body);
+    }
+}
+
+/// The ELF `e_machine` value of the target.
+fn elf_machine(isa: &dyn TargetIsa) -> u32 {
+    match (isa.name(), isa.triple().pointer_width()) {
+        ("x86", Ok(PointerWidth::U64)) => 62,
+        ("x86", _) => 3,
+        ("arm32", _) => 40,
+        ("arm64", _) => 183,
+        ("riscv", _) => 243,
+        _ => 0,
+    }
+}
+
+/// The time in nanoseconds, from the clock `perf record -k 1` uses.
+#[cfg(target_os = "linux")]
+fn timestamp() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+#[cfg(not(target_os = "linux"))]
+fn timestamp() -> u64 {
+    0
+}
+
+#[cfg(target_os = "linux")]
+fn thread_id() -> u32 {
+    unsafe { libc::syscall(libc::SYS_gettid) as u32 }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn thread_id() -> u32 {
+    0
+}
@@ -198,3 +198,214 @@ fn libcall_function() {
 
     module.finalize_definitions();
 }
+
+/// Define `callee` as a function returning `value`.
+fn define_constant_function(
+    module: &mut Module<SimpleJITBackend>,
+    callee: FuncId,
+    value: i64,
+    redefine: bool,
+) {
+    let mut sig = module.make_signature();
+    sig.returns.push(AbiParam::new(types::I64));
+
+    let mut ctx = Context::new();
+    ctx.func = {
+        let func = Function::with_name_signature(ExternalName::user(0, callee.as_u32()), sig);
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(func);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let v = bcx.ins().iconst(types::I64, value);
+        bcx.ins().return_(&[v]);
+        bcx.seal_all_blocks();
+        bcx.finalize()
+    };
+    if redefine {
+        module.redefine_function(callee, &mut ctx).unwrap();
+    } else {
+        module.define_function(callee, &mut ctx).unwrap();
+    }
+}
+
+#[test]
+fn hotswap_function() {
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    builder.hotswap(true);
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+    let mut sig = module.make_signature();
+    sig.returns.push(AbiParam::new(types::I64));
+    let callee = module
+        .declare_function("callee", Linkage::Local, &sig)
+        .unwrap();
+    let caller = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+
+    define_constant_function(&mut module, callee, 1, false);
+    let mut ctx = Context::new();
+    ctx.func = {
+        let func = Function::with_name_signature(ExternalName::user(0, caller.as_u32()), sig);
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(func);
+        let callee_ref = module.declare_func_in_func(callee, &mut bcx.func);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let call = bcx.ins().call(callee_ref, &[]);
+        let result = bcx.inst_results(call)[0];
+        bcx.ins().return_(&[result]);
+        bcx.seal_all_blocks();
+        bcx.finalize()
+    };
+    module.define_function(caller, &mut ctx).unwrap();
+    module.finalize_definitions();
+
+    let code = module.get_finalized_function(caller);
+    let caller_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i64>(code) };
+    assert_eq!(caller_fn(), 1);
+
+    // `caller` isn't recompiled, but calls the new body of `callee`.
+    define_constant_function(&mut module, callee, 2, true);
+    module.finalize_definitions();
+    assert_eq!(caller_fn(), 2);
+
+    // A freed function can be defined again.
+    module.free_function(callee).unwrap();
+    define_constant_function(&mut module, callee, 3, false);
+    module.finalize_definitions();
+    assert_eq!(caller_fn(), 3);
+
+    // The stable entry point of `callee` also reaches the new body.
+    let code = module.get_finalized_function(callee);
+    let callee_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i64>(code) };
+    assert_eq!(callee_fn(), 3);
+}
+
+#[test]
+fn redefine_without_hotswap() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let func_id = define_simple_function(&mut module);
+    module.finalize_definitions();
+    match module.free_function(func_id) {
+        Err(ModuleError::Backend(_)) => {}
+        _ => panic!("expected an error from the backend"),
+    }
+}
+
+#[test]
+fn hotswap_unsupported_isa() {
+    let isa_builder = match cranelift_codegen::isa::lookup_by_name("riscv64") {
+        Ok(isa_builder) => isa_builder,
+        Err(_) => return,
+    };
+    let flag_builder = cranelift_codegen::settings::builder();
+    let isa = isa_builder.finish(cranelift_codegen::settings::Flags::new(flag_builder));
+    let mut builder = SimpleJITBuilder::with_isa(isa, default_libcall_names());
+    builder.hotswap(true);
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+    let sig = module.make_signature();
+    let func_id = module
+        .declare_function("abc", Linkage::Local, &sig)
+        .unwrap();
+    let mut ctx = Context::new();
+    ctx.func = {
+        let func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig);
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(func);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        bcx.ins().return_(&[]);
+        bcx.seal_all_blocks();
+        bcx.finalize()
+    };
+    match module.define_function(func_id, &mut ctx) {
+        Err(ModuleError::Backend(_)) => {}
+        _ => panic!("expected an error from the backend"),
+    }
+}
+
+#[test]
+fn lazy_compilation() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let compiled = Rc::new(Cell::new(0));
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    let call_conv = CallConv::triple_default(&target_lexicon::Triple::host());
+    let counter = Rc::clone(&compiled);
+    builder.lazy_compilation(move |id| {
+        counter.set(counter.get() + 1);
+        let mut sig = Signature::new(call_conv);
+        sig.returns.push(AbiParam::new(types::I64));
+        let func = Function::with_name_signature(ExternalName::user(0, id.as_u32()), sig.clone());
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(func);
+        let ebb = bcx.create_ebb();
+        bcx.switch_to_block(ebb);
+        let result = if id.as_u32() == 0 {
+            // `answer` returns 42.
+            bcx.ins().iconst(types::I64, 42)
+        } else {
+            // `caller` returns `answer() + 1`.
+            let signature = bcx.import_signature(sig);
+            let answer = bcx.import_function(ExtFuncData {
+                name: ExternalName::user(0, 0),
+                signature,
+                colocated: true,
+            });
+            let call = bcx.ins().call(answer, &[]);
+            let answer = bcx.inst_results(call)[0];
+            bcx.ins().iadd_imm(answer, 1)
+        };
+        bcx.ins().return_(&[result]);
+        bcx.seal_all_blocks();
+        bcx.finalize()
+    });
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+    let mut sig = module.make_signature();
+    sig.returns.push(AbiParam::new(types::I64));
+    let answer = module
+        .declare_function("answer", Linkage::Local, &sig)
+        .unwrap();
+    let caller = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+    module.define_function_lazily(answer).unwrap();
+    module.define_function_lazily(caller).unwrap();
+    module.finalize_definitions();
+    assert_eq!(compiled.get(), 0);
+
+    let code = module.get_finalized_function(caller);
+    let caller_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i64>(code) };
+    assert_eq!(caller_fn(), 43);
+    assert_eq!(compiled.get(), 2);
+
+    // Both functions are compiled only once.
+    assert_eq!(caller_fn(), 43);
+    assert_eq!(compiled.get(), 2);
+    assert!(module.take_lazy_compilation_errors().is_empty());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn perf_map() {
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    builder.perf_map(true);
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+    let sig = module.make_signature();
+    let answer = module
+        .declare_function("perf_map_answer", Linkage::Local, &sig)
+        .unwrap();
+    define_constant_function(&mut module, answer, 42, false);
+    module.finalize_definitions();
+    let code = module.get_finalized_function(answer);
+
+    let map = std::fs::read_to_string(format!("/tmp/perf-{}.map", std::process::id())).unwrap();
+    let line = map
+        .lines()
+        .find(|line| line.ends_with(" perf_map_answer"))
+        .unwrap();
+    assert!(line.starts_with(&format!("{:x} ", code as usize)));
+}
@@ -25,10 +25,10 @@
 use super::{hash_map, HashMap};
 use crate::environ::{FuncEnvironment, GlobalVariable, ReturnMode, WasmResult};
 use crate::state::{ControlStackFrame, TranslationState};
+use crate::translation_utils::{blocktype_params_results, f32_translation, f64_translation};
 use crate::translation_utils::{
-    blocktype_to_type, f32_translation, f64_translation, num_return_values,
+    DataIndex, ElemIndex, FuncIndex, MemoryIndex, SignatureIndex, TableIndex,
 };
-use crate::translation_utils::{FuncIndex, MemoryIndex, SignatureIndex, TableIndex};
 use crate::wasm_unsupported;
 use core::{i32, u32};
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
@@ -49,7 +49,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
     environ: &mut FE,
 ) -> WasmResult<()> {
     if !state.reachable {
-        translate_unreachable_operator(&op, builder, state);
+        translate_unreachable_operator(&op, builder, state, environ)?;
         return Ok(());
     }
 
@@ -85,7 +85,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 GlobalVariable::Const(val) => val,
                 GlobalVariable::Memory { gv, offset, ty } => {
                     let addr = builder.ins().global_value(environ.pointer_type(), gv);
-                    let flags = ir::MemFlags::trusted();
+                    let mut flags = ir::MemFlags::trusted();
+                    flags.set_alias_region(Some(ir::AliasRegion::Vmctx));
                     builder.ins().load(ty, flags, addr, offset)
                 }
             };
@@ -96,7 +97,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 GlobalVariable::Const(_) => panic!("global #{} is a constant", *global_index),
                 GlobalVariable::Memory { gv, offset, ty } => {
                     let addr = builder.ins().global_value(environ.pointer_type(), gv);
-                    let flags = ir::MemFlags::trusted();
+                    let mut flags = ir::MemFlags::trusted();
+                    flags.set_alias_region(Some(ir::AliasRegion::Vmctx));
                     let val = state.pop1();
                     debug_assert_eq!(ty, builder.func.dfg.value_type(val));
                     builder.ins().store(flags, val, addr, offset);
@@ -132,27 +134,28 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          *  possible `Ebb`'s arguments values.
          ***********************************************************************************/
         Operator::Block { ty } => {
-            let next = builder.create_ebb();
-            if let Some(ty_cre) = blocktype_to_type(*ty)? {
-                builder.append_ebb_param(next, ty_cre);
-            }
-            state.push_block(next, num_return_values(*ty)?);
+            let (params, results) = blocktype_params_results(*ty, environ)?;
+            let next = ebb_with_params(builder, &results);
+            state.push_block(next, params.len(), results.len());
         }
         Operator::Loop { ty } => {
-            let loop_body = builder.create_ebb();
-            let next = builder.create_ebb();
-            if let Some(ty_cre) = blocktype_to_type(*ty)? {
-                builder.append_ebb_param(next, ty_cre);
-            }
-            builder.ins().jump(loop_body, &[]);
-            state.push_loop(loop_body, next, num_return_values(*ty)?);
+            let (params, results) = blocktype_params_results(*ty, environ)?;
+            let loop_body = ebb_with_params(builder, &params);
+            let next = ebb_with_params(builder, &results);
+            builder.ins().jump(loop_body, state.peekn(params.len()));
+            state.push_loop(loop_body, next, params.len(), results.len());
+            // The loop parameters are replaced by those of the header, since branches back to the
+            // top of the loop provide new values for them.
+            state.popn(params.len());
+            state.stack.extend_from_slice(builder.ebb_params(loop_body));
             builder.switch_to_block(loop_body);
             environ.translate_loop_header(builder.cursor())?;
         }
         Operator::If { ty } => {
             let val = state.pop1();
-            let if_not = builder.create_ebb();
-            let jump_inst = builder.ins().brz(val, if_not, &[]);
+            let (params, results) = blocktype_params_results(*ty, environ)?;
+            let if_not = ebb_with_params(builder, &results);
+            let jump_inst = builder.ins().brz(val, if_not, state.peekn(params.len()));
 
             #[cfg(feature = "basic-blocks")]
             {
@@ -162,47 +165,44 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 builder.switch_to_block(next_ebb);
             }
 
-            // Here we append an argument to an Ebb targeted by an argumentless jump instruction
-            // But in fact there are two cases:
-            // - either the If does not have a Else clause, in that case ty = EmptyBlock
-            //   and we add nothing;
+            // The `brz` passes the parameters of the If to an Ebb expecting its results. But in
+            // fact there are two cases:
+            // - either the If does not have a Else clause, in that case its parameters and results
+            //   have the same types and are passed through unchanged;
             // - either the If have an Else clause, in that case the destination of this jump
             //   instruction will be changed later when we translate the Else operator.
-            if let Some(ty_cre) = blocktype_to_type(*ty)? {
-                builder.append_ebb_param(if_not, ty_cre);
-            }
-            state.push_if(jump_inst, if_not, num_return_values(*ty)?);
+            state.push_if(jump_inst, if_not, *ty, params.len(), results.len());
         }
         Operator::Else => {
             // We take the control frame pushed by the if, use its ebb as the else body
             // and push a new control frame with a new ebb for the code after the if/then/else
             // At the end of the then clause we jump to the destination
             let i = state.control_stack.len() - 1;
-            let (destination, return_count, branch_inst, ref mut reachable_from_top) =
-                match state.control_stack[i] {
-                    ControlStackFrame::If {
-                        destination,
-                        num_return_values,
-                        branch_inst,
-                        reachable_from_top,
-                        ..
-                    } => (
-                        destination,
-                        num_return_values,
-                        branch_inst,
-                        reachable_from_top,
-                    ),
-                    _ => panic!("should not happen"),
-                };
-            // The if has an else, so there's no branch to the end from the top.
-            *reachable_from_top = false;
+            let (destination, return_count, branch_inst, blocktype) = match state.control_stack[i] {
+                ControlStackFrame::If {
+                    destination,
+                    num_return_values,
+                    branch_inst,
+                    blocktype,
+                    ref mut reachable_from_top,
+                    ..
+                } => {
+                    // The if has an else, so there's no branch to the end from the top.
+                    *reachable_from_top = false;
+                    (destination, num_return_values, branch_inst, blocktype)
+                }
+                _ => panic!("should not happen"),
+            };
             builder.ins().jump(destination, state.peekn(return_count));
             state.popn(return_count);
-            // We change the target of the branch instruction
-            let else_ebb = builder.create_ebb();
+            // We change the target of the branch instruction, which passes the parameters of the
+            // If on to the else body.
+            let (params, _) = blocktype_params_results(blocktype, environ)?;
+            let else_ebb = ebb_with_params(builder, &params);
             builder.change_jump_destination(branch_inst, else_ebb);
             builder.seal_block(else_ebb);
             builder.switch_to_block(else_ebb);
+            state.stack.extend_from_slice(builder.ebb_params(else_ebb));
         }
         Operator::End => {
             let frame = state.control_stack.pop().unwrap();
@@ -251,7 +251,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 // We signal that all the code that follows until the next End is unreachable
                 frame.set_branched_to_exit();
                 let return_count = if frame.is_loop() {
-                    0
+                    frame.num_param_values()
                 } else {
                     frame.num_return_values()
                 };
@@ -276,7 +276,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 let i = state.control_stack.len() - 1 - (min_depth as usize);
                 let min_depth_frame = &state.control_stack[i];
                 if min_depth_frame.is_loop() {
-                    0
+                    min_depth_frame.num_param_values()
                 } else {
                     min_depth_frame.num_return_values()
                 }
@@ -839,89 +839,256 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::RefIsNull => {
             let arg = state.pop1();
             let val = builder.ins().is_null(arg);
-            state.push1(val);
+            state.push1(builder.ins().bint(I32, val));
         }
-        Operator::Wake { .. }
-        | Operator::I32Wait { .. }
-        | Operator::I64Wait { .. }
-        | Operator::I32AtomicLoad { .. }
-        | Operator::I64AtomicLoad { .. }
-        | Operator::I32AtomicLoad8U { .. }
-        | Operator::I32AtomicLoad16U { .. }
-        | Operator::I64AtomicLoad8U { .. }
-        | Operator::I64AtomicLoad16U { .. }
-        | Operator::I64AtomicLoad32U { .. }
-        | Operator::I32AtomicStore { .. }
-        | Operator::I64AtomicStore { .. }
-        | Operator::I32AtomicStore8 { .. }
-        | Operator::I32AtomicStore16 { .. }
-        | Operator::I64AtomicStore8 { .. }
-        | Operator::I64AtomicStore16 { .. }
-        | Operator::I64AtomicStore32 { .. }
-        | Operator::I32AtomicRmwAdd { .. }
-        | Operator::I64AtomicRmwAdd { .. }
-        | Operator::I32AtomicRmw8UAdd { .. }
-        | Operator::I32AtomicRmw16UAdd { .. }
-        | Operator::I64AtomicRmw8UAdd { .. }
-        | Operator::I64AtomicRmw16UAdd { .. }
-        | Operator::I64AtomicRmw32UAdd { .. }
-        | Operator::I32AtomicRmwSub { .. }
-        | Operator::I64AtomicRmwSub { .. }
-        | Operator::I32AtomicRmw8USub { .. }
-        | Operator::I32AtomicRmw16USub { .. }
-        | Operator::I64AtomicRmw8USub { .. }
-        | Operator::I64AtomicRmw16USub { .. }
-        | Operator::I64AtomicRmw32USub { .. }
-        | Operator::I32AtomicRmwAnd { .. }
-        | Operator::I64AtomicRmwAnd { .. }
-        | Operator::I32AtomicRmw8UAnd { .. }
-        | Operator::I32AtomicRmw16UAnd { .. }
-        | Operator::I64AtomicRmw8UAnd { .. }
-        | Operator::I64AtomicRmw16UAnd { .. }
-        | Operator::I64AtomicRmw32UAnd { .. }
-        | Operator::I32AtomicRmwOr { .. }
-        | Operator::I64AtomicRmwOr { .. }
-        | Operator::I32AtomicRmw8UOr { .. }
-        | Operator::I32AtomicRmw16UOr { .. }
-        | Operator::I64AtomicRmw8UOr { .. }
-        | Operator::I64AtomicRmw16UOr { .. }
-        | Operator::I64AtomicRmw32UOr { .. }
-        | Operator::I32AtomicRmwXor { .. }
-        | Operator::I64AtomicRmwXor { .. }
-        | Operator::I32AtomicRmw8UXor { .. }
-        | Operator::I32AtomicRmw16UXor { .. }
-        | Operator::I64AtomicRmw8UXor { .. }
-        | Operator::I64AtomicRmw16UXor { .. }
-        | Operator::I64AtomicRmw32UXor { .. }
-        | Operator::I32AtomicRmwXchg { .. }
-        | Operator::I64AtomicRmwXchg { .. }
-        | Operator::I32AtomicRmw8UXchg { .. }
-        | Operator::I32AtomicRmw16UXchg { .. }
-        | Operator::I64AtomicRmw8UXchg { .. }
-        | Operator::I64AtomicRmw16UXchg { .. }
-        | Operator::I64AtomicRmw32UXchg { .. }
-        | Operator::I32AtomicRmwCmpxchg { .. }
-        | Operator::I64AtomicRmwCmpxchg { .. }
-        | Operator::I32AtomicRmw8UCmpxchg { .. }
-        | Operator::I32AtomicRmw16UCmpxchg { .. }
-        | Operator::I64AtomicRmw8UCmpxchg { .. }
-        | Operator::I64AtomicRmw16UCmpxchg { .. }
-        | Operator::I64AtomicRmw32UCmpxchg { .. }
-        | Operator::Fence { .. } => {
-            wasm_unsupported!("proposed thread operator {:?}", op);
-        }
-        Operator::MemoryInit { .. }
-        | Operator::DataDrop { .. }
-        | Operator::MemoryCopy
-        | Operator::MemoryFill
-        | Operator::TableInit { .. }
-        | Operator::ElemDrop { .. }
-        | Operator::TableCopy
-        | Operator::TableGet { .. }
-        | Operator::TableSet { .. }
-        | Operator::TableGrow { .. }
-        | Operator::TableSize { .. } => {
-            wasm_unsupported!("proposed bulk memory operator {:?}", op);
+        /******************************* Atomic operators *********************************
+         * The threads proposal only supports the first linear memory. Atomic accesses trap if
+         * their effective address isn't naturally aligned, and are sequentially consistent.
+         ************************************************************************************/
+        Operator::I32AtomicLoad { memarg } => {
+            translate_atomic_load(I32, I32, memarg, builder, state, environ)?
+        }
+        Operator::I64AtomicLoad { memarg } => {
+            translate_atomic_load(I64, I64, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicLoad8U { memarg } => {
+            translate_atomic_load(I32, I8, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicLoad16U { memarg } => {
+            translate_atomic_load(I32, I16, memarg, builder, state, environ)?
+        }
+        Operator::I64AtomicLoad8U { memarg } => {
+            translate_atomic_load(I64, I8, memarg, builder, state, environ)?
+        }
+        Operator::I64AtomicLoad16U { memarg } => {
+            translate_atomic_load(I64, I16, memarg, builder, state, environ)?
+        }
+        Operator::I64AtomicLoad32U { memarg } => {
+            translate_atomic_load(I64, I32, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicStore { memarg } => {
+            translate_atomic_store(I32, memarg, builder, state, environ)?
+        }
+        Operator::I64AtomicStore { memarg } => {
+            translate_atomic_store(I64, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicStore8 { memarg } | Operator::I64AtomicStore8 { memarg } => {
+            translate_atomic_store(I8, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicStore16 { memarg } | Operator::I64AtomicStore16 { memarg } => {
+            translate_atomic_store(I16, memarg, builder, state, environ)?
+        }
+        Operator::I64AtomicStore32 { memarg } => {
+            translate_atomic_store(I32, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwAdd { memarg }
+        | Operator::I64AtomicRmwAdd { memarg }
+        | Operator::I32AtomicRmw8UAdd { memarg }
+        | Operator::I32AtomicRmw16UAdd { memarg }
+        | Operator::I64AtomicRmw8UAdd { memarg }
+        | Operator::I64AtomicRmw16UAdd { memarg }
+        | Operator::I64AtomicRmw32UAdd { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            let rmw_op = ir::AtomicRmwOp::Add;
+            translate_atomic_rmw(rmw_op, access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwSub { memarg }
+        | Operator::I64AtomicRmwSub { memarg }
+        | Operator::I32AtomicRmw8USub { memarg }
+        | Operator::I32AtomicRmw16USub { memarg }
+        | Operator::I64AtomicRmw8USub { memarg }
+        | Operator::I64AtomicRmw16USub { memarg }
+        | Operator::I64AtomicRmw32USub { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            let rmw_op = ir::AtomicRmwOp::Sub;
+            translate_atomic_rmw(rmw_op, access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwAnd { memarg }
+        | Operator::I64AtomicRmwAnd { memarg }
+        | Operator::I32AtomicRmw8UAnd { memarg }
+        | Operator::I32AtomicRmw16UAnd { memarg }
+        | Operator::I64AtomicRmw8UAnd { memarg }
+        | Operator::I64AtomicRmw16UAnd { memarg }
+        | Operator::I64AtomicRmw32UAnd { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            let rmw_op = ir::AtomicRmwOp::And;
+            translate_atomic_rmw(rmw_op, access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwOr { memarg }
+        | Operator::I64AtomicRmwOr { memarg }
+        | Operator::I32AtomicRmw8UOr { memarg }
+        | Operator::I32AtomicRmw16UOr { memarg }
+        | Operator::I64AtomicRmw8UOr { memarg }
+        | Operator::I64AtomicRmw16UOr { memarg }
+        | Operator::I64AtomicRmw32UOr { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            let rmw_op = ir::AtomicRmwOp::Or;
+            translate_atomic_rmw(rmw_op, access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwXor { memarg }
+        | Operator::I64AtomicRmwXor { memarg }
+        | Operator::I32AtomicRmw8UXor { memarg }
+        | Operator::I32AtomicRmw16UXor { memarg }
+        | Operator::I64AtomicRmw8UXor { memarg }
+        | Operator::I64AtomicRmw16UXor { memarg }
+        | Operator::I64AtomicRmw32UXor { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            let rmw_op = ir::AtomicRmwOp::Xor;
+            translate_atomic_rmw(rmw_op, access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwXchg { memarg }
+        | Operator::I64AtomicRmwXchg { memarg }
+        | Operator::I32AtomicRmw8UXchg { memarg }
+        | Operator::I32AtomicRmw16UXchg { memarg }
+        | Operator::I64AtomicRmw8UXchg { memarg }
+        | Operator::I64AtomicRmw16UXchg { memarg }
+        | Operator::I64AtomicRmw32UXchg { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            let rmw_op = ir::AtomicRmwOp::Xchg;
+            translate_atomic_rmw(rmw_op, access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32AtomicRmwCmpxchg { memarg }
+        | Operator::I64AtomicRmwCmpxchg { memarg }
+        | Operator::I32AtomicRmw8UCmpxchg { memarg }
+        | Operator::I32AtomicRmw16UCmpxchg { memarg }
+        | Operator::I64AtomicRmw8UCmpxchg { memarg }
+        | Operator::I64AtomicRmw16UCmpxchg { memarg }
+        | Operator::I64AtomicRmw32UCmpxchg { memarg } => {
+            let access_ty = atomic_rmw_access_type(op);
+            translate_atomic_cas(access_ty, memarg, builder, state, environ)?
+        }
+        Operator::I32Wait { memarg } | Operator::I64Wait { memarg } => {
+            let (addr32, expected, timeout) = state.pop3();
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(&mut builder.func, 0, environ)?;
+            let access_ty = builder.func.dfg.value_type(expected);
+            let addr = get_atomic_heap_addr(
+                heap,
+                addr32,
+                memarg.offset,
+                access_ty,
+                environ.pointer_type(),
+                builder,
+            );
+            let res = environ.translate_atomic_wait(
+                builder.cursor(),
+                heap_index,
+                heap,
+                addr,
+                expected,
+                timeout,
+            )?;
+            state.push1(res);
+        }
+        Operator::Wake { memarg } => {
+            let (addr32, count) = state.pop2();
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(&mut builder.func, 0, environ)?;
+            let addr = get_atomic_heap_addr(
+                heap,
+                addr32,
+                memarg.offset,
+                I32,
+                environ.pointer_type(),
+                builder,
+            );
+            let res =
+                environ.translate_atomic_notify(builder.cursor(), heap_index, heap, addr, count)?;
+            state.push1(res);
+        }
+        Operator::Fence { flags: _ } => {
+            builder.ins().fence();
+        }
+        /******************************* Bulk memory operators *******************************
+         * The bulk memory proposal only supports the first linear memory and table.
+         * The segments and the runtime functions are provided by the environment.
+         ************************************************************************************/
+        Operator::MemoryCopy => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(&mut builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_memory_copy(builder.cursor(), heap_index, heap, dst, src, len)?;
+        }
+        Operator::MemoryFill => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(&mut builder.func, 0, environ)?;
+            let (dst, val, len) = state.pop3();
+            environ.translate_memory_fill(builder.cursor(), heap_index, heap, dst, val, len)?;
+        }
+        Operator::MemoryInit { segment } => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(&mut builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_memory_init(
+                builder.cursor(),
+                heap_index,
+                heap,
+                DataIndex::from_u32(*segment),
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::DataDrop { segment } => {
+            environ.translate_data_drop(builder.cursor(), DataIndex::from_u32(*segment))?;
+        }
+        Operator::TableCopy => {
+            let table_index = TableIndex::from_u32(0);
+            let table = state.get_table(&mut builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_table_copy(
+                builder.cursor(),
+                table_index,
+                table,
+                table_index,
+                table,
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::TableInit { segment } => {
+            let table_index = TableIndex::from_u32(0);
+            let table = state.get_table(&mut builder.func, 0, environ)?;
+            let (dst, src, len) = state.pop3();
+            environ.translate_table_init(
+                builder.cursor(),
+                ElemIndex::from_u32(*segment),
+                table_index,
+                table,
+                dst,
+                src,
+                len,
+            )?;
+        }
+        Operator::ElemDrop { segment } => {
+            environ.translate_elem_drop(builder.cursor(), ElemIndex::from_u32(*segment))?;
+        }
+        Operator::TableGet { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(&mut builder.func, *table, environ)?;
+            let index = state.pop1();
+            let value = environ.translate_table_get(builder.cursor(), table_index, table, index)?;
+            state.push1(value);
+        }
+        Operator::TableSet { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(&mut builder.func, *table, environ)?;
+            let (index, value) = state.pop2();
+            environ.translate_table_set(builder.cursor(), table_index, table, value, index)?;
+        }
+        Operator::TableGrow { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(&mut builder.func, *table, environ)?;
+            let delta = state.pop1();
+            let old_size =
+                environ.translate_table_grow(builder.cursor(), table_index, table, delta)?;
+            state.push1(old_size);
+        }
+        Operator::TableSize { table } => {
+            let table_index = TableIndex::from_u32(*table);
+            let table = state.get_table(&mut builder.func, *table, environ)?;
+            state.push1(environ.translate_table_size(builder.cursor(), table_index, table)?);
         }
         Operator::V128Const { value } => {
             let handle = builder.func.dfg.constants.insert(value.bytes().to_vec());
@@ -975,6 +1142,72 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 builder,
             ))
         }
+        Operator::V8x16Swizzle => {
+            let (vector, indices) = state.pop2();
+            let vector = optionally_bitcast_vector(vector, I8X16, builder);
+            let indices = optionally_bitcast_vector(indices, I8X16, builder);
+            state.push1(builder.ins().swizzle(vector, indices))
+        }
+        Operator::I8x16LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I16x8LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I32x4LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        }
+        | Operator::I64x2LoadSplat {
+            memarg: MemoryImmediate { flags: _, offset },
+        } => {
+            let ty = type_of(op);
+            translate_load(
+                *offset,
+                ir::Opcode::Load,
+                ty.lane_type(),
+                builder,
+                state,
+                environ,
+            )?;
+            let value_to_splat = state.pop1();
+            state.push1(builder.ins().splat(ty, value_to_splat))
+        }
+        Operator::F32x4Add | Operator::F64x2Add => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fadd(a, b))
+        }
+        Operator::F32x4Sub | Operator::F64x2Sub => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fsub(a, b))
+        }
+        Operator::F32x4Mul | Operator::F64x2Mul => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fmul(a, b))
+        }
+        Operator::F32x4Div | Operator::F64x2Div => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fdiv(a, b))
+        }
+        Operator::F32x4Min | Operator::F64x2Min => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fmin(a, b))
+        }
+        Operator::F32x4Max | Operator::F64x2Max => {
+            let (a, b) = pop2_with_bitcast(state, type_of(op), builder);
+            state.push1(builder.ins().fmax(a, b))
+        }
+        Operator::F32x4Sqrt | Operator::F64x2Sqrt => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().sqrt(a))
+        }
+        Operator::I32x4TruncSF32x4Sat => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_to_sint_sat(I32X4, a))
+        }
+        Operator::F32x4ConvertSI32x4 => {
+            let a = optionally_bitcast_vector(state.pop1(), type_of(op), builder);
+            state.push1(builder.ins().fcvt_from_sint(F32X4, a))
+        }
         Operator::V128Load { .. }
         | Operator::V128Store { .. }
         | Operator::V8x16Shuffle { .. }
@@ -1070,35 +1303,14 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         | Operator::I64x2Sub
         | Operator::F32x4Abs
         | Operator::F32x4Neg
-        | Operator::F32x4Sqrt
-        | Operator::F32x4Add
-        | Operator::F32x4Sub
-        | Operator::F32x4Mul
-        | Operator::F32x4Div
-        | Operator::F32x4Min
-        | Operator::F32x4Max
         | Operator::F64x2Abs
         | Operator::F64x2Neg
-        | Operator::F64x2Sqrt
-        | Operator::F64x2Add
-        | Operator::F64x2Sub
-        | Operator::F64x2Mul
-        | Operator::F64x2Div
-        | Operator::F64x2Min
-        | Operator::F64x2Max
-        | Operator::I32x4TruncSF32x4Sat
         | Operator::I32x4TruncUF32x4Sat
         | Operator::I64x2TruncSF64x2Sat
         | Operator::I64x2TruncUF64x2Sat
-        | Operator::F32x4ConvertSI32x4
         | Operator::F32x4ConvertUI32x4
         | Operator::F64x2ConvertSI64x2
-        | Operator::F64x2ConvertUI64x2 { .. }
-        | Operator::V8x16Swizzle
-        | Operator::I8x16LoadSplat { .. }
-        | Operator::I16x8LoadSplat { .. }
-        | Operator::I32x4LoadSplat { .. }
-        | Operator::I64x2LoadSplat { .. } => {
+        | Operator::F64x2ConvertUI64x2 { .. } => {
             wasm_unsupported!("proposed SIMD operator {:?}", op);
         }
     };
@@ -1110,24 +1322,33 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
 /// Deals with a Wasm instruction located in an unreachable portion of the code. Most of them
 /// are dropped but special ones like `End` or `Else` signal the potential end of the unreachable
 /// portion so the translation state must be updated accordingly.
-fn translate_unreachable_operator(
+fn translate_unreachable_operator<FE: FuncEnvironment + ?Sized>(
     op: &Operator,
     builder: &mut FunctionBuilder,
     state: &mut TranslationState,
-) {
+    environ: &mut FE,
+) -> WasmResult<()> {
     match *op {
-        Operator::If { ty: _ } => {
+        Operator::If { ty } => {
             // Push a placeholder control stack entry. The if isn't reachable,
             // so we don't have any branches anywhere.
-            state.push_if(ir::Inst::reserved_value(), ir::Ebb::reserved_value(), 0);
+            state.push_if(
+                ir::Inst::reserved_value(),
+                ir::Ebb::reserved_value(),
+                ty,
+                0,
+                0,
+            );
         }
         Operator::Loop { ty: _ } | Operator::Block { ty: _ } => {
-            state.push_block(ir::Ebb::reserved_value(), 0);
+            state.push_block(ir::Ebb::reserved_value(), 0, 0);
         }
         Operator::Else => {
             let i = state.control_stack.len() - 1;
             if let ControlStackFrame::If {
                 branch_inst,
+                blocktype,
+                original_stack_size,
                 ref mut reachable_from_top,
                 ..
             } = state.control_stack[i]
@@ -1139,11 +1360,15 @@ fn translate_unreachable_operator(
                     // branch from the top directly to the end.
                     *reachable_from_top = false;
 
-                    // We change the target of the branch instruction
-                    let else_ebb = builder.create_ebb();
+                    // We change the target of the branch instruction, which passes the
+                    // parameters of the if on to the else body.
+                    let (params, _) = blocktype_params_results(blocktype, environ)?;
+                    let else_ebb = ebb_with_params(builder, &params);
                     builder.change_jump_destination(branch_inst, else_ebb);
                     builder.seal_block(else_ebb);
                     builder.switch_to_block(else_ebb);
+                    state.stack.truncate(original_stack_size);
+                    state.stack.extend_from_slice(builder.ebb_params(else_ebb));
                 }
             }
         }
@@ -1188,6 +1413,16 @@ fn translate_unreachable_operator(
             // We don't translate because this is unreachable code
         }
     }
+    Ok(())
+}
+
+/// Create an `Ebb` with parameters of the given types, such as the destination of a block.
+fn ebb_with_params(builder: &mut FunctionBuilder, params: &[Type]) -> ir::Ebb {
+    let ebb = builder.create_ebb();
+    for ty in params {
+        builder.append_ebb_param(ebb, *ty);
+    }
+    ebb
 }
 
 /// Get the address+offset to use for a heap access.
@@ -1243,7 +1478,7 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     // Note that we don't set `is_aligned` here, even if the load instruction's
     // alignment immediate says it's aligned, because WebAssembly's immediate
     // field is just a hint, while Cranelift's aligned flag needs a guarantee.
-    let flags = MemFlags::new();
+    let flags = heap_flags();
     let (load, dfg) = builder
         .ins()
         .Load(opcode, result_ty, flags, offset.into(), base);
@@ -1251,6 +1486,13 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Get the flags of an access to the linear memory.
+fn heap_flags() -> MemFlags {
+    let mut flags = MemFlags::new();
+    flags.set_alias_region(Some(ir::AliasRegion::Heap));
+    flags
+}
+
 /// Translate a store instruction.
 fn translate_store<FE: FuncEnvironment + ?Sized>(
     offset: u32,
@@ -1266,13 +1508,168 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     let heap = state.get_heap(&mut builder.func, 0, environ)?;
     let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
     // See the comments in `translate_load` about the flags.
-    let flags = MemFlags::new();
+    let flags = heap_flags();
     builder
         .ins()
         .Store(opcode, val_ty, flags, offset.into(), val, base);
     Ok(())
 }
 
+/// Get the native address of an atomic heap access, trapping if the effective address isn't
+/// naturally aligned for `access_ty`.
+fn get_atomic_heap_addr(
+    heap: ir::Heap,
+    addr32: ir::Value,
+    offset: u32,
+    access_ty: Type,
+    addr_ty: Type,
+    builder: &mut FunctionBuilder,
+) -> ir::Value {
+    let access_size = i64::from(access_ty.bytes());
+    if access_size > 1 {
+        // The offset is added with wrapping arithmetic since only the low bits matter here.
+        let effective_addr = if offset == 0 {
+            addr32
+        } else {
+            builder.ins().iadd_imm(addr32, i64::from(offset))
+        };
+        let misalignment = builder.ins().band_imm(effective_addr, access_size - 1);
+        builder
+            .ins()
+            .trapnz(misalignment, ir::TrapCode::HeapMisaligned);
+    }
+
+    // The atomic instructions don't have an offset immediate.
+    let (base, offset) = get_heap_addr(heap, addr32, offset, addr_ty, builder);
+    if offset == 0 {
+        base
+    } else {
+        builder.ins().iadd_imm(base, i64::from(offset))
+    }
+}
+
+/// Translate an atomic load of `access_ty`, zero-extended to `widened_ty`.
+fn translate_atomic_load<FE: FuncEnvironment + ?Sized>(
+    widened_ty: Type,
+    access_ty: Type,
+    memarg: &MemoryImmediate,
+    builder: &mut FunctionBuilder,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let addr32 = state.pop1();
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(&mut builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(
+        heap,
+        addr32,
+        memarg.offset,
+        access_ty,
+        environ.pointer_type(),
+        builder,
+    );
+    // See the comments in `translate_load` about the flags.
+    let flags = heap_flags();
+    let mut loaded = builder.ins().atomic_load(access_ty, flags, addr, 0);
+    if access_ty != widened_ty {
+        loaded = builder.ins().uextend(widened_ty, loaded);
+    }
+    state.push1(loaded);
+    Ok(())
+}
+
+/// Translate an atomic store of the low `access_ty` bits of the operand.
+fn translate_atomic_store<FE: FuncEnvironment + ?Sized>(
+    access_ty: Type,
+    memarg: &MemoryImmediate,
+    builder: &mut FunctionBuilder,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (addr32, mut val) = state.pop2();
+    let heap = state.get_heap(&mut builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(
+        heap,
+        addr32,
+        memarg.offset,
+        access_ty,
+        environ.pointer_type(),
+        builder,
+    );
+    if builder.func.dfg.value_type(val) != access_ty {
+        val = builder.ins().ireduce(access_ty, val);
+    }
+    let flags = heap_flags();
+    builder.ins().atomic_store(flags, val, addr, 0);
+    Ok(())
+}
+
+/// Translate an atomic read-modify-write of `access_ty`, whose result is zero-extended to the
+/// type of the operand.
+fn translate_atomic_rmw<FE: FuncEnvironment + ?Sized>(
+    rmw_op: ir::AtomicRmwOp,
+    access_ty: Type,
+    memarg: &MemoryImmediate,
+    builder: &mut FunctionBuilder,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (addr32, mut arg) = state.pop2();
+    let heap = state.get_heap(&mut builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(
+        heap,
+        addr32,
+        memarg.offset,
+        access_ty,
+        environ.pointer_type(),
+        builder,
+    );
+    let widened_ty = builder.func.dfg.value_type(arg);
+    if widened_ty != access_ty {
+        arg = builder.ins().ireduce(access_ty, arg);
+    }
+    let flags = heap_flags();
+    let mut old = builder.ins().atomic_rmw(flags, rmw_op, addr, arg);
+    if widened_ty != access_ty {
+        old = builder.ins().uextend(widened_ty, old);
+    }
+    state.push1(old);
+    Ok(())
+}
+
+/// Translate an atomic compare and exchange of `access_ty`, whose result is zero-extended to the
+/// type of the operands.
+fn translate_atomic_cas<FE: FuncEnvironment + ?Sized>(
+    access_ty: Type,
+    memarg: &MemoryImmediate,
+    builder: &mut FunctionBuilder,
+    state: &mut TranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (addr32, mut expected, mut replacement) = state.pop3();
+    let heap = state.get_heap(&mut builder.func, 0, environ)?;
+    let addr = get_atomic_heap_addr(
+        heap,
+        addr32,
+        memarg.offset,
+        access_ty,
+        environ.pointer_type(),
+        builder,
+    );
+    let widened_ty = builder.func.dfg.value_type(expected);
+    if widened_ty != access_ty {
+        expected = builder.ins().ireduce(access_ty, expected);
+        replacement = builder.ins().ireduce(access_ty, replacement);
+    }
+    let flags = heap_flags();
+    let mut actual = builder.ins().atomic_cas(flags, addr, expected, replacement);
+    if widened_ty != access_ty {
+        actual = builder.ins().uextend(widened_ty, actual);
+    }
+    state.push1(actual);
+    Ok(())
+}
+
 fn translate_icmp(cc: IntCC, builder: &mut FunctionBuilder, state: &mut TranslationState) {
     let (arg0, arg1) = state.pop2();
     let val = builder.ins().icmp(cc, arg0, arg1);
@@ -1314,7 +1711,7 @@ fn translate_br_if_args(
         // code that comes after it
         frame.set_branched_to_exit();
         let return_count = if frame.is_loop() {
-            0
+            frame.num_param_values()
         } else {
             frame.num_return_values()
         };
@@ -1324,6 +1721,67 @@ fn translate_br_if_args(
     (br_destination, inputs)
 }
 
+/// Get the type of the memory accessed by an atomic read-modify-write or compare and exchange
+/// operator.
+fn atomic_rmw_access_type(operator: &Operator) -> Type {
+    match operator {
+        Operator::I32AtomicRmw8UAdd { .. }
+        | Operator::I64AtomicRmw8UAdd { .. }
+        | Operator::I32AtomicRmw8USub { .. }
+        | Operator::I64AtomicRmw8USub { .. }
+        | Operator::I32AtomicRmw8UAnd { .. }
+        | Operator::I64AtomicRmw8UAnd { .. }
+        | Operator::I32AtomicRmw8UOr { .. }
+        | Operator::I64AtomicRmw8UOr { .. }
+        | Operator::I32AtomicRmw8UXor { .. }
+        | Operator::I64AtomicRmw8UXor { .. }
+        | Operator::I32AtomicRmw8UXchg { .. }
+        | Operator::I64AtomicRmw8UXchg { .. }
+        | Operator::I32AtomicRmw8UCmpxchg { .. }
+        | Operator::I64AtomicRmw8UCmpxchg { .. } => I8,
+
+        Operator::I32AtomicRmw16UAdd { .. }
+        | Operator::I64AtomicRmw16UAdd { .. }
+        | Operator::I32AtomicRmw16USub { .. }
+        | Operator::I64AtomicRmw16USub { .. }
+        | Operator::I32AtomicRmw16UAnd { .. }
+        | Operator::I64AtomicRmw16UAnd { .. }
+        | Operator::I32AtomicRmw16UOr { .. }
+        | Operator::I64AtomicRmw16UOr { .. }
+        | Operator::I32AtomicRmw16UXor { .. }
+        | Operator::I64AtomicRmw16UXor { .. }
+        | Operator::I32AtomicRmw16UXchg { .. }
+        | Operator::I64AtomicRmw16UXchg { .. }
+        | Operator::I32AtomicRmw16UCmpxchg { .. }
+        | Operator::I64AtomicRmw16UCmpxchg { .. } => I16,
+
+        Operator::I32AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmw32UAdd { .. }
+        | Operator::I32AtomicRmwSub { .. }
+        | Operator::I64AtomicRmw32USub { .. }
+        | Operator::I32AtomicRmwAnd { .. }
+        | Operator::I64AtomicRmw32UAnd { .. }
+        | Operator::I32AtomicRmwOr { .. }
+        | Operator::I64AtomicRmw32UOr { .. }
+        | Operator::I32AtomicRmwXor { .. }
+        | Operator::I64AtomicRmw32UXor { .. }
+        | Operator::I32AtomicRmwXchg { .. }
+        | Operator::I64AtomicRmw32UXchg { .. }
+        | Operator::I32AtomicRmwCmpxchg { .. }
+        | Operator::I64AtomicRmw32UCmpxchg { .. } => I32,
+
+        Operator::I64AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmwSub { .. }
+        | Operator::I64AtomicRmwAnd { .. }
+        | Operator::I64AtomicRmwOr { .. }
+        | Operator::I64AtomicRmwXor { .. }
+        | Operator::I64AtomicRmwXchg { .. }
+        | Operator::I64AtomicRmwCmpxchg { .. } => I64,
+
+        _ => unreachable!("not an atomic read-modify-write operator: {:?}", operator),
+    }
+}
+
 /// Determine the returned value type of a WebAssembly operator
 fn type_of(operator: &Operator) -> Type {
     match operator {
@@ -1363,7 +1821,9 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I8x16Sub
         | Operator::I8x16SubSaturateS
         | Operator::I8x16SubSaturateU
-        | Operator::I8x16Mul => I8X16,
+        | Operator::I8x16Mul
+        | Operator::V8x16Swizzle
+        | Operator::I8x16LoadSplat { .. } => I8X16,
 
         Operator::I16x8Splat
         | Operator::I16x8ExtractLaneS { .. }
@@ -1391,7 +1851,8 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I16x8Sub
         | Operator::I16x8SubSaturateS
         | Operator::I16x8SubSaturateU
-        | Operator::I16x8Mul => I16X8,
+        | Operator::I16x8Mul
+        | Operator::I16x8LoadSplat { .. } => I16X8,
 
         Operator::I32x4Splat
         | Operator::I32x4ExtractLane { .. }
@@ -1416,7 +1877,8 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I32x4Sub
         | Operator::I32x4Mul
         | Operator::F32x4ConvertSI32x4
-        | Operator::F32x4ConvertUI32x4 => I32X4,
+        | Operator::F32x4ConvertUI32x4
+        | Operator::I32x4LoadSplat { .. } => I32X4,
 
         Operator::I64x2Splat
         | Operator::I64x2ExtractLane { .. }
@@ -1430,7 +1892,8 @@ fn type_of(operator: &Operator) -> Type {
         | Operator::I64x2Add
         | Operator::I64x2Sub
         | Operator::F64x2ConvertSI64x2
-        | Operator::F64x2ConvertUI64x2 => I64X2,
+        | Operator::F64x2ConvertUI64x2
+        | Operator::I64x2LoadSplat { .. } => I64X2,
 
         Operator::F32x4Splat
         | Operator::F32x4ExtractLane { .. }
@@ -1494,3 +1957,16 @@ fn optionally_bitcast_vector(
         value
     }
 }
+
+/// Pop two values from the stack and convert them to `needed_type` with a `raw_bitcast` if
+/// necessary.
+fn pop2_with_bitcast(
+    state: &mut TranslationState,
+    needed_type: Type,
+    builder: &mut FunctionBuilder,
+) -> (Value, Value) {
+    let (a, b) = state.pop2();
+    let a = optionally_bitcast_vector(a, needed_type, builder);
+    let b = optionally_bitcast_vector(b, needed_type, builder);
+    (a, b)
+}
@@ -8,7 +8,8 @@
 
 use crate::state::VisibleTranslationState;
 use crate::translation_utils::{
-    FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, SignatureIndex, Table, TableIndex,
+    DataIndex, ElemIndex, FuncIndex, Global, GlobalIndex, Memory, MemoryIndex, SignatureIndex,
+    Table, TableIndex,
 };
 use core::convert::From;
 use cranelift_codegen::cursor::FuncCursor;
@@ -18,6 +19,7 @@ use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_frontend::FunctionBuilder;
 use failure_derive::Fail;
 use std::boxed::Box;
+use std::vec::Vec;
 use wasmparser::BinaryReaderError;
 use wasmparser::Operator;
 
@@ -103,12 +105,9 @@ pub enum ReturnMode {
     FallthroughReturn,
 }
 
-/// Environment affecting the translation of a single WebAssembly function.
-///
-/// A `FuncEnvironment` trait object is required to translate a WebAssembly function to Cranelift
-/// IR. The function environment provides information about the WebAssembly module as well as the
-/// runtime environment.
-pub trait FuncEnvironment {
+/// Environment containing the information about the target that is shared by function and
+/// module translation.
+pub trait TargetEnvironment {
     /// Get the information needed to produce Cranelift IR for the given target.
     fn target_config(&self) -> TargetFrontendConfig;
 
@@ -124,13 +123,6 @@ pub trait FuncEnvironment {
         self.target_config().pointer_bytes()
     }
 
-    /// Should the code be structured to use a single `fallthrough_return` instruction at the end
-    /// of the function body, rather than `return` instructions as needed? This is used by VMs
-    /// to append custom epilogues.
-    fn return_mode(&self) -> ReturnMode {
-        ReturnMode::NormalReturns
-    }
-
     /// Get the Cranelift reference type to use for native references.
     ///
     /// This returns `R64` for 64-bit architectures and `R32` for 32-bit architectures.
@@ -141,6 +133,20 @@ pub trait FuncEnvironment {
             _ => panic!("unsupported pointer type"),
         }
     }
+}
+
+/// Environment affecting the translation of a single WebAssembly function.
+///
+/// A `FuncEnvironment` trait object is required to translate a WebAssembly function to Cranelift
+/// IR. The function environment provides information about the WebAssembly module as well as the
+/// runtime environment.
+pub trait FuncEnvironment: TargetEnvironment {
+    /// Should the code be structured to use a single `fallthrough_return` instruction at the end
+    /// of the function body, rather than `return` instructions as needed? This is used by VMs
+    /// to append custom epilogues.
+    fn return_mode(&self) -> ReturnMode {
+        ReturnMode::NormalReturns
+    }
 
     /// Set up the necessary preamble definitions in `func` to access the global variable
     /// identified by `index`.
@@ -182,6 +188,17 @@ pub trait FuncEnvironment {
         index: SignatureIndex,
     ) -> WasmResult<ir::SigRef>;
 
+    /// Get the parameter and result types of the WebAssembly signature `index`, which is used as
+    /// the type of a multi-value `block`, `loop` or `if`.
+    ///
+    /// Only the WebAssembly parameters and results are returned, without any of the
+    /// special-purpose arguments the environment adds to call signatures.
+    ///
+    /// The default implementation rejects multi-value block signatures.
+    fn block_signature(&self, index: SignatureIndex) -> WasmResult<(Vec<ir::Type>, Vec<ir::Type>)> {
+        wasm_unsupported!("multi-value block signature {:?}", index)
+    }
+
     /// Set up an external function definition in the preamble of `func` that can be used to
     /// directly call the function `index`.
     ///
@@ -266,6 +283,206 @@ pub trait FuncEnvironment {
         heap: ir::Heap,
     ) -> WasmResult<ir::Value>;
 
+    /// Translate a `memory.copy` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to copy within, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index.
+    ///
+    /// Copies `len` bytes from the address `src` to the address `dst`. The regions may overlap.
+    fn translate_memory_copy(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate a `memory.fill` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to fill, and `heap` is the heap reference
+    /// returned by `make_heap` for the same index.
+    ///
+    /// Sets `len` bytes from the address `dst` to the low byte of `val`.
+    fn translate_memory_fill(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate a `memory.init` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to initialize, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index.
+    ///
+    /// Copies `len` bytes from the offset `src` of the data segment `seg_index` to the address
+    /// `dst`.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_memory_init(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        seg_index: DataIndex,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate a `data.drop` WebAssembly instruction.
+    ///
+    /// Discards the contents of the data segment `seg_index`, which can no longer be used by
+    /// `memory.init`.
+    fn translate_data_drop(&mut self, pos: FuncCursor, seg_index: DataIndex) -> WasmResult<()>;
+
+    /// Translate a `table.get` WebAssembly instruction.
+    ///
+    /// The `table_index` provided identifies the table to read, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Returns the reference stored at `index`, as a value of the target's reference type. If the
+    /// access calls into the runtime, it should do so with a `call` so that a safepoint is emitted
+    /// there and the collector can find the live references.
+    fn translate_table_get(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+        index: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate a `table.set` WebAssembly instruction.
+    ///
+    /// The `table_index` provided identifies the table to write, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Stores the reference `value` at `index`. This is where a collector's write barrier goes.
+    fn translate_table_set(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+        value: ir::Value,
+        index: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate a `table.grow` WebAssembly instruction.
+    ///
+    /// The `table_index` provided identifies the table to grow, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// The `delta` value is the number of elements to add to the table.
+    ///
+    /// Returns the old size of the table, or -1 if it could not be grown.
+    fn translate_table_grow(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+        delta: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate a `table.size` WebAssembly instruction.
+    ///
+    /// The `table_index` provided identifies the table to query, and `table` is the table reference
+    /// returned by `make_table` for the same index.
+    ///
+    /// Returns the number of elements in the table.
+    fn translate_table_size(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate a `table.copy` WebAssembly instruction.
+    ///
+    /// The `dst_table` and `src_table` are the table references returned by `make_table` for the
+    /// `dst_table_index` and `src_table_index` tables.
+    ///
+    /// Copies `len` elements from the index `src` of the source table to the index `dst` of the
+    /// destination table. The ranges may overlap.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_table_copy(
+        &mut self,
+        pos: FuncCursor,
+        dst_table_index: TableIndex,
+        dst_table: ir::Table,
+        src_table_index: TableIndex,
+        src_table: ir::Table,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate a `table.init` WebAssembly instruction.
+    ///
+    /// The `table` is the table reference returned by `make_table` for `table_index`.
+    ///
+    /// Copies `len` elements from the index `src` of the element segment `seg_index` to the index
+    /// `dst` of the table.
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
+    fn translate_table_init(
+        &mut self,
+        pos: FuncCursor,
+        seg_index: ElemIndex,
+        table_index: TableIndex,
+        table: ir::Table,
+        dst: ir::Value,
+        src: ir::Value,
+        len: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate an `elem.drop` WebAssembly instruction.
+    ///
+    /// Discards the contents of the element segment `seg_index`, which can no longer be used by
+    /// `table.init`.
+    fn translate_elem_drop(&mut self, pos: FuncCursor, seg_index: ElemIndex) -> WasmResult<()>;
+
+    /// Translate an `i32.atomic.wait` or `i64.atomic.wait` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to wait on, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index. The type of `expected` tells the
+    /// two instructions apart.
+    ///
+    /// The `addr` value is the native address of the waited on location. It has already been
+    /// checked to be in bounds and naturally aligned. The `timeout` is an `i64` number of
+    /// nanoseconds, where a negative value means that the wait never times out.
+    ///
+    /// Returns 0 ("ok"), 1 ("not-equal") or 2 ("timed-out") as an `i32`.
+    fn translate_atomic_wait(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        addr: ir::Value,
+        expected: ir::Value,
+        timeout: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate an `atomic.notify` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to notify waiters on, and `heap` is the
+    /// heap reference returned by `make_heap` for the same index.
+    ///
+    /// The `addr` value is the native address of the location, checked like for
+    /// `translate_atomic_wait`. At most `count` waiters are woken up.
+    ///
+    /// Returns the number of waiters that were woken up as an `i32`.
+    fn translate_atomic_notify(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        addr: ir::Value,
+        count: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
@@ -301,10 +518,7 @@ pub trait FuncEnvironment {
 /// An object satisfying the `ModuleEnvironment` trait can be passed as argument to the
 /// [`translate_module`](fn.translate_module.html) function. These methods should not be called
 /// by the user, they are only for `cranelift-wasm` internal use.
-pub trait ModuleEnvironment<'data> {
-    /// Get the information needed to produce Cranelift IR for the current target.
-    fn target_config(&self) -> TargetFrontendConfig;
-
+pub trait ModuleEnvironment<'data>: TargetEnvironment {
     /// Provides the number of signatures up front. By default this does nothing, but
     /// implementations can use this to preallocate memory if desired.
     fn reserve_signatures(&mut self, _num: u32) -> WasmResult<()> {
@@ -443,6 +657,16 @@ pub trait ModuleEnvironment<'data> {
         elements: Box<[FuncIndex]>,
     ) -> WasmResult<()>;
 
+    /// Fills a passive element segment with references to functions in the module.
+    ///
+    /// The `elem_index` covers both active and passive element segments. Passive segments are
+    /// only used by the `table.init` and `elem.drop` instructions.
+    fn declare_passive_element(
+        &mut self,
+        elem_index: ElemIndex,
+        elements: Box<[FuncIndex]>,
+    ) -> WasmResult<()>;
+
     /// Provides the contents of a function body.
     ///
     /// Note there's no `reserve_function_bodies` function because the number of
@@ -468,6 +692,12 @@ pub trait ModuleEnvironment<'data> {
         data: &'data [u8],
     ) -> WasmResult<()>;
 
+    /// Provides the contents of a passive data segment.
+    ///
+    /// The `data_index` covers both active and passive data segments. Passive segments are only
+    /// used by the `memory.init` and `data.drop` instructions.
+    fn declare_passive_data(&mut self, data_index: DataIndex, data: &'data [u8]) -> WasmResult<()>;
+
     /// Declares the name of a function to the environment.
     ///
     /// By default this does nothing, but implementations can use this to read
@@ -1,6 +1,6 @@
 //! Translation skeleton that traverses the whole WebAssembly module and call helper functions
 //! to deal with each part of it.
-use crate::environ::{ModuleEnvironment, WasmError, WasmResult};
+use crate::environ::{ModuleEnvironment, WasmResult};
 use crate::sections_translator::{
     parse_code_section, parse_data_section, parse_element_section, parse_export_section,
     parse_function_section, parse_global_section, parse_import_section, parse_memory_section,
@@ -77,10 +77,9 @@ pub fn translate_module<'data>(
             }
 
             SectionCode::DataCount => {
-                return Err(WasmError::InvalidWebAssembly {
-                    message: "don't know how to handle the data count section yet",
-                    offset: reader.current_position(),
-                });
+                // The number of data segments is only needed to validate the `memory.init` and
+                // `data.drop` instructions, which refer to segments declared after the code.
+                section.get_data_count_section_content()?;
             }
 
             SectionCode::Custom {
@@ -9,8 +9,8 @@
 //! interpreted on the fly.
 use crate::environ::{ModuleEnvironment, WasmResult};
 use crate::translation_utils::{
-    tabletype_to_type, type_to_type, FuncIndex, Global, GlobalIndex, GlobalInit, Memory,
-    MemoryIndex, SignatureIndex, Table, TableElementType, TableIndex,
+    tabletype_to_type, type_to_type, DataIndex, ElemIndex, FuncIndex, Global, GlobalIndex,
+    GlobalInit, Memory, MemoryIndex, SignatureIndex, Table, TableElementType, TableIndex,
 };
 use crate::{wasm_unsupported, HashMap};
 use core::convert::TryFrom;
@@ -41,13 +41,15 @@ pub fn parse_type_section(
             } => {
                 let mut sig = Signature::new(environ.target_config().default_call_conv);
                 sig.params.extend(params.iter().map(|ty| {
-                    let cret_arg: ir::Type = type_to_type(*ty)
-                        .expect("only numeric types are supported in function signatures");
+                    let cret_arg: ir::Type = type_to_type(*ty, environ).expect(
+                        "only numeric and reference types are supported in function signatures",
+                    );
                     AbiParam::new(cret_arg)
                 }));
                 sig.returns.extend(returns.iter().map(|ty| {
-                    let cret_arg: ir::Type = type_to_type(*ty)
-                        .expect("only numeric types are supported in function signatures");
+                    let cret_arg: ir::Type = type_to_type(*ty, environ).expect(
+                        "only numeric and reference types are supported in function signatures",
+                    );
                     AbiParam::new(cret_arg)
                 }));
                 environ.declare_signature(sig)?;
@@ -95,7 +97,7 @@ pub fn parse_import_section<'data>(
             ImportSectionEntryType::Global(ref ty) => {
                 environ.declare_global_import(
                     Global {
-                        ty: type_to_type(ty.content_type).unwrap(),
+                        ty: type_to_type(ty.content_type, environ)?,
                         mutability: ty.mutable,
                         initializer: GlobalInit::Import,
                     },
@@ -106,7 +108,7 @@ pub fn parse_import_section<'data>(
             ImportSectionEntryType::Table(ref tab) => {
                 environ.declare_table_import(
                     Table {
-                        ty: match tabletype_to_type(tab.element_type)? {
+                        ty: match tabletype_to_type(tab.element_type, environ)? {
                             Some(t) => TableElementType::Val(t),
                             None => TableElementType::Func,
                         },
@@ -149,7 +151,7 @@ pub fn parse_table_section(
     for entry in tables {
         let table = entry?;
         environ.declare_table(Table {
-            ty: match tabletype_to_type(table.element_type)? {
+            ty: match tabletype_to_type(table.element_type, environ)? {
                 Some(t) => TableElementType::Val(t),
                 None => TableElementType::Func,
             },
@@ -201,6 +203,7 @@ pub fn parse_global_section(
             Operator::I64Const { value } => GlobalInit::I64Const(value),
             Operator::F32Const { value } => GlobalInit::F32Const(value.bits()),
             Operator::F64Const { value } => GlobalInit::F64Const(value.bits()),
+            Operator::RefNull => GlobalInit::RefNullConst,
             Operator::GetGlobal { global_index } => {
                 GlobalInit::GetGlobal(GlobalIndex::from_u32(global_index))
             }
@@ -209,7 +212,7 @@ pub fn parse_global_section(
             }
         };
         let global = Global {
-            ty: type_to_type(content_type).unwrap(),
+            ty: type_to_type(content_type, environ)?,
             mutability: mutable,
             initializer,
         };
@@ -266,37 +269,39 @@ pub fn parse_element_section<'data>(
 ) -> WasmResult<()> {
     environ.reserve_table_elements(elements.get_count())?;
 
-    for entry in elements {
+    for (index, entry) in elements.into_iter().enumerate() {
         let Element { kind, items } = entry?;
-        if let ElementKind::Active {
-            table_index,
-            init_expr,
-        } = kind
-        {
-            let mut init_expr_reader = init_expr.get_binary_reader();
-            let (base, offset) = match init_expr_reader.read_operator()? {
-                Operator::I32Const { value } => (None, value as u32 as usize),
-                Operator::GetGlobal { global_index } => {
-                    (Some(GlobalIndex::from_u32(global_index)), 0)
-                }
-                ref s => {
-                    wasm_unsupported!("unsupported init expr in element section: {:?}", s);
-                }
-            };
-            let items_reader = items.get_items_reader()?;
-            let mut elems = Vec::with_capacity(usize::try_from(items_reader.get_count()).unwrap());
-            for item in items_reader {
-                let x = item?;
-                elems.push(FuncIndex::from_u32(x));
+        let items_reader = items.get_items_reader()?;
+        let mut elems = Vec::with_capacity(usize::try_from(items_reader.get_count()).unwrap());
+        for item in items_reader {
+            let x = item?;
+            elems.push(FuncIndex::from_u32(x));
+        }
+        match kind {
+            ElementKind::Active {
+                table_index,
+                init_expr,
+            } => {
+                let mut init_expr_reader = init_expr.get_binary_reader();
+                let (base, offset) = match init_expr_reader.read_operator()? {
+                    Operator::I32Const { value } => (None, value as u32 as usize),
+                    Operator::GetGlobal { global_index } => {
+                        (Some(GlobalIndex::from_u32(global_index)), 0)
+                    }
+                    ref s => {
+                        wasm_unsupported!("unsupported init expr in element section: {:?}", s);
+                    }
+                };
+                environ.declare_table_elements(
+                    TableIndex::from_u32(table_index),
+                    base,
+                    offset,
+                    elems.into_boxed_slice(),
+                )?
+            }
+            ElementKind::Passive(_) => {
+                environ.declare_passive_element(ElemIndex::new(index), elems.into_boxed_slice())?
             }
-            environ.declare_table_elements(
-                TableIndex::from_u32(table_index),
-                base,
-                offset,
-                elems.into_boxed_slice(),
-            )?
-        } else {
-            wasm_unsupported!("unsupported passive elements section: {:?}", kind);
         }
     }
     Ok(())
@@ -323,29 +328,29 @@ pub fn parse_data_section<'data>(
 ) -> WasmResult<()> {
     environ.reserve_data_initializers(data.get_count())?;
 
-    for entry in data {
+    for (index, entry) in data.into_iter().enumerate() {
         let Data { kind, data } = entry?;
-        if let DataKind::Active {
-            memory_index,
-            init_expr,
-        } = kind
-        {
-            let mut init_expr_reader = init_expr.get_binary_reader();
-            let (base, offset) = match init_expr_reader.read_operator()? {
-                Operator::I32Const { value } => (None, value as u32 as usize),
-                Operator::GetGlobal { global_index } => {
-                    (Some(GlobalIndex::from_u32(global_index)), 0)
-                }
-                ref s => wasm_unsupported!("unsupported init expr in data section: {:?}", s),
-            };
-            environ.declare_data_initialization(
-                MemoryIndex::from_u32(memory_index),
-                base,
-                offset,
-                data,
-            )?;
-        } else {
-            wasm_unsupported!("unsupported passive data section: {:?}", kind);
+        match kind {
+            DataKind::Active {
+                memory_index,
+                init_expr,
+            } => {
+                let mut init_expr_reader = init_expr.get_binary_reader();
+                let (base, offset) = match init_expr_reader.read_operator()? {
+                    Operator::I32Const { value } => (None, value as u32 as usize),
+                    Operator::GetGlobal { global_index } => {
+                        (Some(GlobalIndex::from_u32(global_index)), 0)
+                    }
+                    ref s => wasm_unsupported!("unsupported init expr in data section: {:?}", s),
+                };
+                environ.declare_data_initialization(
+                    MemoryIndex::from_u32(memory_index),
+                    base,
+                    offset,
+                    data,
+                )?;
+            }
+            DataKind::Passive => environ.declare_passive_data(DataIndex::new(index), data)?,
         }
     }
 
@@ -13,17 +13,22 @@ use std::vec::Vec;
 /// fields:
 ///
 /// - `destination`: reference to the `Ebb` that will hold the code after the control block;
+/// - `num_param_values`: number of values taken by the control block from the value stack;
 /// - `num_return_values`: number of values returned by the control block;
-/// - `original_stack_size`: size of the value stack at the beginning of the control block.
+/// - `original_stack_size`: size of the value stack at the beginning of the control block, not
+///   counting its parameters.
 ///
 /// Moreover, the `if` frame has the `branch_inst` field that points to the `brz` instruction
-/// separating the `true` and `false` branch. The `loop` frame has a `header` field that references
-/// the `Ebb` that contains the beginning of the body of the loop.
+/// separating the `true` and `false` branch, and keeps its `blocktype` so that the `else` branch
+/// can be given the same parameters. The `loop` frame has a `header` field that references the
+/// `Ebb` that contains the beginning of the body of the loop.
 #[derive(Debug)]
 pub enum ControlStackFrame {
     If {
         destination: Ebb,
         branch_inst: Inst,
+        blocktype: wasmparser::TypeOrFuncType,
+        num_param_values: usize,
         num_return_values: usize,
         original_stack_size: usize,
         exit_is_branched_to: bool,
@@ -31,6 +36,7 @@ pub enum ControlStackFrame {
     },
     Block {
         destination: Ebb,
+        num_param_values: usize,
         num_return_values: usize,
         original_stack_size: usize,
         exit_is_branched_to: bool,
@@ -38,6 +44,7 @@ pub enum ControlStackFrame {
     Loop {
         destination: Ebb,
         header: Ebb,
+        num_param_values: usize,
         num_return_values: usize,
         original_stack_size: usize,
     },
@@ -45,6 +52,19 @@ pub enum ControlStackFrame {
 
 /// Helper methods for the control stack objects.
 impl ControlStackFrame {
+    pub fn num_param_values(&self) -> usize {
+        match *self {
+            ControlStackFrame::If {
+                num_param_values, ..
+            }
+            | ControlStackFrame::Block {
+                num_param_values, ..
+            }
+            | ControlStackFrame::Loop {
+                num_param_values, ..
+            } => num_param_values,
+        }
+    }
     pub fn num_return_values(&self) -> usize {
         match *self {
             ControlStackFrame::If {
@@ -215,6 +235,7 @@ impl TranslationState {
         self.clear();
         self.push_block(
             exit_block,
+            0,
             sig.returns
                 .iter()
                 .filter(|arg| arg.purpose == ir::ArgumentPurpose::Normal)
@@ -271,31 +292,56 @@ impl TranslationState {
     }
 
     /// Push a block on the control stack.
-    pub fn push_block(&mut self, following_code: Ebb, num_result_types: usize) {
+    pub fn push_block(
+        &mut self,
+        following_code: Ebb,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
+        debug_assert!(num_param_types <= self.stack.len());
         self.control_stack.push(ControlStackFrame::Block {
             destination: following_code,
-            original_stack_size: self.stack.len(),
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
             num_return_values: num_result_types,
             exit_is_branched_to: false,
         });
     }
 
     /// Push a loop on the control stack.
-    pub fn push_loop(&mut self, header: Ebb, following_code: Ebb, num_result_types: usize) {
+    pub fn push_loop(
+        &mut self,
+        header: Ebb,
+        following_code: Ebb,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
+        debug_assert!(num_param_types <= self.stack.len());
         self.control_stack.push(ControlStackFrame::Loop {
             header,
             destination: following_code,
-            original_stack_size: self.stack.len(),
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
             num_return_values: num_result_types,
         });
     }
 
     /// Push an if on the control stack.
-    pub fn push_if(&mut self, branch_inst: Inst, following_code: Ebb, num_result_types: usize) {
+    pub fn push_if(
+        &mut self,
+        branch_inst: Inst,
+        following_code: Ebb,
+        blocktype: wasmparser::TypeOrFuncType,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
+        debug_assert!(num_param_types <= self.stack.len());
         self.control_stack.push(ControlStackFrame::If {
             branch_inst,
             destination: following_code,
-            original_stack_size: self.stack.len(),
+            blocktype,
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
             num_return_values: num_result_types,
             exit_is_branched_to: false,
             reachable_from_top: self.reachable,
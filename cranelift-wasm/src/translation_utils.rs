@@ -1,11 +1,12 @@
 //! Helper functions and structures for the translation.
-use crate::environ::WasmResult;
+use crate::environ::{FuncEnvironment, TargetEnvironment, WasmResult};
 use crate::wasm_unsupported;
 use core::u32;
 use cranelift_codegen::entity::entity_impl;
 use cranelift_codegen::ir;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
+use std::vec::Vec;
 use wasmparser;
 
 /// Index type of a function (imported or defined) inside the WebAssembly module.
@@ -54,6 +55,16 @@ entity_impl!(MemoryIndex);
 pub struct SignatureIndex(u32);
 entity_impl!(SignatureIndex);
 
+/// Index type of a data segment (active or passive) inside the WebAssembly module.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct DataIndex(u32);
+entity_impl!(DataIndex);
+
+/// Index type of an element segment (active or passive) inside the WebAssembly module.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct ElemIndex(u32);
+entity_impl!(ElemIndex);
+
 /// WebAssembly global.
 #[derive(Debug, Clone, Copy, Hash)]
 pub struct Global {
@@ -76,6 +87,8 @@ pub enum GlobalInit {
     F32Const(u32),
     /// An `f64.const`.
     F64Const(u64),
+    /// A `ref.null`.
+    RefNullConst,
     /// A `get_global` of another global.
     GetGlobal(GlobalIndex),
     ///< The global is imported from, and thus initialized by, a different module.
@@ -114,44 +127,60 @@ pub struct Memory {
 }
 
 /// Helper function translating wasmparser types to Cranelift types when possible.
-pub fn type_to_type(ty: wasmparser::Type) -> WasmResult<ir::Type> {
+///
+/// Reference types are translated to the target's reference type, as given by `environ`.
+pub fn type_to_type<PE: TargetEnvironment + ?Sized>(
+    ty: wasmparser::Type,
+    environ: &PE,
+) -> WasmResult<ir::Type> {
     match ty {
         wasmparser::Type::I32 => Ok(ir::types::I32),
         wasmparser::Type::I64 => Ok(ir::types::I64),
         wasmparser::Type::F32 => Ok(ir::types::F32),
         wasmparser::Type::F64 => Ok(ir::types::F64),
+        wasmparser::Type::AnyRef | wasmparser::Type::AnyFunc => Ok(environ.reference_type()),
         ty => wasm_unsupported!("type_to_type: wasm type {:?}", ty),
     }
 }
 
 /// Helper function translating wasmparser possible table types to Cranelift types when possible,
 /// or None for Func tables.
-pub fn tabletype_to_type(ty: wasmparser::Type) -> WasmResult<Option<ir::Type>> {
+pub fn tabletype_to_type<PE: TargetEnvironment + ?Sized>(
+    ty: wasmparser::Type,
+    environ: &PE,
+) -> WasmResult<Option<ir::Type>> {
     match ty {
         wasmparser::Type::I32 => Ok(Some(ir::types::I32)),
         wasmparser::Type::I64 => Ok(Some(ir::types::I64)),
         wasmparser::Type::F32 => Ok(Some(ir::types::F32)),
         wasmparser::Type::F64 => Ok(Some(ir::types::F64)),
+        wasmparser::Type::AnyRef => Ok(Some(environ.reference_type())),
         wasmparser::Type::AnyFunc => Ok(None),
         ty => wasm_unsupported!("tabletype_to_type: table wasm type {:?}", ty),
     }
 }
 
-/// Helper function translating wasmparser block signatures to Cranelift types when possible.
-pub fn blocktype_to_type(ty_or_ft: wasmparser::TypeOrFuncType) -> WasmResult<Option<ir::Type>> {
+/// Get the parameter and result types of a wasmparser block signature.
+///
+/// Multi-value block signatures refer to a function type, which is looked up in `environ`.
+pub fn blocktype_params_results<FE: FuncEnvironment + ?Sized>(
+    ty_or_ft: wasmparser::TypeOrFuncType,
+    environ: &FE,
+) -> WasmResult<(Vec<ir::Type>, Vec<ir::Type>)> {
     match ty_or_ft {
         wasmparser::TypeOrFuncType::Type(ty) => match ty {
-            wasmparser::Type::I32 => Ok(Some(ir::types::I32)),
-            wasmparser::Type::I64 => Ok(Some(ir::types::I64)),
-            wasmparser::Type::F32 => Ok(Some(ir::types::F32)),
-            wasmparser::Type::F64 => Ok(Some(ir::types::F64)),
-            wasmparser::Type::EmptyBlockType => Ok(None),
-            ty => wasm_unsupported!("blocktype_to_type: type {:?}", ty),
+            wasmparser::Type::EmptyBlockType => Ok((Vec::new(), Vec::new())),
+            wasmparser::Type::I32
+            | wasmparser::Type::I64
+            | wasmparser::Type::F32
+            | wasmparser::Type::F64
+            | wasmparser::Type::AnyRef
+            | wasmparser::Type::AnyFunc => Ok((Vec::new(), vec![type_to_type(ty, environ)?])),
+            ty => wasm_unsupported!("blocktype_params_results: type {:?}", ty),
         },
-        wasmparser::TypeOrFuncType::FuncType(_) => wasm_unsupported!(
-            "blocktype_to_type: multi-value block signature {:?}",
-            ty_or_ft
-        ),
+        wasmparser::TypeOrFuncType::FuncType(index) => {
+            environ.block_signature(SignatureIndex::from_u32(index))
+        }
     }
 }
 
@@ -165,23 +194,6 @@ pub fn f64_translation(x: wasmparser::Ieee64) -> ir::immediates::Ieee64 {
     ir::immediates::Ieee64::with_bits(x.bits())
 }
 
-/// Translate a `wasmparser` type into its `Cranelift` equivalent, when possible
-pub fn num_return_values(ty: wasmparser::TypeOrFuncType) -> WasmResult<usize> {
-    match ty {
-        wasmparser::TypeOrFuncType::Type(ty) => match ty {
-            wasmparser::Type::EmptyBlockType => Ok(0),
-            wasmparser::Type::I32
-            | wasmparser::Type::F32
-            | wasmparser::Type::I64
-            | wasmparser::Type::F64 => Ok(1),
-            ty => wasm_unsupported!("unsupported return value type {:?}", ty),
-        },
-        wasmparser::TypeOrFuncType::FuncType(_) => {
-            wasm_unsupported!("multi-value block signature {:?}", ty);
-        }
-    }
-}
-
 /// Special VMContext value label. It is tracked as 0xffff_fffe label.
 pub fn get_vmctx_value_label() -> ir::ValueLabel {
     const VMCTX_LABEL: u32 = 0xffff_fffe;
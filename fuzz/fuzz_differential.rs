@@ -0,0 +1,162 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate cranelift_codegen;
+extern crate cranelift_filetests;
+extern crate cranelift_frontend;
+extern crate cranelift_native;
+
+mod generator;
+
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::ir::Function;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::verifier::verify_function;
+use cranelift_codegen::{CodegenResult, Context};
+use cranelift_filetests::FunctionRunner;
+use generator::Input;
+use std::fmt;
+
+/// An optimization pass run on a function before compiling it, on top of the passes enabled by
+/// the optimization level.
+#[derive(Clone, Copy, Debug)]
+enum Pass {
+    Preopt,
+    Licm,
+    SimpleGvn,
+}
+
+/// A way of compiling the generated functions.
+struct Config {
+    opt_level: &'static str,
+    passes: &'static [Pass],
+}
+
+/// Every configuration must compute the same results as the first one, which optimizes nothing.
+const CONFIGS: &[Config] = &[
+    Config {
+        opt_level: "fastest",
+        passes: &[],
+    },
+    Config {
+        opt_level: "fastest",
+        passes: &[Pass::Preopt],
+    },
+    Config {
+        opt_level: "fastest",
+        passes: &[Pass::Licm],
+    },
+    Config {
+        opt_level: "fastest",
+        passes: &[Pass::SimpleGvn],
+    },
+    Config {
+        opt_level: "fastest",
+        passes: &[Pass::Preopt, Pass::Licm, Pass::SimpleGvn],
+    },
+    Config {
+        opt_level: "default",
+        passes: &[],
+    },
+    Config {
+        opt_level: "best",
+        passes: &[],
+    },
+    Config {
+        opt_level: "best",
+        passes: &[Pass::Preopt, Pass::Licm, Pass::SimpleGvn],
+    },
+];
+
+/// The number of calls made to each generated function, each with different arguments.
+const CALLS: usize = 4;
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "opt_level={}", self.opt_level)?;
+        if !self.passes.is_empty() {
+            write!(f, " with {:?}", self.passes)?;
+        }
+        Ok(())
+    }
+}
+
+fn host_isa(opt_level: &str) -> Box<dyn TargetIsa> {
+    let mut flags = settings::builder();
+    flags.set("opt_level", opt_level).unwrap();
+    let isa_builder = cranelift_native::builder().expect("unsupported host");
+    isa_builder.finish(settings::Flags::new(flags))
+}
+
+/// Run `passes` over `func`, verifying it after each of them.
+fn optimize(func: Function, passes: &[Pass], isa: &dyn TargetIsa) -> CodegenResult<Function> {
+    let mut context = Context::for_function(func);
+    for pass in passes {
+        context.flowgraph();
+        match pass {
+            Pass::Preopt => context.preopt(isa)?,
+            Pass::Licm => {
+                context.compute_loop_analysis();
+                context.licm(isa)?
+            }
+            Pass::SimpleGvn => context.simple_gvn(isa)?,
+        }
+    }
+    Ok(context.func)
+}
+
+/// Do two calls return the same values? NaNs are all considered equal, since their payloads
+/// depend on which instructions produced them.
+fn same_results(a: &[DataValue], b: &[DataValue]) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|pair| match pair {
+            (DataValue::F32(x), DataValue::F32(y)) => {
+                x == y || f32::from_bits(x.bits()).is_nan() && f32::from_bits(y.bits()).is_nan()
+            }
+            (DataValue::F64(x), DataValue::F64(y)) => {
+                x == y || f64::from_bits(x.bits()).is_nan() && f64::from_bits(y.bits()).is_nan()
+            }
+            (x, y) => x == y,
+        })
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut input = Input::new(data);
+    let isa = host_isa("fastest");
+    let func = generator::generate(&mut input, isa.default_call_conv());
+    if let Err(errors) = verify_function(&func, isa.as_ref()) {
+        panic!("generated an invalid function:\n{}\n{}", func, errors);
+    }
+    let calls: Vec<Vec<DataValue>> = (0..CALLS)
+        .map(|_| generator::arguments(&mut input, &func.signature))
+        .collect();
+
+    let mut reference: Option<(&Config, Vec<Vec<DataValue>>)> = None;
+    for config in CONFIGS {
+        let isa = host_isa(config.opt_level);
+        let optimized = optimize(func.clone(), config.passes, isa.as_ref())
+            .unwrap_or_else(|err| panic!("{} failed on:\n{}\n{}", config, func, err));
+        let compiled = FunctionRunner::new(optimized, isa)
+            .compile()
+            .unwrap_or_else(|err| panic!("{} failed to compile:\n{}\n{}", config, func, err));
+        let results: Vec<Vec<DataValue>> = calls
+            .iter()
+            .map(|args| compiled.call(args).unwrap())
+            .collect();
+
+        match reference {
+            None => reference = Some((config, results)),
+            Some((reference_config, ref expected)) => {
+                for ((args, expected), actual) in calls.iter().zip(expected).zip(&results) {
+                    if !same_results(expected, actual) {
+                        panic!(
+                            "{} and {} disagree on:\n{}\narguments: {:?}\n{:?} != {:?}",
+                            reference_config, config, func, args, expected, actual
+                        );
+                    }
+                }
+            }
+        }
+    }
+});
@@ -0,0 +1,486 @@
+//! A generator of well-formed Cranelift IR functions, driven by the fuzzer's input.
+//!
+//! Functions are built with a `FunctionBuilder` from structured statements over a set of typed
+//! variables: assignments, conditionals and counted loops. This way every generated function
+//! verifies and terminates. No instruction that can trap is ever generated either: divisors are
+//! forced to be positive and float to integer conversions saturate, so any trap is a bug.
+
+use cranelift_codegen::data_value::DataValue;
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::immediates::{Ieee32, Ieee64};
+use cranelift_codegen::ir::types::*;
+use cranelift_codegen::ir::{
+    AbiParam, ExternalName, Function, InstBuilder, Signature, Type, Value,
+};
+use cranelift_codegen::isa::CallConv;
+use cranelift_frontend::{FunctionBuilder, Variable};
+
+/// Types of the values generated functions compute with.
+const TYPES: [Type; 6] = [I8, I16, I32, I64, F32, F64];
+
+const INT_CCS: [IntCC; 10] = [
+    IntCC::Equal,
+    IntCC::NotEqual,
+    IntCC::SignedLessThan,
+    IntCC::SignedGreaterThanOrEqual,
+    IntCC::SignedGreaterThan,
+    IntCC::SignedLessThanOrEqual,
+    IntCC::UnsignedLessThan,
+    IntCC::UnsignedGreaterThanOrEqual,
+    IntCC::UnsignedGreaterThan,
+    IntCC::UnsignedLessThanOrEqual,
+];
+
+const FLOAT_CCS: [FloatCC; 14] = [
+    FloatCC::Ordered,
+    FloatCC::Unordered,
+    FloatCC::Equal,
+    FloatCC::NotEqual,
+    FloatCC::OrderedNotEqual,
+    FloatCC::UnorderedOrEqual,
+    FloatCC::LessThan,
+    FloatCC::LessThanOrEqual,
+    FloatCC::GreaterThan,
+    FloatCC::GreaterThanOrEqual,
+    FloatCC::UnorderedOrLessThan,
+    FloatCC::UnorderedOrLessThanOrEqual,
+    FloatCC::UnorderedOrGreaterThan,
+    FloatCC::UnorderedOrGreaterThanOrEqual,
+];
+
+const MAX_PARAMS: usize = 4;
+const MAX_RETURNS: usize = 2;
+const MAX_LOCALS: usize = 6;
+const MAX_STATEMENTS: usize = 8;
+const MAX_DEPTH: usize = 3;
+const MAX_TRIP_COUNT: usize = 8;
+
+/// A source of choices, reading the fuzzer's input a byte at a time.
+///
+/// Once the input is exhausted every choice is the first one, so generation always terminates.
+pub struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => {
+                self.data = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    /// Choose a number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        usize::from(self.byte()) % n
+    }
+
+    /// Choose one of `items`.
+    fn choose<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    /// Choose 64 arbitrary bits, favoring the edge cases of integer arithmetic.
+    fn bits(&mut self) -> i64 {
+        match self.byte() {
+            0 => 0,
+            1 => 1,
+            2 => -1,
+            3 => i64::min_value(),
+            4 => i64::max_value(),
+            5 => i64::from(i32::min_value()),
+            6 => i64::from(i32::max_value()),
+            _ => (0..8).fold(0, |bits, _| bits << 8 | i64::from(self.byte())),
+        }
+    }
+
+    /// Choose an argument of type `ty`.
+    fn value(&mut self, ty: Type) -> DataValue {
+        let bits = self.bits();
+        match ty {
+            F32 => DataValue::F32(Ieee32::with_bits(bits as u32)),
+            F64 => DataValue::F64(Ieee64::with_bits(bits as u64)),
+            _ => DataValue::from_integer(i128::from(bits), ty).unwrap(),
+        }
+    }
+}
+
+/// Generate a function with the calling convention `call_conv`.
+pub fn generate(input: &mut Input, call_conv: CallConv) -> Function {
+    let mut sig = Signature::new(call_conv);
+    for _ in 0..input.below(MAX_PARAMS + 1) {
+        sig.params.push(AbiParam::new(input.choose(&TYPES)));
+    }
+    for _ in 0..1 + input.below(MAX_RETURNS) {
+        sig.returns.push(AbiParam::new(input.choose(&TYPES)));
+    }
+
+    let func = Function::with_name_signature(ExternalName::testcase("fuzz"), sig);
+    let mut generator = FunctionGenerator {
+        input,
+        builder: FunctionBuilder::new(func),
+        vars: Vec::new(),
+        num_vars: 0,
+    };
+    generator.generate()
+}
+
+/// Generate arguments for a call to a function with `sig`.
+pub fn arguments(input: &mut Input, sig: &Signature) -> Vec<DataValue> {
+    sig.params
+        .iter()
+        .map(|param| input.value(param.value_type))
+        .collect()
+}
+
+struct FunctionGenerator<'r, 'a> {
+    input: &'r mut Input<'a>,
+    builder: FunctionBuilder,
+    /// The variables statements may assign to and read from.
+    vars: Vec<(Variable, Type)>,
+    /// The number of variables declared so far, including loop counters.
+    num_vars: usize,
+}
+
+impl<'r, 'a> FunctionGenerator<'r, 'a> {
+    fn generate(&mut self) -> Function {
+        let sig = self.builder.func.signature.clone();
+        let entry = self.builder.create_ebb();
+        self.builder.append_ebb_params_for_function_params(entry);
+        self.builder.switch_to_block(entry);
+        self.builder.seal_block(entry);
+
+        let params = self.builder.ebb_params(entry).to_vec();
+        for (param, abi) in params.into_iter().zip(&sig.params) {
+            let var = self.declare(abi.value_type);
+            self.vars.push((var, abi.value_type));
+            self.builder.def_var(var, param);
+        }
+        for _ in 0..1 + self.input.below(MAX_LOCALS) {
+            let ty = self.input.choose(&TYPES);
+            let var = self.declare(ty);
+            self.vars.push((var, ty));
+            let value = self.constant(ty);
+            self.builder.def_var(var, value);
+        }
+
+        self.statements(0);
+
+        let results: Vec<Value> = sig
+            .returns
+            .iter()
+            .map(|ret| self.operand(ret.value_type))
+            .collect();
+        self.builder.ins().return_(&results);
+        self.builder.finalize()
+    }
+
+    fn declare(&mut self, ty: Type) -> Variable {
+        let var = Variable::new(self.num_vars);
+        self.num_vars += 1;
+        self.builder.declare_var(var, ty);
+        var
+    }
+
+    fn statements(&mut self, depth: usize) {
+        for _ in 0..self.input.below(MAX_STATEMENTS + 1) {
+            let kinds = if depth < MAX_DEPTH { 4 } else { 2 };
+            match self.input.below(kinds) {
+                0 | 1 => self.assignment(),
+                2 => self.conditional(depth),
+                _ => self.counted_loop(depth),
+            }
+        }
+    }
+
+    fn assignment(&mut self) {
+        let (var, ty) = self.input.choose(&self.vars);
+        let value = self.expression(ty);
+        self.builder.def_var(var, value);
+    }
+
+    fn conditional(&mut self, depth: usize) {
+        let cond_ty = self.input.choose(&[I32, I64]);
+        let cond = self.operand(cond_ty);
+        let then_ebb = self.builder.create_ebb();
+        let else_ebb = self.builder.create_ebb();
+        let merge = self.builder.create_ebb();
+        self.builder.ins().brz(cond, else_ebb, &[]);
+        self.builder.ins().jump(then_ebb, &[]);
+
+        for &ebb in &[then_ebb, else_ebb] {
+            self.builder.switch_to_block(ebb);
+            self.builder.seal_block(ebb);
+            self.statements(depth + 1);
+            self.builder.ins().jump(merge, &[]);
+        }
+
+        self.builder.switch_to_block(merge);
+        self.builder.seal_block(merge);
+    }
+
+    fn counted_loop(&mut self, depth: usize) {
+        // The counter isn't in `vars`, so the body can't change the trip count.
+        let counter = self.declare(I32);
+        let trip_count = self.input.below(MAX_TRIP_COUNT + 1) as i64;
+        let initial = self.builder.ins().iconst(I32, trip_count);
+        self.builder.def_var(counter, initial);
+
+        let header = self.builder.create_ebb();
+        let body = self.builder.create_ebb();
+        let exit = self.builder.create_ebb();
+        self.builder.ins().jump(header, &[]);
+
+        self.builder.switch_to_block(header);
+        let remaining = self.builder.use_var(counter);
+        self.builder.ins().brz(remaining, exit, &[]);
+        self.builder.ins().jump(body, &[]);
+
+        self.builder.switch_to_block(body);
+        self.builder.seal_block(body);
+        self.statements(depth + 1);
+        let remaining = self.builder.use_var(counter);
+        let remaining = self.builder.ins().iadd_imm(remaining, -1);
+        self.builder.def_var(counter, remaining);
+        self.builder.ins().jump(header, &[]);
+        self.builder.seal_block(header);
+
+        self.builder.switch_to_block(exit);
+        self.builder.seal_block(exit);
+    }
+
+    /// Read a variable of type `ty`, or make up a constant.
+    fn operand(&mut self, ty: Type) -> Value {
+        let candidates: Vec<Variable> = self
+            .vars
+            .iter()
+            .filter(|&&(_, var_ty)| var_ty == ty)
+            .map(|&(var, _)| var)
+            .collect();
+        if candidates.is_empty() || self.input.below(4) == 3 {
+            self.constant(ty)
+        } else {
+            let var = self.input.choose(&candidates);
+            self.builder.use_var(var)
+        }
+    }
+
+    fn constant(&mut self, ty: Type) -> Value {
+        let bits = self.input.bits();
+        match ty {
+            F32 => self.builder.ins().f32const(Ieee32::with_bits(bits as u32)),
+            F64 => self.builder.ins().f64const(Ieee64::with_bits(bits as u64)),
+            _ => {
+                // Keep immediates sign-extended from the width of the type.
+                let shift = 64 - ty.bits();
+                self.builder.ins().iconst(ty, bits << shift >> shift)
+            }
+        }
+    }
+
+    fn expression(&mut self, ty: Type) -> Value {
+        if ty.is_float() {
+            self.float_expression(ty)
+        } else if ty.bits() < 32 {
+            self.narrow_expression(ty)
+        } else {
+            self.int_expression(ty)
+        }
+    }
+
+    /// Compute an `i8` or `i16`, which have few instructions of their own on most targets.
+    fn narrow_expression(&mut self, ty: Type) -> Value {
+        match self.input.below(3) {
+            0 => {
+                let wide = self.int_expression(I32);
+                self.builder.ins().ireduce(ty, wide)
+            }
+            1 => self.int_conversion(ty),
+            _ => self.operand(ty),
+        }
+    }
+
+    fn int_expression(&mut self, ty: Type) -> Value {
+        match self.input.below(10) {
+            0 => {
+                let x = self.operand(ty);
+                let y = self.operand(ty);
+                let mut ins = self.builder.ins();
+                match self.input.below(6) {
+                    0 => ins.iadd(x, y),
+                    1 => ins.isub(x, y),
+                    2 => ins.imul(x, y),
+                    3 => ins.band(x, y),
+                    4 => ins.bor(x, y),
+                    _ => ins.bxor(x, y),
+                }
+            }
+            1 => {
+                let x = self.operand(ty);
+                let y = self.operand(ty);
+                let mut ins = self.builder.ins();
+                match self.input.below(5) {
+                    0 => ins.ishl(x, y),
+                    1 => ins.ushr(x, y),
+                    2 => ins.sshr(x, y),
+                    3 => ins.rotl(x, y),
+                    _ => ins.rotr(x, y),
+                }
+            }
+            2 => {
+                let x = self.operand(ty);
+                let imm = self.input.bits();
+                let mut ins = self.builder.ins();
+                match self.input.below(7) {
+                    0 => ins.iadd_imm(x, imm),
+                    1 => ins.imul_imm(x, imm),
+                    2 => ins.band_imm(x, imm),
+                    3 => ins.bor_imm(x, imm),
+                    4 => ins.bxor_imm(x, imm),
+                    5 => ins.ishl_imm(x, imm & 63),
+                    _ => ins.sshr_imm(x, imm & 63),
+                }
+            }
+            3 => {
+                let x = self.operand(ty);
+                let mut ins = self.builder.ins();
+                match self.input.below(4) {
+                    0 => ins.bnot(x),
+                    1 => ins.popcnt(x),
+                    2 => ins.clz(x),
+                    _ => ins.ctz(x),
+                }
+            }
+            4 => {
+                let x = self.operand(ty);
+                let y = self.operand(ty);
+                // Keep the divisor positive so that neither division by zero nor the overflow of
+                // dividing the smallest integer by -1 can trap.
+                let max = (!0u64 >> (65 - ty.bits())) as i64;
+                let y = self.builder.ins().band_imm(y, max);
+                let y = self.builder.ins().bor_imm(y, 1);
+                let signed = self.input.below(2) == 0;
+                let mut ins = self.builder.ins();
+                match (signed, self.input.below(2) == 0) {
+                    (false, false) => ins.udiv(x, y),
+                    (false, true) => ins.urem(x, y),
+                    (true, false) => ins.sdiv(x, y),
+                    (true, true) => ins.srem(x, y),
+                }
+            }
+            5 => {
+                let cc = self.input.choose(&INT_CCS);
+                let x = self.operand(ty);
+                let y = self.operand(ty);
+                let cond = self.builder.ins().icmp(cc, x, y);
+                let a = self.operand(ty);
+                let b = self.operand(ty);
+                self.builder.ins().select(cond, a, b)
+            }
+            6 => self.int_conversion(ty),
+            7 => {
+                let from = self.input.choose(&[F32, F64]);
+                let x = self.operand(from);
+                if self.input.below(2) == 0 {
+                    self.builder.ins().fcvt_to_sint_sat(ty, x)
+                } else {
+                    self.builder.ins().fcvt_to_uint_sat(ty, x)
+                }
+            }
+            8 => {
+                let cc = self.input.choose(&FLOAT_CCS);
+                let from = self.input.choose(&[F32, F64]);
+                let x = self.operand(from);
+                let y = self.operand(from);
+                let cond = self.builder.ins().fcmp(cc, x, y);
+                self.builder.ins().bint(ty, cond)
+            }
+            _ => self.operand(ty),
+        }
+    }
+
+    /// Convert a value of another integer type to `ty`.
+    fn int_conversion(&mut self, ty: Type) -> Value {
+        let from = self.input.choose(&[I8, I16, I32, I64]);
+        let x = self.operand(from);
+        if from.bits() > ty.bits() {
+            self.builder.ins().ireduce(ty, x)
+        } else if from.bits() < ty.bits() {
+            if self.input.below(2) == 0 {
+                self.builder.ins().sextend(ty, x)
+            } else {
+                self.builder.ins().uextend(ty, x)
+            }
+        } else {
+            x
+        }
+    }
+
+    fn float_expression(&mut self, ty: Type) -> Value {
+        match self.input.below(6) {
+            0 => {
+                let x = self.operand(ty);
+                let y = self.operand(ty);
+                let mut ins = self.builder.ins();
+                match self.input.below(7) {
+                    0 => ins.fadd(x, y),
+                    1 => ins.fsub(x, y),
+                    2 => ins.fmul(x, y),
+                    3 => ins.fdiv(x, y),
+                    4 => ins.fmin(x, y),
+                    5 => ins.fmax(x, y),
+                    _ => ins.fcopysign(x, y),
+                }
+            }
+            1 => {
+                let x = self.operand(ty);
+                let mut ins = self.builder.ins();
+                match self.input.below(7) {
+                    0 => ins.fabs(x),
+                    1 => ins.fneg(x),
+                    2 => ins.sqrt(x),
+                    3 => ins.ceil(x),
+                    4 => ins.floor(x),
+                    5 => ins.trunc(x),
+                    _ => ins.nearest(x),
+                }
+            }
+            2 => {
+                let from = self.input.choose(&[I32, I64]);
+                let x = self.operand(from);
+                if self.input.below(2) == 0 {
+                    self.builder.ins().fcvt_from_sint(ty, x)
+                } else {
+                    self.builder.ins().fcvt_from_uint(ty, x)
+                }
+            }
+            3 => {
+                if ty == F32 {
+                    let x = self.operand(F64);
+                    self.builder.ins().fdemote(F32, x)
+                } else {
+                    let x = self.operand(F32);
+                    self.builder.ins().fpromote(F64, x)
+                }
+            }
+            4 => {
+                let cc = self.input.choose(&FLOAT_CCS);
+                let x = self.operand(ty);
+                let y = self.operand(ty);
+                let cond = self.builder.ins().fcmp(cc, x, y);
+                let a = self.operand(ty);
+                let b = self.operand(ty);
+                self.builder.ins().select(cond, a, b)
+            }
+            _ => self.operand(ty),
+        }
+    }
+}
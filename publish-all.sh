@@ -40,7 +40,7 @@ echo git push origin v$version
 for crate in \
     entity bforest codegen/meta codegen frontend native \
     preopt \
-    reader wasm module \
+    reader interpreter wasm module \
     faerie umbrella simplejit
 do
     echo cargo publish --manifest-path "cranelift-$crate/Cargo.toml"
@@ -0,0 +1,107 @@
+//! CLI tool to interpret Cranelift IR files without compiling them.
+
+use crate::run::iterate_files;
+use crate::utils::read_to_string;
+use cranelift_interpreter::{Interpreter, Outcome};
+use cranelift_reader::{parse_run_command, parse_test, ParseOptions};
+use std::path::PathBuf;
+
+pub fn run(files: Vec<String>, flag_print: bool) -> Result<(), String> {
+    let mut total = 0;
+    let mut errors = 0;
+    for file in iterate_files(files) {
+        total += 1;
+        match interpret_single_file(&file) {
+            Ok(_) => {
+                if flag_print {
+                    println!("{}", file.to_string_lossy());
+                }
+            }
+            Err(e) => {
+                if flag_print {
+                    println!("{}: {}", file.to_string_lossy(), e);
+                }
+                errors += 1;
+            }
+        }
+    }
+
+    if flag_print {
+        match total {
+            0 => println!("0 files"),
+            1 => println!("1 file"),
+            n => println!("{} files", n),
+        }
+    }
+
+    match errors {
+        0 => Ok(()),
+        1 => Err(String::from("1 failure")),
+        n => Err(format!("{} failures", n)),
+    }
+}
+
+/// Interpret all functions in a file that are succeeded by "run:" comments
+fn interpret_single_file(path: &PathBuf) -> Result<(), String> {
+    let file_contents = read_to_string(&path).map_err(|e| e.to_string())?;
+    interpret_file_contents(file_contents)
+}
+
+/// Main body of `interpret_single_file` separated for testing
+fn interpret_file_contents(file_contents: String) -> Result<(), String> {
+    let test_file =
+        parse_test(&file_contents, ParseOptions::default()).map_err(|e| e.to_string())?;
+    for (func, details) in &test_file.functions {
+        for comment in &details.comments {
+            if let Some(command) =
+                parse_run_command(comment.text, func).map_err(|e| e.to_string())?
+            {
+                let functions = test_file.functions.iter().map(|(func, _)| func);
+                let mut interpreter = Interpreter::new(functions.collect());
+                command.run(|args| {
+                    match interpreter.call(func, args).map_err(|e| e.to_string())? {
+                        Outcome::Return(values) => Ok(values),
+                        Outcome::Trap(code) => Err(format!("{} trapped with {}", func.name, code)),
+                    }
+                })?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn call() {
+        let code = String::from(
+            "
+            function %answer() -> i32 {
+            ebb0:
+                v0 = iconst.i32 42
+                return v0
+            }
+
+            function %test() -> b1 {
+                fn0 = %answer() -> i32
+            ebb0:
+                v0 = call fn0()
+                v1 = icmp_imm eq v0, 42
+                return v1
+            }
+            ; run
+
+            function %add(i64, i64) -> i64 {
+            ebb0(v0: i64, v1: i64):
+                v2 = iadd v0, v1
+                return v2
+            }
+            ; run: %add(40, 2) == 42
+            ; run: %add(-1, 1) != 1
+            ",
+        );
+        interpret_file_contents(code).unwrap()
+    }
+}
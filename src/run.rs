@@ -4,7 +4,7 @@ use crate::utils::read_to_string;
 use cranelift_codegen::isa::{CallConv, TargetIsa};
 use cranelift_filetests::FunctionRunner;
 use cranelift_native::builder as host_isa_builder;
-use cranelift_reader::{parse_test, Details, IsaSpec, ParseOptions};
+use cranelift_reader::{parse_run_command, parse_test, Details, IsaSpec, ParseOptions};
 use std::path::PathBuf;
 use target_lexicon::Triple;
 use walkdir::WalkDir;
@@ -45,7 +45,7 @@ pub fn run(files: Vec<String>, flag_print: bool) -> Result<(), String> {
 }
 
 /// Iterate over all of the files passed as arguments, recursively iterating through directories
-fn iterate_files(files: Vec<String>) -> impl Iterator<Item = PathBuf> {
+pub fn iterate_files(files: Vec<String>) -> impl Iterator<Item = PathBuf> {
     files
         .into_iter()
         .flat_map(WalkDir::new)
@@ -81,9 +81,17 @@ fn run_file_contents(file_contents: String) -> Result<(), String> {
     };
     let test_file = parse_test(&file_contents, options).map_err(|e| e.to_string())?;
     for (func, Details { comments, .. }) in test_file.functions {
-        if comments.iter().any(|c| c.text.contains("run")) {
+        let mut commands = Vec::new();
+        for comment in comments {
+            if let Some(command) =
+                parse_run_command(comment.text, &func).map_err(|e| e.to_string())?
+            {
+                commands.push(command);
+            }
+        }
+        if !commands.is_empty() {
             let isa = create_target_isa(&test_file.isa_spec)?;
-            FunctionRunner::new(func, isa).run()?
+            FunctionRunner::new(func, isa).run(&commands)?
         }
     }
     Ok(())
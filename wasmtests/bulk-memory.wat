@@ -0,0 +1,46 @@
+(module
+  (memory 1)
+  (table 2 anyfunc)
+  (elem (i32.const 0) $copy $init)
+  (data (i32.const 0) "active")
+  (data "passive")
+
+  (func $copy (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.copy
+  )
+
+  (func $fill (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.fill
+  )
+
+  (func $init (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    memory.init 1
+    data.drop 1
+  )
+
+  (func $table (param i32 i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    table.copy
+    local.get 0
+    local.get 1
+    local.get 2
+    table.init 0
+    elem.drop 0
+  )
+
+  (export "copy" (func $copy))
+  (export "fill" (func $fill))
+  (export "init" (func $init))
+  (export "table" (func $table))
+)
@@ -0,0 +1,96 @@
+(module
+  (func $pair (param i32 i64) (result i64 i32)
+    local.get 1
+    local.get 0
+  )
+
+  (func $call_pair (param i32) (result i32)
+    local.get 0
+    i64.const 7
+    call $pair
+    local.set 0
+    i32.wrap_i64
+    local.get 0
+    i32.add
+  )
+
+  (func $block_params (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    block (param i32 i32) (result i32)
+      i32.add
+    end
+  )
+
+  (func $loop_sum (param i32) (result i32)
+    i32.const 0
+    local.get 0
+    loop (param i32 i32) (result i32)
+      local.tee 0
+      i32.add
+      local.get 0
+      i32.const 1
+      i32.sub
+      local.tee 0
+      local.get 0
+      br_if 0
+      drop
+    end
+  )
+
+  (func $if_else_params (param i32 i32 i32) (result i32 i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    if (param i32 i32) (result i32 i32)
+      i32.add
+      i32.const 1
+    else
+      i32.sub
+      i32.const 2
+    end
+  )
+
+  (func $if_no_else (param i32 i32) (result i32)
+    local.get 1
+    local.get 0
+    if (param i32) (result i32)
+      i32.const 1
+      i32.add
+    end
+  )
+
+  (func $unreachable_then (param i32 i32) (result i32)
+    local.get 1
+    local.get 0
+    if (param i32) (result i32)
+      unreachable
+    else
+      i32.const 2
+      i32.mul
+    end
+  )
+
+  (func $branches (param i32) (result i32 i64)
+    block (result i32 i64)
+      i32.const 1
+      i64.const 2
+      local.get 0
+      br_if 0
+      drop
+      drop
+      i32.const 3
+      i64.const 4
+      local.get 0
+      br_table 0 0
+    end
+  )
+
+  (func $many_results (result i32 i32 i32 i32 f64)
+    i32.const 1
+    i32.const 2
+    i32.const 3
+    i32.const 4
+    f64.const 5
+  )
+)
@@ -0,0 +1,45 @@
+(module
+  (import "env" "gc" (func $gc))
+  (table $refs 10 anyref)
+  (global $last (mut anyref) (ref.null))
+
+  (func $keep_alive (param anyref) (result anyref)
+    call $gc
+    local.get 0
+  )
+
+  (func $store (param i32 anyref)
+    local.get 0
+    local.get 1
+    table.set $refs
+    local.get 1
+    global.set $last
+  )
+
+  (func $load (param i32) (result anyref)
+    local.get 0
+    table.get $refs
+  )
+
+  (func $size (result i32)
+    table.size $refs
+  )
+
+  (func $is_null (param anyref) (result i32)
+    local.get 0
+    ref.is_null
+  )
+
+  (func $choose (param anyref anyref i32) (result anyref)
+    local.get 0
+    local.get 1
+    local.get 2
+    select
+  )
+
+  (func $null_local (result anyref) (local anyref)
+    global.get $last
+    drop
+    local.get 0
+  )
+)
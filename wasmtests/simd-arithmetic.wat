@@ -0,0 +1,65 @@
+(module
+  (memory 1)
+
+  (func $test_float_arithmetic (result f32)
+    v128.const f32x4 1 2 3 4
+    v128.const f32x4 4 3 2 1
+    f32x4.add
+    v128.const f32x4 2 2 2 2
+    f32x4.mul
+    v128.const f32x4 -1 0 1 nan
+    f32x4.min
+    v128.const f32x4 -0 0 -0 0
+    f32x4.max
+    f32x4.sqrt
+    f32x4.extract_lane 2
+  )
+
+  (func $test_f64_arithmetic (result f64)
+    v128.const f64x2 1 2
+    v128.const f64x2 3 4
+    f64x2.sub
+    v128.const f64x2 0.5 0.25
+    f64x2.div
+    f64x2.extract_lane 1
+  )
+
+  (func $test_conversions (result f32)
+    v128.const f32x4 1.5 -2.5 3e10 nan
+    i32x4.trunc_sat_f32x4_s
+    f32x4.convert_i32x4_s
+    f32x4.extract_lane 1
+  )
+
+  (func $test_swizzle (result i32)
+    v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+    v128.const i8x16 15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 16
+    v8x16.swizzle
+    i8x16.extract_lane_s 0
+  )
+
+  (func $test_load_splat (param i32) (result i32)
+    local.get 0
+    i8x16.load_splat
+    i8x16.extract_lane_s 15
+    local.get 0
+    i16x8.load_splat offset=2
+    i16x8.extract_lane_s 7
+    i32.add
+    local.get 0
+    i32x4.load_splat offset=4
+    i32x4.extract_lane 3
+    i32.add
+    local.get 0
+    i64x2.load_splat offset=8
+    i64x2.extract_lane 1
+    i32.wrap_i64
+    i32.add
+  )
+
+  (export "test_float_arithmetic" (func $test_float_arithmetic))
+  (export "test_f64_arithmetic" (func $test_f64_arithmetic))
+  (export "test_conversions" (func $test_conversions))
+  (export "test_swizzle" (func $test_swizzle))
+  (export "test_load_splat" (func $test_load_splat))
+)
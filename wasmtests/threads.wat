@@ -0,0 +1,63 @@
+(module
+  (memory 1 1 shared)
+
+  (func $load (param i32) (result i64)
+    local.get 0
+    i32.atomic.load
+    i64.extend_i32_u
+    local.get 0
+    i64.atomic.load16_u offset=8
+    i64.add
+  )
+
+  (func $store (param i32 i64)
+    local.get 0
+    local.get 1
+    i64.atomic.store
+    local.get 0
+    local.get 1
+    i64.atomic.store8 offset=3
+  )
+
+  (func $rmw (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.atomic.rmw.add
+    local.get 0
+    local.get 1
+    i32.atomic.rmw8.sub_u
+    i32.add
+    local.get 0
+    local.get 1
+    i32.atomic.rmw16.or_u
+    i32.add
+    local.get 0
+    local.get 1
+    i32.atomic.rmw.xchg
+    i32.add
+  )
+
+  (func $cmpxchg (param i32 i64 i64) (result i64)
+    local.get 0
+    local.get 1
+    local.get 2
+    i64.atomic.rmw32.cmpxchg_u
+  )
+
+  (func $wait_notify (param i32 i32 i64) (result i32)
+    local.get 0
+    local.get 1
+    local.get 2
+    i32.atomic.wait
+    local.get 0
+    i32.const 1
+    atomic.notify
+    i32.add
+  )
+
+  (export "load" (func $load))
+  (export "store" (func $store))
+  (export "rmw" (func $rmw))
+  (export "cmpxchg" (func $cmpxchg))
+  (export "wait_notify" (func $wait_notify))
+)